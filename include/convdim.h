@@ -0,0 +1,87 @@
+/*
+ * The C interface of convdim, implemented in src/ffi.rs.
+ *
+ * Link against the shared library built by `cargo build --release`, e.g. with
+ * `-Ltarget/release -lconvdim`.
+ *
+ * The functions returning `char *` return a JSON document owned by the library, which must be
+ * released with convdim_free(). On success, it holds the result in its "ok" member, otherwise the
+ * message of the error in its "error" member. Networks are passed as their toml description, in
+ * the list or the graph format, or in JSON with the same structure for
+ * convdim_eval_network_json(). A bug of the library is reported like an error, by a message
+ * starting with "Internal error:".
+ */
+#ifndef CONVDIM_H
+#define CONVDIM_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The status of a successful call. */
+#define CONVDIM_OK 0
+/* The parameters of a layer do not fit its input, e.g. its filter is larger than the padded input. */
+#define CONVDIM_ERROR_LAYER 1
+/* The structure of the network is invalid, e.g. the branches of a parallel block disagree. */
+#define CONVDIM_ERROR_NETWORK 2
+/* The description of the network cannot be read or parsed. */
+#define CONVDIM_ERROR_PARSE 3
+/* A pointer argument is null. */
+#define CONVDIM_ERROR_NULL 4
+/* The library panicked, which is a bug. */
+#define CONVDIM_ERROR_INTERNAL 5
+
+/*
+ * The output dimension of a (transposed) convolution, which is written to `out`.
+ *
+ * Returns CONVDIM_OK or the status of the error, whose message is returned by
+ * convdim_last_error().
+ */
+int convdim_conv_out(uint32_t in_dim, uint32_t filter_size, uint32_t padding, uint32_t stride,
+                     bool transposed, uint32_t *out);
+
+/*
+ * The message of the last error of convdim_conv_out() on the calling thread, or NULL.
+ *
+ * The message is owned by the library and valid until the next error on the thread.
+ */
+const char *convdim_last_error(void);
+
+/* The output dimension of a convolution applied `repeat` times, as a JSON document. */
+char *convdim_conv_output_dim(uint32_t in_dim, uint32_t filter_size, uint32_t padding,
+                              uint32_t stride, uint32_t repeat);
+
+/* The output dimension of a transposed convolution applied `repeat` times, as a JSON document. */
+char *convdim_transposed_conv_output_dim(uint32_t in_dim, uint32_t filter_size, uint32_t padding,
+                                         uint32_t stride, uint32_t repeat);
+
+/*
+ * The outputs of all the layers or nodes of the network `description` in JSON, as a JSON
+ * document of objects with the "name", the "dim" and, for graphs, the "channels".
+ */
+char *convdim_eval_network_json(const char *description, uint32_t in_dim);
+
+/* The outputs of all the layers or nodes of the toml `description`, like `convdim --trace`. */
+char *convdim_trace(const char *description, uint32_t in_dim);
+
+/* The summary rows of the toml `description`, with the parameters and receptive fields. */
+char *convdim_summary(const char *description, uint32_t in_dim);
+
+/* A zeroed buffer for a description of `len` bytes followed by its nul byte, or NULL. */
+char *convdim_alloc(size_t len);
+
+/* Release a `buffer` of `len` bytes allocated by convdim_alloc(). */
+void convdim_dealloc(char *buffer, size_t len);
+
+/* Release a JSON document returned by the library. */
+void convdim_free(char *response);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CONVDIM_H */
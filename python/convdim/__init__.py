@@ -0,0 +1,31 @@
+"""Compute the dimensions of the outputs of convolutional layers.
+
+The functions are implemented by the native module ``convdim._convdim``, which is built from the
+Rust library with PyO3 by maturin, e.g. with ``pip install .`` or ``maturin develop`` in the
+repository.
+
+Networks are given as their toml description, in the list or the graph format, e.g. read from a
+file by :func:`load`.
+"""
+from ._convdim import (
+    ConvDimError,
+    conv_output_dim,
+    summary,
+    trace,
+    transposed_conv_output_dim,
+)
+
+__all__ = [
+    "ConvDimError",
+    "conv_output_dim",
+    "transposed_conv_output_dim",
+    "load",
+    "trace",
+    "summary",
+]
+
+
+def load(path):
+    """The toml description of the network in the file at ``path``."""
+    with open(path, encoding="utf-8") as file:
+        return file.read()
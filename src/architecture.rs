@@ -0,0 +1,309 @@
+//! Network architectures read from files, either as a list of layers or as a graph.
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::error::ConvDimError;
+use crate::json::parse_json;
+use crate::layers::{Entry, Layers};
+use crate::{convert, fetch, graph, import, schema};
+
+#[derive(Debug, Clone)]
+/// ## A network architecture read from a toml file.
+///
+/// The file either contains a flat list of successive `layers` or a graph of named `nodes`.
+pub enum Architecture {
+    Layers(Layers),
+    Graph(graph::Graph),
+}
+
+/// ## Append the nodes for `layers`, applied to the output of `input`, to `nodes`.
+///
+/// Layers without a name are named by their (one-based) position, like in the trace, and the
+/// concatenations of parallel blocks by their position among the parallel blocks. Returns the
+/// name of the node producing the output of the last layer.
+fn layer_nodes(
+    layers: &[Entry],
+    input: &str,
+    nodes: &mut Vec<graph::Node>,
+    counts: &mut (usize, usize),
+) -> String {
+    let mut previous = input.to_string();
+    for entry in layers {
+        let node = match entry {
+            Entry::Layer(layer) => {
+                counts.0 += 1;
+                // Pooling and upsampling are (transposed) convolutional nodes without channels, and
+                // the pass-through layers nodes of their own.
+                let (filter_size, stride, padding) = layer.geometry();
+                graph::Node {
+                    name: match layer.name() {
+                        Some(name) => name.to_string(),
+                        None => format!("layer{}", counts.0),
+                    },
+                    op: match layer.pass_through() {
+                        Some(kind) => kind.into(),
+                        None if layer.is_transposed() => graph::Op::Transposed,
+                        None => graph::Op::Conv,
+                    },
+                    inputs: vec![previous],
+                    filter_size: Some(filter_size),
+                    stride,
+                    padding,
+                    formula: layer.formula().cloned(),
+                    channels: layer.channels(),
+                    subnetwork: None,
+                    ceil_mode: layer.ceil_mode(),
+                }
+            }
+            Entry::Parallel { parallel } => {
+                counts.1 += 1;
+                let name = format!("parallel{}", counts.1);
+                let inputs = parallel
+                    .iter()
+                    .map(|branch| layer_nodes(&branch.layers, &previous, nodes, counts))
+                    .collect();
+                graph::Node {
+                    name,
+                    op: graph::Op::Concat,
+                    inputs,
+                    filter_size: None,
+                    stride: 1,
+                    padding: 0,
+                    formula: None,
+                    channels: None,
+                    subnetwork: None,
+                    ceil_mode: false,
+                }
+            }
+        };
+        previous = node.name.clone();
+        nodes.push(node);
+    }
+    previous
+}
+
+impl Architecture {
+    /// ## The architecture in the graph format.
+    ///
+    /// A list of layers becomes a chain of nodes, in which parallel blocks become branches
+    /// that are merged by concatenation.
+    pub fn into_graph(self) -> graph::Graph {
+        match self {
+            Architecture::Graph(graph) => graph,
+            Architecture::Layers(layers) => {
+                let mut nodes = vec![];
+                layer_nodes(&layers.layers, graph::INPUT, &mut nodes, &mut (0, 0));
+                graph::Graph {
+                    input_channels: layers.input_channels,
+                    outputs: layers.outputs,
+                    inputs: vec![],
+                    nodes,
+                }
+            }
+        }
+    }
+}
+
+/// ## Import the network from the model file at `path`.
+///
+/// The warnings about approximated layers are printed to the standard error.
+pub fn import_model(path: &Path, format: import::Format) -> Result<graph::Graph, ConvDimError> {
+    imported_graph(path, import::read(path, format))
+}
+
+/// The graph of the model `imported` from `path`, printing its warnings to the standard error.
+fn imported_graph(
+    path: &Path,
+    imported: Result<import::Imported, String>,
+) -> Result<graph::Graph, ConvDimError> {
+    let imported = imported.map_err(|message| read_error(path, message))?;
+    for warning in &imported.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    Ok(imported.graph)
+}
+
+/// The error reading the file at `path`.
+fn read_error(path: &Path, message: impl ToString) -> ConvDimError {
+    ConvDimError::Read {
+        path: path.to_path_buf(),
+        message: message.to_string(),
+    }
+}
+
+/// ## Read the network architecture from the toml file at `path`.
+///
+/// Files defining `nodes` are interpreted in the graph format, all others as a list of `layers`.
+/// Model files of the supported frameworks (e.g. `.onnx`) are imported as graphs, except for JSON
+/// files defining `nodes` or `layers`, which are read like toml files.
+pub fn read_architecture(path: &Path) -> Result<Architecture, ConvDimError> {
+    if let Some(url) = fetch::url(path) {
+        let download = fetch::download(url)?;
+        return downloaded_architecture(path, Path::new(download.name()), download.bytes());
+    }
+    if let Some(value) = read_json_description(path)? {
+        return architecture_from_value(path, value, None);
+    }
+    if let Some(format) = import::Format::from_path(path) {
+        return import_model(path, format).map(Architecture::Graph);
+    }
+    let toml_content = std::fs::read_to_string(path).map_err(|e| read_error(path, e))?;
+    let value: toml::Value = toml::from_str(&toml_content).map_err(|e| read_error(path, e))?;
+    architecture_from_value(path, value, Some(&toml_content))
+}
+
+/// The architecture of the file `name` downloaded from the URL `path` as `bytes`, read like
+/// [`read_architecture`] reads a file.
+fn downloaded_architecture(
+    path: &Path,
+    name: &Path,
+    bytes: &[u8],
+) -> Result<Architecture, ConvDimError> {
+    if is_json(name) {
+        if let Some(value) = json_description(&String::from_utf8_lossy(bytes)) {
+            return value
+                .map_err(|message| read_error(path, message))
+                .and_then(|value| architecture_from_value(path, value, None));
+        }
+    }
+    if let Some(format) = import::Format::from_path(name) {
+        return imported_graph(path, import::from_bytes(bytes, format)).map(Architecture::Graph);
+    }
+    let toml_content = std::str::from_utf8(bytes).map_err(|e| read_error(path, e))?;
+    let value: toml::Value = toml::from_str(toml_content).map_err(|e| read_error(path, e))?;
+    architecture_from_value(path, value, Some(toml_content))
+}
+
+/// Whether the file at `path` is a JSON file.
+fn is_json(path: &Path) -> bool {
+    path.extension().and_then(|extension| extension.to_str()) == Some("json")
+}
+
+/// ## Read the description in JSON at `path`, if it is a JSON file describing `nodes` or `layers`.
+fn read_json_description(path: &Path) -> Result<Option<toml::Value>, ConvDimError> {
+    if !is_json(path) {
+        return Ok(None);
+    }
+    match std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| json_description(&text))
+    {
+        Some(value) => value.map(Some).map_err(|message| read_error(path, message)),
+        None => Ok(None),
+    }
+}
+
+/// The description in the JSON `text`, if it describes `nodes` or `layers`.
+fn json_description(text: &str) -> Option<Result<toml::Value, String>> {
+    parse_json(text)
+        .ok()
+        .filter(convert::is_description)
+        .map(|json| convert::from_json(&json))
+}
+
+/// Interpret the description `value` as a graph if it defines `nodes`, or as a list of `layers`.
+///
+/// All the problems of the structure found by [`schema::check`] are reported at once, located in
+/// the `text` of the description if given.
+pub(crate) fn interpret(value: toml::Value, text: Option<&str>) -> Result<Architecture, String> {
+    let problems = schema::check(&value, text);
+    if !problems.is_empty() {
+        let problems: Vec<String> = problems.iter().map(ToString::to_string).collect();
+        return Err(problems.join("\n"));
+    }
+    let architecture = if value.get("nodes").is_some() {
+        value.try_into().map(Architecture::Graph)
+    } else {
+        value.try_into().map(Architecture::Layers)
+    };
+    architecture.map_err(|e| e.to_string())
+}
+
+/// ## Interpret the description `value` read from `path` as a graph if it defines `nodes`, or as
+/// a list of `layers`.
+fn architecture_from_value(
+    path: &Path,
+    value: toml::Value,
+    text: Option<&str>,
+) -> Result<Architecture, ConvDimError> {
+    interpret(value, text).map_err(|e| read_error(path, e))
+}
+
+/// ## Parse the network architecture from the toml `description`.
+///
+/// Descriptions defining `nodes` are interpreted in the graph format, all others as a list of
+/// `layers`, like the files read by [`read_architecture`].
+pub fn parse_architecture(description: &str) -> Result<Architecture, ConvDimError> {
+    let value: toml::Value = toml::from_str(description).map_err(|e| ConvDimError::Parse {
+        message: e.to_string(),
+    })?;
+    interpret(value, Some(description)).map_err(|message| ConvDimError::Parse { message })
+}
+
+/// ## Parse the network architecture from the `description` in JSON.
+///
+/// The JSON description has the same structure as the toml description, see [`parse_architecture`].
+pub fn parse_json_architecture(description: &str) -> Result<Architecture, ConvDimError> {
+    let json = parse_json(description).map_err(|message| ConvDimError::Parse { message })?;
+    architecture_from_json(&json)
+}
+
+/// ## The architecture described by the parsed JSON `description`, see [`parse_json_architecture`].
+pub fn architecture_from_json(description: &Value) -> Result<Architecture, ConvDimError> {
+    let parse_error = |message: String| ConvDimError::Parse { message };
+    let value = convert::from_json(description).map_err(parse_error)?;
+    interpret(value, None).map_err(parse_error)
+}
+
+/// ## Read the successive layers from the toml file at `path`.
+///
+/// Fails if the file describes a graph instead of a list of layers.
+pub fn read_layers(path: &Path) -> Result<Layers, ConvDimError> {
+    match read_architecture(path)? {
+        Architecture::Layers(layers) => Ok(layers),
+        Architecture::Graph(_) => Err(read_error(
+            path,
+            "the file describes a graph, but a list of layers is required.",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers_into_graph() {
+        let layers: Layers = toml::from_str(
+            r#"
+            input_channels = 3
+
+            [[layers]]
+            filter_size = 3
+            stride = 2
+            padding = 1
+            transposed = false
+            channels = 16
+
+            [[layers]]
+            parallel = [
+                { layers = [{ filter_size = 1, stride = 1, padding = 0, transposed = false, channels = 8 }] },
+                { layers = [{ name = "pool", filter_size = 3, stride = 1, padding = 1, transposed = false }] },
+            ]
+            "#,
+        )
+        .unwrap();
+        let graph = Architecture::Layers(layers).into_graph();
+        let names: Vec<&str> = graph.nodes.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, vec!["layer1", "layer2", "pool", "parallel1"]);
+        assert_eq!(graph.nodes[3].inputs, vec!["layer2", "pool"]);
+        assert_eq!(
+            graph.output_shape(64).unwrap(),
+            graph::Shape {
+                dim: 32,
+                channels: Some(24)
+            }
+        );
+    }
+}
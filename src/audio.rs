@@ -0,0 +1,169 @@
+//! The frames that a convolutional frontend produces from an audio signal, e.g. the feature encoder
+//! of wav2vec 2.0, and their hop and receptive field in milliseconds.
+//!
+//! The signal is a one-dimensional input whose dimension is its number of samples. Like the
+//! [`summary`](crate::summary), the hop is the distance between the centers of the receptive
+//! fields of neighboring frames, in samples of the signal.
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::graph::Graph;
+use crate::summary;
+use crate::trace::write_columns;
+
+/// A positive number followed by one of the `units`, e.g. `2.5 s`, in multiples of the base unit.
+pub(crate) fn quantity(text: &str, units: &[(&str, f64)]) -> Option<f64> {
+    let text = text.trim();
+    let (unit, factor) = units.iter().find(|(unit, _)| text.ends_with(unit))?;
+    let number: f64 = text[..text.len() - unit.len()].trim().parse().ok()?;
+    Some(number * factor).filter(|value| value.is_finite() && *value > 0.0)
+}
+
+/// ## The duration in seconds given by `text` in seconds (`s`) or milliseconds (`ms`), e.g. `2.5 s`.
+pub fn duration(text: &str) -> Result<f64, ConvDimError> {
+    quantity(text, &[("ms", 1e-3), ("s", 1.0)]).ok_or_else(|| ConvDimError::Audio {
+        message: format!("'{}' is not a duration like '2.5 s'.", text),
+    })
+}
+
+/// ## The sample rate in Hz given by `text` in `Hz` or `kHz`, e.g. `16 kHz`.
+pub fn rate(text: &str) -> Result<f64, ConvDimError> {
+    quantity(text, &[("kHz", 1e3), ("Hz", 1.0)]).ok_or_else(|| ConvDimError::Audio {
+        message: format!("'{}' is not a sample rate like '16 kHz'.", text),
+    })
+}
+
+/// ## The number of samples of a signal of `duration` seconds at `rate` Hz, rounded up.
+///
+/// The product is not rounded up if it only exceeds an integer by a rounding error. Returns `None`
+/// if it exceeds the range of `u64`.
+pub fn samples(duration: f64, rate: f64) -> Option<u64> {
+    let samples = (duration * rate * (1.0 - 1e-12)).ceil();
+    Some(samples as u64).filter(|_| samples < u64::MAX as f64)
+}
+
+/// `samples` at `rate` Hz in milliseconds, rounded to microseconds.
+fn milliseconds(samples: f64, rate: f64) -> String {
+    format!("{} ms", (samples / rate * 1e6).round() / 1e3)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+/// ## The frames of a signal of `samples` samples at `rate` Hz, and their hop and receptive field.
+pub struct Frames {
+    pub samples: u64,
+    pub rate: f64,
+    pub frames: u32,
+    /// The hop between neighboring frames in samples.
+    pub hop: f64,
+    /// The receptive field of a frame in samples.
+    pub receptive_field: u64,
+}
+
+impl Frames {
+    /// The number of frames per second.
+    pub fn frame_rate(&self) -> f64 {
+        self.rate / self.hop
+    }
+}
+
+impl fmt::Display for Frames {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = vec![
+            vec!["samples:".to_string(), self.samples.to_string()],
+            vec!["frames:".to_string(), self.frames.to_string()],
+            vec![
+                "hop:".to_string(),
+                self.hop.to_string(),
+                milliseconds(self.hop, self.rate),
+            ],
+            vec![
+                "receptive field:".to_string(),
+                self.receptive_field.to_string(),
+                milliseconds(self.receptive_field as f64, self.rate),
+            ],
+            vec![
+                "frame rate:".to_string(),
+                format!("{}", (self.frame_rate() * 1e3).round() / 1e3),
+                "Hz".to_string(),
+            ],
+        ];
+        write_columns(f, &rows, &[1])
+    }
+}
+
+/// ## The frames the convolutional frontend `graph` produces from `duration` seconds at `rate` Hz.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{audio, Architecture, Network};
+/// // The feature encoder of wav2vec 2.0.
+/// let network = Network::builder()
+///     .conv(10, 5, 0)
+///     .conv(3, 2, 0).conv(3, 2, 0).conv(3, 2, 0).conv(3, 2, 0)
+///     .conv(2, 2, 0).conv(2, 2, 0)
+///     .build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let frames = audio::frames(&graph, 2.5, 16_000.0).unwrap();
+/// assert_eq!((frames.samples, frames.frames), (40_000, 124));
+/// assert_eq!((frames.hop, frames.receptive_field), (320.0, 400));
+/// ```
+pub fn frames(graph: &Graph, duration: f64, rate: f64) -> Result<Frames, ConvDimError> {
+    let samples = samples(duration, rate)
+        .and_then(|samples| u32::try_from(samples).ok())
+        .ok_or_else(|| ConvDimError::Audio {
+            message: format!(
+                "{} s at {} Hz exceed the {} samples of an input.",
+                duration,
+                rate,
+                u32::MAX
+            ),
+        })?;
+    let rows = summary::rows(graph, samples)?;
+    let last = rows.last().expect("The summary has a row for every input.");
+    Ok(Frames {
+        samples: u64::from(samples),
+        rate,
+        frames: last.shape.dim,
+        hop: last.jump,
+        receptive_field: last.receptive_field,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_frames() {
+        assert_eq!(duration("250 ms"), Ok(0.25));
+        assert_eq!(rate("16kHz"), Ok(16_000.0));
+        assert!(rate("16 kbps").is_err());
+        assert_eq!(samples(0.1, 44_100.0), Some(4410));
+
+        // A frontend with a hop of 10 ms and a window of 25 ms at 16 kHz, like log-mel features.
+        let graph = parse_architecture(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 400
+            stride = 160
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let frames = frames(&graph, 1.0, 16_000.0).unwrap();
+        assert_eq!(
+            frames.to_string(),
+            "samples:          16000\n\
+             frames:              98\n\
+             hop:                160  10 ms\n\
+             receptive field:    400  25 ms\n\
+             frame rate:         100  Hz\n"
+        );
+    }
+}
@@ -0,0 +1,324 @@
+//! Invert (transposed) convolutional layers.
+//!
+//! Given the dimension of the output of a layer, the formulas for the output dimension of
+//! (transposed) convolutional layers can be inverted to obtain the input dimensions that
+//! would lead to it. Because of the integer division in the convolution formula, several
+//! inputs can lead to the same output, so the result is in general a range of dimensions.
+use serde::Serialize;
+
+use crate::formula::Formula;
+use crate::{Entry, Layer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+/// ## An inclusive range of dimensions.
+///
+/// Every dimension `d` with `min <= d <= max` belongs to the range.
+pub struct DimRange {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl std::fmt::Display for DimRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.min == self.max {
+            write!(f, "{}", self.min)
+        } else {
+            write!(f, "{}-{}", self.min, self.max)
+        }
+    }
+}
+
+/// Integer division rounding towards negative infinity.
+fn floor_div(a: i64, b: i64) -> i64 {
+    let q = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+/// Integer division rounding towards positive infinity.
+fn ceil_div(a: i64, b: i64) -> i64 {
+    -floor_div(-a, b)
+}
+
+/// Build a `DimRange` from bounds computed in `i64`, clamping it to the representable dimensions.
+///
+/// Inputs are required to be strictly positive. `None` is returned if the range is empty.
+fn to_range(min: i64, max: i64) -> Option<DimRange> {
+    let min = min.max(1);
+    let max = max.min(u32::MAX as i64);
+    if min > max {
+        None
+    } else {
+        Some(DimRange {
+            min: min as u32,
+            max: max as u32,
+        })
+    }
+}
+
+/// ## Compute the inputs of a convolutional layer that lead to outputs in `out`.
+///
+/// Inverting o = (n - f + 2*p) / s + 1 for the integer division yields
+///
+/// (o - 1) * s + f - 2*p <= n <= (o - 1) * s + f - 2*p + s - 1
+///
+/// where additionally the padded input needs to be at least as large as the filter.
+/// Returns `None` if no input produces an output in the given range.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::backward::{conv_input_range, DimRange};
+/// assert_eq!(conv_input_range(DimRange { min: 32, max: 32 }, 2, 0, 2), Some(DimRange { min: 64, max: 65 }));
+/// ```
+pub fn conv_input_range(
+    out: DimRange,
+    filter_size: u16,
+    padding: u16,
+    stride: u16,
+) -> Option<DimRange> {
+    let (f, p, s) = (filter_size as i64, padding as i64, stride as i64);
+    let out_min = (out.min as i64).max(1);
+    let out_max = out.max as i64;
+    if out_min > out_max {
+        return None;
+    }
+    let min = ((out_min - 1) * s + f - 2 * p).max(f - 2 * p);
+    let max = (out_max - 1) * s + f - 2 * p + s - 1;
+    to_range(min, max)
+}
+
+/// ## Compute the inputs of a transposed convolutional layer that lead to outputs in `out`.
+///
+/// Inverting o = (n - 1) * s + f - 2*p yields n = (o - f + 2*p) / s + 1, which only has
+/// a solution if the division is exact. Returns `None` if no input produces an output in
+/// the given range.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::backward::{transposed_conv_input_range, DimRange};
+/// assert_eq!(transposed_conv_input_range(DimRange { min: 64, max: 64 }, 2, 0, 2), Some(DimRange { min: 32, max: 32 }));
+/// ```
+pub fn transposed_conv_input_range(
+    out: DimRange,
+    filter_size: u16,
+    padding: u16,
+    stride: u16,
+) -> Option<DimRange> {
+    let (f, p, s) = (filter_size as i64, padding as i64, stride as i64);
+    let min = ceil_div(out.min as i64 - f + 2 * p, s) + 1;
+    let max = floor_div(out.max as i64 - f + 2 * p, s) + 1;
+    to_range(min, max)
+}
+
+/// The inputs of a layer with a `formula` and the given `geometry` that lead to outputs in `out`,
+/// assuming that the output does not decrease with the input.
+fn formula_input_range(
+    formula: &Formula,
+    geometry: (u16, u16, u16),
+    out: DimRange,
+) -> Option<DimRange> {
+    // The smallest input whose output is at least `dim`.
+    let first_reaching = |dim: u32| {
+        if !formula.reaches(u32::MAX, geometry, dim) {
+            return None;
+        }
+        let (mut low, mut high) = (1, u32::MAX);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if formula.reaches(middle, geometry, dim) {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+        Some(low)
+    };
+    let min = first_reaching(out.min)?;
+    let max = match out.max.checked_add(1).map(first_reaching) {
+        Some(Some(first_above)) => first_above as i64 - 1,
+        _ => u32::MAX.into(),
+    };
+    to_range(min.into(), max)
+}
+
+/// ## Compute the inputs of a single layer that lead to outputs in `out`.
+///
+/// Layers with a stride or filter size of zero fail for every input, so they return `None`. The
+/// inputs of layers with a formula are searched, assuming that the output grows with the input.
+pub fn layer_input_range(layer: &Layer, out: DimRange) -> Option<DimRange> {
+    let (filter_size, stride, padding) = layer.geometry();
+    if let Some(formula) = layer.formula() {
+        formula_input_range(formula, (filter_size, stride, padding), out)
+    } else if stride == 0 || filter_size == 0 {
+        None
+    } else if layer.is_transposed() {
+        transposed_conv_input_range(out, filter_size, padding, stride)
+    } else {
+        conv_input_range(out, filter_size, padding, stride)
+    }
+}
+
+/// ## Compute the inputs of successive `layers` that lead to outputs in `out`.
+///
+/// The `layers` are traversed in reverse order and each layer is inverted in turn.
+/// The branches of a parallel block need to produce outputs of the same dimension,
+/// so the inputs of the block are those that lead to outputs in `out` for all branches.
+pub fn entries_input_range(layers: &[Entry], out: DimRange) -> Option<DimRange> {
+    layers
+        .iter()
+        .rev()
+        .try_fold(out, |range, entry| match entry {
+            Entry::Layer(layer) => layer_input_range(layer, range),
+            Entry::Parallel { parallel } => {
+                let mut intersection: Option<DimRange> = None;
+                for branch in parallel {
+                    let r = entries_input_range(&branch.layers, range)?;
+                    intersection = Some(match intersection {
+                        None => r,
+                        Some(i) => DimRange {
+                            min: i.min.max(r.min),
+                            max: i.max.min(r.max),
+                        },
+                    });
+                }
+                intersection.filter(|r| r.min <= r.max)
+            }
+        })
+}
+
+/// ## Compute the input dimensions of a network that lead to the output dimension `out_dim`.
+///
+/// Returns `None` if there is no input dimension that results in `out_dim`.
+pub fn input_range_for_output(layers: &[Entry], out_dim: u32) -> Option<DimRange> {
+    entries_input_range(
+        layers,
+        DimRange {
+            min: out_dim,
+            max: out_dim,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{conv_output_dim, dim_after_layers, transposed_conv_output_dim, ConvParams};
+
+    fn range(min: u32, max: u32) -> DimRange {
+        DimRange { min, max }
+    }
+
+    #[test]
+    fn test_formula_input_range() {
+        let layers: crate::Layers = toml::from_str(
+            r#"
+            [[layers]]
+            type = "formula"
+            formula = "ceil(n / 2)"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            input_range_for_output(&layers.layers, 10),
+            Some(range(19, 20))
+        );
+        assert_eq!(input_range_for_output(&layers.layers, 0), None);
+    }
+
+    #[test]
+    fn test_conv_input_range() {
+        assert_eq!(
+            conv_input_range(range(24, 24), 5, 0, 1),
+            Some(range(28, 28))
+        );
+        assert_eq!(
+            conv_input_range(range(32, 32), 2, 0, 2),
+            Some(range(64, 65))
+        );
+        assert_eq!(
+            conv_input_range(range(32, 32), 3, 1, 2),
+            Some(range(63, 64))
+        );
+        // The output of a convolution is at least one.
+        assert_eq!(conv_input_range(range(0, 0), 3, 0, 1), None);
+
+        for n in 63u32..=64 {
+            assert_eq!(conv_output_dim(n, 3, 1, 2, 1).unwrap(), 32);
+        }
+    }
+
+    #[test]
+    fn test_transposed_conv_input_range() {
+        assert_eq!(
+            transposed_conv_input_range(range(64, 64), 2, 0, 2),
+            Some(range(32, 32))
+        );
+        // Odd outputs cannot be produced by a stride 2 layer with an even filter.
+        assert_eq!(transposed_conv_input_range(range(63, 63), 2, 0, 2), None);
+        assert_eq!(
+            transposed_conv_input_range(range(63, 63), 3, 1, 2),
+            Some(range(32, 32))
+        );
+        assert_eq!(transposed_conv_output_dim(32u32, 3, 1, 2, 1).unwrap(), 63);
+    }
+
+    #[test]
+    fn test_input_range_for_output() {
+        let layers = vec![
+            Entry::Layer(Layer::Conv(ConvParams {
+                name: None,
+                filter_size: 3,
+                stride: 1,
+                padding: 1,
+                channels: None,
+            })),
+            Entry::Layer(Layer::Conv(ConvParams {
+                name: None,
+                filter_size: 2,
+                stride: 2,
+                padding: 0,
+                channels: None,
+            })),
+            Entry::Layer(Layer::Conv(ConvParams {
+                name: None,
+                filter_size: 2,
+                stride: 2,
+                padding: 0,
+                channels: None,
+            })),
+        ];
+
+        let inputs = input_range_for_output(&layers, 7).unwrap();
+        assert_eq!(inputs, range(28, 31));
+        for n in inputs.min..=inputs.max {
+            assert_eq!(dim_after_layers(&layers, n).unwrap(), 7);
+        }
+        assert_ne!(dim_after_layers(&layers, 27).unwrap(), 7);
+        assert_ne!(dim_after_layers(&layers, 32).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_input_range_for_parallel_block() {
+        let layers: crate::Layers = toml::from_str(
+            r#"
+            [[layers]]
+            parallel = [
+                { layers = [{ filter_size = 2, stride = 2, padding = 0, transposed = false }] },
+                { layers = [{ filter_size = 3, stride = 2, padding = 1, transposed = false }] },
+            ]
+            "#,
+        )
+        .unwrap();
+
+        // The first branch maps 14-15 to 7, the second one 13-14.
+        assert_eq!(
+            input_range_for_output(&layers.layers, 7),
+            Some(range(14, 14))
+        );
+    }
+}
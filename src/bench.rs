@@ -0,0 +1,285 @@
+//! Tables comparing several architectures, e.g. the variants of a network in a model selection.
+//!
+//! Every description is evaluated for the same input dimension and summarized by a row with its
+//! depth, the dimensions of its outputs, a sparkline of its dimensions, its receptive field, its numbers of parameters and
+//! operations and the memory of its activations. The rows can be sorted by any of these columns.
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::architecture::read_architecture;
+use crate::error::ConvDimError;
+use crate::graph::{Graph, Op};
+use crate::report::abbreviate;
+use crate::sparkline::Sparkline;
+use crate::summary;
+use crate::trace::write_columns;
+
+/// The bytes of a value of the activations, which are assumed to be 32-bit floats.
+const BYTES_PER_VALUE: u64 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## A column of the table by which its rows can be sorted.
+pub enum Column {
+    Name,
+    Depth,
+    Output,
+    ReceptiveField,
+    Params,
+    Flops,
+    Memory,
+}
+
+impl std::str::FromStr for Column {
+    type Err = String;
+
+    fn from_str(column: &str) -> Result<Column, String> {
+        match column {
+            "name" => Ok(Column::Name),
+            "depth" => Ok(Column::Depth),
+            "output" => Ok(Column::Output),
+            "receptive-field" | "rf" => Ok(Column::ReceptiveField),
+            "params" => Ok(Column::Params),
+            "flops" => Ok(Column::Flops),
+            "memory" => Ok(Column::Memory),
+            _ => Err(format!(
+                "Unknown column '{}', expected 'name', 'depth', 'output', 'receptive-field', \
+                 'params', 'flops' or 'memory'.",
+                column
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The measures of an architecture for an input dimension.
+pub struct Measures {
+    /// The number of (transposed) convolutions.
+    pub depth: usize,
+    /// The dimensions of the outputs.
+    pub outputs: Vec<u32>,
+    /// The dimensions of the first input and of every node, in the order of evaluation.
+    pub trajectory: Vec<u32>,
+    /// The largest receptive field of the outputs.
+    pub receptive_field: u64,
+    /// The number of weights and biases, if the channels of all the layers are known.
+    pub params: Option<u64>,
+    /// The number of floating point operations, if the channels of all the layers are known.
+    pub flops: Option<u64>,
+    /// The bytes of the activations of all the inputs and nodes, if their channels are known.
+    pub memory: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## A row of the table: the measures of the description at `path`, or the error.
+pub struct Entry {
+    pub path: PathBuf,
+    pub measures: Result<Measures, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The rows of the table, in the order in which they are displayed.
+pub struct Table {
+    pub entries: Vec<Entry>,
+}
+
+impl Table {
+    /// The number of descriptions that could not be evaluated.
+    pub fn failed(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.measures.is_err())
+            .count()
+    }
+
+    /// ## Sort the rows by `column`, in ascending order unless `descending`.
+    ///
+    /// Unknown values and descriptions that could not be evaluated are listed last. The sort is
+    /// stable, so rows with equal values keep their order.
+    pub fn sort(&mut self, column: Column, descending: bool) {
+        let key = |entry: &Entry| -> Option<u64> {
+            let measures = entry.measures.as_ref().ok()?;
+            match column {
+                Column::Name => None,
+                Column::Depth => Some(measures.depth as u64),
+                Column::Output => measures.outputs.iter().min().map(|&dim| u64::from(dim)),
+                Column::ReceptiveField => Some(measures.receptive_field),
+                Column::Params => measures.params,
+                Column::Flops => measures.flops,
+                Column::Memory => measures.memory,
+            }
+        };
+        self.entries.sort_by(|a, b| {
+            if column == Column::Name {
+                let order = a.path.cmp(&b.path);
+                return if descending { order.reverse() } else { order };
+            }
+            match (key(a), key(b)) {
+                (Some(a), Some(b)) if descending => b.cmp(&a),
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        });
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let known = |n: Option<u64>, unit: &str| {
+            n.map_or_else(|| "-".to_string(), |n| abbreviate(n as f64, unit))
+        };
+        let mut rows: Vec<Vec<String>> = vec![[
+            "architecture",
+            "depth",
+            "output",
+            "shape",
+            "receptive field",
+            "params",
+            "flops",
+            "memory",
+        ]
+        .iter()
+        .map(|header| header.to_string())
+        .collect()];
+        let mut errors = vec![];
+        for entry in &self.entries {
+            let measures = match &entry.measures {
+                Ok(measures) => measures,
+                Err(message) => {
+                    errors.push(format!("{}: {}", entry.path.display(), message));
+                    continue;
+                }
+            };
+            let outputs: Vec<String> = measures.outputs.iter().map(u32::to_string).collect();
+            rows.push(vec![
+                entry.path.display().to_string(),
+                measures.depth.to_string(),
+                outputs.join(", "),
+                Sparkline::from(measures.trajectory.clone()).to_string(),
+                measures.receptive_field.to_string(),
+                known(measures.params, ""),
+                known(measures.flops, ""),
+                known(measures.memory, "B"),
+            ]);
+        }
+        write_columns(f, &rows, &[1, 2, 4, 5, 6, 7])?;
+        if !errors.is_empty() {
+            writeln!(f)?;
+        }
+        for error in errors {
+            writeln!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// ## The measures of `graph` for an input of dimension `in_dim`.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{bench, Architecture, Network};
+/// let network = Network::builder().input_channels(3).conv(3, 2, 1).channels(8).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let measures = bench::measure(&graph, 32).unwrap();
+/// assert_eq!((measures.depth, measures.outputs.clone()), (1, vec![16]));
+/// assert_eq!(measures.params, Some(3 * 3 * 3 * 8 + 8));
+/// assert_eq!(measures.memory, Some(4 * (32 * 32 * 3 + 16 * 16 * 8)));
+/// ```
+pub fn measure(graph: &Graph, in_dim: u32) -> Result<Measures, ConvDimError> {
+    let rows = summary::rows(graph, in_dim)?;
+    let outputs = graph.output_shapes(in_dim)?;
+    let receptive_field = rows
+        .iter()
+        .filter(|row| outputs.iter().any(|(name, _)| name == row.name))
+        .map(|row| row.receptive_field)
+        .max()
+        .unwrap_or(1);
+    let memory: Option<u64> = rows.iter().map(|row| row.activations()).sum();
+    Ok(Measures {
+        depth: graph
+            .nodes
+            .iter()
+            .filter(|node| matches!(node.op, Op::Conv | Op::Transposed))
+            .count(),
+        outputs: outputs.iter().map(|(_, shape)| shape.dim).collect(),
+        trajectory: Sparkline::new(graph, in_dim)?.dims,
+        receptive_field,
+        params: rows.iter().map(|row| row.params).sum(),
+        flops: rows.iter().map(|row| row.flops).sum(),
+        memory: memory.map(|values| values.saturating_mul(BYTES_PER_VALUE)),
+    })
+}
+
+/// ## The row of the description at `path` for an input of dimension `in_dim`.
+pub fn entry(path: &Path, in_dim: u32) -> Entry {
+    let measures = read_architecture(path)
+        .and_then(|architecture| measure(&architecture.into_graph(), in_dim))
+        .map_err(|e| e.to_string());
+    Entry {
+        path: path.to_path_buf(),
+        measures,
+    }
+}
+
+/// ## The table of the descriptions at `paths` for an input of dimension `in_dim`, in their order.
+pub fn table(paths: &[PathBuf], in_dim: u32) -> Table {
+    Table {
+        entries: paths.iter().map(|path| entry(path, in_dim)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table() {
+        let directory = std::env::temp_dir().join(format!("convdim-bench-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let write = |name: &str, text: &str| {
+            std::fs::write(directory.join(name), text).unwrap();
+            directory.join(name)
+        };
+        let conv = "[[layers]]\ntype = \"conv\"\nfilter_size = 3\nstride = 2\nchannels = 8\n";
+        let paths = vec![
+            write(
+                "deep.toml",
+                &format!("input_channels = 3\n\n{}\n{}", conv, conv),
+            ),
+            write("broken.toml", "[[layers]\n"),
+            write("shallow.toml", &format!("input_channels = 3\n\n{}", conv)),
+            write("unknown.toml", conv),
+        ];
+        let mut table = table(&paths, 33);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(table.failed(), 1);
+        table.sort(Column::Params, true);
+        let names: Vec<_> = table
+            .entries
+            .iter()
+            .map(|entry| entry.path.file_stem().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["deep", "shallow", "broken", "unknown"]);
+        let deep = table.entries[0].measures.as_ref().unwrap();
+        assert_eq!(
+            (deep.depth, deep.outputs.clone(), deep.receptive_field),
+            (2, vec![7], 7)
+        );
+        assert_eq!(deep.trajectory, [33, 16, 7]);
+        assert_eq!(deep.params, Some(224 + 584));
+
+        table.sort(Column::Output, false);
+        let text = table.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("architecture"));
+        assert!(lines[1].contains("deep.toml") && lines[1].ends_with("  22.8 kB"));
+        assert!(lines[3].contains("unknown.toml") && lines[3].ends_with("  -"));
+        assert!(lines[5].contains("broken.toml: Unable to read"));
+    }
+}
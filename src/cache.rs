@@ -0,0 +1,289 @@
+//! A memo cache of the outputs of layers, for searches evaluating the same layers on the same
+//! inputs over and over, and a cache of the results of analyses on disk, for repeated runs.
+//!
+//! The outputs are keyed on the input dimension and the geometry of the layer, so layers of
+//! different types or names with the same arithmetic share their entries, e.g. a max pooling
+//! and a convolution with the same filter size, stride and padding.
+//!
+//! The results on disk are keyed on the analysis and its parameters, the
+//! [fingerprint](crate::fingerprint) of the network, the input dimension and the version of
+//! `convdim`, so a result is only reused for the same network, whatever the format and names of
+//! its description, and never across versions that may analyze differently.
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::error::ConvDimError;
+use crate::fingerprint::{self, sha256};
+use crate::graph::Graph;
+use crate::layers::{layer_output_dim, unnamed_output_dim, Layer};
+
+/// The input dimension, the filter size, the stride, the padding and whether the layer is transposed.
+pub(crate) type Key = (u32, u16, u16, u16, bool);
+
+/// The number of outputs held before the cache is emptied, so that its memory stays bounded.
+pub const MAX_ENTRIES: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// ## The number of lookups answered from the cache and of outputs computed.
+pub struct Statistics {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl std::ops::Add for Statistics {
+    type Output = Statistics;
+
+    fn add(self, other: Statistics) -> Statistics {
+        Statistics {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+        }
+    }
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lookups = self.hits + self.misses;
+        let rate = if lookups == 0 {
+            0.0
+        } else {
+            100.0 * self.hits as f64 / lookups as f64
+        };
+        write!(
+            f,
+            "{} lookups, {} hits ({:.1}%), {} misses",
+            lookups, self.hits, rate, self.misses
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// ## The outputs of the layers evaluated so far, and the statistics of the lookups.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::cache::Cache;
+/// # use convdim::{Layer, PoolParams};
+/// let pool = Layer::MaxPool(PoolParams { name: None, filter_size: 2, stride: 2, padding: 0 });
+/// let mut cache = Cache::default();
+/// assert_eq!(cache.layer_output_dim(64, &pool), Ok(32));
+/// assert_eq!(cache.layer_output_dim(64, &pool), Ok(32));
+/// assert_eq!(cache.statistics().hits, 1);
+/// ```
+pub struct Cache {
+    outputs: HashMap<Key, Result<u32, ConvDimError>>,
+    statistics: Statistics,
+}
+
+impl Cache {
+    /// ## The output dimension of `layer` for `in_dim`, like [`layer_output_dim`](crate::layer_output_dim).
+    pub fn layer_output_dim(&mut self, in_dim: u32, layer: &Layer) -> Result<u32, ConvDimError> {
+        // Layers with a formula share their geometry with other layers, but not their outputs.
+        if layer.formula().is_some() {
+            self.statistics.misses += 1;
+            return layer_output_dim(in_dim, layer);
+        }
+        let (filter_size, stride, padding) = layer.geometry();
+        let key = (in_dim, filter_size, stride, padding, layer.is_transposed());
+        // The errors are stored without the name of the layer, which may differ between the
+        // layers sharing the entry.
+        let dim = self.output_dim(key, || unnamed_output_dim(in_dim, layer));
+        match layer.name() {
+            Some(name) => dim.map_err(|e| e.in_layer(name)),
+            None => dim,
+        }
+    }
+
+    /// ## The output stored for `key`, or the one `compute` returns, which is then stored.
+    ///
+    /// The errors of `compute` are stored too, so they must not name the layer.
+    pub(crate) fn output_dim(
+        &mut self,
+        key: Key,
+        compute: impl FnOnce() -> Result<u32, ConvDimError>,
+    ) -> Result<u32, ConvDimError> {
+        if let Some(dim) = self.outputs.get(&key) {
+            self.statistics.hits += 1;
+            return dim.clone();
+        }
+        self.statistics.misses += 1;
+        if self.outputs.len() >= MAX_ENTRIES {
+            self.outputs.clear();
+        }
+        let dim = compute();
+        self.outputs.insert(key, dim.clone());
+        dim
+    }
+
+    /// The statistics of the lookups so far.
+    pub fn statistics(&self) -> Statistics {
+        self.statistics
+    }
+
+    /// The number of outputs in the cache.
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Whether the cache holds no outputs.
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+}
+
+/// The environment variable overriding the directory of the cache on disk.
+pub const CACHE_DIR: &str = "CONVDIM_CACHE_DIR";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## The results of analyses stored in a directory, which persist across runs.
+///
+/// Every result is a file named after the hash of its key, which starts with the key itself so
+/// that a result is never returned for another key. The results are written to a temporary file
+/// first and then renamed, so concurrent runs never read a partial result.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::cache::DiskCache;
+/// # use convdim::{Architecture, Network};
+/// let network = Network::builder().conv(3, 2, 1).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let cache = DiskCache::new(std::env::temp_dir().join("convdim-doctest-cache"));
+/// let key = DiskCache::key("outputs", &graph, 64, "");
+/// cache.put(&key, "layer1 32\n").unwrap();
+/// assert_eq!(cache.get(&key).as_deref(), Some("layer1 32\n"));
+/// assert_eq!(cache.get(&DiskCache::key("outputs", &graph, 65, "")), None);
+/// # std::fs::remove_dir_all(std::env::temp_dir().join("convdim-doctest-cache")).unwrap();
+/// ```
+pub struct DiskCache {
+    directory: PathBuf,
+}
+
+impl DiskCache {
+    /// The cache in `directory`, which is created when the first result is stored.
+    pub fn new(directory: impl Into<PathBuf>) -> DiskCache {
+        DiskCache {
+            directory: directory.into(),
+        }
+    }
+
+    /// ## The default directory of the cache.
+    ///
+    /// The directory is `$CONVDIM_CACHE_DIR`, or `convdim` in `$XDG_CACHE_HOME` or in `~/.cache`,
+    /// and `None` if none of these variables is set.
+    pub fn default_directory() -> Option<PathBuf> {
+        let variable = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+        if let Some(directory) = variable(CACHE_DIR) {
+            return Some(PathBuf::from(directory));
+        }
+        let base = variable("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| variable("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        Some(base.join("convdim"))
+    }
+
+    /// ## The key of the result of `analysis` with `params` of `graph` for `in_dim`.
+    pub fn key(analysis: &str, graph: &Graph, in_dim: u32, params: &str) -> String {
+        format!(
+            "convdim {} {} {} {} {}",
+            env!("CARGO_PKG_VERSION"),
+            analysis,
+            fingerprint::hash(graph),
+            in_dim,
+            params
+        )
+        .trim_end()
+        .to_string()
+    }
+
+    /// The file of the result of `key`.
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(sha256(key.as_bytes()))
+    }
+
+    /// ## The result stored for `key`, if there is one.
+    ///
+    /// Unreadable files are treated like missing ones, so a broken cache only costs the time of
+    /// computing the results again.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let text = std::fs::read_to_string(self.path(key)).ok()?;
+        let (stored, result) = text.split_once('\n')?;
+        match stored == key {
+            true => Some(result.to_string()),
+            false => None,
+        }
+    }
+
+    /// ## Store `result` for `key`, replacing the result stored before.
+    pub fn put(&self, key: &str, result: &str) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.directory)?;
+        let path = self.path(key);
+        let temporary = path.with_extension(format!("tmp{}", std::process::id()));
+        std::fs::write(&temporary, format!("{}\n{}", key, result))?;
+        std::fs::rename(&temporary, &path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::{ConvParams, PoolParams};
+
+    #[test]
+    fn test_cache() {
+        let conv = Layer::Conv(ConvParams {
+            name: Some("stem".to_string()),
+            filter_size: 2,
+            stride: 2,
+            padding: 0,
+            channels: None,
+        });
+        let pool = Layer::MaxPool(PoolParams {
+            name: Some("pool".to_string()),
+            filter_size: 2,
+            stride: 2,
+            padding: 0,
+        });
+        let mut cache = Cache::default();
+        assert_eq!(cache.layer_output_dim(64, &conv), Ok(32));
+        assert_eq!(cache.layer_output_dim(64, &pool), Ok(32));
+        assert_eq!(cache.layer_output_dim(1, &conv), layer_output_dim(1, &conv));
+        assert_eq!(cache.layer_output_dim(1, &pool), layer_output_dim(1, &pool));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.statistics(), Statistics { hits: 2, misses: 2 });
+        assert_eq!(
+            cache.statistics().to_string(),
+            "4 lookups, 2 hits (50.0%), 2 misses"
+        );
+    }
+
+    #[test]
+    fn test_disk_cache() {
+        let graph =
+            crate::parse_architecture("[[layers]]\ntype = \"conv\"\nfilter_size = 3\nstride = 2\n")
+                .unwrap()
+                .into_graph();
+        let mut renamed = graph.clone();
+        renamed.nodes[0].name = "stem".to_string();
+        let key = DiskCache::key("resize", &graph, 64, "max_results=1");
+        assert_eq!(key, DiskCache::key("resize", &renamed, 64, "max_results=1"));
+        assert_ne!(key, DiskCache::key("resize", &graph, 64, "max_results=2"));
+        assert_ne!(key, DiskCache::key("scan", &graph, 64, "max_results=1"));
+
+        let directory = std::env::temp_dir().join(format!("convdim-cache-{}", std::process::id()));
+        let cache = DiskCache::new(&directory);
+        assert_eq!(cache.get(&key), None);
+        cache.put(&key, "first\n").unwrap();
+        cache.put(&key, "second\nresult\n").unwrap();
+        let stored = cache.get(&key);
+        // A file holding another key is not a result for this one.
+        std::fs::write(cache.path(&key), "other key\nresult\n").unwrap();
+        let collision = cache.get(&key);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(stored.as_deref(), Some("second\nresult\n"));
+        assert_eq!(collision, None);
+    }
+}
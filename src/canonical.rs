@@ -0,0 +1,560 @@
+//! A canonical form of the descriptions of networks, so that their diffs are reviewable.
+//!
+//! A description in toml, JSON or YAML is rewritten in the same format with:
+//!
+//! - the keys in a fixed order: the name and the type or operation first, then the inputs, the
+//!   geometry and the channels, and the lists of nodes or layers last.
+//! - the default values stripped, or made explicit: the stride of 1 and padding of 0 of
+//!   (transposed) convolutions, the filter size of 1 of formulas, and the inputs of nodes reading
+//!   the node before them.
+//! - the layers in the format predating the types written with their `type`.
+//! - the names defined more than once made unique by a suffix, e.g. the second `conv` becomes
+//!   `conv_2`. The references to a name in the `inputs` of later nodes and in the `outputs` are
+//!   renamed to its last definition before them.
+//!
+//! The comments of toml descriptions are kept with the keys and tables they precede or follow,
+//! read with [toml_edit]. The comments that can not be kept, e.g. those of stripped defaults, of
+//! the elements of arrays or of YAML descriptions, are only dropped if this is forced.
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use toml_edit::{DocumentMut, Item, RawString, Value};
+
+use crate::architecture::interpret;
+use crate::convert::{self, Target};
+use crate::error::ConvDimError;
+use crate::graph::INPUT;
+use crate::import::yolo::strip_comment;
+use crate::json::parse_json;
+
+type Table = toml::value::Table;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## Whether the default values are stripped from the description or made explicit.
+pub enum Defaults {
+    Strip,
+    Explicit,
+}
+
+impl FromStr for Defaults {
+    type Err = ConvDimError;
+
+    fn from_str(text: &str) -> Result<Defaults, ConvDimError> {
+        match text {
+            "strip" => Ok(Defaults::Strip),
+            "explicit" => Ok(Defaults::Explicit),
+            _ => Err(ConvDimError::Parse {
+                message: format!("'{}' is not one of 'strip' and 'explicit'.", text),
+            }),
+        }
+    }
+}
+
+/// The names defined so far, and the unique name each of them refers to.
+#[derive(Default)]
+struct Names {
+    taken: HashSet<String>,
+    current: HashMap<String, String>,
+}
+
+impl Names {
+    /// Define `name`, returning it with the first suffix that makes it unique.
+    fn define(&mut self, name: &str) -> String {
+        let mut unique = name.to_string();
+        let mut suffix = 2;
+        while !self.taken.insert(unique.clone()) {
+            unique = format!("{}_{}", name, suffix);
+            suffix += 1;
+        }
+        self.current.insert(name.to_string(), unique.clone());
+        unique
+    }
+
+    /// The unique name of the last definition of `name`.
+    fn resolve(&self, name: &str) -> String {
+        self.current
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// Rename the string `key` of `table` to the unique name of a new definition.
+    fn define_in(&mut self, table: &mut Table, key: &str) -> Option<String> {
+        let name = self.define(table.get(key)?.as_str()?);
+        table.insert(key.to_string(), toml::Value::String(name.clone()));
+        Some(name)
+    }
+
+    /// Rename the strings of the array `key` of `table` to the unique names they refer to.
+    fn resolve_in(&self, table: &mut Table, key: &str) {
+        if let Some(toml::Value::Array(names)) = table.get_mut(key) {
+            for name in names.iter_mut() {
+                if let toml::Value::String(text) = name {
+                    *text = self.resolve(text);
+                }
+            }
+        }
+    }
+}
+
+/// Strip the members of `table` that have their value of `defaults`, or insert the missing ones.
+fn apply(table: &mut Table, defaults: &[(&str, toml::Value)], mode: Defaults) {
+    for (key, value) in defaults {
+        match mode {
+            Defaults::Strip if table.get(*key) == Some(value) => {
+                table.remove(*key);
+            }
+            Defaults::Strip => {}
+            Defaults::Explicit => {
+                table
+                    .entry(key.to_string())
+                    .or_insert_with(|| value.clone());
+            }
+        }
+    }
+}
+
+/// The defaults of the geometry of a (transposed) convolution.
+fn geometry() -> Vec<(&'static str, toml::Value)> {
+    vec![
+        ("stride", toml::Value::Integer(1)),
+        ("padding", toml::Value::Integer(0)),
+    ]
+}
+
+/// The nodes in canonical form, the first one reading `first_input`.
+fn nodes(values: &mut [toml::Value], first_input: String, names: &mut Names, mode: Defaults) {
+    let mut previous = first_input;
+    for node in values.iter_mut().filter_map(toml::Value::as_table_mut) {
+        names.resolve_in(node, "inputs");
+        let reads_previous = match node.get("inputs").and_then(toml::Value::as_array) {
+            Some(inputs) => {
+                inputs.is_empty() || inputs.len() == 1 && inputs[0].as_str() == Some(&previous)
+            }
+            None => true,
+        };
+        match (mode, reads_previous) {
+            (Defaults::Strip, true) => {
+                node.remove("inputs");
+            }
+            (Defaults::Explicit, true) => {
+                let previous = toml::Value::String(previous.clone());
+                node.insert("inputs".to_string(), toml::Value::Array(vec![previous]));
+            }
+            (_, false) => {}
+        }
+
+        let op = node.get("op").and_then(toml::Value::as_str);
+        match (mode, op) {
+            (Defaults::Explicit, Some("conv")) | (Defaults::Explicit, Some("transposed")) => {
+                apply(node, &geometry(), mode)
+            }
+            (Defaults::Strip, _) => apply(node, &geometry(), mode),
+            _ => {}
+        }
+        if let Some(name) = names.define_in(node, "name") {
+            previous = name;
+        }
+    }
+}
+
+/// The entries of a list of layers in canonical form.
+fn entries(values: &mut [toml::Value], names: &mut Names, mode: Defaults) {
+    for entry in values.iter_mut().filter_map(toml::Value::as_table_mut) {
+        if let Some(toml::Value::Array(branches)) = entry.get_mut("parallel") {
+            for branch in branches.iter_mut().filter_map(toml::Value::as_table_mut) {
+                if let Some(toml::Value::Array(layers)) = branch.get_mut("layers") {
+                    entries(layers, names, mode);
+                }
+            }
+            continue;
+        }
+        if !entry.contains_key("type") {
+            let transposed = entry.remove("transposed").and_then(|flag| flag.as_bool());
+            let kind = match transposed {
+                Some(true) => "conv_transpose",
+                _ => "conv",
+            };
+            entry.insert("type".to_string(), toml::Value::String(kind.to_string()));
+        }
+        let defaults = match entry.get("type").and_then(toml::Value::as_str) {
+            Some("conv") | Some("conv_transpose") => geometry(),
+            Some("max_pool") => vec![("padding", toml::Value::Integer(0))],
+            Some("formula") => {
+                let mut defaults = vec![("filter_size", toml::Value::Integer(1))];
+                defaults.extend(geometry());
+                defaults.push(("transposed", toml::Value::Boolean(false)));
+                defaults
+            }
+            _ => vec![],
+        };
+        apply(entry, &defaults, mode);
+        names.define_in(entry, "name");
+    }
+}
+
+/// ## The description `value` in canonical form, with the defaults stripped or made explicit.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::canonical::{canonical, Defaults};
+/// let value: toml::Value = toml::from_str(
+///     r#"
+///     [[layers]]
+///     name = "conv"
+///     filter_size = 3
+///     stride = 1
+///     padding = 1
+///     transposed = false
+///
+///     [[layers]]
+///     name = "conv"
+///     type = "max_pool"
+///     filter_size = 2
+///     stride = 2
+///     "#,
+/// )
+/// .unwrap();
+/// let canonical = canonical(&value, Defaults::Strip);
+/// let layers = canonical["layers"].as_array().unwrap();
+/// assert_eq!(layers[0].get("type").unwrap().as_str(), Some("conv"));
+/// assert_eq!(layers[0].get("stride"), None);
+/// assert_eq!(layers[1].get("name").unwrap().as_str(), Some("conv_2"));
+/// ```
+pub fn canonical(value: &toml::Value, mode: Defaults) -> toml::Value {
+    let mut value = value.clone();
+    let table = match value.as_table_mut() {
+        Some(table) => table,
+        None => return value,
+    };
+    let mut names = Names::default();
+    let first_input = match table.get_mut("inputs") {
+        Some(toml::Value::Array(inputs)) => {
+            let mut first = None;
+            for input in inputs.iter_mut().filter_map(toml::Value::as_table_mut) {
+                let name = names.define_in(input, "name");
+                first = first.or(name);
+            }
+            first
+        }
+        _ => None,
+    };
+    if let Some(toml::Value::Array(values)) = table.get_mut("nodes") {
+        let first_input = first_input.unwrap_or_else(|| INPUT.to_string());
+        nodes(values, first_input, &mut names, mode);
+    }
+    if let Some(toml::Value::Array(values)) = table.get_mut("layers") {
+        entries(values, &mut names, mode);
+    }
+    names.resolve_in(table, "outputs");
+    if let Some(toml::Value::Array(subnetworks)) = table.get_mut("subnetworks") {
+        for subnetwork in subnetworks.iter_mut().filter_map(toml::Value::as_table_mut) {
+            if let Some(toml::Value::Array(values)) = subnetwork.get_mut("nodes") {
+                let mut names = Names::default();
+                names.define(INPUT);
+                nodes(values, INPUT.to_string(), &mut names, mode);
+            }
+        }
+    }
+    value
+}
+
+/// The comments in the whitespace `raw`, without their indentation.
+fn comments(raw: Option<&RawString>) -> Vec<&str> {
+    raw.and_then(RawString::as_str)
+        .map(|raw| {
+            raw.lines()
+                .map(str::trim)
+                .filter(|line| line.starts_with('#'))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The whitespace `raw` followed by the lines of `comments`.
+fn commented(raw: Option<&RawString>, comments: &[&str]) -> String {
+    let raw = raw.and_then(RawString::as_str).unwrap_or_default();
+    comments
+        .iter()
+        .fold(raw.to_string(), |raw, comment| raw + comment + "\n")
+}
+
+/// The number of comments within `value`, including those before and after it.
+fn value_comments(value: &Value) -> usize {
+    let decor = value.decor();
+    let nested = match value {
+        Value::Array(array) => {
+            let elements: usize = array.iter().map(value_comments).sum();
+            elements + comments(Some(array.trailing())).len()
+        }
+        _ => 0,
+    };
+    comments(decor.prefix()).len() + comments(decor.suffix()).len() + nested
+}
+
+/// The number of comments within `item`.
+fn item_comments(item: &Item) -> usize {
+    match item {
+        Item::Value(value) => value_comments(value),
+        Item::Table(table) => table_comments(table),
+        Item::ArrayOfTables(tables) => tables.iter().map(table_comments).sum(),
+        Item::None => 0,
+    }
+}
+
+/// The number of comments within `table`, including those of its header.
+fn table_comments(table: &toml_edit::Table) -> usize {
+    let decor = table.decor();
+    let members: usize = table
+        .iter()
+        .map(|(key, item)| {
+            let key = table.key(key).map(|key| key.leaf_decor().prefix());
+            comments(key.flatten()).len() + item_comments(item)
+        })
+        .sum();
+    comments(decor.prefix()).len() + comments(decor.suffix()).len() + members
+}
+
+/// Move the comments of the table `from` to the same keys and tables of `to`.
+///
+/// Returns the number of comments that could not be moved, since their key was removed or they
+/// are within a value.
+fn move_comments(from: &toml_edit::Table, to: &mut toml_edit::Table) -> usize {
+    let decor = from.decor();
+    let prefix = commented(to.decor().prefix(), &comments(decor.prefix()));
+    let suffix = commented(None, &comments(decor.suffix()));
+    to.decor_mut().set_prefix(prefix);
+    if !suffix.is_empty() {
+        to.decor_mut().set_suffix(format!(" {}", suffix.trim_end()));
+    }
+    let mut dropped = 0;
+    for (key, item) in from.iter() {
+        let key_comments = comments(from.key(key).and_then(|key| key.leaf_decor().prefix()));
+        let (mut to_key, to_item) = match to.get_key_value_mut(key) {
+            Some(member) => member,
+            None => {
+                dropped += key_comments.len() + item_comments(item);
+                continue;
+            }
+        };
+        let prefix = commented(to_key.leaf_decor().prefix(), &key_comments);
+        to_key.leaf_decor_mut().set_prefix(prefix);
+        dropped += match (item, to_item) {
+            (Item::Value(value), Item::Value(to_value)) => {
+                let suffix = comments(value.decor().suffix());
+                if let Some(comment) = suffix.first() {
+                    to_value.decor_mut().set_suffix(format!(" {}", comment));
+                }
+                value_comments(value) - suffix.len()
+            }
+            (Item::Table(table), Item::Table(to_table)) => move_comments(table, to_table),
+            (Item::ArrayOfTables(tables), Item::ArrayOfTables(to_tables))
+                if tables.len() == to_tables.len() =>
+            {
+                tables
+                    .iter()
+                    .zip(to_tables.iter_mut())
+                    .map(|(table, to_table)| move_comments(table, to_table))
+                    .sum()
+            }
+            (item, _) => item_comments(item),
+        };
+    }
+    dropped
+}
+
+/// The `canonical` toml description with the comments of the description `text`.
+///
+/// Returns the number of comments that can not be kept if there are any.
+fn keep_comments(text: &str, canonical: &str) -> Result<String, usize> {
+    let parse = |text: &str| {
+        text.parse::<DocumentMut>()
+            .expect("The description was read as toml.")
+    };
+    let (original, mut document) = (parse(text), parse(canonical));
+    let dropped = move_comments(original.as_table(), document.as_table_mut());
+    let trailing = comments(Some(original.trailing()));
+    document.set_trailing(commented(None, &trailing));
+    match dropped {
+        0 => Ok(document.to_string()),
+        dropped => Err(dropped),
+    }
+}
+
+/// ## Rewrite the description `text` in the format `target` in canonical form.
+///
+/// The comments of toml descriptions are kept with their keys and tables. Returns an error if
+/// the canonical description can not be read as a network, so that broken descriptions are not
+/// rewritten, or if comments would be dropped, unless `force` is set.
+pub fn format(
+    text: &str,
+    target: Target,
+    mode: Defaults,
+    force: bool,
+) -> Result<String, ConvDimError> {
+    let parse_error = |message: String| ConvDimError::Parse { message };
+    let value = match target {
+        Target::Toml => toml::from_str(text).map_err(|e| parse_error(e.to_string()))?,
+        Target::Json => {
+            convert::from_json(&parse_json(text).map_err(parse_error)?).map_err(parse_error)?
+        }
+        Target::Yaml => convert::from_yaml(text).map_err(parse_error)?,
+    };
+    let located = match target {
+        Target::Toml => Some(text),
+        Target::Json | Target::Yaml => None,
+    };
+    let canonical = canonical(&value, mode);
+    if let Err(message) = interpret(canonical.clone(), None) {
+        // The problems are located in the given description, unless only its canonical form has them.
+        return Err(parse_error(
+            interpret(value, located).err().unwrap_or(message),
+        ));
+    }
+    let table = match canonical {
+        toml::Value::Table(table) => table,
+        _ => unreachable!("The description is a table, since it was read as a network."),
+    };
+    let (formatted, dropped) = match target {
+        Target::Toml => {
+            let formatted = convert::toml(&table, "");
+            match keep_comments(text, &formatted) {
+                Ok(commented) => (commented, 0),
+                Err(dropped) => (formatted, dropped),
+            }
+        }
+        Target::Yaml => {
+            let comments = text.lines().filter(|line| strip_comment(line) != *line);
+            (convert::write_table(&table, target), comments.count())
+        }
+        Target::Json => (convert::write_table(&table, target), 0),
+    };
+    match dropped {
+        0 => Ok(formatted),
+        _ if force => Ok(formatted),
+        dropped => Err(parse_error(format!(
+            "{} comment(s) can not be kept in canonical form, e.g. those of stripped defaults or \
+             of YAML descriptions; force the rewrite to drop them.",
+            dropped
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let description = r#"
+            outputs = ["up"]
+            input_channels = 3
+
+            [[nodes]]
+            stride = 2
+            op = "conv"
+            name = "conv"
+            filter_size = 3
+            padding = 0
+            inputs = ["input"]
+
+            [[nodes]]
+            name = "conv"
+            op = "conv"
+            filter_size = 3
+            inputs = ["conv"]
+
+            [[nodes]]
+            name = "up"
+            op = "concat"
+            inputs = ["conv", "input"]
+            "#;
+        let stripped = format(description, Target::Toml, Defaults::Strip, false).unwrap();
+        assert_eq!(
+            stripped,
+            "input_channels = 3\n\
+             outputs = [\"up\"]\n\
+             \n\
+             [[nodes]]\n\
+             name = \"conv\"\n\
+             op = \"conv\"\n\
+             filter_size = 3\n\
+             stride = 2\n\
+             \n\
+             [[nodes]]\n\
+             name = \"conv_2\"\n\
+             op = \"conv\"\n\
+             filter_size = 3\n\
+             \n\
+             [[nodes]]\n\
+             name = \"up\"\n\
+             op = \"concat\"\n\
+             inputs = [\"conv_2\", \"input\"]\n"
+        );
+        assert_eq!(
+            format(&stripped, Target::Toml, Defaults::Strip, false).unwrap(),
+            stripped
+        );
+        let explicit = format(&stripped, Target::Toml, Defaults::Explicit, false).unwrap();
+        assert!(explicit.contains(
+            "name = \"conv_2\"\nop = \"conv\"\ninputs = [\"conv\"]\nfilter_size = 3\nstride = 1\npadding = 0\n"
+        ));
+
+        let yaml = convert::write_table(
+            toml::from_str::<toml::Value>(&explicit)
+                .unwrap()
+                .as_table()
+                .unwrap(),
+            Target::Yaml,
+        );
+        assert_eq!(
+            format(&yaml, Target::Yaml, Defaults::Explicit, false).unwrap(),
+            yaml
+        );
+        assert!(format(&yaml, Target::Toml, Defaults::Strip, false).is_err());
+    }
+
+    #[test]
+    fn test_keep_comments() {
+        let description = "# The encoder.\n\
+                           input_channels = 3 # RGB\n\
+                           \n\
+                           # The first stage.\n\
+                           [[layers]]\n\
+                           padding = 1\n\
+                           # A 3x3 kernel.\n\
+                           filter_size = 3\n\
+                           # The end.\n";
+        assert_eq!(
+            format(description, Target::Toml, Defaults::Strip, false).unwrap(),
+            "# The encoder.\n\
+             input_channels = 3 # RGB\n\
+             \n\
+             # The first stage.\n\
+             [[layers]]\n\
+             type = \"conv\"\n\
+             # A 3x3 kernel.\n\
+             filter_size = 3\n\
+             padding = 1\n\
+             # The end.\n"
+        );
+
+        let description = "[[layers]]\nfilter_size = 3\nstride = 1 # The default.\n";
+        assert_eq!(
+            format(description, Target::Toml, Defaults::Strip, false),
+            Err(ConvDimError::Parse {
+                message: "1 comment(s) can not be kept in canonical form, e.g. those of stripped \
+                          defaults or of YAML descriptions; force the rewrite to drop them."
+                    .to_string()
+            })
+        );
+        assert_eq!(
+            format(description, Target::Toml, Defaults::Strip, true).unwrap(),
+            "[[layers]]\ntype = \"conv\"\nfilter_size = 3\n"
+        );
+        let yaml = "layers:\n  - filter_size: 3 # A 3x3 kernel.\n";
+        assert!(format(yaml, Target::Yaml, Defaults::Strip, false).is_err());
+        assert!(format(yaml, Target::Yaml, Defaults::Strip, true).is_ok());
+    }
+}
@@ -0,0 +1,99 @@
+//! Terminal charts of the dimension after every layer of a network.
+//!
+//! The depth of the layers runs along the `x` axis and their output dimension along the `y` axis,
+//! so that the shape of a network, e.g. the hourglass of an autoencoder, is seen at a glance.
+use std::fmt;
+
+/// The number of rows of the bars of a chart.
+pub const HEIGHT: usize = 12;
+
+/// Every how many layers the depth is labelled on the `x` axis.
+const LABEL_EVERY: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## A bar chart of the output dimensions of successive layers.
+///
+/// Every layer is a column of `#`, whose height is proportional to its dimension. A nonzero
+/// dimension has at least one `#`.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::chart::Chart;
+/// let chart = Chart { dims: vec![4, 2, 4], height: 2 };
+/// assert_eq!(chart.to_string(), "4 | #   #\n  | # # #\n0 +------\n    1\n");
+/// ```
+pub struct Chart {
+    pub dims: Vec<u32>,
+    pub height: usize,
+}
+
+impl From<Vec<u32>> for Chart {
+    fn from(dims: Vec<u32>) -> Chart {
+        Chart {
+            dims,
+            height: HEIGHT,
+        }
+    }
+}
+
+impl fmt::Display for Chart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let max = self.dims.iter().copied().max().unwrap_or(0).max(1);
+        let width = max.to_string().len();
+        // The number of rows of every bar, rounded up.
+        let bars: Vec<u64> = self
+            .dims
+            .iter()
+            .map(|&dim| (u64::from(dim) * self.height as u64).div_ceil(u64::from(max)))
+            .collect();
+        for row in (1..=self.height as u64).rev() {
+            let label = match row == self.height as u64 {
+                true => max.to_string(),
+                false => String::new(),
+            };
+            let line: String = bars
+                .iter()
+                .map(|&bar| if bar >= row { " #" } else { "  " })
+                .collect();
+            writeln!(f, "{:>width$} |{}", label, line.trim_end(), width = width)?;
+        }
+        writeln!(
+            f,
+            "{:>width$} +{}",
+            0,
+            "--".repeat(bars.len()),
+            width = width
+        )?;
+
+        // The depths of the first layer and every fifth layer, under their columns.
+        let mut labels = String::new();
+        for depth in (1..=bars.len()).filter(|&depth| depth == 1 || depth % LABEL_EVERY == 0) {
+            let column = width + 2 * depth + 1;
+            if labels.len() < column {
+                labels += &" ".repeat(column - labels.len());
+                labels += &depth.to_string();
+            }
+        }
+        writeln!(f, "{}", labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chart() {
+        let chart = Chart::from(vec![64, 32, 16, 8, 16, 32, 64]);
+        let text = chart.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), HEIGHT + 2);
+        assert_eq!(lines[0], "64 | #           #");
+        assert_eq!(lines[HEIGHT - 3], "   | # # #   # # #");
+        assert_eq!(lines[HEIGHT - 2], "   | # # # # # # #");
+        assert_eq!(lines[HEIGHT], " 0 +--------------");
+        assert_eq!(lines[HEIGHT + 1], "     1       5");
+        assert!(Chart::from(vec![]).to_string().ends_with("0 +\n\n"));
+    }
+}
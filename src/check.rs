@@ -0,0 +1,308 @@
+//! The validation of all the descriptions in a directory, e.g. the configs of a repository in CI.
+//!
+//! The descriptions are the toml files defining `layers` or `nodes`, and the JSON files describing
+//! a network, in the directory and its subdirectories. Toml files that cannot be parsed are
+//! checked too, so that their syntax errors are reported. A description passes if all its outputs
+//! can be evaluated, for the dimensions declared by its inputs or the default input dimension.
+//!
+//! The outcomes can be written as a JUnit report with a test case per description, which CI
+//! systems render like the results of unit tests, or as [JSON lines](crate::ndjson).
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::architecture::read_architecture;
+use crate::convert;
+use crate::error::ConvDimError;
+use crate::export::svg::escape;
+use crate::json::parse_json;
+use crate::ndjson::{self, table};
+use crate::trace::write_columns;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The outcome of checking a description: the dimensions of its outputs, or the error.
+pub struct Outcome {
+    pub path: PathBuf,
+    pub outputs: Result<Vec<(String, u32)>, String>,
+}
+
+impl Outcome {
+    /// Whether all the outputs of the description could be evaluated.
+    pub fn passed(&self) -> bool {
+        self.outputs.is_ok()
+    }
+
+    /// ## The outcome as a JSON line, with the dimensions of the `outputs` or the `error`.
+    pub fn json_line(&self) -> String {
+        let mut record = table([
+            ("path", toml::Value::String(self.path.display().to_string())),
+            ("passed", toml::Value::Boolean(self.passed())),
+        ]);
+        match &self.outputs {
+            Ok(outputs) => {
+                let outputs = outputs
+                    .iter()
+                    .map(|(name, dim)| {
+                        toml::Value::Table(table([
+                            ("name", toml::Value::String(name.clone())),
+                            ("dim", toml::Value::Integer(i64::from(*dim))),
+                        ]))
+                    })
+                    .collect();
+                record.insert("outputs".to_string(), toml::Value::Array(outputs));
+            }
+            Err(e) => {
+                record.insert("error".to_string(), toml::Value::String(e.clone()));
+            }
+        }
+        ndjson::line("check", record)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The outcomes of checking several descriptions.
+pub struct Check {
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Check {
+    /// The number of descriptions that passed.
+    pub fn passed(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.passed())
+            .count()
+    }
+
+    /// The number of descriptions that failed.
+    pub fn failed(&self) -> usize {
+        self.outcomes.len() - self.passed()
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows: Vec<Vec<String>> = self
+            .outcomes
+            .iter()
+            .map(|outcome| {
+                let (status, detail) = match &outcome.outputs {
+                    Ok(outputs) => {
+                        let outputs: Vec<String> = outputs
+                            .iter()
+                            .map(|(name, dim)| format!("{} {}", name, dim))
+                            .collect();
+                        ("pass", outputs.join(", "))
+                    }
+                    Err(e) => ("FAIL", e.lines().next().unwrap_or("").to_string()),
+                };
+                vec![
+                    status.to_string(),
+                    outcome.path.display().to_string(),
+                    detail,
+                ]
+            })
+            .collect();
+        write_columns(f, &rows, &[])?;
+        writeln!(f, "\n{} passed, {} failed", self.passed(), self.failed())
+    }
+}
+
+/// ## The path of the report given by `spec`, which is `junit=` followed by the path.
+pub fn report_path(spec: &str) -> Result<PathBuf, ConvDimError> {
+    match spec.split_once('=') {
+        Some(("junit", path)) if !path.is_empty() => Ok(PathBuf::from(path)),
+        _ => Err(ConvDimError::Check {
+            message: format!("'{}' is not a report like 'junit=out.xml'.", spec),
+        }),
+    }
+}
+
+/// ## The outcomes of `check` as a JUnit report, with a test case per description.
+///
+/// The outputs of the descriptions that passed are the standard output of their test case.
+pub fn junit(check: &Check) -> String {
+    let counts = format!(
+        "tests=\"{}\" failures=\"{}\"",
+        check.outcomes.len(),
+        check.failed()
+    );
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites {0}>\n  <testsuite name=\"convdim check\" {0} errors=\"0\">\n",
+        counts
+    );
+    for outcome in &check.outcomes {
+        xml += &format!(
+            "    <testcase classname=\"convdim.check\" name=\"{}\">\n",
+            escape(&outcome.path.display().to_string())
+        );
+        match &outcome.outputs {
+            Ok(outputs) => {
+                let outputs: Vec<String> = outputs
+                    .iter()
+                    .map(|(name, dim)| format!("{}: {}", name, dim))
+                    .collect();
+                xml += &format!(
+                    "      <system-out>{}</system-out>\n",
+                    escape(&outputs.join("\n"))
+                );
+            }
+            Err(e) => {
+                xml += &format!(
+                    "      <failure message=\"{}\">{}</failure>\n",
+                    escape(e.lines().next().unwrap_or("")),
+                    escape(e)
+                );
+            }
+        }
+        xml += "    </testcase>\n";
+    }
+    xml + "  </testsuite>\n</testsuites>\n"
+}
+
+/// Whether the file at `path` is a description: a toml file defining `layers` or `nodes` (or one
+/// that cannot be parsed), or a JSON file describing a network.
+fn is_description(path: &Path) -> bool {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    let text = match extension {
+        Some("toml") | Some("json") => match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return true,
+        },
+        _ => return false,
+    };
+    if extension == Some("json") {
+        return parse_json(&text).is_ok_and(|json| convert::is_description(&json));
+    }
+    match toml::from_str::<toml::Value>(&text) {
+        Ok(value) => value.get("layers").is_some() || value.get("nodes").is_some(),
+        Err(_) => true,
+    }
+}
+
+/// The paths of the descriptions in `directory` and its subdirectories.
+pub(crate) fn descriptions(directory: &Path, paths: &mut Vec<PathBuf>) -> Result<(), ConvDimError> {
+    let unreadable = |e: std::io::Error| ConvDimError::Check {
+        message: format!("'{}': {}", directory.display(), e),
+    };
+    for entry in std::fs::read_dir(directory).map_err(unreadable)? {
+        let path = entry.map_err(unreadable)?.path();
+        if path.is_dir() {
+            descriptions(&path, paths)?;
+        } else if is_description(&path) {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// ## Check the description at `path` for the input dimension `in_dim`.
+///
+/// The input dimension is only needed by the networks whose inputs don't all declare their own.
+pub fn check_file(path: &Path, in_dim: Option<u32>) -> Outcome {
+    let outputs = read_architecture(path).and_then(|architecture| {
+        let graph = architecture.into_graph();
+        let in_dim = match in_dim {
+            Some(in_dim) => in_dim,
+            None if !graph.needs_input_dim() => 0,
+            None => return Err(ConvDimError::Check {
+                message:
+                    "the input dimension is required, since not all the inputs declare their own."
+                        .to_string(),
+            }),
+        };
+        graph.output_shapes(in_dim)
+    });
+    Outcome {
+        path: path.to_path_buf(),
+        outputs: outputs
+            .map(|shapes| {
+                shapes
+                    .into_iter()
+                    .map(|(name, shape)| (name, shape.dim))
+                    .collect()
+            })
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// ## Check the descriptions at `paths` for the input dimension `in_dim`.
+pub fn check_files(paths: &[PathBuf], in_dim: Option<u32>) -> Check {
+    Check {
+        outcomes: paths.iter().map(|path| check_file(path, in_dim)).collect(),
+    }
+}
+
+/// ## The paths of the descriptions in `directory` and its subdirectories, in order.
+pub fn description_paths(directory: &Path) -> Result<Vec<PathBuf>, ConvDimError> {
+    let mut paths = vec![];
+    descriptions(directory, &mut paths)?;
+    paths.sort();
+    Ok(paths)
+}
+
+/// ## Check the descriptions in `directory` and its subdirectories for the input dimension `in_dim`.
+pub fn check_dir(directory: &Path, in_dim: Option<u32>) -> Result<Check, ConvDimError> {
+    Ok(check_files(&description_paths(directory)?, in_dim))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_dir() {
+        let directory = std::env::temp_dir().join(format!("convdim-check-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("nested")).unwrap();
+        let write = |name: &str, text: &str| std::fs::write(directory.join(name), text).unwrap();
+        write(
+            "a.toml",
+            "[[layers]]\ntype = \"conv\"\nfilter_size = 3\nstride = 2\n",
+        );
+        write(
+            "nested/b.toml",
+            "[[inputs]]\nname = \"x\"\ninput_dim = 2\n\n[[nodes]]\nname = \"c\"\nop = \"conv\"\nfilter_size = 3\n",
+        );
+        write("Cargo.toml", "[package]\nname = \"other\"\n");
+        write("c.toml", "[[layers]\n");
+
+        let check = check_dir(&directory, None);
+        let with_dim = check_dir(&directory, Some(9));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let check = check.unwrap();
+        assert_eq!(check.outcomes.len(), 3);
+        assert_eq!((check.passed(), check.failed()), (0, 3));
+        let with_dim = with_dim.unwrap();
+        assert_eq!(
+            with_dim.outcomes[0].outputs,
+            Ok(vec![("layer1".to_string(), 4)])
+        );
+        assert!(with_dim.outcomes[2]
+            .outputs
+            .as_ref()
+            .unwrap_err()
+            .contains("'c'"));
+        assert!(with_dim.to_string().ends_with("\n1 passed, 2 failed\n"));
+        assert!(check_dir(&directory, None).is_err());
+
+        let xml = junit(&with_dim);
+        assert!(xml.contains("<testsuites tests=\"3\" failures=\"2\">"));
+        assert!(xml.contains("<system-out>layer1: 4</system-out>"));
+        assert_eq!(xml.matches("<failure message=").count(), 2);
+        assert_eq!(report_path("junit=out.xml"), Ok(PathBuf::from("out.xml")));
+        assert!(report_path("xml=out.xml").is_err());
+
+        assert_eq!(
+            with_dim.outcomes[0].json_line(),
+            format!(
+                "{{\"type\": \"check\", \"outputs\": [{{\"name\": \"layer1\", \"dim\": 4}}], \"passed\": true, \"path\": \"{}\"}}",
+                directory.join("a.toml").display()
+            )
+        );
+        let failure = with_dim.outcomes[2].json_line();
+        assert!(failure.starts_with("{\"type\": \"check\", \"error\": "));
+        assert!(failure.contains("\"passed\": false"));
+    }
+}
@@ -0,0 +1,287 @@
+//! The subcommands analyzing a network beyond its output dimension, e.g. the pairing of its skip
+//! connections, the tiles of its inputs or the frames of a signal it is applied to.
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+use convdim::convention::Convention;
+use convdim::progress::Progress;
+use convdim::{
+    audio, backward, cache, contract, convert, fingerprint, fpn, generate, ndjson, patches,
+    read_layers, resize, scan, segmentation, series, skips, snapshot, stft, tiling,
+};
+
+use super::evaluate::input_dims;
+use super::{fail, or_exit, read, write_output};
+
+/// ## Print the range of the input dimensions for which the layers in `toml` output `out_dim`.
+pub(crate) fn backward(toml: &Path, out_dim: u32) {
+    let layers = or_exit(read_layers(toml));
+    match backward::input_range_for_output(&layers.layers, out_dim) {
+        Some(range) => println!("{}", range),
+        None => println!(
+            "No input dimension leads to an output of dimension {}.",
+            out_dim
+        ),
+    }
+}
+
+/// ## Print the levels of the feature pyramid of the network in `toml`, with the sizes of a
+/// detection head if its `anchors` or `classes` are given.
+pub(crate) fn fpn(
+    toml: &Path,
+    in_dim: u32,
+    anchors: Option<String>,
+    classes: Option<u32>,
+    box_outputs: u32,
+    convention: Convention,
+) {
+    let levels = or_exit(fpn::pyramid(&read(toml, convention), in_dim));
+    let per_location = anchors.map(|anchors| or_exit(fpn::anchors_per_location(&anchors)));
+    let report = fpn::Report::new(levels, per_location.as_deref(), box_outputs, classes);
+    print!("{}", or_exit(report));
+}
+
+/// ## Print the pairs of encoder and decoder outputs of the network in `toml`.
+pub(crate) fn skips(toml: &Path, in_dim: u32, convention: Convention) {
+    let pairing = or_exit(skips::pair_skips(&read(toml, convention), in_dim));
+    for pair in &pairing.pairs {
+        println!(
+            "{} ({}) <-> {} ({}): {}",
+            pair.encoder.name,
+            pair.encoder.dim,
+            pair.decoder.name,
+            pair.decoder.dim,
+            pair.fix().unwrap_or_else(|| "ok".to_string())
+        );
+    }
+    for name in &pairing.unpaired {
+        println!("{}: unpaired", name);
+    }
+}
+
+/// ## Check that the segmentation network in `toml` outputs the resolution of its input.
+pub(crate) fn segmentation(toml: &Path, in_dim: u32, convention: Convention) {
+    let graph = read(toml, convention).into_graph();
+    let resolution = or_exit(segmentation::check(&graph, in_dim));
+    print!("{}", resolution);
+    if !resolution.is_ok() {
+        std::process::exit(1);
+    }
+}
+
+/// ## Print the frames the network in `toml` outputs for audio of the given `duration` and `rate`.
+pub(crate) fn audio(toml: &Path, duration: &str, rate: &str, convention: Convention) {
+    let graph = read(toml, convention).into_graph();
+    let (duration, rate) = (
+        or_exit(audio::duration(duration)),
+        or_exit(audio::rate(rate)),
+    );
+    print!("{}", or_exit(audio::frames(&graph, duration, rate)));
+}
+
+/// ## Print the frames of the short-time Fourier transform of a signal, and the output
+/// dimension of the network in `toml` over them.
+pub(crate) fn stft(
+    length: &str,
+    window: u64,
+    hop: u64,
+    padding: &str,
+    toml: Option<PathBuf>,
+    convention: Convention,
+) {
+    let length = or_exit(generate::samples(length));
+    let padding = or_exit(padding.parse());
+    let frames = or_exit(stft::frames(length, window, hop, padding));
+    println!("frames:  {}", frames);
+    if let Some(toml) = toml {
+        let graph = read(&toml, convention).into_graph();
+        let frames = u32::try_from(frames).unwrap_or_else(|_| {
+            fail(format!(
+                "The {} frames exceed the input of a network.",
+                frames
+            ))
+        });
+        println!("output:  {}", or_exit(graph.output_shape(frames)).dim);
+    }
+}
+
+/// ## Print the patches a sliding window extracts from an input, per axis and in total.
+pub(crate) fn patches(input: &str, window: &str, stride: &str, padding: &str) {
+    let [input, window, stride, padding] =
+        [input, window, stride, padding].map(|sizes| or_exit(patches::sizes(sizes)));
+    print!(
+        "{}",
+        or_exit(patches::count(&input, &window, &stride, &padding))
+    );
+}
+
+/// ## Print the tiles of the input of the network in `toml` computing an `output_tile`, or the
+/// largest tiles fitting into the memory `budget`.
+pub(crate) fn tile(
+    toml: &Path,
+    output_tile: Option<u32>,
+    budget: Option<String>,
+    bytes_per_value: u64,
+    image: Option<u32>,
+    convention: Convention,
+) {
+    let graph = read(toml, convention).into_graph();
+    let plan = match (output_tile, budget) {
+        (Some(output_tile), _) => or_exit(tiling::plan(&graph, output_tile)),
+        (None, Some(budget)) => or_exit(tiling::plan_for_budget(
+            &graph,
+            or_exit(tiling::bytes(&budget)),
+            bytes_per_value,
+        )),
+        (None, None) => fail("Either the output tile or the memory budget is required."),
+    };
+    match image {
+        Some(image) => print!("{}", plan.cover(image)),
+        None => print!("{}", plan),
+    }
+}
+
+/// ## Print the changes of the network in `toml` that make it valid for the input `in_dim`.
+///
+/// The suggestions are cached on disk unless `no_cache` is given.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn resize(
+    toml: &Path,
+    in_dim: &str,
+    max_results: usize,
+    max_depth: u32,
+    timeout: Option<String>,
+    jobs: usize,
+    no_cache: bool,
+    convention: Convention,
+) {
+    let architecture = read(toml, convention);
+    let graph = architecture.clone().into_graph();
+    let limits = resize::Limits {
+        max_results,
+        max_depth,
+        timeout: timeout.map(|timeout| or_exit(resize::duration(&timeout))),
+        jobs,
+    };
+    let disk_cache = match no_cache {
+        true => None,
+        false => cache::DiskCache::default_directory().map(cache::DiskCache::new),
+    };
+    let params = format!("max_results={} max_depth={}", max_results, max_depth);
+    let (dims, _) = input_dims(in_dim, None);
+    for &(label, in_dim) in &dims {
+        if dims.len() > 1 {
+            println!("{} {}:", label, in_dim);
+        }
+        let key = cache::DiskCache::key("resize", &graph, in_dim, &params);
+        if let Some(result) = disk_cache.as_ref().and_then(|cache| cache.get(&key)) {
+            print!("{}", result);
+            continue;
+        }
+        let suggestion = or_exit(resize::search(&architecture, in_dim, &limits));
+        // A search cut short by the timeout depends on the machine, so it is not reused.
+        if let (Some(cache), false) = (
+            &disk_cache,
+            matches!(
+                suggestion.truncated,
+                Some(resize::Truncation::Timeout { .. })
+            ),
+        ) {
+            // The cache only saves time, so failing to write it is not an error.
+            let _ = cache.put(&key, &suggestion.to_string());
+        }
+        print!("{}", suggestion);
+    }
+}
+
+/// ## Print the output dimensions of the network in `toml` for the images in `directory`.
+pub(crate) fn scan(
+    directory: &Path,
+    toml: &Path,
+    jobs: usize,
+    quiet: bool,
+    format: ndjson::Format,
+    convention: Convention,
+) {
+    let graph = read(toml, convention).into_graph();
+    let mut progress = Progress::stderr("images", quiet);
+    match format {
+        ndjson::Format::Text => print!(
+            "{}",
+            or_exit(scan::scan(&graph, directory, jobs, &mut progress))
+        ),
+        ndjson::Format::Ndjson => {
+            or_exit(scan::scan_each(
+                &graph,
+                directory,
+                jobs,
+                &mut progress,
+                |entry| println!("{}", entry.json_line()),
+            ));
+        }
+    }
+}
+
+/// ## Print the output lengths of the network in `toml` for the series in `column` of the `files`.
+pub(crate) fn series(files: &[PathBuf], column: &str, toml: &Path, convention: Convention) {
+    let graph = read(toml, convention).into_graph();
+    print!("{}", series::lengths(&graph, files, column));
+}
+
+/// ## Write the shape contract of the network in `toml`, as YAML unless `output` says otherwise.
+pub(crate) fn contract(toml: &Path, in_dim: u32, output: Option<PathBuf>, convention: Convention) {
+    let target = match &output {
+        Some(output) => or_exit(convert::Target::from_path(output)),
+        None => convert::Target::Yaml,
+    };
+    let graph = read(toml, convention).into_graph();
+    let contract = or_exit(contract::contract(&graph, in_dim));
+    write_output(output, contract.write(target));
+}
+
+/// ## Print the fingerprint of the network in `toml`, or the normalized form it is computed from.
+pub(crate) fn hash(toml: &Path, normalized: bool, convention: Convention) {
+    let graph = read(toml, convention).into_graph();
+    if normalized {
+        print!("{}", fingerprint::normalize(&graph));
+    } else {
+        println!("{}", fingerprint::hash(&graph));
+    }
+}
+
+/// ## Record the trace of the network in `toml` to the snapshot `write`, or `check` it against one.
+pub(crate) fn snapshot(
+    toml: &Path,
+    in_dim: Option<u32>,
+    write: Option<PathBuf>,
+    check: Option<PathBuf>,
+    convention: Convention,
+) {
+    let graph = read(toml, convention).into_graph();
+    if let Some(path) = check {
+        let recorded = match std::fs::read_to_string(&path) {
+            Ok(recorded) => recorded,
+            Err(e) => fail(format!("Unable to read the snapshot '{:?}': {}", path, e)),
+        };
+        let diff = or_exit(snapshot::check(&graph, &recorded, in_dim));
+        if !diff.is_empty() {
+            print!("{}", diff);
+            std::process::exit(1);
+        }
+        println!("The trace matches the snapshot {:?}.", path);
+    } else if let (Some(path), Some(in_dim)) = (write, in_dim) {
+        let recorded = or_exit(snapshot::record(&graph, in_dim));
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            if let Err(e) = std::fs::create_dir_all(parent) {
+                fail(format!(
+                    "Unable to create the directory '{:?}': {}",
+                    parent, e
+                ));
+            }
+        }
+        write_output(Some(path), recorded);
+    }
+}
@@ -0,0 +1,49 @@
+//! The subcommands writing new descriptions: `init`, `wizard` and `generate`.
+use std::path::PathBuf;
+
+use convdim::{generate, init};
+
+use super::{fail, or_exit, wizard, write_output};
+use crate::GenerateCommand;
+
+/// ## Write the description of the `template` to `output`.
+pub(crate) fn init(template: &str, output: Option<PathBuf>) {
+    let template: init::Template = or_exit(template.parse());
+    write_output(output, template.toml());
+}
+
+/// ## Ask for the network on the terminal and write its description to the file it names.
+pub(crate) fn wizard() {
+    let stdin = std::io::stdin();
+    let answers = wizard::run(stdin.lock(), std::io::stdout());
+    match answers {
+        Ok(Some((block, path))) => {
+            write_output(Some(path.clone()), block.to_toml());
+            println!("Wrote '{}': {}", path.display(), block.description);
+        }
+        Ok(None) => fail("The answers ended before the network was complete."),
+        Err(e) => fail(format!("Unable to ask the questions: {}", e)),
+    }
+}
+
+/// ## Write the block generated by the `command`.
+pub(crate) fn generate(command: GenerateCommand) {
+    let (block, output) = match command {
+        GenerateCommand::Wavenet {
+            kernel,
+            layers,
+            channels,
+            output,
+        } => (generate::wavenet(kernel, layers, channels), output),
+        GenerateCommand::Tcn {
+            kernel,
+            receptive_field,
+            channels,
+            output,
+        } => {
+            let samples = or_exit(generate::samples(&receptive_field));
+            (generate::tcn(kernel, samples, channels), output)
+        }
+    };
+    write_output(output, or_exit(block).to_toml());
+}
@@ -0,0 +1,251 @@
+//! The main command, evaluating the network read from a description or the layer given by the
+//! options for the input dimensions.
+use std::convert::TryFrom;
+
+use convdim::chart::Chart;
+use convdim::convention::{Convention, Kind};
+use convdim::diagram::Diagram;
+use convdim::layers::{dim_after_layers, dims_at_outputs, trace_layers};
+use convdim::sparkline::Sparkline;
+use convdim::summary::{self, Summary};
+use convdim::trace::Trace;
+use convdim::{
+    conv_output_dim, energy, image, memory, numeric, series, stats, symbolic,
+    transposed_conv_output_dim, video, Architecture,
+};
+
+use super::{fail, narrow, or_exit, read};
+use crate::{Opt, OutputFormat};
+
+/// ## Run the main command with the options `opt`, computing the dimensions by the `convention`.
+pub(crate) fn run(opt: &Opt, convention: Convention) {
+    let architecture = opt.toml.as_deref().map(|path| read(path, convention));
+
+    // The formulas don't depend on the input dimension.
+    if opt.symbolic {
+        match architecture {
+            Some(architecture) if opt.simplify => {
+                let graph = architecture.into_graph();
+                print!("{}", Trace::from(or_exit(symbolic::closed_forms(&graph))));
+            }
+            Some(architecture) => {
+                let graph = architecture.into_graph();
+                print!("{}", Trace::from(or_exit(symbolic::graph_exprs(&graph))));
+            }
+            None => {
+                let expr = or_exit(symbolic::repeated(
+                    symbolic::Expr::variable(symbolic::VARIABLE),
+                    (opt.filter_size, opt.stride, opt.padding),
+                    opt.transposed,
+                    opt.repeat,
+                ));
+                match opt.simplify {
+                    true => println!("{}", symbolic::closed_form(&expr, &[&expr])),
+                    false => println!("{}", expr),
+                }
+            }
+        }
+        return;
+    }
+
+    // The input dimension is required unless a subcommand is used or
+    // all the inputs of the graph declare their own dimension.
+    let (dims, channels) = match (&opt.in_dim, &architecture) {
+        (Some(in_dim), _) => input_dims(in_dim, opt.column.as_deref()),
+        // The dimension is not used, since every input declares its own.
+        (None, Some(Architecture::Graph(graph))) if !graph.needs_input_dim() => {
+            (vec![("", 0)], None)
+        }
+        (None, _) => structopt::clap::Error::with_description(
+            "The following required arguments were not provided:\n    --input-dim <in-dim>",
+            structopt::clap::ErrorKind::MissingRequiredArgument,
+        )
+        .exit(),
+    };
+    let architecture = match (architecture, channels) {
+        (Some(Architecture::Layers(mut layers)), Some(channels)) => {
+            layers.input_channels = Some(channels);
+            Some(Architecture::Layers(layers))
+        }
+        (Some(Architecture::Graph(mut graph)), Some(channels)) => {
+            graph.input_channels = Some(channels);
+            Some(Architecture::Graph(graph))
+        }
+        (architecture, _) => architecture,
+    };
+    // The inputs are square, so the height and the width of an image (and the frames of a video)
+    // are evaluated separately.
+    for &(label, in_dim) in &dims {
+        if dims.len() > 1 {
+            println!("{} {}:", label, in_dim);
+        }
+        evaluate(opt, architecture.clone(), in_dim);
+    }
+}
+
+/// ## The input dimensions given by `--input-dim`, labelled if there are several, and the channels.
+///
+/// The input dimension is a number, or the path to an image whose height and width are used, or
+/// its side if it is square, and whose channels replace those of the input. The frames, height
+/// and width of a video are used like the time and space dimensions of a clip, and the length of
+/// the series in `column` of a CSV or Parquet file like the dimension of a 1D input.
+pub(crate) fn input_dims(
+    text: &str,
+    column: Option<&str>,
+) -> (Vec<(&'static str, u32)>, Option<u16>) {
+    if let Ok(in_dim) = text.parse() {
+        return (vec![("", in_dim)], None);
+    }
+    let path = std::path::Path::new(text);
+    if let Some(column) = column {
+        let length = or_exit(series::length(path, column));
+        match u32::try_from(length) {
+            Ok(length) => return (vec![("", length)], None),
+            Err(_) => fail(format!(
+                "The length {} of the series exceeds the largest input dimension.",
+                length
+            )),
+        }
+    }
+    if video::is_video(path) {
+        let header = or_exit(video::read(path));
+        let dims = vec![
+            ("frames", header.frames),
+            ("height", header.height),
+            ("width", header.width),
+        ];
+        return (dims, None);
+    }
+    let header = or_exit(image::read(path));
+    let dims = if header.height == header.width {
+        vec![("", header.height)]
+    } else {
+        vec![("height", header.height), ("width", header.width)]
+    };
+    (dims, Some(header.channels))
+}
+
+/// ## Evaluate the `architecture`, or the layer given by the options, for the input `in_dim`.
+fn evaluate(opt: &Opt, architecture: Option<Architecture>, in_dim: u32) {
+    if let Some(architecture) = architecture {
+        match architecture {
+            architecture if opt.verify_numeric => {
+                let graph = architecture.into_graph();
+                let (lines, agree) = or_exit(numeric::verify(&graph, in_dim));
+                for line in lines {
+                    println!("{}", line);
+                }
+                if !agree {
+                    std::process::exit(1);
+                }
+            }
+            architecture if opt.format == Some(OutputFormat::Table) => {
+                let graph = architecture.into_graph();
+                print!("{}", Summary::from(or_exit(summary::rows(&graph, in_dim))));
+            }
+            architecture if opt.format == Some(OutputFormat::Latex) => {
+                let graph = architecture.into_graph();
+                print!(
+                    "{}",
+                    summary::latex(&or_exit(summary::rows(&graph, in_dim)))
+                );
+            }
+            architecture if opt.format == Some(OutputFormat::Csv) => {
+                let graph = architecture.into_graph();
+                print!("{}", summary::csv(&or_exit(summary::rows(&graph, in_dim))));
+            }
+            architecture if opt.stats => {
+                let graph = architecture.into_graph();
+                print!("{}", or_exit(stats::stats(&graph, in_dim)));
+            }
+            architecture if opt.memory => {
+                let graph = architecture.into_graph();
+                print!("{}", or_exit(memory::memory(&graph, in_dim, opt.fuse)));
+            }
+            architecture if opt.energy => {
+                let graph = architecture.into_graph();
+                let costs = energy::Costs {
+                    pj_per_mac: opt.pj_per_mac,
+                    pj_per_byte: opt.pj_per_byte,
+                };
+                print!("{}", or_exit(energy::energy(&graph, in_dim, costs)));
+            }
+            architecture if opt.diagram => {
+                let graph = architecture.into_graph();
+                print!(
+                    "{}",
+                    or_exit(Diagram::new(&graph, in_dim, opt.diagram_channels))
+                );
+            }
+            architecture if opt.sparkline => {
+                let graph = architecture.into_graph();
+                println!("{}", or_exit(Sparkline::new(&graph, in_dim)));
+            }
+            architecture if opt.chart => {
+                let shapes = or_exit(architecture.into_graph().shapes(in_dim));
+                let dims: Vec<u32> = shapes.into_iter().map(|(_, shape)| shape.dim).collect();
+                print!("{}", Chart::from(dims));
+            }
+            Architecture::Layers(layers) if opt.trace => {
+                print!(
+                    "{}",
+                    Trace::from(or_exit(trace_layers(&layers.layers, in_dim)))
+                );
+            }
+            Architecture::Layers(layers) if layers.outputs.is_empty() => {
+                println!("{}", or_exit(dim_after_layers(&layers.layers, in_dim)))
+            }
+            Architecture::Layers(layers) => {
+                print!("{}", Trace::from(or_exit(dims_at_outputs(&layers, in_dim))));
+            }
+            Architecture::Graph(graph) if opt.trace => {
+                print!("{}", Trace::from(or_exit(graph.shapes(in_dim))));
+            }
+            Architecture::Graph(graph) if graph.outputs.is_empty() => {
+                println!("{}", or_exit(graph.output_shape(in_dim)).dim)
+            }
+            Architecture::Graph(graph) => {
+                print!("{}", Trace::from(or_exit(graph.output_shapes(in_dim))));
+            }
+        }
+    } else if let Some(formula) = opt.convention.and_then(|convention| {
+        let kind = match opt.transposed {
+            true => Kind::Transposed,
+            false => Kind::Conv,
+        };
+        convention.formula(kind, narrow(opt.padding, "padding"))
+    }) {
+        let (filter_size, stride, padding) = (
+            narrow(opt.filter_size, "filter size"),
+            narrow(opt.stride, "stride"),
+            narrow(opt.padding, "padding"),
+        );
+        let mut dim = in_dim;
+        for _ in 0..opt.repeat {
+            dim = or_exit(formula.output_dim(dim, filter_size, stride, padding));
+        }
+        println!("{}", dim);
+    } else if opt.transposed {
+        println!(
+            "{}",
+            or_exit(transposed_conv_output_dim(
+                in_dim,
+                opt.filter_size,
+                opt.padding,
+                opt.stride,
+                opt.repeat
+            ))
+        );
+    } else {
+        println!(
+            "{}",
+            or_exit(conv_output_dim(
+                in_dim,
+                opt.filter_size,
+                opt.padding,
+                opt.stride,
+                opt.repeat
+            ))
+        );
+    }
+}
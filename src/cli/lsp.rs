@@ -0,0 +1,505 @@
+//! A language server for the toml descriptions, e.g. for editor extensions.
+//!
+//! The server speaks JSON-RPC over the standard input and output, framed like the Language
+//! Server Protocol. It publishes the errors of the descriptions as diagnostics, together with
+//! warnings about lossy divisions, i.e. convolutions ignoring the last rows and columns of their
+//! input, and answers inlay hint requests with the output dimension of every layer or node.
+//!
+//! The input dimension is given by the `inputDim` of the initialization options, or when starting
+//! the server. Without it, only graphs whose inputs declare their own dimension are evaluated.
+//!
+//! The layers are located in the document by their `name` or, for unnamed layers, by the position
+//! of their `filter_size` or `scale` among those of all layers.
+use std::collections::HashMap;
+use std::io::{self, BufRead, Read, Write};
+
+use serde_json::Value;
+
+use super::parse_json;
+use convdim::architecture::{parse_architecture, Architecture};
+use convdim::convert::{json, string};
+use convdim::error::{ConvDimError, LayerRef};
+use convdim::layers::{dim_after_layers, dims_at_outputs, walk_layers};
+use convdim::schema::{self, code_lines, values};
+use convdim::summary;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## The severity of a diagnostic, numbered like in the Language Server Protocol.
+pub enum Severity {
+    Error = 1,
+    Warning = 2,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## A diagnostic of the (zero-based) `line` of a description.
+pub struct Diagnostic {
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## The output of a layer or node, shown at the end of the (zero-based) `line` of the layer.
+pub struct Hint {
+    pub line: usize,
+    pub label: String,
+}
+
+/// The line of the layer or node called `name`.
+fn name_line(text: &str, name: &str) -> Option<usize> {
+    let quoted = string(name);
+    code_lines(text)
+        .iter()
+        .position(|line| values(line, "name").iter().any(|v| v.starts_with(&quoted)))
+}
+
+/// The line of the (one-based) `position`-th layer of a list of layers.
+fn position_line(text: &str, position: usize) -> Option<usize> {
+    code_lines(text)
+        .iter()
+        .enumerate()
+        .flat_map(|(i, line)| {
+            let count = values(line, "filter_size").len() + values(line, "scale").len();
+            std::iter::repeat_n(i, count)
+        })
+        .nth(position.checked_sub(1)?)
+}
+
+/// The line of the layer `reference`.
+fn layer_line(text: &str, reference: &LayerRef) -> Option<usize> {
+    match reference {
+        LayerRef::Name(name) => name_line(text, name),
+        LayerRef::Position(position) => position_line(text, *position),
+        LayerRef::Located { position, name, .. } => name
+            .as_deref()
+            .and_then(|name| name_line(text, name))
+            .or_else(|| position_line(text, *position)),
+    }
+}
+
+/// The line `error` refers to, or the first line if it concerns the whole description.
+fn error_line(text: &str, error: &ConvDimError) -> usize {
+    let line = match error {
+        ConvDimError::InputCount { node, .. }
+        | ConvDimError::MissingFilterSize { node }
+        | ConvDimError::IncompatibleMerge { node, .. } => name_line(text, node),
+        ConvDimError::Unevaluable { nodes } => nodes.first().and_then(|node| name_line(text, node)),
+        ConvDimError::UnknownOutput { .. } => code_lines(text)
+            .iter()
+            .position(|line| !values(line, "outputs").is_empty()),
+        error => error.layer().and_then(|layer| layer_line(text, layer)),
+    };
+    line.unwrap_or(0)
+}
+
+/// The line of the node `name` of the graph a list of layers is converted into, whose unnamed
+/// layers are called `layer1`, `layer2` and so on.
+fn node_line(text: &str, name: &str) -> Option<usize> {
+    name_line(text, name).or_else(|| {
+        name.strip_prefix("layer")
+            .and_then(|position| position.parse().ok())
+            .and_then(|position| position_line(text, position))
+    })
+}
+
+/// The input dimension for `architecture`, if it can be evaluated.
+fn input_dim(architecture: &Architecture, in_dim: Option<u32>) -> Option<u32> {
+    match (in_dim, architecture) {
+        (Some(in_dim), _) => Some(in_dim),
+        (None, Architecture::Graph(graph)) if !graph.needs_input_dim() => Some(0),
+        (None, _) => None,
+    }
+}
+
+/// ## The diagnostics of the description `text` for an input of dimension `in_dim`.
+pub fn diagnostics(text: &str, in_dim: Option<u32>) -> Vec<Diagnostic> {
+    let error = |line, message: String| Diagnostic {
+        line,
+        severity: Severity::Error,
+        message,
+    };
+    let value = match toml::from_str::<toml::Value>(text) {
+        Ok(value) => value,
+        Err(e) => {
+            let line = e.line_col().map_or(0, |(line, _)| line);
+            return vec![error(line, e.to_string())];
+        }
+    };
+    let problems = schema::check(&value, Some(text));
+    if !problems.is_empty() {
+        return problems
+            .into_iter()
+            .map(|problem| {
+                let line = problem.line.map_or(0, |line| line - 1);
+                error(line, format!("{}: {}", problem.location, problem.message))
+            })
+            .collect();
+    }
+    let architecture = match parse_architecture(text) {
+        Ok(architecture) => architecture,
+        Err(e) => return vec![error(0, e.to_string())],
+    };
+    let in_dim = match input_dim(&architecture, in_dim) {
+        Some(in_dim) => in_dim,
+        None => return vec![],
+    };
+    let evaluated = match &architecture {
+        Architecture::Layers(layers) if layers.outputs.is_empty() => {
+            dim_after_layers(&layers.layers, in_dim).map(|_| ())
+        }
+        Architecture::Layers(layers) => dims_at_outputs(layers, in_dim).map(|_| ()),
+        Architecture::Graph(graph) => graph.output_shapes(in_dim).map(|_| ()),
+    };
+    if let Err(e) = evaluated {
+        return vec![error(error_line(text, &e), e.to_string())];
+    }
+
+    let graph = architecture.into_graph();
+    let rows = match summary::rows(&graph, in_dim) {
+        Ok(rows) => rows,
+        Err(e) => return vec![error(error_line(text, &e), e.to_string())],
+    };
+    summary::issues(&graph, &rows)
+        .into_iter()
+        .filter_map(|(node, message)| {
+            Some(Diagnostic {
+                line: node_line(text, node?)?,
+                severity: Severity::Warning,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// ## The outputs of the layers or nodes of the description `text` for an input of dimension
+/// `in_dim`.
+///
+/// The outputs of the layers evaluated before an error are still hinted.
+pub fn hints(text: &str, in_dim: Option<u32>) -> Vec<Hint> {
+    let architecture = match parse_architecture(text) {
+        Ok(architecture) => architecture,
+        Err(_) => return vec![],
+    };
+    let in_dim = match input_dim(&architecture, in_dim) {
+        Some(in_dim) => in_dim,
+        None => return vec![],
+    };
+    let mut hints = vec![];
+    match &architecture {
+        Architecture::Layers(layers) => {
+            let mut position = 0;
+            let _ = walk_layers(&layers.layers, in_dim, &mut |_, dim| {
+                position += 1;
+                if let Some(line) = position_line(text, position) {
+                    hints.push(Hint {
+                        line,
+                        label: format!("→ {}", dim),
+                    });
+                }
+            });
+        }
+        Architecture::Graph(graph) => {
+            for (name, shape) in graph.shapes(in_dim).unwrap_or_default() {
+                if let Some(line) = name_line(text, &name) {
+                    hints.push(Hint {
+                        line,
+                        label: format!("→ {}", shape),
+                    });
+                }
+            }
+        }
+    }
+    hints
+}
+
+/// The LSP position at the `character` of the zero-based `line`.
+fn position(line: usize, character: usize) -> toml::Value {
+    let mut position = toml::value::Table::new();
+    position.insert("line".to_string(), toml::Value::Integer(line as i64));
+    position.insert(
+        "character".to_string(),
+        toml::Value::Integer(character as i64),
+    );
+    toml::Value::Table(position)
+}
+
+/// The length of the zero-based `line` of `text` in UTF-16 code units, like LSP positions.
+fn line_length(text: &str, line: usize) -> usize {
+    text.lines()
+        .nth(line)
+        .map_or(0, |line| line.encode_utf16().count())
+}
+
+/// The parameters of the `textDocument/publishDiagnostics` notification for the document `uri`.
+fn publish(uri: &str, text: &str, in_dim: Option<u32>) -> toml::Value {
+    let diagnostics = diagnostics(text, in_dim)
+        .into_iter()
+        .map(|diagnostic| {
+            let mut range = toml::value::Table::new();
+            range.insert("start".to_string(), position(diagnostic.line, 0));
+            range.insert(
+                "end".to_string(),
+                position(diagnostic.line, line_length(text, diagnostic.line)),
+            );
+            let mut value = toml::value::Table::new();
+            value.insert("range".to_string(), toml::Value::Table(range));
+            value.insert(
+                "severity".to_string(),
+                toml::Value::Integer(diagnostic.severity as i64),
+            );
+            value.insert("source".to_string(), toml::Value::from("convdim"));
+            value.insert("message".to_string(), toml::Value::from(diagnostic.message));
+            toml::Value::Table(value)
+        })
+        .collect();
+    let mut params = toml::value::Table::new();
+    params.insert("uri".to_string(), toml::Value::from(uri));
+    params.insert("diagnostics".to_string(), toml::Value::Array(diagnostics));
+    toml::Value::Table(params)
+}
+
+/// The result of the `textDocument/inlayHint` request for `text`.
+fn inlay_hints(text: &str, in_dim: Option<u32>) -> toml::Value {
+    let hints = hints(text, in_dim)
+        .into_iter()
+        .map(|hint| {
+            let mut value = toml::value::Table::new();
+            value.insert(
+                "position".to_string(),
+                position(hint.line, line_length(text, hint.line)),
+            );
+            value.insert("label".to_string(), toml::Value::from(hint.label));
+            value.insert("paddingLeft".to_string(), toml::Value::Boolean(true));
+            toml::Value::Table(value)
+        })
+        .collect();
+    toml::Value::Array(hints)
+}
+
+/// Read the next message framed by its `Content-Length` header, or `None` at the end of `input`.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let length = length.ok_or_else(|| invalid("missing Content-Length".to_string()))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    let body = String::from_utf8(body).map_err(|e| invalid(e.to_string()))?;
+    parse_json(&body).map(Some).map_err(invalid)
+}
+
+/// Write the JSON `message` framed by its `Content-Length` header.
+fn write_message(output: &mut impl Write, message: &str) -> io::Result<()> {
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        message.len(),
+        message
+    )?;
+    output.flush()
+}
+
+/// The JSON text of the request `id`.
+fn id_text(id: &Value) -> String {
+    match (id.as_i64(), id.as_str()) {
+        (Some(id), _) => id.to_string(),
+        (_, Some(id)) => string(id),
+        _ => "null".to_string(),
+    }
+}
+
+/// The text of the document in the `params` of a notification, with its `uri`.
+fn document(params: &Value) -> Option<(String, String)> {
+    let uri = params.get("textDocument")?.get("uri")?.as_str()?;
+    let text = match params.get("contentChanges") {
+        Some(changes) => changes.as_array()?.last()?.get("text")?.as_str()?,
+        None => params.get("textDocument")?.get("text")?.as_str()?,
+    };
+    Some((uri.to_string(), text.to_string()))
+}
+
+/// ## Answer the messages read from `input` on `output` until the client exits.
+///
+/// `in_dim` is the input dimension of the networks, unless the client initializes the server
+/// with another one.
+pub fn run(input: impl Read, mut output: impl Write, mut in_dim: Option<u32>) -> io::Result<()> {
+    let mut input = io::BufReader::new(input);
+    let mut documents: HashMap<String, String> = HashMap::new();
+    while let Some(message) = read_message(&mut input)? {
+        let method = message.get("method").and_then(Value::as_str).unwrap_or("");
+        let params = message.get("params");
+        let result = match method {
+            "initialize" => {
+                if let Some(dim) = params
+                    .and_then(|params| params.get("initializationOptions"))
+                    .and_then(|options| options.get("inputDim"))
+                    .and_then(Value::as_i64)
+                {
+                    in_dim = std::convert::TryFrom::try_from(dim).ok();
+                }
+                Some(
+                    "{\"capabilities\": {\"textDocumentSync\": 1, \"inlayHintProvider\": true}, \
+                     \"serverInfo\": {\"name\": \"convdim\"}}"
+                        .to_string(),
+                )
+            }
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                if let Some((uri, text)) = params.and_then(document) {
+                    let notification = publish(&uri, &text, in_dim);
+                    documents.insert(uri, text);
+                    write_message(
+                        &mut output,
+                        &format!(
+                            "{{\"jsonrpc\": \"2.0\", \"method\": \"textDocument/publishDiagnostics\", \
+                             \"params\": {}}}",
+                            json(&notification, 0)
+                        ),
+                    )?;
+                }
+                None
+            }
+            "textDocument/didClose" => {
+                let uri = params
+                    .and_then(|params| params.get("textDocument"))
+                    .and_then(|document| document.get("uri"))
+                    .and_then(Value::as_str);
+                if let Some(uri) = uri {
+                    documents.remove(uri);
+                    write_message(
+                        &mut output,
+                        &format!(
+                            "{{\"jsonrpc\": \"2.0\", \"method\": \"textDocument/publishDiagnostics\", \
+                             \"params\": {{\"uri\": {}, \"diagnostics\": []}}}}",
+                            string(uri)
+                        ),
+                    )?;
+                }
+                None
+            }
+            "textDocument/inlayHint" => {
+                let text = params
+                    .and_then(|params| params.get("textDocument"))
+                    .and_then(|document| document.get("uri"))
+                    .and_then(Value::as_str)
+                    .and_then(|uri| documents.get(uri));
+                Some(match text {
+                    Some(text) => json(&inlay_hints(text, in_dim), 0),
+                    None => "[]".to_string(),
+                })
+            }
+            "shutdown" => Some("null".to_string()),
+            "exit" => return Ok(()),
+            _ => None,
+        };
+        let id = match message.get("id") {
+            Some(id) => id_text(id),
+            // Notifications are not answered.
+            None => continue,
+        };
+        let response = match result {
+            Some(result) => format!(
+                "{{\"jsonrpc\": \"2.0\", \"id\": {}, \"result\": {}}}",
+                id, result
+            ),
+            None => format!(
+                "{{\"jsonrpc\": \"2.0\", \"id\": {}, \"error\": {{\"code\": -32601, \
+                 \"message\": {}}}}}",
+                id,
+                string(&format!("Unknown method '{}'.", method))
+            ),
+        };
+        write_message(&mut output, &response)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYERS: &str = r#"
+[[layers]]
+name = "stem"
+type = "conv"
+filter_size = 3
+stride = 2
+
+[[layers]]
+type = "max_pool"  # halves the dimension
+filter_size = 2
+stride = 2
+"#;
+
+    #[test]
+    fn test_diagnostics_and_hints() {
+        assert_eq!(
+            hints(LAYERS, Some(64)),
+            vec![
+                Hint {
+                    line: 4,
+                    label: "→ 31".to_string()
+                },
+                Hint {
+                    line: 9,
+                    label: "→ 15".to_string()
+                },
+            ]
+        );
+        let found = diagnostics(LAYERS, Some(64));
+        assert_eq!(
+            found
+                .iter()
+                .map(|diagnostic| (diagnostic.line, diagnostic.severity))
+                .collect::<Vec<_>>(),
+            vec![(2, Severity::Warning), (9, Severity::Warning)]
+        );
+        assert!(found[1].message.contains("ignores the last 1 rows"));
+
+        let found = diagnostics(LAYERS, Some(2));
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].line, found[0].severity), (2, Severity::Error));
+        let found = diagnostics(&LAYERS.replace("stride = 2\n\n", "stride = \n\n"), Some(64));
+        assert_eq!((found[0].line, found[0].severity), (5, Severity::Error));
+    }
+
+    #[test]
+    fn test_session() {
+        let messages = [
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"initializationOptions": {"inputDim": 64}}}"#,
+            r#"{"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {"textDocument": {"uri": "file:///a.toml", "text": "[[layers]]\ntype = \"conv\"\nfilter_size = 3\n"}}}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "textDocument/inlayHint", "params": {"textDocument": {"uri": "file:///a.toml"}}}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "shutdown"}"#,
+            r#"{"jsonrpc": "2.0", "method": "exit"}"#,
+        ];
+        let input: String = messages
+            .iter()
+            .map(|message| format!("Content-Length: {}\r\n\r\n{}", message.len(), message))
+            .collect();
+        let mut output = vec![];
+        run(input.as_bytes(), &mut output, None).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\"inlayHintProvider\": true"));
+        assert!(output.contains("\"diagnostics\": []"));
+        assert!(output.contains("\"label\": \"→ 62\""));
+        assert!(output.contains("{\"jsonrpc\": \"2.0\", \"id\": 3, \"result\": null}"));
+
+        let nested = "[".repeat(100_000);
+        let input = format!("Content-Length: {}\r\n\r\n{}", nested.len(), nested);
+        let error = run(input.as_bytes(), &mut vec![], None).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
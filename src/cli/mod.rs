@@ -0,0 +1,85 @@
+//! The handlers of the subcommands, and the services of the command line interface.
+//!
+//! The handlers call the `convdim` library with the arguments parsed in `main.rs` and print its
+//! results, grouped by what they work on. The language server, the HTTP server, the web
+//! playground, the wizard and the evaluation of streams are applications of the library rather
+//! than part of it, so they are implemented here.
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+use convdim::convention::Convention;
+use convdim::{read_architecture, Architecture};
+
+pub(crate) mod analyses;
+pub(crate) mod authoring;
+pub(crate) mod evaluate;
+pub(crate) mod lsp;
+pub(crate) mod models;
+pub(crate) mod reports;
+pub(crate) mod server;
+pub(crate) mod services;
+pub(crate) mod stream;
+pub(crate) mod web;
+pub(crate) mod wizard;
+
+/// ## Print the error `message` to the standard error and exit with status 1.
+pub(crate) fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", message);
+    std::process::exit(1)
+}
+
+/// ## The value of `result`, or [`fail`] with its error.
+pub(crate) fn or_exit<T, E: std::fmt::Display>(result: Result<T, E>) -> T {
+    result.unwrap_or_else(|e| fail(e))
+}
+
+/// ## `value` as a parameter of a layer, or [`fail`] if it exceeds the range of `u16`.
+pub(crate) fn narrow(value: u32, parameter: &str) -> u16 {
+    u16::try_from(value).unwrap_or_else(|_| {
+        fail(format!(
+            "The {} {} exceeds the largest {} of a layer, {}.",
+            parameter,
+            value,
+            parameter,
+            u16::MAX
+        ))
+    })
+}
+
+/// ## Write `content` to the file `output`, or print it if no file is given.
+pub(crate) fn write_output(output: Option<PathBuf>, content: impl AsRef<[u8]>) {
+    match output {
+        Some(output) => {
+            if let Err(e) = std::fs::write(&output, content) {
+                fail(format!("Unable to write output file '{:?}': {}", output, e));
+            }
+        }
+        None => {
+            if let Err(e) = std::io::Write::write_all(&mut std::io::stdout(), content.as_ref()) {
+                fail(format!("Unable to write to the standard output: {}", e));
+            }
+        }
+    }
+}
+
+/// ## The architecture read from `path`, whose dimensions are computed by the `convention`.
+pub(crate) fn read(path: &Path, convention: Convention) -> Architecture {
+    convention.apply(or_exit(read_architecture(path)))
+}
+
+/// ## The input dimension `in_dim`, or 0 if every input of `graph` declares its own.
+pub(crate) fn required_in_dim(graph: &convdim::graph::Graph, in_dim: Option<u32>) -> u32 {
+    match in_dim {
+        Some(in_dim) => in_dim,
+        None if !graph.needs_input_dim() => 0,
+        None => {
+            fail("The input dimension is required, since not all the inputs declare their own.")
+        }
+    }
+}
+
+/// ## Parse the JSON document `text` of a request, with an error naming the line and column of the
+/// problem.
+pub(crate) fn parse_json(text: &str) -> Result<serde_json::Value, String> {
+    serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}.", e))
+}
@@ -0,0 +1,83 @@
+//! The subcommands reading and writing model files and descriptions: `verify`, `import`,
+//! `export`, `convert` and `fmt`.
+use std::path::{Path, PathBuf};
+
+use convdim::convention::Convention;
+use convdim::{canonical, convert, export, import, import_model, verify};
+
+use super::{fail, or_exit, read, write_output};
+use crate::{ExportCommand, ImportCommand};
+
+/// ## Compare the shapes computed for the ONNX `model` to the shapes recorded in it.
+pub(crate) fn verify(model: &Path, in_dim: Option<u32>) {
+    let mut graph = or_exit(import_model(model, import::Format::Onnx));
+    let bytes = match std::fs::read(model) {
+        Ok(bytes) => bytes,
+        Err(e) => fail(format!("Unable to read '{}': {}", model.display(), e)),
+    };
+    let (in_dim, recorded) = or_exit(verify::model_shapes(&mut graph, &bytes, in_dim));
+    let comparisons = or_exit(verify::compare(&graph, in_dim, &recorded));
+    if comparisons.is_empty() && cfg!(feature = "tract") {
+        fail("tract infers no shapes of feature maps of the model.");
+    } else if comparisons.is_empty() {
+        fail(
+            "The model records no shapes of feature maps, run the ONNX shape inference on it first.",
+        );
+    }
+    print!("{}", verify::report(&comparisons));
+    if !comparisons.iter().all(verify::Comparison::agrees) {
+        std::process::exit(1);
+    }
+}
+
+/// ## Import the model of the `command` and write it in the graph format.
+pub(crate) fn import(command: ImportCommand) {
+    let (model, format, output) = command.into_parts();
+    write_output(output, or_exit(import_model(&model, format)).to_toml());
+}
+
+/// ## Export the network of the `command` to the code or diagram of its format.
+pub(crate) fn export(command: ExportCommand, convention: Convention) {
+    let (toml, format, output) = command.into_parts();
+    let graph = read(&toml, convention).into_graph();
+    match export::export(&graph, format) {
+        Ok(code) => write_output(output, code),
+        Err(e) => fail(format!("Unable to export the network: {}", e)),
+    }
+}
+
+/// ## Convert the description `input` into the format given by the extension of `output`.
+pub(crate) fn convert(input: &Path, output: PathBuf, convention: Convention) {
+    let target = or_exit(convert::Target::from_path(&output));
+    let graph = read(input, convention).into_graph();
+    write_output(Some(output), convert::write(&graph, target));
+}
+
+/// ## Rewrite the descriptions `files` in their canonical form, or only list them with `check`.
+pub(crate) fn fmt(files: &[PathBuf], defaults: &str, check: bool, force: bool) {
+    let defaults: canonical::Defaults = or_exit(defaults.parse());
+    let mut failed = false;
+    for file in files {
+        let formatted = convert::Target::from_path(file).and_then(|target| {
+            let text = std::fs::read_to_string(file).map_err(|e| e.to_string())?;
+            canonical::format(&text, target, defaults, force)
+                .map(|formatted| (text, formatted))
+                .map_err(|e| e.to_string())
+        });
+        match formatted {
+            Ok((text, formatted)) if text == formatted => {}
+            Ok(_) if check => {
+                println!("{}", file.display());
+                failed = true;
+            }
+            Ok((_, formatted)) => write_output(Some(file.clone()), formatted),
+            Err(e) => {
+                eprintln!("error: {}: {}", file.display(), e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
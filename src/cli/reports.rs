@@ -0,0 +1,127 @@
+//! The subcommands describing networks for readers: `report`, `plot`, `check`, `stats` and
+//! `bench-table`.
+use std::path::{Path, PathBuf};
+
+use convdim::convention::Convention;
+use convdim::plot::{self, Series};
+use convdim::{bench, check, ndjson, report, zoo};
+
+use super::{fail, or_exit, read, required_in_dim, write_output};
+use crate::CheckCommand;
+
+/// ## Write the design review of the network in `toml` as HTML, and as Markdown to `markdown`.
+///
+/// The Markdown path `-` prints the report.
+pub(crate) fn report(
+    toml: &Path,
+    in_dim: Option<u32>,
+    html: Option<PathBuf>,
+    markdown: Option<PathBuf>,
+    convention: Convention,
+) {
+    let graph = read(toml, convention).into_graph();
+    let in_dim = required_in_dim(&graph, in_dim);
+    if let Some(markdown) = markdown {
+        let output = Some(markdown).filter(|path| path.as_os_str() != "-");
+        write_output(output, or_exit(report::markdown(&graph, in_dim)));
+        if html.is_none() {
+            return;
+        }
+    }
+    write_output(html, or_exit(report::html(&graph, in_dim)));
+}
+
+/// ## Plot the dimensions of the network in `toml`, and its parameters and operations, to
+/// the PNG or SVG file `output`.
+pub(crate) fn plot(
+    toml: &Path,
+    in_dim: Option<u32>,
+    output: PathBuf,
+    params: bool,
+    flops: bool,
+    convention: Convention,
+) {
+    let graph = read(toml, convention).into_graph();
+    let in_dim = required_in_dim(&graph, in_dim);
+    let series: Vec<Series> = std::iter::once(Series::Dims)
+        .chain(Some(Series::Params).filter(|_| params))
+        .chain(Some(Series::Flops).filter(|_| flops))
+        .collect();
+    match output.extension().and_then(|e| e.to_str()) {
+        Some("png") => or_exit(plot::png(&graph, in_dim, &series, &output)),
+        Some("svg") => write_output(
+            Some(output),
+            or_exit(plot::svg(&graph, in_dim, &series)).into_bytes(),
+        ),
+        _ => fail(format!(
+            "Unable to plot to '{}': the file needs the extension .png or .svg.",
+            output.display()
+        )),
+    }
+}
+
+/// ## Check the descriptions of the `command`, printing the outcomes and writing a JUnit report.
+pub(crate) fn check(command: CheckCommand) {
+    let (paths, in_dim, report, format) = match command {
+        CheckCommand::Dir {
+            directory,
+            in_dim,
+            report,
+            format,
+        } => (
+            or_exit(check::description_paths(&directory)),
+            in_dim,
+            report,
+            format,
+        ),
+        CheckCommand::Files {
+            files,
+            in_dim,
+            report,
+            format,
+        } => (files, in_dim, report, format),
+    };
+    let check = check::Check {
+        outcomes: paths
+            .iter()
+            .map(|path| {
+                let outcome = check::check_file(path, in_dim);
+                if format == ndjson::Format::Ndjson {
+                    println!("{}", outcome.json_line());
+                }
+                outcome
+            })
+            .collect(),
+    };
+    if let Some(report) = report {
+        write_output(
+            Some(or_exit(check::report_path(&report))),
+            check::junit(&check),
+        );
+    }
+    if format == ndjson::Format::Text {
+        print!("{}", check);
+    }
+    if check.failed() > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// ## Print the statistics of the zoo of descriptions in `directory`.
+pub(crate) fn stats(directory: &Path, in_dim: Option<u32>) {
+    let zoo = or_exit(zoo::zoo(directory, in_dim));
+    print!("{}", zoo);
+    if !zoo.failed.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// ## Print the table comparing the networks in `files`, sorted by the column `sort`.
+pub(crate) fn bench_table(files: &[PathBuf], in_dim: u32, sort: bench::Column, descending: bool) {
+    let mut table = bench::table(files, in_dim);
+    table.sort(sort, descending);
+    print!("{}", table);
+    if table.failed() > 0 {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,279 @@
+//! An HTTP server evaluating networks, e.g. to validate architectures uploaded to a model registry.
+//!
+//! The server answers `POST` requests whose bodies are JSON objects holding the `network`, in
+//! JSON with the same structure as the toml description:
+//!
+//! - `/eval` with the `input_dim` returns the `trace` of the outputs of all layers or nodes.
+//! - `/solve` with the `output_dim` returns the range `min` to `max` of the input dimensions
+//!   leading to it, for lists of layers like the `backward` subcommand.
+//!
+//! Errors are returned as a JSON object with the `error` message and a status of 400, or 422 if
+//! the network is valid but no input leads to the requested output.
+use std::convert::TryFrom;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use serde_json::Value;
+
+use super::parse_json;
+use convdim::architecture::{architecture_from_json, Architecture};
+use convdim::backward::input_range_for_output;
+use convdim::convert::json;
+use convdim::trace::outputs;
+
+/// The largest body of a request, in bytes.
+const MAX_BODY: usize = 16 << 20;
+/// The time after which connections sending nothing are closed.
+pub(crate) const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## The response to a request, whose `body` is a JSON document.
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    /// The response of status 200 holding `members`.
+    fn ok(members: Vec<(&str, toml::Value)>) -> Response {
+        let document = members
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+        Response {
+            status: 200,
+            body: json(&toml::Value::Table(document), 0) + "\n",
+        }
+    }
+
+    /// The response of `status` holding the error `message`.
+    fn error(status: u16, message: impl ToString) -> Response {
+        let mut document = toml::value::Table::new();
+        document.insert(
+            "error".to_string(),
+            toml::Value::String(message.to_string()),
+        );
+        Response {
+            status,
+            body: json(&toml::Value::Table(document), 0) + "\n",
+        }
+    }
+
+    /// The reason phrase of the status.
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            422 => "Unprocessable Entity",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// The network and the dimension `key` of the JSON `body` of a request.
+fn network_and_dim(body: &str, key: &str) -> Result<(Architecture, u32), Response> {
+    let body = parse_json(body).map_err(|e| Response::error(400, e))?;
+    let network = body
+        .get("network")
+        .ok_or_else(|| Response::error(400, "The request is missing the 'network'."))?;
+    let dim = body
+        .get(key)
+        .and_then(Value::as_i64)
+        .and_then(|dim| u32::try_from(dim).ok())
+        .ok_or_else(|| {
+            Response::error(
+                400,
+                format!("The request needs the '{}' as a positive integer.", key),
+            )
+        })?;
+    let network = architecture_from_json(network).map_err(|e| Response::error(400, e))?;
+    Ok((network, dim))
+}
+
+/// The trace of the network of the request `body` for its `input_dim`.
+fn eval(body: &str) -> Result<Response, Response> {
+    let (network, in_dim) = network_and_dim(body, "input_dim")?;
+    let trace = outputs(network, in_dim).map_err(|e| Response::error(400, e))?;
+    let trace = toml::Value::try_from(trace).map_err(|e| Response::error(500, e))?;
+    Ok(Response::ok(vec![("trace", trace)]))
+}
+
+/// The input dimensions of the network of the request `body` leading to its `output_dim`.
+fn solve(body: &str) -> Result<Response, Response> {
+    let (layers, out_dim) = match network_and_dim(body, "output_dim")? {
+        (Architecture::Layers(layers), out_dim) => (layers, out_dim),
+        (Architecture::Graph(_), _) => {
+            return Err(Response::error(
+                400,
+                "Solving for the input requires a list of layers, but the network is a graph.",
+            ))
+        }
+    };
+    match input_range_for_output(&layers.layers, out_dim) {
+        Some(range) => Ok(Response::ok(vec![
+            ("min", toml::Value::Integer(range.min.into())),
+            ("max", toml::Value::Integer(range.max.into())),
+        ])),
+        None => Err(Response::error(
+            422,
+            format!(
+                "No input dimension leads to an output of dimension {}.",
+                out_dim
+            ),
+        )),
+    }
+}
+
+/// ## The response to the request for `path` with `method` and `body`.
+pub fn handle(method: &str, path: &str, body: &str) -> Response {
+    let path = path.split('?').next().unwrap_or(path);
+    let endpoint: fn(&str) -> Result<Response, Response> = match path {
+        "/eval" => eval,
+        "/solve" => solve,
+        _ => return Response::error(404, format!("There is no endpoint '{}'.", path)),
+    };
+    if method != "POST" {
+        return Response::error(405, format!("'{}' only accepts POST requests.", path));
+    }
+    endpoint(body).unwrap_or_else(|response| response)
+}
+
+/// ## Read the request line and the headers of a request from `reader`.
+///
+/// Returns the method, the path and the length of the body, which is left in `reader`.
+pub(crate) fn read_head(reader: &mut impl BufRead) -> io::Result<(String, String, usize)> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("").to_string();
+
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    Ok((method, path, length))
+}
+
+/// Read the request from `stream` and write the response to it.
+fn respond(stream: &TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(stream.take(MAX_BODY as u64 + (64 << 10)));
+    let (method, path, length) = read_head(&mut reader)?;
+
+    let response = if length > MAX_BODY {
+        Response::error(413, "The body of the request is too large.")
+    } else {
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        match String::from_utf8(body) {
+            Ok(body) => handle(&method, &path, &body),
+            Err(_) => Response::error(400, "The body of the request is not UTF-8."),
+        }
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        response.status,
+        response.reason(),
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+/// ## Answer the requests of the connections accepted by `listener`, each on its own thread.
+///
+/// Failing connections are closed without affecting the others, so the server runs until the
+/// process is stopped.
+pub fn serve(listener: TcpListener) {
+    for stream in listener.incoming().flatten() {
+        std::thread::spawn(move || {
+            let _ = respond(&stream);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETWORK: &str = r#""network": {"layers": [
+        {"type": "conv", "filter_size": 3, "padding": 1},
+        {"type": "max_pool", "filter_size": 2, "stride": 2}
+    ]}"#;
+
+    #[test]
+    fn test_endpoints() {
+        let response = handle(
+            "POST",
+            "/eval",
+            &format!("{{{}, \"input_dim\": 64}}", NETWORK),
+        );
+        assert_eq!(response.status, 200);
+        assert!(response
+            .body
+            .contains("\"name\": \"layer 2\",\n      \"dim\": 32"));
+
+        let response = handle(
+            "POST",
+            "/solve",
+            &format!("{{{}, \"output_dim\": 32}}", NETWORK),
+        );
+        assert_eq!(
+            response,
+            Response {
+                status: 200,
+                body: "{\n  \"max\": 65,\n  \"min\": 64\n}\n".to_string()
+            }
+        );
+
+        let response = handle(
+            "POST",
+            "/eval",
+            &format!("{{{}, \"input_dim\": 1}}", NETWORK),
+        );
+        assert_eq!(response.status, 400);
+        assert!(response.body.contains("smaller than the kernel"));
+        assert_eq!(handle("POST", "/eval", "{}").status, 400);
+        let nested = format!("{{\"network\": {}}}", "[".repeat(100_000));
+        assert_eq!(handle("POST", "/eval", &nested).status, 400);
+        assert_eq!(handle("GET", "/eval", "").status, 405);
+        assert_eq!(handle("POST", "/", "").status, 404);
+    }
+
+    #[test]
+    fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || serve(listener));
+
+        let body = format!("{{{}, \"input_dim\": 64}}", NETWORK);
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "POST /eval HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("}\n"));
+    }
+}
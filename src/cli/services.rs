@@ -0,0 +1,78 @@
+//! The subcommands running a service until their input ends or they are stopped: `lsp`, `serve`,
+//! `web` and `stream`.
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+
+use convdim::progress::Progress;
+use convdim::{parse_architecture, read_layers, Architecture};
+
+use super::{fail, lsp, or_exit, server, stream, web};
+
+/// ## Listen on `host` at `port`, or [`fail`].
+fn listen(host: &str, port: u16) -> TcpListener {
+    match TcpListener::bind((host, port)) {
+        Ok(listener) => listener,
+        Err(e) => fail(format!("Unable to listen on {}:{}: {}", host, port, e)),
+    }
+}
+
+/// ## Run the language server on the standard input and output.
+pub(crate) fn lsp(in_dim: Option<u32>) {
+    if let Err(e) = lsp::run(std::io::stdin(), std::io::stdout(), in_dim) {
+        fail(format!("The language server failed: {}", e));
+    }
+}
+
+/// ## Serve the HTTP API on `host` at `port`.
+pub(crate) fn serve(port: u16, host: &str) {
+    let listener = listen(host, port);
+    eprintln!("Listening on http://{}:{}", host, port);
+    server::serve(listener);
+}
+
+/// ## Serve the playground of the layers in `toml`, or of the example, with the WebAssembly
+/// build in `pkg`.
+pub(crate) fn web(toml: Option<PathBuf>, in_dim: u32, pkg: &Path, port: u16, host: &str) {
+    let layers = match &toml {
+        Some(toml) => or_exit(read_layers(toml)),
+        None => match or_exit(parse_architecture(web::EXAMPLE)) {
+            Architecture::Layers(layers) => layers,
+            Architecture::Graph(_) => unreachable!(),
+        },
+    };
+    let unbuilt = |file: &str, e: std::io::Error| -> ! {
+        fail(format!(
+            "Unable to read the WebAssembly build '{}': {}. It is built with `wasm-pack \
+             build --target web -- --features wasm`.",
+            pkg.join(file).display(),
+            e
+        ))
+    };
+    let module = std::fs::read_to_string(pkg.join("convdim.js"))
+        .unwrap_or_else(|e| unbuilt("convdim.js", e));
+    let wasm = std::fs::read(pkg.join("convdim_bg.wasm"))
+        .unwrap_or_else(|e| unbuilt("convdim_bg.wasm", e));
+    let playground = or_exit(web::Playground::new(&layers, in_dim, module, wasm));
+    let listener = listen(host, port);
+    eprintln!("Open http://{}:{} in a browser", host, port);
+    web::serve(playground, listener);
+}
+
+/// ## Evaluate the candidates on the standard input, writing their outputs as JSON lines.
+pub(crate) fn stream(in_dim: Option<u32>, jobs: usize, quiet: bool, cache_stats: bool) {
+    let stdout = std::io::stdout();
+    let output = std::io::BufWriter::new(stdout.lock());
+    let progress = Progress::stderr("candidates", quiet);
+    match stream::evaluate(std::io::stdin().lock(), output, in_dim, jobs, &progress) {
+        Ok(totals) => {
+            eprintln!(
+                "Evaluated {} candidates, {} failed.",
+                totals.evaluated, totals.failed
+            );
+            if cache_stats {
+                eprintln!("Cache: {}.", totals.cache);
+            }
+        }
+        Err(e) => fail(format!("Unable to evaluate the stream: {}", e)),
+    }
+}
@@ -0,0 +1,207 @@
+//! The evaluation of streams of candidate networks, e.g. produced by an architecture search.
+//!
+//! Every line of the input is a candidate in JSON, either the description of the network with
+//! the structure of the toml descriptions or an object holding the `network` and its `input_dim`.
+//! For every line, a line holding the shapes of the `outputs` of the candidate, or its `error`, is
+//! written in the order of the input. The candidates are read and evaluated in batches, so the
+//! memory does not grow with the length of the stream.
+//!
+//! The candidates of a search share most of their layers and input dimensions, so every job looks
+//! the outputs of the convolutions up in its own [`Cache`], which lasts for the whole stream.
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Write};
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+use super::parse_json;
+use convdim::architecture::architecture_from_json;
+use convdim::cache::{Cache, Statistics};
+use convdim::convert::compact_json;
+use convdim::parallel;
+use convdim::progress::Progress;
+use convdim::trace::Output;
+
+/// The number of candidates evaluated by every job before the results are written.
+const BATCH: usize = 256;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// ## The number of candidates evaluated, of those which failed, and the statistics of the caches.
+pub struct Totals {
+    pub evaluated: u64,
+    pub failed: u64,
+    pub cache: Statistics,
+}
+
+/// ## The outputs of the candidate `line`, for the input of dimension `in_dim` unless it has its own.
+fn evaluate_line(
+    line: &str,
+    in_dim: Option<u32>,
+    cache: &mut Cache,
+) -> Result<toml::Value, String> {
+    let candidate = parse_json(line)?;
+    let (network, in_dim) = match candidate.get("network") {
+        Some(network) => {
+            let own = candidate
+                .get("input_dim")
+                .and_then(Value::as_i64)
+                .and_then(|dim| u32::try_from(dim).ok());
+            (network, own.or(in_dim))
+        }
+        None => (&candidate, in_dim),
+    };
+    let graph = architecture_from_json(network)
+        .map_err(|e| e.to_string())?
+        .into_graph();
+    let in_dim = match in_dim {
+        Some(in_dim) => in_dim,
+        None if !graph.needs_input_dim() => 0,
+        None => return Err("The candidate needs an 'input_dim'.".to_string()),
+    };
+    let outputs: Vec<Output> = graph
+        .cached_output_shapes(in_dim, cache)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|(name, shape)| Output {
+            name,
+            dim: shape.dim,
+            channels: shape.channels,
+        })
+        .collect();
+    toml::Value::try_from(outputs).map_err(|e| e.to_string())
+}
+
+/// The line written for the candidate `line`, and whether it failed.
+fn result_line(line: &str, in_dim: Option<u32>, cache: &mut Cache) -> (String, bool) {
+    let (key, value, failed) = match evaluate_line(line, in_dim, cache) {
+        Ok(outputs) => ("outputs", outputs, false),
+        Err(message) => ("error", toml::Value::String(message), true),
+    };
+    let mut result = toml::value::Table::new();
+    result.insert(key.to_string(), value);
+    (compact_json(&toml::Value::Table(result)), failed)
+}
+
+/// ## Evaluate the candidates on the lines of `input` on `jobs` threads, writing a line to
+/// `output` for each of them.
+///
+/// The candidates without an `input_dim` take `in_dim`. The results are flushed after every
+/// batch, so that consumers receive them while the stream is evaluated, and the candidates that
+/// didn't fail are counted as matching by the `progress`.
+pub fn evaluate(
+    input: impl BufRead,
+    mut output: impl Write,
+    in_dim: Option<u32>,
+    jobs: usize,
+    progress: &Progress,
+) -> io::Result<Totals> {
+    let jobs = parallel::jobs(jobs);
+    // Every job evaluates a chunk of the batch with its cache, so the locks are never contended.
+    let caches: Vec<Mutex<Cache>> = (0..jobs).map(|_| Mutex::default()).collect();
+    let mut totals = Totals::default();
+    let mut lines = input.lines();
+    loop {
+        let batch = lines
+            .by_ref()
+            .take(BATCH * jobs)
+            .collect::<io::Result<Vec<String>>>()?;
+        if batch.is_empty() {
+            progress.finish();
+            totals.cache = caches
+                .into_iter()
+                .map(|cache| {
+                    cache
+                        .into_inner()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .statistics()
+                })
+                .fold(Statistics::default(), |total, statistics| {
+                    total + statistics
+                });
+            return Ok(totals);
+        }
+        let chunks: Vec<(usize, &[String])> = batch.chunks(BATCH).enumerate().collect();
+        let results = parallel::map(&chunks, jobs, |&(job, chunk)| {
+            let mut cache = caches[job]
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            chunk
+                .iter()
+                .map(|line| {
+                    let (line, failed) = result_line(line, in_dim, &mut cache);
+                    progress.add(1, u64::from(!failed));
+                    (line, failed)
+                })
+                .collect::<Vec<_>>()
+        });
+        for (line, failed) in results.into_iter().flatten() {
+            writeln!(output, "{}", line)?;
+            totals.evaluated += 1;
+            totals.failed += u64::from(failed);
+        }
+        output.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let input = r#"{"layers": [{"type": "max_pool", "filter_size": 2, "stride": 2}]}
+{"network": {"layers": [{"type": "conv", "filter_size": 9}]}, "input_dim": 4}
+"#;
+        let mut output = vec![];
+        let progress = Progress::hidden("candidates");
+        let totals = evaluate(input.as_bytes(), &mut output, Some(64), 1, &progress).unwrap();
+        assert_eq!((totals.evaluated, totals.failed), (2, 1));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output
+            .starts_with("{\"outputs\": [{\"name\": \"layer1\", \"dim\": 32}]}\n{\"error\": "));
+    }
+
+    #[test]
+    fn test_candidates_share_the_cache() {
+        let candidate = r#"{"layers": [{"type": "conv", "filter_size": 3, "stride": 2}, {"type": "max_pool", "filter_size": 3, "stride": 2}]}"#;
+        let candidates = format!("{}\n", candidate).repeat(BATCH * 2 + 1);
+        let progress = Progress::hidden("candidates");
+        let totals = evaluate(candidates.as_bytes(), io::sink(), Some(224), 2, &progress).unwrap();
+        assert_eq!(totals.evaluated, BATCH as u64 * 2 + 1);
+        // Both jobs compute the two outputs once for their caches, which outlast the batches.
+        assert_eq!(totals.cache.misses, 4);
+        assert_eq!(totals.cache.hits, 2 * totals.evaluated - 4);
+    }
+
+    #[test]
+    fn test_evaluate_keeps_the_order() {
+        let candidates: String = (1..=2000)
+            .map(|filter_size| {
+                format!(
+                    "{{\"layers\": [{{\"type\": \"conv\", \"filter_size\": {}}}]}}\n",
+                    filter_size
+                )
+            })
+            .collect();
+        let mut output = vec![];
+        let progress = Progress::hidden("candidates");
+        let totals =
+            evaluate(candidates.as_bytes(), &mut output, Some(1000), 4, &progress).unwrap();
+        assert_eq!((totals.evaluated, totals.failed), (2000, 1000));
+        // Every filter size is evaluated once, for the same input.
+        assert_eq!(totals.cache.misses, 2000);
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            "{\"outputs\": [{\"name\": \"layer1\", \"dim\": 1000}]}"
+        );
+        assert_eq!(
+            lines[999],
+            "{\"outputs\": [{\"name\": \"layer1\", \"dim\": 1}]}"
+        );
+        assert!(lines[1000].starts_with("{\"error\": "));
+        assert_eq!(progress.counts(), (2000, 1000));
+        assert!(result_line("", None, &mut Cache::default()).1);
+    }
+}
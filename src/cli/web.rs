@@ -0,0 +1,210 @@
+//! A local web playground exploring a list of layers in the browser.
+//!
+//! The page has sliders for the input dimension and the filter size, stride, padding and channels
+//! of every layer, and shows the shapes and receptive fields of the layers as they are moved. The
+//! network is evaluated client-side by the WebAssembly build of the library, through the
+//! JavaScript API generated by wasm-bindgen (see the `wasm` feature), so the server only serves the
+//! page, the npm package built by wasm-pack and the network to start from:
+//!
+//! - `/` is the page.
+//! - `/convdim.js` is the JavaScript module and `/convdim_bg.wasm` the WebAssembly build.
+//! - `/network.json` holds the `network`, with the structure of the toml description, and the
+//!   `input_dim` to start from.
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+use super::server::{read_head, TIMEOUT};
+use convdim::convert::json;
+use convdim::error::ConvDimError;
+use convdim::layers::Layers;
+
+/// The page of the playground.
+const PAGE: &str = include_str!("../../js/playground.html");
+
+/// The network the playground starts from when no description is given, a small classifier stem.
+pub const EXAMPLE: &str = r#"input_channels = 3
+
+[[layers]]
+name = "stem"
+type = "conv"
+filter_size = 7
+stride = 2
+padding = 3
+channels = 64
+
+[[layers]]
+name = "pool"
+type = "max_pool"
+filter_size = 3
+stride = 2
+padding = 1
+
+[[layers]]
+name = "conv"
+type = "conv"
+filter_size = 3
+stride = 1
+padding = 1
+channels = 128
+"#;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## The response to a request, a file of type `content_type`.
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// The response of status 200 holding `body`.
+    fn ok(content_type: &'static str, body: impl Into<Vec<u8>>) -> Response {
+        Response {
+            status: 200,
+            content_type,
+            body: body.into(),
+        }
+    }
+
+    /// The response of `status` holding the plain text `message`.
+    fn error(status: u16, message: impl ToString) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: (message.to_string() + "\n").into_bytes(),
+        }
+    }
+
+    /// The reason phrase of the status.
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## The files served by the playground.
+pub struct Playground {
+    /// The document of `/network.json`.
+    pub network: String,
+    /// The JavaScript module of the WebAssembly build.
+    pub module: String,
+    /// The WebAssembly build of the library.
+    pub wasm: Vec<u8>,
+}
+
+impl Playground {
+    /// ## The playground starting from `layers` for an input of dimension `in_dim`.
+    ///
+    /// `module` and `wasm` are the files `convdim.js` and `convdim_bg.wasm` of the npm package
+    /// built by wasm-pack.
+    pub fn new(
+        layers: &Layers,
+        in_dim: u32,
+        module: String,
+        wasm: Vec<u8>,
+    ) -> Result<Playground, ConvDimError> {
+        let network = toml::Value::try_from(layers).map_err(|e| ConvDimError::Parse {
+            message: e.to_string(),
+        })?;
+        let mut document = toml::value::Table::new();
+        document.insert("network".to_string(), network);
+        document.insert("input_dim".to_string(), toml::Value::Integer(in_dim.into()));
+        Ok(Playground {
+            network: json(&toml::Value::Table(document), 0) + "\n",
+            module,
+            wasm,
+        })
+    }
+
+    /// ## The response to the request for `path` with `method`.
+    pub fn handle(&self, method: &str, path: &str) -> Response {
+        let path = path.split('?').next().unwrap_or(path);
+        let response = match path {
+            "/" | "/index.html" => Response::ok("text/html; charset=utf-8", PAGE),
+            "/convdim.js" => Response::ok("text/javascript; charset=utf-8", self.module.clone()),
+            "/convdim_bg.wasm" => Response::ok("application/wasm", self.wasm.clone()),
+            "/network.json" => Response::ok("application/json", self.network.clone()),
+            _ => return Response::error(404, format!("There is no file '{}'.", path)),
+        };
+        match method {
+            "GET" => response,
+            _ => Response::error(405, format!("'{}' only accepts GET requests.", path)),
+        }
+    }
+
+    /// Read the request from `stream` and write the response to it.
+    fn respond(&self, stream: &TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let mut reader = BufReader::new(stream.take(64 << 10));
+        let (method, path, _) = read_head(&mut reader)?;
+        let response = self.handle(&method, &path);
+        let mut stream = stream;
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n",
+            response.status,
+            response.reason(),
+            response.content_type,
+            response.body.len(),
+        )?;
+        stream.write_all(&response.body)?;
+        stream.flush()
+    }
+}
+
+/// ## Serve `playground` to the connections accepted by `listener`, each on its own thread.
+///
+/// Failing connections are closed without affecting the others, so the server runs until the
+/// process is stopped.
+pub fn serve(playground: Playground, listener: TcpListener) {
+    let playground = Arc::new(playground);
+    for stream in listener.incoming().flatten() {
+        let playground = Arc::clone(&playground);
+        std::thread::spawn(move || {
+            let _ = playground.respond(&stream);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use convdim::architecture::{parse_architecture, Architecture};
+
+    #[test]
+    fn test_playground() {
+        let layers = match parse_architecture(EXAMPLE) {
+            Ok(Architecture::Layers(layers)) => layers,
+            _ => unreachable!(),
+        };
+        let module = "export default async function init() {}".to_string();
+        let playground = Playground::new(&layers, 64, module, vec![0, 97, 115, 109]).unwrap();
+        let network = playground.handle("GET", "/network.json");
+        assert_eq!(network.content_type, "application/json");
+        let network = String::from_utf8(network.body).unwrap();
+        assert!(network.contains("\"input_dim\": 64"));
+        assert!(network.contains("\"name\": \"stem\""));
+
+        assert_eq!(
+            playground.handle("GET", "/convdim_bg.wasm").body,
+            [0, 97, 115, 109]
+        );
+        assert!(
+            String::from_utf8(playground.handle("GET", "/convdim.js").body)
+                .unwrap()
+                .starts_with("export default")
+        );
+        assert!(String::from_utf8(playground.handle("GET", "/").body)
+            .unwrap()
+            .contains("from \"./convdim.js\""));
+        assert_eq!(playground.handle("POST", "/").status, 405);
+        assert_eq!(playground.handle("GET", "/eval").status, 404);
+    }
+}
@@ -0,0 +1,225 @@
+//! An interactive wizard assembling a network from a few answers, e.g. for students who don't yet
+//! know the arithmetic of convolutions.
+//!
+//! The wizard asks for the number of spatial dimensions, the input size, the downsampling factor
+//! and the output size, and validates every answer before it asks the next question. The network
+//! halves its input with a convolution of size 3, stride 2 and padding 1 for every factor of 2 of
+//! the downsampling, doubling the channels up to 512, and reaches a smaller output size with a
+//! final convolution without padding.
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use convdim::error::ConvDimError;
+use convdim::generate::Block;
+use convdim::layers::{ConvParams, Entry, Layer, Layers};
+use convdim::summary;
+use convdim::Architecture;
+
+/// The channels of the first convolution, which double with every convolution up to 512.
+const CHANNELS: u32 = 32;
+
+fn invalid(message: String) -> ConvDimError {
+    ConvDimError::Generate { message }
+}
+
+/// The size of the output after downsampling an input of size `input` by `factor`.
+fn downsampled(input: u32, factor: u32) -> u32 {
+    input.div_ceil(factor)
+}
+
+/// ## The network with `dimensions` spatial dimensions from `input` to `output` downsampling by `factor`.
+pub fn network(
+    dimensions: u8,
+    input: u32,
+    factor: u32,
+    output: u32,
+) -> Result<Block, ConvDimError> {
+    if !factor.is_power_of_two() {
+        return Err(invalid(format!(
+            "the downsampling factor {} is not a power of 2.",
+            factor
+        )));
+    }
+    let downsampled = downsampled(input, factor);
+    if output == 0 || output > downsampled {
+        return Err(invalid(format!(
+            "the output size needs to be between 1 and {}, the input size {} downsampled by {}.",
+            downsampled, input, factor
+        )));
+    }
+    let conv = |i: u32, filter_size, stride, padding| {
+        Entry::Layer(Layer::Conv(ConvParams {
+            name: None,
+            filter_size,
+            stride,
+            padding,
+            channels: Some((CHANNELS << i.min(4)) as u16),
+        }))
+    };
+    let stages = factor.trailing_zeros();
+    let mut entries: Vec<Entry> = (0..stages).map(|i| conv(i, 3, 2, 1)).collect();
+    if output < downsampled {
+        let filter_size = u16::try_from(downsampled - output + 1).map_err(|_| {
+            invalid(format!(
+                "the output size needs to be at least {} to be reached by a single convolution.",
+                downsampled + 1 - u32::from(u16::MAX)
+            ))
+        })?;
+        entries.push(conv(stages.saturating_sub(1), filter_size, 1, 0));
+    }
+    let layers = Layers {
+        input_channels: None,
+        outputs: vec![],
+        layers: entries,
+    };
+    let graph = Architecture::Layers(layers.clone()).into_graph();
+    let receptive_field = match summary::rows(&graph, input)?.last() {
+        Some(row) => row.receptive_field,
+        None => 1,
+    };
+    Ok(Block {
+        layers,
+        receptive_field,
+        description: format!(
+            "{}D network from {} to {}, downsampling by {}.",
+            dimensions, input, output, factor
+        ),
+    })
+}
+
+/// Ask the `question` until its answer is valid, returning `None` once the answers end.
+///
+/// An empty answer takes the `default`, if there is one.
+fn ask<T>(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+    default: Option<String>,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> io::Result<Option<T>> {
+    loop {
+        match &default {
+            Some(default) => write!(output, "{} [{}]: ", question, default)?,
+            None => write!(output, "{}: ", question)?,
+        }
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let answer = match (line.trim(), &default) {
+            ("", Some(default)) => default.clone(),
+            (answer, _) => answer.to_string(),
+        };
+        match parse(&answer) {
+            Ok(value) => return Ok(Some(value)),
+            Err(message) => writeln!(output, "  {}", message)?,
+        }
+    }
+}
+
+/// A positive integer.
+fn positive(answer: &str) -> Result<u32, String> {
+    match answer.parse() {
+        Ok(0) | Err(_) => Err(format!("'{}' is not a positive integer.", answer)),
+        Ok(value) => Ok(value),
+    }
+}
+
+/// ## Ask the questions on `output` and read the answers from `input`.
+///
+/// Returns the network and the path of the file to write it to, or `None` if the answers end
+/// before the network is complete.
+pub fn run(
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<Option<(Block, PathBuf)>> {
+    let (input, output) = (&mut input, &mut output);
+    let dimensions = ask(
+        input,
+        output,
+        "Spatial dimensions (1, 2 or 3)",
+        Some("2".to_string()),
+        |answer| match answer {
+            "1" | "1d" | "1D" => Ok(1),
+            "2" | "2d" | "2D" => Ok(2),
+            "3" | "3d" | "3D" => Ok(3),
+            _ => Err(format!("'{}' is not 1, 2 or 3.", answer)),
+        },
+    )?;
+    let dimensions = match dimensions {
+        Some(dimensions) => dimensions,
+        None => return Ok(None),
+    };
+    let in_size = match ask(input, output, "Input size", None, positive)? {
+        Some(size) => size,
+        None => return Ok(None),
+    };
+    let factor = ask(
+        input,
+        output,
+        "Downsampling factor (a power of 2)",
+        Some(8.min(in_size.next_power_of_two()).to_string()),
+        |answer| {
+            let factor = positive(answer)?;
+            network(dimensions, in_size, factor, downsampled(in_size, factor))
+                .map(|_| factor)
+                .map_err(|e| e.to_string())
+        },
+    )?;
+    let factor = match factor {
+        Some(factor) => factor,
+        None => return Ok(None),
+    };
+    let block = ask(
+        input,
+        output,
+        "Output size",
+        Some(downsampled(in_size, factor).to_string()),
+        |answer| network(dimensions, in_size, factor, positive(answer)?).map_err(|e| e.to_string()),
+    )?;
+    let block = match block {
+        Some(block) => block,
+        None => return Ok(None),
+    };
+    let path = ask(
+        input,
+        output,
+        "File to write",
+        Some("layers.toml".to_string()),
+        |answer| Ok(PathBuf::from(answer)),
+    )?;
+    Ok(path.map(|path| (block, path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network() {
+        let block = network(2, 100, 8, 10).unwrap();
+        assert_eq!(block.layers.layers.len(), 4);
+        assert!(network(2, 100, 8, 14).is_err());
+    }
+
+    #[test]
+    fn test_wizard() {
+        let answers = "2\n0\n100\n6\n\n14\n10\nnet.toml\n";
+        let mut questions = vec![];
+        let (block, path) = run(answers.as_bytes(), &mut questions).unwrap().unwrap();
+        assert_eq!(path, PathBuf::from("net.toml"));
+        assert_eq!(block.layers.layers.len(), 4);
+        let graph = Architecture::Layers(block.layers).into_graph();
+        assert_eq!(graph.output_shape(100).unwrap().dim, 10);
+
+        let questions = String::from_utf8(questions).unwrap();
+        assert!(questions.contains("Input size:   '0' is not a positive integer.\nInput size: "));
+        assert!(questions.contains("Downsampling factor (a power of 2) [8]: "));
+        assert!(questions.contains("6 is not a power of 2."));
+        assert!(questions.contains("between 1 and 13"));
+
+        assert!(run("2\n".as_bytes(), Vec::new()).unwrap().is_none());
+    }
+}
@@ -0,0 +1,233 @@
+//! The arithmetic of convolutional layers as `const fn`s, to check shapes at compile time.
+//!
+//! The functions mirror [`conv_output_dim`](crate::conv_output_dim) and
+//! [`transposed_conv_output_dim`](crate::transposed_conv_output_dim) for `usize`, the type of the
+//! dimensions in const generics. Their errors are a [`ShapeError`], which unlike a
+//! [`ConvDimError`](crate::ConvDimError) holds no heap data and can be matched in constants.
+//! [`unwrap`] turns an error into a compile error when it is evaluated in a constant.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use convdim::constant::{conv_output_dim, unwrap};
+//!
+//! const FEATURES: usize = unwrap(conv_output_dim(28, 5, 0, 1, 2));
+//! let features = [0.0f32; FEATURES];
+//! assert_eq!(features.len(), 20);
+//! ```
+//!
+//! ```compile_fail
+//! use convdim::constant::{conv_output_dim, unwrap};
+//!
+//! // The kernel is larger than the input, which fails the build.
+//! const FEATURES: usize = unwrap(conv_output_dim(2, 5, 0, 1, 1));
+//! ```
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// ## The reason why a layer cannot be applied to its input.
+pub enum ShapeError {
+    /// The filter is larger than the padded input.
+    KernelLargerThanInput,
+    /// The input of a transposed convolution is empty.
+    EmptyInput,
+    /// The padding of a transposed convolution exceeds its output.
+    NegativeOutput,
+    /// The stride is zero.
+    ZeroStride,
+    /// The filter size is zero.
+    ZeroFilterSize,
+    /// The output or an intermediate value exceeds the range of `usize`.
+    Overflow,
+}
+
+impl ShapeError {
+    /// The description of the error.
+    pub const fn message(self) -> &'static str {
+        match self {
+            ShapeError::KernelLargerThanInput => "the filter is larger than the padded input",
+            ShapeError::EmptyInput => "the input of the transposed convolution is empty",
+            ShapeError::NegativeOutput => "the padding exceeds the output",
+            ShapeError::ZeroStride => "the stride is zero",
+            ShapeError::ZeroFilterSize => "the filter size is zero",
+            ShapeError::Overflow => "the dimension overflows",
+        }
+    }
+}
+
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for ShapeError {}
+
+/// ## Compute the output dimension of a convolutional layer applied `repeat` times.
+///
+/// o = (n + 2*p - f) / s + 1, see [`conv_output_dim`](crate::conv_output_dim).
+pub const fn conv_output_dim(
+    in_dim: usize,
+    filter_size: usize,
+    padding: usize,
+    stride: usize,
+    repeat: u32,
+) -> Result<usize, ShapeError> {
+    if stride == 0 {
+        return Err(ShapeError::ZeroStride);
+    }
+    if filter_size == 0 {
+        return Err(ShapeError::ZeroFilterSize);
+    }
+    let twice = match padding.checked_mul(2) {
+        Some(twice) => twice,
+        None => return Err(ShapeError::Overflow),
+    };
+    let mut dim = in_dim;
+    let mut remaining = repeat;
+    loop {
+        let padded = match dim.checked_add(twice) {
+            Some(padded) => padded,
+            None => return Err(ShapeError::Overflow),
+        };
+        let difference = match padded.checked_sub(filter_size) {
+            Some(difference) => difference,
+            None => return Err(ShapeError::KernelLargerThanInput),
+        };
+        if remaining == 0 {
+            return Ok(dim);
+        }
+        // The filter size is positive, so the difference is below the maximum and the output fits.
+        dim = difference / stride + 1;
+        remaining -= 1;
+        if remaining == 0 {
+            return Ok(dim);
+        }
+    }
+}
+
+/// ## Compute the output dimension of a transposed convolutional layer applied `repeat` times.
+///
+/// o = (n - 1) * s + f - 2*p, see [`transposed_conv_output_dim`](crate::transposed_conv_output_dim).
+pub const fn transposed_conv_output_dim(
+    in_dim: usize,
+    filter_size: usize,
+    padding: usize,
+    stride: usize,
+    repeat: u32,
+) -> Result<usize, ShapeError> {
+    if in_dim == 0 {
+        return Err(ShapeError::EmptyInput);
+    }
+    if stride == 0 {
+        return Err(ShapeError::ZeroStride);
+    }
+    if filter_size == 0 {
+        return Err(ShapeError::ZeroFilterSize);
+    }
+    let mut dim = in_dim;
+    let mut remaining = repeat;
+    while remaining > 0 {
+        let full = match (dim - 1).checked_mul(stride) {
+            Some(scattered) => match scattered.checked_add(filter_size) {
+                Some(full) => full,
+                None => return Err(ShapeError::Overflow),
+            },
+            None => return Err(ShapeError::Overflow),
+        };
+        let twice = match padding.checked_mul(2) {
+            Some(twice) => twice,
+            None => return Err(ShapeError::Overflow),
+        };
+        dim = match full.checked_sub(twice) {
+            Some(dim) => dim,
+            None => return Err(ShapeError::NegativeOutput),
+        };
+        if dim == 0 && remaining > 1 {
+            return Err(ShapeError::EmptyInput);
+        }
+        remaining -= 1;
+    }
+    Ok(dim)
+}
+
+/// ## The dimension of `result`, panicking with the message of its error.
+///
+/// In a constant, the panic fails the build with the message of the error.
+pub const fn unwrap(result: Result<usize, ShapeError>) -> usize {
+    match result {
+        Ok(dim) => dim,
+        Err(ShapeError::KernelLargerThanInput) => {
+            panic!("the filter is larger than the padded input")
+        }
+        Err(ShapeError::EmptyInput) => panic!("the input of the transposed convolution is empty"),
+        Err(ShapeError::NegativeOutput) => panic!("the padding exceeds the output"),
+        Err(ShapeError::ZeroStride) => panic!("the stride is zero"),
+        Err(ShapeError::ZeroFilterSize) => panic!("the filter size is zero"),
+        Err(ShapeError::Overflow) => panic!("the dimension overflows"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POOLED: usize = unwrap(conv_output_dim(64, 2, 0, 2, 3));
+    const _: () = assert!(POOLED == 8);
+    const _: () = assert!(matches!(
+        transposed_conv_output_dim(0, 2, 0, 2, 1),
+        Err(ShapeError::EmptyInput)
+    ));
+
+    #[test]
+    fn test_agrees_with_the_generic_functions() {
+        for in_dim in 0..12usize {
+            for filter_size in 0..5 {
+                for padding in 0..3 {
+                    for stride in 0..4 {
+                        for repeat in 0..3 {
+                            let args = (in_dim, filter_size, padding, stride, repeat);
+                            assert_eq!(
+                                conv_output_dim(in_dim, filter_size, padding, stride, repeat).ok(),
+                                crate::conv_output_dim(
+                                    in_dim,
+                                    filter_size,
+                                    padding,
+                                    stride,
+                                    repeat
+                                )
+                                .ok(),
+                                "{:?}",
+                                args
+                            );
+                            assert_eq!(
+                                transposed_conv_output_dim(
+                                    in_dim,
+                                    filter_size,
+                                    padding,
+                                    stride,
+                                    repeat
+                                )
+                                .ok(),
+                                crate::transposed_conv_output_dim(
+                                    in_dim,
+                                    filter_size,
+                                    padding,
+                                    stride,
+                                    repeat
+                                )
+                                .ok(),
+                                "{:?}",
+                                args
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            conv_output_dim(usize::MAX, 3, 1, 1, 1),
+            Err(ShapeError::Overflow)
+        );
+    }
+}
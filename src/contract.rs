@@ -0,0 +1,139 @@
+//! Shape contracts, the output shapes a network is expected to produce, e.g. for serving or
+//! data-pipeline code validating its tensors at runtime.
+//!
+//! The contract of a network for an input dimension maps the names of its inputs, outputs and
+//! layers to their shapes, i.e. their height, width and channels if they are known. It is written
+//! as YAML, JSON or toml, which have the same structure, so it can be loaded with the standard
+//! parsers of these formats.
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::convert::{self, Target};
+use crate::error::ConvDimError;
+use crate::graph::{Graph, Shape};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+/// ## The expected shape of a tensor, without the batch dimension.
+pub struct Expected {
+    pub height: u32,
+    pub width: u32,
+    /// The number of channels, which is left out if it is unknown.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u16>,
+}
+
+impl From<Shape> for Expected {
+    fn from(shape: Shape) -> Expected {
+        Expected {
+            height: shape.dim,
+            width: shape.dim,
+            channels: shape.channels,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The shapes of the inputs, outputs and layers of a network for an input dimension.
+///
+/// The shapes are keyed by the names of the inputs and nodes. The outputs are among the layers
+/// too, so that the contract of a tap doesn't depend on whether it is an output.
+pub struct Contract {
+    pub input_dim: u32,
+    pub inputs: BTreeMap<String, Expected>,
+    pub outputs: BTreeMap<String, Expected>,
+    pub layers: BTreeMap<String, Expected>,
+}
+
+impl Contract {
+    /// ## Write the contract in the format `target`.
+    pub fn write(&self, target: Target) -> String {
+        match toml::Value::try_from(self) {
+            Ok(toml::Value::Table(table)) => convert::write_table(&table, target),
+            Ok(_) => unreachable!(),
+            Err(e) => panic!("Unable to convert the contract: {}", e),
+        }
+    }
+}
+
+/// ## The contract of `graph` for an input of dimension `in_dim`.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{contract, convert::Target, Architecture, Network};
+/// let network = Network::builder().input_channels(3).conv(3, 2, 1).channels(8).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let contract = contract::contract(&graph, 32).unwrap();
+/// assert_eq!(contract.outputs["layer1"].height, 16);
+/// assert!(contract.write(Target::Yaml).contains("outputs:\n  layer1:\n    channels: 8\n"));
+/// ```
+pub fn contract(graph: &Graph, in_dim: u32) -> Result<Contract, ConvDimError> {
+    let expected = |shapes: Vec<(String, Shape)>| {
+        shapes
+            .into_iter()
+            .map(|(name, shape)| (name, Expected::from(shape)))
+            .collect()
+    };
+    let inputs = graph
+        .input_shapes(in_dim)
+        .into_iter()
+        .map(|(name, shape)| (name.to_string(), shape))
+        .collect();
+    Ok(Contract {
+        input_dim: in_dim,
+        inputs: expected(inputs),
+        outputs: expected(graph.output_shapes(in_dim)?),
+        layers: expected(graph.shapes(in_dim)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_contract() {
+        let graph = parse_architecture(
+            r#"
+            [[inputs]]
+            name = "image"
+            channels = 3
+
+            [[nodes]]
+            name = "stem"
+            op = "conv"
+            filter_size = 3
+            stride = 2
+            channels = 16
+
+            [[nodes]]
+            name = "head"
+            op = "relu"
+            inputs = ["stem"]
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let contract = contract(&graph, 65).unwrap();
+        let stem = Expected {
+            height: 32,
+            width: 32,
+            channels: Some(16),
+        };
+        assert_eq!(contract.layers["stem"], stem);
+        assert_eq!(contract.outputs.keys().collect::<Vec<_>>(), ["head"]);
+        assert_eq!(
+            contract.write(Target::Yaml),
+            "outputs:\n  head:\n    channels: 16\n    height: 32\n    width: 32\n\
+             inputs:\n  image:\n    channels: 3\n    height: 65\n    width: 65\n\
+             input_dim: 65\n\
+             layers:\n  head:\n    channels: 16\n    height: 32\n    width: 32\n  \
+             stem:\n    channels: 16\n    height: 32\n    width: 32\n"
+        );
+        let toml = contract.write(Target::Toml);
+        let value: toml::Value = toml::from_str(&toml).unwrap();
+        assert_eq!(value["layers"]["stem"]["height"].as_integer(), Some(32));
+    }
+}
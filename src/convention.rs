@@ -0,0 +1,204 @@
+//! The conventions of the deep learning frameworks for the output dimensions of their layers, e.g.
+//! to compute the dimensions the way Caffe would.
+//!
+//! By default, `convdim` computes the dimensions like PyTorch and ONNX with explicit pads: the
+//! outputs of convolutions and poolings are rounded down, and transposed convolutions crop their
+//! padding off the output. The other conventions differ in their rounding and padding rules:
+//!
+//! - Caffe rounds the outputs of poolings up, as `ceil((n + 2*p - f) / s) + 1`, and drops the
+//!   last output if its window would start in the padding.
+//! - TensorFlow has no explicit padding, so padded layers are taken to use 'SAME' padding, whose
+//!   output dimension is `ceil(n / s)` (resp. `n * s` for transposed convolutions), and the others
+//!   'VALID' padding. Transposed convolutions with 'VALID' padding output `n * s + max(f - s, 0)`.
+//!
+//! A convention is applied by giving the (transposed) convolutions and poolings the formula of
+//! their output dimension, and the poolings rounding up the flag of their ceil mode. Layers that
+//! already have a formula keep it. The layers have no dilation, since dilated filters are
+//! described by their effective size, which is the same in all the frameworks. In graphs, poolings are convolutions, so only the poolings of lists of
+//! layers are rounded up by Caffe.
+use crate::architecture::Architecture;
+use crate::formula::Formula;
+use crate::graph::Op;
+use crate::layers::{ConvParams, ConvTransposeParams, Entry, FormulaParams, Layer, PoolParams};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## A framework whose rules for the output dimensions are followed.
+pub enum Convention {
+    PyTorch,
+    TensorFlow,
+    Caffe,
+    Onnx,
+}
+
+impl std::str::FromStr for Convention {
+    type Err = String;
+
+    fn from_str(convention: &str) -> Result<Convention, String> {
+        match convention {
+            "pytorch" => Ok(Convention::PyTorch),
+            "tensorflow" => Ok(Convention::TensorFlow),
+            "caffe" => Ok(Convention::Caffe),
+            "onnx" => Ok(Convention::Onnx),
+            _ => Err(format!(
+                "Unknown convention '{}', expected 'pytorch', 'tensorflow', 'caffe' or 'onnx'.",
+                convention
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## The kinds of layers whose output dimensions depend on the convention.
+pub enum Kind {
+    Conv,
+    Pool,
+    Transposed,
+}
+
+impl Convention {
+    /// ## The formula of the output dimension of a layer of `kind` with `padding`.
+    ///
+    /// Returns `None` if the convention computes the output dimension like `convdim` does.
+    pub fn formula(self, kind: Kind, padding: u16) -> Option<Formula> {
+        let formula = match (self, kind) {
+            (Convention::PyTorch, _) | (Convention::Onnx, _) => return None,
+            (Convention::Caffe, Kind::Pool) => {
+                "min(ceil((n + 2*p - f) / s) + 1, ceil((n + p) / s))"
+            }
+            (Convention::Caffe, _) => return None,
+            (Convention::TensorFlow, Kind::Transposed) if padding > 0 => "n * s",
+            (Convention::TensorFlow, Kind::Transposed) => "n * s + max(f - s, 0)",
+            (Convention::TensorFlow, _) if padding > 0 => "ceil(n / s)",
+            // 'VALID' padding rounds down like `convdim`.
+            (Convention::TensorFlow, _) => return None,
+        };
+        Some(Formula::parse(formula).expect("The formulas of the conventions are valid."))
+    }
+
+    /// ## `architecture` with the output dimensions of its layers computed by the convention.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use convdim::{convention::Convention, Architecture, Network};
+    /// let network = Network::builder().maxpool(3, 2).build();
+    /// let caffe = Convention::Caffe.apply(Architecture::Layers(network.layers().clone()));
+    /// assert_eq!(caffe.into_graph().output_shape(112).unwrap().dim, 56);
+    /// let pytorch = Convention::PyTorch.apply(Architecture::Layers(network.layers().clone()));
+    /// assert_eq!(pytorch.into_graph().output_shape(112).unwrap().dim, 55);
+    /// ```
+    pub fn apply(self, architecture: Architecture) -> Architecture {
+        match architecture {
+            Architecture::Layers(mut layers) => {
+                self.apply_entries(&mut layers.layers);
+                Architecture::Layers(layers)
+            }
+            Architecture::Graph(mut graph) => {
+                for node in graph.nodes.iter_mut().filter(|node| node.formula.is_none()) {
+                    let kind = match node.op {
+                        Op::Conv => Kind::Conv,
+                        Op::Transposed => Kind::Transposed,
+                        _ => continue,
+                    };
+                    node.formula = self.formula(kind, node.padding);
+                }
+                Architecture::Graph(graph)
+            }
+        }
+    }
+
+    /// Apply the convention to the layers of `entries` and of their parallel branches.
+    fn apply_entries(self, entries: &mut [Entry]) {
+        for entry in entries {
+            match entry {
+                Entry::Layer(layer) => {
+                    if let Some(replaced) = self.apply_layer(layer) {
+                        *layer = replaced;
+                    }
+                }
+                Entry::Parallel { parallel } => {
+                    for branch in parallel {
+                        self.apply_entries(&mut branch.layers);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The layer of the formula replacing `layer`, if the convention computes its output dimension
+    /// differently.
+    fn apply_layer(self, layer: &Layer) -> Option<Layer> {
+        let (kind, name, channels) = match layer {
+            Layer::Conv(ConvParams { name, channels, .. }) => (Kind::Conv, name, *channels),
+            Layer::ConvTranspose(ConvTransposeParams { name, channels, .. }) => {
+                (Kind::Transposed, name, *channels)
+            }
+            Layer::MaxPool(PoolParams { name, .. }) => (Kind::Pool, name, None),
+            _ => return None,
+        };
+        let (filter_size, stride, padding) = layer.geometry();
+        Some(Layer::Formula(FormulaParams {
+            name: name.clone(),
+            formula: self.formula(kind, padding)?,
+            filter_size,
+            stride,
+            padding,
+            transposed: kind == Kind::Transposed,
+            channels,
+            ceil_mode: self == Convention::Caffe && kind == Kind::Pool,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_conventions() {
+        let architecture = || {
+            parse_architecture(
+                r#"
+                [[layers]]
+                type = "conv"
+                filter_size = 3
+                stride = 2
+                padding = 1
+                channels = 8
+
+                [[layers]]
+                type = "max_pool"
+                filter_size = 3
+                stride = 2
+                padding = 1
+
+                [[layers]]
+                type = "conv_transpose"
+                filter_size = 1
+                stride = 2
+                "#,
+            )
+            .unwrap()
+        };
+        let shapes = |convention: Convention| {
+            let graph = convention.apply(architecture()).into_graph();
+            graph
+                .shapes(10)
+                .unwrap()
+                .into_iter()
+                .map(|(_, shape)| shape.dim)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(shapes(Convention::PyTorch), [5, 3, 5]);
+        assert_eq!(shapes(Convention::Onnx), [5, 3, 5]);
+        // Caffe only differs where the windows of the pooling don't tile its padded input.
+        assert_eq!(shapes(Convention::Caffe), [5, 3, 5]);
+        assert_eq!(shapes(Convention::TensorFlow), [5, 3, 6]);
+
+        let graph = Convention::Caffe.apply(architecture()).into_graph();
+        assert_eq!(graph.shapes(12).unwrap()[1].1.dim, 4);
+        assert_eq!(graph.nodes[0].formula, None);
+        assert!("caffe2".parse::<Convention>().is_err());
+    }
+}
@@ -0,0 +1,480 @@
+//! Conversion of network descriptions between the formats of `convdim`.
+//!
+//! Networks are converted through the graph representation: the description is read like any
+//! other input (a toml file, a model file of a supported framework, or a description in JSON) and
+//! written as toml, JSON or YAML. The JSON and YAML descriptions have the same structure as the
+//! toml files, i.e. they consist of the `inputs`, `nodes` and `outputs` of the graph.
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::graph::Graph;
+use crate::import::yolo::strip_comment;
+use crate::json::parse_json;
+
+/// The order of the keys of the written descriptions, which is the order of the fields of the
+/// graph, inputs and nodes. Unknown keys follow in alphabetical order.
+const KEYS: &[&str] = &[
+    "input_channels",
+    "outputs",
+    "name",
+    "op",
+    "type",
+    "inputs",
+    "input_dim",
+    "dim",
+    "filter_size",
+    "stride",
+    "padding",
+    "formula",
+    "scale",
+    "transposed",
+    "channels",
+    "subnetwork",
+    "nodes",
+    "layers",
+    "parallel",
+    "subnetworks",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## The formats in which descriptions can be written.
+pub enum Target {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl Target {
+    /// The format of the description at `path`, recognized by its extension.
+    pub fn from_path(path: &Path) -> Result<Target, String> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Ok(Target::Toml),
+            Some("json") => Ok(Target::Json),
+            Some("yaml") | Some("yml") => Ok(Target::Yaml),
+            _ => Err(format!(
+                "Unknown output format of '{}', expected a .toml, .json or .yaml file.",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// The members of `table` in the order of [`KEYS`].
+fn members(table: &toml::value::Table) -> Vec<(&String, &toml::Value)> {
+    let rank = |key: &str| KEYS.iter().position(|k| *k == key).unwrap_or(KEYS.len());
+    let mut members: Vec<(&String, &toml::Value)> = table.iter().collect();
+    members.sort_by_key(|(key, _)| rank(key));
+    members
+}
+
+/// ## `text` as a JSON string.
+pub fn string(text: &str) -> String {
+    let mut string = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => string += "\\\"",
+            '\\' => string += "\\\\",
+            '\n' => string += "\\n",
+            '\r' => string += "\\r",
+            '\t' => string += "\\t",
+            c if (c as u32) < 0x20 => string += &format!("\\u{:04x}", c as u32),
+            c => string.push(c),
+        }
+    }
+    string + "\""
+}
+
+/// A scalar `value`, which is written the same in JSON and YAML.
+fn scalar(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(text) => string(text),
+        toml::Value::Integer(n) => n.to_string(),
+        toml::Value::Float(x) => x.to_string(),
+        toml::Value::Boolean(b) => b.to_string(),
+        toml::Value::Datetime(datetime) => string(&datetime.to_string()),
+        toml::Value::Array(_) | toml::Value::Table(_) => unreachable!(),
+    }
+}
+
+/// ## Write `value` as JSON, indented by two spaces per level starting at `indent`.
+///
+/// The keys of descriptions come in the order of the fields of the graph, inputs and nodes.
+pub fn json(value: &toml::Value, indent: usize) -> String {
+    let inner = " ".repeat(indent + 2);
+    match value {
+        toml::Value::Array(values) if values.is_empty() => "[]".to_string(),
+        toml::Value::Array(values) if values.iter().all(|v| !v.is_table() && !v.is_array()) => {
+            let values: Vec<String> = values.iter().map(scalar).collect();
+            format!("[{}]", values.join(", "))
+        }
+        toml::Value::Array(values) => {
+            let values: Vec<String> = values
+                .iter()
+                .map(|value| format!("{}{}", inner, json(value, indent + 2)))
+                .collect();
+            format!("[\n{}\n{}]", values.join(",\n"), " ".repeat(indent))
+        }
+        toml::Value::Table(table) if table.is_empty() => "{}".to_string(),
+        toml::Value::Table(table) => {
+            let members: Vec<String> = members(table)
+                .into_iter()
+                .map(|(key, value)| {
+                    format!("{}{}: {}", inner, string(key), json(value, indent + 2))
+                })
+                .collect();
+            format!("{{\n{}\n{}}}", members.join(",\n"), " ".repeat(indent))
+        }
+        value => scalar(value),
+    }
+}
+
+/// ## Write `value` as JSON on a single line, e.g. for JSON lines.
+pub fn compact_json(value: &toml::Value) -> String {
+    match value {
+        toml::Value::Array(values) => {
+            let values: Vec<String> = values.iter().map(compact_json).collect();
+            format!("[{}]", values.join(", "))
+        }
+        toml::Value::Table(table) => {
+            let members: Vec<String> = members(table)
+                .into_iter()
+                .map(|(key, value)| format!("{}: {}", string(key), compact_json(value)))
+                .collect();
+            format!("{{{}}}", members.join(", "))
+        }
+        value => scalar(value),
+    }
+}
+
+/// Write the members of `table` as YAML, indented by `indent` spaces.
+///
+/// The items of arrays of tables are written as block sequences, their first member on the line
+/// of the dash, and arrays of scalars in the flow style.
+fn yaml(table: &toml::value::Table, indent: usize) -> Vec<String> {
+    let prefix = " ".repeat(indent);
+    let mut lines = vec![];
+    for (key, value) in members(table) {
+        match value {
+            toml::Value::Table(table) => {
+                lines.push(format!("{}{}:", prefix, key));
+                lines.extend(yaml(table, indent + 2));
+            }
+            toml::Value::Array(values) if values.iter().any(toml::Value::is_table) => {
+                lines.push(format!("{}{}:", prefix, key));
+                for value in values {
+                    let item = match value {
+                        toml::Value::Table(table) => yaml(table, indent + 4),
+                        value => vec![format!("{}    {}", prefix, json(value, 0))],
+                    };
+                    for (i, line) in item.into_iter().enumerate() {
+                        if i == 0 {
+                            lines.push(format!("{}  - {}", prefix, &line[indent + 4..]));
+                        } else {
+                            lines.push(line);
+                        }
+                    }
+                }
+            }
+            value => lines.push(format!("{}{}: {}", prefix, key, json(value, 0))),
+        }
+    }
+    lines
+}
+
+/// `key` as a toml key, which is quoted unless it is a bare key.
+fn toml_key(key: &str) -> String {
+    let bare = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    match !key.is_empty() && key.chars().all(bare) {
+        true => key.to_string(),
+        false => string(key),
+    }
+}
+
+/// Write `value` as an inline toml value.
+fn toml_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::Float(x) if x.is_nan() => "nan".to_string(),
+        toml::Value::Float(x) => format!("{:?}", x),
+        toml::Value::Datetime(datetime) => datetime.to_string(),
+        toml::Value::Array(values) => {
+            let values: Vec<String> = values.iter().map(toml_value).collect();
+            format!("[{}]", values.join(", "))
+        }
+        toml::Value::Table(table) if table.is_empty() => "{}".to_string(),
+        toml::Value::Table(table) => {
+            let members: Vec<String> = members(table)
+                .into_iter()
+                .map(|(key, value)| format!("{} = {}", toml_key(key), toml_value(value)))
+                .collect();
+            format!("{{ {} }}", members.join(", "))
+        }
+        value => scalar(value),
+    }
+}
+
+/// Write the members of `table` as toml, as the table at the dotted `path`.
+///
+/// The plain values precede the tables and arrays of tables, which are written as sections, so
+/// all members are in the order of [`KEYS`] within their kind.
+pub(crate) fn toml(table: &toml::value::Table, path: &str) -> String {
+    let mut text = String::new();
+    let mut sections = String::new();
+    for (key, value) in members(table) {
+        let path = match path {
+            "" => toml_key(key),
+            path => format!("{}.{}", path, toml_key(key)),
+        };
+        match value {
+            toml::Value::Table(table) => {
+                sections += &format!("\n[{}]\n{}", path, toml(table, &path));
+            }
+            toml::Value::Array(values)
+                if !values.is_empty() && values.iter().all(toml::Value::is_table) =>
+            {
+                for table in values.iter().filter_map(toml::Value::as_table) {
+                    sections += &format!("\n[[{}]]\n{}", path, toml(table, &path));
+                }
+            }
+            value => text += &format!("{} = {}\n", toml_key(key), toml_value(value)),
+        }
+    }
+    match text.is_empty() && path.is_empty() {
+        true => sections.trim_start().to_string(),
+        false => text + &sections,
+    }
+}
+
+/// ## Write `graph` in the format `target`.
+pub fn write(graph: &Graph, target: Target) -> String {
+    let description = match (target, toml::Value::try_from(graph)) {
+        (Target::Toml, _) => return graph.to_toml(),
+        (_, Ok(toml::Value::Table(description))) => description,
+        (_, Ok(_)) => unreachable!(),
+        (_, Err(e)) => panic!("Unable to convert the network: {}", e),
+    };
+    write_table(&description, target)
+}
+
+/// Write `table` in the format `target`, with its keys in the order of [`KEYS`].
+pub(crate) fn write_table(table: &toml::value::Table, target: Target) -> String {
+    let value = toml::Value::Table(table.clone());
+    match target {
+        Target::Toml => toml::to_string(&value).expect("A table can be written as toml."),
+        Target::Json => json(&value, 0) + "\n",
+        Target::Yaml => yaml(table, 0).join("\n") + "\n",
+    }
+}
+
+/// ## Convert the JSON `value` into the value of a toml description.
+///
+/// Returns an error for `null`, which toml cannot represent.
+pub fn from_json(value: &Value) -> Result<toml::Value, String> {
+    match value {
+        Value::Null => Err("The description contains 'null', which is not supported.".to_string()),
+        Value::Bool(b) => Ok(toml::Value::Boolean(*b)),
+        Value::Number(number) => Ok(match (number.as_i64(), number.as_f64()) {
+            (Some(integer), _) => toml::Value::Integer(integer),
+            (None, Some(x)) => toml::Value::Float(x),
+            (None, None) => return Err(format!("The number {} is out of range.", number)),
+        }),
+        Value::String(text) => Ok(toml::Value::String(text.clone())),
+        Value::Array(values) => values
+            .iter()
+            .map(from_json)
+            .collect::<Result<Vec<toml::Value>, String>>()
+            .map(toml::Value::Array),
+        Value::Object(members) => members
+            .iter()
+            .map(|(key, value)| from_json(value).map(|value| (key.clone(), value)))
+            .collect::<Result<toml::value::Table, String>>()
+            .map(toml::Value::Table),
+    }
+}
+
+/// The key of the `line` of a YAML mapping and the rest of the line, if it has a key.
+fn yaml_key(line: &str) -> Option<(String, &str)> {
+    let (key, rest) = match line.strip_prefix('"') {
+        Some(quoted) => {
+            let end = quoted.find('"')?;
+            (
+                quoted[..end].to_string(),
+                quoted[end + 1..].strip_prefix(':')?,
+            )
+        }
+        None if line.starts_with(['[', '{', '\'']) => return None,
+        None => {
+            let (key, rest) = line.split_once(':')?;
+            (key.trim_end().to_string(), rest)
+        }
+    };
+    match rest.is_empty() || rest.starts_with(' ') {
+        true => Some((key, rest.trim())),
+        false => None,
+    }
+}
+
+/// The YAML scalar or flow sequence `text`, read as JSON or as a plain number, boolean or string.
+fn yaml_scalar(text: &str) -> Result<toml::Value, String> {
+    if let Ok(json) = parse_json(text) {
+        return from_json(&json);
+    }
+    Ok(match text {
+        "true" => toml::Value::Boolean(true),
+        "false" => toml::Value::Boolean(false),
+        _ => match (text.parse::<i64>(), text.parse::<f64>()) {
+            (Ok(n), _) => toml::Value::Integer(n),
+            (_, Ok(x)) => toml::Value::Float(x),
+            _ => toml::Value::String(text.to_string()),
+        },
+    })
+}
+
+/// Read the YAML block of `lines` starting at `pos`, whose lines are indented by `indent`.
+///
+/// The items of block sequences whose first member is on the line of the dash are read as
+/// mappings indented by the dash.
+fn yaml_block(
+    lines: &mut [(usize, String)],
+    pos: &mut usize,
+    indent: usize,
+) -> Result<toml::Value, String> {
+    if lines[*pos].1.starts_with('-') {
+        let mut values = vec![];
+        while *pos < lines.len() && lines[*pos].0 == indent && lines[*pos].1.starts_with('-') {
+            let rest = lines[*pos].1[1..].trim_start().to_string();
+            let offset = lines[*pos].1.len() - rest.len();
+            if rest.is_empty() {
+                *pos += 1;
+                match lines.get(*pos) {
+                    Some(&(next, _)) if next > indent => values.push(yaml_block(lines, pos, next)?),
+                    _ => return Err("An item of a sequence has no value.".to_string()),
+                }
+            } else if yaml_key(&rest).is_some() {
+                lines[*pos] = (indent + offset, rest);
+                values.push(yaml_block(lines, pos, indent + offset)?);
+            } else {
+                values.push(yaml_scalar(&rest)?);
+                *pos += 1;
+            }
+        }
+        return Ok(toml::Value::Array(values));
+    }
+
+    let mut table = toml::value::Table::new();
+    while *pos < lines.len() && lines[*pos].0 == indent && !lines[*pos].1.starts_with('-') {
+        let line = lines[*pos].1.clone();
+        let (key, rest) =
+            yaml_key(&line).ok_or_else(|| format!("Expected a key in the line '{}'.", line))?;
+        *pos += 1;
+        let value = if !rest.is_empty() {
+            yaml_scalar(rest)?
+        } else {
+            match lines.get(*pos) {
+                Some((next, text))
+                    if *next > indent || (*next == indent && text.starts_with('-')) =>
+                {
+                    let next = *next;
+                    yaml_block(lines, pos, next)?
+                }
+                _ => return Err(format!("The key '{}' has no value.", key)),
+            }
+        };
+        table.insert(key, value);
+    }
+    Ok(toml::Value::Table(table))
+}
+
+/// ## Convert the YAML `text` into the value of a toml description.
+///
+/// Only the subset of YAML written by [`write`] is read: block mappings and block sequences of
+/// JSON values, numbers, booleans and plain strings.
+pub fn from_yaml(text: &str) -> Result<toml::Value, String> {
+    let mut lines: Vec<(usize, String)> = text
+        .lines()
+        .map(strip_comment)
+        .filter(|line| !line.trim().is_empty() && line.trim() != "---")
+        .map(|line| {
+            let trimmed = line.trim();
+            (line.trim_end().len() - trimmed.len(), trimmed.to_string())
+        })
+        .collect();
+    if lines.is_empty() {
+        return Ok(toml::Value::Table(toml::value::Table::new()));
+    }
+    let mut pos = 0;
+    let indent = lines[0].0;
+    let value = yaml_block(&mut lines, &mut pos, indent)?;
+    match (lines.get(pos), value) {
+        (Some((_, line)), _) => Err(format!("Unexpected indentation of the line '{}'.", line)),
+        (None, toml::Value::Table(table)) => Ok(toml::Value::Table(table)),
+        (None, _) => Err("The description needs to be a mapping.".to_string()),
+    }
+}
+
+/// ## Whether the JSON `value` is a description of `convdim` rather than a model of a framework.
+pub fn is_description(value: &Value) -> bool {
+    value.get("nodes").is_some() || value.get("layers").is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAPH: &str = r#"
+        input_channels = 3
+        outputs = ["conv2"]
+
+        [[nodes]]
+        name = "conv1"
+        op = "conv"
+        filter_size = 3
+        padding = 1
+        channels = 8
+
+        [[nodes]]
+        name = "conv2"
+        op = "conv"
+        inputs = ["conv1"]
+        filter_size = 2
+        stride = 2
+        "#;
+
+    #[test]
+    fn test_json_round_trip() {
+        let graph: Graph = toml::from_str(GRAPH).unwrap();
+        let json = write(&graph, Target::Json);
+        assert!(json.starts_with(
+            "{\n  \"input_channels\": 3,\n  \"outputs\": [\"conv2\"],\n  \"nodes\": [\n    {\n      \"name\": \"conv1\",\n"
+        ));
+        let parsed = parse_json(&json).unwrap();
+        assert!(is_description(&parsed));
+        let value = from_json(&parsed).unwrap();
+        assert_eq!(value, toml::Value::try_from(&graph).unwrap());
+        let converted: Graph = value.try_into().unwrap();
+        assert_eq!(converted.to_toml(), graph.to_toml());
+    }
+
+    #[test]
+    fn test_yaml() {
+        let graph: Graph = toml::from_str(GRAPH).unwrap();
+        assert_eq!(
+            write(&graph, Target::Yaml),
+            r#"input_channels: 3
+outputs: ["conv2"]
+nodes:
+  - name: "conv1"
+    op: "conv"
+    inputs: ["input"]
+    filter_size: 3
+    padding: 1
+    channels: 8
+  - name: "conv2"
+    op: "conv"
+    inputs: ["conv1"]
+    filter_size: 2
+    stride: 2
+"#
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Terminal block diagrams of the layers of a network.
+//!
+//! Every input and node is a box, whose width is proportional to its output dimension and whose
+//! height can be proportional to its channels. The boxes are centered and stacked in the order in
+//! which the nodes are evaluated, so that the hourglass of an encoder-decoder or the pyramid of a
+//! classifier is drawn straight from the description. The branches and merges of graphs are not
+//! drawn, only the order of the nodes.
+use std::fmt;
+
+use crate::error::ConvDimError;
+use crate::graph::{Graph, Shape};
+
+/// The width of the box of the largest output, without its borders.
+pub const WIDTH: usize = 40;
+
+/// The largest number of rows inside a box, for the output with the most channels.
+const MAX_ROWS: u64 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## A block diagram of the outputs of successive layers.
+///
+/// The box of an output has at least one column and row inside. Without `channels`, every box has
+/// a single row; with it, the rows are proportional to the channels, and outputs whose channels
+/// are unknown have a single row.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::diagram::Diagram;
+/// # use convdim::graph::Shape;
+/// let blocks = vec![
+///     ("input".to_string(), Shape { dim: 8, channels: Some(3) }),
+///     ("conv".to_string(), Shape { dim: 4, channels: Some(16) }),
+/// ];
+/// let diagram = Diagram { blocks, width: 8, channels: false };
+/// assert_eq!(
+///     diagram.to_string(),
+///     "+--------+  input  8x8x3\n\
+///      |        |\n\
+///      +--------+\n     v\n  \
+///        +----+    conv   4x4x16\n  \
+///        |    |\n  \
+///        +----+\n"
+/// );
+/// ```
+pub struct Diagram {
+    pub blocks: Vec<(String, Shape)>,
+    pub width: usize,
+    pub channels: bool,
+}
+
+impl Diagram {
+    /// ## The diagram of the inputs and nodes of `graph` for an input of dimension `in_dim`.
+    pub fn new(graph: &Graph, in_dim: u32, channels: bool) -> Result<Diagram, ConvDimError> {
+        let mut blocks: Vec<(String, Shape)> = graph
+            .input_shapes(in_dim)
+            .into_iter()
+            .map(|(name, shape)| (name.to_string(), shape))
+            .collect();
+        blocks.extend(graph.shapes(in_dim)?);
+        Ok(Diagram {
+            blocks,
+            width: WIDTH,
+            channels,
+        })
+    }
+}
+
+impl fmt::Display for Diagram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let max_dim = self.blocks.iter().map(|(_, shape)| shape.dim).max();
+        let max_dim = u64::from(max_dim.unwrap_or(0).max(1));
+        let max_channels = self
+            .blocks
+            .iter()
+            .filter_map(|(_, shape)| shape.channels)
+            .max();
+        let max_channels = u64::from(max_channels.unwrap_or(0).max(1));
+        let name_width = self
+            .blocks
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let center = (self.width + 2) / 2;
+
+        for (i, (name, shape)) in self.blocks.iter().enumerate() {
+            if i > 0 {
+                writeln!(f, "{}v", " ".repeat(center))?;
+            }
+            // The inside of the box, rounded to the nearest column and up to the next row.
+            let inner = (u64::from(shape.dim) * self.width as u64 + max_dim / 2) / max_dim;
+            let inner = (inner as usize).clamp(1, self.width);
+            let rows = match (self.channels, shape.channels) {
+                (true, Some(channels)) => (u64::from(channels) * MAX_ROWS)
+                    .div_ceil(max_channels)
+                    .max(1),
+                _ => 1,
+            };
+            let indent = " ".repeat((self.width - inner) / 2);
+            let border = format!("{}+{}+", indent, "-".repeat(inner));
+            let size = match shape.channels {
+                Some(channels) => format!("{0}x{0}x{1}", shape.dim, channels),
+                None => format!("{0}x{0}", shape.dim),
+            };
+            // The label follows the widest box, so that the labels are aligned.
+            let padding = " ".repeat(self.width + 2 - border.chars().count() + 2);
+            writeln!(
+                f,
+                "{}{}{:width$}  {}",
+                border,
+                padding,
+                name,
+                size,
+                width = name_width
+            )?;
+            for _ in 0..rows {
+                writeln!(f, "{}|{}|", indent, " ".repeat(inner))?;
+            }
+            writeln!(f, "{}", border)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_diagram() {
+        let graph = parse_architecture(
+            r#"
+            input_channels = 3
+
+            [[layers]]
+            name = "down"
+            type = "conv"
+            filter_size = 2
+            stride = 2
+            channels = 12
+
+            [[layers]]
+            name = "up"
+            type = "conv_transpose"
+            filter_size = 2
+            stride = 2
+            channels = 3
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let mut diagram = Diagram::new(&graph, 16, true).unwrap();
+        diagram.width = 8;
+        assert_eq!(
+            diagram.to_string(),
+            "+--------+  input  16x16x3\n\
+             |        |\n\
+             +--------+\n     v\n  \
+               +----+    down   8x8x12\n  \
+               |    |\n  |    |\n  |    |\n  |    |\n  \
+               +----+\n     v\n\
+             +--------+  up     16x16x3\n\
+             |        |\n\
+             +--------+\n"
+        );
+    }
+}
@@ -0,0 +1,25 @@
+//! The integer types in which the dimensions of feature maps can be computed.
+use std::fmt::{Debug, Display};
+
+use num_traits::{PrimInt, Unsigned};
+
+/// ## An unsigned integer type holding dimensions, filter sizes, strides and paddings.
+///
+/// The arithmetic of the layers is generic over this trait, so that embedders can compute in
+/// the type of their own code, e.g. `u32` or `usize`. It is implemented for the unsigned
+/// [primitive integers](num_traits::PrimInt) of `num-traits`, whose values are reported as `u64`
+/// in the [`ConvDimError`](crate::ConvDimError)s, where values of `u128` beyond its range are
+/// reported as its maximum.
+pub trait Dimension: PrimInt + Unsigned + Debug + Display {
+    /// The value as a `u64`, or its maximum if the value exceeds it.
+    fn reported(self) -> u64 {
+        self.to_u64().unwrap_or(u64::MAX)
+    }
+
+    /// The value as an `i128`, to compute with signed values, or its maximum if the value exceeds it.
+    fn wide(self) -> i128 {
+        self.to_i128().unwrap_or(i128::MAX)
+    }
+}
+
+impl<T: PrimInt + Unsigned + Debug + Display> Dimension for T {}
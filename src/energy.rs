@@ -0,0 +1,203 @@
+//! Rough estimates of the energy of an inference, e.g. to compare hardware for edge devices.
+//!
+//! The energy is the sum of the energy of the computations, counted in multiply-accumulate
+//! operations (MACs, half of the FLOPs), and of the energy of the traffic to the DRAM. Every
+//! weight is read once, every output of a node is written once, and every input or output is read
+//! once by every node reading it. The weights and values are 32-bit floats.
+//!
+//! The default costs are those of 32-bit floats in 45 nm according to Horowitz, "Computing's
+//! energy problem (and what we can do about it)", ISSCC 2014: 4.6 pJ for a multiplication and an
+//! addition, and 640 pJ for reading 32 bits from DRAM.
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::graph::Graph;
+use crate::report::abbreviate;
+use crate::summary;
+use crate::trace::write_columns;
+
+/// The bytes of a weight or a value of the activations, which are assumed to be 32-bit floats.
+const BYTES_PER_VALUE: u64 = 4;
+
+/// The default energy of a MAC, in pJ.
+pub const PJ_PER_MAC: f64 = 4.6;
+
+/// The default energy of a byte of DRAM traffic, in pJ.
+pub const PJ_PER_BYTE: f64 = 160.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+/// ## The costs of the operations of an inference, in pJ.
+pub struct Costs {
+    pub pj_per_mac: f64,
+    pub pj_per_byte: f64,
+}
+
+impl Default for Costs {
+    fn default() -> Costs {
+        Costs {
+            pj_per_mac: PJ_PER_MAC,
+            pj_per_byte: PJ_PER_BYTE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+/// ## The estimated energy of an inference, and the operations it is estimated from.
+pub struct Energy {
+    /// The number of multiply-accumulate operations.
+    pub macs: u64,
+    /// The bytes read from and written to the DRAM.
+    pub traffic: u64,
+    /// The energy of the computations, in mJ.
+    pub compute: f64,
+    /// The energy of the DRAM traffic, in mJ.
+    pub memory: f64,
+}
+
+impl Energy {
+    /// The energy of an inference, in mJ.
+    pub fn total(&self) -> f64 {
+        self.compute + self.memory
+    }
+}
+
+impl fmt::Display for Energy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows: Vec<Vec<String>> = vec![
+            vec!["macs:".to_string(), abbreviate(self.macs as f64, "")],
+            vec!["traffic:".to_string(), abbreviate(self.traffic as f64, "B")],
+            vec!["compute:".to_string(), format!("{:.3} mJ", self.compute)],
+            vec!["memory:".to_string(), format!("{:.3} mJ", self.memory)],
+            vec!["total:".to_string(), format!("{:.3} mJ", self.total())],
+        ];
+        write_columns(f, &rows, &[1])
+    }
+}
+
+/// ## The energy of an inference of `graph` for an input of dimension `in_dim`.
+///
+/// Returns an error if a cost is negative or not finite, or if the channels of a layer are
+/// unknown, since its operations and memory are.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{energy, Architecture, Network};
+/// let network = Network::builder().input_channels(1).conv(1, 1, 0).channels(2).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let costs = energy::Costs { pj_per_mac: 1.0, pj_per_byte: 1e9 };
+/// let energy = energy::energy(&graph, 10, costs).unwrap();
+/// assert_eq!(energy.macs, 200);
+/// // The weights and biases, the input read once and the output written once.
+/// assert_eq!(energy.traffic, 4 * (4 + 100 + 200));
+/// assert!((energy.memory - 1216.0).abs() < 1e-6);
+/// ```
+pub fn energy(graph: &Graph, in_dim: u32, costs: Costs) -> Result<Energy, ConvDimError> {
+    for (cost, flag) in [
+        (costs.pj_per_mac, "pJ per MAC"),
+        (costs.pj_per_byte, "pJ per byte"),
+    ] {
+        if !cost.is_finite() || cost < 0.0 {
+            return Err(ConvDimError::Energy {
+                message: format!(
+                    "the cost of {} {} is not a finite, non-negative energy.",
+                    cost, flag
+                ),
+            });
+        }
+    }
+    let rows = summary::rows(graph, in_dim)?;
+    let edges = graph.edges();
+    let mut readers: HashMap<&str, u64> = HashMap::new();
+    for inputs in edges.values() {
+        for input in inputs {
+            *readers.entry(input).or_insert(0) += 1;
+        }
+    }
+
+    let (mut flops, mut values) = (0u64, 0u64);
+    for row in &rows {
+        let unknown = || ConvDimError::Energy {
+            message: format!(
+                "the channels of '{}' are unknown, so its operations and memory are too.",
+                row.name
+            ),
+        };
+        let activations = row.activations().ok_or_else(unknown)?;
+        let writes = u64::from(row.node.is_some());
+        let reads = readers.get(row.name).copied().unwrap_or(0);
+        flops = flops.saturating_add(row.flops.ok_or_else(unknown)?);
+        values = values
+            .saturating_add(row.params.ok_or_else(unknown)?)
+            .saturating_add(activations.saturating_mul(writes + reads));
+    }
+    let macs = flops / 2;
+    let traffic = values.saturating_mul(BYTES_PER_VALUE);
+    Ok(Energy {
+        macs,
+        traffic,
+        compute: macs as f64 * costs.pj_per_mac * 1e-9,
+        memory: traffic as f64 * costs.pj_per_byte * 1e-9,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_energy() {
+        let graph = parse_architecture(
+            r#"
+            input_channels = 1
+
+            [[nodes]]
+            name = "conv"
+            op = "conv"
+            filter_size = 3
+            padding = 1
+            channels = 1
+
+            [[nodes]]
+            name = "skip"
+            op = "add"
+            inputs = ["input", "conv"]
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let costs = Costs {
+            pj_per_mac: 1e6,
+            pj_per_byte: 1e6,
+        };
+        let energy = energy(&graph, 10, costs).unwrap();
+        // The convolution accumulates 9 products at every output, the addition adds them once.
+        assert_eq!(energy.macs, (2 * 9 * 100 + 100) / 2);
+        // The input is read twice, the output of the convolution written and read once, and
+        // the sum written once.
+        assert_eq!(energy.traffic, 4 * (10 + 2 * 100 + 2 * 100 + 100));
+        assert_eq!(
+            energy.to_string(),
+            "macs:          950\n\
+             traffic:    2.0 kB\n\
+             compute:  0.950 mJ\n\
+             memory:   2.040 mJ\n\
+             total:    2.990 mJ\n"
+        );
+
+        let mut unknown = graph.clone();
+        unknown.input_channels = None;
+        assert!(super::energy(&unknown, 10, costs).is_err());
+        for invalid in [-5.0, f64::NAN, f64::INFINITY] {
+            let costs = Costs {
+                pj_per_mac: invalid,
+                ..Costs::default()
+            };
+            assert!(super::energy(&graph, 10, costs).is_err());
+        }
+    }
+}
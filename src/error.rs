@@ -0,0 +1,364 @@
+//! The errors of the computations and of reading networks.
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## The layer an error occurred in.
+pub enum LayerRef {
+    /// A layer or node with a name.
+    Name(String),
+    /// An unnamed layer of a list of layers, by its (one-based) position like in the trace.
+    Position(usize),
+    /// A layer or node by its (one-based) position in the network and its name, if it has one,
+    /// with its parameters and the dimension of its input.
+    Located {
+        position: usize,
+        name: Option<String>,
+        params: String,
+        in_dim: u32,
+    },
+}
+
+impl std::fmt::Display for LayerRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LayerRef::Name(name) => write!(f, "layer '{}'", name),
+            LayerRef::Position(position) => write!(f, "layer {}", position),
+            LayerRef::Located {
+                position,
+                name,
+                params,
+                in_dim,
+            } => {
+                write!(f, "layer {}", position)?;
+                if let Some(name) = name {
+                    write!(f, " '{}'", name)?;
+                }
+                write!(f, " ({}, input {})", params, in_dim)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+/// ## An error computing the dimensions of a network, or reading it.
+///
+/// The errors of the arithmetic of a single layer refer to the `layer` once it is known, i.e. when
+/// the layer is evaluated as part of a network.
+pub enum ConvDimError {
+    /// The input of a convolution, including its padding, is smaller than its filter.
+    #[error(
+        "{}",
+        about(
+            .layer,
+            format!(
+                "the padded input ({}) is smaller than the kernel ({}), for an input of dimension \
+                 {} padded by {}.",
+                *.in_dim as u128 + 2 * *.padding as u128,
+                .filter_size,
+                .in_dim,
+                .padding
+            )
+        )
+    )]
+    KernelLargerThanInput {
+        layer: Option<LayerRef>,
+        filter_size: u64,
+        in_dim: u64,
+        padding: u64,
+    },
+    /// A transposed convolution receives an input of dimension zero.
+    #[error(
+        "{}",
+        about(.layer, "the input of the transposed convolution needs to be strictly positive.")
+    )]
+    EmptyInput { layer: Option<LayerRef> },
+    /// A transposed convolution crops more padding off its output than it produces.
+    #[error(
+        "{}",
+        about(
+            .layer,
+            format!(
+                "the padding {} of the transposed convolution exceeds its output of dimension {} \
+                 for an input of dimension {} (filter size {}, stride {}).",
+                .padding,
+                (*.in_dim as u128 - 1) * *.stride as u128 + *.filter_size as u128,
+                .in_dim,
+                .filter_size,
+                .stride
+            )
+        )
+    )]
+    NegativeOutput {
+        layer: Option<LayerRef>,
+        in_dim: u64,
+        filter_size: u64,
+        stride: u64,
+        padding: u64,
+    },
+    /// A layer with a stride of zero, which does not move its filter.
+    #[error("{}", about(.layer, "the stride of 0 does not move the filter."))]
+    ZeroStride { layer: Option<LayerRef> },
+    /// A layer with a filter size of zero.
+    #[error("{}", about(.layer, "the filter size needs to be strictly positive."))]
+    ZeroFilterSize { layer: Option<LayerRef> },
+    /// The padded input or the output of a layer exceeds the range of the integer type.
+    #[error(
+        "{}",
+        about(
+            .layer,
+            format!(
+                "the dimensions for an input of dimension {} (filter size {}, stride {}, padding \
+                 {}) exceed the range of the integer type.",
+                .in_dim, .filter_size, .stride, .padding
+            )
+        )
+    )]
+    Overflow {
+        layer: Option<LayerRef>,
+        in_dim: u64,
+        filter_size: u64,
+        stride: u64,
+        padding: u64,
+    },
+    /// The branches of a parallel block produce outputs of different dimensions.
+    #[error(
+        "The branches of a parallel block produce outputs of different dimensions: {}",
+        .dims
+            .iter()
+            .enumerate()
+            .map(|(i, d)| format!("branch {}: {}", i + 1, d))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )]
+    BranchMismatch { dims: Vec<u32> },
+    /// A parallel block without branches.
+    #[error("A parallel block needs to contain at least one branch.")]
+    EmptyParallel,
+    /// An output names neither a layer nor a node.
+    #[error("The output '{name}' does not name any layer or node.")]
+    UnknownOutput { name: String },
+    /// A (transposed) convolutional node with a number of inputs other than one.
+    #[error("Node '{node}' must have exactly one input, but has {count}.")]
+    InputCount { node: String, count: usize },
+    /// A (transposed) convolutional node without `filter_size`.
+    #[error("Node '{node}' is missing the 'filter_size'.")]
+    MissingFilterSize { node: String },
+    /// The inputs of an `add` or `concat` node have incompatible shapes, for the given reasons.
+    #[error("The inputs of {op} node '{node}' have incompatible shapes:\n{}", .problems.join("\n"))]
+    IncompatibleMerge {
+        node: String,
+        op: String,
+        problems: Vec<String>,
+    },
+    /// Nodes whose inputs do not exist or form a cycle.
+    #[error("Unable to evaluate nodes {nodes:?}: their inputs do not exist or form a cycle.")]
+    Unevaluable { nodes: Vec<String> },
+    /// A graph without nodes.
+    #[error("The graph does not contain any nodes.")]
+    EmptyGraph,
+    /// A network without outputs, which could serve as the levels of a feature pyramid.
+    #[error("The network does not define any outputs that could serve as pyramid levels.")]
+    NoOutputs,
+    /// The file at `path` cannot be read, parsed or imported.
+    #[error("Unable to read '{}': {message}", .path.display())]
+    Read { path: PathBuf, message: String },
+    /// A description given as text rather than a file cannot be parsed.
+    #[error("Unable to parse the description: {message}")]
+    Parse { message: String },
+    /// A layer of a type that is neither built in nor registered as a [`plugin`](crate::plugin).
+    #[error("Unknown layer type '{kind}'.")]
+    UnknownLayerType { kind: String },
+    /// A custom layer whose geometry cannot be computed, or whose type cannot be registered.
+    #[error("Invalid layer of type '{kind}': {message}")]
+    CustomLayer { kind: String, message: String },
+    /// The [`formula`](crate::formula) of a layer does not give a valid dimension.
+    #[error("{}", about(.layer, .message))]
+    Formula {
+        layer: Option<LayerRef>,
+        message: String,
+    },
+    /// The descriptions of a directory cannot be [`check`](crate::check)ed.
+    #[error("Unable to check the descriptions: {message}")]
+    Check { message: String },
+    /// A [`snapshot`](crate::snapshot) of the trace of a network cannot be read.
+    #[error("Unable to read the snapshot: {message}")]
+    Snapshot { message: String },
+    /// The parameters of a [`generated`](crate::generate) block of layers are invalid.
+    #[error("Unable to generate the layers: {message}")]
+    Generate { message: String },
+    /// The sizes of the input, window, stride or padding of [`patches`](crate::patches) are invalid.
+    #[error("Unable to count the patches: {message}")]
+    Patches { message: String },
+    /// No [`tiling`](crate::tiling) plan satisfies the requirements.
+    #[error("Unable to plan the tiles: {message}")]
+    Tiling { message: String },
+    /// The limits of the search of clean input dimensions by [`resize`](crate::resize) are invalid.
+    #[error("Unable to search the input dimensions: {message}")]
+    Resize { message: String },
+    /// The numbers of anchors per location don't match the levels of the [`fpn`](crate::fpn).
+    #[error("Unable to count the anchors: {message}")]
+    Anchors { message: String },
+    /// The duration or sample rate of an [`audio`](crate::audio) signal is invalid.
+    #[error("Unable to compute the frames: {message}")]
+    Audio { message: String },
+    /// The signal, window or hop of a short-time Fourier transform ([`stft`](crate::stft)) is invalid.
+    #[error("Unable to count the frames: {message}")]
+    Stft { message: String },
+    /// The header of an [`image`](crate::image) cannot be read.
+    #[error("Unable to read the image: {message}")]
+    Image { message: String },
+    /// The [`init`](crate::init) template is unknown.
+    #[error("Unable to write the template: {message}")]
+    Init { message: String },
+    /// The images of a dataset cannot be [`scan`](crate::scan)ned.
+    #[error("Unable to scan the dataset: {message}")]
+    Scan { message: String },
+    /// The length of a [`series`](crate::series) cannot be read.
+    #[error("Unable to read the series: {message}")]
+    Series { message: String },
+    /// The metadata of a [`video`](crate::video) cannot be read.
+    #[error("Unable to read the video: {message}")]
+    Video { message: String },
+    /// The [`memory`](crate::memory) of the activations cannot be estimated.
+    #[error("Unable to estimate the memory: {message}")]
+    Memory { message: String },
+    /// The [`energy`](crate::energy) of an inference cannot be estimated.
+    #[error("Unable to estimate the energy: {message}")]
+    Energy { message: String },
+    /// The descriptions of a [`zoo`](crate::zoo) cannot be summarized.
+    #[error("Unable to summarize the descriptions: {message}")]
+    Zoo { message: String },
+    /// The shapes of a model cannot be [`verify`](crate::verify)'d, e.g. for the input given.
+    #[error("Unable to verify the shapes: {message}")]
+    Verify { message: String },
+    /// The dimensions of a network cannot be verified [`numeric`](crate::numeric)ally.
+    #[error("Unable to verify the dimensions numerically: {message}")]
+    Numeric { message: String },
+    /// The parameters or operations of a network cannot be [`summar`](crate::summary)ized.
+    #[error("Unable to summarize the network: {message}")]
+    Summary { message: String },
+    /// The [`plot`](crate::plot) of a network cannot be written.
+    #[error("Unable to plot the network: {message}")]
+    Plot { message: String },
+}
+
+impl ConvDimError {
+    /// The layer the error occurred in, if the error concerns a single layer.
+    fn layer_mut(&mut self) -> Option<&mut Option<LayerRef>> {
+        match self {
+            ConvDimError::KernelLargerThanInput { layer, .. }
+            | ConvDimError::EmptyInput { layer }
+            | ConvDimError::NegativeOutput { layer, .. }
+            | ConvDimError::ZeroStride { layer }
+            | ConvDimError::ZeroFilterSize { layer }
+            | ConvDimError::Overflow { layer, .. }
+            | ConvDimError::Formula { layer, .. } => Some(layer),
+            _ => None,
+        }
+    }
+
+    /// The error with the `layer` it occurred in, unless it already refers to one.
+    fn with_layer(mut self, reference: LayerRef) -> ConvDimError {
+        if let Some(layer @ None) = self.layer_mut() {
+            *layer = Some(reference);
+        }
+        self
+    }
+
+    /// The layer the error occurred in, if it concerns a single layer and the layer is known.
+    pub fn layer(&self) -> Option<&LayerRef> {
+        match self {
+            ConvDimError::KernelLargerThanInput { layer, .. }
+            | ConvDimError::EmptyInput { layer }
+            | ConvDimError::NegativeOutput { layer, .. }
+            | ConvDimError::ZeroStride { layer }
+            | ConvDimError::ZeroFilterSize { layer }
+            | ConvDimError::Overflow { layer, .. }
+            | ConvDimError::Formula { layer, .. } => layer.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// The error with the name of the `layer` it occurred in, unless it already refers to one.
+    pub fn in_layer(self, name: &str) -> ConvDimError {
+        self.with_layer(LayerRef::Name(name.to_string()))
+    }
+
+    /// The error with the (one-based) `position` of the layer it occurred in, unless it already
+    /// refers to one.
+    pub fn at_position(self, position: usize) -> ConvDimError {
+        self.with_layer(LayerRef::Position(position))
+    }
+
+    /// ## The error with the (one-based) `position`, the `name`, the `params` and the input
+    /// dimension `in_dim` of the layer it occurred in.
+    ///
+    /// The location replaces a reference to the layer by its name or position alone, but not the
+    /// location of a layer nested in it, e.g. in a subnetwork.
+    pub fn located(
+        mut self,
+        position: usize,
+        name: Option<&str>,
+        params: String,
+        in_dim: u32,
+    ) -> ConvDimError {
+        if let Some(layer) = self.layer_mut() {
+            if !matches!(layer, Some(LayerRef::Located { .. })) {
+                *layer = Some(LayerRef::Located {
+                    position,
+                    name: name.map(String::from),
+                    params,
+                    in_dim,
+                });
+            }
+        }
+        self
+    }
+}
+
+/// The `message` about a single layer, prefixed by the `layer` if it is known.
+fn about(layer: &Option<LayerRef>, message: impl AsRef<str>) -> String {
+    let message = message.as_ref();
+    match layer {
+        Some(layer) => {
+            let layer = layer.to_string();
+            format!("{}{}: {}", layer[..1].to_uppercase(), &layer[1..], message)
+        }
+        None => message[..1].to_uppercase() + &message[1..],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_name_the_layer() {
+        let error = ConvDimError::KernelLargerThanInput {
+            layer: None,
+            filter_size: 31,
+            in_dim: 28,
+            padding: 1,
+        };
+        assert_eq!(
+            error.to_string(),
+            "The padded input (30) is smaller than the kernel (31), for an input of dimension 28 padded by 1."
+        );
+        assert_eq!(
+            error.clone().in_layer("conv1").at_position(4).to_string(),
+            "Layer 'conv1': the padded input (30) is smaller than the kernel (31), for an input of dimension 28 padded by 1."
+        );
+        assert_eq!(
+            error.at_position(4).to_string(),
+            "Layer 4: the padded input (30) is smaller than the kernel (31), for an input of dimension 28 padded by 1."
+        );
+        assert_eq!(
+            ConvDimError::ZeroStride { layer: None }
+                .at_position(2)
+                .to_string(),
+            "Layer 2: the stride of 0 does not move the filter."
+        );
+    }
+}
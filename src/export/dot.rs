@@ -0,0 +1,127 @@
+//! Export of a network as a Graphviz DOT graph, e.g. for `dot -Tsvg network.dot > network.svg`.
+//!
+//! Every input and node becomes a vertex labeled with its name, its parameters and, if the input
+//! dimension is known, its output shape. The outputs of the network are drawn with a double border.
+use super::{operation, Diagram};
+use crate::error::ConvDimError;
+use crate::graph::Graph;
+
+/// `text` escaped for a quoted DOT string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// `text` as a quoted DOT identifier.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", escape(text))
+}
+
+/// The quoted label consisting of `lines`.
+fn label(lines: &[String]) -> String {
+    let lines: Vec<String> = lines.iter().map(|line| escape(line)).collect();
+    format!("\"{}\"", lines.join("\\n"))
+}
+
+/// ## Export `graph` as a DOT graph.
+///
+/// The output shapes are only shown if the input dimension `in_dim` is given or all the inputs
+/// declare their own dimension.
+pub fn export(graph: &Graph, in_dim: Option<u32>) -> Result<String, ConvDimError> {
+    let diagram = Diagram::new(graph, in_dim)?;
+
+    let vertex = |name: &str, lines: Vec<String>, attributes: &str| {
+        let border = if diagram.is_output(name) {
+            ", peripheries=2"
+        } else {
+            ""
+        };
+        format!(
+            "    {} [label={}{}{}];\n",
+            quote(name),
+            label(&lines),
+            attributes,
+            border
+        )
+    };
+
+    let mut dot = String::from("digraph network {\n    node [shape=box];\n\n");
+    for (name, _) in graph.input_shapes(0) {
+        let lines = std::iter::once(name.to_string())
+            .chain(diagram.describe(name))
+            .collect();
+        dot += &vertex(name, lines, ", shape=ellipse");
+    }
+    let edges = graph.edges();
+    for node in graph.order() {
+        let lines = vec![node.name.clone(), operation(node)]
+            .into_iter()
+            .chain(diagram.describe(&node.name))
+            .collect();
+        dot += &vertex(&node.name, lines, "");
+        for input in &edges[node.name.as_str()] {
+            dot += &format!("    {} -> {};\n", quote(input), quote(&node.name));
+        }
+    }
+    dot += "}\n";
+    Ok(dot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAPH: &str = r#"
+        input_channels = 3
+        outputs = ["sum"]
+
+        [[nodes]]
+        name = "conv1"
+        op = "conv"
+        filter_size = 3
+        stride = 2
+        padding = 1
+        channels = 8
+
+        [[nodes]]
+        name = "conv2"
+        op = "conv"
+        filter_size = 3
+        padding = 1
+
+        [[nodes]]
+        name = "sum"
+        op = "add"
+        inputs = ["conv1", "conv2"]
+        "#;
+
+    #[test]
+    fn test_export() {
+        let graph: Graph = toml::from_str(GRAPH).unwrap();
+        assert_eq!(
+            export(&graph, Some(32)).unwrap(),
+            r#"digraph network {
+    node [shape=box];
+
+    "input" [label="input\n32x32x3", shape=ellipse];
+    "conv1" [label="conv1\nconv 3x3, stride 2, padding 1\n16x16x8"];
+    "input" -> "conv1";
+    "conv2" [label="conv2\nconv 3x3, padding 1\n16x16x8"];
+    "conv1" -> "conv2";
+    "sum" [label="sum\nadd\n16x16x8", peripheries=2];
+    "conv1" -> "sum";
+    "conv2" -> "sum";
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_unknown_input_dim() {
+        let graph: Graph = toml::from_str(GRAPH).unwrap();
+        let dot = export(&graph, None).unwrap();
+        assert!(dot.contains("    \"input\" [label=\"input\\n3 channels\", shape=ellipse];\n"));
+        assert!(dot.contains(
+            "    \"conv1\" [label=\"conv1\\nconv 3x3, stride 2, padding 1\\n8 channels\"];\n"
+        ));
+    }
+}
@@ -0,0 +1,272 @@
+//! Export of a network as a Keras model.
+//!
+//! Chains of layers become a `tf.keras.Sequential`, all other networks a functional
+//! `tf.keras.Model`. Keras works on channels-last tensors, so the numbers of channels only appear
+//! in the shapes of the inputs, which are parameters of the exported function.
+//!
+//! Keras has no explicit padding, but `padding="same"` results in the output dimension
+//! ceil(n / s), which equals the output dimension of a convolution if 2p = f - 1, and in n * s
+//! for transposed convolutions, which equals their output dimension if f - 2p = s. Any other
+//! padding is added by a `ZeroPadding2D` before a convolution or removed by a `Cropping2D` after a
+//! transposed convolution.
+use super::{Kind, Layer, Network, HEADER};
+use crate::graph::Graph;
+
+/// The Keras layers that `layer` is exported as, in the order in which they are applied.
+///
+/// Identities are exported as no layers at all.
+fn layers(layer: &Layer, name: &str) -> Vec<String> {
+    let node = layer.node;
+    let filter_size = node.filter_size.unwrap_or(1);
+    let (stride, padding) = (node.stride, node.padding);
+
+    let mut arguments = vec![];
+    let mut before = None;
+    let mut after = None;
+    match layer.kind {
+        Kind::Conv | Kind::Pool => {
+            if layer.kind == Kind::Conv {
+                arguments.push(layer.channels.to_string());
+            }
+            arguments.push(filter_size.to_string());
+            // The stride of pooling defaults to the pool size.
+            let default_stride = if layer.kind == Kind::Conv {
+                1
+            } else {
+                filter_size
+            };
+            if stride != default_stride {
+                arguments.push(format!("strides={}", stride));
+            }
+            if padding > 0 && 2 * padding + 1 == filter_size {
+                arguments.push("padding=\"same\"".to_string());
+            } else if padding > 0 {
+                before = Some(format!(
+                    "layers.ZeroPadding2D({}, name=\"{}_pad\")",
+                    padding, name
+                ));
+            }
+        }
+        Kind::Transposed => {
+            arguments.push(layer.channels.to_string());
+            arguments.push(filter_size.to_string());
+            if stride != 1 {
+                arguments.push(format!("strides={}", stride));
+            }
+            if padding == 0 && filter_size >= stride {
+                // The default "valid" padding.
+            } else if filter_size == 2 * padding + stride {
+                arguments.push("padding=\"same\"".to_string());
+            } else {
+                // Without an explicit output padding, the output would be at least n * s.
+                if filter_size < stride {
+                    arguments.push("output_padding=0".to_string());
+                }
+                if padding > 0 {
+                    after = Some(format!(
+                        "layers.Cropping2D({}, name=\"{}_crop\")",
+                        padding, name
+                    ));
+                }
+            }
+        }
+        Kind::Pad => {
+            return vec![format!(
+                "layers.ZeroPadding2D({}, name=\"{}\")",
+                padding, name
+            )]
+        }
+        Kind::Upsample => {
+            return vec![format!(
+                "layers.UpSampling2D({}, name=\"{}\")",
+                stride, name
+            )]
+        }
+        Kind::BatchNorm => return vec![format!("layers.BatchNormalization(name=\"{}\")", name)],
+        Kind::Relu => return vec![format!("layers.ReLU(name=\"{}\")", name)],
+        Kind::Dropout => return vec![format!("layers.Dropout(0.5, name=\"{}\")", name)],
+        Kind::Identity => return vec![],
+        Kind::Add => return vec![format!("layers.Add(name=\"{}\")", name)],
+        Kind::Concat => return vec![format!("layers.Concatenate(name=\"{}\")", name)],
+    }
+    let class = match layer.kind {
+        Kind::Conv => "Conv2D",
+        Kind::Pool => "MaxPooling2D",
+        _ => "Conv2DTranspose",
+    };
+    arguments.push(format!("name=\"{}\"", name));
+    before
+        .into_iter()
+        .chain(Some(format!("layers.{}({})", class, arguments.join(", "))))
+        .chain(after)
+        .collect()
+}
+
+/// ## Export `graph` as the code of a function `network` returning a Keras model.
+pub fn export(graph: &Graph) -> Result<String, String> {
+    let network = Network::new(graph)?;
+
+    let mut code = String::from(HEADER);
+    code += "import tensorflow as tf\nfrom tensorflow.keras import layers\n\n\n";
+    code += &format!("def network({}):\n", network.parameters().join(", "));
+
+    if network.is_sequential() {
+        code += "    return tf.keras.Sequential(\n        [\n";
+        code += &format!(
+            "            layers.Input(shape=(None, None, {})),\n",
+            network.inputs[0].1
+        );
+        for layer in &network.layers {
+            for layer in layers(layer, network.identifier(&layer.node.name)) {
+                code += &format!("            {},\n", layer);
+            }
+        }
+        code += "        ],\n        name=\"network\",\n    )\n";
+        return Ok(code);
+    }
+
+    for (input, parameter, _) in &network.inputs {
+        let name = network.identifier(input);
+        code += &format!(
+            "    {} = layers.Input(shape=(None, None, {}), name=\"{}\")\n",
+            name, parameter, name
+        );
+    }
+    for layer in &network.layers {
+        let name = network.identifier(&layer.node.name);
+        let inputs: Vec<&str> = layer
+            .inputs
+            .iter()
+            .map(|input| network.identifier(input))
+            .collect();
+        let mut value = match layer.kind {
+            Kind::Add | Kind::Concat => format!("[{}]", inputs.join(", ")),
+            _ => inputs[0].to_string(),
+        };
+        for layer in layers(layer, name) {
+            value = format!("{}({})", layer, value);
+        }
+        code += &format!("    {} = {}\n", name, value);
+    }
+
+    let list = |names: Vec<&str>| {
+        let names: Vec<&str> = names.iter().map(|name| network.identifier(name)).collect();
+        format!("[{}]", names.join(", "))
+    };
+    code += &format!(
+        "    return tf.keras.Model(inputs={}, outputs={}, name=\"network\")\n",
+        list(network.inputs.iter().map(|(name, _, _)| *name).collect()),
+        list(network.outputs.clone())
+    );
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequential() {
+        let graph: Graph = toml::from_str(
+            r#"
+            input_channels = 3
+
+            [[nodes]]
+            name = "conv1"
+            op = "conv"
+            filter_size = 3
+            stride = 2
+            padding = 1
+            channels = 16
+
+            [[nodes]]
+            name = "conv2"
+            op = "conv"
+            filter_size = 5
+            padding = 1
+            channels = 32
+
+            [[nodes]]
+            name = "pool1"
+            op = "conv"
+            filter_size = 2
+            stride = 2
+
+            [[nodes]]
+            name = "up1"
+            op = "transposed"
+            filter_size = 4
+            stride = 2
+            padding = 1
+            channels = 16
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            export(&graph).unwrap(),
+            r#"# Exported by convdim. Activations and normalization layers that are not part of the
+# network description need to be added.
+import tensorflow as tf
+from tensorflow.keras import layers
+
+
+def network(in_channels=3):
+    return tf.keras.Sequential(
+        [
+            layers.Input(shape=(None, None, in_channels)),
+            layers.Conv2D(16, 3, strides=2, padding="same", name="conv1"),
+            layers.ZeroPadding2D(1, name="conv2_pad"),
+            layers.Conv2D(32, 5, name="conv2"),
+            layers.MaxPooling2D(2, name="pool1"),
+            layers.Conv2DTranspose(16, 4, strides=2, padding="same", name="up1"),
+        ],
+        name="network",
+    )
+"#
+        );
+    }
+
+    #[test]
+    fn test_functional() {
+        let graph: Graph = toml::from_str(
+            r#"
+            outputs = ["cat", "up"]
+
+            [[inputs]]
+            name = "rgb"
+            channels = 3
+
+            [[inputs]]
+            name = "depth"
+            channels = 1
+
+            [[nodes]]
+            name = "cat"
+            op = "concat"
+            inputs = ["rgb", "depth"]
+
+            [[nodes]]
+            name = "up"
+            op = "transposed"
+            inputs = ["cat"]
+            filter_size = 3
+            stride = 2
+            padding = 1
+            channels = 8
+            "#,
+        )
+        .unwrap();
+        let code = export(&graph).unwrap();
+        assert!(code.contains("def network(rgb_channels=3, depth_channels=1):\n"));
+        assert!(code
+            .contains("    rgb = layers.Input(shape=(None, None, rgb_channels), name=\"rgb\")\n"));
+        assert!(code.contains("    cat = layers.Concatenate(name=\"cat\")([rgb, depth])\n"));
+        // The padding of 1 is cropped from the "valid" output (n - 1) * 2 + 3.
+        assert!(code.contains(
+            "    up = layers.Cropping2D(1, name=\"up_crop\")(layers.Conv2DTranspose(8, 3, strides=2, name=\"up\")(cat))\n"
+        ));
+        assert!(code.ends_with(
+            "    return tf.keras.Model(inputs=[rgb, depth], outputs=[cat, up], name=\"network\")\n"
+        ));
+    }
+}
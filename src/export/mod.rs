@@ -0,0 +1,477 @@
+//! Export of networks as code or models of deep learning frameworks, or as diagrams.
+//!
+//! A network only describes the arithmetic of the spatial dimensions, so the exported code is a
+//! skeleton: every (transposed) convolutional node that declares its number of output `channels`
+//! becomes a (transposed) convolution, whereas the nodes that keep the channels of their input
+//! become layers without weights, i.e. max pooling, zero padding (for 1x1 nodes with padding) or
+//! upsampling (for transposed nodes without padding whose filter size equals their stride).
+//! Activations and normalization layers don't change the shapes and are left to be added by hand.
+use std::collections::{HashMap, HashSet};
+
+use crate::error::ConvDimError;
+use crate::graph::{Graph, Node, Op, Shape};
+
+pub mod dot;
+pub mod keras;
+pub mod onnx;
+pub mod pytorch;
+pub mod rust;
+pub mod svg;
+pub mod tikz;
+
+/// The comment at the top of the exported code.
+const HEADER: &str =
+    "# Exported by convdim. Activations and normalization layers that are not part of the\n\
+     # network description need to be added.\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## The formats that networks can be exported to.
+pub enum Format {
+    PyTorch,
+    Keras,
+    /// An ONNX model, whose weights are filled with random values if `random` is set.
+    Onnx {
+        random: bool,
+    },
+    /// A Graphviz DOT graph, showing the shapes for the input dimension `in_dim`.
+    Dot {
+        in_dim: Option<u32>,
+    },
+    /// A standalone SVG diagram, whose boxes are scaled to the dimensions for `in_dim`.
+    Svg {
+        in_dim: Option<u32>,
+    },
+    /// A TikZ figure, whose blocks are scaled to the dimensions for `in_dim` and the channels.
+    Tikz {
+        in_dim: Option<u32>,
+    },
+    /// A Rust module of constants holding the shapes for `in_dim`.
+    RustConsts {
+        in_dim: u32,
+    },
+}
+
+/// ## Export `graph` in `format`, as the content of the file to write.
+pub fn export(graph: &Graph, format: Format) -> Result<Vec<u8>, String> {
+    let diagram = |export: fn(&Graph, Option<u32>) -> Result<String, ConvDimError>, in_dim| {
+        export(graph, in_dim)
+            .map(String::into_bytes)
+            .map_err(|e| e.to_string())
+    };
+    match format {
+        Format::PyTorch => pytorch::export(graph).map(String::into_bytes),
+        Format::Keras => keras::export(graph).map(String::into_bytes),
+        Format::Onnx { random } => onnx::export(graph, random),
+        Format::Dot { in_dim } => diagram(dot::export, in_dim),
+        Format::Svg { in_dim } => diagram(svg::export, in_dim),
+        Format::Tikz { in_dim } => diagram(tikz::export, in_dim),
+        Format::RustConsts { in_dim } => rust::export(graph, in_dim)
+            .map(String::into_bytes)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// The outputs of `graph`, which is the last node unless they are declared.
+pub(crate) fn outputs(graph: &Graph) -> Vec<&str> {
+    if graph.outputs.is_empty() {
+        graph
+            .nodes
+            .last()
+            .map(|node| node.name.as_str())
+            .into_iter()
+            .collect()
+    } else {
+        graph.outputs.iter().map(String::as_str).collect()
+    }
+}
+
+/// The description of the operation of `node`, e.g. `conv 3x3, stride 2, padding 1`.
+pub(crate) fn operation(node: &Node) -> String {
+    let op = match node.op {
+        Op::Conv => "conv",
+        Op::Transposed => "transposed conv",
+        Op::Add => return "add".to_string(),
+        Op::Concat => return "concat".to_string(),
+        Op::Subnetwork => return "subnetwork".to_string(),
+        Op::BatchNorm | Op::Relu | Op::Dropout | Op::Identity => {
+            return node
+                .op
+                .pass_through()
+                .map_or("", |kind| kind.kind())
+                .to_string()
+        }
+    };
+    if let Some(formula) = &node.formula {
+        return format!("{} n -> {}", op, formula);
+    }
+    let mut operation = match node.filter_size {
+        Some(f) => format!("{} {}x{}", op, f, f),
+        None => op.to_string(),
+    };
+    if node.stride != 1 {
+        operation += &format!(", stride {}", node.stride);
+    }
+    if node.padding != 0 {
+        operation += &format!(", padding {}", node.padding);
+    }
+    operation
+}
+
+/// The columns of the inputs and nodes of `graph` in diagrams drawn from left to right.
+///
+/// The inputs are in the first column and every node is in the column after the last of its inputs.
+fn columns(graph: &Graph) -> HashMap<&str, usize> {
+    let mut columns: HashMap<&str, usize> = graph
+        .input_shapes(0)
+        .into_iter()
+        .map(|(name, _)| (name, 0))
+        .collect();
+    let edges = graph.edges();
+    for node in graph.order() {
+        let inputs = &edges[node.name.as_str()];
+        let column = inputs.iter().map(|input| columns[input]).max().unwrap_or(0) + 1;
+        columns.insert(&node.name, column);
+    }
+    columns
+}
+
+/// ## The shapes of the inputs and nodes of a network, as shown in diagrams.
+struct Diagram<'a> {
+    /// Whether the dimensions are known, otherwise the shapes only hold the channels.
+    dims: bool,
+    shapes: HashMap<&'a str, Shape>,
+    outputs: Vec<&'a str>,
+}
+
+impl<'a> Diagram<'a> {
+    /// The shapes for the input dimension `in_dim`, which is only needed if not all the inputs
+    /// of `graph` declare their own dimension.
+    fn new(graph: &'a Graph, in_dim: Option<u32>) -> Result<Diagram<'a>, ConvDimError> {
+        let dims = in_dim.is_some() || !graph.needs_input_dim();
+        let mut shapes: HashMap<&str, Shape> = graph
+            .input_shapes(in_dim.unwrap_or(0))
+            .into_iter()
+            .collect();
+        if dims {
+            let computed: HashMap<String, Shape> =
+                graph.shapes(in_dim.unwrap_or(0))?.into_iter().collect();
+            for node in &graph.nodes {
+                shapes.insert(&node.name, computed[&node.name]);
+            }
+        } else {
+            // Without the dimension, only the channels are propagated like by `Graph::shapes`.
+            let edges = graph.edges();
+            for node in graph.order() {
+                let inputs = &edges[node.name.as_str()];
+                let channels = match node.op {
+                    Op::Concat => inputs.iter().map(|input| shapes[input].channels).sum(),
+                    _ => node.channels.or(shapes[inputs[0]].channels),
+                };
+                shapes.insert(&node.name, Shape { dim: 0, channels });
+            }
+        }
+        Ok(Diagram {
+            dims,
+            shapes,
+            outputs: outputs(graph),
+        })
+    }
+
+    /// The description of the shape of the input or node `name`, e.g. `56x56x64`, or of its
+    /// channels only if the dimension is unknown.
+    fn describe(&self, name: &str) -> Option<String> {
+        let shape = self.shapes[name];
+        match (self.dims, shape.channels) {
+            (true, Some(channels)) => Some(format!("{}x{}x{}", shape.dim, shape.dim, channels)),
+            (true, None) => Some(format!("{}x{}", shape.dim, shape.dim)),
+            (false, Some(channels)) => Some(format!("{} channels", channels)),
+            (false, None) => None,
+        }
+    }
+
+    fn is_output(&self, name: &str) -> bool {
+        self.outputs.contains(&name)
+    }
+}
+
+/// The keywords of Python, which cannot be used as identifiers.
+const KEYWORDS: [&str; 35] = [
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// The names used by the exported code itself.
+const RESERVED: [&str; 6] = ["self", "torch", "nn", "tf", "keras", "OrderedDict"];
+
+/// The Python identifier closest to `name`, e.g. `model_0_conv` for `model.0.conv`.
+fn identifier(name: &str) -> String {
+    let identifier: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if identifier.starts_with(|c: char| c.is_ascii_digit()) || identifier.is_empty() {
+        format!("_{}", identifier)
+    } else {
+        identifier
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## The kind of layer that a node is exported as.
+pub enum Kind {
+    Conv,
+    Transposed,
+    Pool,
+    Pad,
+    Identity,
+    Upsample,
+    BatchNorm,
+    Relu,
+    Dropout,
+    Add,
+    Concat,
+}
+
+impl Kind {
+    /// The kind of layer of `node`, which fails for (transposed) convolutions without a filter size
+    /// or with a formula.
+    pub fn of(node: &Node) -> Result<Kind, String> {
+        match node.op {
+            Op::Add => return Ok(Kind::Add),
+            Op::Concat => return Ok(Kind::Concat),
+            Op::BatchNorm => return Ok(Kind::BatchNorm),
+            Op::Relu => return Ok(Kind::Relu),
+            Op::Dropout => return Ok(Kind::Dropout),
+            Op::Identity => return Ok(Kind::Identity),
+            Op::Conv | Op::Transposed | Op::Subnetwork => {}
+        }
+        if let Some(formula) = &node.formula {
+            return Err(format!(
+                "Node '{}' is computed by the formula '{}', which cannot be exported.",
+                node.name, formula
+            ));
+        }
+        let filter_size = match node.filter_size {
+            Some(filter_size) => filter_size,
+            None => {
+                return Err(format!(
+                    "Node '{}' is missing the 'filter_size'.",
+                    node.name
+                ))
+            }
+        };
+        Ok(match (node.op, node.channels) {
+            (Op::Conv, Some(_)) => Kind::Conv,
+            (Op::Conv, None) if filter_size == 1 && node.stride == 1 && node.padding > 0 => {
+                Kind::Pad
+            }
+            (Op::Conv, None) if filter_size == 1 && node.stride == 1 => Kind::Identity,
+            (Op::Conv, None) => Kind::Pool,
+            (Op::Transposed, None) if filter_size == node.stride && node.padding == 0 => {
+                Kind::Upsample
+            }
+            (Op::Transposed, _) => Kind::Transposed,
+            _ => return Err(format!("Node '{}' cannot be exported.", node.name)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## A number of channels, known or depending on the numbers of channels of the inputs.
+///
+/// The channels of the inputs are parameters of the exported network, whose defaults are the
+/// declared numbers of channels.
+pub struct Channels {
+    constant: u32,
+    parameters: Vec<String>,
+}
+
+impl Channels {
+    fn known(channels: u16) -> Channels {
+        Channels {
+            constant: channels as u32,
+            parameters: vec![],
+        }
+    }
+
+    /// The total of `channels`, e.g. for a concatenation.
+    fn sum<'a>(channels: impl Iterator<Item = &'a Channels>) -> Channels {
+        channels.fold(
+            Channels {
+                constant: 0,
+                parameters: vec![],
+            },
+            |mut sum, channels| {
+                sum.constant += channels.constant;
+                sum.parameters.extend(channels.parameters.iter().cloned());
+                sum
+            },
+        )
+    }
+}
+
+impl std::fmt::Display for Channels {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut terms = self.parameters.clone();
+        if self.constant > 0 || terms.is_empty() {
+            terms.push(self.constant.to_string());
+        }
+        write!(f, "{}", terms.join(" + "))
+    }
+}
+
+#[derive(Debug)]
+/// ## A node prepared for export.
+pub struct Layer<'a> {
+    pub node: &'a Node,
+    pub kind: Kind,
+    pub inputs: Vec<&'a str>,
+    pub in_channels: Channels,
+    pub channels: Channels,
+}
+
+#[derive(Debug)]
+/// ## A network prepared for export, with its layers in topological order.
+pub struct Network<'a> {
+    /// The identifiers of the inputs and nodes, by their names.
+    identifiers: HashMap<&'a str, String>,
+    /// The inputs, with the name of the parameter for their channels and their declared channels.
+    pub inputs: Vec<(&'a str, String, Option<u16>)>,
+    pub layers: Vec<Layer<'a>>,
+    pub outputs: Vec<&'a str>,
+}
+
+impl<'a> Network<'a> {
+    /// ## Prepare `graph` for export.
+    pub fn new(graph: &'a Graph) -> Result<Network<'a>, String> {
+        let input_shapes = graph.input_shapes(0);
+        let inputs: Vec<(&str, String, Option<u16>)> = input_shapes
+            .iter()
+            .map(|&(name, shape)| {
+                let parameter = if input_shapes.len() == 1 {
+                    "in_channels".to_string()
+                } else {
+                    format!("{}_channels", identifier(name))
+                };
+                (name, parameter, shape.channels)
+            })
+            .collect();
+
+        let mut taken: HashSet<String> = inputs
+            .iter()
+            .map(|(_, parameter, _)| parameter.clone())
+            .collect();
+        let mut identifiers = HashMap::new();
+        let names = inputs
+            .iter()
+            .map(|(name, _, _)| *name)
+            .chain(graph.nodes.iter().map(|node| node.name.as_str()));
+        for name in names {
+            // Like in Python itself, keywords are made usable by a trailing underscore.
+            let mut base = identifier(name);
+            if KEYWORDS.contains(&base.as_str()) || RESERVED.contains(&base.as_str()) {
+                base.push('_');
+            }
+            let mut unique = base.clone();
+            let mut suffix = 2;
+            while taken.contains(&unique) {
+                unique = format!("{}_{}", base, suffix);
+                suffix += 1;
+            }
+            taken.insert(unique.clone());
+            identifiers.insert(name, unique);
+        }
+
+        let mut channels: HashMap<&str, Channels> = inputs
+            .iter()
+            .map(|(name, parameter, _)| {
+                let channels = Channels {
+                    constant: 0,
+                    parameters: vec![parameter.clone()],
+                };
+                (*name, channels)
+            })
+            .collect();
+        let edges = graph.edges();
+        let mut layers = vec![];
+        for node in graph.order() {
+            let kind = Kind::of(node)?;
+            let inputs = edges[node.name.as_str()].clone();
+            let in_channels = channels[inputs[0]].clone();
+            let out_channels = match (kind, node.channels) {
+                (Kind::Conv, Some(declared)) | (Kind::Transposed, Some(declared)) => {
+                    Channels::known(declared)
+                }
+                (Kind::Concat, _) => Channels::sum(inputs.iter().map(|input| &channels[input])),
+                _ => in_channels.clone(),
+            };
+            channels.insert(&node.name, out_channels.clone());
+            layers.push(Layer {
+                node,
+                kind,
+                inputs,
+                in_channels,
+                channels: out_channels,
+            });
+        }
+
+        Ok(Network {
+            identifiers,
+            inputs,
+            layers,
+            outputs: outputs(graph),
+        })
+    }
+
+    /// The value of `channels` for the declared channels of the inputs, if they are all declared.
+    pub fn evaluate(&self, channels: &Channels) -> Option<u32> {
+        channels
+            .parameters
+            .iter()
+            .try_fold(channels.constant, |sum, parameter| {
+                let (_, _, declared) = self.inputs.iter().find(|(_, p, _)| p == parameter)?;
+                Some(sum + (*declared)? as u32)
+            })
+    }
+
+    /// The identifier of the input or node `name`.
+    pub fn identifier(&self, name: &str) -> &str {
+        &self.identifiers[name]
+    }
+
+    /// Whether the network is a chain of layers, each applied to the output of the previous one.
+    pub fn is_sequential(&self) -> bool {
+        let mut previous = match self.inputs.as_slice() {
+            [(input, _, _)] => *input,
+            _ => return false,
+        };
+        for layer in &self.layers {
+            if layer.inputs != [previous] || layer.kind == Kind::Add || layer.kind == Kind::Concat {
+                return false;
+            }
+            previous = &layer.node.name;
+        }
+        self.outputs == [previous]
+    }
+
+    /// The parameters of the network for the channels of its inputs, as `name` or `name=default`.
+    ///
+    /// Parameters without a default come first, as required by Python.
+    pub fn parameters(&self) -> Vec<String> {
+        let mut parameters: Vec<(bool, String)> = self
+            .inputs
+            .iter()
+            .map(|(_, parameter, declared)| match declared {
+                Some(declared) => (true, format!("{}={}", parameter, declared)),
+                None => (false, parameter.clone()),
+            })
+            .collect();
+        parameters.sort_by_key(|(default, _)| *default);
+        parameters
+            .into_iter()
+            .map(|(_, parameter)| parameter)
+            .collect()
+    }
+}
@@ -0,0 +1,394 @@
+//! Export of a network as an ONNX model.
+//!
+//! The exported model declares the operations with their attributes and the shapes of their
+//! weights, so that it can be viewed (e.g. in Netron) and checked by the shape inference of other
+//! tools. The weights are left empty unless random weights are requested, which makes the model
+//! runnable. Since the weights need concrete shapes, the channels of all inputs must be declared.
+use super::{Kind, Layer, Network};
+use crate::graph::Graph;
+use crate::import::onnx::{
+    ATTRIBUTE_I, ATTRIBUTE_INTS, ATTRIBUTE_NAME, DIMENSION_VALUE, FLOAT, GRAPH_INITIALIZER,
+    GRAPH_INPUT, GRAPH_NODE, GRAPH_OUTPUT, MODEL_GRAPH, NODE_ATTRIBUTE, NODE_INPUT, NODE_NAME,
+    NODE_OP_TYPE, NODE_OUTPUT, SHAPE_DIM, TENSOR_DATA_TYPE, TENSOR_DIMS, TENSOR_FLOAT_DATA,
+    TENSOR_NAME, TENSOR_RAW_DATA, TENSOR_TYPE_SHAPE, TYPE_TENSOR_TYPE, VALUE_INFO_NAME,
+    VALUE_INFO_TYPE,
+};
+use crate::protobuf::Encoder;
+
+// Field numbers of the messages defined in `onnx.proto` that the import doesn't need.
+const MODEL_IR_VERSION: u32 = 1;
+const MODEL_PRODUCER_NAME: u32 = 2;
+const MODEL_OPSET_IMPORT: u32 = 8;
+const OPERATOR_SET_VERSION: u32 = 2;
+const GRAPH_NAME: u32 = 2;
+const ATTRIBUTE_S: u32 = 4;
+const ATTRIBUTE_TYPE: u32 = 20;
+const TENSOR_INT64_DATA: u32 = 7;
+const TENSOR_TYPE_ELEM_TYPE: u32 = 1;
+const DIMENSION_PARAM: u32 = 2;
+
+// The `type` of attributes and the `data_type` of tensors.
+const ATTRIBUTE_TYPE_INT: i64 = 2;
+const ATTRIBUTE_TYPE_STRING: i64 = 3;
+const ATTRIBUTE_TYPE_INTS: i64 = 7;
+const INT64: i64 = 7;
+
+/// The version of the IR and of the operator set in which all exported operations are defined.
+const IR_VERSION: i64 = 7;
+const OPSET_VERSION: i64 = 13;
+
+/// An attribute holding an integer.
+fn int(name: &str, value: i64) -> Encoder {
+    Encoder::new()
+        .string(ATTRIBUTE_NAME, name)
+        .varint(ATTRIBUTE_TYPE, ATTRIBUTE_TYPE_INT)
+        .varint(ATTRIBUTE_I, value)
+}
+
+/// An attribute holding a list of integers.
+fn ints(name: &str, values: &[i64]) -> Encoder {
+    values.iter().fold(
+        Encoder::new()
+            .string(ATTRIBUTE_NAME, name)
+            .varint(ATTRIBUTE_TYPE, ATTRIBUTE_TYPE_INTS),
+        |attribute, &value| attribute.varint(ATTRIBUTE_INTS, value),
+    )
+}
+
+/// A `NodeProto` computing the tensor `name` from the tensors `inputs`.
+fn operation(op_type: &str, name: &str, inputs: &[&str], attributes: Vec<Encoder>) -> Encoder {
+    let operation = inputs
+        .iter()
+        .fold(Encoder::new(), |operation, input| {
+            operation.string(NODE_INPUT, input)
+        })
+        .string(NODE_OUTPUT, name)
+        .string(NODE_NAME, name)
+        .string(NODE_OP_TYPE, op_type);
+    attributes
+        .into_iter()
+        .fold(operation, |operation, attribute| {
+            operation.message(NODE_ATTRIBUTE, attribute)
+        })
+}
+
+/// A `ValueInfoProto` of a float tensor, whose dimensions are either known or named.
+fn value_info(name: &str, dims: &[Result<i64, &str>]) -> Encoder {
+    let shape = dims.iter().fold(Encoder::new(), |shape, dim| {
+        let dim = match *dim {
+            Ok(value) => Encoder::new().varint(DIMENSION_VALUE, value),
+            Err(param) => Encoder::new().string(DIMENSION_PARAM, param),
+        };
+        shape.message(SHAPE_DIM, dim)
+    });
+    Encoder::new().string(VALUE_INFO_NAME, name).message(
+        VALUE_INFO_TYPE,
+        Encoder::new().message(
+            TYPE_TENSOR_TYPE,
+            Encoder::new()
+                .varint(TENSOR_TYPE_ELEM_TYPE, FLOAT as i64)
+                .message(TENSOR_TYPE_SHAPE, shape),
+        ),
+    )
+}
+
+/// A generator of pseudo-random numbers (xorshift), such that exports are reproducible.
+struct Random(u64);
+
+impl Random {
+    /// A number drawn uniformly from [-bound, bound].
+    fn uniform(&mut self, bound: f32) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        ((self.0 >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0) * bound
+    }
+}
+
+/// The converter of the layers of a network into the tensors and operations of an ONNX graph.
+struct Exporter<'a> {
+    network: &'a Network<'a>,
+    random: Option<Random>,
+    graph: Encoder,
+}
+
+impl<'a> Exporter<'a> {
+    /// The concrete number of `channels` of the input or output of `layer`.
+    fn channels(&self, layer: &Layer, input: bool) -> Result<i64, String> {
+        let channels = if input {
+            &layer.in_channels
+        } else {
+            &layer.channels
+        };
+        match self.network.evaluate(channels) {
+            Some(channels) => Ok(channels as i64),
+            None => Err(format!(
+                "The channels of the input of node '{}' are unknown, declare the channels of the inputs.",
+                layer.node.name
+            )),
+        }
+    }
+
+    /// Add a float weight of shape `dims` named `name`, whose values are random or left empty.
+    fn weight(&mut self, name: &str, dims: &[i64], fan_in: i64) {
+        let tensor = dims
+            .iter()
+            .fold(Encoder::new(), |tensor, &dim| {
+                tensor.varint(TENSOR_DIMS, dim)
+            })
+            .varint(TENSOR_DATA_TYPE, FLOAT as i64)
+            .string(TENSOR_NAME, name);
+        let tensor = match &mut self.random {
+            Some(random) => {
+                // The default initialization of PyTorch, bounded by 1 / sqrt(fan in).
+                let bound = 1.0 / (fan_in.max(1) as f32).sqrt();
+                let values: Vec<u8> = (0..dims.iter().product::<i64>())
+                    .flat_map(|_| random.uniform(bound).to_le_bytes())
+                    .collect();
+                tensor.bytes(TENSOR_RAW_DATA, &values)
+            }
+            None => tensor,
+        };
+        self.add(GRAPH_INITIALIZER, tensor);
+    }
+
+    /// Add a constant tensor named `name`, which is always filled.
+    fn constant(&mut self, name: &str, tensor: Encoder, n: usize) {
+        self.add(
+            GRAPH_INITIALIZER,
+            tensor
+                .varint(TENSOR_DIMS, n as i64)
+                .string(TENSOR_NAME, name),
+        );
+    }
+
+    fn add(&mut self, field: u32, message: Encoder) {
+        self.graph = std::mem::take(&mut self.graph).message(field, message);
+    }
+
+    /// Add the operation of `layer`.
+    fn layer(&mut self, layer: &Layer) -> Result<(), String> {
+        let node = layer.node;
+        let name = node.name.as_str();
+        let input = layer.inputs[0];
+        let filter_size = node.filter_size.unwrap_or(1) as i64;
+        let (stride, padding) = (node.stride as i64, node.padding as i64);
+        let attributes = vec![
+            ints("kernel_shape", &[filter_size, filter_size]),
+            ints("strides", &[stride, stride]),
+            ints("pads", &[padding; 4]),
+        ];
+
+        let operation = match layer.kind {
+            Kind::Conv | Kind::Transposed => {
+                let (in_channels, channels) =
+                    (self.channels(layer, true)?, self.channels(layer, false)?);
+                let weight = format!("{}.weight", name);
+                let (op_type, dims, fan_in) = if layer.kind == Kind::Conv {
+                    let fan_in = in_channels * filter_size * filter_size;
+                    ("Conv", [channels, in_channels], fan_in)
+                } else {
+                    let fan_in = channels * filter_size * filter_size;
+                    ("ConvTranspose", [in_channels, channels], fan_in)
+                };
+                self.weight(
+                    &weight,
+                    &[dims[0], dims[1], filter_size, filter_size],
+                    fan_in,
+                );
+                operation(op_type, name, &[input, &weight], attributes)
+            }
+            Kind::Pool => operation("MaxPool", name, &[input], attributes),
+            Kind::Pad => {
+                let pads = format!("{}.pads", name);
+                let values = [0, 0, padding, padding, 0, 0, padding, padding];
+                let tensor = values.iter().fold(
+                    Encoder::new().varint(TENSOR_DATA_TYPE, INT64),
+                    |tensor, &value| tensor.varint(TENSOR_INT64_DATA, value),
+                );
+                self.constant(&pads, tensor, values.len());
+                operation("Pad", name, &[input, &pads], vec![])
+            }
+            Kind::BatchNorm => {
+                // The identity: a scale of one, no shift, and the statistics of a standard normal.
+                let channels = self.channels(layer, true)? as usize;
+                let mut inputs = vec![input.to_string()];
+                for (parameter, value) in
+                    [("scale", 1.0), ("bias", 0.0), ("mean", 0.0), ("var", 1.0)]
+                {
+                    let tensor_name = format!("{}.{}", name, parameter);
+                    let tensor = (0..channels).fold(
+                        Encoder::new().varint(TENSOR_DATA_TYPE, FLOAT as i64),
+                        |tensor, _| tensor.float(TENSOR_FLOAT_DATA, value),
+                    );
+                    self.constant(&tensor_name, tensor, channels);
+                    inputs.push(tensor_name);
+                }
+                let inputs: Vec<&str> = inputs.iter().map(String::as_str).collect();
+                operation("BatchNormalization", name, &inputs, vec![])
+            }
+            Kind::Relu => operation("Relu", name, &[input], vec![]),
+            // Dropout is disabled for inference.
+            Kind::Identity | Kind::Dropout => operation("Identity", name, &[input], vec![]),
+            Kind::Upsample => {
+                let scales = format!("{}.scales", name);
+                let values = [1.0, 1.0, stride as f32, stride as f32];
+                let tensor = values.iter().fold(
+                    Encoder::new().varint(TENSOR_DATA_TYPE, FLOAT as i64),
+                    |tensor, &value| tensor.float(TENSOR_FLOAT_DATA, value),
+                );
+                self.constant(&scales, tensor, values.len());
+                let mode = Encoder::new()
+                    .string(ATTRIBUTE_NAME, "mode")
+                    .varint(ATTRIBUTE_TYPE, ATTRIBUTE_TYPE_STRING)
+                    .string(ATTRIBUTE_S, "nearest");
+                operation("Resize", name, &[input, "", &scales], vec![mode])
+            }
+            Kind::Add => {
+                let op_type = if layer.inputs.len() == 2 {
+                    "Add"
+                } else {
+                    "Sum"
+                };
+                operation(op_type, name, &layer.inputs, vec![])
+            }
+            Kind::Concat => operation("Concat", name, &layer.inputs, vec![int("axis", 1)]),
+        };
+        self.add(GRAPH_NODE, operation);
+        Ok(())
+    }
+}
+
+/// ## Export `graph` as an ONNX model, whose weights are random if `random` is set.
+pub fn export(graph: &Graph, random: bool) -> Result<Vec<u8>, String> {
+    let network = Network::new(graph)?;
+    let mut exporter = Exporter {
+        network: &network,
+        random: if random {
+            Some(Random(0x2545_f491_4f6c_dd1d))
+        } else {
+            None
+        },
+        graph: Encoder::new().string(GRAPH_NAME, "network"),
+    };
+
+    for (&(name, _, channels), (_, shape)) in network.inputs.iter().zip(graph.input_shapes(0)) {
+        let channels = channels.map(i64::from).ok_or("channels");
+        let dim = if shape.dim > 0 {
+            Ok(shape.dim as i64)
+        } else {
+            Err("dim")
+        };
+        let info = value_info(name, &[Err("batch"), channels, dim, dim]);
+        exporter.add(GRAPH_INPUT, info);
+    }
+    for layer in &network.layers {
+        exporter.layer(layer)?;
+    }
+    for output in &network.outputs {
+        exporter.add(GRAPH_OUTPUT, value_info(output, &[]));
+    }
+
+    let opset = Encoder::new().varint(OPERATOR_SET_VERSION, OPSET_VERSION);
+    Ok(Encoder::new()
+        .varint(MODEL_IR_VERSION, IR_VERSION)
+        .string(MODEL_PRODUCER_NAME, "convdim")
+        .message(MODEL_GRAPH, exporter.graph)
+        .message(MODEL_OPSET_IMPORT, opset)
+        .finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::onnx::import;
+    use crate::protobuf::Message;
+
+    fn graph() -> Graph {
+        toml::from_str(
+            r#"
+            outputs = ["cat"]
+
+            [[inputs]]
+            name = "image"
+            input_dim = 64
+            channels = 3
+
+            [[nodes]]
+            name = "conv1"
+            op = "conv"
+            inputs = ["image"]
+            filter_size = 3
+            stride = 2
+            padding = 1
+            channels = 16
+
+            [[nodes]]
+            name = "pool"
+            op = "conv"
+            inputs = ["conv1"]
+            filter_size = 2
+            stride = 2
+
+            [[nodes]]
+            name = "up"
+            op = "transposed"
+            inputs = ["pool"]
+            filter_size = 2
+            stride = 2
+
+            [[nodes]]
+            name = "deconv"
+            op = "transposed"
+            inputs = ["pool"]
+            filter_size = 4
+            stride = 2
+            padding = 1
+            channels = 8
+
+            [[nodes]]
+            name = "sum"
+            op = "add"
+            inputs = ["conv1", "up"]
+
+            [[nodes]]
+            name = "cat"
+            op = "concat"
+            inputs = ["sum", "deconv"]
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_through_import() {
+        let graph = graph();
+        let imported = import(&export(&graph, false).unwrap()).unwrap();
+        assert!(imported.warnings.is_empty());
+        assert_eq!(imported.graph.outputs, graph.outputs);
+        assert_eq!(imported.graph.shapes(0), graph.shapes(0));
+    }
+
+    #[test]
+    fn test_random_weights() {
+        let bytes = export(&graph(), true).unwrap();
+        let model = Message::decode(&bytes).unwrap();
+        let onnx_graph = model.message(MODEL_GRAPH).unwrap().unwrap();
+        let weights = onnx_graph.messages(GRAPH_INITIALIZER).unwrap();
+        let weight = &weights[0];
+        assert_eq!(weight.string(TENSOR_NAME), Some("conv1.weight".to_string()));
+        assert_eq!(weight.varints(TENSOR_DIMS).unwrap(), vec![16, 3, 3, 3]);
+        let values = weight.floats(TENSOR_RAW_DATA).unwrap();
+        assert_eq!(values.len(), 16 * 3 * 3 * 3);
+        let bound = 1.0 / 27f32.sqrt();
+        assert!(values.iter().all(|value| value.abs() <= bound));
+        assert!(values.iter().any(|&value| value != values[0]));
+    }
+
+    #[test]
+    fn test_unknown_channels() {
+        let mut graph = graph();
+        graph.inputs[0].channels = None;
+        assert!(export(&graph, false).is_err());
+    }
+}
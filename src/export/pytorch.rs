@@ -0,0 +1,242 @@
+//! Export of a network as a PyTorch module.
+//!
+//! Chains of layers become an `nn.Sequential`, all other networks an `nn.Module` whose `forward`
+//! method applies the layers and merges their outputs. The numbers of channels of the inputs that
+//! are not declared become parameters of the constructor.
+use super::{Kind, Layer, Network, HEADER};
+use crate::graph::Graph;
+
+/// The keyword arguments `kernel_size`, `stride` and `padding`, omitting the defaults.
+fn arguments(layer: &Layer, default_stride: u16) -> String {
+    let node = layer.node;
+    let mut arguments = vec![format!("kernel_size={}", node.filter_size.unwrap_or(1))];
+    if node.stride != default_stride {
+        arguments.push(format!("stride={}", node.stride));
+    }
+    if node.padding != 0 {
+        arguments.push(format!("padding={}", node.padding));
+    }
+    arguments.join(", ")
+}
+
+/// The expression constructing the module of `layer`, which must not be a merge.
+fn module(layer: &Layer) -> String {
+    let node = layer.node;
+    match layer.kind {
+        Kind::Conv => format!(
+            "nn.Conv2d({}, {}, {})",
+            layer.in_channels,
+            layer.channels,
+            arguments(layer, 1)
+        ),
+        Kind::Transposed => format!(
+            "nn.ConvTranspose2d({}, {}, {})",
+            layer.in_channels,
+            layer.channels,
+            arguments(layer, 1)
+        ),
+        // The stride of pooling defaults to the kernel size.
+        Kind::Pool => format!(
+            "nn.MaxPool2d({})",
+            arguments(layer, node.filter_size.unwrap_or(1))
+        ),
+        Kind::Pad => format!("nn.ZeroPad2d({})", node.padding),
+        Kind::Identity => "nn.Identity()".to_string(),
+        Kind::Upsample => format!("nn.Upsample(scale_factor={})", node.stride),
+        Kind::BatchNorm => format!("nn.BatchNorm2d({})", layer.in_channels),
+        Kind::Relu => "nn.ReLU()".to_string(),
+        Kind::Dropout => "nn.Dropout()".to_string(),
+        Kind::Add | Kind::Concat => unreachable!("Merges are not modules."),
+    }
+}
+
+/// ## Export `graph` as the code of a PyTorch module named `Network`.
+pub fn export(graph: &Graph) -> Result<String, String> {
+    let network = Network::new(graph)?;
+    let sequential = network.is_sequential();
+    let concat = network
+        .layers
+        .iter()
+        .any(|layer| layer.kind == Kind::Concat);
+
+    let mut code = String::from(HEADER);
+    if sequential {
+        code += "from collections import OrderedDict\n\n";
+    } else if concat {
+        code += "import torch\n";
+    }
+    code += "from torch import nn\n\n\n";
+    code += &format!(
+        "class Network(nn.{}):\n    def __init__(self{}):\n",
+        if sequential { "Sequential" } else { "Module" },
+        network
+            .parameters()
+            .iter()
+            .map(|parameter| format!(", {}", parameter))
+            .collect::<String>()
+    );
+
+    if sequential {
+        code += "        super().__init__(\n            OrderedDict(\n                [\n";
+        for layer in &network.layers {
+            code += &format!(
+                "                    (\"{}\", {}),\n",
+                network.identifier(&layer.node.name),
+                module(layer)
+            );
+        }
+        code += "                ]\n            )\n        )\n";
+        return Ok(code);
+    }
+
+    code += "        super().__init__()\n";
+    for layer in &network.layers {
+        if layer.kind != Kind::Add && layer.kind != Kind::Concat {
+            code += &format!(
+                "        self.{} = {}\n",
+                network.identifier(&layer.node.name),
+                module(layer)
+            );
+        }
+    }
+
+    let inputs: Vec<&str> = network
+        .inputs
+        .iter()
+        .map(|(name, _, _)| network.identifier(name))
+        .collect();
+    code += &format!("\n    def forward(self, {}):\n", inputs.join(", "));
+    for layer in &network.layers {
+        let name = network.identifier(&layer.node.name);
+        let inputs: Vec<&str> = layer
+            .inputs
+            .iter()
+            .map(|input| network.identifier(input))
+            .collect();
+        let value = match layer.kind {
+            Kind::Add => inputs.join(" + "),
+            Kind::Concat => format!("torch.cat([{}], dim=1)", inputs.join(", ")),
+            _ => format!("self.{}({})", name, inputs[0]),
+        };
+        code += &format!("        {} = {}\n", name, value);
+    }
+    let outputs: Vec<&str> = network
+        .outputs
+        .iter()
+        .map(|output| network.identifier(output))
+        .collect();
+    code += &format!("        return {}\n", outputs.join(", "));
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequential() {
+        let graph: Graph = toml::from_str(
+            r#"
+            input_channels = 3
+
+            [[nodes]]
+            name = "conv1"
+            op = "conv"
+            filter_size = 3
+            padding = 1
+            channels = 16
+
+            [[nodes]]
+            name = "pool1"
+            op = "conv"
+            filter_size = 2
+            stride = 2
+
+            [[nodes]]
+            name = "up1"
+            op = "transposed"
+            filter_size = 2
+            stride = 2
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            export(&graph).unwrap(),
+            r#"# Exported by convdim. Activations and normalization layers that are not part of the
+# network description need to be added.
+from collections import OrderedDict
+
+from torch import nn
+
+
+class Network(nn.Sequential):
+    def __init__(self, in_channels=3):
+        super().__init__(
+            OrderedDict(
+                [
+                    ("conv1", nn.Conv2d(in_channels, 16, kernel_size=3, padding=1)),
+                    ("pool1", nn.MaxPool2d(kernel_size=2)),
+                    ("up1", nn.Upsample(scale_factor=2)),
+                ]
+            )
+        )
+"#
+        );
+    }
+
+    #[test]
+    fn test_module_with_merges() {
+        let graph: Graph = toml::from_str(
+            r#"
+            outputs = ["res.add", "cat"]
+
+            [[inputs]]
+            name = "rgb"
+            channels = 3
+
+            [[inputs]]
+            name = "depth"
+
+            [[nodes]]
+            name = "res.conv"
+            op = "conv"
+            inputs = ["rgb"]
+            filter_size = 3
+            padding = 1
+            channels = 3
+
+            [[nodes]]
+            name = "res.add"
+            op = "add"
+            inputs = ["rgb", "res.conv"]
+
+            [[nodes]]
+            name = "cat"
+            op = "concat"
+            inputs = ["res.add", "depth"]
+
+            [[nodes]]
+            name = "class"
+            op = "transposed"
+            inputs = ["cat"]
+            filter_size = 4
+            stride = 2
+            padding = 1
+            "#,
+        )
+        .unwrap();
+        let code = export(&graph).unwrap();
+        assert!(code.contains("import torch\n"));
+        assert!(code.contains("    def __init__(self, depth_channels, rgb_channels=3):\n"));
+        assert!(code.contains(
+            "        self.res_conv = nn.Conv2d(rgb_channels, 3, kernel_size=3, padding=1)\n"
+        ));
+        // Without declared channels, the transposed convolution keeps the channels of its input.
+        assert!(code.contains(
+            "        self.class_ = nn.ConvTranspose2d(rgb_channels + depth_channels, rgb_channels + depth_channels, kernel_size=4, stride=2, padding=1)\n"
+        ));
+        assert!(code.ends_with(
+            "        res_add = rgb + res_conv\n        cat = torch.cat([res_add, depth], dim=1)\n        class_ = self.class_(cat)\n        return res_add, cat\n"
+        ));
+    }
+}
@@ -0,0 +1,138 @@
+//! Export of the shapes of a network as Rust constants, e.g. for `no_std` firmware allocating the
+//! buffers of its tensors statically.
+//!
+//! Every input and node becomes the constants `NAME_H`, `NAME_W` and `NAME_C` of its height, width
+//! and channels for the input dimension, and `NAME_LEN` of the number of values of its tensor. The
+//! channels and lengths of tensors with unknown channels are left out. `MAX_LEN` is the length of
+//! the largest tensor, i.e. the size of a buffer that can hold any of them.
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use super::operation;
+use crate::error::ConvDimError;
+use crate::graph::{Graph, Shape};
+
+/// The names of tensors whose constants would clash with `MAX_LEN`.
+const RESERVED: [&str; 1] = ["MAX"];
+
+/// The name of the constants of the tensor `name`, e.g. `MODEL_0_CONV` for `model.0.conv`.
+fn constant(name: &str) -> String {
+    let constant: String = name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect();
+    if constant.starts_with(|c: char| c.is_ascii_digit()) || constant.is_empty() {
+        format!("_{}", constant)
+    } else {
+        constant
+    }
+}
+
+/// ## Export the shapes of `graph` for an input of dimension `in_dim` as a Rust module.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{export::rust, Architecture, Network};
+/// let network = Network::builder().input_channels(3).conv(3, 2, 1).channels(8).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let code = rust::export(&graph, 32).unwrap();
+/// assert!(code.contains("pub const LAYER1_H: usize = 16;\n"));
+/// assert!(code.contains("pub const LAYER1_LEN: usize = 2048;\n"));
+/// assert!(code.contains("pub const MAX_LEN: usize = 3072;\n"));
+/// ```
+pub fn export(graph: &Graph, in_dim: u32) -> Result<String, ConvDimError> {
+    let mut tensors: Vec<(String, String, Shape)> = graph
+        .input_shapes(in_dim)
+        .into_iter()
+        .map(|(name, shape)| (name.to_string(), "input of the network".to_string(), shape))
+        .collect();
+    for (name, shape) in graph.shapes(in_dim)? {
+        let node = graph.nodes.iter().find(|node| node.name == name);
+        tensors.push((name, node.map_or_else(String::new, operation), shape));
+    }
+
+    let mut code = format!(
+        "//! Shapes of the tensors of the network for an input of dimension {}, exported by \
+         convdim.\n//!\n//! The lengths are numbers of values, without the batch dimension.\n\n\
+         /// The dimension of the input for which the shapes are computed.\n\
+         pub const INPUT_DIM: usize = {};\n",
+        in_dim, in_dim
+    );
+    let mut taken: HashSet<String> = RESERVED.iter().map(|name| name.to_string()).collect();
+    let mut max_len = Some(0u128);
+    for (name, operation, shape) in &tensors {
+        let base = constant(name);
+        let mut unique = base.clone();
+        let mut suffix = 2;
+        while !taken.insert(unique.clone()) {
+            unique = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        let dim = shape.dim;
+        let _ = write!(code, "\n/// `{}`: {}.\n", name, operation);
+        let _ = writeln!(code, "pub const {}_H: usize = {};", unique, dim);
+        let _ = writeln!(code, "pub const {}_W: usize = {};", unique, dim);
+        match shape.channels {
+            Some(channels) => {
+                let len = u128::from(dim) * u128::from(dim) * u128::from(channels);
+                let _ = writeln!(code, "pub const {}_C: usize = {};", unique, channels);
+                let _ = writeln!(code, "pub const {}_LEN: usize = {};", unique, len);
+                max_len = max_len.map(|max| max.max(len));
+            }
+            None => {
+                let _ = writeln!(code, "// The channels of `{}` are unknown.", name);
+                max_len = None;
+            }
+        }
+    }
+    if let Some(max_len) = max_len {
+        let _ = write!(
+            code,
+            "\n/// The length of the largest tensor.\npub const MAX_LEN: usize = {};\n",
+            max_len
+        );
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_export() {
+        let graph = parse_architecture(
+            r#"
+            [[layers]]
+            name = "stem.conv"
+            type = "conv"
+            filter_size = 3
+            stride = 2
+            padding = 1
+
+            [[layers]]
+            name = "max"
+            type = "max_pool"
+            filter_size = 2
+            stride = 2
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let code = export(&graph, 96).unwrap();
+        assert!(code.contains("pub const INPUT_DIM: usize = 96;\n"));
+        assert!(code.contains(
+            "\n/// `stem.conv`: conv 3x3, stride 2, padding 1.\n\
+             pub const STEM_CONV_H: usize = 48;\n\
+             pub const STEM_CONV_W: usize = 48;\n\
+             // The channels of `stem.conv` are unknown.\n"
+        ));
+        assert!(code.contains("pub const MAX_2_H: usize = 24;\n"));
+        assert!(!code.contains("_LEN"));
+    }
+}
@@ -0,0 +1,264 @@
+//! Export of a network as a standalone SVG diagram, which needs no layout engine like Graphviz.
+//!
+//! The inputs and nodes are laid out from left to right in columns, where every node is placed in
+//! the column after the last of its inputs. The boxes are labeled with the name, the parameters
+//! and the output shape, and their heights grow with the spatial dimension of the output, such
+//! that downsampling and upsampling are visible at a glance.
+use std::collections::HashMap;
+
+use super::{columns, operation, Diagram};
+use crate::error::ConvDimError;
+use crate::graph::{Graph, Op};
+
+const MARGIN: u32 = 20;
+const PADDING: u32 = 8;
+const LINE_HEIGHT: u32 = 16;
+/// The approximate width of a character of the labels.
+const CHAR_WIDTH: u32 = 7;
+/// The horizontal space between the columns, which holds the edges.
+const GAP_X: u32 = 60;
+const GAP_Y: u32 = 24;
+/// The height added to the box of the largest spatial dimension, and proportionally to the others.
+const SCALE: u32 = 120;
+
+const STYLE: &str = "  <style>
+    rect { stroke: #333333; stroke-width: 1; }
+    rect.input { fill: #e0e0e0; }
+    rect.conv { fill: #a6cee3; }
+    rect.transposed { fill: #b2df8a; }
+    rect.pool { fill: #fdbf6f; }
+    rect.merge { fill: #cab2d6; }
+    rect.pass { fill: #ffffff; }
+    rect.output { stroke-width: 3; }
+    text { font-family: sans-serif; font-size: 12px; text-anchor: middle; }
+    text.name { font-weight: bold; }
+    path { fill: none; stroke: #555555; marker-end: url(#arrow); }
+  </style>
+  <defs>
+    <marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\">
+      <path d=\"M 0 0 L 10 5 L 0 10 z\" style=\"fill: #555555; stroke: none;\"/>
+    </marker>
+  </defs>
+";
+
+/// `text` escaped for XML.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A box of the diagram.
+struct Vertex<'a> {
+    name: &'a str,
+    class: &'static str,
+    lines: Vec<String>,
+    column: usize,
+    x: u32,
+    y: u32,
+    height: u32,
+}
+
+/// ## Export `graph` as an SVG diagram.
+///
+/// The shapes, and thereby the heights of the boxes, are only known if the input dimension
+/// `in_dim` is given or all the inputs declare their own dimension.
+pub fn export(graph: &Graph, in_dim: Option<u32>) -> Result<String, ConvDimError> {
+    let diagram = Diagram::new(graph, in_dim)?;
+
+    let mut vertices: Vec<(&str, &str, Vec<String>)> = vec![];
+    let columns = columns(graph);
+    for (name, _) in graph.input_shapes(0) {
+        let lines = std::iter::once(name.to_string())
+            .chain(diagram.describe(name))
+            .collect();
+        vertices.push((name, "input", lines));
+    }
+    for node in graph.order() {
+        let class = match (node.op, node.channels) {
+            (Op::Conv, Some(_)) => "conv",
+            (Op::Conv, None) => "pool",
+            (Op::Transposed, _) => "transposed",
+            (op, _) if op.pass_through().is_some() => "pass",
+            _ => "merge",
+        };
+        let lines = vec![node.name.clone(), operation(node)]
+            .into_iter()
+            .chain(diagram.describe(&node.name))
+            .collect();
+        vertices.push((node.name.as_str(), class, lines));
+    }
+
+    // All boxes have the same width, which fits the longest label.
+    let width = vertices
+        .iter()
+        .flat_map(|(_, _, lines)| lines.iter().map(|line| line.chars().count() as u32))
+        .max()
+        .unwrap_or(0)
+        * CHAR_WIDTH
+        + 2 * PADDING;
+    let max_dim = diagram.shapes.values().map(|shape| shape.dim).max();
+    let mut vertices: Vec<Vertex> = vertices
+        .into_iter()
+        .map(|(name, class, lines)| {
+            let dim = diagram.shapes[name].dim as u64;
+            let height = 3 * LINE_HEIGHT
+                + 2 * PADDING
+                + match max_dim {
+                    Some(max_dim) if diagram.dims && max_dim > 0 => {
+                        (SCALE as u64 * dim / max_dim as u64) as u32
+                    }
+                    _ => 0,
+                };
+            Vertex {
+                name,
+                class,
+                lines,
+                column: columns[name],
+                x: MARGIN + columns[name] as u32 * (width + GAP_X),
+                y: 0,
+                height,
+            }
+        })
+        .collect();
+
+    // The columns are centered vertically.
+    let n_columns = vertices.iter().map(|v| v.column).max().unwrap_or(0) + 1;
+    let column_heights: Vec<u32> = (0..n_columns)
+        .map(|column| {
+            let heights: Vec<u32> = vertices
+                .iter()
+                .filter(|v| v.column == column)
+                .map(|v| v.height)
+                .collect();
+            heights.iter().sum::<u32>() + GAP_Y * (heights.len() as u32).saturating_sub(1)
+        })
+        .collect();
+    let total_height = column_heights.iter().max().copied().unwrap_or(0);
+    let mut tops: Vec<u32> = column_heights
+        .iter()
+        .map(|height| MARGIN + (total_height - height) / 2)
+        .collect();
+    for vertex in &mut vertices {
+        vertex.y = tops[vertex.column];
+        tops[vertex.column] += vertex.height + GAP_Y;
+    }
+
+    let svg_width = 2 * MARGIN + n_columns as u32 * width + (n_columns as u32 - 1) * GAP_X;
+    let svg_height = 2 * MARGIN + total_height;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+        svg_width, svg_height
+    );
+    svg += STYLE;
+
+    let edges = graph.edges();
+    let positions: HashMap<&str, &Vertex> = vertices.iter().map(|v| (v.name, v)).collect();
+    for node in &graph.nodes {
+        let target = positions[node.name.as_str()];
+        for input in &edges[node.name.as_str()] {
+            let source = positions[input];
+            let (x1, y1) = (source.x + width, source.y + source.height / 2);
+            let (x2, y2) = (target.x, target.y + target.height / 2);
+            svg += &format!(
+                "  <path d=\"M {} {} C {} {}, {} {}, {} {}\"/>\n",
+                x1,
+                y1,
+                x1 + GAP_X / 2,
+                y1,
+                x2 - GAP_X / 2,
+                y2,
+                x2,
+                y2
+            );
+        }
+    }
+
+    for vertex in &vertices {
+        let class = if diagram.is_output(vertex.name) {
+            format!("{} output", vertex.class)
+        } else {
+            vertex.class.to_string()
+        };
+        svg += &format!(
+            "  <rect class=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"4\"/>\n",
+            class, vertex.x, vertex.y, width, vertex.height
+        );
+        // The lines are centered in the box, 4 pixels lower to account for the baseline.
+        let first =
+            vertex.y + vertex.height / 2 + 4 - (vertex.lines.len() as u32 - 1) * LINE_HEIGHT / 2;
+        for (i, line) in vertex.lines.iter().enumerate() {
+            svg += &format!(
+                "  <text{} x=\"{}\" y=\"{}\">{}</text>\n",
+                if i == 0 { " class=\"name\"" } else { "" },
+                vertex.x + width / 2,
+                first + i as u32 * LINE_HEIGHT,
+                escape(line)
+            );
+        }
+    }
+    svg += "</svg>\n";
+    Ok(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAPH: &str = r#"
+        input_channels = 3
+
+        [[nodes]]
+        name = "conv<1>"
+        op = "conv"
+        filter_size = 3
+        stride = 2
+        padding = 1
+        channels = 8
+
+        [[nodes]]
+        name = "skip"
+        op = "conv"
+        inputs = ["input"]
+        filter_size = 2
+        stride = 2
+
+        [[nodes]]
+        name = "cat"
+        op = "concat"
+        inputs = ["conv<1>", "skip"]
+        "#;
+
+    #[test]
+    fn test_layout() {
+        let graph: Graph = toml::from_str(GRAPH).unwrap();
+        let svg = export(&graph, Some(32)).unwrap();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        // The label "conv 3x3, stride 2, padding 1" has 29 characters, so the boxes are 219 wide.
+        // The input of dimension 32 is higher than the nodes of dimension 16, which are stacked.
+        assert!(
+            svg.contains("<rect class=\"input\" x=\"20\" y=\"64\" width=\"219\" height=\"184\"")
+        );
+        assert!(
+            svg.contains("<rect class=\"conv\" x=\"299\" y=\"20\" width=\"219\" height=\"124\"")
+        );
+        assert!(
+            svg.contains("<rect class=\"pool\" x=\"299\" y=\"168\" width=\"219\" height=\"124\"")
+        );
+        assert!(svg.contains("<rect class=\"merge output\" x=\"578\""));
+        assert!(svg.contains(">conv&lt;1&gt;</text>"));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn test_unknown_input_dim() {
+        let graph: Graph = toml::from_str(GRAPH).unwrap();
+        let svg = export(&graph, None).unwrap();
+        // Without the dimension, all the boxes have the same height.
+        assert!(svg.contains(
+            "height=\"64\" rx=\"4\"/>\n  <text class=\"name\" x=\"129\" y=\"92\">input</text>"
+        ));
+        assert!(svg.contains(">3 channels</text>"));
+    }
+}
@@ -0,0 +1,254 @@
+//! Export of a network as a TikZ figure in the style of PlotNeuralNet.
+//!
+//! Every input and node is drawn as a block, whose width is proportional to its number of
+//! channels and whose height and depth are proportional to its spatial dimension. The blocks are
+//! laid out from left to right in columns like in the SVG diagrams, and captioned with their names
+//! and shapes. The figure is a standalone LaTeX document, whose `tikzpicture` can be copied into a
+//! paper together with the `\block` command.
+use std::collections::HashMap;
+
+use super::{columns, Diagram};
+use crate::error::ConvDimError;
+use crate::graph::{Graph, Op};
+
+/// The width of the block with the most channels, in cm.
+const MAX_WIDTH: f64 = 1.5;
+/// The height of the block with the largest spatial dimension, in cm.
+const MAX_HEIGHT: f64 = 4.0;
+/// The minimal width and height of blocks, and the width of blocks of unknown channels, in cm.
+const MIN_SIZE: f64 = 0.2;
+/// The space between the columns and between the blocks of a column, which holds the captions.
+const GAP_X: f64 = 1.2;
+const GAP_Y: f64 = 1.5;
+
+/// The command `\block{name}{x}{y}{width}{height}{color}` drawing a cuboid whose front face is
+/// centered vertically at `y`, with the coordinates `name-west` and `name-east` for the edges.
+const PREAMBLE: &str = r"% Exported by convdim.
+\documentclass[border=8pt]{standalone}
+\usepackage{tikz}
+
+\newcommand{\block}[6]{
+  \pgfmathsetmacro{\blockdepth}{0.35 * #5}
+  \colorlet{blockcolor}{#6}
+  \filldraw[fill=blockcolor] (#2, #3 - #5 / 2) rectangle (#2 + #4, #3 + #5 / 2);
+  \filldraw[fill=blockcolor!70!black] (#2, #3 + #5 / 2) -- (#2 + #4, #3 + #5 / 2)
+    -- (#2 + #4 + \blockdepth, #3 + #5 / 2 + \blockdepth) -- (#2 + \blockdepth, #3 + #5 / 2 + \blockdepth) -- cycle;
+  \filldraw[fill=blockcolor!50!black] (#2 + #4, #3 - #5 / 2) -- (#2 + #4 + \blockdepth, #3 - #5 / 2 + \blockdepth)
+    -- (#2 + #4 + \blockdepth, #3 + #5 / 2 + \blockdepth) -- (#2 + #4, #3 + #5 / 2) -- cycle;
+  \coordinate (#1-west) at (#2, #3);
+  \coordinate (#1-east) at (#2 + #4, #3);
+}
+
+\begin{document}
+\begin{tikzpicture}
+";
+
+/// `text` escaped for LaTeX.
+pub(crate) fn escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\\' => r"\textbackslash{}".to_string(),
+            '~' => r"\textasciitilde{}".to_string(),
+            '^' => r"\textasciicircum{}".to_string(),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => format!("\\{}", c),
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+/// A block of the figure.
+struct Block<'a> {
+    name: &'a str,
+    color: &'a str,
+    column: usize,
+    width: f64,
+    height: f64,
+}
+
+/// ## Export `graph` as a standalone LaTeX document with a TikZ figure.
+///
+/// The heights of the blocks are only proportional to the spatial dimensions if the input
+/// dimension `in_dim` is given or all the inputs declare their own dimension.
+pub fn export(graph: &Graph, in_dim: Option<u32>) -> Result<String, ConvDimError> {
+    let diagram = Diagram::new(graph, in_dim)?;
+    let columns = columns(graph);
+
+    let max_dim = diagram
+        .shapes
+        .values()
+        .map(|shape| shape.dim)
+        .max()
+        .unwrap_or(0);
+    let max_channels = diagram
+        .shapes
+        .values()
+        .filter_map(|shape| shape.channels)
+        .max()
+        .unwrap_or(0);
+    let mut colors: Vec<(&str, &str)> = graph
+        .input_shapes(0)
+        .into_iter()
+        .map(|(name, _)| (name, "gray!20"))
+        .collect();
+    for node in graph.order() {
+        let color = match (node.op, node.channels) {
+            (Op::Conv, Some(_)) => "yellow!40",
+            (Op::Conv, None) => "red!30",
+            (Op::Transposed, _) => "blue!25",
+            (op, _) if op.pass_through().is_some() => "white",
+            _ => "green!30",
+        };
+        colors.push((&node.name, color));
+    }
+    let blocks: Vec<Block> = colors
+        .into_iter()
+        .map(|(name, color)| {
+            let shape = diagram.shapes[name];
+            let width = match shape.channels {
+                Some(channels) => MAX_WIDTH * channels as f64 / max_channels as f64,
+                None => MIN_SIZE,
+            };
+            let height = if diagram.dims && max_dim > 0 {
+                MAX_HEIGHT * shape.dim as f64 / max_dim as f64
+            } else {
+                MAX_HEIGHT / 2.0
+            };
+            Block {
+                name,
+                color,
+                column: columns[name],
+                width: width.max(MIN_SIZE),
+                height: height.max(MIN_SIZE),
+            }
+        })
+        .collect();
+
+    // The columns are as wide as their widest block including its depth, and centered vertically.
+    let n_columns = blocks.iter().map(|block| block.column).max().unwrap_or(0) + 1;
+    let mut lefts = vec![0.0; n_columns];
+    let mut tops = vec![0.0; n_columns];
+    for column in 0..n_columns {
+        let column_blocks: Vec<&Block> = blocks.iter().filter(|b| b.column == column).collect();
+        let width = column_blocks
+            .iter()
+            .map(|b| b.width + 0.35 * b.height)
+            .fold(0.0, f64::max);
+        if column + 1 < n_columns {
+            lefts[column + 1] = lefts[column] + width + GAP_X;
+        }
+        let height: f64 = column_blocks.iter().map(|b| b.height + GAP_Y).sum::<f64>() - GAP_Y;
+        tops[column] = height / 2.0;
+    }
+
+    let mut tikz = String::from(PREAMBLE);
+    let mut ids: HashMap<&str, String> = HashMap::new();
+    for (i, block) in blocks.iter().enumerate() {
+        let id = format!("b{}", i);
+        let x = lefts[block.column];
+        let y = tops[block.column] - block.height / 2.0;
+        tops[block.column] -= block.height + GAP_Y;
+        tikz += &format!(
+            "\\block{{{}}}{{{:.2}}}{{{:.2}}}{{{:.2}}}{{{:.2}}}{{{}}}\n",
+            id, x, y, block.width, block.height, block.color
+        );
+        let caption = std::iter::once(escape(block.name))
+            .chain(diagram.describe(block.name).map(|shape| escape(&shape)))
+            .collect::<Vec<String>>()
+            .join(r"\\");
+        tikz += &format!(
+            "\\node[below, align=center, font=\\scriptsize{}] at ({:.2}, {:.2}) {{{}}};\n",
+            if diagram.is_output(block.name) {
+                r"\bfseries"
+            } else {
+                ""
+            },
+            x + block.width / 2.0,
+            y - block.height / 2.0,
+            caption
+        );
+        ids.insert(block.name, id);
+    }
+    let edges = graph.edges();
+    for node in &graph.nodes {
+        for input in &edges[node.name.as_str()] {
+            tikz += &format!(
+                "\\draw[->, thick] ({}-east) -- ({}-west);\n",
+                ids[input],
+                ids[node.name.as_str()]
+            );
+        }
+    }
+    tikz += "\\end{tikzpicture}\n\\end{document}\n";
+    Ok(tikz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_are_proportional() {
+        let graph: Graph = toml::from_str(
+            r#"
+            input_channels = 3
+
+            [[nodes]]
+            name = "conv_1"
+            op = "conv"
+            filter_size = 3
+            stride = 2
+            padding = 1
+            channels = 64
+
+            [[nodes]]
+            name = "conv_2"
+            op = "conv"
+            filter_size = 3
+            stride = 2
+            padding = 1
+            channels = 32
+            "#,
+        )
+        .unwrap();
+        let tikz = export(&graph, Some(64)).unwrap();
+        assert!(tikz.starts_with("% Exported by convdim.\n\\documentclass"));
+        assert!(tikz.contains("\\block{b0}{0.00}{0.00}{0.20}{4.00}{gray!20}\n"));
+        // The widest block, at half the dimension of the input.
+        assert!(tikz.contains("\\block{b1}{2.80}{0.00}{1.50}{2.00}{yellow!40}\n"));
+        assert!(tikz.contains("\\block{b2}{6.20}{0.00}{0.75}{1.00}{yellow!40}\n"));
+        assert!(tikz.contains("{conv\\_2\\\\16x16x32};\n"));
+        assert!(tikz.contains("\\draw[->, thick] (b1-east) -- (b2-west);\n"));
+        assert!(tikz.ends_with("\\end{tikzpicture}\n\\end{document}\n"));
+    }
+
+    #[test]
+    fn test_branches_are_stacked() {
+        let graph: Graph = toml::from_str(
+            r#"
+            [[nodes]]
+            name = "left"
+            op = "conv"
+            filter_size = 1
+
+            [[nodes]]
+            name = "right"
+            op = "conv"
+            inputs = ["input"]
+            filter_size = 1
+
+            [[nodes]]
+            name = "sum"
+            op = "add"
+            inputs = ["left", "right"]
+            "#,
+        )
+        .unwrap();
+        let tikz = export(&graph, None).unwrap();
+        // Without the dimension and the channels, all blocks have the same size.
+        assert!(tikz.contains("\\block{b1}{2.10}{1.75}{0.20}{2.00}{red!30}\n"));
+        assert!(tikz.contains("\\block{b2}{2.10}{-1.75}{0.20}{2.00}{red!30}\n"));
+        assert!(tikz.contains(
+            "\\node[below, align=center, font=\\scriptsize\\bfseries] at (4.30, -1.00) {sum};\n"
+        ));
+    }
+}
@@ -0,0 +1,332 @@
+//! Descriptions fetched from URLs, e.g. from a registry of architectures, behind the `fetch`
+//! feature.
+//!
+//! Paths starting with `http://` or `https://` are downloaded into memory, keeping the name of the
+//! file so that its format is recognized by its extension. Downloads larger than [`MAX_SIZE`] are
+//! given up. Plain HTTP is spoken by a minimal client following redirects, while HTTPS is
+//! delegated to the `curl` executable, which brings the certificates of the system. Without the
+//! feature, URLs are rejected, so that builds which must not access the network cannot be made to.
+use std::path::{Path, PathBuf};
+
+use crate::error::ConvDimError;
+
+/// ## The URL `path` is, if it starts with `http://` or `https://`.
+pub fn url(path: &Path) -> Option<&str> {
+    path.to_str()
+        .filter(|path| path.starts_with("http://") || path.starts_with("https://"))
+}
+
+/// The largest number of bytes downloaded, including the headers of plain HTTP responses.
+pub const MAX_SIZE: u64 = 1 << 30;
+
+/// ## A file downloaded into memory.
+#[derive(Debug)]
+pub struct Download {
+    name: String,
+    bytes: Vec<u8>,
+}
+
+impl Download {
+    /// The name of the downloaded file, whose extension tells its format.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The content of the downloaded file.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+fn invalid(url: &str, message: impl ToString) -> ConvDimError {
+    ConvDimError::Read {
+        path: PathBuf::from(url),
+        message: message.to_string(),
+    }
+}
+
+/// The name of the file at `url`, i.e. the last segment of its path without the query, or
+/// `description.toml` if it has none.
+fn file_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+    match path
+        .split_once('/')
+        .and_then(|(_, path)| path.rsplit('/').next())
+    {
+        Some(name) if !name.is_empty() && name != "." && name != ".." => name.to_string(),
+        _ => "description.toml".to_string(),
+    }
+}
+
+/// ## Download the file at `url` into memory.
+pub fn download(url: &str) -> Result<Download, ConvDimError> {
+    Ok(Download {
+        name: file_name(url),
+        bytes: get(url)?,
+    })
+}
+
+/// The error about a download of `url` exceeding [`MAX_SIZE`].
+#[cfg(feature = "fetch")]
+fn too_large(url: &str) -> ConvDimError {
+    invalid(
+        url,
+        format!("the download exceeds the limit of {} bytes.", MAX_SIZE),
+    )
+}
+
+#[cfg(not(feature = "fetch"))]
+/// The body of the response to a `GET` request of `url`.
+fn get(url: &str) -> Result<Vec<u8>, ConvDimError> {
+    Err(invalid(
+        url,
+        "URLs can only be read if convdim is built with the `fetch` feature.",
+    ))
+}
+
+#[cfg(feature = "fetch")]
+/// The body of the response to a `GET` request of `url`.
+fn get(url: &str) -> Result<Vec<u8>, ConvDimError> {
+    if url.starts_with("https://") {
+        http::curl(url)
+    } else {
+        http::get(url)
+    }
+}
+
+#[cfg(feature = "fetch")]
+mod http {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    use super::{invalid, too_large, MAX_SIZE};
+    use crate::error::ConvDimError;
+
+    /// The number of redirects followed.
+    const REDIRECTS: usize = 5;
+    /// The time after which a server sending nothing is given up.
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// The host, port and target of the `http://` URL `url`.
+    fn split(url: &str) -> Option<(&str, u16, &str)> {
+        let rest = url.strip_prefix("http://")?;
+        let (authority, target) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        let target = target.split('#').next().unwrap_or(target);
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host, port.parse().ok()?),
+            _ => (authority, 80),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        Some((host, port, target)).filter(|(host, _, _)| !host.is_empty())
+    }
+
+    /// A response to a request, with the names of its headers in lowercase.
+    struct Response {
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    /// The HTTP response `bytes`, whose body may be chunked.
+    fn parse(bytes: &[u8]) -> Option<Response> {
+        let end = bytes.windows(4).position(|window| window == b"\r\n\r\n")?;
+        let head = String::from_utf8_lossy(&bytes[..end]);
+        let mut lines = head.split("\r\n");
+        let status = lines.next()?.split(' ').nth(1)?.parse().ok()?;
+        let headers: Vec<(String, String)> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect();
+        let body = &bytes[end + 4..];
+        let chunked = headers
+            .iter()
+            .any(|(name, value)| name == "transfer-encoding" && value.contains("chunked"));
+        if !chunked {
+            let length = headers
+                .iter()
+                .find(|(name, _)| name == "content-length")
+                .and_then(|(_, value)| value.parse().ok())
+                .unwrap_or(body.len());
+            return Some(Response {
+                status,
+                headers,
+                body: body.get(..length)?.to_vec(),
+            });
+        }
+        let (mut decoded, mut rest) = (vec![], body);
+        loop {
+            let line = rest.windows(2).position(|window| window == b"\r\n")?;
+            let size = String::from_utf8_lossy(&rest[..line]);
+            let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+            if size == 0 {
+                return Some(Response {
+                    status,
+                    headers,
+                    body: decoded,
+                });
+            }
+            // The size is sent by the server, so a size past the end of the response is malformed.
+            let end = (line + 2).checked_add(size)?;
+            decoded.extend_from_slice(rest.get(line + 2..end)?);
+            rest = rest.get(end.checked_add(2)?..)?;
+        }
+    }
+
+    /// The body of the response to a `GET` request of the `http://` URL `url`, following redirects.
+    pub(super) fn get(url: &str) -> Result<Vec<u8>, ConvDimError> {
+        let mut location = url.to_string();
+        for _ in 0..=REDIRECTS {
+            let (host, port, target) =
+                split(&location).ok_or_else(|| invalid(url, "the URL is invalid."))?;
+            let mut stream = TcpStream::connect((host, port)).map_err(|e| invalid(url, e))?;
+            stream
+                .set_read_timeout(Some(TIMEOUT))
+                .map_err(|e| invalid(url, e))?;
+            let request = format!(
+                "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: convdim/{}\r\nAccept: */*\r\n\
+                 Connection: close\r\n\r\n",
+                target,
+                host,
+                env!("CARGO_PKG_VERSION")
+            );
+            let mut response = vec![];
+            stream
+                .write_all(request.as_bytes())
+                .and_then(|_| (&mut stream).take(MAX_SIZE + 1).read_to_end(&mut response))
+                .map_err(|e| invalid(url, e))?;
+            if response.len() as u64 > MAX_SIZE {
+                return Err(too_large(url));
+            }
+            let Response {
+                status,
+                headers,
+                body,
+            } = parse(&response).ok_or_else(|| invalid(url, "the response is malformed."))?;
+            let redirect = headers
+                .iter()
+                .find(|(name, _)| name == "location")
+                .map(|(_, value)| value.clone())
+                .filter(|_| matches!(status, 301 | 302 | 303 | 307 | 308));
+            match redirect {
+                Some(target) if target.starts_with('/') => {
+                    location = format!("http://{}:{}{}", host, port, target)
+                }
+                Some(target) if target.starts_with("https://") => return curl(&target),
+                Some(target) => location = target,
+                None if status == 200 => return Ok(body),
+                None => {
+                    return Err(invalid(
+                        url,
+                        format!("the server answered with the status {}.", status),
+                    ))
+                }
+            }
+        }
+        Err(invalid(
+            url,
+            format!("the server redirected more than {} times.", REDIRECTS),
+        ))
+    }
+
+    /// The body of the response to a `GET` request of the `https://` URL `url`, by `curl`.
+    pub(super) fn curl(url: &str) -> Result<Vec<u8>, ConvDimError> {
+        let output = std::process::Command::new("curl")
+            .args(["--fail", "--silent", "--show-error", "--location"])
+            .args(["--max-filesize", &MAX_SIZE.to_string(), "--"])
+            .arg(url)
+            .output()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => {
+                    invalid(url, "fetching over HTTPS needs curl on the PATH.")
+                }
+                _ => invalid(url, e),
+            })?;
+        // curl exits with 63 if the announced size exceeds the limit.
+        if output.status.code() == Some(63) || output.stdout.len() as u64 > MAX_SIZE {
+            return Err(too_large(url));
+        }
+        if !output.status.success() {
+            return Err(invalid(url, String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(output.stdout)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_parse() {
+            assert_eq!(
+                split("http://[::1]:8080/a/b.toml?x#y"),
+                Some(("::1", 8080, "/a/b.toml?x"))
+            );
+            assert_eq!(split("http://host"), Some(("host", 80, "/")));
+            let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                             4\r\nconv\r\n3;x=y\r\ndim\r\n0\r\n\r\n";
+            assert_eq!(parse(response).unwrap().body, b"convdim".to_vec());
+            assert!(parse(b"HTTP/1.1 200 OK\r\n").is_none());
+            let huge = format!(
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\nconv\r\n",
+                usize::MAX - 1
+            );
+            assert!(parse(huge.as_bytes()).is_none());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url() {
+        assert_eq!(
+            url(Path::new("https://registry/nets/unet.onnx?v=2")),
+            Some("https://registry/nets/unet.onnx?v=2")
+        );
+        assert_eq!(url(Path::new("nets/unet.toml")), None);
+        assert_eq!(
+            file_name("https://registry/nets/unet.onnx?v=2"),
+            "unet.onnx"
+        );
+        assert_eq!(file_name("http://registry"), "description.toml");
+        assert_eq!(file_name("http://registry/nets/"), "description.toml");
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn test_download() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let layers = "[[layers]]\ntype = \"conv\"\nfilter_size = 3\nstride = 2\n";
+            let responses = [
+                "HTTP/1.1 302 Found\r\nLocation: /nets/layers.toml\r\nContent-Length: 0\r\n\r\n"
+                    .to_string(),
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                    layers.len(),
+                    layers
+                ),
+            ];
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).unwrap();
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        let url = format!("http://{}/latest", address);
+        let architecture = crate::read_architecture(Path::new(&url)).unwrap();
+        server.join().unwrap();
+        assert_eq!(architecture.into_graph().output_shape(9).unwrap().dim, 4);
+    }
+}
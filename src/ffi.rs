@@ -0,0 +1,420 @@
+//! A C interface to the arithmetic and the analyses, e.g. for C code and the WebAssembly module.
+//!
+//! Every function returns a JSON document allocated by the library, which must be released with
+//! [`convdim_free`]. On success, the document holds the result in its `ok` member, otherwise the
+//! message of the error in its `error` member, e.g. `{"ok": 32}` or `{"error": "..."}`.
+//! Networks are passed as their toml description, in the list or the graph format.
+//!
+//! Hosts that cannot allocate memory of the library themselves, e.g. runtimes of WebAssembly,
+//! write the descriptions into buffers of [`convdim_alloc`]. JavaScript uses the API generated by
+//! wasm-bindgen instead, see the `wasm` feature.
+//!
+//! For C and C++ code, [`convdim_conv_out`] returns a status code instead, and the declarations
+//! of all the functions are in `include/convdim.h`.
+//!
+//! No panic unwinds into the caller, which would abort it: a panic is reported like an error,
+//! by its message prefixed with `Internal error:`.
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use serde::Serialize;
+
+use crate::architecture::{parse_architecture, parse_json_architecture, Architecture};
+use crate::convert::json;
+use crate::error::ConvDimError;
+use crate::layers::{conv_output_dim, transposed_conv_output_dim};
+use crate::summary;
+use crate::trace::outputs;
+
+/// The message of a panic with `payload`.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let message = match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map_or("unknown panic", |message| message)
+            .to_string(),
+    };
+    format!("Internal error: {}", message)
+}
+
+/// The JSON document holding the result of `compute`, or the message of its panic.
+fn respond<T: Serialize>(compute: impl FnOnce() -> Result<T, ConvDimError>) -> *mut c_char {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        compute()
+            .map(toml::Value::try_from)
+            .map_err(|e| e.to_string())
+    }));
+    let (key, value) = match result.map_err(panic_message) {
+        Ok(Ok(Ok(value))) => ("ok", value),
+        Ok(Ok(Err(e))) => ("error", toml::Value::String(e.to_string())),
+        Ok(Err(message)) | Err(message) => ("error", toml::Value::String(message)),
+    };
+    let mut document = toml::value::Table::new();
+    document.insert(key.to_string(), value);
+    // Control characters are escaped in JSON strings, so the document contains no nul byte.
+    CString::new(json(&toml::Value::Table(document), 0))
+        .unwrap()
+        .into_raw()
+}
+
+/// The nul-terminated `description` as a string.
+///
+/// # Safety
+///
+/// `description` must be null or point to a nul-terminated string.
+unsafe fn text<'a>(description: *const c_char) -> Result<&'a str, ConvDimError> {
+    if description.is_null() {
+        return Err(ConvDimError::Parse {
+            message: "the description is null.".to_string(),
+        });
+    }
+    CStr::from_ptr(description)
+        .to_str()
+        .map_err(|e| ConvDimError::Parse {
+            message: e.to_string(),
+        })
+}
+
+/// The architecture described by the nul-terminated toml `description`.
+///
+/// # Safety
+///
+/// `description` must be null or point to a nul-terminated string.
+unsafe fn architecture(description: *const c_char) -> Result<Architecture, ConvDimError> {
+    text(description).and_then(parse_architecture)
+}
+
+/// The status of a successful call.
+pub const CONVDIM_OK: c_int = 0;
+/// The parameters of a layer do not fit its input, e.g. its filter is larger than the padded input.
+pub const CONVDIM_ERROR_LAYER: c_int = 1;
+/// The structure of the network is invalid, e.g. the branches of a parallel block disagree.
+pub const CONVDIM_ERROR_NETWORK: c_int = 2;
+/// The description of the network cannot be read or parsed.
+pub const CONVDIM_ERROR_PARSE: c_int = 3;
+/// A pointer argument is null.
+pub const CONVDIM_ERROR_NULL: c_int = 4;
+/// The library panicked, which is a bug.
+pub const CONVDIM_ERROR_INTERNAL: c_int = 5;
+
+thread_local! {
+    /// The message of the last error of a function returning a status on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The status code of `error`.
+fn status(error: &ConvDimError) -> c_int {
+    match error {
+        ConvDimError::KernelLargerThanInput { .. }
+        | ConvDimError::EmptyInput { .. }
+        | ConvDimError::NegativeOutput { .. }
+        | ConvDimError::ZeroStride { .. }
+        | ConvDimError::ZeroFilterSize { .. }
+        | ConvDimError::Overflow { .. } => CONVDIM_ERROR_LAYER,
+        ConvDimError::Read { .. } | ConvDimError::Parse { .. } => CONVDIM_ERROR_PARSE,
+        _ => CONVDIM_ERROR_NETWORK,
+    }
+}
+
+/// Record the message of `error` for [`convdim_last_error`], returning its status.
+fn fail(status: c_int, message: String) -> c_int {
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+    status
+}
+
+/// ## The output dimension of a convolution applied `repeat` times, see [`conv_output_dim`].
+#[no_mangle]
+pub extern "C" fn convdim_conv_output_dim(
+    in_dim: u32,
+    filter_size: u32,
+    padding: u32,
+    stride: u32,
+    repeat: u32,
+) -> *mut c_char {
+    respond(|| conv_output_dim(in_dim, filter_size, padding, stride, repeat))
+}
+
+/// ## The output dimension of a transposed convolution applied `repeat` times, see
+/// [`transposed_conv_output_dim`].
+#[no_mangle]
+pub extern "C" fn convdim_transposed_conv_output_dim(
+    in_dim: u32,
+    filter_size: u32,
+    padding: u32,
+    stride: u32,
+    repeat: u32,
+) -> *mut c_char {
+    respond(|| transposed_conv_output_dim(in_dim, filter_size, padding, stride, repeat))
+}
+
+/// ## The output dimension of a (transposed) convolution, which is written to `out`.
+///
+/// Returns [`CONVDIM_OK`] or the status of the error, whose message is returned by
+/// [`convdim_last_error`].
+///
+/// # Safety
+///
+/// `out` must be null or point to a writable `u32`.
+#[no_mangle]
+pub unsafe extern "C" fn convdim_conv_out(
+    in_dim: u32,
+    filter_size: u32,
+    padding: u32,
+    stride: u32,
+    transposed: bool,
+    out: *mut u32,
+) -> c_int {
+    if out.is_null() {
+        return fail(
+            CONVDIM_ERROR_NULL,
+            "The output pointer is null.".to_string(),
+        );
+    }
+    let dim = catch_unwind(|| {
+        if transposed {
+            transposed_conv_output_dim(in_dim, filter_size, padding, stride, 1)
+        } else {
+            conv_output_dim(in_dim, filter_size, padding, stride, 1)
+        }
+    });
+    match dim {
+        Ok(Ok(dim)) => {
+            *out = dim;
+            CONVDIM_OK
+        }
+        Ok(Err(e)) => fail(status(&e), e.to_string()),
+        Err(payload) => fail(CONVDIM_ERROR_INTERNAL, panic_message(payload)),
+    }
+}
+
+/// ## The message of the last error of [`convdim_conv_out`] on the calling thread.
+///
+/// The message is owned by the library and valid until the next error on the thread. Returns
+/// null if no error occurred yet.
+#[no_mangle]
+pub extern "C" fn convdim_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// ## The outputs of all the layers or nodes of the network `description` in JSON.
+///
+/// Like [`convdim_trace`], but the description is given in JSON with the same structure as the
+/// toml description, e.g. `{"layers": [{"type": "conv", "filter_size": 3}]}`.
+///
+/// # Safety
+///
+/// `description` must be null or point to a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn convdim_eval_network_json(
+    description: *const c_char,
+    in_dim: u32,
+) -> *mut c_char {
+    respond(|| {
+        text(description)
+            .and_then(parse_json_architecture)
+            .and_then(|architecture| outputs(architecture, in_dim))
+    })
+}
+
+/// ## The outputs of all the layers or nodes of the network `description`, like `--trace`.
+///
+/// The outputs are objects with the `name`, the `dim` and, for graphs, the `channels`.
+///
+/// # Safety
+///
+/// `description` must be null or point to a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn convdim_trace(description: *const c_char, in_dim: u32) -> *mut c_char {
+    respond(|| architecture(description).and_then(|architecture| outputs(architecture, in_dim)))
+}
+
+/// ## The summary rows of the network `description`, see [`summary::rows`].
+///
+/// The rows hold the shapes, parameters, operations and receptive fields of the inputs and nodes.
+///
+/// # Safety
+///
+/// `description` must be null or point to a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn convdim_summary(description: *const c_char, in_dim: u32) -> *mut c_char {
+    respond(|| {
+        architecture(description).and_then(|architecture| {
+            let graph = architecture.into_graph();
+            summary::rows(&graph, in_dim).and_then(|rows| {
+                toml::Value::try_from(rows).map_err(|e| ConvDimError::Parse {
+                    message: e.to_string(),
+                })
+            })
+        })
+    })
+}
+
+/// ## Allocate a zeroed buffer for a description of `len` bytes, followed by its nul byte.
+///
+/// The buffer is released with [`convdim_dealloc`] and the same `len`. Returns null if `len` is
+/// too large.
+#[no_mangle]
+pub extern "C" fn convdim_alloc(len: usize) -> *mut c_char {
+    catch_unwind(|| {
+        let buffer = vec![0 as c_char; len.checked_add(1).expect("The buffer is too large.")];
+        Box::into_raw(buffer.into_boxed_slice()) as *mut c_char
+    })
+    .unwrap_or(std::ptr::null_mut())
+}
+
+/// ## Release a `buffer` of `len` bytes allocated by [`convdim_alloc`].
+///
+/// # Safety
+///
+/// `buffer` must have been returned by [`convdim_alloc`] for the same `len`, and must not be
+/// released twice.
+#[no_mangle]
+pub unsafe extern "C" fn convdim_dealloc(buffer: *mut c_char, len: usize) {
+    drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+        buffer,
+        len + 1,
+    )));
+}
+
+/// ## Release a `response` returned by the functions of this module.
+///
+/// # Safety
+///
+/// `response` must be null or have been returned by a function of this module, and must not be
+/// released twice.
+#[no_mangle]
+pub unsafe extern "C" fn convdim_free(response: *mut c_char) {
+    if !response.is_null() {
+        drop(CString::from_raw(response));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The response as a string, releasing it.
+    fn read(response: *mut c_char) -> String {
+        unsafe {
+            let text = CStr::from_ptr(response).to_str().unwrap().to_string();
+            convdim_free(response);
+            text
+        }
+    }
+
+    #[test]
+    fn test_status_codes() {
+        let mut dim = 0;
+        assert_eq!(
+            unsafe { convdim_conv_out(32, 2, 0, 2, true, &mut dim) },
+            CONVDIM_OK
+        );
+        assert_eq!(dim, 64);
+        assert_eq!(
+            unsafe { convdim_conv_out(64, 3, 1, 0, false, &mut dim) },
+            CONVDIM_ERROR_LAYER
+        );
+        let message = unsafe { CStr::from_ptr(convdim_last_error()) };
+        assert_eq!(
+            message.to_str().unwrap(),
+            "The stride of 0 does not move the filter."
+        );
+
+        let description =
+            CString::new(r#"{"layers": [{"type": "max_pool", "filter_size": 2, "stride": 2}]}"#)
+                .unwrap();
+        assert!(
+            read(unsafe { convdim_eval_network_json(description.as_ptr(), 64) })
+                .contains("\"dim\": 32")
+        );
+        let nested = CString::new("[".repeat(100_000)).unwrap();
+        assert!(
+            read(unsafe { convdim_eval_network_json(nested.as_ptr(), 64) })
+                .contains("recursion limit exceeded")
+        );
+    }
+
+    #[test]
+    fn test_panics_are_errors() {
+        let response = respond(|| -> Result<u32, ConvDimError> { panic!("a bug") });
+        assert_eq!(
+            read(response),
+            "{\n  \"error\": \"Internal error: a bug\"\n}"
+        );
+        assert!(convdim_alloc(usize::MAX).is_null());
+
+        let description = CString::new(
+            "input_channels = 3\n[[nodes]]\nname = \"conv\"\nop = \"conv\"\nfilter_size = 1\n",
+        )
+        .unwrap();
+        assert!(
+            read(unsafe { convdim_summary(description.as_ptr(), 4_000_000_000) })
+                .starts_with("{\n  \"error\": \"Unable to summarize the network")
+        );
+    }
+
+    #[test]
+    fn test_header_declares_the_functions() {
+        let header = include_str!("../include/convdim.h");
+        let source = include_str!("ffi.rs");
+        let functions = source
+            .lines()
+            .filter(|line| line.starts_with("pub ") && line.contains(r#"extern "C" fn "#));
+        for line in functions {
+            let name = line.split("fn ").nth(1).unwrap().split('(').next().unwrap();
+            assert!(header.contains(&format!("{}(", name)), "{}", name);
+        }
+        for line in source
+            .lines()
+            .filter(|line| line.starts_with("pub const CONVDIM_"))
+        {
+            let name = line
+                .split_whitespace()
+                .nth(2)
+                .unwrap()
+                .trim_end_matches(':');
+            assert!(header.contains(&format!("#define {} ", name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_responses() {
+        assert_eq!(
+            read(convdim_conv_output_dim(64, 3, 1, 2, 1)),
+            "{\n  \"ok\": 32\n}"
+        );
+        assert_eq!(
+            read(convdim_conv_output_dim(3, 7, 1, 1, 1)),
+            "{\n  \"error\": \"The padded input (5) is smaller than the kernel (7), for an input of \
+             dimension 3 padded by 1.\"\n}"
+        );
+
+        let description = CString::new(
+            "[[layers]]\nname = \"stem\"\ntype = \"conv\"\nfilter_size = 3\nstride = 2\npadding = 1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            read(unsafe { convdim_trace(description.as_ptr(), 64) }),
+            "{\n  \"ok\": [\n    {\n      \"name\": \"stem\",\n      \"dim\": 32\n    }\n  ]\n}"
+        );
+        let summary = read(unsafe { convdim_summary(description.as_ptr(), 64) });
+        assert!(summary.contains("\"receptive_field\": 3"));
+        assert!(read(unsafe { convdim_trace(std::ptr::null(), 64) }).contains("\"error\""));
+
+        let text = description.as_bytes();
+        let buffer = convdim_alloc(text.len());
+        unsafe {
+            std::ptr::copy_nonoverlapping(text.as_ptr() as *const c_char, buffer, text.len());
+            assert!(read(convdim_trace(buffer, 64)).contains("\"dim\": 32"));
+            convdim_dealloc(buffer, text.len());
+        }
+    }
+}
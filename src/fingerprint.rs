@@ -0,0 +1,177 @@
+//! Fingerprints of the structure of networks, e.g. to tag experiment runs with their architecture
+//! or to find out whether two descriptions define the same network.
+//!
+//! The fingerprint is the SHA-256 hash of a normalized text of the graph, which lists its inputs,
+//! its nodes in their order and its outputs. The names of the inputs and nodes are replaced by
+//! their positions, so comments, the order of the fields, the names and the format of the
+//! description don't change the fingerprint, and a list of layers has the fingerprint of the
+//! equivalent graph.
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::graph::{Graph, Op, INPUT};
+
+/// The SHA-256 hash of `bytes` in hexadecimal.
+pub(crate) fn sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// `value` or `-` if it is unknown.
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
+
+/// ## The normalized text of `graph`, whose inputs and nodes are referred to by their positions.
+///
+/// Every line describes an input (`input0`, ...) with its dimension and channels, a node
+/// (`node0`, ...) with its operation, the geometry of a convolution, its channels and inputs, or
+/// the outputs of the graph.
+pub fn normalize(graph: &Graph) -> String {
+    let mut references: HashMap<&str, String> = HashMap::new();
+    let mut text = String::new();
+    if graph.inputs.is_empty() {
+        references.insert(INPUT, "input0".to_string());
+        text += &format!("input0 dim=- channels={}\n", optional(graph.input_channels));
+    }
+    for (i, input) in graph.inputs.iter().enumerate() {
+        references.insert(&input.name, format!("input{}", i));
+        text += &format!(
+            "input{} dim={} channels={}\n",
+            i,
+            optional(input.input_dim),
+            optional(input.channels)
+        );
+    }
+    let edges = graph.edges();
+    for (i, node) in graph.nodes.iter().enumerate() {
+        let inputs: Vec<&str> = edges[node.name.as_str()]
+            .iter()
+            .map(|input| references.get(input).map_or(*input, String::as_str))
+            .collect();
+        // Only the (transposed) convolutions have a geometry, the other operations ignore it.
+        let geometry = match node.op {
+            Op::Conv | Op::Transposed => {
+                let formula = node
+                    .formula
+                    .as_ref()
+                    .map(|formula| formula.to_string().split_whitespace().collect::<String>());
+                format!(
+                    " filter_size={} stride={} padding={} formula={}",
+                    optional(node.filter_size),
+                    node.stride,
+                    node.padding,
+                    optional(formula)
+                )
+            }
+            _ => String::new(),
+        };
+        text += &format!(
+            "node{} op={:?}{} channels={} inputs={}\n",
+            i,
+            node.op,
+            geometry,
+            optional(node.channels),
+            inputs.join(",")
+        );
+        references.insert(&node.name, format!("node{}", i));
+    }
+    let outputs: Vec<&str> = if graph.outputs.is_empty() {
+        graph
+            .nodes
+            .last()
+            .map(|node| node.name.as_str())
+            .into_iter()
+            .collect()
+    } else {
+        graph.outputs.iter().map(String::as_str).collect()
+    };
+    let outputs: Vec<&str> = outputs
+        .iter()
+        .map(|output| references.get(output).map_or(*output, String::as_str))
+        .collect();
+    text + &format!("outputs={}\n", outputs.join(","))
+}
+
+/// ## The fingerprint of `graph`: the SHA-256 hash of its [normalized](normalize) text in hexadecimal.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{fingerprint, Architecture, Network};
+/// let network = Network::builder().conv(3, 2, 1).conv(3, 2, 1).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let other = Network::builder().conv(3, 2, 1).conv(3, 1, 1).build();
+/// let other = Architecture::Layers(other.layers().clone()).into_graph();
+/// assert_eq!(fingerprint::hash(&graph).len(), 64);
+/// assert_ne!(fingerprint::hash(&graph), fingerprint::hash(&other));
+/// ```
+pub fn hash(graph: &Graph) -> String {
+    sha256(normalize(graph).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn test_hash() {
+        let layers = parse_architecture(
+            r#"
+            # A comment.
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            stride = 2
+            channels = 8
+
+            [[layers]]
+            name = "act"
+            type = "relu"
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let nodes = parse_architecture(
+            r#"
+            [[nodes]]
+            name = "c"
+            op = "conv"
+            channels = 8
+            stride = 2
+            filter_size = 3
+
+            [[nodes]]
+            name = "r"
+            op = "relu"
+            inputs = ["c"]
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        assert_eq!(
+            normalize(&nodes),
+            "input0 dim=- channels=-\n\
+             node0 op=Conv filter_size=3 stride=2 padding=0 formula=- channels=8 inputs=input0\n\
+             node1 op=Relu channels=- inputs=node0\n\
+             outputs=node1\n"
+        );
+        assert_eq!(hash(&layers), hash(&nodes));
+    }
+}
@@ -0,0 +1,273 @@
+//! A minimal reader for the FlatBuffers binary format.
+//!
+//! A flatbuffer is a tree of tables, which are read in place: every table starts with the offset
+//! of its vtable, which gives the position of each field within the table (or 0 for fields that
+//! are absent and take their default value). Fields are identified by their index in the schema,
+//! whose interpretation is left to the caller (e.g. the TensorFlow Lite model format).
+
+/// Read the little-endian unsigned integer of `n` bytes at `pos`.
+fn uint(bytes: &[u8], pos: usize, n: usize) -> Result<u64, String> {
+    match bytes.get(pos..pos + n) {
+        Some(slice) => Ok(slice
+            .iter()
+            .rev()
+            .fold(0, |value, &byte| (value << 8) | byte as u64)),
+        None => Err("Unexpected end of the flatbuffer.".to_string()),
+    }
+}
+
+/// Follow the unsigned offset stored at `pos`, which is relative to `pos`.
+fn follow(bytes: &[u8], pos: usize) -> Result<usize, String> {
+    Ok(pos + uint(bytes, pos, 4)? as usize)
+}
+
+#[derive(Debug, Clone, Copy)]
+/// ## A table of a flatbuffer.
+pub struct Table<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    vtable: usize,
+}
+
+impl<'a> Table<'a> {
+    /// The table at `pos`, whose vtable is at the (signed) offset stored at its start.
+    fn at(bytes: &'a [u8], pos: usize) -> Result<Table<'a>, String> {
+        let offset = uint(bytes, pos, 4)? as u32 as i32 as i64;
+        let vtable = pos as i64 - offset;
+        if vtable < 0 {
+            return Err("Invalid vtable offset in the flatbuffer.".to_string());
+        }
+        Ok(Table {
+            bytes,
+            pos,
+            vtable: vtable as usize,
+        })
+    }
+
+    /// ## The root table of the flatbuffer in `bytes`.
+    pub fn root(bytes: &'a [u8]) -> Result<Table<'a>, String> {
+        Table::at(bytes, follow(bytes, 0)?)
+    }
+
+    /// The file identifier following the offset of the root table, e.g. `TFL3`.
+    pub fn identifier(bytes: &[u8]) -> Option<&[u8]> {
+        bytes.get(4..8)
+    }
+
+    /// The position of the field with index `field`, or `None` if it is absent.
+    fn field(&self, field: usize) -> Result<Option<usize>, String> {
+        let size = uint(self.bytes, self.vtable, 2)? as usize;
+        let entry = 4 + 2 * field;
+        if entry + 2 > size {
+            // Fields added to the schema after the buffer was written are absent.
+            return Ok(None);
+        }
+        match uint(self.bytes, self.vtable + entry, 2)? {
+            0 => Ok(None),
+            offset => Ok(Some(self.pos + offset as usize)),
+        }
+    }
+
+    /// The unsigned scalar of `n` bytes in the field `field`, or `default` if it is absent.
+    pub fn uint(&self, field: usize, n: usize, default: u64) -> Result<u64, String> {
+        match self.field(field)? {
+            Some(pos) => uint(self.bytes, pos, n),
+            None => Ok(default),
+        }
+    }
+
+    /// The 32 bit signed integer in the field `field`, or `default` if it is absent.
+    pub fn int(&self, field: usize, default: i32) -> Result<i32, String> {
+        Ok(self.uint(field, 4, default as u32 as u64)? as u32 as i32)
+    }
+
+    /// The byte (e.g. of an enum or union type) in the field `field`, or `default` if it is absent.
+    pub fn byte(&self, field: usize, default: u8) -> Result<u8, String> {
+        Ok(self.uint(field, 1, default as u64)? as u8)
+    }
+
+    /// The table in the field `field`.
+    pub fn table(&self, field: usize) -> Result<Option<Table<'a>>, String> {
+        match self.field(field)? {
+            Some(pos) => Table::at(self.bytes, follow(self.bytes, pos)?).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The position and length of the vector in the field `field`, which is empty if absent.
+    fn vector(&self, field: usize) -> Result<(usize, usize), String> {
+        match self.field(field)? {
+            Some(pos) => {
+                let start = follow(self.bytes, pos)?;
+                Ok((start + 4, uint(self.bytes, start, 4)? as usize))
+            }
+            None => Ok((0, 0)),
+        }
+    }
+
+    /// The vector of tables in the field `field`.
+    pub fn tables(&self, field: usize) -> Result<Vec<Table<'a>>, String> {
+        let (start, length) = self.vector(field)?;
+        (0..length)
+            .map(|i| Table::at(self.bytes, follow(self.bytes, start + 4 * i)?))
+            .collect()
+    }
+
+    /// The vector of 32 bit signed integers in the field `field`.
+    pub fn ints(&self, field: usize) -> Result<Vec<i32>, String> {
+        let (start, length) = self.vector(field)?;
+        (0..length)
+            .map(|i| Ok(uint(self.bytes, start + 4 * i, 4)? as u32 as i32))
+            .collect()
+    }
+
+    /// The vector of bytes in the field `field`.
+    pub fn bytes(&self, field: usize) -> Result<&'a [u8], String> {
+        let (start, length) = self.vector(field)?;
+        self.bytes
+            .get(start..start + length)
+            .ok_or_else(|| "Unexpected end of the flatbuffer.".to_string())
+    }
+
+    /// The string in the field `field`.
+    pub fn string(&self, field: usize) -> Result<Option<String>, String> {
+        if self.field(field)?.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(
+            String::from_utf8_lossy(self.bytes(field)?).into_owned(),
+        ))
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone)]
+/// ## A field of a table to be written by [`write`].
+pub enum Field {
+    /// A scalar, written as 32 bits, so that it can also be read as a byte.
+    Int(i32),
+    Table(Vec<Option<Field>>),
+    Tables(Vec<Vec<Option<Field>>>),
+    Ints(Vec<i32>),
+    Bytes(Vec<u8>),
+    String(String),
+}
+
+#[cfg(test)]
+/// ## Write the flatbuffer with the root table `fields` and the file `identifier`.
+///
+/// Every table is preceded by its vtable and followed by the values it refers to, so that all
+/// offsets point forward as the format requires.
+pub fn write(identifier: &[u8; 4], fields: &[Option<Field>]) -> Vec<u8> {
+    fn patch(bytes: &mut [u8], pos: usize, target: usize) {
+        bytes[pos..pos + 4].copy_from_slice(&((target - pos) as u32).to_le_bytes());
+    }
+    fn vector(bytes: &mut Vec<u8>, length: usize) {
+        bytes.extend_from_slice(&(length as u32).to_le_bytes());
+    }
+    fn table(bytes: &mut Vec<u8>, fields: &[Option<Field>]) -> usize {
+        // The vtable: its size, the size of the table and the offset of every field.
+        let vtable = bytes.len();
+        let mut offsets = vec![];
+        let mut offset = 4;
+        for field in fields {
+            offsets.push(if field.is_some() { offset } else { 0 });
+            if field.is_some() {
+                offset += 4;
+            }
+        }
+        for value in [4 + 2 * fields.len(), offset].iter().chain(&offsets) {
+            bytes.extend_from_slice(&(*value as u16).to_le_bytes());
+        }
+        let start = bytes.len();
+        bytes.extend_from_slice(&((start - vtable) as i32).to_le_bytes());
+        let mut references = vec![];
+        for field in fields.iter().flatten() {
+            let pos = bytes.len();
+            match field {
+                Field::Int(value) => bytes.extend_from_slice(&value.to_le_bytes()),
+                _ => {
+                    bytes.extend_from_slice(&[0; 4]);
+                    references.push((pos, field));
+                }
+            }
+        }
+        for (pos, field) in references {
+            let target = match field {
+                Field::Int(_) => unreachable!(),
+                Field::Table(fields) => table(bytes, fields),
+                Field::Tables(tables) => {
+                    let target = bytes.len();
+                    vector(bytes, tables.len());
+                    bytes.extend(vec![0; 4 * tables.len()]);
+                    for (i, fields) in tables.iter().enumerate() {
+                        let element = table(bytes, fields);
+                        patch(bytes, target + 4 + 4 * i, element);
+                    }
+                    target
+                }
+                Field::Ints(values) => {
+                    let target = bytes.len();
+                    vector(bytes, values.len());
+                    for value in values {
+                        bytes.extend_from_slice(&value.to_le_bytes());
+                    }
+                    target
+                }
+                Field::Bytes(values) => {
+                    let target = bytes.len();
+                    vector(bytes, values.len());
+                    bytes.extend_from_slice(values);
+                    target
+                }
+                Field::String(value) => {
+                    let target = bytes.len();
+                    vector(bytes, value.len());
+                    bytes.extend_from_slice(value.as_bytes());
+                    bytes.push(0);
+                    target
+                }
+            };
+            patch(bytes, pos, target);
+        }
+        start
+    }
+    let mut bytes = vec![0; 4];
+    bytes.extend_from_slice(identifier);
+    let root = table(&mut bytes, fields);
+    patch(&mut bytes, 0, root);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_written_buffer() {
+        let bytes = write(
+            b"TEST",
+            &[
+                Some(Field::Int(-3)),
+                None,
+                Some(Field::String("conv".to_string())),
+                Some(Field::Tables(vec![
+                    vec![Some(Field::Ints(vec![1, 224, 224, 3]))],
+                    vec![None, Some(Field::Bytes(vec![7, 8]))],
+                ])),
+            ],
+        );
+        assert_eq!(Table::identifier(&bytes), Some(&b"TEST"[..]));
+        let root = Table::root(&bytes).unwrap();
+        assert_eq!(root.int(0, 0), Ok(-3));
+        assert_eq!(root.byte(1, 5), Ok(5));
+        assert_eq!(root.string(2), Ok(Some("conv".to_string())));
+        assert_eq!(root.int(7, 1), Ok(1));
+        let tables = root.tables(3).unwrap();
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].ints(0), Ok(vec![1, 224, 224, 3]));
+        assert_eq!(tables[1].ints(0), Ok(vec![]));
+        assert_eq!(tables[1].bytes(1), Ok(&[7, 8][..]));
+        assert!(Table::root(&bytes[..10]).is_err());
+    }
+}
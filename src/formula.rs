@@ -0,0 +1,555 @@
+//! Inline formulas for the output dimension of layers, for operations without a built-in type.
+//!
+//! A formula is an expression of the input dimension `n` and of the filter size `f`, the stride `s`
+//! and the padding `p` of its layer, e.g. `ceil((n + 2*p - f) / s) + 1` for a pooling that rounds
+//! up. Expressions consist of integers, the four arithmetic operations, powers with `^`, parentheses
+//! and the functions `floor`, `ceil`, `min` and `max`. They are evaluated exactly on fractions, so
+//! divisions have to be rounded with `floor` or `ceil` where the output would not be an integer.
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConvDimError;
+
+/// The variables of formulas: the input dimension, the filter size, the stride and the padding.
+pub const VARIABLES: [&str; 4] = ["n", "f", "s", "p"];
+
+/// The functions of formulas, with their number of arguments.
+const FUNCTIONS: [(&str, usize); 4] = [("floor", 1), ("ceil", 1), ("min", 2), ("max", 2)];
+
+const OVERFLOW: &str = "the formula exceeds the range of its arithmetic";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// An exact fraction, whose denominator is positive and coprime to its numerator.
+struct Ratio {
+    num: i128,
+    den: i128,
+}
+
+pub(crate) fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl Ratio {
+    fn integer(value: i128) -> Ratio {
+        Ratio { num: value, den: 1 }
+    }
+
+    fn new(num: Option<i128>, den: Option<i128>) -> Result<Ratio, String> {
+        let (num, den) = num.zip(den).ok_or(OVERFLOW)?;
+        if den == 0 {
+            return Err("the formula divides by zero".to_string());
+        }
+        let divisor = gcd(num, den) * den.signum();
+        Ok(Ratio {
+            num: num / divisor,
+            den: den / divisor,
+        })
+    }
+
+    fn add(self, other: Ratio) -> Result<Ratio, String> {
+        let num = self
+            .num
+            .checked_mul(other.den)
+            .zip(other.num.checked_mul(self.den))
+            .and_then(|(a, b)| a.checked_add(b));
+        Ratio::new(num, self.den.checked_mul(other.den))
+    }
+
+    fn neg(self) -> Ratio {
+        Ratio {
+            num: -self.num,
+            den: self.den,
+        }
+    }
+
+    fn mul(self, other: Ratio) -> Result<Ratio, String> {
+        Ratio::new(
+            self.num.checked_mul(other.num),
+            self.den.checked_mul(other.den),
+        )
+    }
+
+    fn div(self, other: Ratio) -> Result<Ratio, String> {
+        self.mul(Ratio::new(Some(other.den), Some(other.num))?)
+    }
+
+    fn pow(self, exponent: Ratio) -> Result<Ratio, String> {
+        if exponent.den != 1 || exponent.num < 0 {
+            return Err("the exponents of the formula need to be natural numbers".to_string());
+        }
+        let exponent = u32::try_from(exponent.num).map_err(|_| OVERFLOW)?;
+        Ratio::new(
+            self.num.checked_pow(exponent),
+            self.den.checked_pow(exponent),
+        )
+    }
+
+    fn cmp(self, other: Ratio) -> Result<Ordering, String> {
+        match (
+            self.num.checked_mul(other.den),
+            other.num.checked_mul(self.den),
+        ) {
+            (Some(a), Some(b)) => Ok(a.cmp(&b)),
+            _ => Err(OVERFLOW.to_string()),
+        }
+    }
+
+    fn floor(self) -> Ratio {
+        Ratio::integer(self.num.div_euclid(self.den))
+    }
+
+    fn ceil(self) -> Ratio {
+        Ratio::integer(-(-self.num).div_euclid(self.den))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The parsed expression of a formula.
+enum Expr {
+    Number(i128),
+    /// A variable, by its index in [`VARIABLES`].
+    Variable(usize),
+    Neg(Box<Expr>),
+    /// An arithmetic operation, `+`, `-`, `*`, `/` or `^`.
+    Binary(char, Box<Expr>, Box<Expr>),
+    /// A function, by its index in [`FUNCTIONS`], with its arguments.
+    Call(usize, Vec<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, values: &[i128; 4]) -> Result<Ratio, String> {
+        match self {
+            Expr::Number(number) => Ok(Ratio::integer(*number)),
+            Expr::Variable(index) => Ok(Ratio::integer(values[*index])),
+            Expr::Neg(operand) => Ok(operand.eval(values)?.neg()),
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.eval(values)?, right.eval(values)?);
+                match op {
+                    '+' => left.add(right),
+                    '-' => left.add(right.neg()),
+                    '*' => left.mul(right),
+                    '/' => left.div(right),
+                    _ => left.pow(right),
+                }
+            }
+            Expr::Call(function, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.eval(values))
+                    .collect::<Result<Vec<Ratio>, String>>()?;
+                match FUNCTIONS[*function].0 {
+                    "floor" => Ok(arguments[0].floor()),
+                    "ceil" => Ok(arguments[0].ceil()),
+                    "min" if arguments[0].cmp(arguments[1])? == Ordering::Greater => {
+                        Ok(arguments[1])
+                    }
+                    "max" if arguments[0].cmp(arguments[1])? == Ordering::Less => Ok(arguments[1]),
+                    _ => Ok(arguments[0]),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i128),
+    Name(String),
+    Symbol(char),
+}
+
+/// Append the characters accepted by `accept` to `word`.
+fn take_while(word: &mut String, chars: &mut Peekable<CharIndices>, accept: fn(char) -> bool) {
+    while let Some(&(_, c)) = chars.peek().filter(|(_, c)| accept(*c)) {
+        word.push(c);
+        chars.next();
+    }
+}
+
+/// Split `text` into tokens, together with their (one-based) positions.
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut word = c.to_string();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '0'..='9' => {
+                take_while(&mut word, &mut chars, |c| c.is_ascii_digit());
+                Token::Number(
+                    word.parse()
+                        .map_err(|_| format!("the number {} is too large", word))?,
+                )
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                take_while(&mut word, &mut chars, |c| c.is_alphanumeric() || c == '_');
+                Token::Name(word)
+            }
+            '+' | '-' | '*' | '/' | '^' | '(' | ')' | ',' => Token::Symbol(c),
+            c => {
+                return Err(format!(
+                    "unexpected '{}' at position {}",
+                    c,
+                    text[..start].chars().count() + 1
+                ))
+            }
+        };
+        tokens.push((text[..start].chars().count() + 1, token));
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser of the tokens of a formula.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    /// Consume the next token if it is the `symbol`.
+    fn eat(&mut self, symbol: char) -> bool {
+        let found = self.peek() == Some(&Token::Symbol(symbol));
+        self.next += usize::from(found);
+        found
+    }
+
+    /// The error for the next token, which is not expected.
+    fn unexpected(&self) -> String {
+        match self.tokens.get(self.next) {
+            Some((position, token)) => {
+                let token = match token {
+                    Token::Number(number) => number.to_string(),
+                    Token::Name(name) => name.clone(),
+                    Token::Symbol(symbol) => symbol.to_string(),
+                };
+                format!("unexpected '{}' at position {}", token, position)
+            }
+            None => "unexpected end of the formula".to_string(),
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// A sum or difference of terms.
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut expr = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol(op @ ('+' | '-'))) => *op,
+                _ => return Ok(expr),
+            };
+            self.next += 1;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.term()?));
+        }
+    }
+
+    /// A product or quotient of factors.
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut expr = self.factor()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol(op @ ('*' | '/'))) => *op,
+                _ => return Ok(expr),
+            };
+            self.next += 1;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.factor()?));
+        }
+    }
+
+    /// A negated factor, or a power, whose exponent binds to the right.
+    fn factor(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.factor()?)));
+        }
+        let base = self.atom()?;
+        if self.eat('^') {
+            Ok(Expr::Binary('^', Box::new(base), Box::new(self.factor()?)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    /// A number, a variable, a call or a parenthesized expression.
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.peek().cloned() {
+            Some(Token::Number(number)) => {
+                self.next += 1;
+                Ok(Expr::Number(number))
+            }
+            Some(Token::Name(name)) => {
+                self.next += 1;
+                if self.eat('(') {
+                    self.call(&name)
+                } else {
+                    match VARIABLES.iter().position(|variable| *variable == name) {
+                        Some(index) => Ok(Expr::Variable(index)),
+                        None => Err(format!(
+                            "unknown variable '{}', expected one of {}",
+                            name,
+                            VARIABLES.join(", ")
+                        )),
+                    }
+                }
+            }
+            Some(Token::Symbol('(')) => {
+                self.next += 1;
+                let expr = self.expr()?;
+                self.expect(')')?;
+                Ok(expr)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// The call of the function `name`, whose opening parenthesis is consumed.
+    fn call(&mut self, name: &str) -> Result<Expr, String> {
+        let function = FUNCTIONS
+            .iter()
+            .position(|(function, _)| *function == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = FUNCTIONS.iter().map(|(function, _)| *function).collect();
+                format!(
+                    "unknown function '{}', expected one of {}",
+                    name,
+                    names.join(", ")
+                )
+            })?;
+        let mut arguments = vec![self.expr()?];
+        while self.eat(',') {
+            arguments.push(self.expr()?);
+        }
+        self.expect(')')?;
+        let count = FUNCTIONS[function].1;
+        if arguments.len() != count {
+            return Err(format!(
+                "'{}' takes {} argument{}, but is given {}",
+                name,
+                count,
+                if count == 1 { "" } else { "s" },
+                arguments.len()
+            ));
+        }
+        Ok(Expr::Call(function, arguments))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+/// ## A formula for the output dimension of a layer, parsed from its text.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::formula::Formula;
+/// let formula = Formula::parse("ceil((n + 2*p - f) / s) + 1").unwrap();
+/// assert_eq!(formula.output_dim(28, 3, 2, 0), Ok(14));
+/// assert!(Formula::parse("floor(n / 2").is_err());
+/// ```
+pub struct Formula {
+    text: String,
+    expr: Expr,
+}
+
+impl Formula {
+    /// ## Parse the formula `text`, with an error describing the first problem.
+    pub fn parse(text: &str) -> Result<Formula, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            next: 0,
+        };
+        let expr = parser.expr()?;
+        if parser.next < parser.tokens.len() {
+            return Err(parser.unexpected());
+        }
+        Ok(Formula {
+            text: text.to_string(),
+            expr,
+        })
+    }
+
+    /// The text of the formula, as it was written.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// The text of the formula with the input dimension replaced by `input` and the filter size,
+    /// the stride and the padding by their values in `geometry`.
+    pub(crate) fn substitute(&self, input: &str, geometry: (u16, u16, u16)) -> String {
+        let values = [
+            input.to_string(),
+            geometry.0.to_string(),
+            geometry.1.to_string(),
+            geometry.2.to_string(),
+        ];
+        let mut text = String::new();
+        let mut chars = self.text.char_indices().peekable();
+        while let Some((_, c)) = chars.next() {
+            let mut word = c.to_string();
+            if c.is_alphabetic() || c == '_' {
+                take_while(&mut word, &mut chars, |c| c.is_alphanumeric() || c == '_');
+            }
+            match VARIABLES.iter().position(|variable| *variable == word) {
+                Some(index) => text += &values[index],
+                None => text += &word,
+            }
+        }
+        text
+    }
+
+    /// The integer value of a formula of the input dimension `n` alone, if it has one.
+    pub(crate) fn value(&self, n: i128) -> Option<i128> {
+        let value = self.expr.eval(&[n, 0, 0, 0]).ok()?;
+        if value.den == 1 {
+            Some(value.num)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the formula gives at least `dim` for an input of dimension `in_dim`, where
+    /// formulas that cannot be evaluated don't reach any dimension.
+    pub(crate) fn reaches(
+        &self,
+        in_dim: u32,
+        (filter_size, stride, padding): (u16, u16, u16),
+        dim: u32,
+    ) -> bool {
+        let values = [in_dim, filter_size.into(), stride.into(), padding.into()].map(i128::from);
+        self.expr
+            .eval(&values)
+            .and_then(|value| value.cmp(Ratio::integer(dim.into())))
+            .is_ok_and(|ordering| ordering != Ordering::Less)
+    }
+
+    /// ## The output dimension for an input of dimension `in_dim` through a layer with the given
+    /// `filter_size`, `stride` and `padding`.
+    ///
+    /// Returns an error if the formula does not give a strictly positive integer within the range
+    /// of the dimensions.
+    pub fn output_dim(
+        &self,
+        in_dim: u32,
+        filter_size: u16,
+        stride: u16,
+        padding: u16,
+    ) -> Result<u32, ConvDimError> {
+        let error = |message: String| ConvDimError::Formula {
+            layer: None,
+            message,
+        };
+        let values = [in_dim, filter_size.into(), stride.into(), padding.into()].map(i128::from);
+        let dim = self.expr.eval(&values).map_err(error)?;
+        if dim.den != 1 {
+            return Err(error(format!(
+                "the formula '{}' gives the fraction {}/{} for an input of dimension {}, which \
+                 needs to be rounded with floor or ceil.",
+                self.text, dim.num, dim.den, in_dim
+            )));
+        }
+        match u32::try_from(dim.num) {
+            Ok(dim) if dim > 0 => Ok(dim),
+            _ => Err(error(format!(
+                "the formula '{}' gives the dimension {} for an input of dimension {}.",
+                self.text, dim.num, in_dim
+            ))),
+        }
+    }
+}
+
+impl TryFrom<String> for Formula {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Formula, String> {
+        Formula::parse(&text).map_err(|e| format!("invalid formula '{}': {}", text, e))
+    }
+}
+
+impl From<Formula> for String {
+    fn from(formula: Formula) -> String {
+        formula.text
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::conv_output_dim;
+
+    #[test]
+    fn test_formula_agrees_with_convolutions() {
+        let formula = Formula::parse("floor((n + 2*p - f)/s) + 1").unwrap();
+        for n in 5..40u32 {
+            for (f, s, p) in [(3, 1, 1), (5, 2, 0), (4, 3, 2)] {
+                assert_eq!(
+                    formula.output_dim(n, f, s, p),
+                    conv_output_dim(n, f.into(), p.into(), s.into(), 1)
+                );
+            }
+        }
+        let formula = Formula::parse("max(n / 2^2, 2 * -(-1)) - min(1, n)").unwrap();
+        assert_eq!(formula.output_dim(12, 1, 1, 0), Ok(2));
+        assert_eq!(formula.output_dim(40, 1, 1, 0), Ok(9));
+        assert_eq!(formula.to_string(), "max(n / 2^2, 2 * -(-1)) - min(1, n)");
+    }
+
+    #[test]
+    fn test_formula_errors() {
+        let parse_error = |text| Formula::parse(text).unwrap_err();
+        assert_eq!(parse_error("n +"), "unexpected end of the formula");
+        assert_eq!(parse_error("n + 2 )"), "unexpected ')' at position 7");
+        assert_eq!(parse_error("n % 2"), "unexpected '%' at position 3");
+        assert_eq!(
+            parse_error("n / k"),
+            "unknown variable 'k', expected one of n, f, s, p"
+        );
+        assert_eq!(
+            parse_error("round(n / 2)"),
+            "unknown function 'round', expected one of floor, ceil, min, max"
+        );
+        assert_eq!(
+            parse_error("min(n)"),
+            "'min' takes 2 arguments, but is given 1"
+        );
+
+        let formula = Formula::parse("n / 2").unwrap();
+        assert_eq!(
+            formula.output_dim(5, 1, 1, 0).unwrap_err().to_string(),
+            "The formula 'n / 2' gives the fraction 5/2 for an input of dimension 5, which needs \
+             to be rounded with floor or ceil."
+        );
+        let formula = Formula::parse("n - f").unwrap();
+        assert_eq!(
+            formula.output_dim(3, 3, 1, 0).unwrap_err().to_string(),
+            "The formula 'n - f' gives the dimension 0 for an input of dimension 3."
+        );
+        let formula = Formula::parse("n / (s - 1)").unwrap();
+        assert!(formula.output_dim(3, 3, 1, 0).is_err());
+        let formula = Formula::parse("n ^ 100").unwrap();
+        assert!(formula.output_dim(3, 3, 1, 0).is_err());
+    }
+}
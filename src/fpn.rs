@@ -0,0 +1,593 @@
+//! Report the levels of a feature pyramid.
+//!
+//! Feature pyramid networks and detection heads operate on the outputs of a backbone at
+//! several resolutions. Each of these levels is characterized by the spatial dimension of
+//! its feature map and by its effective stride, i.e. the number of input pixels per pixel
+//! of the feature map. By convention, the level with effective stride `2^k` is called `Pk`,
+//! e.g. `P3` for a feature map at 1/8 of the input resolution.
+//!
+//! Detectors like SSD and RetinaNet place a number of anchors (or prior boxes) at every location
+//! of every level, whose total needs to match the target assignment. For every anchor, the head
+//! predicts the box and the scores of the classes, so the output of the head at a level of
+//! dimension `S` with `A` anchors per location is a tensor of `S×S×A×(5+C)` values in YOLO, with
+//! the 4 coordinates of the box, the objectness and the `C` class scores.
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::graph::{Graph, Op};
+use crate::trace::write_columns;
+use crate::{dims_at_outputs, Architecture, Entry};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+/// ## The effective stride of a feature map with respect to the network input.
+///
+/// Transposed convolutions increase the resolution, so the stride is represented
+/// as the fraction `num / den`.
+pub struct Stride {
+    pub num: u32,
+    pub den: u32,
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl Stride {
+    /// The stride of the network input itself.
+    pub const ONE: Stride = Stride { num: 1, den: 1 };
+
+    fn new(num: u32, den: u32) -> Stride {
+        let divisor = gcd(num, den);
+        Stride {
+            num: num / divisor,
+            den: den / divisor,
+        }
+    }
+
+    /// The stride after a convolutional layer with the given `stride`.
+    pub fn downsample(self, stride: u16) -> Stride {
+        Stride::new(self.num * stride as u32, self.den)
+    }
+
+    /// The stride after a transposed convolutional layer with the given `stride`.
+    pub fn upsample(self, stride: u16) -> Stride {
+        Stride::new(self.num, self.den * stride as u32)
+    }
+
+    /// The pyramid level `k` if the stride is `2^k`.
+    pub fn level(self) -> Option<u32> {
+        if self.den == 1 && self.num.is_power_of_two() {
+            Some(self.num.trailing_zeros())
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for Stride {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## A level of the feature pyramid, corresponding to an output of the network.
+pub struct Level {
+    pub name: String,
+    pub dim: u32,
+    pub stride: Stride,
+}
+
+impl Level {
+    /// The conventional label `Pk` of the level, if its stride is a power of two.
+    pub fn label(&self) -> String {
+        match self.stride.level() {
+            Some(k) => format!("P{}", k),
+            None => "-".to_string(),
+        }
+    }
+
+    /// The resolution of the level relative to the input, e.g. `1/8`.
+    pub fn scale(&self) -> String {
+        Stride {
+            num: self.stride.den,
+            den: self.stride.num,
+        }
+        .to_string()
+    }
+}
+
+/// Collect the effective strides of all named layers in `layers`, starting from `stride`.
+pub(crate) fn layer_strides<'a>(
+    layers: &'a [Entry],
+    stride: Stride,
+    strides: &mut HashMap<&'a str, Stride>,
+) -> Stride {
+    layers.iter().fold(stride, |stride, entry| match entry {
+        Entry::Layer(layer) => {
+            let (_, layer_stride, _) = layer.geometry();
+            let stride = if layer.is_transposed() {
+                stride.upsample(layer_stride)
+            } else {
+                stride.downsample(layer_stride)
+            };
+            if let Some(name) = layer.name() {
+                strides.insert(name, stride);
+            }
+            stride
+        }
+        // All branches produce outputs of the same dimension, so they are
+        // expected to have the same stride.
+        Entry::Parallel { parallel } => parallel
+            .iter()
+            .map(|branch| layer_strides(&branch.layers, stride, strides))
+            .last()
+            .unwrap_or(stride),
+    })
+}
+
+/// Compute the effective strides of all nodes of `graph`.
+fn node_strides(graph: &Graph, in_dim: u32) -> Result<HashMap<String, Stride>, ConvDimError> {
+    let edges = graph.edges();
+    // The strides of every input are measured with respect to that input.
+    let mut strides: HashMap<String, Stride> = graph
+        .input_shapes(in_dim)
+        .into_iter()
+        .map(|(name, _)| (name.to_string(), Stride::ONE))
+        .collect();
+    // The shapes are computed in topological order, so the inputs are always known.
+    for (name, _) in graph.shapes(in_dim)? {
+        let node = graph.nodes.iter().find(|node| node.name == name).unwrap();
+        let input = strides[edges[name.as_str()][0]];
+        let stride = match node.op {
+            Op::Conv => input.downsample(node.stride),
+            Op::Transposed => input.upsample(node.stride),
+            Op::Add | Op::Concat | Op::BatchNorm | Op::Relu | Op::Dropout | Op::Identity => input,
+            Op::Subnetwork => unreachable!("Subnetworks are expanded when the graph is read."),
+        };
+        strides.insert(name, stride);
+    }
+    Ok(strides)
+}
+
+/// ## Compute the levels of the feature pyramid for an input of dimension `in_dim`.
+///
+/// Every output of the network is considered a level of the pyramid. The levels are
+/// returned in the order in which the outputs are listed.
+pub fn pyramid(architecture: &Architecture, in_dim: u32) -> Result<Vec<Level>, ConvDimError> {
+    let levels: Vec<Level> = match architecture {
+        Architecture::Layers(layers) => {
+            // The dimensions are computed first, which validates the strides.
+            let dims = dims_at_outputs(layers, in_dim)?;
+            let mut strides = HashMap::new();
+            layer_strides(&layers.layers, Stride::ONE, &mut strides);
+            dims.into_iter()
+                .map(|(name, dim)| {
+                    let stride = strides[name.as_str()];
+                    Level { name, dim, stride }
+                })
+                .collect()
+        }
+        Architecture::Graph(graph) => {
+            let strides = node_strides(graph, in_dim)?;
+            graph
+                .output_shapes(in_dim)?
+                .into_iter()
+                .map(|(name, shape)| Level {
+                    stride: strides[&name],
+                    name,
+                    dim: shape.dim,
+                })
+                .collect()
+        }
+    };
+    if levels.is_empty() {
+        return Err(ConvDimError::NoOutputs);
+    }
+    Ok(levels)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The number of anchors of every level of a pyramid, and in total.
+pub struct Priors {
+    pub per_level: Vec<u64>,
+    pub total: u64,
+}
+
+/// ## The numbers of anchors per location given by `text`, e.g. `9` or `4,6,6,6,4,4`.
+pub fn anchors_per_location(text: &str) -> Result<Vec<u32>, ConvDimError> {
+    text.split(',')
+        .map(|anchors| anchors.trim().parse())
+        .collect::<Result<Vec<u32>, _>>()
+        .map_err(|_| ConvDimError::Anchors {
+            message: format!("'{}' are not numbers of anchors like '4,6,6'.", text),
+        })
+}
+
+/// The numbers of anchors per location of every level of `levels`, given for every level or once
+/// for all of them by `per_location`.
+fn anchors_of_levels(levels: &[Level], per_location: &[u32]) -> Result<Vec<u32>, ConvDimError> {
+    if per_location.len() != 1 && per_location.len() != levels.len() {
+        return Err(ConvDimError::Anchors {
+            message: format!(
+                "{} numbers of anchors are given for {} levels.",
+                per_location.len(),
+                levels.len()
+            ),
+        });
+    }
+    Ok((0..levels.len())
+        .map(|i| per_location[i.min(per_location.len() - 1)])
+        .collect())
+}
+
+/// ## The number of anchors of the `levels` with `per_location` anchors at every location.
+///
+/// The numbers of anchors per location are given for every level, or once for all of them.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::fpn::{self, Level, Stride};
+/// // The feature maps of SSD300.
+/// let levels: Vec<Level> = [38, 19, 10, 5, 3, 1]
+///     .iter()
+///     .map(|&dim| Level { name: String::new(), dim, stride: Stride::ONE })
+///     .collect();
+/// let priors = fpn::priors(&levels, &[4, 6, 6, 6, 4, 4]).unwrap();
+/// assert_eq!((priors.per_level[0], priors.total), (5776, 8732));
+/// ```
+pub fn priors(levels: &[Level], per_location: &[u32]) -> Result<Priors, ConvDimError> {
+    let anchors = anchors_of_levels(levels, per_location)?;
+    let too_many = || ConvDimError::Anchors {
+        message: "the number of anchors exceeds the range of 64-bit integers.".to_string(),
+    };
+    let per_level = levels
+        .iter()
+        .zip(anchors)
+        .map(|(level, anchors)| {
+            u64::from(level.dim)
+                .checked_mul(u64::from(level.dim))
+                .and_then(|positions| positions.checked_mul(u64::from(anchors)))
+                .ok_or_else(too_many)
+        })
+        .collect::<Result<Vec<u64>, _>>()?;
+    Ok(Priors {
+        total: per_level
+            .iter()
+            .try_fold(0u64, |total, &anchors| total.checked_add(anchors))
+            .ok_or_else(too_many)?,
+        per_level,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+/// ## The shape of the output of a detection head at a level, `dim×dim×anchors×outputs`.
+pub struct Head {
+    pub dim: u32,
+    pub anchors: u32,
+    /// The outputs of every anchor, i.e. the box, the objectness if any, and the class scores.
+    pub outputs: u32,
+}
+
+impl Head {
+    /// The number of values of the output, which saturates at the largest 64-bit integer.
+    pub fn values(&self) -> u64 {
+        u64::from(self.dim)
+            .saturating_mul(u64::from(self.dim))
+            .saturating_mul(u64::from(self.anchors))
+            .saturating_mul(u64::from(self.outputs))
+    }
+}
+
+impl std::fmt::Display for Head {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{0}x{0}x{1}x{2}", self.dim, self.anchors, self.outputs)
+    }
+}
+
+/// ## The outputs of the detection head at the `levels`, with `per_location` anchors at every
+/// location, `box_outputs` outputs for the box of every anchor and the scores of `classes`.
+///
+/// `box_outputs` is 5 for heads predicting the 4 coordinates and the objectness like YOLO, and 4
+/// for heads without objectness like RetinaNet.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::fpn::{self, Level, Stride};
+/// // The levels of YOLOv3 for an input of 416 pixels and the 80 classes of COCO.
+/// let levels: Vec<Level> = [13, 26, 52]
+///     .iter()
+///     .map(|&dim| Level { name: String::new(), dim, stride: Stride::ONE })
+///     .collect();
+/// let heads = fpn::heads(&levels, &[3], 5, 80).unwrap();
+/// assert_eq!(heads[0].to_string(), "13x13x3x85");
+/// assert_eq!(heads.iter().map(|head| head.values()).sum::<u64>(), 10647 * 85);
+/// ```
+pub fn heads(
+    levels: &[Level],
+    per_location: &[u32],
+    box_outputs: u32,
+    classes: u32,
+) -> Result<Vec<Head>, ConvDimError> {
+    let outputs = box_outputs
+        .checked_add(classes)
+        .ok_or_else(|| ConvDimError::Anchors {
+            message: format!("{} classes are too many.", classes),
+        })?;
+    Ok(levels
+        .iter()
+        .zip(anchors_of_levels(levels, per_location)?)
+        .map(|(level, anchors)| Head {
+            dim: level.dim,
+            anchors,
+            outputs,
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The report on the levels of a pyramid, with their anchors and detection heads if given.
+///
+/// The report is displayed as a table with aligned columns, which ends with the total numbers of
+/// priors and of values of the heads if the anchors are given.
+pub struct Report {
+    pub levels: Vec<Level>,
+    /// The number of anchors per location of every level.
+    pub anchors: Option<Vec<u32>>,
+    pub priors: Option<Priors>,
+    pub heads: Option<Vec<Head>>,
+}
+
+impl Report {
+    /// ## The report on the `levels`, with `per_location` anchors at every location, see [`priors`],
+    /// and the heads with `box_outputs` outputs for the box of every anchor and `classes`, see
+    /// [`heads`].
+    pub fn new(
+        levels: Vec<Level>,
+        per_location: Option<&[u32]>,
+        box_outputs: u32,
+        classes: Option<u32>,
+    ) -> Result<Report, ConvDimError> {
+        let (anchors, priors, heads) = match per_location {
+            Some(per_location) => (
+                Some(anchors_of_levels(&levels, per_location)?),
+                Some(priors(&levels, per_location)?),
+                classes
+                    .map(|classes| heads(&levels, per_location, box_outputs, classes))
+                    .transpose()?,
+            ),
+            None => (None, None, None),
+        };
+        Ok(Report {
+            levels,
+            anchors,
+            priors,
+            heads,
+        })
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut header = vec!["level", "output", "dim", "stride", "scale"];
+        if self.priors.is_some() {
+            header.extend(["anchors", "priors"]);
+            if self.heads.is_some() {
+                header.extend(["head", "values"]);
+            }
+        }
+        let mut rows = vec![header.iter().map(|cell| cell.to_string()).collect()];
+        for (i, level) in self.levels.iter().enumerate() {
+            let mut row = vec![
+                level.label(),
+                level.name.clone(),
+                level.dim.to_string(),
+                level.stride.to_string(),
+                level.scale(),
+            ];
+            if let (Some(anchors), Some(priors)) = (&self.anchors, &self.priors) {
+                row.extend([anchors[i].to_string(), priors.per_level[i].to_string()]);
+            }
+            if let Some(heads) = &self.heads {
+                row.extend([heads[i].to_string(), heads[i].values().to_string()]);
+            }
+            rows.push(row);
+        }
+        if let Some(priors) = &self.priors {
+            let mut total = vec![String::new(); header.len()];
+            total[0] = "total".to_string();
+            total[6] = priors.total.to_string();
+            if let Some(heads) = &self.heads {
+                let values = heads
+                    .iter()
+                    .fold(0u64, |total, head| total.saturating_add(head.values()));
+                total[8] = values.to_string();
+            }
+            rows.push(total);
+        }
+        write_columns(f, &rows, &[2, 3, 4, 5, 6, 7, 8])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Layers;
+
+    #[test]
+    fn test_report() {
+        let level = |name: &str, dim, stride| Level {
+            name: name.to_string(),
+            dim,
+            stride: Stride::ONE.downsample(stride),
+        };
+        let levels = vec![level("c3", 80, 8), level("a_long_name", 40, 16)];
+        let report = Report::new(levels.clone(), None, 5, None).unwrap();
+        assert_eq!(
+            report.to_string(),
+            "level  output       dim  stride  scale
+P3     c3            80       8    1/8
+P4     a_long_name   40      16   1/16
+"
+        );
+        let report = Report::new(levels, Some(&[3]), 5, Some(80)).unwrap();
+        assert_eq!(
+            report.to_string(),
+            "level  output       dim  stride  scale  anchors  priors        head   values
+P3     c3            80       8    1/8        3   19200  80x80x3x85  1632000
+P4     a_long_name   40      16   1/16        3    4800  40x40x3x85   408000
+total                                             24000              2040000
+"
+        );
+    }
+
+    #[test]
+    fn test_stride() {
+        assert_eq!(Stride::ONE.downsample(2).downsample(4).to_string(), "8");
+        assert_eq!(Stride::ONE.downsample(2).upsample(4).to_string(), "1/2");
+        assert_eq!(Stride::ONE.downsample(8).level(), Some(3));
+        assert_eq!(Stride::ONE.downsample(3).level(), None);
+        assert_eq!(Stride::ONE.upsample(2).level(), None);
+    }
+
+    #[test]
+    fn test_pyramid_from_layers() {
+        let layers: Layers = toml::from_str(
+            r#"
+            outputs = ["c3", "c4", "c5"]
+
+            [[layers]]
+            filter_size = 7
+            stride = 2
+            padding = 3
+            transposed = false
+
+            [[layers]]
+            filter_size = 3
+            stride = 2
+            padding = 1
+            transposed = false
+
+            [[layers]]
+            name = "c3"
+            filter_size = 3
+            stride = 2
+            padding = 1
+            transposed = false
+
+            [[layers]]
+            name = "c4"
+            filter_size = 3
+            stride = 2
+            padding = 1
+            transposed = false
+
+            [[layers]]
+            name = "c5"
+            filter_size = 3
+            stride = 2
+            padding = 1
+            transposed = false
+            "#,
+        )
+        .unwrap();
+
+        let levels = pyramid(&Architecture::Layers(layers), 640).unwrap();
+        let summary: Vec<(String, u32, String)> = levels
+            .iter()
+            .map(|level| (level.label(), level.dim, level.scale()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("P3".to_string(), 80, "1/8".to_string()),
+                ("P4".to_string(), 40, "1/16".to_string()),
+                ("P5".to_string(), 20, "1/32".to_string()),
+            ]
+        );
+        assert_eq!(
+            toml::to_string(&levels[0]).unwrap(),
+            "name = \"c3\"\ndim = 80\n\n[stride]\nnum = 8\nden = 1\n"
+        );
+
+        // The nine anchors per location of RetinaNet.
+        let priors = priors(&levels, &anchors_per_location("9").unwrap()).unwrap();
+        assert_eq!(priors.per_level, vec![57600, 14400, 3600]);
+        assert_eq!(priors.total, 75600);
+        assert!(super::priors(&levels, &[9, 9]).is_err());
+        let huge = Level {
+            name: String::new(),
+            dim: u32::MAX,
+            stride: Stride::ONE,
+        };
+        assert!(super::priors(&[huge], &[9]).is_err());
+        // The class and box subnets of RetinaNet, for the 80 classes of COCO.
+        let heads = heads(&levels, &[9], 4, 80).unwrap();
+        assert_eq!(heads[2].to_string(), "20x20x9x84");
+        assert_eq!(heads[2].values(), 3600 * 84);
+        assert!(anchors_per_location("9,x").is_err());
+    }
+
+    #[test]
+    fn test_pyramid_from_graph() {
+        // A top-down pathway merging an upsampled coarse level into a finer one.
+        let graph: Graph = toml::from_str(
+            r#"
+            outputs = ["p3", "c4"]
+
+            [[nodes]]
+            name = "c3"
+            op = "conv"
+            filter_size = 8
+            stride = 8
+
+            [[nodes]]
+            name = "c4"
+            op = "conv"
+            filter_size = 2
+            stride = 2
+
+            [[nodes]]
+            name = "up"
+            op = "transposed"
+            filter_size = 2
+            stride = 2
+
+            [[nodes]]
+            name = "p3"
+            op = "add"
+            inputs = ["c3", "up"]
+            "#,
+        )
+        .unwrap();
+
+        let levels = pyramid(&Architecture::Graph(graph), 256).unwrap();
+        assert_eq!(
+            levels,
+            vec![
+                Level {
+                    name: "p3".to_string(),
+                    dim: 32,
+                    stride: Stride { num: 8, den: 1 }
+                },
+                Level {
+                    name: "c4".to_string(),
+                    dim: 16,
+                    stride: Stride { num: 16, den: 1 }
+                },
+            ]
+        );
+    }
+}
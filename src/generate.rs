@@ -0,0 +1,225 @@
+//! Generators of blocks of layers that are tedious to write by hand, e.g. the stacks of dilated
+//! causal convolutions of WaveNet.
+//!
+//! The layers of `convdim` have no dilation, so a dilated convolution is described by its effective
+//! filter size `(k - 1) * d + 1`, like in the CSV traces. Causal convolutions pad their input on
+//! one side only, which symmetric padding cannot express, so they are [`formula`](crate::formula)
+//! layers padding their input by `p = f - 1` before applying the filter, and keep the dimension of
+//! their input.
+use std::convert::TryFrom;
+
+use crate::audio;
+use crate::error::ConvDimError;
+use crate::formula::Formula;
+use crate::layers::{Entry, FormulaParams, Layer, Layers};
+
+/// The formula of a causal convolution, which pads its input by `p` on one side.
+const CAUSAL: &str = "n + p - f + 1";
+
+#[derive(Debug, Clone, PartialEq)]
+/// ## A generated block of layers and its receptive field.
+pub struct Block {
+    pub layers: Layers,
+    pub receptive_field: u64,
+    /// A description of the block, written as a comment above its layers.
+    pub description: String,
+}
+
+impl Block {
+    /// ## The block in the toml format, following a comment with its description.
+    pub fn to_toml(&self) -> String {
+        let layers = match toml::to_string(&self.layers) {
+            Ok(layers) => layers,
+            Err(e) => panic!("Unable to write the layers as toml: {}", e),
+        };
+        format!(
+            "# {}\n# Receptive field: {}\n\n{}",
+            self.description, self.receptive_field, layers
+        )
+    }
+}
+
+/// A causal convolution with the given `kernel` size and `dilation`.
+fn causal(kernel: u16, dilation: u32, channels: Option<u16>) -> Result<Layer, ConvDimError> {
+    let filter_size = u32::from(kernel - 1)
+        .checked_mul(dilation)
+        .and_then(|size| u16::try_from(size + 1).ok())
+        .ok_or_else(|| ConvDimError::Generate {
+            message: format!(
+                "the effective filter size of a kernel of size {} dilated by {} exceeds {}.",
+                kernel,
+                dilation,
+                u16::MAX
+            ),
+        })?;
+    Ok(Layer::Formula(FormulaParams {
+        name: Some(format!("causal_d{}", dilation)),
+        formula: Formula::parse(CAUSAL).expect("The formula of causal convolutions is valid."),
+        filter_size,
+        stride: 1,
+        padding: filter_size - 1,
+        transposed: false,
+        channels,
+        ceil_mode: false,
+    }))
+}
+
+/// ## A WaveNet block of `layers` causal convolutions whose dilations double, i.e. 1, 2, 4, ...
+///
+/// The convolutions have `channels` output channels, if given. Since they keep the dimension of
+/// their input, the receptive field of the block is `1 + (kernel - 1) * (2^layers - 1)`.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::generate;
+/// let block = generate::wavenet(2, 10, None).unwrap();
+/// assert_eq!(block.receptive_field, 1024);
+/// assert_eq!(block.layers.layers.len(), 10);
+/// ```
+pub fn wavenet(kernel: u16, layers: u32, channels: Option<u16>) -> Result<Block, ConvDimError> {
+    let invalid = |message: &str| ConvDimError::Generate {
+        message: message.to_string(),
+    };
+    if kernel == 0 {
+        return Err(invalid("the kernel size needs to be positive."));
+    }
+    if layers == 0 {
+        return Err(invalid("the block needs at least one layer."));
+    }
+    let dilations = (0..layers).map(|i| 2u32.checked_pow(i));
+    let entries = dilations
+        .map(|dilation| match dilation {
+            Some(dilation) => causal(kernel, dilation, channels).map(Entry::Layer),
+            None => Err(invalid(
+                "the dilation of the last layer exceeds the range of u32.",
+            )),
+        })
+        .collect::<Result<Vec<Entry>, ConvDimError>>()?;
+    let receptive_field = 1 + u64::from(kernel - 1) * ((1u64 << layers) - 1);
+    Ok(Block {
+        layers: Layers {
+            input_channels: None,
+            outputs: vec![],
+            layers: entries,
+        },
+        receptive_field,
+        description: format!(
+            "WaveNet block of {} causal convolutions with kernel size {} and dilations 1 to {}.",
+            layers,
+            kernel,
+            1u64 << (layers - 1)
+        ),
+    })
+}
+
+/// ## The smallest TCN block of causal convolutions whose receptive field covers `receptive_field`.
+///
+/// The block is the [`wavenet`] block with the fewest layers whose receptive field is at least
+/// `receptive_field`, which can be given in samples or as a duration at a sample rate, see
+/// [`samples`]. Returns an error if the kernel of size one cannot grow the receptive field.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::generate;
+/// let samples = generate::samples("3 s at 16 kHz").unwrap();
+/// let block = generate::tcn(3, samples, None).unwrap();
+/// assert_eq!((samples, block.layers.layers.len(), block.receptive_field), (48000, 15, 65535));
+/// ```
+pub fn tcn(
+    kernel: u16,
+    receptive_field: u64,
+    channels: Option<u16>,
+) -> Result<Block, ConvDimError> {
+    if kernel < 2 && receptive_field > 1 {
+        return Err(ConvDimError::Generate {
+            message: format!(
+                "convolutions with a kernel of size {} cannot cover a receptive field of {}.",
+                kernel, receptive_field
+            ),
+        });
+    }
+    // 1 + (k - 1) * (2^n - 1) >= r holds for 2^n >= (r - 1) / (k - 1) + 1.
+    let needed = receptive_field
+        .saturating_sub(1)
+        .div_ceil(u64::from(kernel.max(2) - 1))
+        + 1;
+    let layers = needed.next_power_of_two().trailing_zeros().max(1);
+    let mut block = wavenet(kernel, layers, channels)?;
+    block.description = format!(
+        "TCN block covering a receptive field of {} with {} causal convolutions \
+         with kernel size {} and dilations 1 to {}.",
+        receptive_field,
+        layers,
+        kernel,
+        1u64 << (layers - 1)
+    );
+    Ok(block)
+}
+
+/// ## The number of samples `text` describes, e.g. `48000`, or `3 s at 16 kHz`.
+///
+/// A duration is given in seconds (`s`) or milliseconds (`ms`) and the sample rate in `Hz` or
+/// `kHz`, and the samples are rounded up.
+pub fn samples(text: &str) -> Result<u64, ConvDimError> {
+    let invalid = || {
+        ConvDimError::Generate {
+        message: format!(
+            "'{}' is neither a number of samples nor a duration at a sample rate, like '3 s at 16 kHz'.",
+            text
+        ),
+    }
+    };
+    if let Ok(samples) = text.trim().parse() {
+        return Ok(samples);
+    }
+    let (duration, rate) = text.split_once(" at ").ok_or_else(invalid)?;
+    let duration = audio::duration(duration).map_err(|_| invalid())?;
+    let rate = audio::rate(rate).map_err(|_| invalid())?;
+    audio::samples(duration, rate).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+    use crate::summary;
+
+    #[test]
+    fn test_wavenet() {
+        let block = wavenet(3, 4, Some(32)).unwrap();
+        assert_eq!(block.receptive_field, 31);
+        let toml = block.to_toml();
+        assert!(toml.starts_with(
+            "# WaveNet block of 4 causal convolutions with kernel size 3 and dilations 1 to 8.\n\
+             # Receptive field: 31\n\n[[layers]]\ntype = \"formula\"\nname = \"causal_d1\"\n"
+        ));
+
+        // The block keeps the dimension, and its receptive field agrees with the summary.
+        let graph = parse_architecture(&toml).unwrap().into_graph();
+        let rows = summary::rows(&graph, 100).unwrap();
+        let last = rows.last().unwrap();
+        assert_eq!((last.shape.dim, last.receptive_field), (100, 31));
+
+        assert!(wavenet(0, 4, None).is_err());
+        assert!(wavenet(3, 0, None).is_err());
+        assert!(wavenet(3, 17, None).is_err());
+    }
+
+    #[test]
+    fn test_tcn() {
+        assert_eq!(samples("48000"), Ok(48000));
+        assert_eq!(samples("250 ms at 8kHz"), Ok(2000));
+        assert_eq!(samples("0.1s at 44100 Hz"), Ok(4410));
+        assert!(samples("3 minutes at 16 kHz").is_err());
+
+        // Three layers of kernel size 3 cover 15 samples, four cover 31.
+        for (receptive_field, layers) in [(1, 1), (15, 3), (16, 4), (31, 4), (32, 5)] {
+            let block = tcn(3, receptive_field, None).unwrap();
+            assert_eq!(block.layers.layers.len(), layers, "{}", receptive_field);
+            assert!(block.receptive_field >= receptive_field);
+        }
+        assert!(tcn(1, 2, None).is_err());
+    }
+}
@@ -0,0 +1,1580 @@
+//! Networks with several branches described as a directed acyclic graph.
+//!
+//! A flat list of layers cannot express architectures such as U-Nets, ResNets or Inception
+//! networks, in which the output of a layer is consumed by several other layers and the results
+//! of different branches are merged again. In the graph format, every layer is a named node that
+//! explicitly lists the nodes it takes its `inputs` from. Merge nodes combine the outputs of several
+//! branches, either by element-wise addition (`add`) or by concatenation along the channels (`concat`).
+//!
+//! The input of the network is referred to by the name [`INPUT`]. Networks with several inputs,
+//! e.g. multi-modal models, declare their `inputs` explicitly, each with its own name and dimension.
+//!
+//! Subnetworks that are applied to several inputs with shared weights, as in siamese networks, are
+//! defined once under `subnetworks` and instantiated by `subnetwork` nodes. When the graph is read,
+//! every instantiation is expanded into copies of the nodes of the subnetwork, which are named after
+//! the instantiating node, e.g. `left/conv1` and `right/conv1`.
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+use crate::cache::Cache;
+use crate::error::ConvDimError;
+use crate::formula::Formula;
+use crate::layers::PassThrough;
+use crate::{conv_output_dim, transposed_conv_output_dim};
+
+/// The name under which nodes refer to the input of the network.
+pub const INPUT: &str = "input";
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+/// ## The operation performed by a node.
+pub enum Op {
+    /// A convolutional layer.
+    Conv,
+    /// A transposed convolutional layer.
+    Transposed,
+    /// Element-wise addition of the inputs, e.g. for residual connections.
+    Add,
+    /// Concatenation of the inputs along the channels, e.g. for skip connections.
+    Concat,
+    /// An instantiation of a subnetwork.
+    Subnetwork,
+    /// A batch normalization, which keeps the shape of its input like the following operations.
+    BatchNorm,
+    Relu,
+    Dropout,
+    Identity,
+}
+
+impl Op {
+    /// The kind of pass-through layer the operation is, if it keeps the shape of its input.
+    pub fn pass_through(self) -> Option<PassThrough> {
+        match self {
+            Op::BatchNorm => Some(PassThrough::BatchNorm),
+            Op::Relu => Some(PassThrough::Relu),
+            Op::Dropout => Some(PassThrough::Dropout),
+            Op::Identity => Some(PassThrough::Identity),
+            Op::Conv | Op::Transposed | Op::Add | Op::Concat | Op::Subnetwork => None,
+        }
+    }
+}
+
+impl From<PassThrough> for Op {
+    fn from(kind: PassThrough) -> Op {
+        match kind {
+            PassThrough::BatchNorm => Op::BatchNorm,
+            PassThrough::Relu => Op::Relu,
+            PassThrough::Dropout => Op::Dropout,
+            PassThrough::Identity => Op::Identity,
+        }
+    }
+}
+
+fn default_stride() -> u16 {
+    1
+}
+
+fn is_default_stride(stride: &u16) -> bool {
+    *stride == default_stride()
+}
+
+fn is_zero(padding: &u16) -> bool {
+    *padding == 0
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// ## A node of the graph.
+///
+/// Convolutional nodes are defined by their `filter_size`, `stride` and `padding` like the layers
+/// of the list format. If `inputs` is omitted, the node takes its input from the node defined before it,
+/// or from the network input if it is the first node. The number of output `channels` is optional and
+/// only required for checking merge nodes. Nodes instantiating a `subnetwork` refer to it by its name.
+/// The output dimension of a (transposed) convolutional node with a `formula` is given by the formula,
+/// which may leave out the `filter_size`.
+pub struct Node {
+    pub name: String,
+    pub op: Op,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter_size: Option<u16>,
+    #[serde(default = "default_stride", skip_serializing_if = "is_default_stride")]
+    pub stride: u16,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub padding: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formula: Option<Formula>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subnetwork: Option<String>,
+    /// Whether the node is a pooling in ceil mode, see [`FormulaParams::ceil_mode`].
+    ///
+    /// [`FormulaParams::ceil_mode`]: crate::layers::FormulaParams::ceil_mode
+    #[serde(skip)]
+    pub ceil_mode: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+/// ## A named subnetwork that can be instantiated several times.
+///
+/// Within the subnetwork, its input is referred to by the name [`INPUT`].
+pub struct Subnetwork {
+    pub name: String,
+    pub nodes: Vec<Node>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// ## An explicitly declared input of the network.
+///
+/// If the dimension `input_dim` is omitted, the dimension passed on the command line is used.
+pub struct Input {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_dim: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u16>,
+}
+
+#[derive(Deserialize, Debug)]
+/// ## A graph as it is written in the configuration, before the subnetworks are expanded.
+struct RawGraph {
+    input_channels: Option<u16>,
+    #[serde(default)]
+    inputs: Vec<Input>,
+    nodes: Vec<Node>,
+    #[serde(default)]
+    outputs: Vec<String>,
+    #[serde(default)]
+    subnetworks: Vec<Subnetwork>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(try_from = "RawGraph")]
+/// ## A network described as a directed acyclic graph of named nodes.
+///
+/// The output of the network is the output of the last node, unless the names of
+/// the nodes that serve as outputs are listed in `outputs`. The instantiations of
+/// subnetworks are expanded when the graph is deserialized.
+pub struct Graph {
+    /// The number of channels of the network input, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_channels: Option<u16>,
+    // The plain values precede the tables, so that the graph can be written as toml.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<String>,
+    /// The inputs of the network. If none are declared, the network has the single input [`INPUT`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<Input>,
+    pub nodes: Vec<Node>,
+}
+
+/// ## Expand the instantiations of subnetworks among `nodes`.
+///
+/// The omitted inputs are made explicit, with the first node taking its input from `first_input`.
+/// The nodes of an instantiated subnetwork are prefixed by the name of the instantiating node,
+/// and the instantiating node itself is recorded in `aliases` as an alias for the last node of
+/// the expanded subnetwork. The names of the subnetworks currently being expanded are kept in
+/// `stack` to detect recursive definitions.
+fn expand(
+    nodes: &[Node],
+    first_input: &str,
+    subnetworks: &HashMap<&str, &Subnetwork>,
+    stack: &mut Vec<String>,
+    aliases: &mut HashMap<String, String>,
+) -> Result<Vec<Node>, String> {
+    let mut expanded = vec![];
+    for (i, node) in nodes.iter().enumerate() {
+        let previous = if i == 0 {
+            first_input
+        } else {
+            &nodes[i - 1].name
+        };
+        let mut node = node.clone();
+        node.inputs = node
+            .resolved_inputs(previous)
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        if node.op != Op::Subnetwork {
+            expanded.push(node);
+            continue;
+        }
+
+        let name = match &node.subnetwork {
+            Some(name) => name,
+            None => {
+                return Err(format!(
+                    "Node '{}' does not name its 'subnetwork'.",
+                    node.name
+                ))
+            }
+        };
+        let subnetwork = match subnetworks.get(name.as_str()) {
+            Some(subnetwork) => subnetwork,
+            None => {
+                return Err(format!(
+                    "Node '{}' instantiates the unknown subnetwork '{}'.",
+                    node.name, name
+                ))
+            }
+        };
+        if stack.contains(name) {
+            return Err(format!("The subnetwork '{}' instantiates itself.", name));
+        }
+        if node.inputs.len() != 1 {
+            return Err(format!(
+                "Node '{}' must have exactly one input, but has {}.",
+                node.name,
+                node.inputs.len()
+            ));
+        }
+
+        stack.push(name.clone());
+        let inner = expand(&subnetwork.nodes, INPUT, subnetworks, stack, aliases)?;
+        stack.pop();
+
+        // Internal references are prefixed, the subnetwork input is connected to the input of the node.
+        let internal: HashSet<&str> = inner.iter().map(|n| n.name.as_str()).collect();
+        let rename = |reference: &str| {
+            if reference == INPUT {
+                node.inputs[0].clone()
+            } else if internal.contains(reference) {
+                format!("{}/{}", node.name, reference)
+            } else {
+                reference.to_string()
+            }
+        };
+        let copies: Vec<Node> = inner
+            .iter()
+            .map(|n| Node {
+                name: format!("{}/{}", node.name, n.name),
+                inputs: n.inputs.iter().map(|input| rename(input)).collect(),
+                ..n.clone()
+            })
+            .collect();
+        match copies.last() {
+            Some(last) => aliases.insert(node.name.clone(), last.name.clone()),
+            None => {
+                return Err(format!(
+                    "The subnetwork '{}' does not contain any nodes.",
+                    name
+                ))
+            }
+        };
+        expanded.extend(copies);
+    }
+    Ok(expanded)
+}
+
+impl TryFrom<RawGraph> for Graph {
+    type Error = String;
+
+    fn try_from(raw: RawGraph) -> Result<Graph, String> {
+        let subnetworks: HashMap<&str, &Subnetwork> = raw
+            .subnetworks
+            .iter()
+            .map(|subnetwork| (subnetwork.name.as_str(), subnetwork))
+            .collect();
+        let first_input = raw.inputs.first().map_or(INPUT, |input| &input.name);
+        let mut aliases = HashMap::new();
+        let mut nodes = expand(
+            &raw.nodes,
+            first_input,
+            &subnetworks,
+            &mut vec![],
+            &mut aliases,
+        )?;
+
+        // Nested instantiations can alias other instantiations.
+        let resolve = |name: &String| {
+            let mut name = name;
+            while let Some(target) = aliases.get(name) {
+                name = target;
+            }
+            name.clone()
+        };
+        for node in &mut nodes {
+            node.inputs = node.inputs.iter().map(resolve).collect();
+        }
+        let outputs = raw.outputs.iter().map(resolve).collect();
+
+        let graph = Graph {
+            input_channels: raw.input_channels,
+            inputs: raw.inputs,
+            nodes,
+            outputs,
+        };
+        graph.validate()?;
+        Ok(graph)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+/// ## The shape of the output of a node.
+///
+/// The spatial dimension is assumed to be symmetric, the number of channels is only known if it
+/// has been specified in the configuration.
+pub struct Shape {
+    pub dim: u32,
+    pub channels: Option<u16>,
+}
+
+impl std::fmt::Display for Shape {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.channels {
+            Some(channels) => write!(f, "dim {}, {} channels", self.dim, channels),
+            None => write!(f, "dim {}", self.dim),
+        }
+    }
+}
+
+impl std::fmt::Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.op {
+            Op::Conv | Op::Transposed if self.formula.is_some() => write!(
+                f,
+                "'{}' ({}, formula '{}')",
+                self.name,
+                if self.op == Op::Conv {
+                    "conv"
+                } else {
+                    "transposed"
+                },
+                self.formula.as_ref().map_or("", |formula| formula.as_str())
+            ),
+            Op::Conv | Op::Transposed => write!(
+                f,
+                "'{}' ({}, filter_size {}, stride {}, padding {})",
+                self.name,
+                if self.op == Op::Conv {
+                    "conv"
+                } else {
+                    "transposed"
+                },
+                self.filter_size.unwrap_or(0),
+                self.stride,
+                self.padding
+            ),
+            Op::Add => write!(f, "'{}' (add)", self.name),
+            Op::Concat => write!(f, "'{}' (concat)", self.name),
+            Op::Subnetwork => write!(
+                f,
+                "'{}' (subnetwork '{}')",
+                self.name,
+                self.subnetwork.as_deref().unwrap_or("")
+            ),
+            Op::BatchNorm | Op::Relu | Op::Dropout | Op::Identity => {
+                let kind = self.op.pass_through().map_or("", |kind| kind.kind());
+                write!(f, "'{}' ({})", self.name, kind)
+            }
+        }
+    }
+}
+
+impl Node {
+    /// The names of the nodes this node takes its input from, with omitted inputs resolved
+    /// to the `previous` node.
+    fn resolved_inputs<'a>(&'a self, previous: &'a str) -> Vec<&'a str> {
+        if self.inputs.is_empty() {
+            vec![previous]
+        } else {
+            self.inputs.iter().map(String::as_str).collect()
+        }
+    }
+
+    /// The parameters of the node as text, e.g. `conv, filter size 3, stride 2, padding 1`.
+    pub fn params(&self) -> String {
+        let op = format!("{:?}", self.op).to_lowercase();
+        if self.op != Op::Conv && self.op != Op::Transposed {
+            return op;
+        }
+        let mut params = op;
+        if let Some(formula) = &self.formula {
+            params += &format!(", formula '{}'", formula);
+        }
+        if let Some(filter_size) = self.filter_size {
+            params += &format!(", filter size {}", filter_size);
+        }
+        params + &format!(", stride {}, padding {}", self.stride, self.padding)
+    }
+
+    /// Compute the output shape of the node from the shapes of its `inputs`, looking the outputs
+    /// of convolutions without a formula up in the `cache` if there is one.
+    fn output_shape(
+        &self,
+        inputs: &[Shape],
+        cache: Option<&mut Cache>,
+    ) -> Result<Shape, ConvDimError> {
+        match self.op {
+            Op::Conv | Op::Transposed => {
+                if inputs.len() != 1 {
+                    return Err(ConvDimError::InputCount {
+                        node: self.name.clone(),
+                        count: inputs.len(),
+                    });
+                }
+                let dim = match (&self.formula, self.filter_size) {
+                    (Some(formula), filter_size) => formula.output_dim(
+                        inputs[0].dim,
+                        filter_size.unwrap_or(1),
+                        self.stride,
+                        self.padding,
+                    ),
+                    (None, Some(filter_size)) => {
+                        let transposed = self.op == Op::Transposed;
+                        let compute = || {
+                            let (filter_size, stride, padding) =
+                                (filter_size as u32, self.stride as u32, self.padding as u32);
+                            if transposed {
+                                transposed_conv_output_dim(
+                                    inputs[0].dim,
+                                    filter_size,
+                                    padding,
+                                    stride,
+                                    1,
+                                )
+                            } else {
+                                conv_output_dim(inputs[0].dim, filter_size, padding, stride, 1)
+                            }
+                        };
+                        match cache {
+                            Some(cache) => cache.output_dim(
+                                (
+                                    inputs[0].dim,
+                                    filter_size,
+                                    self.stride,
+                                    self.padding,
+                                    transposed,
+                                ),
+                                compute,
+                            ),
+                            None => compute(),
+                        }
+                    }
+                    (None, None) => {
+                        return Err(ConvDimError::MissingFilterSize {
+                            node: self.name.clone(),
+                        })
+                    }
+                };
+                Ok(Shape {
+                    dim: dim.map_err(|e| e.in_layer(&self.name))?,
+                    channels: self.channels.or(inputs[0].channels),
+                })
+            }
+            Op::Add | Op::Concat => {
+                // The compatibility of the inputs is verified by `Graph::check_merge`.
+                let channels = if self.op == Op::Add {
+                    inputs[0].channels
+                } else {
+                    inputs.iter().map(|shape| shape.channels).sum()
+                };
+                Ok(Shape {
+                    dim: inputs[0].dim,
+                    channels,
+                })
+            }
+            Op::BatchNorm | Op::Relu | Op::Dropout | Op::Identity => match inputs {
+                [input] => Ok(*input),
+                _ => Err(ConvDimError::InputCount {
+                    node: self.name.clone(),
+                    count: inputs.len(),
+                }),
+            },
+            Op::Subnetwork => unreachable!("Subnetworks are expanded when the graph is read."),
+        }
+    }
+}
+
+/// The split of `offset` over both sides of a dimension, e.g. `2 on each side`.
+pub(crate) fn sides(offset: u32) -> String {
+    if offset.is_multiple_of(2) {
+        format!("{} on each side", offset / 2)
+    } else {
+        format!(
+            "{} on one side and {} on the other",
+            offset / 2,
+            offset - offset / 2
+        )
+    }
+}
+
+/// ## Suggest how to reconcile the spatial dimensions of two branches that are concatenated.
+///
+/// The difference in dimension (the offset) can be compensated either by center-cropping the larger
+/// feature map, as done in the original U-Net, or by zero-padding the smaller one. If the offset is odd,
+/// the crop or padding can not be distributed symmetrically.
+pub(crate) fn reconcile(a: &str, a_dim: u32, b: &str, b_dim: u32) -> String {
+    let (larger, smaller) = if a_dim > b_dim { (a, b) } else { (b, a) };
+    let offset = a_dim.abs_diff(b_dim);
+    let sides = sides(offset);
+    format!(
+        "offset of {}: crop '{}' by {} or pad '{}' by {}",
+        offset, larger, sides, smaller, sides
+    )
+}
+
+impl Graph {
+    /// The names of the nodes every node takes its input from, with omitted inputs resolved.
+    pub(crate) fn edges(&self) -> HashMap<&str, Vec<&str>> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let previous = if i == 0 {
+                    self.inputs.first().map_or(INPUT, |input| &input.name)
+                } else {
+                    &self.nodes[i - 1].name
+                };
+                (node.name.as_str(), node.resolved_inputs(previous))
+            })
+            .collect()
+    }
+
+    /// The node named `name` together with all the nodes it (indirectly) depends on.
+    pub(crate) fn ancestors<'a>(
+        &'a self,
+        name: &'a str,
+        edges: &HashMap<&str, Vec<&'a str>>,
+    ) -> HashSet<&'a str> {
+        let mut visited = HashSet::new();
+        let mut stack = vec![name];
+        while let Some(current) = stack.pop() {
+            if visited.insert(current) {
+                if let Some(inputs) = edges.get(current) {
+                    stack.extend(inputs.iter().copied());
+                }
+            }
+        }
+        visited
+    }
+
+    /// ## Verify that the inputs of the merge node `node` have compatible shapes.
+    ///
+    /// The inputs of an `add` node need to have identical spatial dimensions and numbers of channels,
+    /// while the inputs of a `concat` node only need to agree in their spatial dimensions.
+    ///
+    /// The first input is taken as reference. For every input whose shape is incompatible with it,
+    /// the layers that are exclusive to either of the two branches and change the mismatching quantity
+    /// are reported, pointing to the cause of the mismatch. For `concat` nodes, the crop or padding that
+    /// would reconcile the spatial dimensions is suggested.
+    fn check_merge(
+        &self,
+        node: &Node,
+        edges: &HashMap<&str, Vec<&str>>,
+        computed: &HashMap<&str, Shape>,
+        order: &[(String, Shape)],
+    ) -> Result<(), ConvDimError> {
+        let inputs = &edges[node.name.as_str()];
+        let reference = inputs[0];
+        let reference_shape = computed[reference];
+
+        let mut problems = vec![];
+        for &branch in &inputs[1..] {
+            let shape = computed[branch];
+            let dim_mismatch = shape.dim != reference_shape.dim;
+            // Concatenation sums up the channels, so only the spatial dimensions need to agree.
+            let channel_mismatch = node.op == Op::Add
+                && matches!(
+                    (shape.channels, reference_shape.channels),
+                    (Some(a), Some(b)) if a != b
+                );
+            if !dim_mismatch && !channel_mismatch {
+                continue;
+            }
+            problems.push(format!(
+                "  branch '{}' ({}) does not match branch '{}' ({})",
+                branch, shape, reference, reference_shape
+            ));
+            if node.op == Op::Concat {
+                problems.push(format!(
+                    "    {}",
+                    reconcile(reference, reference_shape.dim, branch, shape.dim)
+                ));
+            }
+
+            for &(this, other) in &[(branch, reference), (reference, branch)] {
+                let own = self.ancestors(this, edges);
+                let shared = self.ancestors(other, edges);
+                // Only the layers exclusive to this branch can be responsible, in evaluation order.
+                for (name, out) in order.iter().filter(|(name, _)| {
+                    own.contains(name.as_str()) && !shared.contains(name.as_str())
+                }) {
+                    let culprit = self.nodes.iter().find(|n| &n.name == name).unwrap();
+                    let input = computed[edges[name.as_str()][0]];
+                    if dim_mismatch && input.dim != out.dim {
+                        problems.push(format!(
+                            "    layer {} of branch '{}' changes the dim from {} to {}",
+                            culprit, this, input.dim, out.dim
+                        ));
+                    }
+                    if channel_mismatch && input.channels != out.channels {
+                        problems.push(format!(
+                            "    layer {} of branch '{}' changes the channels from {} to {}",
+                            culprit,
+                            this,
+                            input
+                                .channels
+                                .map_or("unknown".to_string(), |c| c.to_string()),
+                            out.channels
+                                .map_or("unknown".to_string(), |c| c.to_string())
+                        ));
+                    }
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConvDimError::IncompatibleMerge {
+                node: node.name.clone(),
+                op: if node.op == Op::Add { "add" } else { "concat" }.to_string(),
+                problems,
+            })
+        }
+    }
+
+    /// The cycles among the nodes, each listed in the direction of the data flow.
+    fn cycles(&self, edges: &HashMap<&str, Vec<&str>>) -> Vec<Vec<String>> {
+        fn visit<'a>(
+            name: &'a str,
+            edges: &HashMap<&str, Vec<&'a str>>,
+            stack: &mut Vec<&'a str>,
+            done: &mut HashSet<&'a str>,
+            cycles: &mut Vec<Vec<String>>,
+        ) {
+            if done.contains(name) {
+                return;
+            }
+            if let Some(position) = stack.iter().position(|&n| n == name) {
+                // The stack follows the inputs, i.e. runs against the data flow.
+                let mut cycle: Vec<String> = stack[position..]
+                    .iter()
+                    .rev()
+                    .map(|n| n.to_string())
+                    .collect();
+                cycle.push(cycle[0].clone());
+                cycles.push(cycle);
+                return;
+            }
+            stack.push(name);
+            for &input in edges.get(name).into_iter().flatten() {
+                visit(input, edges, stack, done, cycles);
+            }
+            stack.pop();
+            done.insert(name);
+        }
+
+        let mut cycles = vec![];
+        let mut done = HashSet::new();
+        for node in &self.nodes {
+            visit(&node.name, edges, &mut vec![], &mut done, &mut cycles);
+        }
+        cycles
+    }
+
+    /// ## Validate the structure of the graph.
+    ///
+    /// Verifies that the names of the inputs and nodes are unique, that all the nodes referred to
+    /// exist, that the graph is free of cycles and that every node can be reached from an input.
+    /// All the problems found are reported together.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = vec![];
+        let edges = self.edges();
+
+        let mut names = HashSet::new();
+        let inputs = self.input_shapes(0).into_iter().map(|(name, _)| name);
+        for name in inputs.chain(self.nodes.iter().map(|node| node.name.as_str())) {
+            if !names.insert(name) {
+                problems.push(format!("The name '{}' is defined more than once.", name));
+            }
+        }
+
+        for node in &self.nodes {
+            for input in &edges[node.name.as_str()] {
+                if !names.contains(input) {
+                    problems.push(format!(
+                        "Node '{}' takes its input from '{}', which does not exist.",
+                        node.name, input
+                    ));
+                }
+            }
+        }
+        for output in &self.outputs {
+            if !names.contains(output.as_str()) {
+                problems.push(format!("The output '{}' does not name any node.", output));
+            }
+        }
+
+        for cycle in self.cycles(&edges) {
+            problems.push(format!("The nodes form a cycle: {}", cycle.join(" -> ")));
+        }
+
+        // Propagate the reachability from the inputs until nothing changes anymore.
+        let mut reachable: HashSet<&str> = self
+            .input_shapes(0)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        loop {
+            let newly_reachable: Vec<&str> = self
+                .nodes
+                .iter()
+                .map(|node| node.name.as_str())
+                .filter(|name| !reachable.contains(name))
+                .filter(|name| edges[name].iter().any(|input| reachable.contains(input)))
+                .collect();
+            if newly_reachable.is_empty() {
+                break;
+            }
+            reachable.extend(newly_reachable);
+        }
+        for node in &self.nodes {
+            if !reachable.contains(node.name.as_str()) {
+                problems.push(format!(
+                    "Node '{}' is not reachable from any input.",
+                    node.name
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Invalid graph:\n  {}", problems.join("\n  ")))
+        }
+    }
+
+    /// ## Whether the dimension of some input has to be provided from outside of the graph.
+    pub fn needs_input_dim(&self) -> bool {
+        self.inputs.is_empty() || self.inputs.iter().any(|input| input.input_dim.is_none())
+    }
+
+    /// ## The shapes of the inputs of the network.
+    ///
+    /// Inputs that don't declare their own dimension are assigned the dimension `in_dim`.
+    pub fn input_shapes(&self, in_dim: u32) -> Vec<(&str, Shape)> {
+        if self.inputs.is_empty() {
+            return vec![(
+                INPUT,
+                Shape {
+                    dim: in_dim,
+                    channels: self.input_channels,
+                },
+            )];
+        }
+        self.inputs
+            .iter()
+            .map(|input| {
+                (
+                    input.name.as_str(),
+                    Shape {
+                        dim: input.input_dim.unwrap_or(in_dim),
+                        channels: input.channels,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// ## Compute the output shape of every node for an input of dimension `in_dim`.
+    ///
+    /// The dimension `in_dim` is only used for inputs that don't declare their own dimension.
+    /// The nodes are evaluated in topological order, such that every node is evaluated after
+    /// all the nodes it takes its input from. The shapes are returned in that order.
+    pub fn shapes(&self, in_dim: u32) -> Result<Vec<(String, Shape)>, ConvDimError> {
+        self.evaluate(in_dim, true, None)
+    }
+
+    /// Compute the output shapes like [`Graph::shapes`], without checking that the inputs of the
+    /// merges agree. A merge takes the dimension of its first input.
+    pub(crate) fn unchecked_shapes(
+        &self,
+        in_dim: u32,
+    ) -> Result<Vec<(String, Shape)>, ConvDimError> {
+        self.evaluate(in_dim, false, None)
+    }
+
+    /// Compute the output shapes of the nodes in evaluation order, checking the merges if
+    /// `check_merges` and looking the outputs of the convolutions up in the `cache` if there is one.
+    fn evaluate(
+        &self,
+        in_dim: u32,
+        check_merges: bool,
+        mut cache: Option<&mut Cache>,
+    ) -> Result<Vec<(String, Shape)>, ConvDimError> {
+        let mut computed: HashMap<&str, Shape> = self.input_shapes(in_dim).into_iter().collect();
+
+        let edges = self.edges();
+
+        let mut order = vec![];
+        let mut pending: Vec<&Node> = self.nodes.iter().collect();
+        while !pending.is_empty() {
+            // Evaluate all the nodes whose inputs are available.
+            let (ready, blocked): (Vec<&Node>, Vec<&Node>) =
+                pending.into_iter().partition(|node| {
+                    edges[node.name.as_str()]
+                        .iter()
+                        .all(|name| computed.contains_key(name))
+                });
+            if ready.is_empty() {
+                return Err(ConvDimError::Unevaluable {
+                    nodes: blocked.iter().map(|node| node.name.clone()).collect(),
+                });
+            }
+            for node in ready {
+                if check_merges && (node.op == Op::Add || node.op == Op::Concat) {
+                    self.check_merge(node, &edges, &computed, &order)?;
+                }
+                let input_shapes: Vec<Shape> = edges[node.name.as_str()]
+                    .iter()
+                    .map(|name| computed[name])
+                    .collect();
+                let shape = node
+                    .output_shape(&input_shapes, cache.as_deref_mut())
+                    .map_err(|e| {
+                        let position = self.nodes.iter().position(|n| n.name == node.name);
+                        let in_dim = input_shapes.first().map_or(in_dim, |shape| shape.dim);
+                        e.located(
+                            position.map_or(0, |position| position + 1),
+                            Some(&node.name),
+                            node.params(),
+                            in_dim,
+                        )
+                    })?;
+                computed.insert(&node.name, shape);
+                order.push((node.name.clone(), shape));
+            }
+            pending = blocked;
+        }
+        Ok(order)
+    }
+
+    /// ## The nodes in topological order, such that every node follows the nodes it takes its input from.
+    ///
+    /// Nodes whose inputs don't exist or form a cycle are omitted, which cannot happen for a validated graph.
+    pub fn order(&self) -> Vec<&Node> {
+        let edges = self.edges();
+        let mut done: HashSet<&str> = self
+            .input_shapes(0)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        let mut order = vec![];
+        let mut pending: Vec<&Node> = self.nodes.iter().collect();
+        loop {
+            let (ready, blocked): (Vec<&Node>, Vec<&Node>) =
+                pending.into_iter().partition(|node| {
+                    edges[node.name.as_str()]
+                        .iter()
+                        .all(|name| done.contains(name))
+                });
+            if ready.is_empty() {
+                return order;
+            }
+            for node in ready {
+                done.insert(&node.name);
+                order.push(node);
+            }
+            pending = blocked;
+        }
+    }
+
+    /// ## Compute the shape of the output of the network for an input of dimension `in_dim`.
+    ///
+    /// This is the output shape of the last node.
+    pub fn output_shape(&self, in_dim: u32) -> Result<Shape, ConvDimError> {
+        let last = match self.nodes.last() {
+            Some(node) => &node.name,
+            None => return Err(ConvDimError::EmptyGraph),
+        };
+        Ok(self
+            .shapes(in_dim)?
+            .into_iter()
+            .find(|(name, _)| name == last)
+            .map(|(_, shape)| shape)
+            .unwrap())
+    }
+
+    /// ## Write the graph in the toml graph format.
+    ///
+    /// Subnetworks have already been expanded, so their nodes are written individually.
+    pub fn to_toml(&self) -> String {
+        match toml::to_string(self) {
+            Ok(toml) => toml,
+            Err(e) => panic!("Unable to write the graph as toml: {}", e),
+        }
+    }
+
+    /// ## Compute the shapes of all the outputs of the network for an input of dimension `in_dim`.
+    ///
+    /// The shapes are returned together with the names of the output nodes in the order in which
+    /// they are listed in `outputs`. If no outputs are listed, the last node is the only output.
+    pub fn output_shapes(&self, in_dim: u32) -> Result<Vec<(String, Shape)>, ConvDimError> {
+        self.select_outputs(self.shapes(in_dim)?)
+    }
+
+    /// ## Compute the shapes of the outputs like [`Graph::output_shapes`], looking the outputs of
+    /// the convolutions up in `cache`.
+    ///
+    /// This saves the arithmetic when many networks sharing layers and input dimensions are
+    /// evaluated, e.g. the candidates of an architecture search.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use convdim::cache::Cache;
+    /// # use convdim::{Architecture, Network};
+    /// let network = Network::builder().conv(3, 2, 1).conv(3, 2, 1).build();
+    /// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+    /// let mut cache = Cache::default();
+    /// let shapes = graph.cached_output_shapes(64, &mut cache).unwrap();
+    /// assert_eq!(shapes, graph.output_shapes(64).unwrap());
+    /// graph.cached_output_shapes(64, &mut cache).unwrap();
+    /// assert_eq!(cache.statistics().hits, 2);
+    /// ```
+    pub fn cached_output_shapes(
+        &self,
+        in_dim: u32,
+        cache: &mut Cache,
+    ) -> Result<Vec<(String, Shape)>, ConvDimError> {
+        self.select_outputs(self.evaluate(in_dim, true, Some(cache))?)
+    }
+
+    /// The `shapes` of the outputs, in the order in which they are listed in `outputs`, or of the
+    /// last node if none are.
+    fn select_outputs(
+        &self,
+        shapes: Vec<(String, Shape)>,
+    ) -> Result<Vec<(String, Shape)>, ConvDimError> {
+        let outputs = match (self.outputs.is_empty(), self.nodes.last()) {
+            (false, _) => self.outputs.iter().collect(),
+            (true, Some(last)) => vec![&last.name],
+            (true, None) => return Err(ConvDimError::EmptyGraph),
+        };
+        let shapes: HashMap<String, Shape> = shapes.into_iter().collect();
+        outputs
+            .into_iter()
+            .map(|name| match shapes.get(name) {
+                Some(&shape) => Ok((name.clone(), shape)),
+                None => Err(ConvDimError::UnknownOutput { name: name.clone() }),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_residual_block() {
+        let graph: Graph = toml::from_str(
+            r#"
+            input_channels = 64
+
+            [[nodes]]
+            name = "conv1"
+            op = "conv"
+            filter_size = 3
+            padding = 1
+
+            [[nodes]]
+            name = "conv2"
+            op = "conv"
+            filter_size = 3
+            padding = 1
+
+            [[nodes]]
+            name = "residual"
+            op = "add"
+            inputs = ["input", "conv2"]
+            "#,
+        )
+        .unwrap();
+
+        let shapes = graph.shapes(56).unwrap();
+        assert_eq!(shapes.len(), 3);
+        assert_eq!(
+            graph.output_shape(56).unwrap(),
+            Shape {
+                dim: 56,
+                channels: Some(64)
+            }
+        );
+    }
+
+    #[test]
+    fn test_unet_skip_connection() {
+        // Nodes may be listed in any order as long as the edges are explicit.
+        let graph: Graph = toml::from_str(
+            r#"
+            [[nodes]]
+            name = "up"
+            op = "transposed"
+            inputs = ["down"]
+            filter_size = 2
+            stride = 2
+            channels = 32
+
+            [[nodes]]
+            name = "enc"
+            op = "conv"
+            inputs = ["input"]
+            filter_size = 3
+            padding = 1
+            channels = 32
+
+            [[nodes]]
+            name = "down"
+            op = "conv"
+            inputs = ["enc"]
+            filter_size = 2
+            stride = 2
+            channels = 64
+
+            [[nodes]]
+            name = "merge"
+            op = "concat"
+            inputs = ["enc", "up"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            graph.output_shape(64).unwrap(),
+            Shape {
+                dim: 64,
+                channels: Some(64)
+            }
+        );
+    }
+
+    #[test]
+    fn test_add_dimension_mismatch() {
+        let graph: Graph = toml::from_str(
+            r#"
+            [[nodes]]
+            name = "down"
+            op = "conv"
+            filter_size = 2
+            stride = 2
+
+            [[nodes]]
+            name = "merge"
+            op = "add"
+            inputs = ["input", "down"]
+            "#,
+        )
+        .unwrap();
+
+        let message = graph.output_shape(64).unwrap_err().to_string();
+        assert!(message.contains("layer 'down' (conv, filter_size 2, stride 2, padding 0) of branch 'down' changes the dim from 64 to 32"));
+    }
+
+    #[test]
+    fn test_add_channel_mismatch() {
+        // A bottleneck block whose projection has the wrong number of channels.
+        let graph: Graph = toml::from_str(
+            r#"
+            input_channels = 64
+
+            [[nodes]]
+            name = "reduce"
+            op = "conv"
+            filter_size = 1
+            channels = 64
+
+            [[nodes]]
+            name = "conv"
+            op = "conv"
+            filter_size = 3
+            padding = 1
+
+            [[nodes]]
+            name = "expand"
+            op = "conv"
+            filter_size = 1
+            channels = 256
+
+            [[nodes]]
+            name = "project"
+            op = "conv"
+            inputs = ["expand"]
+            filter_size = 1
+
+            [[nodes]]
+            name = "residual"
+            op = "add"
+            inputs = ["input", "project"]
+            "#,
+        )
+        .unwrap();
+
+        let message = graph.output_shape(56).unwrap_err().to_string();
+        assert!(message.contains("layer 'expand' (conv, filter_size 1, stride 1, padding 0) of branch 'project' changes the channels from 64 to 256"));
+    }
+
+    #[test]
+    fn test_add_mismatch_reports_both_branches() {
+        let graph: Graph = toml::from_str(
+            r#"
+            [[nodes]]
+            name = "left"
+            op = "conv"
+            filter_size = 3
+            stride = 2
+            padding = 1
+
+            [[nodes]]
+            name = "right"
+            op = "conv"
+            inputs = ["input"]
+            filter_size = 3
+
+            [[nodes]]
+            name = "sum"
+            op = "add"
+            inputs = ["left", "right"]
+            "#,
+        )
+        .unwrap();
+
+        let message = graph.output_shape(32).unwrap_err().to_string();
+        assert!(message.contains("branch 'right' (dim 30) does not match branch 'left' (dim 16)"));
+        assert!(message.contains("layer 'right' (conv, filter_size 3, stride 1, padding 0) of branch 'right' changes the dim from 32 to 30"));
+        assert!(message.contains("layer 'left' (conv, filter_size 3, stride 2, padding 1) of branch 'left' changes the dim from 32 to 16"));
+    }
+
+    #[test]
+    fn test_reconcile() {
+        assert_eq!(
+            reconcile("enc", 64, "up", 56),
+            "offset of 8: crop 'enc' by 4 on each side or pad 'up' by 4 on each side"
+        );
+        assert_eq!(
+            reconcile("up", 55, "enc", 64),
+            "offset of 9: crop 'enc' by 4 on one side and 5 on the other or pad 'up' by 4 on one side and 5 on the other"
+        );
+    }
+
+    #[test]
+    fn test_concat_dimension_mismatch() {
+        // The unpadded convolutions of the original U-Net shrink the feature maps.
+        let graph: Graph = toml::from_str(
+            r#"
+            [[nodes]]
+            name = "enc"
+            op = "conv"
+            filter_size = 3
+            channels = 64
+
+            [[nodes]]
+            name = "down"
+            op = "conv"
+            filter_size = 2
+            stride = 2
+
+            [[nodes]]
+            name = "bottom"
+            op = "conv"
+            filter_size = 3
+            channels = 128
+
+            [[nodes]]
+            name = "up"
+            op = "transposed"
+            filter_size = 2
+            stride = 2
+            channels = 64
+
+            [[nodes]]
+            name = "merge"
+            op = "concat"
+            inputs = ["enc", "up"]
+            "#,
+        )
+        .unwrap();
+
+        let message = graph.output_shape(572).unwrap_err().to_string();
+        assert!(message
+            .contains("offset of 4: crop 'enc' by 2 on each side or pad 'up' by 2 on each side"));
+    }
+
+    #[test]
+    fn test_output_shapes() {
+        let graph: Graph = toml::from_str(
+            r#"
+            input_channels = 3
+            outputs = ["c3", "c4", "c5"]
+
+            [[nodes]]
+            name = "c3"
+            op = "conv"
+            filter_size = 8
+            stride = 8
+            channels = 256
+
+            [[nodes]]
+            name = "c4"
+            op = "conv"
+            filter_size = 2
+            stride = 2
+            channels = 512
+
+            [[nodes]]
+            name = "c5"
+            op = "conv"
+            filter_size = 2
+            stride = 2
+            channels = 1024
+            "#,
+        )
+        .unwrap();
+
+        let dims: Vec<(String, u32)> = graph
+            .output_shapes(640)
+            .unwrap()
+            .into_iter()
+            .map(|(name, shape)| (name, shape.dim))
+            .collect();
+        assert_eq!(
+            dims,
+            vec![
+                ("c3".to_string(), 80),
+                ("c4".to_string(), 40),
+                ("c5".to_string(), 20)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_located_errors() {
+        let graph: Graph = toml::from_str(
+            r#"
+            [[nodes]]
+            name = "down"
+            op = "conv"
+            filter_size = 3
+            stride = 2
+
+            [[nodes]]
+            name = "head"
+            op = "conv"
+            filter_size = 7
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            graph.shapes(8).unwrap_err().to_string(),
+            "Layer 2 'head' (conv, filter size 7, stride 1, padding 0, input 3): the padded input \
+             (3) is smaller than the kernel (7), for an input of dimension 3 padded by 0."
+        );
+    }
+
+    #[test]
+    fn test_multiple_inputs() {
+        // An RGB branch fused with a low-resolution thermal branch.
+        let graph: Graph = toml::from_str(
+            r#"
+            [[inputs]]
+            name = "rgb"
+            input_dim = 224
+            channels = 3
+
+            [[inputs]]
+            name = "thermal"
+            input_dim = 56
+            channels = 1
+
+            [[nodes]]
+            name = "rgb_down"
+            op = "conv"
+            filter_size = 4
+            stride = 4
+            channels = 32
+
+            [[nodes]]
+            name = "thermal_conv"
+            op = "conv"
+            inputs = ["thermal"]
+            filter_size = 3
+            padding = 1
+            channels = 16
+
+            [[nodes]]
+            name = "fusion"
+            op = "concat"
+            inputs = ["rgb_down", "thermal_conv"]
+            "#,
+        )
+        .unwrap();
+
+        assert!(!graph.needs_input_dim());
+        assert_eq!(
+            graph.output_shape(0).unwrap(),
+            Shape {
+                dim: 56,
+                channels: Some(48)
+            }
+        );
+    }
+
+    #[test]
+    fn test_gigapixel_input() {
+        // A whole-slide image tiled by a strided convolution and fused with a thumbnail.
+        let graph: Graph = toml::from_str(
+            r#"
+            [[inputs]]
+            name = "slide"
+            input_dim = 100000
+            channels = 3
+
+            [[inputs]]
+            name = "thumbnail"
+            input_dim = 390
+            channels = 3
+
+            [[nodes]]
+            name = "tiles"
+            op = "conv"
+            inputs = ["slide"]
+            filter_size = 256
+            stride = 256
+            channels = 64
+
+            [[nodes]]
+            name = "fusion"
+            op = "concat"
+            inputs = ["tiles", "thumbnail"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            graph.output_shape(0).unwrap(),
+            Shape {
+                dim: 390,
+                channels: Some(67)
+            }
+        );
+    }
+
+    #[test]
+    fn test_multiple_inputs_mismatch() {
+        let graph: Graph = toml::from_str(
+            r#"
+            [[inputs]]
+            name = "rgb"
+            channels = 3
+
+            [[inputs]]
+            name = "thermal"
+            input_dim = 64
+            channels = 1
+
+            [[nodes]]
+            name = "rgb_down"
+            op = "conv"
+            filter_size = 4
+            stride = 4
+            channels = 32
+
+            [[nodes]]
+            name = "fusion"
+            op = "concat"
+            inputs = ["rgb_down", "thermal"]
+            "#,
+        )
+        .unwrap();
+
+        assert!(graph.needs_input_dim());
+        let message = graph.output_shape(224).unwrap_err().to_string();
+        assert!(message.contains("branch 'thermal' (dim 64, 1 channels) does not match branch 'rgb_down' (dim 56, 32 channels)"));
+    }
+
+    #[test]
+    fn test_siamese_subnetwork() {
+        let graph: Graph = toml::from_str(
+            r#"
+            outputs = ["left", "right"]
+
+            [[inputs]]
+            name = "anchor"
+            channels = 3
+
+            [[inputs]]
+            name = "positive"
+            channels = 3
+
+            [[subnetworks]]
+            name = "encoder"
+
+            [[subnetworks.nodes]]
+            name = "conv1"
+            op = "conv"
+            filter_size = 3
+            stride = 2
+            padding = 1
+            channels = 32
+
+            [[subnetworks.nodes]]
+            name = "conv2"
+            op = "conv"
+            filter_size = 3
+            stride = 2
+            padding = 1
+            channels = 64
+
+            [[nodes]]
+            name = "left"
+            op = "subnetwork"
+            subnetwork = "encoder"
+
+            [[nodes]]
+            name = "right"
+            op = "subnetwork"
+            subnetwork = "encoder"
+            inputs = ["positive"]
+
+            [[nodes]]
+            name = "distance"
+            op = "add"
+            inputs = ["left", "right"]
+            "#,
+        )
+        .unwrap();
+
+        let names: Vec<String> = graph
+            .shapes(64)
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "left/conv1",
+                "right/conv1",
+                "left/conv2",
+                "right/conv2",
+                "distance"
+            ]
+        );
+        assert_eq!(
+            graph.output_shapes(64).unwrap(),
+            vec![
+                (
+                    "left/conv2".to_string(),
+                    Shape {
+                        dim: 16,
+                        channels: Some(64)
+                    }
+                ),
+                (
+                    "right/conv2".to_string(),
+                    Shape {
+                        dim: 16,
+                        channels: Some(64)
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recursive_subnetwork() {
+        let graph: Result<Graph, _> = toml::from_str(
+            r#"
+            [[subnetworks]]
+            name = "block"
+
+            [[subnetworks.nodes]]
+            name = "inner"
+            op = "subnetwork"
+            subnetwork = "block"
+
+            [[nodes]]
+            name = "outer"
+            op = "subnetwork"
+            subnetwork = "block"
+            "#,
+        );
+
+        assert!(graph
+            .unwrap_err()
+            .to_string()
+            .contains("The subnetwork 'block' instantiates itself."));
+    }
+
+    #[test]
+    fn test_validate_references() {
+        let error = toml::from_str::<Graph>(
+            r#"
+            outputs = ["head", "haed"]
+
+            [[nodes]]
+            name = "conv1"
+            op = "conv"
+            filter_size = 3
+
+            [[nodes]]
+            name = "conv1"
+            op = "conv"
+            filter_size = 3
+
+            [[nodes]]
+            name = "head"
+            op = "add"
+            inputs = ["conv1", "cnov1"]
+            "#,
+        )
+        .unwrap_err()
+        .to_string();
+
+        assert!(error.contains("The name 'conv1' is defined more than once."));
+        assert!(error.contains("Node 'head' takes its input from 'cnov1', which does not exist."));
+        assert!(error.contains("The output 'haed' does not name any node."));
+    }
+
+    #[test]
+    fn test_validate_cycles() {
+        let error = toml::from_str::<Graph>(
+            r#"
+            [[nodes]]
+            name = "a"
+            op = "conv"
+            inputs = ["c"]
+            filter_size = 3
+
+            [[nodes]]
+            name = "b"
+            op = "conv"
+            filter_size = 3
+
+            [[nodes]]
+            name = "c"
+            op = "conv"
+            filter_size = 3
+
+            [[nodes]]
+            name = "d"
+            op = "conv"
+            inputs = ["input"]
+            filter_size = 3
+            "#,
+        )
+        .unwrap_err()
+        .to_string();
+
+        assert!(error.contains("The nodes form a cycle: b -> c -> a -> b"));
+        for name in &["a", "b", "c"] {
+            assert!(error.contains(&format!("Node '{}' is not reachable from any input.", name)));
+        }
+        assert!(!error.contains("Node 'd'"));
+    }
+}
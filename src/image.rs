@@ -0,0 +1,200 @@
+//! The size and channels of images, read from the headers of PNG, JPEG and TIFF files.
+//!
+//! Only the headers are interpreted, so the images are never decoded: the `IHDR` chunk of PNG,
+//! the start-of-frame segment of JPEG and the first image file directory of TIFF.
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+/// ## The size of an image and its number of channels, e.g. 3 for RGB.
+pub struct Header {
+    pub height: u32,
+    pub width: u32,
+    pub channels: u16,
+}
+
+fn invalid(message: &str) -> ConvDimError {
+    ConvDimError::Image {
+        message: message.to_string(),
+    }
+}
+
+/// The header of a PNG image, whose `IHDR` chunk follows the signature.
+fn png(bytes: &[u8]) -> Result<Header, ConvDimError> {
+    let ihdr = bytes
+        .get(8..26)
+        .filter(|ihdr| &ihdr[4..8] == b"IHDR")
+        .ok_or_else(|| invalid("the PNG image has no IHDR chunk."))?;
+    let be = |at: usize| u32::from_be_bytes([ihdr[at], ihdr[at + 1], ihdr[at + 2], ihdr[at + 3]]);
+    // Palette images are RGB.
+    let channels = match ihdr[17] {
+        0 => 1,
+        4 => 2,
+        2 | 3 => 3,
+        6 => 4,
+        _ => return Err(invalid("the PNG image has an unknown color type.")),
+    };
+    Ok(Header {
+        height: be(12),
+        width: be(8),
+        channels,
+    })
+}
+
+/// The header of a JPEG image, from its start-of-frame segment.
+fn jpeg(bytes: &[u8]) -> Result<Header, ConvDimError> {
+    let truncated = || invalid("the JPEG image ends before its start-of-frame segment.");
+    let mut at = 2;
+    loop {
+        let marker = bytes.get(at..at + 2).ok_or_else(truncated)?;
+        if marker[0] != 0xff {
+            return Err(invalid("the JPEG image has an invalid segment."));
+        }
+        // Markers may be padded by fill bytes, and some have no length.
+        match marker[1] {
+            0xff => {
+                at += 1;
+                continue;
+            }
+            0x01 | 0xd0..=0xd7 => {
+                at += 2;
+                continue;
+            }
+            _ => {}
+        }
+        let segment = bytes.get(at + 2..at + 4).ok_or_else(truncated)?;
+        let length = usize::from(u16::from_be_bytes([segment[0], segment[1]]));
+        // SOF0 to SOF15, except for the Huffman tables (C4), JPEG-LS (C8) and arithmetic coding (CC).
+        if matches!(marker[1], 0xc0..=0xcf) && !matches!(marker[1], 0xc4 | 0xc8 | 0xcc) {
+            let frame = bytes.get(at + 4..at + 10).ok_or_else(truncated)?;
+            return Ok(Header {
+                height: u32::from(u16::from_be_bytes([frame[1], frame[2]])),
+                width: u32::from(u16::from_be_bytes([frame[3], frame[4]])),
+                channels: u16::from(frame[5]),
+            });
+        }
+        at += 2 + length;
+    }
+}
+
+/// The header of a TIFF image, from the tags of its first image file directory.
+fn tiff(bytes: &[u8]) -> Result<Header, ConvDimError> {
+    let truncated = || invalid("the TIFF image ends within its image file directory.");
+    let little = bytes[0] == b'I';
+    let read = |at: usize, size: usize| -> Result<u32, ConvDimError> {
+        let field = bytes.get(at..at + size).ok_or_else(truncated)?;
+        let mut value = 0;
+        for i in 0..size {
+            let byte = if little {
+                field[size - 1 - i]
+            } else {
+                field[i]
+            };
+            value = value << 8 | u32::from(byte);
+        }
+        Ok(value)
+    };
+    let directory = read(4, 4)? as usize;
+    let (mut width, mut height, mut channels) = (None, None, 1);
+    for entry in 0..read(directory, 2)? as usize {
+        let at = directory + 2 + 12 * entry;
+        // The values of SHORT fields are left-aligned in the value of the entry.
+        let value = match read(at + 2, 2)? {
+            3 => read(at + 8, 2)?,
+            4 => read(at + 8, 4)?,
+            _ => continue,
+        };
+        match read(at, 2)? {
+            256 => width = Some(value),
+            257 => height = Some(value),
+            277 => channels = value as u16,
+            _ => {}
+        }
+    }
+    match (height, width) {
+        (Some(height), Some(width)) => Ok(Header {
+            height,
+            width,
+            channels,
+        }),
+        _ => Err(invalid(
+            "the TIFF image does not declare its width and height.",
+        )),
+    }
+}
+
+/// ## The header of the PNG, JPEG or TIFF image `bytes`, recognized by their signature.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::image;
+/// let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+/// png.extend([0, 0, 2, 128, 0, 0, 1, 224, 8, 2, 0, 0, 0]);
+/// let header = image::parse(&png).unwrap();
+/// assert_eq!((header.height, header.width, header.channels), (480, 640, 3));
+/// ```
+pub fn parse(bytes: &[u8]) -> Result<Header, ConvDimError> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png(bytes)
+    } else if bytes.starts_with(&[0xff, 0xd8]) {
+        jpeg(bytes)
+    } else if bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*") {
+        tiff(bytes)
+    } else {
+        Err(invalid("the file is not a PNG, JPEG or TIFF image."))
+    }
+}
+
+/// ## The header of the PNG, JPEG or TIFF image at `path`.
+pub fn read(path: &Path) -> Result<Header, ConvDimError> {
+    let bytes = std::fs::read(path).map_err(|e| ConvDimError::Image {
+        message: format!("'{}': {}", path.display(), e),
+    })?;
+    parse(&bytes).map_err(|e| match e {
+        ConvDimError::Image { message } => ConvDimError::Image {
+            message: format!("'{}': {}", path.display(), message),
+        },
+        e => e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers() {
+        // A grayscale JPEG with an APP0 segment, fill bytes and a progressive frame of 300x200.
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe0, 0, 4, 0, 0, 0xff, 0xff];
+        jpeg.extend([0xc2, 0, 11, 8, 0, 200, 1, 44, 1, 1, 0x11, 0]);
+        assert_eq!(
+            parse(&jpeg),
+            Ok(Header {
+                height: 200,
+                width: 300,
+                channels: 1
+            })
+        );
+        assert!(parse(&jpeg[..8]).is_err());
+
+        // A big-endian TIFF whose width is a SHORT and whose height is a LONG.
+        let mut tiff = b"MM\0*\0\0\0\x08\0\x03".to_vec();
+        tiff.extend([1, 0, 0, 3, 0, 0, 0, 1, 1, 0, 0, 0]);
+        tiff.extend([1, 1, 0, 4, 0, 0, 0, 1, 0, 1, 0, 0]);
+        tiff.extend([1, 21, 0, 3, 0, 0, 0, 1, 0, 4, 0, 0]);
+        assert_eq!(
+            parse(&tiff),
+            Ok(Header {
+                height: 65536,
+                width: 256,
+                channels: 4
+            })
+        );
+
+        assert!(parse(b"GIF89a").is_err());
+    }
+}
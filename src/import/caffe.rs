@@ -0,0 +1,639 @@
+//! Import of Caffe network definitions, e.g. `deploy.prototxt`.
+//!
+//! A network definition is a protocol buffers message in the text format, whose layers read the
+//! blobs named by their `bottom`s and write the blobs named by their `top`s, e.g.
+//!
+//! ```text
+//! input: "data"
+//! input_shape { dim: 1 dim: 3 dim: 224 dim: 224 }
+//! layer {
+//!   name: "conv1"
+//!   type: "Convolution"
+//!   bottom: "data"
+//!   top: "conv1"
+//!   convolution_param { num_output: 64 kernel_size: 7 stride: 2 pad: 3 }
+//! }
+//! ```
+//!
+//! `Convolution`, `Deconvolution` and `Pooling` layers become (transposed) convolutional nodes
+//! named after the layers, `Upsample` layers transposed convolutions, and `Eltwise` and `Concat`
+//! layers of several blobs merge nodes. Caffe rounds the output dimension of poolings up, so they
+//! are computed by the formula of the [Caffe convention](crate::convention::Convention::Caffe)
+//! unless their `round_mode` is `FLOOR`. A `Crop` layer takes the dimension of its reference blob,
+//! and once the blobs are flattened (by an `InnerProduct` layer or a global pooling, ...) the
+//! remaining layers are ignored. The blobs that no other layer reads become the outputs. Both the
+//! `layer` and the legacy `layers` definitions are read, and the layers of the `TEST` phase are
+//! skipped.
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{effective_filter_size, Builder, Imported};
+use crate::convention::{Convention, Kind};
+use crate::graph::Op;
+
+#[derive(Debug, Clone, PartialEq)]
+/// A token of the text format, with the quotes of strings removed.
+enum Token {
+    Word(String),
+    Text(String),
+    Symbol(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "'{}'", word),
+            Token::Text(text) => write!(f, "\"{}\"", text),
+            Token::Symbol(symbol) => write!(f, "'{}'", symbol),
+        }
+    }
+}
+
+/// Split `text` into its tokens with their line numbers, skipping the `#` comments.
+fn tokens(text: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = vec![];
+    for (index, line) in text.lines().enumerate() {
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            match c {
+                '#' => break,
+                ',' | ';' => {}
+                _ if c.is_whitespace() => {}
+                ':' | '{' | '}' | '<' | '>' | '[' | ']' => {
+                    tokens.push((Token::Symbol(c), index + 1))
+                }
+                '"' | '\'' => {
+                    let mut text = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, quote)) if quote == c => break,
+                            Some((_, '\\')) => text.extend(chars.next().map(|(_, c)| c)),
+                            Some((_, c)) => text.push(c),
+                            None => {
+                                return Err(format!(
+                                    "The string on line {} is not closed.",
+                                    index + 1
+                                ))
+                            }
+                        }
+                    }
+                    tokens.push((Token::Text(text), index + 1));
+                }
+                _ => {
+                    let mut end = start + c.len_utf8();
+                    while let Some(&(i, c)) = chars.peek() {
+                        if !(c.is_alphanumeric() || "_.+-".contains(c)) {
+                            break;
+                        }
+                        end = i + c.len_utf8();
+                        chars.next();
+                    }
+                    tokens.push((Token::Word(line[start..end].to_string()), index + 1));
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+/// A value of the text format, a scalar or a nested message.
+enum Value {
+    Scalar(String),
+    Message(Message),
+}
+
+#[derive(Debug, Clone, Default)]
+/// A message of the text format, with its fields in the order of the file.
+struct Message {
+    fields: Vec<(String, Value)>,
+}
+
+impl Message {
+    /// The scalars of the (repeated) field `name`.
+    fn scalars<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.fields
+            .iter()
+            .filter_map(move |(field, value)| match value {
+                Value::Scalar(scalar) if field == name => Some(scalar.as_str()),
+                _ => None,
+            })
+    }
+
+    /// The messages of the (repeated) field `name`.
+    fn messages<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Message> + 'a {
+        self.fields
+            .iter()
+            .filter_map(move |(field, value)| match value {
+                Value::Message(message) if field == name => Some(message),
+                _ => None,
+            })
+    }
+
+    /// The scalar of the field `name`, the last one if it is given several times.
+    fn scalar<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        self.scalars(name).last()
+    }
+
+    /// The message of the field `name`, the last one if it is given several times.
+    fn message<'a>(&'a self, name: &'a str) -> Option<&'a Message> {
+        self.messages(name).last()
+    }
+
+    /// The integers of the (repeated) field `name`.
+    fn ints(&self, name: &str) -> Result<Vec<i64>, String> {
+        self.scalars(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("The field '{}' is not an integer: '{}'.", name, value))
+            })
+            .collect()
+    }
+
+    /// The integer of the field `name`.
+    fn int(&self, name: &str) -> Result<Option<i64>, String> {
+        Ok(self.ints(name)?.last().copied())
+    }
+}
+
+/// A parser of the text format of protocol buffers.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    /// The fields up to the symbol `close`, or up to the end of the text.
+    fn message(&mut self, close: Option<char>) -> Result<Message, String> {
+        let mut message = Message::default();
+        loop {
+            let (token, line) = match (self.next(), close) {
+                (Some(next), _) => next,
+                (None, None) => return Ok(message),
+                (None, Some(close)) => {
+                    return Err(format!("A message is not closed by '{}'.", close))
+                }
+            };
+            let name = match token {
+                Token::Symbol(symbol) if Some(symbol) == close => return Ok(message),
+                Token::Word(name) => name,
+                token => {
+                    return Err(format!(
+                        "Expected a field name on line {}, found {}.",
+                        line, token
+                    ))
+                }
+            };
+            let colon = self.peek() == Some(&Token::Symbol(':'));
+            if colon {
+                self.pos += 1;
+            }
+            // Repeated fields may be given as a list, e.g. `dim: [1, 3, 224, 224]`.
+            if colon && self.peek() == Some(&Token::Symbol('[')) {
+                self.pos += 1;
+                while self.peek() != Some(&Token::Symbol(']')) {
+                    let value = self.value()?;
+                    message.fields.push((name.clone(), value));
+                }
+                self.pos += 1;
+            } else {
+                let value = self.value()?;
+                message.fields.push((name, value));
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.next() {
+            Some((Token::Symbol('{'), _)) => Ok(Value::Message(self.message(Some('}'))?)),
+            Some((Token::Symbol('<'), _)) => Ok(Value::Message(self.message(Some('>'))?)),
+            Some((Token::Word(scalar), _)) | Some((Token::Text(scalar), _)) => {
+                Ok(Value::Scalar(scalar))
+            }
+            Some((token, line)) => Err(format!(
+                "Expected a value on line {}, found {}.",
+                line, token
+            )),
+            None => Err("The definition ends before the value of a field.".to_string()),
+        }
+    }
+}
+
+/// Parse the network definition `text`.
+fn parse(text: &str) -> Result<Message, String> {
+    let mut parser = Parser {
+        tokens: tokens(text)?,
+        pos: 0,
+    };
+    parser.message(None)
+}
+
+/// The converter of the layers of a network definition.
+struct Converter {
+    builder: Builder,
+    /// The node computing every blob, `None` if the blob is flattened.
+    blobs: HashMap<String, Option<String>>,
+    /// The blobs that no layer has read since they were written, in the order they were written.
+    unread: Vec<String>,
+}
+
+impl Converter {
+    /// Declare the input `blob` of the given `shape`, which is flattened with less than three dimensions.
+    fn input(&mut self, blob: &str, shape: &[i64]) -> Result<(), String> {
+        let known = |value: i64| Some(value).filter(|&value| value > 0);
+        let producer = if shape.len() > 2 {
+            let dims: Vec<Option<i64>> = shape[2..].iter().map(|&dim| known(dim)).collect();
+            Some(self.builder.input(blob, &dims, known(shape[1]))?)
+        } else if shape.is_empty() {
+            Some(self.builder.input(blob, &[None], None)?)
+        } else {
+            None
+        };
+        self.write(blob, producer);
+        Ok(())
+    }
+
+    /// Record `producer` as the node computing the `blob`.
+    fn write(&mut self, blob: &str, producer: Option<String>) {
+        self.blobs.insert(blob.to_string(), producer);
+        if !self.unread.iter().any(|unread| unread == blob) {
+            self.unread.push(blob.to_string());
+        }
+    }
+
+    /// Convert a (transposed) convolution or pooling `layer` applied to the output of `input`.
+    fn conv(
+        &mut self,
+        layer: &Message,
+        name: &str,
+        kind: &str,
+        input: &str,
+    ) -> Result<Option<String>, String> {
+        let pooling = kind == "pooling";
+        let default = Message::default();
+        let params = layer
+            .message(if pooling {
+                "pooling_param"
+            } else {
+                "convolution_param"
+            })
+            .unwrap_or(&default);
+        if pooling && params.scalar("global_pooling") == Some("true") {
+            return Ok(None);
+        }
+        // The attributes are given once for all spatial dimensions or once per dimension, unless
+        // they are given by their `_h` and `_w` variants.
+        let attribute = |field: &str, prefix: &str, default: i64| -> Result<Vec<i64>, String> {
+            let height = params.int(&format!("{}_h", prefix))?;
+            let width = params.int(&format!("{}_w", prefix))?;
+            if let (Some(height), Some(width)) = (height, width) {
+                return Ok(vec![height, width]);
+            }
+            let values = params.ints(field)?;
+            Ok(if values.is_empty() {
+                vec![default]
+            } else {
+                values
+            })
+        };
+        let kernel = attribute("kernel_size", "kernel", 0)?;
+        let strides = attribute("stride", "stride", 1)?;
+        let pads = attribute("pad", "pad", 0)?;
+        let dilations = attribute("dilation", "dilation", 1)?;
+        if kernel[0] <= 0 {
+            return Err(format!("Layer '{}' has no 'kernel_size'.", name));
+        }
+        self.builder
+            .check_square(name, &[&kernel, &strides, &pads, &dilations]);
+
+        let filter_size = effective_filter_size(kernel[0], dilations[0]);
+        let transposed = kind == "deconvolution";
+        let channels = params.int("num_output")?;
+        let node = self.builder.layer(
+            name,
+            transposed,
+            input,
+            filter_size,
+            strides[0],
+            pads[0],
+            channels,
+        )?;
+        if pooling && params.scalar("round_mode") != Some("FLOOR") {
+            // The formula of Caffe's pooling does not depend on the padding.
+            if let Some(formula) = Convention::Caffe.formula(Kind::Pool, 0) {
+                self.builder.formula(&node, formula);
+            }
+        }
+        Ok(Some(node))
+    }
+
+    /// Convert the `layer`, recording the nodes computing its tops.
+    fn layer(&mut self, layer: &Message) -> Result<(), String> {
+        let name = layer.scalar("name").unwrap_or("layer");
+        // The legacy definitions name the types in upper case, e.g. `INNER_PRODUCT`.
+        let kind = layer
+            .scalar("type")
+            .unwrap_or_default()
+            .replace('_', "")
+            .to_lowercase();
+        let tops: Vec<&str> = layer.scalars("top").collect();
+        let mut bottoms = vec![];
+        for bottom in layer.scalars("bottom") {
+            match self.blobs.get(bottom) {
+                Some(producer) => bottoms.push(producer.clone()),
+                None => {
+                    return Err(format!(
+                        "Layer '{}' reads the blob '{}', which no layer writes before it.",
+                        name, bottom
+                    ))
+                }
+            }
+            if !tops.contains(&bottom) {
+                self.unread.retain(|unread| unread != bottom);
+            }
+        }
+
+        if kind == "input" {
+            let default = Message::default();
+            let params = layer.message("input_param").unwrap_or(&default);
+            let shapes = params
+                .messages("shape")
+                .map(|shape| shape.ints("dim"))
+                .collect::<Result<Vec<_>, _>>()?;
+            // A single shape applies to all the tops.
+            for (index, top) in tops.iter().enumerate() {
+                let shape = shapes.get(index).or_else(|| shapes.last());
+                self.input(top, shape.map(Vec::as_slice).unwrap_or_default())?;
+            }
+            return Ok(());
+        }
+        if kind.ends_with("data") {
+            // The first top holds the images, cropped to the `crop_size` if it is given.
+            let default = Message::default();
+            let crop = layer
+                .message("transform_param")
+                .unwrap_or(&default)
+                .int("crop_size")?
+                .filter(|&crop| crop > 0);
+            for (index, top) in tops.iter().enumerate() {
+                let producer = match index {
+                    0 => Some(self.builder.input(top, &[crop], None)?),
+                    _ => None,
+                };
+                self.write(top, producer);
+            }
+            return Ok(());
+        }
+
+        let spatial: Vec<String> = bottoms.iter().flatten().cloned().collect();
+        let output = match (kind.as_str(), bottoms.first().cloned().flatten()) {
+            (_, None) => None,
+            ("convolution" | "deconvolution" | "pooling", Some(input)) => {
+                self.conv(layer, name, &kind, &input)?
+            }
+            ("upsample", Some(input)) => {
+                let default = Message::default();
+                let params = layer.message("upsample_param").unwrap_or(&default);
+                let scale = params.int("scale")?.unwrap_or(2);
+                Some(self.builder.upsample(name, &input, scale)?)
+            }
+            ("eltwise", Some(_)) if spatial.len() > 1 => {
+                Some(self.builder.merge(name, Op::Add, spatial))
+            }
+            ("concat", Some(input)) if spatial.len() > 1 => {
+                let default = Message::default();
+                let params = layer.message("concat_param").unwrap_or(&default);
+                let axis = match params.int("axis")? {
+                    Some(axis) => axis,
+                    None => params.int("concat_dim")?.unwrap_or(1),
+                };
+                if axis == 1 {
+                    Some(self.builder.merge(name, Op::Concat, spatial))
+                } else {
+                    self.builder.warn(format!(
+                        "Layer '{}' concatenates along an axis other than the channels and is ignored.",
+                        name
+                    ));
+                    Some(input)
+                }
+            }
+            // The output is cropped to the dimension of the reference blob.
+            ("crop", Some(_)) if bottoms.len() > 1 => bottoms[1].clone(),
+            ("innerproduct" | "flatten" | "reshape", Some(_)) => None,
+            ("interp", Some(input)) => {
+                self.builder.warn(format!(
+                    "Layer '{}' of type Interp is assumed to leave the dimensions unchanged.",
+                    name
+                ));
+                Some(input)
+            }
+            // All other layers leave the spatial dimensions unchanged.
+            (_, Some(input)) => Some(input),
+        };
+        for top in tops {
+            self.write(top, output.clone());
+        }
+        Ok(())
+    }
+}
+
+/// ## Import the Caffe network definition `text`.
+pub fn import(text: &str) -> Result<Imported, String> {
+    let net = parse(text)?;
+    let mut converter = Converter {
+        builder: Builder::default(),
+        blobs: HashMap::new(),
+        unread: vec![],
+    };
+
+    // The inputs declared by the network have an `input_shape` or four `input_dim`s each.
+    let shapes = net
+        .messages("input_shape")
+        .map(|shape| shape.ints("dim"))
+        .collect::<Result<Vec<_>, _>>()?;
+    let dims = net.ints("input_dim")?;
+    for (index, input) in net.scalars("input").enumerate() {
+        let shape = match shapes.get(index) {
+            Some(shape) => shape.as_slice(),
+            None => dims.chunks(4).nth(index).unwrap_or_default(),
+        };
+        converter.input(input, shape)?;
+    }
+
+    for layer in net.messages("layer").chain(net.messages("layers")) {
+        let test = layer
+            .messages("include")
+            .any(|include| include.scalar("phase") == Some("TEST"));
+        if !test {
+            converter.layer(layer)?;
+        }
+    }
+    for blob in &converter.unread {
+        if let Some(Some(producer)) = converter.blobs.get(blob) {
+            converter.builder.output(producer);
+        }
+    }
+    converter.builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESNET: &str = r#"
+name: "ResNet-18"
+input: "data"
+input_shape { dim: 1 dim: 3 dim: 224 dim: 224 }
+layer {
+  name: "conv1"
+  type: "Convolution"
+  bottom: "data"
+  top: "conv1"
+  param { lr_mult: 1 decay_mult: 1 }
+  convolution_param {
+    num_output: 64
+    kernel_size: 7
+    pad: 3
+    stride: 2
+    weight_filler { type: "msra" }
+  }
+}
+layer { name: "conv1_relu" type: "ReLU" bottom: "conv1" top: "conv1" }
+layer {
+  name: "pool1"
+  type: "Pooling"
+  bottom: "conv1"
+  top: "pool1"
+  pooling_param { pool: MAX kernel_size: 3 stride: 2 }
+}
+layer {
+  name: "res2a_branch2a"
+  type: "Convolution"
+  bottom: "pool1"
+  top: "res2a_branch2a"
+  convolution_param { num_output: 64 kernel_size: 3 pad: 1 bias_term: false }
+}
+layer {
+  name: "res2a"
+  type: "Eltwise"
+  bottom: "pool1"
+  bottom: "res2a_branch2a"
+  top: "res2a"
+}
+layer {
+  name: "up"
+  type: "Deconvolution"
+  bottom: "res2a"
+  top: "up"
+  convolution_param { num_output: 64 kernel_size: 4 stride: 2 pad: 1 }
+}
+layer {
+  name: "concat"
+  type: "Concat"
+  bottom: "up"
+  bottom: "conv1"
+  top: "concat"
+}
+layer {
+  name: "pool5"
+  type: "Pooling"
+  bottom: "res2a"
+  top: "pool5"
+  pooling_param { pool: AVE global_pooling: true }
+}
+layer { name: "fc1000" type: "InnerProduct" bottom: "pool5" top: "fc1000" }
+layer { name: "prob" type: "Softmax" bottom: "fc1000" top: "prob" }
+"#;
+
+    #[test]
+    fn test_resnet() {
+        let imported = import(RESNET).unwrap();
+        assert!(imported.warnings.is_empty());
+        let graph = imported.graph;
+        assert_eq!(graph.inputs[0].name, "data");
+        assert_eq!(graph.inputs[0].input_dim, Some(224));
+        assert_eq!(graph.inputs[0].channels, Some(3));
+        assert_eq!(graph.outputs, vec!["concat"]);
+        let node = |name: &str| graph.nodes.iter().find(|node| node.name == name).unwrap();
+        assert_eq!(node("conv1").channels, Some(64));
+        assert_eq!(node("res2a").inputs, vec!["pool1", "res2a_branch2a"]);
+        // Caffe rounds the pooling up, to 56 instead of 55.
+        assert!(node("pool1").formula.is_some());
+        let shapes = graph.output_shapes(224).unwrap();
+        assert_eq!(shapes[0].1.dim, 112);
+        assert_eq!(shapes[0].1.channels, Some(128));
+    }
+
+    #[test]
+    fn test_legacy_definitions() {
+        let legacy = r#"
+input: "data"
+input_dim: 1
+input_dim: 3
+input_dim: 227
+input_dim: 227
+layers {
+  name: "conv1"
+  type: CONVOLUTION
+  bottom: "data"
+  top: "conv1"
+  convolution_param { num_output: 96 kernel_size: 11 stride: 4 }
+}
+layers {
+  name: "pool1"
+  type: POOLING
+  bottom: "conv1"
+  top: "pool1"
+  pooling_param { pool: MAX kernel_size: 3 stride: 2 round_mode: FLOOR }
+}
+"#;
+        let graph = import(legacy).unwrap().graph;
+        assert!(graph.nodes[1].formula.is_none());
+        assert_eq!(graph.output_shape(227).unwrap().dim, 27);
+    }
+
+    #[test]
+    fn test_input_layer_and_errors() {
+        let definition = "
+layer {
+  name: 'data' type: 'Input' top: 'data'
+  input_param { shape: { dim: [1, 3, 64, 64] } }
+}
+layer {
+  name: 'conv' type: 'Convolution' bottom: 'data' top: 'conv'
+  convolution_param { num_output: 8 kernel_h: 3 kernel_w: 3 stride: 2 }  # valid padding
+}
+";
+        let graph = import(definition).unwrap().graph;
+        assert_eq!(graph.inputs[0].input_dim, Some(64));
+        assert_eq!(graph.output_shape(64).unwrap().dim, 31);
+
+        assert_eq!(
+            import(&definition.replace("bottom: 'data'", "bottom: 'image'")).unwrap_err(),
+            "Layer 'conv' reads the blob 'image', which no layer writes before it."
+        );
+        assert_eq!(
+            import(&definition.replace("kernel_h: 3 kernel_w: 3", "")).unwrap_err(),
+            "Layer 'conv' has no 'kernel_size'."
+        );
+        assert_eq!(
+            import("layer { name: 'data' ").unwrap_err(),
+            "A message is not closed by '}'."
+        );
+        assert_eq!(
+            import("layer { : 'data' }").unwrap_err(),
+            "Expected a field name on line 1, found ':'."
+        );
+    }
+}
@@ -0,0 +1,429 @@
+//! Import of Darknet configurations, e.g. `yolov3.cfg` or `yolov4-tiny.cfg`.
+//!
+//! A configuration starts with the `[net]` section giving the input size, followed by a section
+//! of `key=value` options for every layer, e.g.
+//!
+//! ```text
+//! [net]
+//! width=416
+//! height=416
+//! channels=3
+//!
+//! [convolutional]
+//! filters=32
+//! size=3
+//! stride=1
+//! pad=1
+//!
+//! [route]
+//! layers=-1, 8
+//! ```
+//!
+//! The layers are numbered from 0 like Darknet numbers them, and the `layers` of a `[route]` and
+//! the `from` of a `[shortcut]` refer to them by their number, or relative to the current layer if
+//! negative. The nodes are named after the section and the number of the layer, e.g.
+//! `convolutional_0`. `[convolutional]`, `[deconvolutional]`, `[maxpool]`, `[upsample]` and
+//! `[reorg]` become (transposed) convolutional nodes, `[shortcut]` and `[route]` of several layers
+//! become merge nodes, and the inputs of the `[yolo]` and `[region]` layers become the outputs.
+//! Darknet pads a max pooling by `padding` in total, `size - 1` by default, so a pooling with an
+//! odd total padding is computed by the formula `floor((n + p - f) / s) + 1`, with the total
+//! padding as its padding.
+use std::collections::HashMap;
+
+use super::{effective_filter_size, Builder, Imported};
+use crate::formula::Formula;
+use crate::graph::Op;
+
+/// The formula of a max pooling with an odd total padding `p`.
+const MAXPOOL: &str = "floor((n + p - f) / s) + 1";
+
+/// A section of the configuration, with the line it starts on.
+struct Section<'a> {
+    name: &'a str,
+    line: usize,
+    options: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> Section<'a> {
+    /// The integers of the option `key`, separated by commas like the `layers` of a route.
+    fn ints(&self, key: &str) -> Result<Vec<i64>, String> {
+        let value = match self.options.get(key) {
+            Some(value) => value,
+            None => return Ok(vec![]),
+        };
+        value
+            .split(',')
+            .map(|number| {
+                number.trim().parse().map_err(|_| {
+                    format!(
+                        "The option '{}' of the section [{}] on line {} is not an integer: '{}'.",
+                        key, self.name, self.line, value
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// The integer option `key`, or `default` if it is missing.
+    fn int(&self, key: &str, default: i64) -> Result<i64, String> {
+        Ok(self.ints(key)?.first().copied().unwrap_or(default))
+    }
+
+    /// The stride of the layer, warning if `stride_x` and `stride_y` differ.
+    fn stride(&self, builder: &mut Builder, name: &str) -> Result<i64, String> {
+        let stride = self.int("stride", 1)?;
+        let strides = [self.int("stride_x", stride)?, self.int("stride_y", stride)?];
+        builder.check_square(name, &[&strides]);
+        Ok(strides[0])
+    }
+}
+
+/// Split the configuration `text` into its sections, skipping the lines commented by `#` or `;`.
+fn parse(text: &str) -> Result<Vec<Section<'_>>, String> {
+    let mut sections: Vec<Section> = vec![];
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            match name.strip_suffix(']') {
+                Some(name) => sections.push(Section {
+                    name: name.trim(),
+                    line: index + 1,
+                    options: HashMap::new(),
+                }),
+                None => return Err(format!("The section on line {} is not closed.", index + 1)),
+            }
+            continue;
+        }
+        let (key, value) = line.split_once('=').ok_or_else(|| {
+            format!(
+                "Line {} is neither a section nor a key=value option: '{}'.",
+                index + 1,
+                line
+            )
+        })?;
+        match sections.last_mut() {
+            Some(section) => {
+                section.options.insert(key.trim(), value.trim());
+            }
+            None => {
+                return Err(format!(
+                    "The option on line {} precedes the first section.",
+                    index + 1
+                ))
+            }
+        }
+    }
+    Ok(sections)
+}
+
+/// The output of the layer `from` as seen from the layer `index`, `None` if it is flattened.
+///
+/// The layer before the first one is the `input` of the network.
+fn source(
+    outputs: &[Option<String>],
+    input: &str,
+    index: usize,
+    from: i64,
+) -> Result<Option<String>, String> {
+    let source = if from < 0 { index as i64 + from } else { from };
+    match source {
+        -1 => Ok(Some(input.to_string())),
+        _ if source >= 0 && (source as usize) < index => Ok(outputs[source as usize].clone()),
+        _ => Err(format!(
+            "Layer {} refers to the layer {}, which does not precede it.",
+            index, from
+        )),
+    }
+}
+
+/// Add the (transposed) convolution of the `section` applied to the output of `input`.
+fn convolution(
+    builder: &mut Builder,
+    section: &Section,
+    name: &str,
+    input: &str,
+) -> Result<String, String> {
+    let size = section.int("size", 1)?;
+    // The flag `pad` overrides the `padding` by half the filter size.
+    let padding = if section.int("pad", 0)? != 0 {
+        size / 2
+    } else {
+        section.int("padding", 0)?
+    };
+    let stride = section.stride(builder, name)?;
+    let filter_size = effective_filter_size(size, section.int("dilation", 1)?);
+    let channels = section.int("filters", 1)?;
+    let transposed = section.name == "deconvolutional";
+    builder.layer(
+        name,
+        transposed,
+        input,
+        filter_size,
+        stride,
+        padding,
+        Some(channels),
+    )
+}
+
+/// Add the max pooling of the `section` applied to the output of `input`.
+fn maxpool(
+    builder: &mut Builder,
+    section: &Section,
+    name: &str,
+    input: &str,
+) -> Result<String, String> {
+    let stride = section.stride(builder, name)?;
+    let size = section.int("size", stride)?;
+    let padding = section.int("padding", size - 1)?;
+    if padding % 2 == 0 {
+        return builder.layer(name, false, input, size, stride, padding / 2, None);
+    }
+    let node = builder.layer(name, false, input, size, stride, padding, None)?;
+    builder.formula(
+        &node,
+        Formula::parse(MAXPOOL).expect("The formula of max pooling is valid."),
+    );
+    Ok(node)
+}
+
+/// ## Import the Darknet configuration `text`.
+pub fn import(text: &str) -> Result<Imported, String> {
+    let sections = parse(text)?;
+    let (net, layers) = match sections.split_first() {
+        Some((net, layers)) if net.name == "net" || net.name == "network" => (net, layers),
+        _ => return Err("The configuration does not start with a [net] section.".to_string()),
+    };
+    let mut builder = Builder::default();
+    let known = |value: i64| Some(value).filter(|&value| value > 0);
+    let dims = [known(net.int("height", 0)?), known(net.int("width", 0)?)];
+    let input = builder.input("input", &dims, known(net.int("channels", 0)?))?;
+
+    // The node computing the output of every layer, `None` once the feature maps are flattened.
+    let mut outputs: Vec<Option<String>> = vec![];
+    for (index, section) in layers.iter().enumerate() {
+        let name = format!("{}_{}", section.name, index);
+        let previous = source(&outputs, &input, index, -1)?;
+        let output = match (section.name, previous) {
+            ("route", _) => {
+                let mut inputs = vec![];
+                for from in section.ints("layers")? {
+                    inputs.push(source(&outputs, &input, index, from)?);
+                }
+                match inputs.into_iter().collect::<Option<Vec<String>>>() {
+                    Some(inputs) if inputs.len() > 1 => {
+                        Some(builder.merge(&name, Op::Concat, inputs))
+                    }
+                    Some(inputs) if inputs.len() == 1 => Some(inputs[0].clone()),
+                    Some(_) => return Err(format!("The route {} lists no layers.", index)),
+                    None => None,
+                }
+            }
+            (_, None) => None,
+            ("shortcut", Some(previous)) => {
+                let mut inputs = vec![previous];
+                for from in section.ints("from")? {
+                    inputs.extend(source(&outputs, &input, index, from)?);
+                }
+                Some(builder.merge(&name, Op::Add, inputs))
+            }
+            ("convolutional" | "deconvolutional" | "local", Some(previous)) => {
+                Some(convolution(&mut builder, section, &name, &previous)?)
+            }
+            ("maxpool", Some(previous)) => Some(maxpool(&mut builder, section, &name, &previous)?),
+            ("upsample", Some(previous)) => {
+                // A negative stride downsamples instead.
+                let stride = section.int("stride", 2)?;
+                Some(if stride < 0 {
+                    builder.layer(&name, false, &previous, -stride, -stride, 0, None)?
+                } else {
+                    builder.upsample(&name, &previous, stride)?
+                })
+            }
+            ("reorg", Some(previous)) => {
+                let stride = section.int("stride", 1)?;
+                Some(builder.layer(&name, false, &previous, stride, stride, 0, None)?)
+            }
+            ("avgpool" | "connected", Some(_)) => None,
+            ("yolo" | "region" | "detection" | "Gaussian_yolo", Some(previous)) => {
+                builder.output(&previous);
+                Some(previous)
+            }
+            // All other layers leave the spatial dimensions unchanged.
+            (_, Some(previous)) => Some(previous),
+        };
+        outputs.push(output);
+    }
+    builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YOLOV3_TINY: &str = "
+[net]
+# Testing
+batch=1
+width=416
+height=416
+channels=3
+
+[convolutional]
+batch_normalize=1
+filters=16
+size=3
+stride=1
+pad=1
+activation=leaky
+
+[maxpool]
+size=2
+stride=2
+
+[convolutional]
+batch_normalize=1
+filters=32
+size=3
+stride=1
+pad=1
+activation=leaky
+
+[maxpool]
+size=2
+stride=2
+
+[convolutional]
+batch_normalize=1
+filters=64
+size=3
+stride=1
+pad=1
+activation=leaky
+
+[maxpool]
+size=2
+stride=1
+
+[convolutional]
+size=1
+stride=1
+pad=1
+filters=255
+activation=linear
+
+[yolo]
+mask = 3,4,5
+classes=80
+
+[route]
+layers = -3
+
+[convolutional]
+batch_normalize=1
+filters=32
+size=1
+stride=1
+pad=1
+activation=leaky
+
+[upsample]
+stride=2
+
+[route]
+layers = -1, 2
+
+[convolutional]
+size=1
+stride=1
+pad=1
+filters=255
+activation=linear
+
+[yolo]
+mask = 0,1,2
+classes=80
+";
+
+    #[test]
+    fn test_yolov3_tiny() {
+        let imported = import(YOLOV3_TINY).unwrap();
+        assert!(imported.warnings.is_empty());
+        let graph = imported.graph;
+        assert_eq!(graph.outputs, vec!["convolutional_6", "convolutional_12"]);
+        assert_eq!(graph.inputs[0].input_dim, Some(416));
+        assert_eq!(graph.inputs[0].channels, Some(3));
+        let node = |name: &str| graph.nodes.iter().find(|node| node.name == name).unwrap();
+        assert_eq!(node("convolutional_0").padding, 1);
+        assert_eq!(node("convolutional_0").channels, Some(16));
+        assert_eq!(
+            node("route_11").inputs,
+            vec!["upsample_10", "convolutional_2"]
+        );
+        // The route of a single layer is the layer itself.
+        assert_eq!(node("convolutional_9").inputs, vec!["maxpool_5"]);
+
+        let grids = |dim: u32| -> Vec<u32> {
+            graph
+                .output_shapes(dim)
+                .unwrap()
+                .iter()
+                .map(|(_, shape)| shape.dim)
+                .collect()
+        };
+        assert_eq!(grids(416), vec![104, 208]);
+        // Darknet pads the poolings by 1 in total, which rounds odd dimensions up.
+        assert_eq!(grids(415), vec![104, 208]);
+    }
+
+    #[test]
+    fn test_shortcut_and_errors() {
+        let cfg = "
+[net]
+width=64
+height=64
+channels=3
+
+[convolutional]
+filters=8
+size=3
+stride=2
+pad=1
+
+[convolutional]
+filters=8
+size=3
+pad=1
+
+[shortcut]
+from=-2
+
+[avgpool]
+
+[connected]
+output=10
+";
+        let graph = import(cfg).unwrap().graph;
+        assert_eq!(
+            graph.nodes[2].inputs,
+            vec!["convolutional_1", "convolutional_0"]
+        );
+        assert_eq!(graph.output_shape(64).unwrap().dim, 32);
+
+        assert_eq!(
+            import("[convolutional]\nsize=3\n").unwrap_err(),
+            "The configuration does not start with a [net] section."
+        );
+        assert_eq!(
+            import(&cfg.replace("from=-2", "from=5")).unwrap_err(),
+            "Layer 2 refers to the layer 5, which does not precede it."
+        );
+        assert_eq!(
+            import(&cfg.replace("size=3\npad", "size=three\npad")).unwrap_err(),
+            "The option 'size' of the section [convolutional] on line 13 is not an integer: 'three'."
+        );
+        assert!(import("[net\nwidth=64\n").is_err());
+    }
+}
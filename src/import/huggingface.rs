@@ -0,0 +1,416 @@
+//! Import of the `config.json` of Hugging Face vision models.
+//!
+//! Unlike the other model formats, the configuration of a `transformers` model does not list its
+//! layers, but the hyperparameters from which the model class builds them, e.g. for a ResNet
+//!
+//! ```text
+//! {"model_type": "resnet", "embedding_size": 64, "hidden_sizes": [256, 512, 1024, 2048],
+//!  "depths": [3, 4, 6, 3], "layer_type": "bottleneck", "downsample_in_first_stage": false}
+//! ```
+//!
+//! The layers that change the spatial dimensions are rebuilt the way the model classes of
+//! `transformers` build them, and named after the paths of the modules (e.g.
+//! `encoder.stages.0.layers.0.shortcut`), so that they can be matched with the weights. Layers
+//! that don't change the spatial dimensions (normalization, activations, the attention and MLP
+//! blocks of transformers) are omitted.
+use serde_json::Value;
+
+use super::{Builder, Imported};
+use crate::graph::Op;
+use crate::json::parse_json;
+
+/// The values of `model_type` of the models that can be imported.
+const MODEL_TYPES: [&str; 13] = [
+    "resnet",
+    "regnet",
+    "convnext",
+    "convnextv2",
+    "vit",
+    "deit",
+    "beit",
+    "dinov2",
+    "vit_mae",
+    "vit_msn",
+    "swin",
+    "swinv2",
+    "donut-swin",
+];
+
+/// A field of the configuration, which fails if it is missing or not an integer.
+fn int(config: &Value, key: &str) -> Result<i64, String> {
+    match config.get(key) {
+        Some(value) => value
+            .as_i64()
+            .ok_or_else(|| format!("The field '{}' of the config is not an integer.", key)),
+        None => Err(format!("The config lacks the field '{}'.", key)),
+    }
+}
+
+/// A list of integers of the configuration, which fails if it is missing.
+fn ints(config: &Value, key: &str) -> Result<Vec<i64>, String> {
+    let values: Option<Vec<i64>> = config
+        .get(key)
+        .and_then(Value::as_array)
+        .and_then(|values| values.iter().map(Value::as_i64).collect());
+    values.ok_or_else(|| format!("The config lacks the list of integers '{}'.", key))
+}
+
+/// The stages of a hierarchical model, as pairs of the number of blocks and their channels.
+fn stages(config: &Value, channels: &str) -> Result<Vec<(i64, i64)>, String> {
+    let depths = ints(config, "depths")?;
+    let channels = ints(config, channels)?;
+    if depths.len() != channels.len() {
+        return Err("The config lists different numbers of 'depths' and channels.".to_string());
+    }
+    Ok(depths.into_iter().zip(channels).collect())
+}
+
+/// The state of the conversion of a configuration.
+struct Converter {
+    builder: Builder,
+    /// The node producing the current feature map and its number of channels.
+    current: String,
+    channels: i64,
+    /// The outputs of the stem and the stages (`stem`, `stage1`, ...), which backbones refer to in `out_features`.
+    features: Vec<String>,
+}
+
+impl Converter {
+    /// Add a (square) convolution applied to the current feature map.
+    fn conv(
+        &mut self,
+        name: &str,
+        filter_size: i64,
+        stride: i64,
+        padding: i64,
+        channels: i64,
+    ) -> Result<(), String> {
+        self.current = self.builder.layer(
+            name,
+            false,
+            &self.current,
+            filter_size,
+            stride,
+            padding,
+            Some(channels),
+        )?;
+        self.channels = channels;
+        Ok(())
+    }
+
+    /// Add the residual connection from `shortcut` to the current feature map.
+    fn residual(&mut self, name: &str, shortcut: String) {
+        self.current = self
+            .builder
+            .merge(name, Op::Add, vec![self.current.clone(), shortcut]);
+    }
+
+    /// Record the current feature map as the output of the stem or of a stage.
+    fn feature(&mut self) {
+        self.features.push(self.current.clone());
+    }
+
+    /// The stages of bottleneck or basic blocks of `ResNetModel`.
+    fn resnet(&mut self, config: &Value) -> Result<(), String> {
+        let bottleneck = config.get("layer_type").and_then(Value::as_str) != Some("basic");
+        let first_stage = config.get("downsample_in_first_stage") == Some(&Value::Bool(true));
+        let in_bottleneck = config.get("downsample_in_bottleneck") == Some(&Value::Bool(true));
+
+        self.conv("embedder.embedder", 7, 2, 3, int(config, "embedding_size")?)?;
+        self.conv("embedder.pooler", 3, 2, 1, self.channels)?;
+        self.feature();
+        for (i, (depth, channels)) in stages(config, "hidden_sizes")?.into_iter().enumerate() {
+            for j in 0..depth {
+                let stride = if j == 0 && (i > 0 || first_stage) {
+                    2
+                } else {
+                    1
+                };
+                let block = format!("encoder.stages.{}.layers.{}", i, j);
+                let input = (self.current.clone(), self.channels);
+                let shortcut = if input.1 != channels || stride != 1 {
+                    self.conv(&format!("{}.shortcut", block), 1, stride, 0, channels)?;
+                    std::mem::replace(&mut self.current, input.0.clone())
+                } else {
+                    input.0
+                };
+                self.channels = input.1;
+                let layer = |k: usize| format!("{}.layer.{}", block, k);
+                if bottleneck {
+                    // The stride is applied by the first 1x1 or by the 3x3 convolution.
+                    let reduced = channels / 4;
+                    let (first, second) = if in_bottleneck {
+                        (stride, 1)
+                    } else {
+                        (1, stride)
+                    };
+                    self.conv(&layer(0), 1, first, 0, reduced)?;
+                    self.conv(&layer(1), 3, second, 1, reduced)?;
+                    self.conv(&layer(2), 1, 1, 0, channels)?;
+                } else {
+                    self.conv(&layer(0), 3, stride, 1, channels)?;
+                    self.conv(&layer(1), 3, 1, 1, channels)?;
+                }
+                self.residual(&block, shortcut);
+            }
+            self.feature();
+        }
+        Ok(())
+    }
+
+    /// The stages of X or Y blocks of `RegNetModel`, which all start by downsampling.
+    fn regnet(&mut self, config: &Value) -> Result<(), String> {
+        let squeeze_excitation = config.get("layer_type").and_then(Value::as_str) == Some("y");
+        self.conv("embedder.embedder", 3, 2, 1, int(config, "embedding_size")?)?;
+        self.feature();
+        for (i, (depth, channels)) in stages(config, "hidden_sizes")?.into_iter().enumerate() {
+            for j in 0..depth {
+                let stride = if j == 0 { 2 } else { 1 };
+                let block = format!("encoder.stages.{}.layers.{}", i, j);
+                let input = (self.current.clone(), self.channels);
+                let shortcut = if input.1 != channels || stride != 1 {
+                    self.conv(&format!("{}.shortcut", block), 1, stride, 0, channels)?;
+                    std::mem::replace(&mut self.current, input.0.clone())
+                } else {
+                    input.0
+                };
+                self.conv(&format!("{}.layer.0", block), 1, 1, 0, channels)?;
+                self.conv(&format!("{}.layer.1", block), 3, stride, 1, channels)?;
+                // Y blocks apply the squeeze and excitation as `layer.2` before the last convolution.
+                let last = if squeeze_excitation { 3 } else { 2 };
+                self.conv(&format!("{}.layer.{}", block, last), 1, 1, 0, channels)?;
+                self.residual(&block, shortcut);
+            }
+            self.feature();
+        }
+        Ok(())
+    }
+
+    /// The patchifying stem and the stages of inverted bottlenecks of `ConvNextModel`.
+    fn convnext(&mut self, config: &Value) -> Result<(), String> {
+        let stages = stages(config, "hidden_sizes")?;
+        let patch_size = config
+            .get("patch_size")
+            .map_or(Ok(4), |_| int(config, "patch_size"))?;
+        self.conv(
+            "embeddings.patch_embeddings",
+            patch_size,
+            patch_size,
+            0,
+            stages.first().map_or(0, |&(_, channels)| channels),
+        )?;
+        self.feature();
+        for (i, (depth, channels)) in stages.into_iter().enumerate() {
+            if i > 0 {
+                let name = format!("encoder.stages.{}.downsampling_layer.1", i);
+                self.conv(&name, 2, 2, 0, channels)?;
+            }
+            // The depthwise 7x7 convolution is followed by pointwise layers, which keep the dimensions.
+            for j in 0..depth {
+                let block = format!("encoder.stages.{}.layers.{}", i, j);
+                let shortcut = self.current.clone();
+                self.conv(&format!("{}.dwconv", block), 7, 1, 3, channels)?;
+                self.residual(&block, shortcut);
+            }
+            self.feature();
+        }
+        Ok(())
+    }
+
+    /// The patch embedding of a vision transformer, which is followed by layers operating on the patches.
+    fn vit(&mut self, config: &Value) -> Result<(), String> {
+        let patch_size = int(config, "patch_size")?;
+        let channels = int(config, "hidden_size")?;
+        self.conv(
+            "embeddings.patch_embeddings.projection",
+            patch_size,
+            patch_size,
+            0,
+            channels,
+        )?;
+        self.feature();
+        Ok(())
+    }
+
+    /// The patch embedding and the patch merging layers between the stages of a Swin transformer.
+    ///
+    /// Patch merging concatenates the features of 2x2 neighbouring patches. It pads inputs of odd
+    /// dimension, which the unpadded convolution with filter size and stride 2 does not reproduce.
+    fn swin(&mut self, config: &Value) -> Result<(), String> {
+        let patch_size = int(config, "patch_size")?;
+        let embed_dim = int(config, "embed_dim")?;
+        let depths = ints(config, "depths")?;
+        self.conv(
+            "embeddings.patch_embeddings.projection",
+            patch_size,
+            patch_size,
+            0,
+            embed_dim,
+        )?;
+        for i in 0..depths.len() {
+            if i > 0 {
+                let name = format!("encoder.layers.{}.downsample", i - 1);
+                self.conv(&name, 2, 2, 0, 2 * self.channels)?;
+            }
+            self.feature();
+        }
+        Ok(())
+    }
+}
+
+/// ## Import the model described by the Hugging Face configuration `text`.
+///
+/// The dimension of the input is taken from the `image_size` of the configuration, if it has one.
+pub fn import(text: &str) -> Result<Imported, String> {
+    let mut config = &parse_json(text)?;
+    let mut builder = Builder::default();
+    let mut model_type = config
+        .get("model_type")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    // Detection and segmentation models nest the configuration of their backbone.
+    if !MODEL_TYPES.contains(&model_type) {
+        if let Some(backbone) = config
+            .get("backbone_config")
+            .filter(|c| c.get("model_type").is_some())
+        {
+            builder.warn(format!(
+                "Only the backbone of the '{}' model is imported.",
+                model_type
+            ));
+            config = backbone;
+            model_type = config
+                .get("model_type")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+        }
+    }
+    if !MODEL_TYPES.contains(&model_type) {
+        return Err(format!(
+            "The model type '{}' is not supported, expected one of: {}.",
+            model_type,
+            MODEL_TYPES.join(", ")
+        ));
+    }
+
+    let image_size = match config.get("image_size") {
+        Some(Value::Array(sizes)) => sizes.iter().map(Value::as_i64).collect(),
+        Some(size) => vec![size.as_i64()],
+        None => vec![None],
+    };
+    let channels = config.get("num_channels").and_then(Value::as_i64);
+    let input = builder.input("pixel_values", &image_size, channels)?;
+    let mut converter = Converter {
+        builder,
+        current: input,
+        channels: channels.unwrap_or(3),
+        features: vec![],
+    };
+    match model_type {
+        "resnet" => converter.resnet(config)?,
+        "regnet" => converter.regnet(config)?,
+        "convnext" | "convnextv2" => converter.convnext(config)?,
+        "swin" | "swinv2" | "donut-swin" => converter.swin(config)?,
+        _ => converter.vit(config)?,
+    }
+
+    // Backbones report the feature maps listed in `out_features`, e.g. `["stage2", "stage3", "stage4"]`.
+    if let Some(out_features) = config.get("out_features").and_then(Value::as_array) {
+        for feature in out_features.iter().filter_map(Value::as_str) {
+            let index = match feature.strip_prefix("stage") {
+                Some(stage) => stage.parse().ok(),
+                None if feature == "stem" => Some(0),
+                None => None,
+            };
+            match index.and_then(|index: usize| converter.features.get(index)) {
+                Some(node) => {
+                    let node = node.clone();
+                    converter.builder.output(&node)
+                }
+                None => converter
+                    .builder
+                    .warn(format!("The output feature '{}' does not exist.", feature)),
+            }
+        }
+    }
+    converter.builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resnet_backbone() {
+        // The configuration of microsoft/resnet-50, used as the backbone of a detection model.
+        let config = r#"{
+            "model_type": "detr",
+            "backbone_config": {
+                "model_type": "resnet", "num_channels": 3, "embedding_size": 64,
+                "hidden_sizes": [256, 512, 1024, 2048], "depths": [3, 4, 6, 3],
+                "layer_type": "bottleneck", "downsample_in_first_stage": false,
+                "out_features": ["stage2", "stage3", "stage4"]
+            }
+        }"#;
+        let imported = import(config).unwrap();
+        assert_eq!(
+            imported.warnings,
+            vec!["Only the backbone of the 'detr' model is imported."]
+        );
+        let graph = imported.graph;
+        assert_eq!(
+            graph.outputs,
+            vec![
+                "encoder.stages.1.layers.3",
+                "encoder.stages.2.layers.5",
+                "encoder.stages.3.layers.2"
+            ]
+        );
+        let dims: Vec<u32> = graph
+            .output_shapes(224)
+            .unwrap()
+            .iter()
+            .map(|(_, shape)| shape.dim)
+            .collect();
+        assert_eq!(dims, vec![28, 14, 7]);
+        // The first block of every stage projects its input in the shortcut.
+        let shortcut = graph
+            .nodes
+            .iter()
+            .find(|node| node.name == "encoder.stages.1.layers.0.shortcut")
+            .unwrap();
+        assert_eq!((shortcut.stride, shortcut.channels), (2, Some(512)));
+        assert_eq!(graph.inputs[0].channels, Some(3));
+    }
+
+    #[test]
+    fn test_transformers() {
+        let vit =
+            r#"{"model_type": "vit", "image_size": 224, "patch_size": 16, "hidden_size": 768}"#;
+        let graph = import(vit).unwrap().graph;
+        assert_eq!(graph.inputs[0].input_dim, Some(224));
+        assert_eq!(graph.output_shape(0).unwrap().dim, 14);
+
+        let swin = r#"{"model_type": "swin", "image_size": 224, "patch_size": 4, "embed_dim": 96,
+                       "depths": [2, 2, 6, 2]}"#;
+        let graph = import(swin).unwrap().graph;
+        assert_eq!(graph.output_shape(0).unwrap().dim, 7);
+        assert_eq!(graph.nodes.last().unwrap().channels, Some(768));
+
+        let convnext = r#"{"model_type": "convnext", "hidden_sizes": [96, 192, 384, 768],
+                           "depths": [3, 3, 9, 3]}"#;
+        let graph = import(convnext).unwrap().graph;
+        assert_eq!(graph.output_shape(224).unwrap().dim, 7);
+    }
+
+    #[test]
+    fn test_unsupported_configs() {
+        assert!(import(r#"{"model_type": "bert"}"#)
+            .unwrap_err()
+            .contains("not supported"));
+        assert_eq!(
+            import(r#"{"model_type": "resnet", "embedding_size": 64}"#).unwrap_err(),
+            "The config lacks the list of integers 'depths'."
+        );
+    }
+}
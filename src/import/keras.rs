@@ -0,0 +1,527 @@
+//! Import of Keras models.
+//!
+//! Keras describes the architecture of a model as JSON (the output of `model.to_json()`). For a
+//! `Sequential` model, the layers are applied one after the other. For a functional model, every
+//! layer lists the layers it is called on in its `inbound_nodes`, and a layer that is called several
+//! times (shared weights) becomes a separate node for every call.
+//!
+//! `Conv2D`, `Conv2DTranspose`, the pooling layers and their 1D and 3D counterparts become
+//! (transposed) convolutional nodes, `UpSampling2D` by a factor `s` becomes a transposed convolution
+//! with `filter_size = stride = s`, `ZeroPadding2D` a 1x1 convolution with the corresponding padding,
+//! and `Cropping2D` by `c` an unpadded convolution with filter size `2c + 1`. `padding='same'` is
+//! converted into the symmetric padding that leads to the same output dimension.
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use super::{base_class, effective_filter_size, Builder, Imported};
+use crate::graph::{Op, INPUT};
+use crate::json::parse_json;
+
+/// An integer or a list of integers (one per spatial dimension) among the `config` of a layer.
+fn ints(config: &Value, key: &str) -> Option<Vec<i64>> {
+    match config.get(key)? {
+        Value::Array(values) => values.iter().map(Value::as_i64).collect(),
+        value => value.as_i64().map(|value| vec![value]),
+    }
+}
+
+/// The `(before, after)` amounts of padding or cropping per spatial dimension, which Keras accepts
+/// as a single integer, one integer per dimension or a pair of integers per dimension.
+fn pairs(config: &Value, key: &str) -> Option<Vec<(i64, i64)>> {
+    match config.get(key)? {
+        Value::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::Array(pair) if pair.len() == 2 => {
+                    Some((pair[0].as_i64()?, pair[1].as_i64()?))
+                }
+                value => value.as_i64().map(|value| (value, value)),
+            })
+            .collect(),
+        value => value.as_i64().map(|value| vec![(value, value)]),
+    }
+}
+
+/// The layers referred to by an entry of `inbound_nodes`, as pairs of the layer name and the index of its call.
+///
+/// Keras 2 writes every call as a list `[layer, call, tensor, kwargs]`, whereas Keras 3 writes the
+/// arguments of the call, in which the tensors record their `keras_history`.
+fn inbound(node: &Value, layers: &mut Vec<(String, usize)>) {
+    match node {
+        Value::Array(values) => match values.as_slice() {
+            [Value::String(name), call, ..] if call.as_i64().is_some() => {
+                layers.push((name.clone(), call.as_i64().unwrap_or(0) as usize))
+            }
+            values => {
+                for value in values {
+                    inbound(value, layers);
+                }
+            }
+        },
+        Value::Object(members) => match node.get("keras_history") {
+            Some(history) => inbound(history, layers),
+            None => {
+                for (key, value) in members {
+                    // Keyword arguments are masks and flags rather than the inputs of the layer.
+                    if key != "kwargs" {
+                        inbound(value, layers);
+                    }
+                }
+            }
+        },
+        _ => {}
+    }
+}
+
+struct Converter {
+    builder: Builder,
+    /// Whether the channels precede the spatial dimensions, as for `data_format='channels_first'`.
+    channels_first: bool,
+}
+
+impl Converter {
+    /// Declare an input with the `batch_input_shape` (or `batch_shape`) found in `config`.
+    fn input(&mut self, name: &str, config: &Value) -> Result<String, String> {
+        let shape = config
+            .get("batch_input_shape")
+            .or_else(|| config.get("batch_shape"))
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        // The batch dimension comes first, unknown dimensions are `null`.
+        let dims: Vec<Option<i64>> = shape.iter().skip(1).map(Value::as_i64).collect();
+        if dims.len() < 2 {
+            return Err(format!(
+                "The input '{}' does not have spatial dimensions.",
+                name
+            ));
+        }
+        let (spatial, channels) = if self.channels_first {
+            (&dims[1..], dims[0])
+        } else {
+            (&dims[..dims.len() - 1], dims[dims.len() - 1])
+        };
+        self.builder.input(name, spatial, channels)
+    }
+
+    /// Convert the layer `name` of the given `class` applied to the outputs of the nodes `inputs`.
+    ///
+    /// Returns the node producing the output of the layer, which is `None` once the feature
+    /// maps have lost their spatial dimensions.
+    fn layer(
+        &mut self,
+        class: &str,
+        config: &Value,
+        name: &str,
+        inputs: &[Option<String>],
+    ) -> Result<Option<String>, String> {
+        let spatial: Vec<String> = inputs.iter().flatten().cloned().collect();
+        let input = match spatial.first() {
+            Some(input) => input.clone(),
+            None => return Ok(None),
+        };
+        let base = base_class(class);
+        let node = match base {
+            "Conv" | "Convolution" | "SeparableConv" | "SeparableConvolution" | "DepthwiseConv" => {
+                self.conv(config, name, &input, false)?
+            }
+            "Conv1DTranspose" | "Conv2DTranspose" | "Conv3DTranspose" | "Deconvolution" => {
+                self.conv(config, name, &input, true)?
+            }
+            "MaxPooling" | "AveragePooling" | "MaxPool" | "AvgPool" => {
+                self.pool(config, name, &input)?
+            }
+            "UpSampling" => {
+                let size = ints(config, "size").unwrap_or_else(|| vec![2]);
+                self.builder.check_square(name, &[&size]);
+                self.builder.upsample(name, &input, size[0])?
+            }
+            "ZeroPadding" => {
+                let padding = self.padding(config, name, "padding")?;
+                self.builder
+                    .layer(name, false, &input, 1, 1, padding, None)?
+            }
+            "Cropping" => {
+                let cropping = self.padding(config, name, "cropping")?;
+                self.builder
+                    .layer(name, false, &input, 2 * cropping + 1, 1, 0, None)?
+            }
+            "Add" | "Subtract" | "Multiply" | "Average" | "Maximum" | "Minimum"
+                if spatial.len() > 1 =>
+            {
+                self.builder.merge(name, Op::Add, spatial)
+            }
+            "Concatenate" if spatial.len() > 1 => {
+                let axis = config.get("axis").and_then(Value::as_i64).unwrap_or(-1);
+                let channels = if self.channels_first { 1 } else { -1 };
+                if axis == channels {
+                    self.builder.merge(name, Op::Concat, spatial)
+                } else {
+                    self.builder.warn(format!(
+                        "Layer '{}' concatenates along an axis other than the channels and is ignored.",
+                        name
+                    ));
+                    input
+                }
+            }
+            "Flatten"
+            | "GlobalAveragePooling"
+            | "GlobalMaxPooling"
+            | "GlobalAvgPool"
+            | "GlobalMaxPool"
+            | "Reshape" => return Ok(None),
+            "Sequential" | "Functional" | "Model" => {
+                self.builder.warn(format!(
+                    "The nested model '{}' is assumed to leave the dimension unchanged.",
+                    name
+                ));
+                input
+            }
+            // All other layers leave the spatial dimensions unchanged.
+            _ => input,
+        };
+        Ok(Some(node))
+    }
+
+    /// Convert a (transposed) convolutional layer.
+    fn conv(
+        &mut self,
+        config: &Value,
+        name: &str,
+        input: &str,
+        transposed: bool,
+    ) -> Result<String, String> {
+        let kernel = match ints(config, "kernel_size") {
+            Some(kernel) if !kernel.is_empty() => kernel,
+            _ => {
+                return Err(format!(
+                    "Layer '{}' does not define its 'kernel_size'.",
+                    name
+                ))
+            }
+        };
+        let strides = ints(config, "strides").unwrap_or_else(|| vec![1]);
+        let dilations = ints(config, "dilation_rate").unwrap_or_else(|| vec![1]);
+        self.builder
+            .check_square(name, &[&kernel, &strides, &dilations]);
+        let filter_size = effective_filter_size(kernel[0], dilations[0]);
+        let stride = strides[0];
+
+        let padding = match config.get("padding").and_then(Value::as_str) {
+            // Causal padding pads `filter_size - 1` on one side, which leads to the same output
+            // dimension as 'same' padding.
+            Some("same") | Some("causal") => {
+                self.builder
+                    .same_padding(name, filter_size, stride, transposed)
+            }
+            _ => {
+                if transposed && filter_size < stride {
+                    self.builder.warn(format!(
+                        "The filter of layer '{}' is smaller than its stride, so Keras pads its output.",
+                        name
+                    ));
+                }
+                0
+            }
+        };
+        if transposed && !matches!(config.get("output_padding"), None | Some(Value::Null)) {
+            self.builder.warn(format!(
+                "The 'output_padding' of layer '{}' is ignored.",
+                name
+            ));
+        }
+        let channels = config.get("filters").and_then(Value::as_i64);
+        self.builder.layer(
+            name,
+            transposed,
+            input,
+            filter_size,
+            stride,
+            padding,
+            channels,
+        )
+    }
+
+    /// Convert a pooling layer, whose `strides` default to its `pool_size`.
+    fn pool(&mut self, config: &Value, name: &str, input: &str) -> Result<String, String> {
+        let size = ints(config, "pool_size").unwrap_or_else(|| vec![2]);
+        let strides = ints(config, "strides").unwrap_or_else(|| size.clone());
+        self.builder.check_square(name, &[&size, &strides]);
+        let padding = match config.get("padding").and_then(Value::as_str) {
+            Some("same") => self.builder.same_padding(name, size[0], strides[0], false),
+            _ => 0,
+        };
+        self.builder
+            .layer(name, false, input, size[0], strides[0], padding, None)
+    }
+
+    /// The symmetric padding (or cropping) of the first spatial dimension given under `key`.
+    fn padding(&mut self, config: &Value, name: &str, key: &str) -> Result<i64, String> {
+        let pairs = match pairs(config, key) {
+            Some(pairs) if !pairs.is_empty() => pairs,
+            _ => return Err(format!("Layer '{}' does not define its '{}'.", name, key)),
+        };
+        let (before, after) = pairs[0];
+        if before != after {
+            self.builder.warn(format!(
+                "Layer '{}' is asymmetric ({} before, {} after), the amount before is used.",
+                name, before, after
+            ));
+        }
+        let flat: Vec<i64> = pairs.iter().map(|&(before, _)| before).collect();
+        self.builder.check_square(name, &[&flat]);
+        Ok(before)
+    }
+}
+
+/// The class, configuration and name of the entry `layer` of the list of layers.
+fn describe(layer: &Value) -> (&str, &Value, String) {
+    let class = layer
+        .get("class_name")
+        .and_then(Value::as_str)
+        .unwrap_or("");
+    let config = layer.get("config").unwrap_or(&Value::Null);
+    let name = layer
+        .get("name")
+        .or_else(|| config.get("name"))
+        .and_then(Value::as_str)
+        .unwrap_or(class)
+        .to_string();
+    (class, config, name)
+}
+
+/// Convert the layers of a `Sequential` model, which are applied one after the other.
+fn sequential(converter: &mut Converter, layers: &[Value]) -> Result<(), String> {
+    let mut current: Option<Option<String>> = None;
+    for layer in layers {
+        let (class, config, name) = describe(layer);
+        if class == "InputLayer" {
+            current = Some(Some(converter.input(&name, config)?));
+            continue;
+        }
+        // Without an `InputLayer`, the input shape is recorded by the first layer.
+        let input = match current {
+            Some(input) => input,
+            None => Some(converter.input(INPUT, config)?),
+        };
+        current = Some(converter.layer(class, config, &name, &[input])?);
+    }
+    Ok(())
+}
+
+/// Convert the layers of a functional model, following the `inbound_nodes` of every layer.
+fn functional(converter: &mut Converter, model: &Value, layers: &[Value]) -> Result<(), String> {
+    // The node producing the output of every call of a layer.
+    let mut producers: HashMap<(String, usize), Option<String>> = HashMap::new();
+    for layer in layers {
+        let (class, config, name) = describe(layer);
+        if class == "InputLayer" {
+            let input = converter.input(&name, config)?;
+            producers.insert((name, 0), Some(input));
+            continue;
+        }
+        let calls = layer
+            .get("inbound_nodes")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for (call, node) in calls.iter().enumerate() {
+            let mut references = vec![];
+            inbound(node, &mut references);
+            let mut inputs = vec![];
+            for reference in references {
+                match producers.get(&reference) {
+                    Some(producer) => inputs.push(producer.clone()),
+                    None => {
+                        return Err(format!(
+                            "Layer '{}' takes its input from '{}', which is not defined before it.",
+                            name, reference.0
+                        ))
+                    }
+                }
+            }
+            let output = converter.layer(class, config, &name, &inputs)?;
+            producers.insert((name.clone(), call), output);
+        }
+    }
+
+    let mut outputs = vec![];
+    if let Some(output_layers) = model.get("output_layers") {
+        inbound(output_layers, &mut outputs);
+    }
+    for output in outputs {
+        if let Some(Some(node)) = producers.get(&output) {
+            converter.builder.output(node);
+        }
+    }
+    Ok(())
+}
+
+/// ## Import the Keras model described by the JSON document `text`.
+pub fn import(text: &str) -> Result<Imported, String> {
+    let json = parse_json(text)?;
+    // Models saved to HDF5 store the architecture as `model_config`.
+    let model = json.get("model_config").unwrap_or(&json);
+    let class = model
+        .get("class_name")
+        .and_then(Value::as_str)
+        .unwrap_or("");
+    let config = model.get("config").unwrap_or(&Value::Null);
+    // Early versions of Keras store the layers of a `Sequential` model directly as its config.
+    let layers = match config {
+        Value::Array(layers) => layers.as_slice(),
+        config => config
+            .get("layers")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default(),
+    };
+
+    let channels_first = layers
+        .iter()
+        .filter_map(|layer| layer.get("config")?.get("data_format")?.as_str())
+        .next()
+        == Some("channels_first");
+    let mut converter = Converter {
+        builder: Builder::default(),
+        channels_first,
+    };
+    match class {
+        "Sequential" => sequential(&mut converter, layers)?,
+        "Functional" | "Model" => functional(&mut converter, config, layers)?,
+        class => return Err(format!("Unsupported Keras model class '{}'.", class)),
+    }
+    converter.builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Shape;
+
+    #[test]
+    fn test_sequential() {
+        let imported = import(
+            r#"{"class_name": "Sequential", "config": {"name": "autoencoder", "layers": [
+                {"class_name": "InputLayer", "config": {"batch_input_shape": [null, 28, 28, 1], "name": "image"}},
+                {"class_name": "Conv2D", "config": {"name": "conv", "filters": 16, "kernel_size": [3, 3],
+                    "strides": [1, 1], "padding": "same", "dilation_rate": [1, 1], "data_format": "channels_last"}},
+                {"class_name": "BatchNormalization", "config": {"name": "bn"}},
+                {"class_name": "MaxPooling2D", "config": {"name": "pool", "pool_size": [2, 2], "strides": null,
+                    "padding": "valid"}},
+                {"class_name": "ZeroPadding2D", "config": {"name": "pad", "padding": [[1, 1], [1, 1]]}},
+                {"class_name": "Conv2DTranspose", "config": {"name": "deconv", "filters": 8, "kernel_size": [4, 4],
+                    "strides": [2, 2], "padding": "same", "output_padding": null}},
+                {"class_name": "UpSampling2D", "config": {"name": "up", "size": [2, 2]}}
+            ]}, "keras_version": "2.13.1", "backend": "tensorflow"}"#,
+        )
+        .unwrap();
+        assert!(imported.warnings.is_empty());
+        let dims: Vec<(String, Shape)> = imported.graph.shapes(0).unwrap();
+        assert_eq!(
+            dims,
+            vec![
+                (
+                    "conv".to_string(),
+                    Shape {
+                        dim: 28,
+                        channels: Some(16)
+                    }
+                ),
+                (
+                    "pool".to_string(),
+                    Shape {
+                        dim: 14,
+                        channels: Some(16)
+                    }
+                ),
+                (
+                    "pad".to_string(),
+                    Shape {
+                        dim: 16,
+                        channels: Some(16)
+                    }
+                ),
+                (
+                    "deconv".to_string(),
+                    Shape {
+                        dim: 32,
+                        channels: Some(8)
+                    }
+                ),
+                (
+                    "up".to_string(),
+                    Shape {
+                        dim: 64,
+                        channels: Some(8)
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_functional() {
+        // A residual connection and a shared layer called twice, in the format of Keras 2.
+        let imported = import(
+            r#"{"class_name": "Functional", "config": {"name": "model", "layers": [
+                {"class_name": "InputLayer", "name": "x", "config": {"batch_input_shape": [null, 32, 32, 8]},
+                    "inbound_nodes": []},
+                {"class_name": "Conv2D", "name": "shared", "config": {"filters": 8, "kernel_size": 3,
+                    "padding": "same"}, "inbound_nodes": [[["x", 0, 0, {}]], [["shared", 0, 0, {}]]]},
+                {"class_name": "Add", "name": "add", "config": {},
+                    "inbound_nodes": [[["x", 0, 0, {}], ["shared", 1, 0, {}]]]},
+                {"class_name": "GlobalAveragePooling2D", "name": "gap", "config": {},
+                    "inbound_nodes": [[["add", 0, 0, {}]]]},
+                {"class_name": "Dense", "name": "logits", "config": {"units": 10},
+                    "inbound_nodes": [[["gap", 0, 0, {}]]]}
+            ], "input_layers": [["x", 0, 0]], "output_layers": [["logits", 0, 0], ["add", 0, 0]]}}"#,
+        )
+        .unwrap();
+        let graph = imported.graph;
+        assert_eq!(graph.outputs, vec!["add".to_string()]);
+        let edges: Vec<(&str, Vec<String>)> = graph
+            .nodes
+            .iter()
+            .map(|node| (node.name.as_str(), node.inputs.clone()))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("shared", vec!["x".to_string()]),
+                ("shared_2", vec!["shared".to_string()]),
+                ("add", vec!["x".to_string(), "shared_2".to_string()]),
+            ]
+        );
+        assert_eq!(graph.output_shape(0).unwrap().dim, 32);
+    }
+
+    #[test]
+    fn test_keras_3_inbound_nodes() {
+        let imported = import(
+            r#"{"class_name": "Functional", "config": {"name": "model", "layers": [
+                {"class_name": "InputLayer", "name": "image", "config": {"batch_shape": [null, 3, 64, 64],
+                    "data_format": "channels_first"}, "inbound_nodes": []},
+                {"class_name": "Conv2D", "name": "stem", "config": {"filters": 32, "kernel_size": [4, 4],
+                    "strides": [2, 2], "padding": "same", "data_format": "channels_first"},
+                    "inbound_nodes": [{"args": [{"class_name": "__keras_tensor__", "config": {
+                        "shape": [null, 3, 64, 64], "dtype": "float32", "keras_history": ["image", 0, 0]}}],
+                        "kwargs": {}}]}
+            ], "input_layers": [["image", 0, 0]], "output_layers": [["stem", 0, 0]]}}"#,
+        )
+        .unwrap();
+        assert_eq!(imported.graph.inputs[0].channels, Some(3));
+        assert_eq!(
+            imported.graph.output_shape(0).unwrap(),
+            Shape {
+                dim: 32,
+                channels: Some(32)
+            }
+        );
+        assert_eq!(
+            imported.warnings,
+            vec!["Node 'stem' uses 'same' padding, which is approximated by a padding of 1."]
+        );
+    }
+}
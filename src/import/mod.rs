@@ -0,0 +1,345 @@
+//! Import network architectures from the model formats of deep learning frameworks.
+//!
+//! Only the information that determines the spatial dimensions is extracted: the (transposed)
+//! convolutions, pooling and resampling layers with their attributes, as well as the merges of
+//! several branches. All other operations (activations, normalization, ...) leave the spatial
+//! dimensions unchanged and are skipped. The result is a [`Graph`] that can be analyzed like any
+//! graph read from a toml file, or written out in the toml graph format.
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::formula::Formula;
+use crate::graph::{Graph, Input, Node, Op};
+
+pub mod caffe;
+pub mod darknet;
+pub mod huggingface;
+pub mod keras;
+pub mod onnx;
+pub mod pytorch;
+pub mod tflite;
+pub mod torchinfo;
+pub mod torchscript;
+pub mod yolo;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## The model formats that can be imported.
+pub enum Format {
+    Onnx,
+    Keras,
+    /// The output of `print(model)` in PyTorch.
+    PyTorch,
+    /// An archive written by `torch.jit.save`.
+    TorchScript,
+    /// The summary table printed by `torchinfo` or `torchsummary`, which may lack the input dimension.
+    Torchinfo {
+        input_dim: Option<u32>,
+    },
+    /// A TensorFlow Lite flatbuffer.
+    TfLite,
+    /// The `config.json` of a Hugging Face vision model.
+    HuggingFace,
+    /// The model file of Ultralytics YOLO, with the model scale (e.g. `n` or `s`).
+    Yolo {
+        scale: Option<char>,
+    },
+    /// A Darknet configuration, e.g. `yolov4.cfg`.
+    Darknet,
+    /// A Caffe network definition in the text format of protocol buffers, e.g. `deploy.prototxt`.
+    Caffe,
+}
+
+impl Format {
+    /// The format of the model file at `path`, recognized by its extension.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("onnx") => Some(Format::Onnx),
+            Some("json") => Some(Format::Keras),
+            Some("pt") => Some(Format::TorchScript),
+            Some("tflite") => Some(Format::TfLite),
+            Some("cfg") => Some(Format::Darknet),
+            Some("prototxt") => Some(Format::Caffe),
+            Some("yaml") | Some("yml") => Some(Format::Yolo {
+                scale: yolo::scale_from_path(path),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// ## A network imported from a model file.
+///
+/// Parts of a model that cannot be represented exactly (e.g. asymmetric padding) are
+/// approximated, and a `warning` is recorded for each of them.
+pub struct Imported {
+    pub graph: Graph,
+    pub warnings: Vec<String>,
+}
+
+/// Convert an attribute value of a model file into a dimension, failing if it is out of range.
+fn to_dim<T: std::convert::TryFrom<i64>>(value: i64, what: &str, node: &str) -> Result<T, String> {
+    T::try_from(value).map_err(|_| {
+        format!(
+            "The {} of node '{}' is out of range: {}.",
+            what, node, value
+        )
+    })
+}
+
+/// The size of the dense filter equivalent to a filter of size `kernel` with `dilation`.
+///
+/// A dilated filter covers as many inputs as a dense filter of this size. The attributes come from
+/// the model file, so the size saturates rather than overflows, and is rejected as out of range.
+pub(crate) fn effective_filter_size(kernel: i64, dilation: i64) -> i64 {
+    dilation
+        .saturating_mul(kernel.saturating_sub(1))
+        .saturating_add(1)
+}
+
+/// The class of a layer without the suffix giving its number of spatial dimensions,
+/// e.g. `Conv` for `Conv2d` or `Conv2D`, which is irrelevant for the arithmetic.
+pub(crate) fn base_class(class: &str) -> &str {
+    ["1d", "2d", "3d", "1D", "2D", "3D"]
+        .iter()
+        .find_map(|suffix| class.strip_suffix(suffix))
+        .unwrap_or(class)
+}
+
+#[derive(Debug, Default)]
+/// ## The graph under construction while a model is imported.
+///
+/// The names of the layers in the model file are used as node names, made unique if necessary.
+pub(crate) struct Builder {
+    names: HashSet<String>,
+    inputs: Vec<Input>,
+    nodes: Vec<Node>,
+    outputs: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl Builder {
+    /// Make `name` unique among the names of the inputs and nodes, by appending a numeric suffix if needed.
+    fn unique_name(&mut self, name: &str) -> String {
+        let mut unique = name.to_string();
+        let mut suffix = 2;
+        while self.names.contains(&unique) {
+            unique = format!("{}_{}", name, suffix);
+            suffix += 1;
+        }
+        self.names.insert(unique.clone());
+        unique
+    }
+
+    /// Record a `warning` about a part of the model that is approximated.
+    pub(crate) fn warn(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    /// Declare an input with the given spatial `dims` (unknown if `None`) and number of `channels`.
+    ///
+    /// Returns the name of the input in the graph.
+    pub(crate) fn input(
+        &mut self,
+        name: &str,
+        dims: &[Option<i64>],
+        channels: Option<i64>,
+    ) -> Result<String, String> {
+        if dims.iter().any(|&dim| dim != dims[0]) {
+            self.warn(format!(
+                "The input '{}' is not square, only its first spatial dimension is considered.",
+                name
+            ));
+        }
+        let unique = self.unique_name(name);
+        self.inputs.push(Input {
+            name: unique.clone(),
+            input_dim: dims[0]
+                .map(|dim| to_dim(dim, "dimension", name))
+                .transpose()?,
+            channels: channels
+                .map(|channels| to_dim(channels, "number of channels", name))
+                .transpose()?,
+        });
+        Ok(unique)
+    }
+
+    /// Warn if the `attributes` of the layer `name` differ between the spatial dimensions,
+    /// of which only the first one is considered.
+    pub(crate) fn check_square(&mut self, name: &str, attributes: &[&[i64]]) {
+        let square = attributes
+            .iter()
+            .all(|values| values.iter().all(|&value| value == values[0]));
+        if !square {
+            self.warn(format!(
+                "Node '{}' differs between the spatial dimensions, only the first one is considered.",
+                name
+            ));
+        }
+    }
+
+    /// The symmetric padding approximating 'same' padding of the layer `name`.
+    ///
+    /// With 'same' padding, the output dimension is `ceil(n / s)` (resp. `n * s` for transposed
+    /// convolutions), which a symmetric padding only reproduces if the filter size allows it.
+    pub(crate) fn same_padding(
+        &mut self,
+        name: &str,
+        filter_size: i64,
+        stride: i64,
+        transposed: bool,
+    ) -> i64 {
+        let total = if transposed {
+            filter_size - stride
+        } else {
+            filter_size - 1
+        };
+        let padding = total.max(0) / 2;
+        if total < 0 || total % 2 != 0 {
+            self.warn(format!(
+                "Node '{}' uses 'same' padding, which is approximated by a padding of {}.",
+                name, padding
+            ));
+        }
+        padding
+    }
+
+    /// Add a (`transposed`) convolutional node taking its input from `input`.
+    ///
+    /// Returns the name of the node in the graph.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn layer(
+        &mut self,
+        name: &str,
+        transposed: bool,
+        input: &str,
+        filter_size: i64,
+        stride: i64,
+        padding: i64,
+        channels: Option<i64>,
+    ) -> Result<String, String> {
+        let node = Node {
+            name: self.unique_name(name),
+            op: if transposed { Op::Transposed } else { Op::Conv },
+            inputs: vec![input.to_string()],
+            filter_size: Some(to_dim(filter_size, "filter size", name)?),
+            stride: to_dim(stride, "stride", name)?,
+            padding: to_dim(padding, "padding", name)?,
+            formula: None,
+            channels: channels
+                .map(|channels| to_dim(channels, "number of channels", name))
+                .transpose()?,
+            subnetwork: None,
+            ceil_mode: false,
+        };
+        let unique = node.name.clone();
+        self.nodes.push(node);
+        Ok(unique)
+    }
+
+    /// Add a node upsampling the output of `input` by the integer factor `scale`.
+    ///
+    /// As far as the dimensions are concerned, this is equivalent to a transposed
+    /// convolution with `filter_size = stride = scale`.
+    pub(crate) fn upsample(
+        &mut self,
+        name: &str,
+        input: &str,
+        scale: i64,
+    ) -> Result<String, String> {
+        self.layer(name, true, input, scale, scale, 0, None)
+    }
+
+    /// Compute the output dimension of the node `name` with `formula`, for layers that round or
+    /// pad differently from convolutions.
+    pub(crate) fn formula(&mut self, name: &str, formula: Formula) {
+        if let Some(node) = self.nodes.iter_mut().find(|node| node.name == name) {
+            node.formula = Some(formula);
+        }
+    }
+
+    /// Add an `add` or `concat` node merging the outputs of `inputs`.
+    ///
+    /// Returns the name of the node in the graph.
+    pub(crate) fn merge(&mut self, name: &str, op: Op, inputs: Vec<String>) -> String {
+        let unique = self.unique_name(name);
+        self.nodes.push(Node {
+            name: unique.clone(),
+            op,
+            inputs,
+            filter_size: None,
+            stride: 1,
+            padding: 0,
+            formula: None,
+            channels: None,
+            subnetwork: None,
+            ceil_mode: false,
+        });
+        unique
+    }
+
+    /// Mark the node `name` as an output of the network.
+    pub(crate) fn output(&mut self, name: &str) {
+        if !self.outputs.iter().any(|output| output == name) {
+            self.outputs.push(name.to_string());
+        }
+    }
+
+    /// Validate the assembled graph.
+    pub(crate) fn finish(self) -> Result<Imported, String> {
+        if self.inputs.is_empty() {
+            return Err("The model does not have any input with spatial dimensions.".to_string());
+        }
+        if self.nodes.is_empty() {
+            return Err(
+                "The model does not contain any layer that changes the spatial dimensions."
+                    .to_string(),
+            );
+        }
+        let graph = Graph {
+            input_channels: None,
+            outputs: self.outputs,
+            inputs: self.inputs,
+            nodes: self.nodes,
+        };
+        graph.validate()?;
+        Ok(Imported {
+            graph,
+            warnings: self.warnings,
+        })
+    }
+}
+
+/// ## Import the model of the given `format` from the file at `path`.
+///
+/// The path `-` refers to the standard input, e.g. for pasting a printed model.
+pub fn read(path: &Path, format: Format) -> Result<Imported, String> {
+    let bytes = if path == Path::new("-") {
+        let mut bytes = vec![];
+        std::io::Read::read_to_end(&mut std::io::stdin(), &mut bytes).map(|_| bytes)
+    } else {
+        std::fs::read(path)
+    };
+    match bytes {
+        Ok(bytes) => from_bytes(&bytes, format),
+        Err(e) => Err(format!("Unable to open model file '{:?}': {}", path, e)),
+    }
+}
+
+/// ## Import the model file `bytes` of the given `format`, e.g. downloaded.
+pub fn from_bytes(bytes: &[u8], format: Format) -> Result<Imported, String> {
+    match format {
+        Format::Onnx => onnx::import(bytes),
+        Format::Keras => keras::import(&String::from_utf8_lossy(bytes)),
+        Format::PyTorch => pytorch::import(&String::from_utf8_lossy(bytes)),
+        Format::TorchScript => torchscript::import(bytes),
+        Format::Torchinfo { input_dim } => {
+            torchinfo::import(&String::from_utf8_lossy(bytes), input_dim)
+        }
+        Format::TfLite => tflite::import(bytes),
+        Format::HuggingFace => huggingface::import(&String::from_utf8_lossy(bytes)),
+        Format::Yolo { scale } => yolo::import(&String::from_utf8_lossy(bytes), scale),
+        Format::Darknet => darknet::import(&String::from_utf8_lossy(bytes)),
+        Format::Caffe => caffe::import(&String::from_utf8_lossy(bytes)),
+    }
+}
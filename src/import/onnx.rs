@@ -0,0 +1,785 @@
+//! Import of ONNX models.
+//!
+//! An ONNX model is a protocol buffers message whose graph lists its operations in topological
+//! order. Every operation consumes and produces named tensors, which are either computed by other
+//! operations, inputs of the graph or constants (the weights). `Conv`, `ConvTranspose` and the
+//! pooling operations become (transposed) convolutional nodes, `Resize` and `Upsample` by an
+//! integer factor `s` become transposed convolutions with `filter_size = stride = s`, and `Add`
+//! and `Concat` of several feature maps become merge nodes. Once the feature maps are flattened
+//! or pooled globally (e.g. in front of a classifier), the remaining operations are ignored.
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use super::{effective_filter_size, Builder, Imported};
+use crate::graph::Op;
+use crate::protobuf::Message;
+
+// Field numbers of the messages defined in `onnx.proto`.
+pub(crate) const MODEL_GRAPH: u32 = 7;
+pub(crate) const GRAPH_NODE: u32 = 1;
+pub(crate) const GRAPH_INITIALIZER: u32 = 5;
+pub(crate) const GRAPH_INPUT: u32 = 11;
+pub(crate) const GRAPH_OUTPUT: u32 = 12;
+const GRAPH_VALUE_INFO: u32 = 13;
+pub(crate) const NODE_INPUT: u32 = 1;
+pub(crate) const NODE_OUTPUT: u32 = 2;
+pub(crate) const NODE_NAME: u32 = 3;
+pub(crate) const NODE_OP_TYPE: u32 = 4;
+pub(crate) const NODE_ATTRIBUTE: u32 = 5;
+pub(crate) const ATTRIBUTE_NAME: u32 = 1;
+pub(crate) const ATTRIBUTE_I: u32 = 3;
+const ATTRIBUTE_S: u32 = 4;
+const ATTRIBUTE_T: u32 = 5;
+const ATTRIBUTE_FLOATS: u32 = 7;
+pub(crate) const ATTRIBUTE_INTS: u32 = 8;
+pub(crate) const TENSOR_DIMS: u32 = 1;
+pub(crate) const TENSOR_DATA_TYPE: u32 = 2;
+pub(crate) const TENSOR_FLOAT_DATA: u32 = 4;
+pub(crate) const TENSOR_NAME: u32 = 8;
+pub(crate) const TENSOR_RAW_DATA: u32 = 9;
+pub(crate) const VALUE_INFO_NAME: u32 = 1;
+pub(crate) const VALUE_INFO_TYPE: u32 = 2;
+pub(crate) const TYPE_TENSOR_TYPE: u32 = 1;
+pub(crate) const TENSOR_TYPE_SHAPE: u32 = 2;
+pub(crate) const SHAPE_DIM: u32 = 1;
+pub(crate) const DIMENSION_VALUE: u32 = 1;
+
+/// The `data_type` of tensors of 32 bit floats.
+pub(crate) const FLOAT: u64 = 1;
+
+/// A constant tensor, i.e. an initializer of the graph or the value of a `Constant` operation.
+struct Tensor<'a> {
+    dims: Vec<i64>,
+    message: Message<'a>,
+}
+
+impl<'a> Tensor<'a> {
+    fn decode(message: Message<'a>) -> Result<Tensor<'a>, String> {
+        let dims = message
+            .varints(TENSOR_DIMS)?
+            .into_iter()
+            .map(|dim| dim as i64)
+            .collect();
+        Ok(Tensor { dims, message })
+    }
+
+    /// The values of a tensor of floats, which are either stored as `float_data` or as little-endian `raw_data`.
+    fn floats(&self) -> Result<Vec<f32>, String> {
+        if self.message.varint(TENSOR_DATA_TYPE) != Some(FLOAT) {
+            return Err("Expected a tensor of floats.".to_string());
+        }
+        let floats = self.message.floats(TENSOR_FLOAT_DATA)?;
+        if floats.is_empty() {
+            match self.message.get(TENSOR_RAW_DATA) {
+                Some(value) => Message {
+                    fields: vec![(TENSOR_RAW_DATA, value)],
+                }
+                .floats(TENSOR_RAW_DATA),
+                None => Ok(floats),
+            }
+        } else {
+            Ok(floats)
+        }
+    }
+}
+
+/// An operation of the ONNX graph.
+struct Operation<'a> {
+    /// The name of the operation, or the name of its first output if it is unnamed.
+    name: String,
+    op_type: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    attributes: HashMap<String, Message<'a>>,
+}
+
+impl<'a> Operation<'a> {
+    fn decode(message: &Message<'a>) -> Result<Operation<'a>, String> {
+        let outputs = message.strings(NODE_OUTPUT);
+        let name = match message.string(NODE_NAME) {
+            Some(name) if !name.is_empty() => name,
+            _ => outputs.first().cloned().unwrap_or_default(),
+        };
+        let mut attributes = HashMap::new();
+        for attribute in message.messages(NODE_ATTRIBUTE)? {
+            if let Some(name) = attribute.string(ATTRIBUTE_NAME) {
+                attributes.insert(name, attribute);
+            }
+        }
+        Ok(Operation {
+            name,
+            op_type: message.string(NODE_OP_TYPE).unwrap_or_default(),
+            inputs: message.strings(NODE_INPUT),
+            outputs,
+            attributes,
+        })
+    }
+
+    fn int(&self, name: &str) -> Option<i64> {
+        self.attributes
+            .get(name)
+            .and_then(|attribute| attribute.varint(ATTRIBUTE_I))
+            .map(|value| value as i64)
+    }
+
+    fn ints(&self, name: &str) -> Result<Option<Vec<i64>>, String> {
+        match self.attributes.get(name) {
+            Some(attribute) => Ok(Some(
+                attribute
+                    .varints(ATTRIBUTE_INTS)?
+                    .into_iter()
+                    .map(|value| value as i64)
+                    .collect(),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn floats(&self, name: &str) -> Result<Option<Vec<f32>>, String> {
+        match self.attributes.get(name) {
+            Some(attribute) => Ok(Some(attribute.floats(ATTRIBUTE_FLOATS)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn string(&self, name: &str) -> Option<String> {
+        self.attributes
+            .get(name)
+            .and_then(|attribute| attribute.string(ATTRIBUTE_S))
+    }
+}
+
+/// The dimensions of the tensor described by the `ValueInfoProto` message `info`, where unknown
+/// (symbolic) dimensions are `None`. The dimensions are empty if the shape is not recorded.
+fn shape(info: &Message) -> Result<Vec<Option<i64>>, String> {
+    let shape = match info.message(VALUE_INFO_TYPE)? {
+        Some(info_type) => match info_type.message(TYPE_TENSOR_TYPE)? {
+            Some(tensor_type) => tensor_type.message(TENSOR_TYPE_SHAPE)?,
+            None => None,
+        },
+        None => None,
+    };
+    match shape {
+        Some(shape) => Ok(shape
+            .messages(SHAPE_DIM)?
+            .iter()
+            .map(|dim| dim.varint(DIMENSION_VALUE).map(|value| value as i64))
+            .collect()),
+        None => Ok(vec![]),
+    }
+}
+
+/// The state of the conversion of an ONNX graph into a [`Graph`](crate::graph::Graph).
+#[derive(Default)]
+struct Converter<'a> {
+    constants: HashMap<String, Tensor<'a>>,
+    /// The node producing every feature map computed so far, or `None` for tensors
+    /// that have lost their spatial dimensions.
+    producers: HashMap<String, Option<String>>,
+    builder: Builder,
+}
+
+impl<'a> Converter<'a> {
+    /// Declare the input described by the `ValueInfoProto` message `info`, if it is a feature map.
+    fn input(&mut self, info: &Message<'a>) -> Result<(), String> {
+        let tensor = info.string(VALUE_INFO_NAME).unwrap_or_default();
+        if self.constants.contains_key(&tensor) {
+            // Older exporters also list the weights among the inputs.
+            return Ok(());
+        }
+        let dims = shape(info)?;
+        // Only inputs of the layout (N, C, spatial...) are feature maps.
+        if dims.len() < 3 {
+            return Ok(());
+        }
+        let name = self.builder.input(&tensor, &dims[2..], dims[1])?;
+        self.producers.insert(tensor, Some(name));
+        Ok(())
+    }
+
+    /// Convert `operation`, recording the node producing each of its outputs.
+    fn operation(&mut self, operation: &Operation<'a>) -> Result<(), String> {
+        if operation.op_type == "Constant" {
+            if let (Some(value), Some(output)) =
+                (operation.attributes.get("value"), operation.outputs.first())
+            {
+                if let Some(tensor) = value.message(ATTRIBUTE_T)? {
+                    self.constants
+                        .insert(output.clone(), Tensor::decode(tensor)?);
+                }
+            }
+            return Ok(());
+        }
+
+        let producers: Vec<&Option<String>> = operation
+            .inputs
+            .iter()
+            .filter_map(|tensor| self.producers.get(tensor))
+            .collect();
+        let spatial: Vec<String> = producers.iter().filter_map(|p| (*p).clone()).collect();
+        if spatial.is_empty() {
+            // Operations on constants and shapes don't compute feature maps, whereas the
+            // feature maps stay flat once they have lost their spatial dimensions.
+            if !producers.is_empty() {
+                self.produce(operation, None);
+            }
+            return Ok(());
+        }
+
+        let node = match operation.op_type.as_str() {
+            "Conv" | "ConvTranspose" | "MaxPool" | "AveragePool" | "LpPool" => {
+                Some(self.conv(operation, &spatial[0])?)
+            }
+            "Resize" | "Upsample" => self.resize(operation, &spatial[0])?,
+            "Add" | "Sum" if spatial.len() > 1 => Some(self.builder.merge(
+                &operation.name,
+                Op::Add,
+                spatial.clone(),
+            )),
+            "Concat" if spatial.len() > 1 => {
+                if operation.int("axis") == Some(1) {
+                    Some(
+                        self.builder
+                            .merge(&operation.name, Op::Concat, spatial.clone()),
+                    )
+                } else {
+                    self.builder.warn(format!(
+                        "Node '{}' concatenates along an axis other than the channels and is ignored.",
+                        operation.name
+                    ));
+                    None
+                }
+            }
+            "Flatten" | "Reshape" | "Gemm" | "MatMul" | "GlobalAveragePool" | "GlobalMaxPool" => {
+                self.produce(operation, None);
+                return Ok(());
+            }
+            "Shape" | "Size" => return Ok(()),
+            // All other operations leave the spatial dimensions unchanged.
+            _ => None,
+        };
+        let producer = node.unwrap_or_else(|| spatial[0].clone());
+        self.produce(operation, Some(producer));
+        Ok(())
+    }
+
+    /// Record `producer` as the node computing the outputs of `operation`.
+    fn produce(&mut self, operation: &Operation, producer: Option<String>) {
+        for output in &operation.outputs {
+            self.producers.insert(output.clone(), producer.clone());
+        }
+    }
+
+    /// Convert a convolution or pooling `operation` applied to the output of `input`.
+    fn conv(&mut self, operation: &Operation, input: &str) -> Result<String, String> {
+        let name = &operation.name;
+        let transposed = operation.op_type == "ConvTranspose";
+        // The shape of the weight is (out channels, in channels / group, kernel...) for convolutions
+        // and (in channels, out channels / group, kernel...) for transposed convolutions.
+        let weight: Vec<i64> = operation
+            .inputs
+            .get(1)
+            .and_then(|tensor| self.constants.get(tensor))
+            .map_or_else(Vec::new, |weight| weight.dims.clone());
+        let kernel = match operation.ints("kernel_shape")? {
+            Some(kernel) => kernel,
+            None if weight.len() > 2 => weight[2..].to_vec(),
+            _ => {
+                return Err(format!(
+                    "Node '{}' has neither a 'kernel_shape' nor a constant weight.",
+                    name
+                ))
+            }
+        };
+        let n = kernel.len();
+        if n == 0 {
+            return Err(format!("Node '{}' has an empty 'kernel_shape'.", name));
+        }
+        let strides = operation.ints("strides")?.unwrap_or_else(|| vec![1; n]);
+        let dilations = operation.ints("dilations")?.unwrap_or_else(|| vec![1; n]);
+        let pads = operation.ints("pads")?.unwrap_or_else(|| vec![0; 2 * n]);
+        if strides.len() != n || dilations.len() != n || pads.len() != 2 * n {
+            return Err(format!(
+                "The attributes of node '{}' don't agree on the number of spatial dimensions.",
+                name
+            ));
+        }
+
+        self.builder
+            .check_square(name, &[&kernel, &strides, &dilations, &pads[..n]]);
+        let filter_size = effective_filter_size(kernel[0], dilations[0]);
+        let stride = strides[0];
+        let mut padding = pads[0];
+        if pads[0] != pads[n] {
+            self.builder.warn(format!(
+                "Node '{}' pads asymmetrically ({} before, {} after), the padding before is used.",
+                name, pads[0], pads[n]
+            ));
+        }
+
+        match operation.string("auto_pad").as_deref() {
+            Some("SAME_UPPER") | Some("SAME_LOWER") => {
+                padding = self
+                    .builder
+                    .same_padding(name, filter_size, stride, transposed)
+            }
+            Some("VALID") => padding = 0,
+            _ => {}
+        }
+        if operation.int("ceil_mode") == Some(1) {
+            self.builder.warn(format!(
+                "Node '{}' rounds its output dimension up, but convdim rounds down.",
+                name
+            ));
+        }
+        if transposed
+            && operation
+                .ints("output_padding")?
+                .is_some_and(|padding| padding.iter().any(|&p| p != 0))
+        {
+            self.builder.warn(format!(
+                "The 'output_padding' of node '{}' is ignored.",
+                name
+            ));
+        }
+
+        let channels = match operation.op_type.as_str() {
+            "Conv" if !weight.is_empty() => Some(weight[0]),
+            "ConvTranspose" if weight.len() > 1 => {
+                Some(weight[1] * operation.int("group").unwrap_or(1))
+            }
+            _ => None,
+        };
+        self.builder.layer(
+            name,
+            transposed,
+            input,
+            filter_size,
+            stride,
+            padding,
+            channels,
+        )
+    }
+
+    /// Convert a resampling `operation` applied to the output of `input`.
+    ///
+    /// Resampling by a factor of one leaves the dimensions unchanged, so no node is created.
+    fn resize(&mut self, operation: &Operation, input: &str) -> Result<Option<String>, String> {
+        // `Upsample-7` stores the scales as an attribute, the later versions take them as an input,
+        // which is the third one for `Resize-11` (X, roi, scales, sizes) and the second one before.
+        let scales = match operation.floats("scales")? {
+            Some(scales) => Some(scales),
+            None => {
+                let index = if operation.op_type == "Resize" && operation.inputs.len() > 2 {
+                    2
+                } else {
+                    1
+                };
+                match operation
+                    .inputs
+                    .get(index)
+                    .and_then(|tensor| self.constants.get(tensor))
+                {
+                    Some(tensor) => Some(tensor.floats()?),
+                    None => None,
+                }
+            }
+        };
+        // The scales are given for all axes (N, C, spatial...).
+        match scales.as_ref().and_then(|scales| scales.get(2).copied()) {
+            Some(1.0) => Ok(None),
+            Some(scale) if scale > 1.0 && scale.fract() == 0.0 => self
+                .builder
+                .upsample(&operation.name, input, scale as i64)
+                .map(Some),
+            _ => {
+                self.builder.warn(format!(
+                    "The scale of node '{}' is not a constant integer factor, the node is assumed to leave the dimension unchanged.",
+                    operation.name
+                ));
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Convert the inputs and operations of the `GraphProto` message `graph`.
+fn convert<'a>(graph: &Message<'a>) -> Result<Converter<'a>, String> {
+    let mut converter = Converter::default();
+    for initializer in graph.messages(GRAPH_INITIALIZER)? {
+        let name = initializer.string(TENSOR_NAME).unwrap_or_default();
+        converter
+            .constants
+            .insert(name, Tensor::decode(initializer)?);
+    }
+    for info in graph.messages(GRAPH_INPUT)? {
+        converter.input(&info)?;
+    }
+    for operation in graph.messages(GRAPH_NODE)? {
+        converter.operation(&Operation::decode(&operation)?)?;
+    }
+    Ok(converter)
+}
+
+/// The `GraphProto` message of the `ModelProto` message `model`.
+fn graph<'a>(model: &Message<'a>) -> Result<Message<'a>, String> {
+    match model.message(MODEL_GRAPH)? {
+        Some(graph) => Ok(graph),
+        None => Err("The model does not contain a graph.".to_string()),
+    }
+}
+
+/// ## Import the ONNX model encoded in `bytes`.
+pub fn import(bytes: &[u8]) -> Result<Imported, String> {
+    let model = Message::decode(bytes)?;
+    let graph = graph(&model)?;
+    let mut converter = convert(&graph)?;
+    for info in graph.messages(GRAPH_OUTPUT)? {
+        let tensor = info.string(VALUE_INFO_NAME).unwrap_or_default();
+        if let Some(Some(node)) = converter.producers.get(&tensor) {
+            converter.builder.output(node);
+        }
+    }
+    converter.builder.finish()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The shape of a tensor recorded in an ONNX model, e.g. by the ONNX shape inference.
+pub struct RecordedShape {
+    pub tensor: String,
+    /// The imported node computing the spatial dimensions of the tensor.
+    pub node: String,
+    /// The dimensions in the layout (N, C, spatial...), where unknown dimensions are `None`.
+    pub dims: Vec<Option<i64>>,
+}
+
+/// ## The shapes of the feature maps recorded in the ONNX model encoded in `bytes`.
+///
+/// The shapes are those of the outputs of the graph and of its `value_info`, which is filled in by
+/// the ONNX shape inference. Every tensor is paired with the imported node that computes its
+/// spatial dimensions, e.g. a convolution for the output of the activation following it.
+pub fn recorded_shapes(bytes: &[u8]) -> Result<Vec<RecordedShape>, String> {
+    let model = Message::decode(bytes)?;
+    let graph = graph(&model)?;
+    let converter = convert(&graph)?;
+    let mut infos = graph.messages(GRAPH_VALUE_INFO)?;
+    infos.extend(graph.messages(GRAPH_OUTPUT)?);
+    let mut shapes = vec![];
+    for info in infos {
+        let tensor = info.string(VALUE_INFO_NAME).unwrap_or_default();
+        let dims = shape(&info)?;
+        if let (Some(Some(node)), true) = (converter.producers.get(&tensor), dims.len() >= 3) {
+            shapes.push(RecordedShape {
+                tensor,
+                node: node.clone(),
+                dims,
+            });
+        }
+    }
+    Ok(shapes)
+}
+
+/// ## The shapes of the feature maps of the ONNX model encoded in `bytes`, inferred by tract.
+///
+/// Unlike the [recorded shapes](recorded_shapes), the shapes are inferred by the ONNX shape
+/// inference of [tract](tract_onnx) for inputs of the spatial dimension `in_dim`, with a batch of
+/// one and the channels the inputs declare, so they are valid for any input dimension.
+#[cfg(feature = "tract")]
+pub fn inferred_shapes(bytes: &[u8], in_dim: u32) -> Result<Vec<RecordedShape>, String> {
+    use tract_onnx::prelude::*;
+    use tract_onnx::tract_hir::infer::Factoid;
+
+    let message = Message::decode(bytes)?;
+    let graph = graph(&message)?;
+    let converter = convert(&graph)?;
+    let mut declared = HashMap::new();
+    for info in graph.messages(GRAPH_INPUT)? {
+        declared.insert(
+            info.string(VALUE_INFO_NAME).unwrap_or_default(),
+            shape(&info)?,
+        );
+    }
+
+    let tract_error = |e: TractError| format!("tract can not infer the shapes: {:#}.", e);
+    let mut model = tract_onnx::onnx()
+        .with_ignore_output_shapes(true)
+        .model_for_read(&mut &*bytes)
+        .map_err(tract_error)?;
+    // The recorded shapes are only valid for the input dimension the model was exported with.
+    let recorded: Vec<String> = graph
+        .messages(GRAPH_VALUE_INFO)?
+        .iter()
+        .map(|info| info.string(VALUE_INFO_NAME).unwrap_or_default())
+        .collect();
+    for node in 0..model.nodes().len() {
+        for slot in 0..model.node(node).outputs.len() {
+            let outlet = OutletId::new(node, slot);
+            if let Some(true) = model
+                .outlet_label(outlet)
+                .map(|label| recorded.iter().any(|name| name == label))
+            {
+                model
+                    .set_outlet_fact(outlet, InferenceFact::default())
+                    .map_err(tract_error)?;
+            }
+        }
+    }
+    let inputs = model.input_outlets().map_err(tract_error)?.to_vec();
+    for (index, outlet) in inputs.iter().enumerate() {
+        let name = &model.node(outlet.node).name;
+        let dims = match declared.get(name) {
+            Some(dims) if dims.len() >= 3 && converter.producers.contains_key(name) => dims,
+            _ => continue,
+        };
+        let channels = dims[1]
+            .ok_or_else(|| format!("The input '{}' does not declare its channels.", name))?;
+        let mut shape = vec![dims[0].unwrap_or(1), channels];
+        shape.extend(vec![i64::from(in_dim); dims.len() - 2]);
+        let fact = model.input_fact(index).map_err(tract_error)?.clone();
+        let shape: Vec<usize> = shape.iter().map(|&dim| dim as usize).collect();
+        model
+            .set_input_fact(index, fact.with_shape(shape))
+            .map_err(tract_error)?;
+    }
+    model.analyse(false).map_err(tract_error)?;
+
+    let mut shapes = vec![];
+    for node in model.nodes() {
+        for (slot, output) in node.outputs.iter().enumerate() {
+            let tensor = match model.outlet_label(OutletId::new(node.id, slot)) {
+                Some(tensor) => tensor,
+                None => continue,
+            };
+            let dims: Vec<Option<i64>> = match output.fact.shape.concretize() {
+                Some(dims) => dims.iter().map(|dim| dim.to_i64().ok()).collect(),
+                None => continue,
+            };
+            if let (Some(Some(node)), true) = (converter.producers.get(tensor), dims.len() >= 3) {
+                shapes.push(RecordedShape {
+                    tensor: tensor.to_string(),
+                    node: node.clone(),
+                    dims,
+                });
+            }
+        }
+    }
+    Ok(shapes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{Graph, Shape};
+    use crate::protobuf::Encoder;
+
+    const TENSOR_TYPE_ELEM_TYPE: u32 = 1;
+    const ATTRIBUTE_TYPE: u32 = 20;
+    const INTS: i64 = 7;
+
+    fn ints(name: &str, values: &[i64]) -> Encoder {
+        values.iter().fold(
+            Encoder::new()
+                .string(ATTRIBUTE_NAME, name)
+                .varint(ATTRIBUTE_TYPE, INTS),
+            |attribute, &value| attribute.varint(ATTRIBUTE_INTS, value),
+        )
+    }
+
+    fn operation(op_type: &str, name: &str, inputs: &[&str], attributes: Vec<Encoder>) -> Encoder {
+        let operation = inputs
+            .iter()
+            .fold(Encoder::new(), |operation, input| {
+                operation.string(NODE_INPUT, input)
+            })
+            .string(NODE_OUTPUT, name)
+            .string(NODE_NAME, name)
+            .string(NODE_OP_TYPE, op_type);
+        attributes
+            .into_iter()
+            .fold(operation, |operation, attribute| {
+                operation.message(NODE_ATTRIBUTE, attribute)
+            })
+    }
+
+    fn value_info(name: &str, dims: &[i64]) -> Encoder {
+        let shape = dims.iter().fold(Encoder::new(), |shape, &dim| {
+            shape.message(SHAPE_DIM, Encoder::new().varint(DIMENSION_VALUE, dim))
+        });
+        Encoder::new().string(VALUE_INFO_NAME, name).message(
+            VALUE_INFO_TYPE,
+            Encoder::new().message(
+                TYPE_TENSOR_TYPE,
+                Encoder::new()
+                    .varint(TENSOR_TYPE_ELEM_TYPE, FLOAT as i64)
+                    .message(TENSOR_TYPE_SHAPE, shape),
+            ),
+        )
+    }
+
+    fn weight(name: &str, dims: &[i64]) -> Encoder {
+        dims.iter()
+            .fold(Encoder::new(), |tensor, &dim| {
+                tensor.varint(TENSOR_DIMS, dim)
+            })
+            .varint(TENSOR_DATA_TYPE, FLOAT as i64)
+            .string(TENSOR_NAME, name)
+    }
+
+    /// A stem with a residual connection around a max-pool and an upsampling layer.
+    fn model() -> Vec<u8> {
+        let scales = [1.0f32, 1.0, 2.0, 2.0]
+            .iter()
+            .fold(weight("scales", &[4]), |tensor, &scale| {
+                tensor.float(TENSOR_FLOAT_DATA, scale)
+            });
+        let graph = Encoder::new()
+            .message(
+                GRAPH_INITIALIZER,
+                weight("w", &[64, 3, 7, 7]).bytes(TENSOR_RAW_DATA, &[0; 64 * 3 * 7 * 7 * 4]),
+            )
+            .message(GRAPH_INITIALIZER, scales)
+            .message(GRAPH_INPUT, value_info("image", &[1, 3, 224, 224]))
+            .message(
+                GRAPH_NODE,
+                operation(
+                    "Conv",
+                    "conv1",
+                    &["image", "w"],
+                    vec![ints("strides", &[2, 2]), ints("pads", &[3, 3, 3, 3])],
+                ),
+            )
+            .message(GRAPH_NODE, operation("Relu", "relu", &["conv1"], vec![]))
+            .message(
+                GRAPH_NODE,
+                operation(
+                    "MaxPool",
+                    "pool",
+                    &["relu"],
+                    vec![
+                        ints("kernel_shape", &[3, 3]),
+                        ints("strides", &[2, 2]),
+                        ints("pads", &[1, 1, 1, 1]),
+                    ],
+                ),
+            )
+            .message(
+                GRAPH_NODE,
+                operation("Resize", "up", &["pool", "", "scales"], vec![]),
+            )
+            .message(GRAPH_NODE, operation("Add", "sum", &["relu", "up"], vec![]))
+            .message(GRAPH_NODE, operation("Flatten", "flat", &["sum"], vec![]))
+            .message(GRAPH_OUTPUT, value_info("flat", &[1, 802816]))
+            .message(GRAPH_OUTPUT, value_info("pool", &[1, 64, 56, 56]));
+        Encoder::new().message(MODEL_GRAPH, graph).finish()
+    }
+
+    #[test]
+    fn test_import() {
+        let imported = import(&model()).unwrap();
+        assert!(imported.warnings.is_empty());
+        let graph = imported.graph;
+        assert_eq!(graph.inputs[0].input_dim, Some(224));
+        assert_eq!(graph.outputs, vec!["pool".to_string()]);
+        assert!(!graph.needs_input_dim());
+
+        let shapes: Vec<(String, Shape)> = graph.shapes(0).unwrap();
+        let dims: Vec<(&str, u32)> = shapes
+            .iter()
+            .map(|(name, shape)| (name.as_str(), shape.dim))
+            .collect();
+        assert_eq!(
+            dims,
+            vec![("conv1", 112), ("pool", 56), ("up", 112), ("sum", 112)]
+        );
+        assert_eq!(shapes[3].1.channels, Some(64));
+    }
+
+    #[test]
+    fn test_recorded_shapes() {
+        let shapes = recorded_shapes(&model()).unwrap();
+        // The flattened output has lost its spatial dimensions.
+        assert_eq!(
+            shapes,
+            vec![RecordedShape {
+                tensor: "pool".to_string(),
+                node: "pool".to_string(),
+                dims: vec![Some(1), Some(64), Some(56), Some(56)],
+            }]
+        );
+    }
+
+    #[cfg(feature = "tract")]
+    #[test]
+    fn test_inferred_shapes() {
+        // The shapes are inferred for the input dimension, not recorded for the exported one.
+        let shapes = inferred_shapes(&model(), 320).unwrap();
+        let dims: Vec<(&str, &str, i64)> = shapes
+            .iter()
+            .map(|shape| {
+                (
+                    shape.tensor.as_str(),
+                    shape.node.as_str(),
+                    shape.dims[3].unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            dims,
+            vec![
+                ("conv1", "conv1", 160),
+                ("relu", "conv1", 160),
+                ("pool", "pool", 80),
+                ("up", "up", 160),
+                ("sum", "sum", 160)
+            ]
+        );
+
+        let mut graph = import(&model()).unwrap().graph;
+        let (in_dim, shapes) =
+            crate::verify::model_shapes(&mut graph, &model(), Some(320)).unwrap();
+        let comparisons = crate::verify::compare(&graph, in_dim, &shapes).unwrap();
+        assert_eq!(comparisons.len(), 5);
+        assert!(comparisons.iter().all(crate::verify::Comparison::agrees));
+    }
+
+    #[test]
+    fn test_round_trip_through_toml() {
+        let graph = import(&model()).unwrap().graph;
+        let reread: Graph = toml::from_str(&graph.to_toml()).unwrap();
+        assert_eq!(reread.shapes(0).unwrap(), graph.shapes(0).unwrap());
+        assert_eq!(reread.outputs, graph.outputs);
+    }
+
+    #[test]
+    fn test_approximations_are_reported() {
+        let graph = Encoder::new()
+            .message(GRAPH_INPUT, value_info("x", &[1, 16, 32, 32]))
+            .message(
+                GRAPH_NODE,
+                operation(
+                    "Conv",
+                    "down",
+                    &["x"],
+                    vec![
+                        ints("kernel_shape", &[3, 3]),
+                        ints("strides", &[2, 2]),
+                        ints("dilations", &[2, 2]),
+                        ints("pads", &[0, 0, 1, 1]),
+                    ],
+                ),
+            );
+        let imported = import(&Encoder::new().message(MODEL_GRAPH, graph).finish()).unwrap();
+        let node = &imported.graph.nodes[0];
+        assert_eq!(
+            (node.filter_size, node.stride, node.padding),
+            (Some(5), 2, 0)
+        );
+        assert_eq!(
+            imported.warnings,
+            vec![
+                "Node 'down' pads asymmetrically (0 before, 1 after), the padding before is used."
+            ]
+        );
+    }
+}
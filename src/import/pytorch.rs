@@ -0,0 +1,561 @@
+//! Import of the architecture printed by PyTorch.
+//!
+//! `print(model)` writes the tree of submodules of a PyTorch model, e.g.
+//!
+//! ```text
+//! ResNet(
+//!   (conv1): Conv2d(3, 64, kernel_size=(7, 7), stride=(2, 2), padding=(3, 3), bias=False)
+//!   (maxpool): MaxPool2d(kernel_size=3, stride=2, padding=1, dilation=1, ceil_mode=False)
+//!   (layer1): Sequential(
+//!     (0-1): 2 x BasicBlock(
+//!   ...
+//! ```
+//!
+//! The printout does not show how the submodules are connected, so the layers are assumed to be
+//! applied one after the other, in the order in which they are printed. The exception are the
+//! projections of residual blocks (modules named `downsample` or `shortcut`), which are applied
+//! to the input of the block in parallel to its other layers and are therefore skipped. Repeated
+//! modules, which recent versions of PyTorch print only once (`(0-1): 2 x BasicBlock(`), are
+//! expanded. Once the feature maps are pooled adaptively or flattened, the remaining layers are ignored.
+//!
+//! Layers whose class is registered as a [`plugin`](crate::plugin), e.g. `LearnedResampler2d`, are
+//! converted with the registered function, which receives their keyword arguments by name and
+//! their positional arguments by position (`"0"`, `"1"`, ...).
+use super::{base_class, effective_filter_size, Builder, Imported};
+use crate::graph::INPUT;
+use crate::plugin::{self, Params};
+
+/// The names of the submodules that form the shortcut of a residual block.
+const SHORTCUTS: [&str; 2] = ["downsample", "shortcut"];
+
+#[derive(Debug, Clone)]
+/// A module of the printed tree.
+struct Module {
+    name: String,
+    class: String,
+    /// The printed arguments of a leaf module.
+    args: String,
+    children: Vec<Module>,
+}
+
+/// Parse the printed tree of modules.
+fn parse(text: &str) -> Result<Module, String> {
+    let mut stack = vec![Module {
+        name: String::new(),
+        class: String::new(),
+        args: String::new(),
+        children: vec![],
+    }];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line == ")" {
+            if stack.len() < 2 {
+                return Err(format!("Unbalanced ')' in line {}.", number + 1));
+            }
+            let module = stack.pop().unwrap();
+            stack.last_mut().unwrap().children.push(module);
+            continue;
+        }
+
+        // Submodules are prefixed by their name, repeated ones by their range of names and the count.
+        let (names, rest) = match line
+            .strip_prefix('(')
+            .and_then(|line| line.split_once("): "))
+        {
+            Some((names, rest)) => (names, rest),
+            None if stack.len() == 1 => ("", line),
+            // The extra information printed by some containers.
+            None => continue,
+        };
+        let (count, rest) = match rest.split_once(" x ") {
+            Some((count, rest)) if count.parse::<usize>().is_ok() => {
+                (count.parse::<usize>().unwrap(), rest)
+            }
+            _ => (1, rest),
+        };
+        let (class, args) = match rest.split_once('(') {
+            Some((class, args)) => (class.trim(), args),
+            // Modules without arguments, e.g. `Identity()`, are printed with parentheses,
+            // anything else is not a module.
+            None => continue,
+        };
+        let first = names
+            .split('-')
+            .next()
+            .and_then(|first| first.parse::<usize>().ok());
+        let names: Vec<String> = match first {
+            Some(first) if count > 1 => (first..first + count).map(|i| i.to_string()).collect(),
+            _ => vec![names.to_string()],
+        };
+
+        if args.is_empty() {
+            // The module is a container whose children follow on the next lines. Repeated containers
+            // are collected once and copied when they are closed.
+            stack.push(Module {
+                name: names.join(","),
+                class: class.to_string(),
+                args: String::new(),
+                children: vec![],
+            });
+        } else {
+            let args = args.strip_suffix(')').unwrap_or(args);
+            let parent = stack.last_mut().unwrap();
+            for name in names {
+                parent.children.push(Module {
+                    name,
+                    class: class.to_string(),
+                    args: args.to_string(),
+                    children: vec![],
+                });
+            }
+        }
+    }
+    if stack.len() != 1 {
+        return Err("The printed model ends before all modules are closed.".to_string());
+    }
+    let mut root = stack.pop().unwrap();
+    expand_repeated(&mut root);
+    match root.children.len() {
+        1 => Ok(root.children.pop().unwrap()),
+        _ => Err("Expected a single printed model.".to_string()),
+    }
+}
+
+/// Copy the repeated containers, whose names have been joined by commas while parsing.
+fn expand_repeated(module: &mut Module) {
+    let children = std::mem::take(&mut module.children);
+    for mut child in children {
+        expand_repeated(&mut child);
+        if child.name.contains(',') {
+            for name in child.name.split(',') {
+                module.children.push(Module {
+                    name: name.to_string(),
+                    ..child.clone()
+                });
+            }
+        } else {
+            module.children.push(child);
+        }
+    }
+}
+
+/// The positional and keyword arguments of a leaf module, split at the top-level commas.
+fn arguments(args: &str) -> (Vec<&str>, Vec<(&str, &str)>) {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(args[start..].trim());
+
+    let mut positional = vec![];
+    let mut keywords = vec![];
+    for part in parts.into_iter().filter(|part| !part.is_empty()) {
+        match part.split_once('=') {
+            Some((key, value)) => keywords.push((key.trim(), value.trim())),
+            None => positional.push(part),
+        }
+    }
+    (positional, keywords)
+}
+
+/// The numbers of an argument written as a single number or a tuple, e.g. `3` or `(3, 3)`.
+fn numbers(value: &str) -> Option<Vec<f64>> {
+    value
+        .trim_matches(|c| c == '(' || c == ')' || c == '[' || c == ']')
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+/// The integers of an argument written as a single integer or a tuple.
+fn ints(value: &str) -> Option<Vec<i64>> {
+    numbers(value)?
+        .into_iter()
+        .map(|number| {
+            if number.fract() == 0.0 {
+                Some(number as i64)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// The printed argument `value` as a parameter of a custom layer.
+fn parameter(value: &str) -> toml::Value {
+    let tuple = value.starts_with('(') || value.starts_with('[');
+    match (ints(value), numbers(value)) {
+        (Some(ints), _) if ints.len() == 1 && !tuple => toml::Value::Integer(ints[0]),
+        (Some(ints), _) => toml::Value::Array(ints.into_iter().map(toml::Value::Integer).collect()),
+        (None, Some(numbers)) if numbers.len() == 1 && !tuple => toml::Value::Float(numbers[0]),
+        (None, Some(numbers)) => {
+            toml::Value::Array(numbers.into_iter().map(toml::Value::Float).collect())
+        }
+        (None, None) => match value {
+            "True" => toml::Value::Boolean(true),
+            "False" => toml::Value::Boolean(false),
+            _ => toml::Value::String(value.trim_matches('\'').to_string()),
+        },
+    }
+}
+
+struct Converter {
+    builder: Builder,
+    /// The node producing the current feature map, `None` once it has lost its spatial dimensions.
+    current: Option<String>,
+    /// Whether the input has been declared, which happens when the first layer is converted.
+    has_input: bool,
+}
+
+impl Converter {
+    /// Convert the layers of `module`, whose name is prefixed by the path of its parent.
+    fn module(&mut self, module: &Module, path: &str) -> Result<(), String> {
+        let path = match (path, module.name.as_str()) {
+            (path, "") => path.to_string(),
+            ("", name) => name.to_string(),
+            (path, name) => format!("{}.{}", path, name),
+        };
+        if module.children.is_empty() {
+            return self.leaf(module, &path);
+        }
+        for child in &module.children {
+            if SHORTCUTS.contains(&child.name.as_str()) {
+                continue;
+            }
+            self.module(child, &path)?;
+        }
+        Ok(())
+    }
+
+    /// Convert the leaf module `module` named `name`.
+    fn leaf(&mut self, module: &Module, name: &str) -> Result<(), String> {
+        let (positional, keywords) = arguments(&module.args);
+        let keyword = |key: &str| {
+            keywords
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, value)| *value)
+        };
+        let int_keyword = |key: &str| -> Result<Option<Vec<i64>>, String> {
+            match keyword(key) {
+                Some(value) => match ints(value) {
+                    Some(values) if !values.is_empty() => Ok(Some(values)),
+                    _ => Err(format!(
+                        "The argument '{}' of layer '{}' is not an integer: {}",
+                        key, name, value
+                    )),
+                },
+                None => Ok(None),
+            }
+        };
+
+        let class = base_class(&module.class);
+        let kind = match class {
+            "Conv" | "ConvTranspose" | "LazyConv" | "LazyConvTranspose" | "MaxPool" | "AvgPool"
+            | "LPPool" | "Upsample" | "UpsamplingNearest" | "UpsamplingBilinear" | "ZeroPad"
+            | "ConstantPad" | "ReflectionPad" | "ReplicationPad" | "CircularPad"
+            | "PixelShuffle" | "PixelUnshuffle" => class,
+            "AdaptiveAvgPool" | "AdaptiveMaxPool" | "Flatten" | "Linear" => {
+                self.current = None;
+                return Ok(());
+            }
+            _ if plugin::is_registered(&module.class) => "custom",
+            // All other layers leave the spatial dimensions unchanged.
+            _ => return Ok(()),
+        };
+
+        // The number of input channels is only known from the first convolution.
+        if !self.has_input {
+            self.has_input = true;
+            let channels = match kind {
+                "Conv" | "ConvTranspose" => positional.first().and_then(|c| c.parse().ok()),
+                _ => None,
+            };
+            self.current = Some(self.builder.input(INPUT, &[None], channels)?);
+        }
+        let input = match &self.current {
+            Some(input) => input.clone(),
+            None => return Ok(()),
+        };
+
+        let node = match kind {
+            "Conv" | "ConvTranspose" | "LazyConv" | "LazyConvTranspose" | "MaxPool" | "AvgPool"
+            | "LPPool" => {
+                let transposed = kind.ends_with("Transpose");
+                let is_conv = kind.contains("Conv");
+                let kernel = match int_keyword("kernel_size")? {
+                    Some(kernel) => kernel,
+                    // `LPPool` prints the kernel size as its second positional argument.
+                    None => match positional.get(1).and_then(|value| ints(value)) {
+                        Some(kernel) if !kernel.is_empty() => kernel,
+                        _ => {
+                            return Err(format!(
+                                "Layer '{}' does not print its 'kernel_size'.",
+                                name
+                            ))
+                        }
+                    },
+                };
+                // The stride of pooling layers defaults to the kernel size.
+                let default_stride = if is_conv { vec![1] } else { kernel.clone() };
+                let strides = int_keyword("stride")?.unwrap_or(default_stride);
+                let dilations = int_keyword("dilation")?.unwrap_or_else(|| vec![1]);
+                self.builder
+                    .check_square(name, &[&kernel, &strides, &dilations]);
+                let filter_size = effective_filter_size(kernel[0], dilations[0]);
+                let stride = strides[0];
+                let padding = match keyword("padding") {
+                    Some("same") | Some("'same'") => {
+                        self.builder
+                            .same_padding(name, filter_size, stride, transposed)
+                    }
+                    Some("valid") | Some("'valid'") => 0,
+                    Some(_) => {
+                        let padding = int_keyword("padding")?.unwrap_or_default();
+                        self.builder.check_square(name, &[&padding]);
+                        padding[0]
+                    }
+                    None => 0,
+                };
+                if keyword("ceil_mode") == Some("True") {
+                    self.builder.warn(format!(
+                        "Layer '{}' rounds its output dimension up, but convdim rounds down.",
+                        name
+                    ));
+                }
+                if transposed
+                    && int_keyword("output_padding")?.is_some_and(|p| p.iter().any(|&p| p != 0))
+                {
+                    self.builder.warn(format!(
+                        "The 'output_padding' of layer '{}' is ignored.",
+                        name
+                    ));
+                }
+                let channels = match kind {
+                    "Conv" | "ConvTranspose" => positional.get(1).and_then(|c| c.parse().ok()),
+                    _ => None,
+                };
+                self.builder.layer(
+                    name,
+                    transposed,
+                    &input,
+                    filter_size,
+                    stride,
+                    padding,
+                    channels,
+                )?
+            }
+            "Upsample" | "UpsamplingNearest" | "UpsamplingBilinear" => {
+                let scale = keyword("scale_factor")
+                    .and_then(numbers)
+                    .and_then(|scales| scales.first().copied());
+                match scale {
+                    Some(scale) if scale >= 1.0 && scale.fract() == 0.0 => {
+                        self.builder.upsample(name, &input, scale as i64)?
+                    }
+                    _ => {
+                        self.builder.warn(format!(
+                            "Layer '{}' does not upsample by an integer factor, it is assumed to leave the dimension unchanged.",
+                            name
+                        ));
+                        input
+                    }
+                }
+            }
+            "PixelShuffle" => {
+                let factor = positional
+                    .first()
+                    .copied()
+                    .or_else(|| keyword("upscale_factor"));
+                match factor.and_then(|f| f.parse().ok()) {
+                    Some(factor) => self.builder.upsample(name, &input, factor)?,
+                    None => return Err(format!("Layer '{}' does not print its factor.", name)),
+                }
+            }
+            "PixelUnshuffle" => {
+                let factor = positional
+                    .first()
+                    .copied()
+                    .or_else(|| keyword("downscale_factor"));
+                match factor.and_then(|f| f.parse().ok()) {
+                    Some(factor) => self
+                        .builder
+                        .layer(name, false, &input, factor, factor, 0, None)?,
+                    None => return Err(format!("Layer '{}' does not print its factor.", name)),
+                }
+            }
+            "custom" => {
+                let params: Params = positional
+                    .iter()
+                    .enumerate()
+                    .map(|(position, value)| (position.to_string(), parameter(value)))
+                    .chain(
+                        keywords
+                            .iter()
+                            .map(|(key, value)| (key.to_string(), parameter(value))),
+                    )
+                    .collect();
+                let geometry = plugin::geometry(&module.class, &params)
+                    .map_err(|e| format!("Layer '{}': {}", name, e))?;
+                let channels = params.get("channels").and_then(toml::Value::as_integer);
+                self.builder.layer(
+                    name,
+                    geometry.transposed,
+                    &input,
+                    geometry.filter_size.into(),
+                    geometry.stride.into(),
+                    geometry.padding.into(),
+                    channels,
+                )?
+            }
+            // The padding layers print their padding as (left, right, top, bottom, ...),
+            // and are equivalent to a 1x1 convolution with that padding.
+            _ => {
+                let padding = match positional.first().and_then(|value| ints(value)) {
+                    Some(padding) if !padding.is_empty() => padding,
+                    _ => return Err(format!("Layer '{}' does not print its padding.", name)),
+                };
+                if padding.iter().any(|&p| p != padding[0]) {
+                    self.builder.warn(format!(
+                        "Layer '{}' pads asymmetrically, only a padding of {} on each side is considered.",
+                        name, padding[0]
+                    ));
+                }
+                self.builder
+                    .layer(name, false, &input, 1, 1, padding[0], None)?
+            }
+        };
+        self.current = Some(node);
+        Ok(())
+    }
+}
+
+/// ## Import the model printed by PyTorch in `text`.
+pub fn import(text: &str) -> Result<Imported, String> {
+    let model = parse(text)?;
+    let mut converter = Converter {
+        builder: Builder::default(),
+        current: None,
+        has_input: false,
+    };
+    converter.module(&model, "")?;
+    converter.builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resnet() {
+        let imported = import(
+            "ResNet(
+  (conv1): Conv2d(3, 64, kernel_size=(7, 7), stride=(2, 2), padding=(3, 3), bias=False)
+  (bn1): BatchNorm2d(64, eps=1e-05, momentum=0.1, affine=True, track_running_stats=True)
+  (relu): ReLU(inplace=True)
+  (maxpool): MaxPool2d(kernel_size=3, stride=2, padding=1, dilation=1, ceil_mode=False)
+  (layer1): Sequential(
+    (0-1): 2 x BasicBlock(
+      (conv1): Conv2d(64, 64, kernel_size=(3, 3), stride=(1, 1), padding=(1, 1), bias=False)
+      (conv2): Conv2d(64, 64, kernel_size=(3, 3), stride=(1, 1), padding=(1, 1), bias=False)
+    )
+  )
+  (layer2): Sequential(
+    (0): BasicBlock(
+      (conv1): Conv2d(64, 128, kernel_size=(3, 3), stride=(2, 2), padding=(1, 1), bias=False)
+      (conv2): Conv2d(128, 128, kernel_size=(3, 3), stride=(1, 1), padding=(1, 1), bias=False)
+      (downsample): Sequential(
+        (0): Conv2d(64, 128, kernel_size=(1, 1), stride=(2, 2), bias=False)
+        (1): BatchNorm2d(128, eps=1e-05, momentum=0.1, affine=True, track_running_stats=True)
+      )
+    )
+  )
+  (avgpool): AdaptiveAvgPool2d(output_size=(1, 1))
+  (fc): Linear(in_features=128, out_features=1000, bias=True)
+)",
+        )
+        .unwrap();
+        assert!(imported.warnings.is_empty());
+        let graph = imported.graph;
+        assert_eq!(graph.inputs[0].channels, Some(3));
+        let dims: Vec<(String, u32)> = graph
+            .shapes(224)
+            .unwrap()
+            .into_iter()
+            .map(|(name, shape)| (name, shape.dim))
+            .collect();
+        assert_eq!(
+            dims,
+            vec![
+                ("conv1".to_string(), 112),
+                ("maxpool".to_string(), 56),
+                ("layer1.0.conv1".to_string(), 56),
+                ("layer1.0.conv2".to_string(), 56),
+                ("layer1.1.conv1".to_string(), 56),
+                ("layer1.1.conv2".to_string(), 56),
+                ("layer2.0.conv1".to_string(), 28),
+                ("layer2.0.conv2".to_string(), 28),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decoder_layers() {
+        let imported = import(
+            "Sequential(
+  (0): ConvTranspose2d(64, 32, kernel_size=(4, 4), stride=(2, 2), padding=(1, 1))
+  (1): Upsample(scale_factor=2.0, mode='nearest')
+  (2): ZeroPad2d((1, 1, 1, 1))
+  (3): PixelShuffle(upscale_factor=2)
+  (4): Conv2d(8, 3, kernel_size=(3, 3), stride=(1, 1), padding=same)
+)",
+        )
+        .unwrap();
+        assert!(imported.warnings.is_empty());
+        let shape = imported.graph.output_shape(7).unwrap();
+        // 7 -> 14 -> 28 -> 30 -> 60 -> 60
+        assert_eq!(shape.dim, 60);
+        assert_eq!(shape.channels, Some(3));
+    }
+
+    #[test]
+    fn test_custom_layers() {
+        plugin::register("TestResampler2d", |params| {
+            match params.get("scale").and_then(toml::Value::as_array) {
+                Some(scale) => {
+                    let scale = scale[0].as_integer().unwrap_or_default() as u16;
+                    Ok(plugin::Geometry::transposed(scale, scale, 0))
+                }
+                None => Err("needs a 'scale'".to_string()),
+            }
+        })
+        .unwrap();
+        let imported = import(
+            "Sequential(
+  (0): Conv2d(3, 8, kernel_size=(3, 3), stride=(2, 2), padding=(1, 1))
+  (1): TestResampler2d(8, scale=(4, 4), mode='learned')
+)",
+        )
+        .unwrap();
+        assert_eq!(imported.graph.output_shape(32).unwrap().dim, 64);
+        let error = import("Sequential(\n  (up): TestResampler2d(8)\n)").unwrap_err();
+        assert!(error.starts_with("Layer 'up': Invalid layer of type 'TestResampler2d'"));
+    }
+
+    #[test]
+    fn test_unbalanced_printout() {
+        assert!(import("Sequential(\n  (0): Conv2d(3, 8, kernel_size=(3, 3))\n").is_err());
+    }
+}
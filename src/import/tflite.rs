@@ -0,0 +1,598 @@
+//! Import of TensorFlow Lite models.
+//!
+//! A `.tflite` file is a flatbuffer holding the tensors and operators of one or more subgraphs,
+//! of which the first one is the model. Like in ONNX, every operator consumes and produces
+//! tensors, referred to by their index, and the weights are tensors backed by constant buffers.
+//! The feature maps have the layout (N, H, W, C). Operators have no names, so the nodes are named
+//! after the tensors they produce (e.g. `model/conv2d/Relu`).
+//!
+//! `CONV_2D`, `DEPTHWISE_CONV_2D`, `TRANSPOSE_CONV` and the pooling operators become (transposed)
+//! convolutional nodes, with their 'SAME' padding approximated by a symmetric padding, resizing by
+//! an integer factor and `DEPTH_TO_SPACE` become transposed convolutions, `SPACE_TO_DEPTH` and
+//! padding become convolutions, and `ADD`, `MUL` and `CONCATENATION` of several feature maps
+//! become merge nodes.
+use std::collections::HashMap;
+
+use super::{effective_filter_size, Builder, Imported};
+use crate::flatbuffers::Table;
+use crate::graph::Op;
+
+// Field indices of the tables defined in `schema.fbs`.
+const MODEL_OPERATOR_CODES: usize = 1;
+const MODEL_SUBGRAPHS: usize = 2;
+const MODEL_BUFFERS: usize = 4;
+const OPERATOR_CODE_DEPRECATED_BUILTIN_CODE: usize = 0;
+const OPERATOR_CODE_CUSTOM_CODE: usize = 1;
+const OPERATOR_CODE_BUILTIN_CODE: usize = 3;
+const SUBGRAPH_TENSORS: usize = 0;
+const SUBGRAPH_INPUTS: usize = 1;
+const SUBGRAPH_OUTPUTS: usize = 2;
+const SUBGRAPH_OPERATORS: usize = 3;
+const TENSOR_SHAPE: usize = 0;
+const TENSOR_TYPE: usize = 1;
+const TENSOR_BUFFER: usize = 2;
+const TENSOR_NAME: usize = 3;
+const TENSOR_SHAPE_SIGNATURE: usize = 7;
+const BUFFER_DATA: usize = 0;
+const OPERATOR_OPCODE_INDEX: usize = 0;
+const OPERATOR_INPUTS: usize = 1;
+const OPERATOR_OUTPUTS: usize = 2;
+const OPERATOR_BUILTIN_OPTIONS: usize = 4;
+// The options of convolutions, depthwise convolutions, transposed convolutions and pooling
+// all start with the padding and the strides.
+const OPTIONS_PADDING: usize = 0;
+const OPTIONS_STRIDE_W: usize = 1;
+const OPTIONS_STRIDE_H: usize = 2;
+const POOL_FILTER_WIDTH: usize = 3;
+const POOL_FILTER_HEIGHT: usize = 4;
+const CONV_DILATION_W: usize = 4;
+const CONV_DILATION_H: usize = 5;
+const DEPTHWISE_DILATION_W: usize = 5;
+const DEPTHWISE_DILATION_H: usize = 6;
+const CONCATENATION_AXIS: usize = 0;
+const BLOCK_SIZE: usize = 0;
+
+// The builtin operators, by their code in `schema.fbs`.
+const ADD: i32 = 0;
+const AVERAGE_POOL_2D: i32 = 1;
+const CONCATENATION: i32 = 2;
+const CONV_2D: i32 = 3;
+const DEPTHWISE_CONV_2D: i32 = 4;
+const DEPTH_TO_SPACE: i32 = 5;
+const FULLY_CONNECTED: i32 = 9;
+const L2_POOL_2D: i32 = 12;
+const MAX_POOL_2D: i32 = 17;
+const MUL: i32 = 18;
+const RESHAPE: i32 = 22;
+const RESIZE_BILINEAR: i32 = 23;
+const SPACE_TO_DEPTH: i32 = 26;
+const CUSTOM: i32 = 32;
+const PAD: i32 = 34;
+const MEAN: i32 = 40;
+const SQUEEZE: i32 = 43;
+const PADV2: i32 = 60;
+const TRANSPOSE_CONV: i32 = 67;
+const RESIZE_NEAREST_NEIGHBOR: i32 = 97;
+const MIRROR_PAD: i32 = 100;
+const ADD_N: i32 = 106;
+const BATCH_MATMUL: i32 = 126;
+
+/// The value of the `Padding` enum for 'VALID' padding, the other one being 'SAME'.
+const VALID: u8 = 1;
+/// The `TensorType`s of 32 and 64 bit integers.
+const INT32: u8 = 2;
+const INT64: u8 = 4;
+
+/// An operator of the subgraph, with its operator code resolved.
+struct Operator<'a> {
+    code: i32,
+    /// The name of a custom operator.
+    custom: Option<String>,
+    /// The indices of the input and output tensors, where -1 marks an omitted optional input.
+    inputs: Vec<i32>,
+    outputs: Vec<i32>,
+    options: Option<Table<'a>>,
+}
+
+/// The state of the conversion of a TensorFlow Lite subgraph into a [`Graph`](crate::graph::Graph).
+struct Converter<'a> {
+    tensors: Vec<Table<'a>>,
+    buffers: Vec<Table<'a>>,
+    /// The node producing every feature map computed so far, or `None` for tensors
+    /// that have lost their spatial dimensions.
+    producers: HashMap<i32, Option<String>>,
+    builder: Builder,
+}
+
+impl<'a> Converter<'a> {
+    fn tensor(&self, index: i32) -> Result<&Table<'a>, String> {
+        self.tensors
+            .get(index as usize)
+            .ok_or_else(|| format!("The model refers to the missing tensor {}.", index))
+    }
+
+    fn name(&self, index: i32) -> Result<String, String> {
+        Ok(self
+            .tensor(index)?
+            .string(TENSOR_NAME)?
+            .unwrap_or_else(|| format!("tensor_{}", index)))
+    }
+
+    fn shape(&self, index: i32) -> Result<Vec<i64>, String> {
+        Ok(self
+            .tensor(index)?
+            .ints(TENSOR_SHAPE)?
+            .into_iter()
+            .map(i64::from)
+            .collect())
+    }
+
+    /// The values of the constant integer tensor `index`, or `None` if it is not constant.
+    fn constant(&self, index: i32) -> Result<Option<Vec<i64>>, String> {
+        let tensor = self.tensor(index)?;
+        let size = match tensor.byte(TENSOR_TYPE, 0)? {
+            INT32 => 4,
+            INT64 => 8,
+            _ => return Ok(None),
+        };
+        // The buffer 0 is the empty buffer of the tensors that are computed.
+        let data = match self.buffers.get(tensor.uint(TENSOR_BUFFER, 4, 0)? as usize) {
+            Some(buffer) => buffer.bytes(BUFFER_DATA)?,
+            None => return Ok(None),
+        };
+        if data.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            data.chunks(size)
+                .map(|chunk| {
+                    let value = chunk
+                        .iter()
+                        .rev()
+                        .fold(0u64, |value, &byte| (value << 8) | byte as u64);
+                    if size == 4 {
+                        value as u32 as i32 as i64
+                    } else {
+                        value as i64
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    /// Declare the input tensor `index`, if it is a feature map.
+    fn input(&mut self, index: i32) -> Result<(), String> {
+        // The shape signature marks the dimensions of dynamic size with -1.
+        let mut shape: Vec<Option<i64>> = self.shape(index)?.into_iter().map(Some).collect();
+        let signature = self.tensor(index)?.ints(TENSOR_SHAPE_SIGNATURE)?;
+        if signature.len() == shape.len() {
+            for (dim, &size) in shape.iter_mut().zip(&signature) {
+                if size < 0 {
+                    *dim = None;
+                }
+            }
+        }
+        // Only inputs of the layout (N, spatial..., C) are feature maps.
+        if shape.len() < 3 {
+            return Ok(());
+        }
+        let tensor = self.name(index)?;
+        let name =
+            self.builder
+                .input(&tensor, &shape[1..shape.len() - 1], shape[shape.len() - 1])?;
+        self.producers.insert(index, Some(name));
+        Ok(())
+    }
+
+    /// Convert `operator`, recording the node producing each of its outputs.
+    fn operator(&mut self, operator: &Operator<'a>) -> Result<(), String> {
+        // The input of a transposed convolution is its third one, after the output shape and the weight.
+        let inputs: Vec<i32> = if operator.code == TRANSPOSE_CONV {
+            operator.inputs.iter().skip(2).copied().collect()
+        } else {
+            operator.inputs.clone()
+        };
+        let producers: Vec<&Option<String>> = inputs
+            .iter()
+            .filter_map(|tensor| self.producers.get(tensor))
+            .collect();
+        let spatial: Vec<String> = producers.iter().filter_map(|p| (*p).clone()).collect();
+        if spatial.is_empty() {
+            if !producers.is_empty() {
+                self.produce(operator, None);
+            }
+            return Ok(());
+        }
+        let name = match operator.outputs.first() {
+            Some(&output) => self.name(output)?,
+            None => return Ok(()),
+        };
+        let input = &spatial[0];
+
+        let node = match operator.code {
+            CONV_2D | DEPTHWISE_CONV_2D | TRANSPOSE_CONV | AVERAGE_POOL_2D | MAX_POOL_2D
+            | L2_POOL_2D => Some(self.conv(operator, &name, input)?),
+            RESIZE_BILINEAR | RESIZE_NEAREST_NEIGHBOR => self.resize(operator, &name, input)?,
+            DEPTH_TO_SPACE | SPACE_TO_DEPTH => {
+                let block = match &operator.options {
+                    Some(options) => options.int(BLOCK_SIZE, 0)? as i64,
+                    None => 0,
+                };
+                if block < 1 {
+                    return Err(format!("The block size of node '{}' is missing.", name));
+                }
+                if operator.code == DEPTH_TO_SPACE {
+                    Some(self.builder.upsample(&name, input, block)?)
+                } else {
+                    Some(
+                        self.builder
+                            .layer(&name, false, input, block, block, 0, None)?,
+                    )
+                }
+            }
+            PAD | PADV2 | MIRROR_PAD => self.pad(operator, &name, input)?,
+            ADD | MUL | ADD_N if spatial.len() > 1 => {
+                Some(self.builder.merge(&name, Op::Add, spatial.clone()))
+            }
+            CONCATENATION if spatial.len() > 1 => {
+                let axis = match &operator.options {
+                    Some(options) => options.int(CONCATENATION_AXIS, 0)?,
+                    None => 0,
+                };
+                // The channels are the last axis.
+                let rank = self.shape(operator.inputs[0])?.len() as i32;
+                if axis == -1 || axis == rank - 1 {
+                    Some(self.builder.merge(&name, Op::Concat, spatial.clone()))
+                } else {
+                    self.builder.warn(format!(
+                        "Node '{}' concatenates along an axis other than the channels and is ignored.",
+                        name
+                    ));
+                    None
+                }
+            }
+            FULLY_CONNECTED | RESHAPE | MEAN | SQUEEZE | BATCH_MATMUL => {
+                self.produce(operator, None);
+                return Ok(());
+            }
+            CUSTOM => {
+                self.builder.warn(format!(
+                    "The custom operator '{}' of node '{}' is assumed to leave the dimension unchanged.",
+                    operator.custom.as_deref().unwrap_or_default(),
+                    name
+                ));
+                None
+            }
+            // All other operators leave the spatial dimensions unchanged.
+            _ => None,
+        };
+        let producer = node.unwrap_or_else(|| input.clone());
+        self.produce(operator, Some(producer));
+        Ok(())
+    }
+
+    /// Record `producer` as the node computing the outputs of `operator`.
+    fn produce(&mut self, operator: &Operator, producer: Option<String>) {
+        for &output in &operator.outputs {
+            self.producers.insert(output, producer.clone());
+        }
+    }
+
+    /// Convert a convolution or pooling `operator` named `name` applied to the output of `input`.
+    fn conv(&mut self, operator: &Operator, name: &str, input: &str) -> Result<String, String> {
+        let options = match &operator.options {
+            Some(options) => *options,
+            None => return Err(format!("Node '{}' lacks its options.", name)),
+        };
+        let transposed = operator.code == TRANSPOSE_CONV;
+        // The weight is (out channels, height, width, in channels), but (1, height, width, out
+        // channels) for depthwise convolutions, and is the second input of transposed convolutions.
+        let weight = match operator.code {
+            CONV_2D | DEPTHWISE_CONV_2D | TRANSPOSE_CONV => match operator.inputs.get(1) {
+                Some(&index) => self.shape(index)?,
+                None => vec![],
+            },
+            _ => vec![],
+        };
+        let (kernel, channels) = match operator.code {
+            CONV_2D | TRANSPOSE_CONV if weight.len() == 4 => {
+                ([weight[1], weight[2]], Some(weight[0]))
+            }
+            DEPTHWISE_CONV_2D if weight.len() == 4 => ([weight[1], weight[2]], Some(weight[3])),
+            CONV_2D | DEPTHWISE_CONV_2D | TRANSPOSE_CONV => {
+                return Err(format!(
+                    "The weight of node '{}' is not 4-dimensional.",
+                    name
+                ))
+            }
+            _ => (
+                [
+                    options.int(POOL_FILTER_HEIGHT, 0)? as i64,
+                    options.int(POOL_FILTER_WIDTH, 0)? as i64,
+                ],
+                None,
+            ),
+        };
+        let strides = [
+            options.int(OPTIONS_STRIDE_H, 1)? as i64,
+            options.int(OPTIONS_STRIDE_W, 1)? as i64,
+        ];
+        let dilations = match operator.code {
+            CONV_2D => [
+                options.int(CONV_DILATION_H, 1)? as i64,
+                options.int(CONV_DILATION_W, 1)? as i64,
+            ],
+            DEPTHWISE_CONV_2D => [
+                options.int(DEPTHWISE_DILATION_H, 1)? as i64,
+                options.int(DEPTHWISE_DILATION_W, 1)? as i64,
+            ],
+            _ => [1, 1],
+        };
+        self.builder
+            .check_square(name, &[&kernel, &strides, &dilations]);
+        let filter_size = effective_filter_size(kernel[0], dilations[0]);
+        let stride = strides[0];
+        let padding = if options.byte(OPTIONS_PADDING, 0)? == VALID {
+            0
+        } else {
+            self.builder
+                .same_padding(name, filter_size, stride, transposed)
+        };
+        self.builder.layer(
+            name,
+            transposed,
+            input,
+            filter_size,
+            stride,
+            padding,
+            channels,
+        )
+    }
+
+    /// Convert a resizing `operator` named `name` applied to the output of `input`.
+    ///
+    /// The size of the output is a constant input, from which the scale is derived.
+    fn resize(
+        &mut self,
+        operator: &Operator,
+        name: &str,
+        input: &str,
+    ) -> Result<Option<String>, String> {
+        let from = self.shape(operator.inputs[0])?;
+        let to = match operator.inputs.get(1) {
+            Some(&size) => self.constant(size)?,
+            None => None,
+        };
+        match (from.get(1), to.as_ref().and_then(|to| to.first())) {
+            (Some(&from), Some(&to)) if from > 0 && to == from => Ok(None),
+            (Some(&from), Some(&to)) if from > 0 && to > from && to % from == 0 => {
+                self.builder.upsample(name, input, to / from).map(Some)
+            }
+            _ => {
+                self.builder.warn(format!(
+                    "The scale of node '{}' is not a constant integer factor, the node is assumed to leave the dimension unchanged.",
+                    name
+                ));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Convert a padding `operator` named `name` applied to the output of `input`, like a 1x1
+    /// convolution with that padding.
+    fn pad(
+        &mut self,
+        operator: &Operator,
+        name: &str,
+        input: &str,
+    ) -> Result<Option<String>, String> {
+        // The paddings are given as (before, after) for each axis (N, H, W, C).
+        let paddings = match operator.inputs.get(1) {
+            Some(&paddings) => self.constant(paddings)?,
+            None => None,
+        };
+        let padding = match paddings {
+            Some(paddings) if paddings.len() >= 6 => paddings[2..paddings.len() - 2].to_vec(),
+            _ => return Err(format!("The padding of node '{}' is not a constant.", name)),
+        };
+        if padding.iter().all(|&p| p == 0) {
+            return Ok(None);
+        }
+        if padding.iter().any(|&p| p != padding[0]) {
+            self.builder.warn(format!(
+                "Node '{}' pads asymmetrically, only a padding of {} on each side is considered.",
+                name, padding[0]
+            ));
+        }
+        self.builder
+            .layer(name, false, input, 1, 1, padding[0], None)
+            .map(Some)
+    }
+}
+
+/// ## Import the TensorFlow Lite model encoded in `bytes`.
+pub fn import(bytes: &[u8]) -> Result<Imported, String> {
+    if Table::identifier(bytes) != Some(b"TFL3") {
+        return Err("The file is not a TensorFlow Lite model.".to_string());
+    }
+    let model = Table::root(bytes)?;
+    // The builtin codes beyond 127 only fit into the newer 32 bit field.
+    let codes = model
+        .tables(MODEL_OPERATOR_CODES)?
+        .iter()
+        .map(|code| {
+            Ok((
+                code.int(OPERATOR_CODE_BUILTIN_CODE, 0)?
+                    .max(code.byte(OPERATOR_CODE_DEPRECATED_BUILTIN_CODE, 0)? as i8 as i32),
+                code.string(OPERATOR_CODE_CUSTOM_CODE)?,
+            ))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let subgraph = match model.tables(MODEL_SUBGRAPHS)?.first() {
+        Some(subgraph) => *subgraph,
+        None => return Err("The model does not contain a subgraph.".to_string()),
+    };
+
+    let mut converter = Converter {
+        tensors: subgraph.tables(SUBGRAPH_TENSORS)?,
+        buffers: model.tables(MODEL_BUFFERS)?,
+        producers: HashMap::new(),
+        builder: Builder::default(),
+    };
+    for input in subgraph.ints(SUBGRAPH_INPUTS)? {
+        converter.input(input)?;
+    }
+    for operator in subgraph.tables(SUBGRAPH_OPERATORS)? {
+        let (code, custom) = match codes.get(operator.uint(OPERATOR_OPCODE_INDEX, 4, 0)? as usize) {
+            Some(code) => code.clone(),
+            None => return Err("An operator refers to a missing operator code.".to_string()),
+        };
+        converter.operator(&Operator {
+            code,
+            custom,
+            inputs: operator.ints(OPERATOR_INPUTS)?,
+            outputs: operator.ints(OPERATOR_OUTPUTS)?,
+            options: operator.table(OPERATOR_BUILTIN_OPTIONS)?,
+        })?;
+    }
+    for output in subgraph.ints(SUBGRAPH_OUTPUTS)? {
+        if let Some(Some(node)) = converter.producers.get(&output) {
+            converter.builder.output(node);
+        }
+    }
+    converter.builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatbuffers::{write, Field};
+
+    fn tensor(name: &str, shape: &[i32], tensor_type: i32, buffer: i32) -> Vec<Option<Field>> {
+        vec![
+            Some(Field::Ints(shape.to_vec())),
+            Some(Field::Int(tensor_type)),
+            Some(Field::Int(buffer)),
+            Some(Field::String(name.to_string())),
+        ]
+    }
+
+    fn operator(
+        opcode_index: i32,
+        inputs: &[i32],
+        outputs: &[i32],
+        options: Vec<Option<Field>>,
+    ) -> Vec<Option<Field>> {
+        vec![
+            Some(Field::Int(opcode_index)),
+            Some(Field::Ints(inputs.to_vec())),
+            Some(Field::Ints(outputs.to_vec())),
+            None,
+            Some(Field::Table(options)),
+        ]
+    }
+
+    /// A model with a strided convolution, a depthwise convolution with 'SAME' padding, their
+    /// addition after padding the input, an upsampling and a classifier.
+    fn model() -> Vec<u8> {
+        let int = |value| Some(Field::Int(value));
+        let codes = [
+            CONV_2D,
+            DEPTHWISE_CONV_2D,
+            PAD,
+            ADD,
+            RESIZE_NEAREST_NEIGHBOR,
+            MEAN,
+        ]
+        .iter()
+        .map(|&code| vec![int(code), None, None, int(code)])
+        .collect();
+        let mut pads = vec![];
+        for value in [0i32, 0, 1, 1, 1, 1, 0, 0] {
+            pads.extend_from_slice(&value.to_le_bytes());
+        }
+        let tensors = vec![
+            tensor("input", &[1, 32, 32, 3], 0, 0),
+            tensor("conv/weight", &[16, 3, 3, 3], 0, 0),
+            tensor("conv/Relu", &[1, 16, 16, 16], 0, 0),
+            tensor("depthwise/weight", &[1, 3, 3, 16], 0, 0),
+            tensor("depthwise/Relu", &[1, 16, 16, 16], 0, 0),
+            tensor("pad/paddings", &[4, 2], INT32 as i32, 1),
+            tensor("pad", &[1, 18, 18, 16], 0, 0),
+            tensor("add", &[1, 16, 16, 16], 0, 0),
+            tensor("resize/size", &[2], INT32 as i32, 2),
+            tensor("resize", &[1, 32, 32, 16], 0, 0),
+            tensor("mean", &[1, 16], 0, 0),
+        ];
+        let operators = vec![
+            // Conv2DOptions: padding, stride_w, stride_h.
+            operator(0, &[0, 1, -1], &[2], vec![int(0), int(2), int(2)]),
+            operator(1, &[2, 3, -1], &[4], vec![int(0), int(1), int(1)]),
+            operator(2, &[2, 5], &[6], vec![]),
+            operator(3, &[4, 2], &[7], vec![]),
+            operator(4, &[7, 8], &[9], vec![]),
+            operator(5, &[9], &[10], vec![]),
+        ];
+        let subgraph = vec![
+            Some(Field::Tables(tensors)),
+            Some(Field::Ints(vec![0])),
+            Some(Field::Ints(vec![9, 10])),
+            Some(Field::Tables(operators)),
+        ];
+        let mut size = vec![];
+        for value in [32i32, 32] {
+            size.extend_from_slice(&value.to_le_bytes());
+        }
+        let buffers = vec![
+            vec![],
+            vec![Some(Field::Bytes(pads))],
+            vec![Some(Field::Bytes(size))],
+        ];
+        write(
+            b"TFL3",
+            &[
+                int(3),
+                Some(Field::Tables(codes)),
+                Some(Field::Tables(vec![subgraph])),
+                None,
+                Some(Field::Tables(buffers)),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_import() {
+        let imported = import(&model()).unwrap();
+        let graph = imported.graph;
+        assert_eq!(graph.inputs[0].input_dim, Some(32));
+        assert_eq!(graph.inputs[0].channels, Some(3));
+        let names: Vec<&str> = graph.nodes.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["conv/Relu", "depthwise/Relu", "pad", "add", "resize"]
+        );
+        let conv = &graph.nodes[0];
+        assert_eq!(
+            (conv.filter_size, conv.stride, conv.padding, conv.channels),
+            (Some(3), 2, 1, Some(16))
+        );
+        assert_eq!(graph.nodes[1].padding, 1);
+        assert_eq!(graph.nodes[3].inputs, vec!["depthwise/Relu", "conv/Relu"]);
+        assert_eq!(graph.nodes[4].op, Op::Transposed);
+        // The flattened output of the classifier is not a feature map.
+        assert_eq!(graph.outputs, vec!["resize"]);
+        assert_eq!(graph.output_shape(32).unwrap().dim, 32);
+        assert!(imported.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_models() {
+        assert_eq!(
+            import(b"not a model").unwrap_err(),
+            "The file is not a TensorFlow Lite model."
+        );
+        let mut bytes = model();
+        bytes.truncate(bytes.len() / 2);
+        assert!(import(&bytes).is_err());
+    }
+}
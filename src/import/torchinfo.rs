@@ -0,0 +1,362 @@
+//! Import of the summary tables printed by `torchinfo.summary()` and `torchsummary.summary()`.
+//!
+//! The summaries list the output shape of every layer, but not the parameters of the layers
+//! (unless the "Kernel Shape" column is requested). Wherever the spatial dimension changes, the
+//! parameters of a (transposed) convolution leading from the input to the output dimension are
+//! inferred: the stride closest to the ratio of the dimensions, the filter size from the "Kernel
+//! Shape" column (or equal to the stride otherwise) and the smallest padding that reproduces the
+//! output shape. Layers whose parameters cannot be inferred are reported.
+//!
+//! Like the printed model, the summary does not show how the layers are connected, so they are assumed
+//! to be applied one after the other. If the "Input Shape" column is shown, layers whose input does not
+//! match the preceding output, such as the projections of residual blocks, are skipped as parallel branches.
+use super::{Builder, Imported};
+use crate::graph::INPUT;
+
+/// The titles of the columns containing shapes.
+const INPUT_SHAPE: &str = "Input Shape";
+const OUTPUT_SHAPE: &str = "Output Shape";
+const KERNEL_SHAPE: &str = "Kernel Shape";
+
+#[derive(Debug, Clone, Default)]
+/// A row of the summary table.
+struct Row {
+    name: String,
+    /// The depth of the layer in the tree of modules, which only `torchinfo` reports.
+    depth: Option<usize>,
+    input: Option<Vec<i64>>,
+    output: Option<Vec<i64>>,
+    kernel: Option<Vec<i64>>,
+}
+
+/// Parse a shape such as `[1, 64, 112, 112]` or `[-1, 64, 112, 112]`.
+fn shape(text: &str) -> Option<Vec<i64>> {
+    text.trim_matches(|c| c == '[' || c == ']')
+        .split(',')
+        .map(|dim| dim.trim().parse().ok())
+        .collect()
+}
+
+/// Parse the name column of a row, e.g. `├─Conv2d (conv1): 1-1` for `torchinfo` or `Conv2d-1` for `torchsummary`.
+fn name(text: &str) -> (String, Option<usize>) {
+    let text = text
+        .trim_start_matches(|c: char| "│├└─ |".contains(c))
+        .trim();
+    match text.rsplit_once(": ") {
+        Some((layer, index)) => {
+            let depth = index.split('-').next().and_then(|depth| depth.parse().ok());
+            // Prefer the name of the attribute holding the layer if it is shown.
+            let name = match (layer.find(" ("), layer.strip_suffix(')')) {
+                (Some(start), Some(layer)) => layer[start + 2..].to_string(),
+                _ => format!("{}-{}", layer, index),
+            };
+            (name, depth)
+        }
+        None => (text.to_string(), None),
+    }
+}
+
+/// Parse the rows of the summary table in `text`.
+fn parse(text: &str) -> Result<Vec<Row>, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let header = match lines.iter().position(|line| line.contains(OUTPUT_SHAPE)) {
+        Some(header) => header,
+        None => {
+            return Err(format!(
+                "The summary does not contain an '{}' column.",
+                OUTPUT_SHAPE
+            ))
+        }
+    };
+    // The positions (in characters) at which the columns containing shapes start.
+    let columns: Vec<(&str, usize)> = [INPUT_SHAPE, OUTPUT_SHAPE, KERNEL_SHAPE]
+        .iter()
+        .filter_map(|title| {
+            let start = lines[header].find(title)?;
+            Some((*title, lines[header][..start].chars().count()))
+        })
+        .collect();
+    let first_column = columns.iter().map(|&(_, start)| start).min().unwrap_or(0);
+
+    let mut rows = vec![];
+    for line in &lines[header + 1..] {
+        if line.starts_with("Total params") {
+            break;
+        }
+        let chars: Vec<char> = line.chars().collect();
+        if chars
+            .iter()
+            .all(|&c| c == '=' || c == '-' || c.is_whitespace())
+        {
+            continue;
+        }
+
+        // The shapes are assigned to the column whose title starts closest to them.
+        let mut row = Row::default();
+        let mut end_of_name = chars.len().min(first_column);
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i] != '[' {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < chars.len() && chars[i] != ']' {
+                i += 1;
+            }
+            end_of_name = end_of_name.min(start);
+            let text: String = chars[start..i.min(chars.len())].iter().collect();
+            let column = columns
+                .iter()
+                .min_by_key(|&&(_, column)| column.abs_diff(start))
+                .map(|&(title, _)| title);
+            match column {
+                Some(INPUT_SHAPE) => row.input = shape(&text),
+                Some(OUTPUT_SHAPE) => row.output = shape(&text),
+                Some(_) => row.kernel = shape(&text),
+                None => {}
+            }
+        }
+        let (name, depth) = name(&chars[..end_of_name].iter().collect::<String>());
+        row.name = name;
+        row.depth = depth;
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Find the parameters `(transposed, filter_size, stride, padding)` of a (transposed) convolution
+/// turning the dimension `n` into `m`, with the given filter size `kernel` if it is known.
+fn infer(n: i64, m: i64, kernel: Option<i64>) -> Option<(bool, i64, i64, i64)> {
+    let transposed = m > n;
+    let ratio = if transposed {
+        m as f64 / n as f64
+    } else {
+        n as f64 / m as f64
+    };
+    let ratio = (ratio.round() as i64).max(1);
+    let mut strides: Vec<i64> = (1..=n.max(m)).collect();
+    strides.sort_by_key(|&stride| (stride - ratio).abs());
+
+    for stride in strides {
+        let filter_size = match kernel {
+            Some(kernel) => kernel,
+            None if transposed => m - (n - 1) * stride,
+            None => stride,
+        };
+        if filter_size < 1 {
+            continue;
+        }
+        for padding in 0..=filter_size {
+            let out = if transposed {
+                (n - 1) * stride + filter_size - 2 * padding
+            } else if filter_size <= n + 2 * padding {
+                (n + 2 * padding - filter_size) / stride + 1
+            } else {
+                continue;
+            };
+            if out == m {
+                return Some((transposed, filter_size, stride, padding));
+            }
+        }
+    }
+    None
+}
+
+/// ## Import the summary table in `text`.
+///
+/// The dimension of the input is read from the "Input Shape" column if it is shown, otherwise it
+/// needs to be given as `in_dim`.
+pub fn import(text: &str, in_dim: Option<u32>) -> Result<Imported, String> {
+    let rows = parse(text)?;
+    // `torchinfo` also lists the model itself (without depth) and the containers, which are
+    // followed by their (deeper) children.
+    let tree = rows.iter().any(|row| row.depth.is_some());
+    let leaves: Vec<&Row> = rows
+        .iter()
+        .enumerate()
+        .filter(|(i, row)| match (row.depth, rows.get(i + 1)) {
+            (None, _) => !tree,
+            (Some(depth), Some(next)) => next.depth.is_none_or(|next| next <= depth),
+            (Some(_), None) => true,
+        })
+        .map(|(_, row)| row)
+        .collect();
+
+    let first_input = leaves.iter().find_map(|row| row.input.as_ref());
+    let mut dim = match (in_dim, first_input) {
+        (Some(in_dim), _) => in_dim as i64,
+        (None, Some(input)) if input.len() > 2 => input[2],
+        _ => {
+            return Err(format!(
+                "The summary does not show the '{}' of the layers, so the input dimension needs to be given.",
+                INPUT_SHAPE
+            ))
+        }
+    };
+    let channels = first_input.and_then(|input| input.get(1).copied());
+
+    let mut builder = Builder::default();
+    let mut current = builder.input(INPUT, &[Some(dim)], channels)?;
+    for row in leaves {
+        let output = match &row.output {
+            Some(output) => output,
+            None => continue,
+        };
+        // Once the feature maps have lost their spatial dimensions, the remaining layers are ignored.
+        if output.len() < 3 {
+            break;
+        }
+        if let Some(input) = &row.input {
+            if input.len() > 2 && input[2] != dim {
+                builder.warn(format!(
+                    "Layer '{}' takes an input of dimension {} instead of {} and is assumed to belong to a parallel branch.",
+                    row.name, input[2], dim
+                ));
+                continue;
+            }
+        }
+        let out_dim = output[2];
+        let kernel = row
+            .kernel
+            .as_ref()
+            .and_then(|kernel| kernel.last().copied());
+        if out_dim == dim && kernel.is_none() {
+            continue;
+        }
+        let parameters = match infer(dim, out_dim, kernel) {
+            Some(parameters) => Some(parameters),
+            None if kernel.is_some() => {
+                builder.warn(format!(
+                    "The output dimension {} of layer '{}' cannot be obtained from {} with a filter of size {}, the filter size is inferred as well.",
+                    out_dim,
+                    row.name,
+                    dim,
+                    kernel.unwrap_or(0)
+                ));
+                infer(dim, out_dim, None)
+            }
+            None => None,
+        };
+        let (transposed, filter_size, stride, padding) = match parameters {
+            Some(parameters) => parameters,
+            None => {
+                return Err(format!(
+                    "The output dimension {} of layer '{}' cannot be obtained from {}.",
+                    out_dim, row.name, dim
+                ))
+            }
+        };
+        current = builder.layer(
+            &row.name,
+            transposed,
+            &current,
+            filter_size,
+            stride,
+            padding,
+            Some(output[1]),
+        )?;
+        dim = out_dim;
+    }
+    builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_torchinfo_with_kernel_and_input_shapes() {
+        let summary = "\
+===================================================================================================================
+Layer (type (var_name):depth-idx)        Input Shape               Output Shape              Kernel Shape
+===================================================================================================================
+ResNet (ResNet)                          [1, 3, 224, 224]          [1, 1000]                 --
+├─Conv2d (conv1): 1-1                    [1, 3, 224, 224]          [1, 64, 112, 112]         [7, 7]
+├─BatchNorm2d (bn1): 1-2                 [1, 64, 112, 112]         [1, 64, 112, 112]         --
+├─MaxPool2d (maxpool): 1-3               [1, 64, 112, 112]         [1, 64, 56, 56]           --
+├─Sequential (layer2): 1-4               [1, 64, 56, 56]           [1, 128, 28, 28]          --
+│    └─BasicBlock (0): 2-1               [1, 64, 56, 56]           [1, 128, 28, 28]          --
+│    │    └─Conv2d (conv1): 3-1          [1, 64, 56, 56]           [1, 128, 28, 28]          [3, 3]
+│    │    └─Conv2d (conv2): 3-2          [1, 128, 28, 28]          [1, 128, 28, 28]          [3, 3]
+│    │    └─Sequential (downsample): 3-3 [1, 64, 56, 56]           [1, 128, 28, 28]          --
+│    │    │    └─Conv2d (0): 4-1         [1, 64, 56, 56]           [1, 128, 28, 28]          [1, 1]
+├─AdaptiveAvgPool2d (avgpool): 1-5       [1, 128, 28, 28]          [1, 128, 1, 1]            --
+├─Linear (fc): 1-6                       [1, 128]                  [1, 1000]                 --
+===================================================================================================================
+Total params: 11,689,512
+";
+        let imported = import(summary, None).unwrap();
+        assert_eq!(
+            imported.warnings,
+            vec!["Layer '0' takes an input of dimension 56 instead of 28 and is assumed to belong to a parallel branch."]
+        );
+        let graph = imported.graph;
+        let nodes: Vec<(&str, Option<u16>, u16, u16)> = graph
+            .nodes
+            .iter()
+            .map(|node| {
+                (
+                    node.name.as_str(),
+                    node.filter_size,
+                    node.stride,
+                    node.padding,
+                )
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("conv1", Some(7), 2, 3),
+                ("maxpool", Some(2), 2, 0),
+                ("conv1_2", Some(3), 2, 1),
+                ("conv2", Some(3), 1, 1),
+                ("avgpool", Some(28), 28, 0),
+            ]
+        );
+        // The shapes are reproduced for the input of the summary, and can be computed for other inputs.
+        assert_eq!(graph.shapes(0).unwrap()[3].1.dim, 28);
+        assert_eq!(graph.inputs[0].input_dim, Some(224));
+    }
+
+    #[test]
+    fn test_torchsummary() {
+        let summary = "\
+----------------------------------------------------------------
+        Layer (type)               Output Shape         Param #
+================================================================
+            Conv2d-1           [-1, 16, 32, 32]             448
+              ReLU-2           [-1, 16, 32, 32]               0
+         MaxPool2d-3           [-1, 16, 16, 16]               0
+   ConvTranspose2d-4           [-1, 8, 32, 32]             520
+================================================================
+Total params: 968
+";
+        assert!(import(summary, None).is_err());
+        let graph = import(summary, Some(32)).unwrap().graph;
+        let nodes: Vec<(&str, bool, u16)> = graph
+            .nodes
+            .iter()
+            .map(|node| {
+                (
+                    node.name.as_str(),
+                    node.op == crate::graph::Op::Transposed,
+                    node.stride,
+                )
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![("MaxPool2d-3", false, 2), ("ConvTranspose2d-4", true, 2)]
+        );
+        assert_eq!(graph.output_shape(0).unwrap().dim, 32);
+    }
+
+    #[test]
+    fn test_infer() {
+        assert_eq!(infer(224, 112, Some(7)), Some((false, 7, 2, 3)));
+        assert_eq!(infer(7, 4, None), Some((false, 2, 2, 1)));
+        assert_eq!(infer(16, 32, Some(4)), Some((true, 4, 2, 1)));
+        assert_eq!(infer(16, 33, None), Some((true, 3, 2, 0)));
+    }
+}
@@ -0,0 +1,1315 @@
+//! Import of TorchScript models.
+//!
+//! `torch.jit.save` (and `torch.jit.trace(...).save`) writes a zip archive containing the
+//! TorchScript code of every class of the model under `code/`, e.g.
+//!
+//! ```text
+//! def forward(self: __torch__.torchvision.models.resnet.BasicBlock,
+//!     argument_1: Tensor) -> Tensor:
+//!     conv1 = self.conv1
+//!     _0 = (bn1).forward((conv1).forward(argument_1, ), )
+//!     ...
+//!     input = torch.add_(_1, argument_1)
+//! ```
+//!
+//! as well as the pickled tree of modules with their attributes and the shapes of their
+//! parameters (`data.pkl`). The `forward` method of the model is interpreted: calls of submodules
+//! are followed into their methods, and the convolution, pooling and resampling operators they
+//! call become (transposed) convolutional nodes named after the path of the module, with the filter
+//! size taken from the shape of the weight. Since the data flow is followed, residual additions and
+//! concatenations become merge nodes. Control flow is not evaluated: only the first branch of a
+//! conditional and a single iteration of a loop are followed.
+use std::collections::{HashMap, HashSet};
+
+use super::{effective_filter_size, Builder, Imported};
+use crate::graph::Op;
+use crate::pickle::{self, Pickle};
+use crate::zip::Archive;
+
+#[derive(Debug, Clone, Default)]
+/// A method or function of the TorchScript code.
+struct Function {
+    params: Vec<String>,
+    /// The logical lines of the body, with their indentation.
+    body: Vec<(usize, String)>,
+}
+
+#[derive(Debug, Default)]
+/// The classes (with their methods) and functions of the TorchScript code, by qualified name.
+struct Code {
+    classes: HashMap<String, HashMap<String, Function>>,
+    functions: HashMap<String, Function>,
+}
+
+/// The positions of the characters of `text` outside of brackets and strings, with the
+/// number of brackets left open at the end of `text`.
+fn top_level(text: &str) -> (Vec<(usize, char)>, i32) {
+    let mut positions = vec![];
+    let (mut depth, mut quote) = (0, None);
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')' | ']' | '}') => depth -= 1,
+            (None, c) if depth == 0 => positions.push((i, c)),
+            _ => {}
+        }
+    }
+    (positions, depth)
+}
+
+/// Split `text` at the occurrences of `separator` outside of brackets and strings.
+fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    for (i, c) in top_level(text).0 {
+        if c == separator {
+            parts.push(&text[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Split the statement `line` into the targets and the value of an assignment, if it is one.
+fn assignment(line: &str) -> Option<(Vec<&str>, &str)> {
+    let bytes = line.as_bytes();
+    let (i, _) = top_level(line).0.into_iter().find(|&(i, c)| {
+        c == '=' && !(i > 0 && b"=!<>".contains(&bytes[i - 1])) && bytes.get(i + 1) != Some(&b'=')
+    })?;
+    // Targets may be annotated with their type, e.g. `x : Tensor = ...`.
+    let targets = line[..i]
+        .split(',')
+        .map(|target| target.split(':').next().unwrap_or_default().trim())
+        .filter(|target| !target.is_empty())
+        .collect();
+    Some((targets, &line[i + 1..]))
+}
+
+/// Join the lines of `source` whose brackets span several lines, skipping empty lines and comments.
+fn logical_lines(source: &str) -> Vec<(usize, String)> {
+    let mut lines = vec![];
+    let mut pending: Option<(usize, String)> = None;
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if pending.is_none() && (trimmed.is_empty() || trimmed.starts_with('#')) {
+            continue;
+        }
+        let (indent, text) = match pending.take() {
+            Some((indent, text)) => (indent, text + " " + trimmed),
+            None => (line.len() - line.trim_start().len(), trimmed.to_string()),
+        };
+        if top_level(&text).1 > 0 {
+            pending = Some((indent, text));
+        } else {
+            lines.push((indent, text));
+        }
+    }
+    lines.extend(pending);
+    lines
+}
+
+impl Code {
+    /// Add the classes and functions of the `source` of the TorchScript module `module`.
+    fn add(&mut self, module: &str, source: &str) {
+        let lines = logical_lines(source);
+        let mut class = None;
+        let mut i = 0;
+        while i < lines.len() {
+            let (indent, line) = &lines[i];
+            i += 1;
+            if *indent == 0 {
+                class = line.strip_prefix("class ").map(|rest| {
+                    let name = rest.split(['(', ':']).next().unwrap_or_default();
+                    format!("{}.{}", module, name.trim())
+                });
+            }
+            let signature = match line.strip_prefix("def ") {
+                Some(signature) => signature,
+                None => continue,
+            };
+            let (name, rest) = signature.split_once('(').unwrap_or((signature, ""));
+            let params = split_top_level(
+                rest.rsplit_once(')').map_or(rest, |(params, _)| params),
+                ',',
+            )
+            .iter()
+            .map(|param| {
+                param
+                    .split([':', '='])
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string()
+            })
+            .filter(|param| !param.is_empty())
+            .collect();
+            let body = lines[i..]
+                .iter()
+                .take_while(|(body_indent, _)| body_indent > indent)
+                .cloned()
+                .collect::<Vec<_>>();
+            i += body.len();
+            let function = Function { params, body };
+            match &class {
+                Some(class) if *indent > 0 => {
+                    self.classes
+                        .entry(class.clone())
+                        .or_default()
+                        .insert(name.trim().to_string(), function);
+                }
+                _ => {
+                    self.functions
+                        .insert(format!("{}.{}", module, name.trim()), function);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// An expression of the TorchScript code.
+enum Expr {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+    None,
+    Name(String),
+    Attr(Box<Expr>, String),
+    Call(Box<Expr>, Vec<Expr>, Vec<(String, Expr)>),
+    Index(Box<Expr>, Box<Expr>),
+    /// A list or a tuple.
+    List(Vec<Expr>),
+    /// An expression with operators, e.g. arithmetic on sizes, whose value is not needed.
+    Other,
+}
+
+impl Expr {
+    /// The dotted name of a function, e.g. `torch.nn.functional.relu`.
+    fn dotted(&self) -> Option<String> {
+        match self {
+            Expr::Name(name) => Some(name.clone()),
+            Expr::Attr(expr, name) => Some(format!("{}.{}", expr.dotted()?, name)),
+            _ => None,
+        }
+    }
+}
+
+/// The binary operators (and conditional expressions) that may follow an operand, longest first.
+const OPERATORS: [&str; 21] = [
+    "==", "!=", "<=", ">=", "//", "**", "<", ">", "+", "-", "*", "/", "%", "&", "|", "and ", "or ",
+    "is ", "in ", "if ", "else ",
+];
+
+/// A recursive descent parser of the expressions of the TorchScript code.
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    /// Parse the expression `text`, if it is well-formed.
+    fn parse(text: &str) -> Option<Expr> {
+        let mut parser = Parser { text, pos: 0 };
+        let expr = parser.expr()?;
+        parser.whitespace();
+        (parser.pos == text.len()).then_some(expr)
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn whitespace(&mut self) {
+        self.pos = self.text.len() - self.rest().trim_start().len();
+    }
+
+    /// Consume `token` if the remaining text starts with it.
+    fn eat(&mut self, token: &str) -> bool {
+        self.whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn identifier(&mut self) -> Option<String> {
+        self.whitespace();
+        let length = self
+            .rest()
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest().len());
+        if length == 0 || self.rest().starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        let identifier = self.rest()[..length].to_string();
+        self.pos += length;
+        Some(identifier)
+    }
+
+    fn expr(&mut self) -> Option<Expr> {
+        let mut expr = self.unary()?;
+        loop {
+            let rest = self.rest().trim_start();
+            match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                Some(op) => {
+                    self.whitespace();
+                    self.pos += op.len();
+                    self.unary()?;
+                    expr = Expr::Other;
+                }
+                None => return Some(expr),
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Option<Expr> {
+        if self.eat("not ") {
+            self.unary()?;
+            return Some(Expr::Other);
+        }
+        if self.eat("-") {
+            return match self.unary()? {
+                Expr::Int(value) => Some(Expr::Int(-value)),
+                Expr::Float(value) => Some(Expr::Float(-value)),
+                _ => Some(Expr::Other),
+            };
+        }
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat(".") {
+                expr = Expr::Attr(Box::new(expr), self.identifier()?);
+            } else if self.eat("(") {
+                let (args, kwargs) = self.arguments()?;
+                expr = Expr::Call(Box::new(expr), args, kwargs);
+            } else if self.eat("[") {
+                let index = self.expr()?;
+                if !self.eat("]") {
+                    return None;
+                }
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else {
+                return Some(expr);
+            }
+        }
+    }
+
+    /// The arguments of a call, up to the closing parenthesis.
+    #[allow(clippy::type_complexity)]
+    fn arguments(&mut self) -> Option<(Vec<Expr>, Vec<(String, Expr)>)> {
+        let (mut args, mut kwargs) = (vec![], vec![]);
+        while !self.eat(")") {
+            let start = self.pos;
+            match self.identifier() {
+                Some(keyword) if self.eat("=") && !self.rest().starts_with('=') => {
+                    kwargs.push((keyword, self.expr()?))
+                }
+                _ => {
+                    self.pos = start;
+                    args.push(self.expr()?);
+                }
+            }
+            if !self.eat(",") && !self.rest().trim_start().starts_with(')') {
+                return None;
+            }
+        }
+        Some((args, kwargs))
+    }
+
+    /// The elements of a list or tuple, up to the closing bracket `close`.
+    fn elements(&mut self, close: &str) -> Option<Vec<Expr>> {
+        let mut elements = vec![];
+        while !self.eat(close) {
+            elements.push(self.expr()?);
+            if !self.eat(",") && !self.rest().trim_start().starts_with(close) {
+                return None;
+            }
+        }
+        Some(elements)
+    }
+
+    fn primary(&mut self) -> Option<Expr> {
+        self.whitespace();
+        let text = self.text;
+        let rest = &text[self.pos..];
+        if self.eat("(") {
+            let mut elements = self.elements(")")?;
+            // Parentheses around a single expression, unless written as a tuple `(a,)`.
+            let tuple = self.text[..self.pos - 1].trim_end().ends_with(',');
+            return Some(if elements.len() == 1 && !tuple {
+                elements.remove(0)
+            } else {
+                Expr::List(elements)
+            });
+        }
+        if self.eat("[") {
+            return self.elements("]").map(Expr::List);
+        }
+        if let Some(quote) = rest.chars().next().filter(|&c| c == '"' || c == '\'') {
+            let length = rest[1..].find(quote)?;
+            self.pos += length + 2;
+            return Some(Expr::Str(rest[1..1 + length].to_string()));
+        }
+        if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            let mut length = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+                .unwrap_or(rest.len());
+            // Exponents may have a sign, e.g. `1e-05`.
+            if rest[..length].ends_with(['e', 'E']) && rest[length..].starts_with(['-', '+']) {
+                length += 1 + rest[length + 1..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len() - length - 1);
+            }
+            let number = &rest[..length];
+            self.pos += length;
+            return match number.parse() {
+                Ok(value) => Some(Expr::Int(value)),
+                Err(_) => number.parse().ok().map(Expr::Float),
+            };
+        }
+        let identifier = self.identifier()?;
+        Some(match identifier.as_str() {
+            "None" => Expr::None,
+            "True" => Expr::Bool(true),
+            "False" => Expr::Bool(false),
+            _ => Expr::Name(identifier),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A value computed while the code is interpreted.
+enum Value<'p> {
+    /// A feature map produced by the given node, or a tensor without spatial dimensions (`None`).
+    Tensor(Option<String>),
+    /// A submodule with its path in the tree of modules.
+    Module(String, &'p Pickle),
+    /// A parameter, e.g. a weight, given by its shape.
+    Parameter(Vec<i64>),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+    List(Vec<Value<'p>>),
+    None,
+    /// Any other value, e.g. the result of arithmetic.
+    Unknown,
+}
+
+impl Value<'_> {
+    fn ints(&self) -> Option<Vec<i64>> {
+        match self {
+            Value::Int(value) => Some(vec![*value]),
+            Value::List(values) => values
+                .iter()
+                .map(|value| match value {
+                    Value::Int(value) => Some(*value),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+
+    /// The first float of a float or a list of floats, e.g. a scale factor.
+    fn float(&self) -> Option<f64> {
+        match self {
+            Value::Float(value) => Some(*value),
+            Value::List(values) => match values.first() {
+                Some(Value::Float(value)) => Some(*value),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The shape of a tensor rebuilt by `torch._utils._rebuild_tensor_v2` (or a parameter wrapping it).
+fn tensor_shape(object: &Pickle) -> Option<Vec<i64>> {
+    match object {
+        Pickle::Object { callable, args, .. } => match (callable.as_ref(), args.as_slice()) {
+            (Pickle::Global(_, name), [tensor, ..]) if name == "_rebuild_parameter" => {
+                tensor_shape(tensor)
+            }
+            (Pickle::Global(_, name), [_, _, Pickle::Tuple(shape), ..])
+                if name.starts_with("_rebuild_tensor") =>
+            {
+                shape.iter().map(Pickle::as_i64).collect()
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The parameters of the operators that are converted into nodes, in the order of their signature.
+fn signature(operator: &str) -> &'static [&'static str] {
+    match operator {
+        "convolution" => &[
+            "input",
+            "weight",
+            "bias",
+            "stride",
+            "padding",
+            "dilation",
+            "transposed",
+            "output_padding",
+            "groups",
+        ],
+        "conv1d" | "conv2d" | "conv3d" => &[
+            "input", "weight", "bias", "stride", "padding", "dilation", "groups",
+        ],
+        "conv_transpose1d" | "conv_transpose2d" | "conv_transpose3d" => &[
+            "input",
+            "weight",
+            "bias",
+            "stride",
+            "padding",
+            "output_padding",
+            "groups",
+            "dilation",
+        ],
+        "max_pool1d"
+        | "max_pool2d"
+        | "max_pool3d"
+        | "max_pool1d_with_indices"
+        | "max_pool2d_with_indices"
+        | "max_pool3d_with_indices" => &[
+            "input",
+            "kernel_size",
+            "stride",
+            "padding",
+            "dilation",
+            "ceil_mode",
+        ],
+        "avg_pool1d" | "avg_pool2d" | "avg_pool3d" => {
+            &["input", "kernel_size", "stride", "padding", "ceil_mode"]
+        }
+        "lp_pool1d" | "lp_pool2d" => &["input", "norm_type", "kernel_size", "stride", "ceil_mode"],
+        "interpolate" | "upsample" => &["input", "size", "scale_factor"],
+        "pixel_shuffle" | "pixel_unshuffle" => &["input", "factor"],
+        _ if is_padding(operator) => &["input", "pad"],
+        _ => &[],
+    }
+}
+
+fn is_padding(operator: &str) -> bool {
+    ["pad", "constant_pad_nd"].contains(&operator)
+        || operator.starts_with("reflection_pad")
+        || operator.starts_with("replication_pad")
+}
+
+/// The arguments of a call of an operator, given by position or keyword.
+struct Arguments<'a, 'p> {
+    operator: &'a str,
+    args: &'a [Value<'p>],
+    kwargs: &'a [(String, Value<'p>)],
+}
+
+impl<'a, 'p> Arguments<'a, 'p> {
+    /// The argument for the parameter `key` of the operator.
+    fn get(&self, key: &str) -> Option<&'a Value<'p>> {
+        let position = signature(self.operator)
+            .iter()
+            .position(|param| *param == key);
+        self.kwargs
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+            .or_else(|| self.args.get(position?))
+    }
+}
+
+/// The local variables of a function being interpreted.
+struct Frame<'p> {
+    variables: HashMap<String, Value<'p>>,
+    /// The path of the module whose method is interpreted, empty for the model itself.
+    path: String,
+}
+
+/// The state of the interpretation of the TorchScript code.
+struct Interpreter {
+    code: Code,
+    builder: Builder,
+    /// The functions whose conditionals have been reported already.
+    reported: HashSet<String>,
+    /// The number of nested calls, which is limited in case of recursion.
+    depth: usize,
+}
+
+impl Interpreter {
+    /// The attribute `name` of the `module` at `path`.
+    fn attribute<'p>(&self, path: &str, module: &'p Pickle, name: &str) -> Value<'p> {
+        let value = match module.get(name) {
+            Some(value) => value,
+            None => return Value::Unknown,
+        };
+        match value {
+            Pickle::Object { .. } if value.class().is_some_and(|c| c.starts_with("__torch__.")) => {
+                let path = match path {
+                    "" => name.to_string(),
+                    path => format!("{}.{}", path, name),
+                };
+                Value::Module(path, value)
+            }
+            Pickle::Object { .. } => tensor_shape(value).map_or(Value::Unknown, Value::Parameter),
+            Pickle::Int(value) => Value::Int(*value),
+            Pickle::Float(value) => Value::Float(*value),
+            Pickle::Bool(value) => Value::Bool(*value),
+            Pickle::String(value) => Value::Str(value.clone()),
+            Pickle::None => Value::None,
+            _ => Value::Unknown,
+        }
+    }
+
+    /// Interpret `function` with the given arguments, returning its result.
+    fn run<'p>(
+        &mut self,
+        name: &str,
+        function: &Function,
+        args: Vec<Value<'p>>,
+        path: &str,
+    ) -> Result<Value<'p>, String> {
+        self.depth += 1;
+        if self.depth > 256 {
+            return Err(format!("The calls of '{}' are nested too deeply.", name));
+        }
+        let mut frame = Frame {
+            variables: function.params.iter().cloned().zip(args).collect(),
+            path: path.to_string(),
+        };
+        let mut result = Value::None;
+        // The indentation below which the lines of a skipped branch are ignored.
+        let mut skip: Option<usize> = None;
+        for (indent, line) in &function.body {
+            match skip {
+                Some(level) if *indent > level => continue,
+                _ => skip = None,
+            }
+            let keyword = line.split([' ', ':']).next().unwrap_or_default();
+            match keyword {
+                "if" | "for" | "while" => {
+                    if keyword == "if" && self.reported.insert(name.to_string()) {
+                        self.builder.warn(format!(
+                            "The code of '{}' contains conditionals, only their first branch is followed.",
+                            name
+                        ));
+                    }
+                    continue;
+                }
+                "elif" | "else" => {
+                    skip = Some(*indent);
+                    continue;
+                }
+                "pass" | "break" | "continue" => continue,
+                "return" => {
+                    result = match Parser::parse(line["return".len()..].trim()) {
+                        Some(expr) => self.eval(&expr, &mut frame)?,
+                        None => Value::Unknown,
+                    };
+                    break;
+                }
+                _ => {}
+            }
+            let (targets, expr) = assignment(line).unwrap_or((vec![], line));
+            let value = match Parser::parse(expr.trim()) {
+                Some(expr) => self.eval(&expr, &mut frame)?,
+                None => Value::Unknown,
+            };
+            match (targets.as_slice(), value) {
+                ([target], value) => {
+                    frame.variables.insert(target.to_string(), value);
+                }
+                (targets, Value::List(values)) if targets.len() == values.len() => {
+                    for (target, value) in targets.iter().zip(values) {
+                        frame.variables.insert(target.to_string(), value);
+                    }
+                }
+                // Tensors that are split (e.g. by `torch.chunk`) keep their spatial dimensions.
+                (targets, value) => {
+                    for target in targets {
+                        frame.variables.insert(target.to_string(), value.clone());
+                    }
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(result)
+    }
+
+    fn eval<'p>(&mut self, expr: &Expr, frame: &mut Frame<'p>) -> Result<Value<'p>, String> {
+        Ok(match expr {
+            Expr::Int(value) => Value::Int(*value),
+            Expr::Float(value) => Value::Float(*value),
+            Expr::Str(value) => Value::Str(value.clone()),
+            Expr::Bool(value) => Value::Bool(*value),
+            Expr::None => Value::None,
+            Expr::Other => Value::Unknown,
+            Expr::Name(name) => frame.variables.get(name).cloned().unwrap_or(Value::Unknown),
+            Expr::List(exprs) => Value::List(
+                exprs
+                    .iter()
+                    .map(|expr| self.eval(expr, frame))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::Attr(expr, name) => match self.eval(expr, frame)? {
+                Value::Module(path, module) => self.attribute(&path, module, name),
+                _ => Value::Unknown,
+            },
+            Expr::Index(expr, index) => match (self.eval(expr, frame)?, self.eval(index, frame)?) {
+                (Value::List(mut values), Value::Int(index)) if (index as usize) < values.len() => {
+                    values.swap_remove(index as usize)
+                }
+                (tensor @ Value::Tensor(_), _) => tensor,
+                _ => Value::Unknown,
+            },
+            Expr::Call(callee, args, kwargs) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg, frame))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let kwargs = kwargs
+                    .iter()
+                    .map(|(key, arg)| Ok((key.clone(), self.eval(arg, frame)?)))
+                    .collect::<Result<Vec<_>, String>>()?;
+                // Methods of submodules, e.g. `(conv1).forward(x, )`.
+                if let Expr::Attr(object, method) = callee.as_ref() {
+                    if let Value::Module(path, module) = self.eval(object, frame)? {
+                        return self.method(&path, module, method, args);
+                    }
+                }
+                let name = callee.dotted().unwrap_or_default();
+                self.call(&name, args, kwargs, &frame.path)?
+            }
+        })
+    }
+
+    /// Call the `method` of the `module` at `path`.
+    fn method<'p>(
+        &mut self,
+        path: &str,
+        module: &'p Pickle,
+        method: &str,
+        mut args: Vec<Value<'p>>,
+    ) -> Result<Value<'p>, String> {
+        let class = module.class().unwrap_or_default();
+        let function = match self
+            .code
+            .classes
+            .get(&class)
+            .and_then(|methods| methods.get(method))
+        {
+            Some(function) => function.clone(),
+            None => {
+                return Err(format!(
+                    "The code of method '{}' of class '{}' is missing.",
+                    method, class
+                ))
+            }
+        };
+        args.insert(0, Value::Module(path.to_string(), module));
+        self.run(&format!("{}.{}", class, method), &function, args, path)
+    }
+
+    /// Call the function or operator `name`.
+    fn call<'p>(
+        &mut self,
+        name: &str,
+        args: Vec<Value<'p>>,
+        kwargs: Vec<(String, Value<'p>)>,
+        path: &str,
+    ) -> Result<Value<'p>, String> {
+        let operator = name
+            .rsplit('.')
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches('_');
+        let tensors: Vec<Option<String>> = args
+            .iter()
+            .flat_map(|arg| match arg {
+                Value::List(values) => values.clone(),
+                arg => vec![arg.clone()],
+            })
+            .filter_map(|arg| match arg {
+                Value::Tensor(producer) => Some(producer),
+                _ => None,
+            })
+            .collect();
+        let spatial: Vec<String> = tensors.iter().flatten().cloned().collect();
+        let input = match spatial.first() {
+            Some(input) => input.clone(),
+            None => {
+                return match name {
+                    "getattr" => match (args.first(), args.get(1)) {
+                        (Some(Value::Module(path, module)), Some(Value::Str(name))) => {
+                            Ok(self.attribute(path, module, name))
+                        }
+                        _ => Ok(Value::Unknown),
+                    },
+                    "annotate" => Ok(args.get(1).cloned().unwrap_or(Value::Unknown)),
+                    _ => match self.code.functions.get(name).cloned() {
+                        Some(function) => self.run(name, &function, args, path),
+                        // Operations on tensors without spatial dimensions.
+                        None if !tensors.is_empty() => Ok(Value::Tensor(None)),
+                        None => Ok(Value::Unknown),
+                    },
+                };
+            }
+        };
+        let argument = Arguments {
+            operator,
+            args: &args,
+            kwargs: &kwargs,
+        };
+        // Layers are named after the module calling the operator, merges after the operator as well.
+        let node = match path {
+            "" => operator.to_string(),
+            path => path.to_string(),
+        };
+        let merge = match path {
+            "" => operator.trim_end_matches('_').to_string(),
+            path => format!("{}.{}", path, operator.trim_end_matches('_')),
+        };
+
+        let output = match operator {
+            _ if operator.starts_with("conv")
+                || operator.contains("pool")
+                    && !operator.starts_with("adaptive")
+                    && !operator.contains("unpool") =>
+            {
+                self.conv(&node, &input, &argument)?
+            }
+            _ if operator.starts_with("upsample") || operator == "interpolate" => {
+                // The scale factors follow the output size, which is `None` if they are given.
+                let scale = match argument.get("scale_factor") {
+                    Some(scale) => scale.float(),
+                    None => args[1..].iter().find_map(Value::float),
+                };
+                match scale {
+                    Some(1.0) => input,
+                    Some(scale) if scale > 1.0 && scale.fract() == 0.0 => {
+                        self.builder.upsample(&node, &input, scale as i64)?
+                    }
+                    _ => {
+                        self.builder.warn(format!(
+                            "Layer '{}' does not upsample by an integer factor, it is assumed to leave the dimension unchanged.",
+                            node
+                        ));
+                        input
+                    }
+                }
+            }
+            "pixel_shuffle" | "pixel_unshuffle" => {
+                let factor = match argument.get("factor").and_then(Value::ints) {
+                    Some(factor) if !factor.is_empty() => factor[0],
+                    _ => return Err(format!("The factor of layer '{}' is not a constant.", node)),
+                };
+                if operator == "pixel_shuffle" {
+                    self.builder.upsample(&node, &input, factor)?
+                } else {
+                    self.builder
+                        .layer(&node, false, &input, factor, factor, 0, None)?
+                }
+            }
+            // The padding is given as (left, right, top, bottom, ...), like a 1x1 convolution with that padding.
+            _ if is_padding(operator) => {
+                let padding = match argument.get("pad").and_then(Value::ints) {
+                    Some(padding) if !padding.is_empty() => padding,
+                    _ => {
+                        return Err(format!(
+                            "The padding of layer '{}' is not a constant.",
+                            node
+                        ))
+                    }
+                };
+                if padding.iter().any(|&p| p != padding[0]) {
+                    self.builder.warn(format!(
+                        "Layer '{}' pads asymmetrically, only a padding of {} on each side is considered.",
+                        node, padding[0]
+                    ));
+                }
+                self.builder
+                    .layer(&node, false, &input, 1, 1, padding[0], None)?
+            }
+            "add" | "add_" | "sub" | "sub_" | "mul" | "mul_" | "div" | "div_"
+                if spatial.len() > 1 =>
+            {
+                self.builder.merge(&merge, Op::Add, spatial)
+            }
+            "cat" | "concat" | "concatenate" if spatial.len() > 1 => {
+                let dim = argument
+                    .get("dim")
+                    .or_else(|| args.get(1))
+                    .and_then(Value::ints);
+                if dim == Some(vec![1]) {
+                    self.builder.merge(&merge, Op::Concat, spatial)
+                } else {
+                    self.builder.warn(format!(
+                        "Node '{}' concatenates along an axis other than the channels and is ignored.",
+                        node
+                    ));
+                    input
+                }
+            }
+            _ if operator.starts_with("adaptive")
+                || [
+                    "flatten", "linear", "matmul", "addmm", "view", "reshape", "mean",
+                ]
+                .contains(&operator) =>
+            {
+                return Ok(Value::Tensor(None));
+            }
+            "size" | "dim" | "len" | "numel" => return Ok(Value::Unknown),
+            _ => match self.code.functions.get(name).cloned() {
+                Some(function) => return self.run(name, &function, args, path),
+                // All other operators leave the spatial dimensions unchanged.
+                None => input,
+            },
+        };
+        Ok(Value::Tensor(Some(output)))
+    }
+
+    /// Convert the convolution or pooling operator called with `argument` on the output of `input`.
+    fn conv(&mut self, node: &str, input: &str, argument: &Arguments) -> Result<String, String> {
+        let transposed = argument.operator.starts_with("conv_transpose")
+            || argument.get("transposed") == Some(&Value::Bool(true));
+        let is_conv = argument.operator.starts_with("conv");
+        let weight = match argument.get("weight") {
+            Some(Value::Parameter(weight)) if weight.len() > 2 => weight.clone(),
+            _ if is_conv => {
+                return Err(format!(
+                    "The weight of node '{}' is not a parameter of the model.",
+                    node
+                ))
+            }
+            _ => vec![],
+        };
+        let kernel = match argument.get("kernel_size").and_then(Value::ints) {
+            Some(kernel) if !kernel.is_empty() => kernel,
+            _ if is_conv => weight[2..].to_vec(),
+            _ => return Err(format!("Node '{}' has no kernel size.", node)),
+        };
+        // The stride of pooling layers defaults to the kernel size, also if it is given as `[]`.
+        let strides = match argument.get("stride").and_then(Value::ints) {
+            Some(strides) if !strides.is_empty() => strides,
+            _ if is_conv => vec![1],
+            _ => kernel.clone(),
+        };
+        let dilations = argument
+            .get("dilation")
+            .and_then(Value::ints)
+            .filter(|dilations| !dilations.is_empty())
+            .unwrap_or_else(|| vec![1]);
+        self.builder
+            .check_square(node, &[&kernel, &strides, &dilations]);
+        let filter_size = effective_filter_size(kernel[0], dilations[0]);
+        let stride = strides[0];
+        let padding = match argument.get("padding") {
+            Some(Value::Str(padding)) if padding == "same" => {
+                self.builder
+                    .same_padding(node, filter_size, stride, transposed)
+            }
+            Some(padding) => {
+                let padding = padding
+                    .ints()
+                    .filter(|p| !p.is_empty())
+                    .unwrap_or_else(|| vec![0]);
+                self.builder.check_square(node, &[&padding]);
+                padding[0]
+            }
+            None => 0,
+        };
+        if argument.get("ceil_mode") == Some(&Value::Bool(true)) {
+            self.builder.warn(format!(
+                "Layer '{}' rounds its output dimension up, but convdim rounds down.",
+                node
+            ));
+        }
+        if transposed
+            && argument
+                .get("output_padding")
+                .and_then(Value::ints)
+                .is_some_and(|p| p.iter().any(|&p| p != 0))
+        {
+            self.builder.warn(format!(
+                "The 'output_padding' of layer '{}' is ignored.",
+                node
+            ));
+        }
+        // The shape of the weight is (out channels, in channels / groups, kernel...) for convolutions
+        // and (in channels, out channels / groups, kernel...) for transposed convolutions.
+        let channels = match (is_conv, transposed) {
+            (true, false) => Some(weight[0]),
+            (true, true) => {
+                let groups = argument
+                    .get("groups")
+                    .and_then(Value::ints)
+                    .map_or(1, |g| g[0]);
+                Some(weight[1] * groups)
+            }
+            _ => None,
+        };
+        self.builder.layer(
+            node,
+            transposed,
+            input,
+            filter_size,
+            stride,
+            padding,
+            channels,
+        )
+    }
+}
+
+/// ## Import the TorchScript model in the zip archive `bytes`.
+pub fn import(bytes: &[u8]) -> Result<Imported, String> {
+    let archive = Archive::read(bytes)?;
+    // All entries are stored in a directory named after the archive.
+    let prefix = match archive
+        .names()
+        .find_map(|name| name.strip_suffix("data.pkl"))
+    {
+        Some(prefix) if prefix.is_empty() || prefix.ends_with('/') => prefix.to_string(),
+        _ => return Err("The archive does not contain a model.".to_string()),
+    };
+    let mut code = Code::default();
+    let code_dir = format!("{}code/", prefix);
+    let sources: Vec<String> = archive
+        .names()
+        .filter(|name| name.starts_with(&code_dir) && name.ends_with(".py"))
+        .map(str::to_string)
+        .collect();
+    if sources.is_empty() {
+        return Err("The archive does not contain TorchScript code, it may have been written by `torch.save` instead of `torch.jit.save`.".to_string());
+    }
+    for source in sources {
+        let module = source[code_dir.len()..source.len() - 3].replace('/', ".");
+        code.add(&module, &String::from_utf8_lossy(archive.get(&source)?));
+    }
+
+    let model = pickle::load(archive.get(&format!("{}data.pkl", prefix))?)?;
+    let class = model.class().unwrap_or_default();
+    let forward = match code
+        .classes
+        .get(&class)
+        .and_then(|methods| methods.get("forward"))
+    {
+        Some(forward) => forward.clone(),
+        None => {
+            return Err(format!(
+                "The code of the model class '{}' is missing.",
+                class
+            ))
+        }
+    };
+
+    let mut interpreter = Interpreter {
+        code,
+        builder: Builder::default(),
+        reported: HashSet::new(),
+        depth: 0,
+    };
+    // The arguments of `forward` are the inputs of the model.
+    let mut args = vec![Value::Module(String::new(), &model)];
+    for param in forward.params.iter().skip(1) {
+        let input = interpreter.builder.input(param, &[None], None)?;
+        args.push(Value::Tensor(Some(input)));
+    }
+    let result = interpreter.run(&format!("{}.forward", class), &forward, args, "")?;
+    let outputs = match result {
+        Value::List(values) => values,
+        value => vec![value],
+    };
+    for output in outputs {
+        if let Value::Tensor(Some(node)) = output {
+            interpreter.builder.output(&node);
+        }
+    }
+    interpreter.builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zip;
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut bytes = vec![b'X'];
+        bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(value.as_bytes());
+        bytes
+    }
+
+    /// Pickle an object of the qualified `class` with the given attributes, as `torch.jit.save` does.
+    fn module(class: &str, attributes: Vec<(&str, Vec<u8>)>) -> Vec<u8> {
+        let (module, name) = class.rsplit_once('.').unwrap();
+        let mut bytes = format!("c{}\n{}\n)", module, name).into_bytes();
+        bytes.extend_from_slice(b"\x81}(");
+        for (key, value) in attributes {
+            bytes.extend(string(key));
+            bytes.extend(value);
+        }
+        bytes.extend_from_slice(b"ub");
+        bytes
+    }
+
+    /// Pickle a tensor of the given shape.
+    fn tensor(shape: &[u8]) -> Vec<u8> {
+        let mut bytes = b"ctorch._utils\n_rebuild_tensor_v2\n((".to_vec();
+        bytes.extend(string("storage"));
+        bytes.extend_from_slice(b"ctorch\nFloatStorage\n");
+        bytes.extend(string("0"));
+        bytes.extend(string("cpu"));
+        bytes.extend_from_slice(b"K\x01tQK\x00(");
+        for &dim in shape {
+            bytes.extend_from_slice(&[b'K', dim]);
+        }
+        bytes.extend_from_slice(b"t)\x89ccollections\nOrderedDict\n)RtR");
+        bytes
+    }
+
+    fn archive(code: &[(&str, &str)], model: Vec<u8>) -> Vec<u8> {
+        let mut data = b"\x80\x02".to_vec();
+        data.extend(model);
+        data.push(b'.');
+        let mut files: Vec<(String, &[u8])> = code
+            .iter()
+            .map(|(path, source)| (format!("net/code/__torch__/{}", path), source.as_bytes()))
+            .collect();
+        files.push(("net/data.pkl".to_string(), &data));
+        let files: Vec<(&str, &[u8])> = files
+            .iter()
+            .map(|(name, bytes)| (name.as_str(), *bytes))
+            .collect();
+        zip::write(&files)
+    }
+
+    /// The code written for traced modules of `torch.nn`.
+    const TRACED_LAYERS: [(&str, &str); 3] = [
+        (
+            "torch/nn/modules/conv.py",
+            "class Conv2d(Module):
+  __parameters__ = [\"weight\", ]
+  __buffers__ = []
+  weight : Tensor
+  training : bool
+  def forward(self: __torch__.torch.nn.modules.conv.Conv2d,
+    x: Tensor) -> Tensor:
+    weight = self.weight
+    _0 = torch._convolution(x, weight, None, [2, 2], [3, 3], [1, 1], False, [0, 0], 1, False, False, True, True)
+    return _0
+",
+        ),
+        (
+            "torch/nn/modules/pooling.py",
+            "class MaxPool2d(Module):
+  __parameters__ = []
+  __buffers__ = []
+  training : bool
+  def forward(self: __torch__.torch.nn.modules.pooling.MaxPool2d,
+    argument_1: Tensor) -> Tensor:
+    _0 = torch.max_pool2d(argument_1, [3, 3], [2, 2], [1, 1], [1, 1], False)
+    return _0
+",
+        ),
+        (
+            "torch/nn/modules/upsampling.py",
+            "class Upsample(Module):
+  __parameters__ = []
+  __buffers__ = []
+  training : bool
+  def forward(self: __torch__.torch.nn.modules.upsampling.Upsample,
+    argument_1: Tensor) -> Tensor:
+    _0 = torch.upsample_nearest2d(argument_1, None, [2., 2.])
+    return _0
+",
+        ),
+    ];
+
+    #[test]
+    fn test_traced_model() {
+        let net = "class Net(Module):
+  __parameters__ = []
+  __buffers__ = []
+  training : bool
+  conv1 : __torch__.torch.nn.modules.conv.Conv2d
+  pool : __torch__.torch.nn.modules.pooling.MaxPool2d
+  up : __torch__.torch.nn.modules.upsampling.Upsample
+  def forward(self: __torch__.net.Net,
+    x: Tensor) -> Tensor:
+    up = self.up
+    pool = self.pool
+    conv1 = self.conv1
+    _0 = torch.relu((conv1).forward(x, ))
+    _1 = (up).forward((pool).forward(_0, ), )
+    input = torch.add(_1, _0, alpha=1)
+    _2 = torch.cat([input, _0], 1)
+    _3 = torch.flatten(torch.adaptive_avg_pool2d(_2, [1, 1]), 1)
+    return _2
+";
+        let mut code = TRACED_LAYERS.to_vec();
+        code.push(("net.py", net));
+        let model = module(
+            "__torch__.net.Net",
+            vec![
+                ("training", b"\x88".to_vec()),
+                (
+                    "conv1",
+                    module(
+                        "__torch__.torch.nn.modules.conv.Conv2d",
+                        vec![("weight", tensor(&[64, 3, 7, 7]))],
+                    ),
+                ),
+                (
+                    "pool",
+                    module("__torch__.torch.nn.modules.pooling.MaxPool2d", vec![]),
+                ),
+                (
+                    "up",
+                    module("__torch__.torch.nn.modules.upsampling.Upsample", vec![]),
+                ),
+            ],
+        );
+        let imported = import(&archive(&code, model)).unwrap();
+        assert!(imported.warnings.is_empty());
+        let graph = imported.graph;
+        let nodes: Vec<(&str, Op, Option<u16>, u16, u16)> = graph
+            .nodes
+            .iter()
+            .map(|node| {
+                (
+                    node.name.as_str(),
+                    node.op,
+                    node.filter_size,
+                    node.stride,
+                    node.padding,
+                )
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("conv1", Op::Conv, Some(7), 2, 3),
+                ("pool", Op::Conv, Some(3), 2, 1),
+                ("up", Op::Transposed, Some(2), 2, 0),
+                ("add", Op::Add, None, 1, 0),
+                ("cat", Op::Concat, None, 1, 0),
+            ]
+        );
+        // The residual addition and the concatenation reuse the output of `conv1`.
+        let inputs: Vec<&Vec<String>> = graph.nodes.iter().map(|node| &node.inputs).collect();
+        assert_eq!(
+            inputs,
+            vec![
+                &vec!["x"],
+                &vec!["conv1"],
+                &vec!["pool"],
+                &vec!["up", "conv1"],
+                &vec!["add", "conv1"]
+            ]
+        );
+        assert_eq!(graph.nodes[0].channels, Some(64));
+        assert_eq!(graph.outputs, vec!["cat"]);
+        assert_eq!(graph.output_shape(224).unwrap().dim, 112);
+    }
+
+    #[test]
+    fn test_scripted_model() {
+        // Scripted code calls helper methods and functions, unrolls containers and keeps conditionals.
+        let net = "class Net(Module):
+  __parameters__ = []
+  __buffers__ = []
+  training : bool
+  def forward(self: __torch__.net.Net,
+    input: Tensor) -> Tensor:
+    _0 = getattr(self.features, \"0\")
+    _1, _2, = ((_0)._conv_forward(input, _0.weight, None, ), 1)
+    if torch.gt(_2, 0):
+      _3 = __torch__.torch.nn.functional._max_pool2d(_1, [2, 2], annotate(List[int], []), [0, 0], [1, 1], False, False, )
+    else:
+      _3 = torch.max_pool2d(_1, [5, 5])
+    return _3
+";
+        let conv = "class Conv2d(Module):
+  __parameters__ = [\"weight\", ]
+  weight : Tensor
+  def _conv_forward(self: __torch__.torch.nn.modules.conv.Conv2d,
+    input: Tensor,
+    weight: Tensor,
+    bias: Optional[Tensor]) -> Tensor:
+    _0 = torch.conv2d(input, weight, bias, [1, 1], \"same\", [2, 2])
+    return _0
+";
+        let sequential = "class Sequential(Module):
+  __parameters__ = []
+";
+        let model = module(
+            "__torch__.net.Net",
+            vec![(
+                "features",
+                module(
+                    "__torch__.torch.nn.modules.container.Sequential",
+                    vec![(
+                        "0",
+                        module(
+                            "__torch__.torch.nn.modules.conv.Conv2d",
+                            vec![("weight", tensor(&[16, 3, 3, 3]))],
+                        ),
+                    )],
+                ),
+            )],
+        );
+        let code = [
+            ("net.py", net),
+            ("torch/nn/modules/conv.py", conv),
+            ("torch/nn/modules/container.py", sequential),
+        ];
+        let imported = import(&archive(&code, model)).unwrap();
+        assert_eq!(
+            imported.warnings,
+            vec!["The code of '__torch__.net.Net.forward' contains conditionals, only their first branch is followed."]
+        );
+        let nodes: Vec<(&str, Option<u16>, u16, u16)> = imported
+            .graph
+            .nodes
+            .iter()
+            .map(|node| {
+                (
+                    node.name.as_str(),
+                    node.filter_size,
+                    node.stride,
+                    node.padding,
+                )
+            })
+            .collect();
+        // The dilated 3x3 filter covers 5 inputs.
+        assert_eq!(
+            nodes,
+            vec![("features.0", Some(5), 1, 2), ("max_pool2d", Some(2), 2, 0)]
+        );
+    }
+
+    #[test]
+    fn test_archives_without_code() {
+        let data = zip::write(&[("model/data.pkl", b"\x80\x02}.")]);
+        assert!(import(&data).unwrap_err().contains("torch.save"));
+        assert!(import(b"not an archive").is_err());
+        assert_eq!(
+            Parser::parse("torch.add(x, _0, alpha=1)"),
+            Some(Expr::Call(
+                Box::new(Expr::Attr(
+                    Box::new(Expr::Name("torch".to_string())),
+                    "add".to_string()
+                )),
+                vec![Expr::Name("x".to_string()), Expr::Name("_0".to_string())],
+                vec![("alpha".to_string(), Expr::Int(1))]
+            ))
+        );
+    }
+}
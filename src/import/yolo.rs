@@ -0,0 +1,774 @@
+//! Import of the model files of Ultralytics YOLO, e.g. `yolov8.yaml` or `yolov5s.yaml`.
+//!
+//! The architecture is a list of `backbone` and `head` layers, each given as
+//! `[from, repeats, module, args]`, where `from` is the index of the layer providing the input
+//! (negative indices count back from the current layer) or a list of them, e.g.
+//!
+//! ```text
+//! backbone:
+//!   - [-1, 1, Conv, [64, 3, 2]] # 0-P1/2
+//!   - [-1, 3, C2f, [128, True]]
+//! head:
+//!   - [-1, 1, nn.Upsample, [None, 2, "nearest"]]
+//!   - [[-1, 6], 1, Concat, [1]]
+//!   - [[15, 18, 21], 1, Detect, [nc]]
+//! ```
+//!
+//! The modules are expanded into their convolutions the way Ultralytics builds them, and named
+//! after their paths (e.g. `model.2.m.0.cv1.conv`). The numbers of channels and repeats are
+//! scaled by the `scales` of the model (or `width_multiple` and `depth_multiple` in YOLOv5), and
+//! the inputs of the `Detect` head become the outputs, so that the grid sizes can be verified.
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+use super::{effective_filter_size, Builder, Imported};
+use crate::graph::Op;
+
+/// Strip the comment from `line`, i.e. from a `#` that is not quoted and follows a space.
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && previous.is_whitespace() => return &line[..i],
+            None => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+/// A scalar of YAML, where unquoted strings that aren't numbers, booleans or null are strings.
+fn scalar(text: &str) -> Value {
+    match text {
+        "" | "~" | "null" | "None" => Value::Null,
+        "true" | "True" => Value::Bool(true),
+        "false" | "False" => Value::Bool(false),
+        _ => match (text.parse::<i64>(), text.parse::<f64>()) {
+            (Ok(integer), _) => Value::from(integer),
+            (_, Ok(number)) => Value::from(number),
+            _ => Value::String(text.to_string()),
+        },
+    }
+}
+
+/// A parser of the flow style of YAML, i.e. of scalars and nested `[...]` sequences.
+struct Flow<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Flow<'a> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        let text = self.text;
+        let rest = &text[self.pos..];
+        match self.peek() {
+            Some('[') => {
+                self.pos += 1;
+                let mut values = vec![];
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some(']') {
+                        self.pos += 1;
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some(']') => {}
+                        _ => return Err(format!("Expected ',' or ']' in '{}'.", text.trim())),
+                    }
+                }
+            }
+            Some(quote) if quote == '"' || quote == '\'' => match rest[1..].find(quote) {
+                Some(end) => {
+                    self.pos += end + 2;
+                    Ok(Value::String(rest[1..=end].to_string()))
+                }
+                None => Err(format!("Unterminated string in '{}'.", text.trim())),
+            },
+            Some(_) => {
+                let end = rest.find([',', ']']).unwrap_or(rest.len());
+                self.pos += end;
+                Ok(scalar(rest[..end].trim()))
+            }
+            None => Err(format!("Expected a value in '{}'.", text.trim())),
+        }
+    }
+}
+
+/// Parse the flow style value `text`.
+fn flow(text: &str) -> Result<Value, String> {
+    let mut parser = Flow { text, pos: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        return Err(format!(
+            "Unexpected text after the value '{}'.",
+            text.trim()
+        ));
+    }
+    Ok(value)
+}
+
+/// ## Parse the subset of YAML used by the model files.
+///
+/// The document is a mapping, whose values are flow style values (which may continue on the
+/// following indented lines), block sequences of them, or mappings of them (like the `scales`).
+fn parse(text: &str) -> Result<Value, String> {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(strip_comment)
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let nested = |line: &str| line.starts_with(char::is_whitespace) || line.starts_with('-');
+    let mut entries = Map::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let (key, rest) = match lines[i].split_once(':') {
+            Some((key, rest)) if !nested(lines[i]) => (key.trim(), rest.trim()),
+            _ => {
+                return Err(format!(
+                    "Unexpected line in the model file: '{}'.",
+                    lines[i].trim()
+                ))
+            }
+        };
+        i += 1;
+        let start = i;
+        while i < lines.len() && nested(lines[i]) {
+            i += 1;
+        }
+        let block: Vec<&str> = lines[start..i].iter().map(|line| line.trim()).collect();
+
+        // A flow sequence may start on the line of the key or on the next one, as in YOLOv5.
+        let value = if !rest.is_empty() || block.first().is_some_and(|line| line.starts_with('[')) {
+            let mut text = rest.to_string();
+            for line in &block {
+                text.push(' ');
+                text.push_str(line);
+            }
+            flow(&text)?
+        } else if block.first().is_some_and(|line| line.starts_with('-')) {
+            let mut items: Vec<String> = vec![];
+            for line in block {
+                match (line.strip_prefix('-'), items.last_mut()) {
+                    (Some(item), _) => items.push(item.to_string()),
+                    (None, Some(item)) => {
+                        item.push(' ');
+                        item.push_str(line);
+                    }
+                    (None, None) => {}
+                }
+            }
+            Value::Array(
+                items
+                    .iter()
+                    .map(|item| flow(item))
+                    .collect::<Result<_, _>>()?,
+            )
+        } else {
+            let mut members = Map::new();
+            for line in block {
+                match line.split_once(':') {
+                    Some((name, value)) => {
+                        members.insert(name.trim().to_string(), flow(value)?);
+                    }
+                    None => return Err(format!("Expected a key in '{}' of '{}'.", line, key)),
+                }
+            }
+            Value::Object(members)
+        };
+        entries.insert(key.to_string(), value);
+    }
+    Ok(Value::Object(entries))
+}
+
+/// ## The model scale in the name of the model file at `path`, e.g. `s` for `yolov8s.yaml`.
+pub fn scale_from_path(path: &Path) -> Option<char> {
+    let stem = path.file_stem()?.to_str()?.to_lowercase();
+    let version = stem.strip_prefix("yolo")?;
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let scale = version.trim_start_matches(|c: char| c.is_ascii_digit());
+    if scale.len() == version.len() {
+        return None;
+    }
+    scale.chars().next().filter(|c| "nslmx".contains(*c))
+}
+
+/// The state of the conversion of the layers of a model.
+struct Converter {
+    builder: Builder,
+    /// The multiplier of the channels and the maximum number of channels.
+    width: f64,
+    max_channels: f64,
+    /// The node producing the output of every layer and its number of channels, where the
+    /// node is `None` for layers that don't produce a feature map (e.g. the heads).
+    layers: Vec<(Option<String>, i64)>,
+}
+
+/// The integer argument `index` of a module, or `default` if it is absent or `None`.
+fn arg(args: &[Value], index: usize, default: i64) -> Result<i64, String> {
+    match args.get(index) {
+        None | Some(Value::Null) => Ok(default),
+        Some(value) => value
+            .as_i64()
+            .ok_or_else(|| format!("Expected an integer argument instead of {}.", value)),
+    }
+}
+
+/// The boolean argument `index` of a module, or `default` if it is absent.
+fn flag(args: &[Value], index: usize, default: bool) -> bool {
+    match args.get(index) {
+        Some(Value::Bool(value)) => *value,
+        _ => default,
+    }
+}
+
+impl Converter {
+    /// The number of output channels of a module declaring `channels`, scaled by the width.
+    fn channels(&self, channels: i64) -> i64 {
+        // Like `make_divisible`, the channels are rounded up to a multiple of 8.
+        let scaled = (channels as f64).min(self.max_channels) * self.width;
+        ((scaled / 8.0).ceil() * 8.0) as i64
+    }
+
+    /// A `Conv` module, i.e. a convolution whose default padding preserves the dimension.
+    #[allow(clippy::too_many_arguments)]
+    fn conv(
+        &mut self,
+        name: &str,
+        input: &str,
+        channels: i64,
+        kernel: i64,
+        stride: i64,
+        padding: Option<i64>,
+        dilation: i64,
+    ) -> Result<String, String> {
+        let filter_size = effective_filter_size(kernel, dilation);
+        let padding = padding.unwrap_or(filter_size / 2);
+        self.builder.layer(
+            &format!("{}.conv", name),
+            false,
+            input,
+            filter_size,
+            stride,
+            padding,
+            Some(channels),
+        )
+    }
+
+    /// A bottleneck of two convolutions with the filter sizes `kernels`, added to its input if `shortcut`.
+    fn bottleneck(
+        &mut self,
+        name: &str,
+        input: &str,
+        channels: i64,
+        kernels: (i64, i64),
+        shortcut: bool,
+    ) -> Result<String, String> {
+        let cv1 = self.conv(
+            &format!("{}.cv1", name),
+            input,
+            channels,
+            kernels.0,
+            1,
+            None,
+            1,
+        )?;
+        let cv2 = self.conv(
+            &format!("{}.cv2", name),
+            &cv1,
+            channels,
+            kernels.1,
+            1,
+            None,
+            1,
+        )?;
+        Ok(if shortcut {
+            self.builder
+                .merge(name, Op::Add, vec![input.to_string(), cv2])
+        } else {
+            cv2
+        })
+    }
+
+    /// Expand the layer `index` of type `module`, returning the node producing its output and its channels.
+    fn module(
+        &mut self,
+        index: usize,
+        module: &str,
+        repeats: i64,
+        args: &[Value],
+        inputs: &[(Option<String>, i64)],
+    ) -> Result<(Option<String>, i64), String> {
+        let name = format!("model.{}", index);
+        let spatial: Vec<String> = inputs.iter().filter_map(|(node, _)| node.clone()).collect();
+        let (input, c1) = match inputs.first() {
+            Some((Some(node), channels)) => (node.clone(), *channels),
+            _ if module == "Detect" || module == "Classify" => (String::new(), 0),
+            _ => {
+                return Err(format!(
+                    "Layer {} ({}) is not applied to a feature map.",
+                    index, module
+                ))
+            }
+        };
+        // The first argument of the modules that convolve is their number of output channels.
+        let c2 = match module {
+            "Conv" | "DWConv" | "Focus" | "C2f" | "C3k2" | "C3" | "SPPF" | "SPP"
+            | "ConvTranspose" | "ConvTranspose2d" => self.channels(arg(args, 0, c1)?),
+            _ => c1,
+        };
+
+        match module {
+            // Repeated modules are applied in sequence.
+            "Conv" | "DWConv" => {
+                let mut node = input;
+                for j in 0..repeats {
+                    let path = if repeats > 1 {
+                        format!("{}.{}", name, j)
+                    } else {
+                        name.clone()
+                    };
+                    node = self.conv(
+                        &path,
+                        &node,
+                        c2,
+                        arg(args, 1, 1)?,
+                        arg(args, 2, 1)?,
+                        args.get(3).and_then(Value::as_i64),
+                        arg(args, 5, 1)?,
+                    )?;
+                }
+                Ok((Some(node), c2))
+            }
+            // The slices of the pixels are stacked along the channels before the convolution.
+            "Focus" => {
+                let stacked = self
+                    .builder
+                    .layer(&name, false, &input, 2, 2, 0, Some(4 * c1))?;
+                let conv = self.conv(
+                    &name,
+                    &stacked,
+                    c2,
+                    arg(args, 1, 1)?,
+                    arg(args, 2, 1)?,
+                    args.get(3).and_then(Value::as_i64),
+                    1,
+                )?;
+                Ok((Some(conv), c2))
+            }
+            // The output of `cv1` is split into two halves, the second one of which is processed by
+            // the bottlenecks, and the outputs of all of them are concatenated. The split is
+            // represented by a 1x1 convolution producing the half of the channels.
+            "C2f" | "C3k2" => {
+                let (shortcut, expansion) = if module == "C2f" {
+                    (flag(args, 1, false), args.get(3))
+                } else {
+                    (true, args.get(2))
+                };
+                let expansion = expansion.and_then(Value::as_f64).unwrap_or(0.5);
+                let c = (c2 as f64 * expansion) as i64;
+                let cv1 = self.conv(&format!("{}.cv1", name), &input, 2 * c, 1, 1, None, 1)?;
+                let half = self.builder.layer(
+                    &format!("{}.chunk", name),
+                    false,
+                    &cv1,
+                    1,
+                    1,
+                    0,
+                    Some(c),
+                )?;
+                let mut outputs = vec![cv1];
+                let mut node = half;
+                for j in 0..repeats {
+                    let path = format!("{}.m.{}", name, j);
+                    node = self.bottleneck(&path, &node, c, (3, 3), shortcut)?;
+                    outputs.push(node.clone());
+                }
+                let cat = self
+                    .builder
+                    .merge(&format!("{}.cat", name), Op::Concat, outputs);
+                let cv2 = self.conv(&format!("{}.cv2", name), &cat, c2, 1, 1, None, 1)?;
+                Ok((Some(cv2), c2))
+            }
+            "C3" => {
+                let c = c2 / 2;
+                let cv1 = self.conv(&format!("{}.cv1", name), &input, c, 1, 1, None, 1)?;
+                let mut node = cv1;
+                for j in 0..repeats {
+                    let path = format!("{}.m.{}", name, j);
+                    node = self.bottleneck(&path, &node, c, (1, 3), flag(args, 1, true))?;
+                }
+                let cv2 = self.conv(&format!("{}.cv2", name), &input, c, 1, 1, None, 1)?;
+                let cat = self
+                    .builder
+                    .merge(&format!("{}.cat", name), Op::Concat, vec![node, cv2]);
+                let cv3 = self.conv(&format!("{}.cv3", name), &cat, c2, 1, 1, None, 1)?;
+                Ok((Some(cv3), c2))
+            }
+            // SPPF pools the output of `cv1` three times in a row, SPP once with every filter size.
+            "SPPF" | "SPP" => {
+                let c = c1 / 2;
+                let cv1 = self.conv(&format!("{}.cv1", name), &input, c, 1, 1, None, 1)?;
+                let kernels = if module == "SPPF" {
+                    vec![arg(args, 1, 5)?; 3]
+                } else {
+                    match args.get(1).and_then(Value::as_array) {
+                        Some(kernels) => kernels.iter().filter_map(Value::as_i64).collect(),
+                        None => vec![5, 9, 13],
+                    }
+                };
+                let mut outputs = vec![cv1.clone()];
+                for kernel in kernels {
+                    let pool_input = if module == "SPPF" {
+                        outputs[outputs.len() - 1].clone()
+                    } else {
+                        cv1.clone()
+                    };
+                    let pool = self.builder.layer(
+                        &format!("{}.m", name),
+                        false,
+                        &pool_input,
+                        kernel,
+                        1,
+                        kernel / 2,
+                        None,
+                    )?;
+                    outputs.push(pool);
+                }
+                let cat = self
+                    .builder
+                    .merge(&format!("{}.cat", name), Op::Concat, outputs);
+                let cv2 = self.conv(&format!("{}.cv2", name), &cat, c2, 1, 1, None, 1)?;
+                Ok((Some(cv2), c2))
+            }
+            "Upsample" => {
+                let scale = match args.get(1).and_then(Value::as_f64) {
+                    Some(scale) if scale.fract() == 0.0 && scale >= 1.0 => scale as i64,
+                    _ => {
+                        return Err(format!(
+                            "The scale factor of layer {} is not an integer.",
+                            index
+                        ))
+                    }
+                };
+                Ok((Some(self.builder.upsample(&name, &input, scale)?), c1))
+            }
+            "ConvTranspose" | "ConvTranspose2d" => {
+                let node = self.builder.layer(
+                    &name,
+                    true,
+                    &input,
+                    arg(args, 1, 2)?,
+                    arg(args, 2, 2)?,
+                    arg(args, 3, 0)?,
+                    Some(c2),
+                )?;
+                Ok((Some(node), c2))
+            }
+            "MaxPool2d" => {
+                let kernel = arg(args, 0, 1)?;
+                let node = self.builder.layer(
+                    &name,
+                    false,
+                    &input,
+                    kernel,
+                    arg(args, 1, kernel)?,
+                    arg(args, 2, 0)?,
+                    None,
+                )?;
+                Ok((Some(node), c1))
+            }
+            "Concat" => {
+                let channels = inputs.iter().map(|(_, channels)| channels).sum();
+                let node = self.builder.merge(&name, Op::Concat, spatial);
+                Ok((Some(node), channels))
+            }
+            // The detection heads predict on the grid of each of their inputs.
+            "Detect" | "Segment" | "Pose" | "OBB" | "v10Detect" => {
+                for node in spatial {
+                    self.builder.output(&node);
+                }
+                Ok((None, 0))
+            }
+            "Classify" => Ok((None, 0)),
+            _ => {
+                self.builder.warn(format!(
+                    "The module {} of layer {} is not supported and assumed to leave the dimension unchanged.",
+                    module, index
+                ));
+                Ok((Some(input), c1))
+            }
+        }
+    }
+}
+
+/// The largest number of repeats of a module, after scaling by the depth multiple. The models of
+/// Ultralytics repeat their modules at most a few times.
+const MAX_REPEATS: i64 = 1000;
+
+/// ## Import the Ultralytics YOLO model file `text`, with the model `scale` (e.g. `n` or `s`).
+pub fn import(text: &str, scale: Option<char>) -> Result<Imported, String> {
+    let yaml = parse(text)?;
+    let mut builder = Builder::default();
+
+    // YOLOv8 and later list the multipliers of every scale, YOLOv5 the ones of a single scale.
+    let number = |key: &str| yaml.get(key).and_then(Value::as_f64);
+    let (mut depth, mut width, mut max_channels) = (
+        number("depth_multiple").unwrap_or(1.0),
+        number("width_multiple").unwrap_or(1.0),
+        f64::INFINITY,
+    );
+    if let Some(Value::Object(scales)) = yaml.get("scales") {
+        let (name, multipliers) = match scale {
+            Some(scale) => match scales
+                .iter()
+                .find(|(name, _)| name.as_str() == scale.to_string())
+            {
+                Some(entry) => entry,
+                None => {
+                    let names: Vec<&str> = scales.iter().map(|(name, _)| name.as_str()).collect();
+                    return Err(format!(
+                        "The model has no scale '{}', expected one of: {}.",
+                        scale,
+                        names.join(", ")
+                    ));
+                }
+            },
+            None => match scales.iter().next() {
+                Some(entry) => {
+                    builder.warn(format!("No model scale given, assuming '{}'.", entry.0));
+                    entry
+                }
+                None => return Err("The model lists no scales.".to_string()),
+            },
+        };
+        let values: Vec<f64> = match multipliers {
+            Value::Array(values) => values.iter().filter_map(Value::as_f64).collect(),
+            _ => vec![],
+        };
+        if values.len() != 3 {
+            return Err(format!(
+                "The scale '{}' does not list depth, width and max_channels.",
+                name
+            ));
+        }
+        depth = values[0];
+        width = values[1];
+        max_channels = values[2];
+    }
+
+    let channels = number("ch").map_or(3, |channels| channels as i64);
+    let input = builder.input("images", &[None], Some(channels))?;
+    let mut converter = Converter {
+        builder,
+        width,
+        max_channels,
+        layers: vec![],
+    };
+
+    let mut layers = vec![];
+    for section in ["backbone", "head"] {
+        match yaml.get(section).and_then(Value::as_array) {
+            Some(section) => layers.extend(section),
+            None => return Err(format!("The model file lacks the '{}' section.", section)),
+        }
+    }
+    for (index, layer) in layers.into_iter().enumerate() {
+        let fields = match layer.as_array() {
+            Some(fields) if fields.len() == 4 => fields,
+            _ => {
+                return Err(format!(
+                    "Layer {} is not a [from, repeats, module, args] list.",
+                    index
+                ))
+            }
+        };
+        let from: Vec<i64> = match &fields[0] {
+            Value::Array(from) => from.iter().filter_map(Value::as_i64).collect(),
+            from => from.as_i64().into_iter().collect(),
+        };
+        let mut inputs = vec![];
+        for from in from {
+            let source = if from < 0 { index as i64 + from } else { from };
+            inputs.push(match source {
+                -1 => (Some(input.clone()), channels),
+                _ if source >= 0 && (source as usize) < index => {
+                    converter.layers[source as usize].clone()
+                }
+                _ => {
+                    return Err(format!(
+                        "Layer {} takes its input from the layer {}, which does not precede it.",
+                        index, from
+                    ))
+                }
+            });
+        }
+        let module = match fields[2].as_str() {
+            // Modules of PyTorch are given with their module, e.g. `nn.Upsample`.
+            Some(module) => module.rsplit('.').next().unwrap_or(module),
+            None => return Err(format!("The module of layer {} is not a name.", index)),
+        };
+        let repeats = fields[1].as_i64().unwrap_or(1);
+        let repeats = if repeats > 1 {
+            ((repeats as f64 * depth).round() as i64).max(1)
+        } else {
+            repeats
+        };
+        if repeats > MAX_REPEATS {
+            return Err(format!(
+                "Layer {} repeats its module {} times, more than the {} supported.",
+                index, repeats, MAX_REPEATS
+            ));
+        }
+        // Arguments may refer to the top-level values, e.g. the number of classes `nc`.
+        let args: Vec<Value> = fields[3]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(|arg| match arg.as_str().and_then(|name| yaml.get(name)) {
+                Some(value) => value.clone(),
+                None => arg.clone(),
+            })
+            .collect();
+        let layer = converter.module(index, module, repeats, &args, &inputs)?;
+        converter.layers.push(layer);
+    }
+    converter.builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YOLOV8: &str = r#"
+# Ultralytics YOLOv8 object detection model with P3-P5 outputs.
+nc: 80 # number of classes
+scales: # model compound scaling constants
+  # [depth, width, max_channels]
+  n: [0.33, 0.25, 1024]
+  s: [0.33, 0.50, 1024]
+
+backbone:
+  # [from, repeats, module, args]
+  - [-1, 1, Conv, [64, 3, 2]] # 0-P1/2
+  - [-1, 1, Conv, [128, 3, 2]] # 1-P2/4
+  - [-1, 3, C2f, [128, True]]
+  - [-1, 1, Conv, [256, 3, 2]] # 3-P3/8
+  - [-1, 6, C2f, [256, True]]
+  - [-1, 1, Conv, [512, 3, 2]] # 5-P4/16
+  - [-1, 6, C2f, [512, True]]
+  - [-1, 1, Conv, [1024, 3, 2]] # 7-P5/32
+  - [-1, 3, C2f, [1024, True]]
+  - [-1, 1, SPPF, [1024, 5]] # 9
+
+head:
+  - [-1, 1, nn.Upsample, [None, 2, "nearest"]]
+  - [[-1, 6], 1, Concat, [1]] # cat backbone P4
+  - [-1, 3, C2f, [512]] # 12
+
+  - [-1, 1, nn.Upsample, [None, 2, "nearest"]]
+  - [[-1, 4], 1, Concat, [1]] # cat backbone P3
+  - [-1, 3, C2f, [256]] # 15 (P3/8-small)
+
+  - [-1, 1, Conv, [256, 3, 2]]
+  - [[-1, 12], 1, Concat, [1]] # cat head P4
+  - [-1, 3, C2f, [512]] # 18 (P4/16-medium)
+
+  - [-1, 1, Conv, [512, 3, 2]]
+  - [[-1, 9], 1, Concat, [1]] # cat head P5
+  - [-1, 3, C2f, [1024]] # 21 (P5/32-large)
+
+  - [[15, 18, 21], 1, Detect, [nc]] # Detect(P3, P4, P5)
+"#;
+
+    #[test]
+    fn test_yolov8() {
+        let imported = import(YOLOV8, Some('s')).unwrap();
+        assert!(imported.warnings.is_empty());
+        let graph = imported.graph;
+        assert_eq!(
+            graph.outputs,
+            vec![
+                "model.15.cv2.conv",
+                "model.18.cv2.conv",
+                "model.21.cv2.conv"
+            ]
+        );
+        let grids: Vec<u32> = graph
+            .output_shapes(640)
+            .unwrap()
+            .iter()
+            .map(|(_, shape)| shape.dim)
+            .collect();
+        assert_eq!(grids, vec![80, 40, 20]);
+        // The width of the 's' scale halves the channels, and the depth reduces 6 repeats to 2.
+        let node = |name: &str| graph.nodes.iter().find(|node| node.name == name).unwrap();
+        assert_eq!(node("model.0.conv").channels, Some(32));
+        assert_eq!(node("model.4.cat").inputs.len(), 3);
+        assert_eq!(node("model.9.cv2.conv").channels, Some(512));
+
+        let imported = import(YOLOV8, None).unwrap();
+        assert_eq!(
+            imported.warnings,
+            vec!["No model scale given, assuming 'n'."]
+        );
+    }
+
+    #[test]
+    fn test_yolov5_flow_style() {
+        let yolov5 = "
+depth_multiple: 0.33  # model depth multiple
+width_multiple: 0.50  # layer channel multiple
+anchors:
+  - [10,13, 16,30, 33,23]  # P3/8
+backbone:
+  [[-1, 1, Conv, [64, 6, 2, 2]],  # 0-P1/2
+   [-1, 1, Conv, [128, 3, 2]],  # 1-P2/4
+   [-1, 3, C3, [128]],
+   [-1, 1, SPPF, [128, 5]],  # 3
+  ]
+head:
+  [[-1, 1, nn.Upsample, [None, 2, 'nearest']],
+   [[-1, 0], 1, Concat, [1]],
+   [[3, 5], 1, Detect, [nc, anchors]],
+  ]
+";
+        let graph = import(yolov5, None).unwrap().graph;
+        assert_eq!(graph.nodes[0].padding, 2);
+        assert_eq!(graph.output_shapes(640).unwrap()[0].1.dim, 160);
+        assert_eq!(graph.output_shapes(640).unwrap()[1].1.dim, 320);
+        assert!(import("backbone:\n  - [-1, 1, Conv, [64, 3, 2]]\n", None)
+            .unwrap_err()
+            .contains("'head'"));
+        assert_eq!(
+            import(&yolov5.replace("[-1, 3, C3", "[-1, 100000000, C3"), None).unwrap_err(),
+            "Layer 2 repeats its module 33000000 times, more than the 1000 supported."
+        );
+    }
+
+    #[test]
+    fn test_scale_from_path() {
+        assert_eq!(scale_from_path(Path::new("models/yolov8s.yaml")), Some('s'));
+        assert_eq!(scale_from_path(Path::new("yolo11n.yaml")), Some('n'));
+        assert_eq!(scale_from_path(Path::new("yolov8.yaml")), None);
+    }
+}
@@ -0,0 +1,278 @@
+//! Starter descriptions of networks, e.g. for new users who need an example file to begin with.
+//!
+//! Every template is a complete description in the toml format, with a comment on every field,
+//! that can be evaluated as it is and then adapted: a plain CNN classifier backbone and a
+//! convolutional autoencoder as lists of layers, and a small U-Net as a graph of nodes.
+use std::str::FromStr;
+
+use crate::error::ConvDimError;
+
+/// A plain CNN, which halves its input four times.
+const CNN: &str = r#"# A plain CNN backbone, e.g. for image classification.
+# Evaluate it with: convdim --toml cnn.toml --input-dim 224
+
+# The channels of the input, e.g. 3 for RGB images. Optional, but needed for the parameters and FLOPs.
+input_channels = 3
+
+# Every [[layers]] entry is applied to the output of the previous one.
+[[layers]]
+# The name shown in the traces and summaries. Optional.
+name = "stem"
+# The kind of layer: conv, conv_transpose, max_pool, upsample, formula, batchnorm, relu, dropout or identity.
+type = "conv"
+# The size of the (square) filter.
+filter_size = 7
+# The step of the filter. Optional, defaults to 1.
+stride = 2
+# The zeros added on both sides of the input. Optional, defaults to 0.
+padding = 3
+# The number of output channels. Optional, keeps the channels of the input if omitted.
+channels = 64
+
+[[layers]]
+# Normalization and activations keep the shape of their input.
+type = "batchnorm"
+
+[[layers]]
+type = "relu"
+
+[[layers]]
+# Max pooling requires its filter size and stride.
+type = "max_pool"
+filter_size = 3
+stride = 2
+padding = 1
+
+[[layers]]
+name = "conv2"
+type = "conv"
+filter_size = 3
+stride = 2
+padding = 1
+channels = 128
+
+[[layers]]
+name = "conv3"
+type = "conv"
+filter_size = 3
+stride = 2
+padding = 1
+channels = 256
+"#;
+
+/// A convolutional autoencoder, whose decoder restores the dimension of the input.
+const AUTOENCODER: &str = r#"# A convolutional autoencoder, whose decoder restores the dimension of the input.
+# Evaluate it with: convdim --toml autoencoder.toml --input-dim 64
+
+# The channels of the input, e.g. 1 for grayscale images. Optional.
+input_channels = 1
+
+# Layers marked in the outputs are reported besides the last one, e.g. the code of the bottleneck.
+# Optional, every layer is named after its position (layer1, layer2, ...) unless it has a name.
+outputs = ["code"]
+
+# --- Encoder ---
+
+[[layers]]
+# The kind of layer: conv, conv_transpose, max_pool, upsample, formula, batchnorm, relu, dropout or identity.
+type = "conv"
+# The size of the (square) filter.
+filter_size = 3
+# The step of the filter, which halves the dimension here. Optional, defaults to 1.
+stride = 2
+# The zeros added on both sides of the input. Optional, defaults to 0.
+padding = 1
+# The number of output channels. Optional, keeps the channels of the input if omitted.
+channels = 16
+
+[[layers]]
+name = "code"
+type = "conv"
+filter_size = 3
+stride = 2
+padding = 1
+channels = 32
+
+# --- Decoder ---
+
+[[layers]]
+# A transposed convolution multiplies the dimension by its stride.
+type = "conv_transpose"
+filter_size = 2
+stride = 2
+channels = 16
+
+[[layers]]
+# Upsampling multiplies the dimension by its scale and keeps the channels.
+type = "upsample"
+scale = 2
+
+[[layers]]
+# A final convolution back to the channels of the input, which keeps the dimension.
+type = "conv"
+filter_size = 3
+padding = 1
+channels = 1
+"#;
+
+/// A small U-Net, whose skip connections concatenate the encoder and decoder feature maps.
+const UNET: &str = r#"# A small U-Net with two levels, whose skip connections need the input to be a multiple of 4.
+# Evaluate it with: convdim --toml unet.toml --input-dim 128
+# Check its skip connections with: convdim skips --toml unet.toml --input-dim 128
+
+# The channels of the input, e.g. 3 for RGB images. Optional.
+input_channels = 3
+
+# Every [[nodes]] entry is a named operation. It takes its input from the node defined before it,
+# or from the nodes listed in its inputs, where the network input is called "input".
+[[nodes]]
+# The name by which other nodes refer to this one.
+name = "enc1"
+# The operation: conv, transposed, add, concat, batchnorm, relu, dropout, identity or subnetwork.
+op = "conv"
+# The size of the (square) filter.
+filter_size = 3
+# The step of the filter. Optional, defaults to 1.
+stride = 1
+# The zeros added on both sides of the input, which keep the dimension here. Optional, defaults to 0.
+padding = 1
+# The number of output channels. Optional, keeps the channels of the input if omitted.
+channels = 32
+
+[[nodes]]
+name = "down1"
+op = "conv"
+filter_size = 2
+stride = 2
+channels = 64
+
+[[nodes]]
+name = "enc2"
+op = "conv"
+filter_size = 3
+padding = 1
+channels = 64
+
+[[nodes]]
+name = "down2"
+op = "conv"
+filter_size = 2
+stride = 2
+channels = 128
+
+[[nodes]]
+name = "bottleneck"
+op = "conv"
+filter_size = 3
+padding = 1
+channels = 128
+
+[[nodes]]
+# A transposed convolution doubling the dimension again.
+name = "up2"
+op = "transposed"
+filter_size = 2
+stride = 2
+channels = 64
+
+[[nodes]]
+# The skip connection: the inputs need the same dimension, and their channels add up.
+name = "skip2"
+op = "concat"
+inputs = ["enc2", "up2"]
+
+[[nodes]]
+name = "dec2"
+op = "conv"
+filter_size = 3
+padding = 1
+channels = 64
+
+[[nodes]]
+name = "up1"
+op = "transposed"
+filter_size = 2
+stride = 2
+channels = 32
+
+[[nodes]]
+name = "skip1"
+op = "concat"
+inputs = ["enc1", "up1"]
+
+[[nodes]]
+# The output of the network is the output of the last node.
+name = "head"
+op = "conv"
+filter_size = 1
+channels = 2
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## A starter description of a network.
+pub enum Template {
+    Cnn,
+    Autoencoder,
+    Unet,
+}
+
+impl FromStr for Template {
+    type Err = ConvDimError;
+
+    fn from_str(text: &str) -> Result<Template, ConvDimError> {
+        match text {
+            "cnn" => Ok(Template::Cnn),
+            "autoencoder" => Ok(Template::Autoencoder),
+            "unet" => Ok(Template::Unet),
+            _ => Err(ConvDimError::Init {
+                message: format!(
+                    "'{}' is not one of the templates 'cnn', 'autoencoder' and 'unet'.",
+                    text
+                ),
+            }),
+        }
+    }
+}
+
+impl Template {
+    /// ## The description of the template in the toml format, with a comment on every field.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use convdim::init::Template;
+    /// # use convdim::architecture::parse_architecture;
+    /// let template: Template = "autoencoder".parse().unwrap();
+    /// let graph = parse_architecture(template.toml()).unwrap().into_graph();
+    /// assert_eq!(graph.output_shape(64).unwrap().dim, 64);
+    /// ```
+    pub fn toml(self) -> &'static str {
+        match self {
+            Template::Cnn => CNN,
+            Template::Autoencoder => AUTOENCODER,
+            Template::Unet => UNET,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+    use crate::schema;
+
+    #[test]
+    fn test_templates() {
+        for (template, in_dim, out_dim) in [
+            (Template::Cnn, 224, 14),
+            (Template::Autoencoder, 64, 64),
+            (Template::Unet, 128, 128),
+        ] {
+            let value: toml::Value = template.toml().parse().unwrap();
+            assert!(schema::check(&value, Some(template.toml())).is_empty());
+            let graph = parse_architecture(template.toml()).unwrap().into_graph();
+            assert_eq!(graph.output_shape(in_dim).unwrap().dim, out_dim);
+        }
+        assert!("resnet".parse::<Template>().is_err());
+    }
+}
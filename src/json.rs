@@ -0,0 +1,71 @@
+//! The parsing of JSON documents with [serde_json].
+//!
+//! The members of objects are kept in the order in which they appear in the document. Arrays and
+//! objects nest at most 127 levels deep, below the recursion limit of `serde_json`, so that a
+//! hostile document cannot overflow the stack.
+use serde_json::Value;
+
+/// ## Parse the JSON document `text`, with an error naming the line and column of the problem.
+pub fn parse_json(text: &str) -> Result<Value, String> {
+    serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}.", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The number of arrays and objects a value may be nested in.
+    const MAX_DEPTH: usize = 127;
+
+    #[test]
+    fn test_parse() {
+        let json = parse_json(
+            r#"{"class_name": "Conv2D", "config": {"kernel_size": [3, 3], "use_bias": true,
+                "bias": null, "scale": -1.5e2, "name": "convé\n"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            json.get("class_name").and_then(Value::as_str),
+            Some("Conv2D")
+        );
+        let config = json.get("config").unwrap();
+        let kernel: Vec<i64> = config
+            .get("kernel_size")
+            .and_then(Value::as_array)
+            .unwrap()
+            .iter()
+            .filter_map(Value::as_i64)
+            .collect();
+        assert_eq!(kernel, vec![3, 3]);
+        assert_eq!(config.get("use_bias"), Some(&Value::Bool(true)));
+        assert_eq!(config.get("bias"), Some(&Value::Null));
+        assert_eq!(config.get("scale").and_then(Value::as_f64), Some(-150.0));
+        assert_eq!(config.get("name").and_then(Value::as_str), Some("convé\n"));
+        let keys: Vec<&String> = config.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["kernel_size", "use_bias", "bias", "scale", "name"]);
+    }
+
+    #[test]
+    fn test_errors_report_the_position() {
+        assert_eq!(
+            parse_json("{\n  \"a\": [1, 2,]\n}"),
+            Err("Invalid JSON: trailing comma at line 2 column 14.".to_string())
+        );
+        assert!(parse_json("[1, 2] 3").is_err());
+        assert!(parse_json("\"unterminated").is_err());
+    }
+
+    #[test]
+    fn test_nesting_is_limited() {
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(parse_json(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            parse_json(&nested(MAX_DEPTH + 1)),
+            Err(format!(
+                "Invalid JSON: recursion limit exceeded at line 1 column {}.",
+                MAX_DEPTH + 1
+            ))
+        );
+        assert!(parse_json(&"[{\"a\": ".repeat(100_000)).is_err());
+    }
+}
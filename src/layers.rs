@@ -0,0 +1,1459 @@
+//! Networks described as a list of successive layers, and the arithmetic of their dimensions.
+//!
+//! The dimension of the output of a (transposed) convolutional layer is computed from the dimension
+//! of its input, the size of its filter, its stride and its padding. A list of layers may contain
+//! blocks of `parallel` branches, whose outputs are concatenated along the channels.
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dimension::Dimension;
+use crate::error::ConvDimError;
+use crate::formula::Formula;
+use crate::plugin::{self, Geometry, Params};
+
+fn default_stride() -> u16 {
+    1
+}
+
+fn default_filter_size() -> u16 {
+    1
+}
+
+fn is_false(flag: &bool) -> bool {
+    !flag
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+/// ## The parameters of a convolutional layer.
+///
+/// The layer is defined by its `filter_size`, the `padding` that is applied to the input before
+/// application of the filter and the `stride` with which the filter moves across the input.
+/// The number of output `channels` is needed to export the network; without it, the layer
+/// keeps the channels of its input like a pooling layer.
+pub struct ConvParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub filter_size: u16,
+    #[serde(default = "default_stride")]
+    pub stride: u16,
+    #[serde(default)]
+    pub padding: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u16>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+/// ## The parameters of a transposed convolutional layer.
+///
+/// The `padding` is cropped off the output of the layer.
+pub struct ConvTransposeParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub filter_size: u16,
+    #[serde(default = "default_stride")]
+    pub stride: u16,
+    #[serde(default)]
+    pub padding: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u16>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+/// ## The parameters of a max pooling layer, which keeps the channels of its input.
+pub struct PoolParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub filter_size: u16,
+    pub stride: u16,
+    #[serde(default)]
+    pub padding: u16,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+/// ## The parameters of an upsampling layer, which repeats every value `scale` times.
+pub struct UpsampleParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub scale: u16,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+/// ## The parameters of a layer whose output dimension is given by a [`formula`](crate::formula).
+///
+/// The `formula` refers to the input dimension `n` and to the `filter_size` (`f`), `stride` (`s`)
+/// and `padding` (`p`) of the layer. These describe the layer as a (`transposed`) convolution in
+/// the analyses that do not evaluate the dimensions, e.g. of the skip connections, so they should
+/// approximate the operation; by default, the layer is a convolution with a 1x1 filter.
+pub struct FormulaParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub formula: Formula,
+    #[serde(default = "default_filter_size")]
+    pub filter_size: u16,
+    #[serde(default = "default_stride")]
+    pub stride: u16,
+    #[serde(default)]
+    pub padding: u16,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub transposed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u16>,
+    /// Whether the layer is a pooling rounding up, which slides to a last partial window, e.g. in
+    /// the Caffe [convention](crate::convention). The formula gives its dimension regardless.
+    #[serde(skip)]
+    pub ceil_mode: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+/// ## A kind of layer that keeps the shape of its input, like the normalizations and activations.
+pub enum PassThrough {
+    /// A batch normalization, which has a scale and a shift per channel.
+    BatchNorm,
+    Relu,
+    Dropout,
+    Identity,
+}
+
+impl PassThrough {
+    /// The `type` of the layer in the configuration, e.g. `batchnorm`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PassThrough::BatchNorm => "batchnorm",
+            PassThrough::Relu => "relu",
+            PassThrough::Dropout => "dropout",
+            PassThrough::Identity => "identity",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+/// The parameters of a pass-through layer as they are written in the configuration.
+struct NamedParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## The parameters of a layer that keeps the shape of its input, e.g. a `relu`.
+///
+/// Such layers don't change the dimensions, but are shown in the traces, and the scales and shifts
+/// of batch normalizations are counted among the parameters.
+pub struct PassThroughParams {
+    pub name: Option<String>,
+    pub kind: PassThrough,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// ## The parameters of a layer of a custom type, registered as a [`plugin`](crate::plugin).
+///
+/// The `geometry` of the layer is computed from its `params` when the layer is created. If the
+/// layer has a `channels` parameter, it is the number of its output channels.
+pub struct CustomParams {
+    pub name: Option<String>,
+    pub kind: String,
+    pub params: Params,
+    pub geometry: Geometry,
+}
+
+impl CustomParams {
+    /// ## A layer of the registered type `kind` with the parameters `params`.
+    ///
+    /// Returns an error if the type is not registered or rejects the parameters.
+    pub fn new(
+        name: Option<String>,
+        kind: &str,
+        params: Params,
+    ) -> Result<CustomParams, ConvDimError> {
+        let geometry = plugin::geometry(kind, &params)?;
+        Ok(CustomParams {
+            name,
+            kind: kind.to_string(),
+            params,
+            geometry,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "RawLayer", into = "RawLayer")]
+/// ## A layer of a list of layers.
+///
+/// In the configuration, the variant is selected by the `type` of the layer, i.e. `conv`,
+/// `conv_transpose`, `max_pool`, `upsample` or `formula`, the pass-through types `batchnorm`,
+/// `relu`, `dropout` and `identity`, or a type registered as a [`plugin`](crate::plugin),
+/// next to its parameters. Layers without a `type` are (transposed) convolutions, depending on
+/// their `transposed` flag.
+///
+/// Optionally, the layer can be given a `name` by which it can be referred to. Layers are always
+/// serialized with their `type`.
+pub enum Layer {
+    Conv(ConvParams),
+    ConvTranspose(ConvTransposeParams),
+    MaxPool(PoolParams),
+    Upsample(UpsampleParams),
+    Formula(FormulaParams),
+    PassThrough(PassThroughParams),
+    Custom(CustomParams),
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// A layer as it is written in the configuration with its `type`.
+enum TypedLayer {
+    Conv(ConvParams),
+    ConvTranspose(ConvTransposeParams),
+    MaxPool(PoolParams),
+    Upsample(UpsampleParams),
+    Formula(FormulaParams),
+    #[serde(rename = "batchnorm")]
+    BatchNorm(NamedParams),
+    Relu(NamedParams),
+    Dropout(NamedParams),
+    Identity(NamedParams),
+}
+
+#[derive(Deserialize, Serialize)]
+/// A layer in the format predating the types, in which all layers are (transposed) convolutions.
+struct UntypedLayer {
+    name: Option<String>,
+    filter_size: u16,
+    stride: u16,
+    padding: u16,
+    transposed: bool,
+    channels: Option<u16>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+/// A layer as it is written in the configuration, either with a built-in `type`, in the format
+/// predating the types, or with a custom type.
+enum RawLayer {
+    Typed(TypedLayer),
+    Untyped(UntypedLayer),
+    Custom(Params),
+}
+
+impl TryFrom<RawLayer> for Layer {
+    type Error = String;
+
+    fn try_from(raw: RawLayer) -> Result<Layer, String> {
+        let layer = match raw {
+            RawLayer::Typed(TypedLayer::Conv(params)) => Layer::Conv(params),
+            RawLayer::Typed(TypedLayer::ConvTranspose(params)) => Layer::ConvTranspose(params),
+            RawLayer::Typed(TypedLayer::MaxPool(params)) => Layer::MaxPool(params),
+            RawLayer::Typed(TypedLayer::Upsample(params)) => Layer::Upsample(params),
+            RawLayer::Typed(TypedLayer::Formula(params)) => Layer::Formula(params),
+            RawLayer::Typed(TypedLayer::BatchNorm(NamedParams { name })) => {
+                Layer::PassThrough(PassThroughParams {
+                    name,
+                    kind: PassThrough::BatchNorm,
+                })
+            }
+            RawLayer::Typed(TypedLayer::Relu(NamedParams { name })) => {
+                Layer::PassThrough(PassThroughParams {
+                    name,
+                    kind: PassThrough::Relu,
+                })
+            }
+            RawLayer::Typed(TypedLayer::Dropout(NamedParams { name })) => {
+                Layer::PassThrough(PassThroughParams {
+                    name,
+                    kind: PassThrough::Dropout,
+                })
+            }
+            RawLayer::Typed(TypedLayer::Identity(NamedParams { name })) => {
+                Layer::PassThrough(PassThroughParams {
+                    name,
+                    kind: PassThrough::Identity,
+                })
+            }
+            RawLayer::Untyped(UntypedLayer {
+                name,
+                filter_size,
+                stride,
+                padding,
+                transposed: false,
+                channels,
+            }) => Layer::Conv(ConvParams {
+                name,
+                filter_size,
+                stride,
+                padding,
+                channels,
+            }),
+            RawLayer::Untyped(UntypedLayer {
+                name,
+                filter_size,
+                stride,
+                padding,
+                transposed: true,
+                channels,
+            }) => Layer::ConvTranspose(ConvTransposeParams {
+                name,
+                filter_size,
+                stride,
+                padding,
+                channels,
+            }),
+            RawLayer::Custom(mut params) => {
+                let kind = match params.remove("type") {
+                    Some(toml::Value::String(kind)) if !plugin::BUILTIN_TYPES.contains(&&*kind) => {
+                        kind
+                    }
+                    // The layer did not match its built-in type (or the untyped format), whose
+                    // error is more helpful than that of the custom types.
+                    Some(kind) => {
+                        params.insert("type".to_string(), kind);
+                        return Err(TypedLayer::deserialize(toml::Value::Table(params))
+                            .err()
+                            .map_or_else(|| "invalid layer type".to_string(), |e| e.to_string()));
+                    }
+                    None => {
+                        return Err(UntypedLayer::deserialize(toml::Value::Table(params))
+                            .err()
+                            .map_or_else(|| "invalid layer".to_string(), |e| e.to_string()))
+                    }
+                };
+                let name = match params.remove("name") {
+                    Some(toml::Value::String(name)) => Some(name),
+                    Some(_) => return Err("the 'name' of a layer needs to be a string".to_string()),
+                    None => None,
+                };
+                Layer::Custom(CustomParams::new(name, &kind, params).map_err(|e| e.to_string())?)
+            }
+        };
+        Ok(layer)
+    }
+}
+
+impl From<Layer> for RawLayer {
+    fn from(layer: Layer) -> RawLayer {
+        match layer {
+            Layer::Conv(params) => RawLayer::Typed(TypedLayer::Conv(params)),
+            Layer::ConvTranspose(params) => RawLayer::Typed(TypedLayer::ConvTranspose(params)),
+            Layer::MaxPool(params) => RawLayer::Typed(TypedLayer::MaxPool(params)),
+            Layer::Upsample(params) => RawLayer::Typed(TypedLayer::Upsample(params)),
+            Layer::Formula(params) => RawLayer::Typed(TypedLayer::Formula(params)),
+            Layer::PassThrough(PassThroughParams { name, kind }) => {
+                let params = NamedParams { name };
+                RawLayer::Typed(match kind {
+                    PassThrough::BatchNorm => TypedLayer::BatchNorm(params),
+                    PassThrough::Relu => TypedLayer::Relu(params),
+                    PassThrough::Dropout => TypedLayer::Dropout(params),
+                    PassThrough::Identity => TypedLayer::Identity(params),
+                })
+            }
+            Layer::Custom(CustomParams {
+                name, kind, params, ..
+            }) => {
+                let mut table = params;
+                table.insert("type".to_string(), toml::Value::String(kind));
+                if let Some(name) = name {
+                    table.insert("name".to_string(), toml::Value::String(name));
+                }
+                RawLayer::Custom(table)
+            }
+        }
+    }
+}
+
+impl Layer {
+    /// The name of the layer, if it has one.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Layer::Conv(ConvParams { name, .. })
+            | Layer::ConvTranspose(ConvTransposeParams { name, .. })
+            | Layer::MaxPool(PoolParams { name, .. })
+            | Layer::Upsample(UpsampleParams { name, .. })
+            | Layer::Formula(FormulaParams { name, .. })
+            | Layer::PassThrough(PassThroughParams { name, .. })
+            | Layer::Custom(CustomParams { name, .. }) => name.as_deref(),
+        }
+    }
+
+    /// The `type` of the layer in the configuration, e.g. `conv` or `max_pool`.
+    pub fn kind(&self) -> &str {
+        match self {
+            Layer::Conv(_) => "conv",
+            Layer::ConvTranspose(_) => "conv_transpose",
+            Layer::MaxPool(_) => "max_pool",
+            Layer::Upsample(_) => "upsample",
+            Layer::Formula(_) => "formula",
+            Layer::PassThrough(PassThroughParams { kind, .. }) => kind.kind(),
+            Layer::Custom(CustomParams { kind, .. }) => kind,
+        }
+    }
+
+    /// Whether the layer increases the dimension like a transposed convolution.
+    pub fn is_transposed(&self) -> bool {
+        match self {
+            Layer::ConvTranspose(_) | Layer::Upsample(_) => true,
+            Layer::Formula(FormulaParams { transposed, .. }) => *transposed,
+            Layer::Custom(CustomParams { geometry, .. }) => geometry.transposed,
+            Layer::Conv(_) | Layer::MaxPool(_) | Layer::PassThrough(_) => false,
+        }
+    }
+
+    /// The `(filter_size, stride, padding)` of the (transposed) convolution equivalent to the layer.
+    ///
+    /// Upsampling by `scale` is equivalent to a transposed convolution whose filter size and
+    /// stride are the `scale`, and pass-through layers to a convolution with a 1x1 filter.
+    pub fn geometry(&self) -> (u16, u16, u16) {
+        match self {
+            Layer::Conv(ConvParams {
+                filter_size,
+                stride,
+                padding,
+                ..
+            })
+            | Layer::ConvTranspose(ConvTransposeParams {
+                filter_size,
+                stride,
+                padding,
+                ..
+            })
+            | Layer::MaxPool(PoolParams {
+                filter_size,
+                stride,
+                padding,
+                ..
+            })
+            | Layer::Formula(FormulaParams {
+                filter_size,
+                stride,
+                padding,
+                ..
+            }) => (*filter_size, *stride, *padding),
+            Layer::Upsample(UpsampleParams { scale, .. }) => (*scale, *scale, 0),
+            Layer::PassThrough(_) => (1, 1, 0),
+            Layer::Custom(CustomParams { geometry, .. }) => {
+                (geometry.filter_size, geometry.stride, geometry.padding)
+            }
+        }
+    }
+
+    /// The parameters of the layer as text, e.g. `conv, filter size 3, stride 2, padding 1`.
+    pub fn params(&self) -> String {
+        let (filter_size, stride, padding) = self.geometry();
+        let geometry = format!(
+            "filter size {}, stride {}, padding {}",
+            filter_size, stride, padding
+        );
+        match self {
+            Layer::Upsample(UpsampleParams { scale, .. }) => format!("upsample, scale {}", scale),
+            Layer::PassThrough(_) => self.kind().to_string(),
+            Layer::Formula(FormulaParams { formula, .. }) => {
+                format!("formula '{}', {}", formula, geometry)
+            }
+            _ => format!("{}, {}", self.kind(), geometry),
+        }
+    }
+
+    /// The kind of the layer if it keeps the shape of its input, e.g. a `relu`.
+    pub fn pass_through(&self) -> Option<PassThrough> {
+        match self {
+            Layer::PassThrough(PassThroughParams { kind, .. }) => Some(*kind),
+            _ => None,
+        }
+    }
+
+    /// The formula giving the output dimension of the layer, if it has one.
+    pub fn formula(&self) -> Option<&Formula> {
+        match self {
+            Layer::Formula(FormulaParams { formula, .. }) => Some(formula),
+            _ => None,
+        }
+    }
+
+    /// Whether the layer is a pooling in ceil mode, see [`FormulaParams::ceil_mode`].
+    pub fn ceil_mode(&self) -> bool {
+        match self {
+            Layer::Formula(FormulaParams { ceil_mode, .. }) => *ceil_mode,
+            _ => false,
+        }
+    }
+
+    /// The number of output channels, if the layer declares them.
+    pub fn channels(&self) -> Option<u16> {
+        match self {
+            Layer::Conv(ConvParams { channels, .. })
+            | Layer::ConvTranspose(ConvTransposeParams { channels, .. })
+            | Layer::Formula(FormulaParams { channels, .. }) => *channels,
+            Layer::MaxPool(_) | Layer::Upsample(_) | Layer::PassThrough(_) => None,
+            Layer::Custom(CustomParams { params, .. }) => params
+                .get("channels")
+                .and_then(toml::Value::as_integer)
+                .and_then(|channels| u16::try_from(channels).ok()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+/// ## An entry of a list of layers.
+///
+/// Apart from a single layer, an entry can consist of several `parallel` branches
+/// that are applied to the same input and whose outputs are concatenated along the
+/// channels, as in the blocks of an Inception network.
+pub enum Entry {
+    Layer(Layer),
+    Parallel { parallel: Vec<Branch> },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+/// ## A branch of a parallel block, consisting of successive layers.
+pub struct Branch {
+    pub layers: Vec<Entry>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+/// ## A collection of successive layers.
+///
+/// This is simply a wrapper around a `Vec<Entry>` that can be
+/// deserialized using [`serde`](https://docs.rs/crate/serde/1.0.116).
+/// The names of the layers whose output dimensions shall be reported can be
+/// listed in `outputs`; by default only the output of the last layer is reported.
+pub struct Layers {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_channels: Option<u16>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<String>,
+    pub layers: Vec<Entry>,
+}
+
+/// ## Compute the output dimension of a convolutional layer.
+///
+/// The dimension of the output (o) of the convolutional layer is computed from
+/// its input dimension `in_dim` (n), the size of its filter `filter_size` (f) as well
+/// as the zero-`padding` applied to the input and the `stride` that is used to slide
+/// the filter according to:
+///
+/// o = (n - f + 2*p) / s + 1
+///
+/// The layer is applied `repeat` times, which takes constant time for a stride of one and otherwise
+/// stops once the dimension does not change anymore. Returns an error if the padded input is smaller than the filter,
+/// if the stride or the filter size is zero, or if the padded input exceeds the range of `T`.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::conv_output_dim;
+/// assert_eq!(conv_output_dim(28u16, 5, 0, 1, 1), Ok(24));
+/// assert_eq!(conv_output_dim(100_000u32, 3, 1, 2, 1), Ok(50_000));
+/// assert!(conv_output_dim(2usize, 5, 1, 1, 1).is_err());
+/// ```
+pub fn conv_output_dim<T: Dimension>(
+    in_dim: T,
+    filter_size: T,
+    padding: T,
+    stride: T,
+    repeat: u32,
+) -> Result<T, ConvDimError> {
+    if stride == T::zero() {
+        return Err(ConvDimError::ZeroStride { layer: None });
+    }
+    if filter_size == T::zero() {
+        return Err(ConvDimError::ZeroFilterSize { layer: None });
+    }
+    if stride == T::one() && repeat > 1 && T::max_value().to_u64().is_some() {
+        // The inputs must cover the filter when padded, and the padded inputs must fit into `T`.
+        let (kernel, twice) = (filter_size.wide(), 2 * padding.wide());
+        return repeat_unit_stride(
+            in_dim,
+            twice + 1 - kernel,
+            (kernel - twice, T::max_value().wide() - twice),
+            repeat,
+            |dim| conv_output_dim(dim, filter_size, padding, stride, 1),
+        );
+    }
+    let mut dim = in_dim;
+    // The input is checked even if the layer is not applied.
+    for _ in 0..repeat.max(1) {
+        let padded = padding
+            .checked_add(&padding)
+            .and_then(|twice| dim.checked_add(&twice))
+            .ok_or(ConvDimError::Overflow {
+                layer: None,
+                in_dim: dim.reported(),
+                filter_size: filter_size.reported(),
+                stride: stride.reported(),
+                padding: padding.reported(),
+            })?;
+        let difference =
+            padded
+                .checked_sub(&filter_size)
+                .ok_or(ConvDimError::KernelLargerThanInput {
+                    layer: None,
+                    filter_size: filter_size.reported(),
+                    in_dim: dim.reported(),
+                    padding: padding.reported(),
+                })?;
+        if repeat > 0 {
+            // The filter size is positive, so the difference is below the maximum and the output fits.
+            let out_dim = difference / stride + T::one();
+            if out_dim == dim {
+                break;
+            }
+            dim = out_dim;
+        }
+    }
+    Ok(dim)
+}
+
+/// ## Compute the output dimension of a transposed convolutional layer.
+///
+/// The dimension of the output (o) of the transposed convolutional layer is computed from
+/// its input dimension `in_dim` (n), the size of its filter `filter_size` (f) as well
+/// as the zero-`padding` applied to the input and the `stride` that is used to slide
+/// the filter according to:
+///
+/// o = (n - 1) * s + f - 2*p
+///
+/// The layer is applied `repeat` times, which takes constant time for a stride of one and otherwise
+/// stops once the dimension does not change anymore. Returns an error if the input is empty, if the padding exceeds
+/// the output, if the stride or the filter size is zero, or if the output exceeds the range of `T`.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::transposed_conv_output_dim;
+/// assert_eq!(transposed_conv_output_dim(32u16, 2, 0, 2, 1), Ok(64));
+/// assert_eq!(transposed_conv_output_dim(50_000u64, 2, 0, 2, 1), Ok(100_000));
+/// assert!(transposed_conv_output_dim(0u16, 2, 0, 2, 1).is_err());
+/// ```
+pub fn transposed_conv_output_dim<T: Dimension>(
+    in_dim: T,
+    filter_size: T,
+    padding: T,
+    stride: T,
+    repeat: u32,
+) -> Result<T, ConvDimError> {
+    if in_dim == T::zero() {
+        return Err(ConvDimError::EmptyInput { layer: None });
+    }
+    if stride == T::zero() {
+        return Err(ConvDimError::ZeroStride { layer: None });
+    }
+    if filter_size == T::zero() {
+        return Err(ConvDimError::ZeroFilterSize { layer: None });
+    }
+    if stride == T::one() && repeat > 1 && T::max_value().to_u64().is_some() {
+        // The inputs must be nonempty, their outputs must not be negative, and both the padding
+        // and the unpadded outputs must fit into `T`.
+        let (kernel, twice) = (filter_size.wide(), 2 * padding.wide());
+        let max = if twice > T::max_value().wide() {
+            -1
+        } else {
+            T::max_value().wide() + 1 - kernel
+        };
+        return repeat_unit_stride(
+            in_dim,
+            kernel - 1 - twice,
+            ((twice + 1 - kernel).max(1), max),
+            repeat,
+            |dim| transposed_conv_output_dim(dim, filter_size, padding, stride, 1),
+        );
+    }
+    let mut dim = in_dim;
+    for _ in 0..repeat {
+        if dim == T::zero() {
+            return Err(ConvDimError::EmptyInput { layer: None });
+        }
+        let overflow = ConvDimError::Overflow {
+            layer: None,
+            in_dim: dim.reported(),
+            filter_size: filter_size.reported(),
+            stride: stride.reported(),
+            padding: padding.reported(),
+        };
+        let full = (dim - T::one())
+            .checked_mul(&stride)
+            .and_then(|scattered| scattered.checked_add(&filter_size))
+            .ok_or_else(|| overflow.clone())?;
+        let twice = padding.checked_add(&padding).ok_or(overflow)?;
+        let out_dim = full
+            .checked_sub(&twice)
+            .ok_or(ConvDimError::NegativeOutput {
+                layer: None,
+                in_dim: dim.reported(),
+                filter_size: filter_size.reported(),
+                stride: stride.reported(),
+                padding: padding.reported(),
+            })?;
+        if out_dim == dim {
+            break;
+        }
+        dim = out_dim;
+    }
+    Ok(dim)
+}
+
+/// ## Apply a layer with a stride of one `repeat` times in constant time.
+///
+/// Such a layer adds `step` to its input, which is valid within the inclusive `range`, so its
+/// inputs are monotonic and only the first invalid one needs to be found. It is passed to
+/// `apply`, the single application of the layer, which reports its error. The dimensions are
+/// computed in `i128`, which is exact for types up to `u64`, so wider layers are applied one by one.
+fn repeat_unit_stride<T: Dimension>(
+    in_dim: T,
+    step: i128,
+    (min, max): (i128, i128),
+    repeat: u32,
+    apply: impl Fn(T) -> Result<T, ConvDimError>,
+) -> Result<T, ConvDimError> {
+    let first = in_dim.wide();
+    let invalid = if first < min || first > max {
+        Some(0)
+    } else if step > 0 {
+        Some((max - first) / step + 1)
+    } else if step < 0 {
+        Some((first - min) / -step + 1)
+    } else {
+        None
+    };
+    // The inputs up to the first invalid one and the final output are outputs of valid
+    // applications, which fit into `T`.
+    let dim = |applied: i128| T::from(first + applied * step).expect("outputs of valid layers fit");
+    match invalid {
+        Some(applied) if applied < i128::from(repeat) => apply(dim(applied)),
+        _ => Ok(dim(i128::from(repeat))),
+    }
+}
+
+/// ## Compute the dimension after a several consecutive (transposed) convolutional layers.
+///
+/// This corresponds to computing the output after passing an `in_dim`-dimensional input
+/// through all the specified `layers`.
+pub fn dim_after_layers(layers: &[Entry], in_dim: u32) -> Result<u32, ConvDimError> {
+    walk_layers(layers, in_dim, &mut |_, _| {})
+}
+
+/// ## Pass an `in_dim`-dimensional input through `layers`, visiting every layer.
+///
+/// The `visit` callback is invoked with every layer and the dimension of its output,
+/// including the layers of parallel branches. All the branches of a parallel block need
+/// to produce outputs of the same dimension, otherwise this function returns an error.
+/// Errors of unnamed layers refer to them by their position, like the trace.
+pub fn walk_layers<'a>(
+    layers: &'a [Entry],
+    in_dim: u32,
+    visit: &mut dyn FnMut(&'a Layer, u32),
+) -> Result<u32, ConvDimError> {
+    walk(layers, in_dim, visit, &mut 0)
+}
+
+/// [`walk_layers`], counting the visited layers in `position`.
+fn walk<'a>(
+    layers: &'a [Entry],
+    in_dim: u32,
+    visit: &mut dyn FnMut(&'a Layer, u32),
+    position: &mut usize,
+) -> Result<u32, ConvDimError> {
+    layers.iter().try_fold(in_dim, |dim, entry| match entry {
+        Entry::Layer(layer) => {
+            *position += 1;
+            let out_dim = located_output_dim(*position, dim, layer)?;
+            visit(layer, out_dim);
+            Ok(out_dim)
+        }
+        Entry::Parallel { parallel } => {
+            let dims = parallel
+                .iter()
+                .map(|branch| walk(&branch.layers, dim, visit, position))
+                .collect::<Result<Vec<u32>, ConvDimError>>()?;
+            match dims.first() {
+                Some(&first) if dims.iter().all(|&d| d == first) => Ok(first),
+                Some(_) => Err(ConvDimError::BranchMismatch { dims }),
+                None => Err(ConvDimError::EmptyParallel),
+            }
+        }
+    })
+}
+
+/// ## Compute the output dimension of a single `layer`.
+///
+/// Errors name the layer if it has a name.
+pub fn layer_output_dim(in_dim: u32, layer: &Layer) -> Result<u32, ConvDimError> {
+    let dim = unnamed_output_dim(in_dim, layer);
+    match layer.name() {
+        Some(name) => dim.map_err(|e| e.in_layer(name)),
+        None => dim,
+    }
+}
+
+/// The output dimension of the `layer` at the (one-based) `position` of a network, whose errors
+/// locate the layer by its position, name, parameters and input dimension.
+pub(crate) fn located_output_dim(
+    position: usize,
+    in_dim: u32,
+    layer: &Layer,
+) -> Result<u32, ConvDimError> {
+    unnamed_output_dim(in_dim, layer)
+        .map_err(|e| e.located(position, layer.name(), layer.params(), in_dim))
+}
+
+/// The output dimension of `layer`, whose errors don't refer to the layer.
+pub(crate) fn unnamed_output_dim(in_dim: u32, layer: &Layer) -> Result<u32, ConvDimError> {
+    let (filter_size, stride, padding) = layer.geometry();
+    if let Some(formula) = layer.formula() {
+        return formula.output_dim(in_dim, filter_size, stride, padding);
+    }
+    let (filter_size, stride, padding) = (filter_size as u32, stride as u32, padding as u32);
+    if layer.is_transposed() {
+        transposed_conv_output_dim(in_dim, filter_size, padding, stride, 1)
+    } else {
+        conv_output_dim(in_dim, filter_size, padding, stride, 1)
+    }
+}
+
+/// ## Compute the output dimensions of the layers marked as outputs.
+///
+/// The dimensions are returned together with the names of the layers in the order
+/// in which they are listed in the `outputs` of `layers`.
+pub fn dims_at_outputs(layers: &Layers, in_dim: u32) -> Result<Vec<(String, u32)>, ConvDimError> {
+    let mut dims = std::collections::HashMap::new();
+    walk_layers(&layers.layers, in_dim, &mut |layer, dim| {
+        if let Some(name) = layer.name() {
+            dims.insert(name, dim);
+        }
+    })?;
+
+    layers
+        .outputs
+        .iter()
+        .map(|name| match dims.get(name.as_str()) {
+            Some(&dim) => Ok((name.clone(), dim)),
+            None => Err(ConvDimError::UnknownOutput { name: name.clone() }),
+        })
+        .collect()
+}
+
+/// ## Compute the output dimension of every layer.
+///
+/// The dimensions are returned together with the names of the layers in the order in which
+/// the layers are evaluated. Layers without a name are labelled by their (one-based) position.
+pub fn trace_layers(layers: &[Entry], in_dim: u32) -> Result<Vec<(String, u32)>, ConvDimError> {
+    let mut trace = vec![];
+    walk_layers(layers, in_dim, &mut |layer, dim| {
+        let name = match layer.name() {
+            Some(name) => name.to_string(),
+            None => format!("layer {}", trace.len() + 1),
+        };
+        trace.push((name, dim));
+    })?;
+    Ok(trace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LayerRef;
+
+    #[test]
+    fn test_conv_output_dim() {
+        assert_eq!(conv_output_dim(28u16, 5, 0, 1, 1), Ok(24));
+        assert_eq!(conv_output_dim(24u16, 5, 0, 1, 1), Ok(20));
+        assert_eq!(conv_output_dim(28u16, 5, 0, 1, 2), Ok(20));
+        assert_eq!(conv_output_dim(4u16, 4, 1, 1, 1), Ok(3));
+        assert_eq!(conv_output_dim(64u16, 2, 0, 2, 1), Ok(32));
+    }
+
+    #[test]
+    fn test_transposed_conv_output_dim() {
+        assert_eq!(transposed_conv_output_dim(32u16, 2, 0, 2, 1), Ok(64));
+        assert_eq!(transposed_conv_output_dim(1u16, 2, 0, 2, 4), Ok(16));
+    }
+
+    #[test]
+    fn test_large_repeats() {
+        // Same-padded convolutions keep the dimension, however often they are applied.
+        assert_eq!(conv_output_dim(64u32, 3, 1, 1, 500_000), Ok(64));
+        assert_eq!(conv_output_dim(64u32, 3, 1, 1, 0), Ok(64));
+        // Shrinking by one per application fails once the input is smaller than the kernel.
+        assert_eq!(conv_output_dim(300_001u32, 2, 0, 1, 300_000), Ok(1));
+        assert!(conv_output_dim(300_001u32, 2, 0, 1, 300_001).is_err());
+        assert_eq!(transposed_conv_output_dim(1u64, 3, 1, 1, 400_000), Ok(1));
+        assert_eq!(
+            transposed_conv_output_dim(1u64, 2, 0, 1, 400_000),
+            Ok(400_001)
+        );
+    }
+
+    #[test]
+    fn test_repeats_agree_with_single_applications() {
+        // The dimensions of `u8` are small enough to overflow and to exhaust the inputs.
+        for in_dim in 0..=255u8 {
+            for (filter_size, padding, stride) in [
+                (1, 0, 1),
+                (3, 1, 1),
+                (2, 0, 1),
+                (1, 3, 1),
+                (5, 0, 2),
+                (2, 3, 2),
+                (7, 200, 1),
+                (1, 130, 1),
+            ] {
+                for repeat in 0..8 {
+                    let mut conv = Ok(in_dim);
+                    let mut transposed = Ok(in_dim);
+                    for _ in 0..repeat.max(1) {
+                        conv = conv.and_then(|dim| {
+                            conv_output_dim(dim, filter_size, padding, stride, repeat.min(1))
+                        });
+                    }
+                    for _ in 0..repeat {
+                        transposed = transposed.and_then(|dim| {
+                            transposed_conv_output_dim(dim, filter_size, padding, stride, 1)
+                        });
+                    }
+                    if in_dim == 0 {
+                        transposed = Err(ConvDimError::EmptyInput { layer: None });
+                    }
+                    let args = (in_dim, filter_size, padding, stride, repeat);
+                    assert_eq!(
+                        conv_output_dim(in_dim, filter_size, padding, stride, repeat),
+                        conv,
+                        "{:?}",
+                        args
+                    );
+                    assert_eq!(
+                        transposed_conv_output_dim(in_dim, filter_size, padding, stride, repeat),
+                        transposed,
+                        "{:?}",
+                        args
+                    );
+                }
+            }
+        }
+        assert_eq!(
+            conv_output_dim(u64::MAX, 2, 0, 1, u32::MAX),
+            Ok(u64::MAX - u64::from(u32::MAX))
+        );
+    }
+
+    #[test]
+    fn test_generic_dimensions() {
+        assert_eq!(conv_output_dim(224u32, 7, 3, 2, 1), Ok(112));
+        assert_eq!(conv_output_dim(224u64, 7, 3, 2, 1), Ok(112));
+        assert_eq!(transposed_conv_output_dim(112usize, 2, 0, 2, 1), Ok(224));
+        assert_eq!(conv_output_dim(u128::MAX, 1, 0, 1, 3), Ok(u128::MAX));
+        assert_eq!(
+            transposed_conv_output_dim(u128::MAX, 1, 0, 1, 3),
+            Ok(u128::MAX)
+        );
+        assert_eq!(
+            conv_output_dim(3u8, 7, 1, 1, 1),
+            Err(ConvDimError::KernelLargerThanInput {
+                layer: None,
+                filter_size: 7,
+                in_dim: 3,
+                padding: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_large_inputs() {
+        // Ten minutes of audio at 16 kHz, downsampled by strided convolutions.
+        let layers: Layers = toml::from_str(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 10
+            stride = 5
+            padding = 3
+
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            stride = 2
+            padding = 1
+
+            [[layers]]
+            type = "upsample"
+            scale = 4
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            trace_layers(&layers.layers, 9_600_000).unwrap(),
+            vec![
+                ("layer 1".to_string(), 1_920_000),
+                ("layer 2".to_string(), 960_000),
+                ("layer 3".to_string(), 3_840_000),
+            ]
+        );
+        // Twice the padding exceeds the range of the parameters.
+        let layer = Layer::Conv(ConvParams {
+            name: None,
+            filter_size: 3,
+            stride: 1,
+            padding: 40_000,
+            channels: None,
+        });
+        assert_eq!(layer_output_dim(10, &layer), Ok(80_008));
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        assert_eq!(
+            conv_output_dim(32u32, 3, 1, 0, 1),
+            Err(ConvDimError::ZeroStride { layer: None })
+        );
+        assert_eq!(
+            transposed_conv_output_dim(32u32, 0, 0, 2, 1),
+            Err(ConvDimError::ZeroFilterSize { layer: None })
+        );
+        assert!(matches!(
+            conv_output_dim(200u8, 3, 100, 1, 1),
+            Err(ConvDimError::Overflow { .. })
+        ));
+        assert!(matches!(
+            transposed_conv_output_dim(40_000u16, 2, 0, 2, 1),
+            Err(ConvDimError::Overflow { .. })
+        ));
+        assert_eq!(transposed_conv_output_dim(7u16, 3, 1, 2, 0), Ok(7));
+
+        let layers: Layers = toml::from_str(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            stride = 2
+
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            stride = 0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            dim_after_layers(&layers.layers, 32)
+                .unwrap_err()
+                .to_string(),
+            "Layer 2 (conv, filter size 3, stride 0, padding 0, input 15): the stride of 0 does \
+             not move the filter."
+        );
+    }
+
+    #[test]
+    fn test_conv_transposed_conv_chain() {
+        let in_dim: u16 = 64;
+        let stride = 2;
+        let filter_size = 3;
+        let padding = 1;
+
+        let conv_out = conv_output_dim(in_dim, filter_size, padding, stride, 1).unwrap();
+        assert_eq!(conv_out, 32);
+        let transposed_conv_out =
+            transposed_conv_output_dim(conv_out, filter_size, padding, stride, 1).unwrap();
+        assert_eq!(transposed_conv_out, 63);
+    }
+
+    #[test]
+    fn test_dim_after_layers() {
+        // Convolutional auto-encoder
+        let layers = vec![
+            // encoder
+            Entry::Layer(Layer::Conv(ConvParams {
+                name: None,
+                filter_size: 3,
+                stride: 1,
+                padding: 1,
+                channels: None,
+            })),
+            Entry::Layer(Layer::Conv(ConvParams {
+                name: None,
+                filter_size: 2,
+                stride: 2,
+                padding: 0,
+                channels: None,
+            })),
+            Entry::Layer(Layer::Conv(ConvParams {
+                name: None,
+                filter_size: 3,
+                stride: 1,
+                padding: 1,
+                channels: None,
+            })),
+            Entry::Layer(Layer::Conv(ConvParams {
+                name: None,
+                filter_size: 2,
+                stride: 2,
+                padding: 0,
+                channels: None,
+            })),
+            // decoder
+            Entry::Layer(Layer::ConvTranspose(ConvTransposeParams {
+                name: None,
+                filter_size: 2,
+                stride: 2,
+                padding: 0,
+                channels: None,
+            })),
+            Entry::Layer(Layer::ConvTranspose(ConvTransposeParams {
+                name: None,
+                filter_size: 2,
+                stride: 2,
+                padding: 0,
+                channels: None,
+            })),
+        ];
+
+        assert_eq!(dim_after_layers(&layers, 64), Ok(64));
+    }
+
+    #[test]
+    fn test_typed_layers() {
+        let layers: Layers = toml::from_str(
+            r#"
+            [[layers]]
+            name = "stem"
+            type = "conv"
+            filter_size = 3
+            padding = 1
+            channels = 16
+
+            [[layers]]
+            type = "max_pool"
+            filter_size = 2
+            stride = 2
+
+            [[layers]]
+            filter_size = 2
+            stride = 2
+            padding = 0
+            transposed = true
+
+            [[layers]]
+            type = "upsample"
+            scale = 2
+            "#,
+        )
+        .unwrap();
+        let kinds: Vec<&Layer> = layers
+            .layers
+            .iter()
+            .map(|entry| match entry {
+                Entry::Layer(layer) => layer,
+                Entry::Parallel { .. } => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            kinds[0],
+            &Layer::Conv(ConvParams {
+                name: Some("stem".to_string()),
+                filter_size: 3,
+                stride: 1,
+                padding: 1,
+                channels: Some(16),
+            })
+        );
+        assert!(matches!(kinds[1], Layer::MaxPool(_)));
+        assert!(matches!(kinds[2], Layer::ConvTranspose(_)));
+        assert_eq!(kinds[3].geometry(), (2, 2, 0));
+        assert_eq!(
+            trace_layers(&layers.layers, 32).unwrap(),
+            vec![
+                ("stem".to_string(), 32),
+                ("layer 2".to_string(), 16),
+                ("layer 3".to_string(), 32),
+                ("layer 4".to_string(), 64),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pass_through_layers() {
+        let text = r#"
+            input_channels = 8
+
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            channels = 16
+
+            [[layers]]
+            type = "batchnorm"
+
+            [[layers]]
+            type = "relu"
+            name = "act"
+
+            [[layers]]
+            type = "dropout"
+            "#;
+        let layers: Layers = toml::from_str(text).unwrap();
+        let kinds: Vec<&str> = layers
+            .layers
+            .iter()
+            .map(|entry| match entry {
+                Entry::Layer(layer) => layer.kind(),
+                Entry::Parallel { .. } => "parallel",
+            })
+            .collect();
+        assert_eq!(kinds, vec!["conv", "batchnorm", "relu", "dropout"]);
+        assert_eq!(
+            trace_layers(&layers.layers, 10).unwrap(),
+            vec![
+                ("layer 1".to_string(), 8),
+                ("layer 2".to_string(), 8),
+                ("act".to_string(), 8),
+                ("layer 4".to_string(), 8),
+            ]
+        );
+        let written = toml::to_string(&layers).unwrap();
+        assert!(written.contains("[[layers]]\ntype = \"relu\"\nname = \"act\"\n"));
+        assert_eq!(toml::from_str::<Layers>(&written).unwrap(), layers);
+
+        // The batch normalization scales and shifts each of the 16 channels.
+        let graph = crate::architecture::parse_architecture(text)
+            .unwrap()
+            .into_graph();
+        let rows = crate::summary::rows(&graph, 10).unwrap();
+        let params: Vec<Option<u64>> = rows.iter().map(|row| row.params).collect();
+        assert_eq!(
+            params,
+            vec![Some(0), Some(1168), Some(32), Some(0), Some(0)]
+        );
+    }
+
+    #[test]
+    fn test_serialize_layers() {
+        let layers: Layers = toml::from_str(
+            r#"
+            outputs = ["stem"]
+
+            [[layers]]
+            name = "stem"
+            filter_size = 3
+            stride = 2
+            padding = 1
+            transposed = false
+
+            [[layers]]
+            parallel = [
+                { layers = [{ type = "conv", filter_size = 1, channels = 8 }] },
+                { layers = [{ type = "max_pool", filter_size = 3, stride = 1, padding = 1 }] },
+            ]
+            "#,
+        )
+        .unwrap();
+        let serialized = toml::to_string(&layers).unwrap();
+        assert!(serialized.contains("type = \"conv\""));
+        assert!(!serialized.contains("transposed"));
+        assert_eq!(toml::from_str::<Layers>(&serialized).unwrap(), layers);
+    }
+
+    #[test]
+    fn test_custom_layers() {
+        plugin::register("test_custom_pool", |params| {
+            let window = params
+                .get("window")
+                .and_then(toml::Value::as_integer)
+                .ok_or("needs a 'window'")?;
+            Ok(Geometry::conv(window as u16, window as u16, 0))
+        })
+        .unwrap();
+        let layers: Layers = toml::from_str(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            padding = 1
+
+            [[layers]]
+            name = "pool"
+            type = "test_custom_pool"
+            window = 4
+            channels = 16
+            "#,
+        )
+        .unwrap();
+        match &layers.layers[1] {
+            Entry::Layer(layer) => {
+                assert_eq!(layer.kind(), "test_custom_pool");
+                assert_eq!(layer.name(), Some("pool"));
+                assert_eq!(layer.geometry(), (4, 4, 0));
+                assert_eq!(layer.channels(), Some(16));
+            }
+            Entry::Parallel { .. } => unreachable!(),
+        }
+        assert_eq!(dim_after_layers(&layers.layers, 64), Ok(16));
+        assert_eq!(
+            trace_layers(&layers.layers, 2),
+            Err(ConvDimError::KernelLargerThanInput {
+                layer: Some(LayerRef::Located {
+                    position: 2,
+                    name: Some("pool".to_string()),
+                    params: "test_custom_pool, filter size 4, stride 4, padding 0".to_string(),
+                    in_dim: 2,
+                }),
+                filter_size: 4,
+                in_dim: 2,
+                padding: 0
+            })
+        );
+        let serialized = toml::to_string(&layers).unwrap();
+        assert!(serialized.contains("type = \"test_custom_pool\""));
+        assert_eq!(toml::from_str::<Layers>(&serialized).unwrap(), layers);
+
+        assert!(toml::from_str::<Layers>("[[layers]]\ntype = \"test_custom_pool\"\n").is_err());
+        assert!(toml::from_str::<Layers>("[[layers]]\ntype = \"test_unregistered\"\n").is_err());
+    }
+
+    #[test]
+    fn test_formula_layers() {
+        let layers: Layers = toml::from_str(
+            r#"
+            [[layers]]
+            name = "pool"
+            type = "formula"
+            formula = "ceil((n + 2*p - f) / s) + 1"
+            filter_size = 3
+            stride = 2
+
+            [[layers]]
+            type = "formula"
+            formula = "n * 3"
+            transposed = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            trace_layers(&layers.layers, 8).unwrap(),
+            vec![("pool".to_string(), 4), ("layer 2".to_string(), 12)]
+        );
+        assert_eq!(
+            dim_after_layers(&layers.layers, 1).unwrap_err().to_string(),
+            "Layer 1 'pool' (formula 'ceil((n + 2*p - f) / s) + 1', filter size 3, stride 2, \
+             padding 0, input 1): the formula 'ceil((n + 2*p - f) / s) + 1' gives the dimension 0 \
+             for an input of dimension 1."
+        );
+        let serialized = toml::to_string(&layers).unwrap();
+        assert!(serialized.contains("formula = \"n * 3\""));
+        assert_eq!(toml::from_str::<Layers>(&serialized).unwrap(), layers);
+        assert!(toml::from_str::<Layers>(
+            "[[layers]]\ntype = \"formula\"\nformula = \"floor(n / 2\"\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_dims_at_outputs() {
+        let layers: Layers = toml::from_str(
+            r#"
+            outputs = ["c4", "c3"]
+
+            [[layers]]
+            filter_size = 3
+            stride = 2
+            padding = 1
+            transposed = false
+
+            [[layers]]
+            name = "c3"
+            filter_size = 3
+            stride = 2
+            padding = 1
+            transposed = false
+
+            [[layers]]
+            name = "c4"
+            filter_size = 3
+            stride = 2
+            padding = 1
+            transposed = false
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            dims_at_outputs(&layers, 64).unwrap(),
+            vec![("c4".to_string(), 8), ("c3".to_string(), 16)]
+        );
+    }
+
+    #[test]
+    fn test_parallel_branches() {
+        // A simplified Inception block.
+        let layers: Layers = toml::from_str(
+            r#"
+            [[layers]]
+            filter_size = 3
+            stride = 2
+            padding = 1
+            transposed = false
+
+            [[layers]]
+            parallel = [
+                { layers = [{ filter_size = 1, stride = 1, padding = 0, transposed = false }] },
+                { layers = [
+                    { filter_size = 1, stride = 1, padding = 0, transposed = false },
+                    { filter_size = 3, stride = 1, padding = 1, transposed = false },
+                ] },
+                { layers = [{ filter_size = 5, stride = 1, padding = 2, transposed = false }] },
+            ]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(dim_after_layers(&layers.layers, 28), Ok(14));
+    }
+
+    #[test]
+    fn test_parallel_branch_mismatch() {
+        let layers: Layers = toml::from_str(
+            r#"
+            [[layers]]
+            [[layers.parallel]]
+            [[layers.parallel.layers]]
+            filter_size = 1
+            stride = 1
+            padding = 0
+            transposed = false
+
+            [[layers.parallel]]
+            [[layers.parallel.layers]]
+            filter_size = 3
+            stride = 1
+            padding = 0
+            transposed = false
+            "#,
+        )
+        .unwrap();
+
+        let error = dim_after_layers(&layers.layers, 14).unwrap_err();
+        assert_eq!(error, ConvDimError::BranchMismatch { dims: vec![14, 12] });
+        assert!(error.to_string().ends_with("branch 1: 14, branch 2: 12"));
+    }
+
+    #[test]
+    fn test_trace_layers() {
+        let layers: Layers = toml::from_str(
+            r#"
+            [[layers]]
+            filter_size = 3
+            stride = 2
+            padding = 1
+            transposed = false
+
+            [[layers]]
+            name = "up"
+            filter_size = 2
+            stride = 2
+            padding = 0
+            transposed = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            trace_layers(&layers.layers, 64).unwrap(),
+            vec![("layer 1".to_string(), 32), ("up".to_string(), 64)]
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Compute the dimension of the output of a convolutional layer in a convolutional network.
+//!
+//! The dimension of the output is computed from the dimension of the input entering the layer, the size
+//! of the filter associated to the layer, the stride that is used to slide the filter along
+//! the input, as well as the padding that can potentially be applied to the input before performing
+//! the convolution.
+//!
+//! It is assumed that everything (dimensions of the input and the filter as well as stride step and padding)
+//! is symmetric in the `x` and `y` directions. If this is not the case, the program can be run twice by specifying
+//! the different parameters corresponding to the horizontal and vertical directions separately.
+//!
+//! Networks are described either as a list of successive [`layers`](layers::Layers) or as a
+//! [`graph`](graph::Graph) of named nodes, which are read by [`read_architecture`] from toml files
+//! or imported from the model files of deep learning frameworks. The [`summary`], [`report`] and
+//! [`export`] modules describe networks for papers, design reviews and other frameworks.
+//!
+//! Invalid parameters and networks are reported as a [`ConvDimError`] rather than a panic.
+pub mod architecture;
+pub mod audio;
+pub mod backward;
+pub mod bench;
+pub mod cache;
+pub mod canonical;
+pub mod chart;
+pub mod check;
+pub mod constant;
+pub mod contract;
+pub mod convention;
+pub mod convert;
+pub mod diagram;
+mod dimension;
+pub mod energy;
+pub mod error;
+pub mod export;
+pub mod fetch;
+pub mod ffi;
+pub mod fingerprint;
+mod flatbuffers;
+pub mod formula;
+pub mod fpn;
+pub mod generate;
+pub mod graph;
+pub mod image;
+pub mod import;
+pub mod init;
+mod json;
+pub mod layers;
+pub mod memory;
+pub mod ndjson;
+pub mod network;
+pub mod numeric;
+pub mod parallel;
+pub mod patches;
+mod pickle;
+pub mod plot;
+pub mod plugin;
+pub mod progress;
+mod protobuf;
+#[cfg(feature = "python")]
+mod python;
+pub mod report;
+pub mod resize;
+pub mod scan;
+pub mod schema;
+pub mod segmentation;
+pub mod series;
+pub mod skips;
+pub mod snapshot;
+pub mod sparkline;
+pub mod stats;
+pub mod stft;
+pub mod summary;
+pub mod symbolic;
+mod thrift;
+pub mod tiling;
+pub mod trace;
+pub mod verify;
+pub mod video;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod zip;
+pub mod zoo;
+
+pub use architecture::{
+    import_model, parse_architecture, parse_json_architecture, read_architecture, read_layers,
+    Architecture,
+};
+pub use dimension::Dimension;
+pub use error::{ConvDimError, LayerRef};
+pub use layers::{
+    conv_output_dim, dim_after_layers, dims_at_outputs, layer_output_dim, trace_layers,
+    transposed_conv_output_dim, walk_layers, Branch, ConvParams, ConvTransposeParams, CustomParams,
+    Entry, FormulaParams, Layer, Layers, PoolParams, UpsampleParams,
+};
+pub use network::{Network, NetworkBuilder};
@@ -1,38 +1,13 @@
-//! Compute the dimension of the output of a convolutional layer in a convolutional network.
+//! The command line interface of `convdim`.
 //!
-//! The dimension of the output is computed from the dimension of the input entering the layer, the size
-//! of the filter associated to the layer, the stride that is used to slide the filter along
-//! the input, as well as the padding that can potentially be applied to the input before performing
-//! the convolution.
-//!
-//! It is assumed that everything (dimensions of the input and the filter as well as stride step and padding)
-//! is symmetric in the `x` and `y` directions. If this is not the case, the program can be run twice by specifying
-//! the different parameters corresponding to the horizontal and vertical directions separately.
-use serde::Deserialize;
+//! The arguments are parsed here and passed to the handlers of the subcommands in [`cli`], which
+//! call the functions of the `convdim` library and print their results.
 use structopt::StructOpt;
 
-#[derive(Deserialize, Debug)]
-/// ## A (transposed) convolutional layer.
-///
-/// It is defined by its `filter_size`, the `padding` that is applied to
-/// the input before application of the filter, the `stride` with which the
-/// filter moves across the input tensor as well as the information on whether
-/// the layer is a convolutional or a transposed convolutional layer.
-struct Layer {
-    filter_size: u16,
-    stride: u16,
-    padding: u16,
-    transposed: bool,
-}
+use convdim::convention::Convention;
+use convdim::{bench, export, import, ndjson};
 
-#[derive(Deserialize, Debug)]
-/// ## A collection of successive layers.
-///
-/// This is simply a wrapper around a `Vec<Layer>` that can be
-/// deserialized using [`serde`](https://docs.rs/crate/serde/1.0.116).
-struct Layers {
-    layers: Vec<Layer>,
-}
+mod cli;
 
 #[derive(Debug, StructOpt)]
 /// ## Compute the dimension of the output of a (transposed) convolutional layer.
@@ -43,6 +18,9 @@ struct Layers {
 /// `--input-dim h` and `--input-dim w`.
 /// The same argument can be made for the filter.
 struct Opt {
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+
     #[structopt(
         short = "t",
         long = "toml",
@@ -50,258 +28,1279 @@ struct Opt {
         // Everything except the input dimension is specified in the toml file.
         conflicts_with_all(&["transposed", "filter-size", "padding", "stride", "repeat"])
     )]
-    /// Path to the toml file from which the successive layers and the input dimension shall be read.
+    /// Path to the toml file (or ONNX model, Keras JSON, TorchScript archive, TFLite model, YOLO YAML) from which the successive layers shall be read.
     toml: Option<std::path::PathBuf>,
 
     #[structopt(short = "i", long = "input-dim")]
-    /// The dimension of input.
-    in_dim: u16,
+    /// The dimension of input (required unless a subcommand is used), or the path to a PNG, JPEG or
+    /// TIFF image whose size and channels are used, or to an MP4 or AVI video whose frames and size
+    /// are used, or to a CSV or Parquet file whose series in `--column` is used.
+    in_dim: Option<String>,
+
+    #[structopt(short = "c", long = "column", requires = "in-dim")]
+    /// The column of the CSV or Parquet file given as input whose length is used.
+    column: Option<String>,
 
     #[structopt(short = "f", long = "filter-size", default_value = "3")]
     /// The filter size.
-    filter_size: u16,
+    filter_size: u32,
 
     #[structopt(short = "p", long = "padding", default_value = "0")]
     /// The zero-padding that is used for the filter.
-    padding: u16,
+    padding: u32,
 
     #[structopt(short = "s", long = "stride", default_value = "1")]
     /// The stride that is used for the filter.
-    stride: u16,
+    stride: u32,
 
     #[structopt(short = "r", long = "repeat", default_value = "1")]
     /// The number of times that the convolution layer is applied.
-    repeat: u16,
+    repeat: u32,
 
     #[structopt(short = "d", long = "transposed")]
     /// Flag that specifies that the layer is a transposed convolutional layer.
     transposed: bool,
+
+    #[structopt(long = "trace", requires = "toml")]
+    /// Print the output dimension of every layer read from the toml file.
+    trace: bool,
+
+    #[structopt(long = "chart", requires = "toml")]
+    /// Draw a chart of the output dimension of every layer (or node) read from the toml file
+    /// against its depth.
+    chart: bool,
+
+    #[structopt(long = "diagram", requires = "toml")]
+    /// Draw a block diagram of the layers (or nodes) read from the toml file, whose boxes are as
+    /// wide as their output dimension.
+    diagram: bool,
+
+    #[structopt(long = "diagram-channels", requires = "diagram")]
+    /// With `--diagram`, make the boxes as high as their channels.
+    diagram_channels: bool,
+
+    #[structopt(long = "sparkline", requires = "toml")]
+    /// Print a one-line sparkline of the dimension of the input and of every layer (or node) read
+    /// from the toml file, e.g. for logs.
+    sparkline: bool,
+
+    #[structopt(long = "stats", requires = "toml")]
+    /// Print the bottleneck of the network read from the toml file, i.e. its smallest output, its
+    /// largest output and the factors by which its stages shrink or grow the dimension.
+    stats: bool,
+
+    #[structopt(long = "memory", requires = "toml")]
+    /// Print the memory of the activations of the network read from the toml file as 32-bit
+    /// floats, in total and at the peak of an inference that releases the outputs no longer
+    /// needed.
+    memory: bool,
+
+    #[structopt(long = "fuse", requires = "memory")]
+    /// With `--memory`, fuse the convolutions with the batch normalizations and activations
+    /// following them, like inference runtimes, so their intermediate outputs are not allocated.
+    fuse: bool,
+
+    #[structopt(long = "energy", requires = "toml")]
+    /// Print a rough estimate of the energy of an inference of the network read from the toml
+    /// file, from its multiply-accumulate operations and its DRAM traffic.
+    energy: bool,
+
+    #[structopt(long = "pj-per-mac", default_value = "4.6")]
+    /// With `--energy`, the energy of a multiply-accumulate operation in pJ.
+    pj_per_mac: f64,
+
+    #[structopt(long = "pj-per-byte", default_value = "160")]
+    /// With `--energy`, the energy of reading or writing a byte of DRAM in pJ.
+    pj_per_byte: f64,
+
+    #[structopt(long = "symbolic")]
+    /// Print the output dimension as a formula of the input dimension `n`, after every layer
+    /// read from the toml file or for the single layer.
+    symbolic: bool,
+
+    #[structopt(long = "simplify", requires = "symbolic")]
+    /// With `--symbolic`, print the simplified formula of every output instead, with the inputs for
+    /// which the network is valid and the linear formula it follows for the multiples of its period.
+    simplify: bool,
+
+    #[structopt(long = "verify-numeric", requires = "toml")]
+    /// Verify the dimension of every layer by applying it to a zero-filled tensor.
+    verify_numeric: bool,
+
+    #[structopt(long = "convention", global = true)]
+    /// Compute the dimensions the way a framework does: `pytorch` (the default), `tensorflow`,
+    /// `caffe` or `onnx`, which differ in the rounding of poolings and in their padding rules.
+    convention: Option<Convention>,
+
+    #[structopt(long = "format", requires = "toml")]
+    /// The format of the output: `text` (the default), `table` for an aligned table of all the layers,
+    /// `latex` for a table of all the layers, or `csv` for the trace of all the layers with their
+    /// shapes, parameters and receptive fields.
+    format: Option<OutputFormat>,
 }
 
-/// ## Compute the output dimension of a convolutional layer.
-///
-/// The dimension of the output (o) of the convolutional layer is computed from
-/// its input dimension `in_dim` (n), the size of its filter `filter_size` (f) as well
-/// as the zero-`padding` applied to the input and the `stride` that is used to slide
-/// the filter according to:
-///
-/// o = (n - f + 2*p) / s + 1
-///
-/// ## Example
-///
-/// ```rust
-/// assert_eq!(conv_output_dim(28, 5, 0, 1, 1), 24);
-/// ```
-fn conv_output_dim(in_dim: u16, filter_size: u16, padding: u16, stride: u16, repeat: u16) -> u16 {
-    if filter_size > in_dim + 2 * padding {
-        panic!(
-            "Filter size ({}) is larger than (padded) input ({})!",
-            filter_size, in_dim
-        );
-    }
-    match repeat {
-        0 => in_dim,
-        1 => (in_dim - filter_size + 2 * padding) / stride + 1,
-        n => conv_output_dim(
-            (in_dim - filter_size + 2 * padding) / stride + 1,
-            filter_size,
-            padding,
-            stride,
-            n - 1,
-        ),
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## The formats in which the dimensions of a network can be printed.
+enum OutputFormat {
+    Text,
+    /// A plain text table of the parameters, output shapes, weights and operations of all layers.
+    Table,
+    /// A LaTeX table of the parameters, output shapes and numbers of weights of all layers.
+    Latex,
+    /// The values of all layers as comma-separated values.
+    Csv,
 }
 
-/// ## Compute the output dimension of a transposed convolutional layer.
-///
-/// The dimension of the output (o) of the transposed convolutional layer is computed from
-/// its input dimension `in_dim` (n), the size of its filter `filter_size` (f) as well
-/// as the zero-`padding` applied to the input and the `stride` that is used to slide
-/// the filter according to:
-///
-/// o = (n - 1) * s + f - 2*p
-///
-/// ## Example
-///
-/// ```rust
-/// assert_eq!(transposed_conv_output_dim(32, 2, 0, 2, 1), 64);
-/// ```
-fn transposed_conv_output_dim(
-    in_dim: u16,
-    filter_size: u16,
-    padding: u16,
-    stride: u16,
-    repeat: u16,
-) -> u16 {
-    if in_dim == 0 {
-        panic!("Input to transposed convolutional layer needs to be strictly positive.");
-    }
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
 
-    if (in_dim - 1) * stride + filter_size < 2 * padding {
-        panic!("Parameters of the transposed convolutional layer lead to a negative output.");
-    }
-    match repeat {
-        1 => (in_dim - 1) * stride + filter_size - 2 * padding,
-        n => transposed_conv_output_dim(
-            (in_dim - 1) * stride + filter_size - 2 * padding,
-            filter_size,
-            padding,
-            stride,
-            n - 1,
-        ),
+    fn from_str(format: &str) -> Result<OutputFormat, String> {
+        match format {
+            "text" => Ok(OutputFormat::Text),
+            "table" => Ok(OutputFormat::Table),
+            "latex" => Ok(OutputFormat::Latex),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!(
+                "Unknown format '{}', expected 'text', 'table', 'latex' or 'csv'.",
+                format
+            )),
+        }
     }
 }
 
-/// ## Compute the dimension after a several consecutive (transposed) convolutional layers.
-///
-/// This corresponds to computing the output after passing an `in_dim`-dimensional input
-/// through all the specified `layers`.
-fn dim_after_layers(layers: &[Layer], in_dim: u16) -> u16 {
-    layers.iter().fold(in_dim, |intermediate_dim, layer| {
-        if layer.transposed {
-            transposed_conv_output_dim(
-                intermediate_dim,
-                layer.filter_size,
-                layer.padding,
-                layer.stride,
-                1,
-            )
-        } else {
-            conv_output_dim(
-                intermediate_dim,
-                layer.filter_size,
-                layer.padding,
-                layer.stride,
-                1,
-            )
-        }
-    })
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Compute the input dimension(s) that lead to a given output dimension.
+    ///
+    /// The layers read from the toml file are traversed in reverse order and the
+    /// output dimension formula of each layer is inverted. Since several inputs can
+    /// lead to the same output, the result is in general a range of dimensions.
+    Backward {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file from which the successive layers shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "o", long = "output-dim")]
+        /// The dimension of the output of the last layer.
+        out_dim: u32,
+    },
+
+    /// Report the levels of a feature pyramid built on the outputs of a backbone.
+    ///
+    /// Every output listed in the toml file is considered a level of the pyramid. For each level,
+    /// the dimension of its feature map and its effective stride with respect to the input are
+    /// reported. Levels with stride 2^k are labelled Pk.
+    Fpn {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file from which the backbone and its outputs shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input.
+        in_dim: u32,
+
+        #[structopt(long = "anchors")]
+        /// The number of anchors per location of every level, e.g. `4,6,6`, or of all levels, to
+        /// count the anchors (or prior boxes) of a detector.
+        anchors: Option<String>,
+
+        #[structopt(long = "classes", requires = "anchors")]
+        /// The number of classes, to compute the shapes of the outputs of the detection head.
+        classes: Option<u32>,
+
+        #[structopt(long = "box-outputs", default_value = "5")]
+        /// With `--classes`, the outputs of the head for the box of every anchor besides the class
+        /// scores: 5 for the coordinates and the objectness like YOLO, 4 without objectness like
+        /// RetinaNet.
+        box_outputs: u32,
+    },
+
+    /// Convert a network description into another format.
+    ///
+    /// The description is read like the toml file of the main command, i.e. it may also be a model
+    /// file of a supported framework or a description in JSON, and written as toml, JSON or YAML
+    /// depending on the extension of the output file.
+    Convert {
+        #[structopt(parse(from_os_str))]
+        /// Path to the description (or model file) to convert.
+        input: std::path::PathBuf,
+
+        #[structopt(parse(from_os_str))]
+        /// Path to the `.toml`, `.json` or `.yaml` file to write.
+        output: std::path::PathBuf,
+    },
+
+    /// Rewrite network descriptions in a canonical form, so that their diffs are reviewable.
+    ///
+    /// The toml, JSON and YAML descriptions are rewritten in place in their format, with the keys
+    /// in a fixed order, the default values stripped or made explicit, the layers without a type
+    /// given their type and the names defined more than once made unique. The comments of toml
+    /// descriptions are kept with their keys and tables, and the files with comments that can not
+    /// be kept are not rewritten unless `--force` is given. With `--check`, the files that are not in canonical form are listed instead, and the exit
+    /// code is 1 if there are any.
+    Fmt {
+        #[structopt(parse(from_os_str), required = true)]
+        /// The `.toml`, `.json` or `.yaml` descriptions.
+        files: Vec<std::path::PathBuf>,
+
+        #[structopt(long = "defaults", default_value = "strip")]
+        /// Whether the default values are stripped (`strip`) or made explicit (`explicit`).
+        defaults: String,
+
+        #[structopt(long = "check")]
+        /// List the files that are not in canonical form instead of rewriting them.
+        check: bool,
+
+        #[structopt(long = "force")]
+        /// Rewrite the files even if comments of them are dropped.
+        force: bool,
+    },
+
+    /// Write a self-contained HTML or Markdown report on the network, e.g. for design reviews.
+    ///
+    /// The report contains the table of the layers with their shapes, parameters and FLOPs, their
+    /// receptive fields and warnings about the design of the network. The HTML report adds charts
+    /// of the dimensions, memory and FLOPs of the layers, the Markdown report a Mermaid diagram.
+    Report {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input, unless all the inputs of the graph declare their own.
+        in_dim: Option<u32>,
+
+        #[structopt(long = "html", parse(from_os_str))]
+        /// Path to the HTML file to write. The HTML report is printed unless a file is given.
+        html: Option<std::path::PathBuf>,
+
+        #[structopt(long = "markdown", parse(from_os_str))]
+        /// Path to the Markdown file to write, or `-` to print it.
+        markdown: Option<std::path::PathBuf>,
+    },
+
+    /// Plot the output dimension of the layers against their depth as a PNG or SVG image.
+    ///
+    /// The format is chosen by the extension of the output file. The parameters and FLOPs of the
+    /// layers can be plotted below the dimensions, e.g. for reports and slides.
+    Plot {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input, unless all the inputs of the graph declare their own.
+        in_dim: Option<u32>,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the `.png` or `.svg` file to write.
+        output: std::path::PathBuf,
+
+        #[structopt(long = "params")]
+        /// Also plot the number of parameters of the layers.
+        params: bool,
+
+        #[structopt(long = "flops")]
+        /// Also plot the FLOPs of the layers.
+        flops: bool,
+    },
+
+    /// Compute the frames a convolutional frontend produces from an audio signal.
+    ///
+    /// The signal is the one-dimensional input of the network, whose dimension is its number of
+    /// samples. The hop between the frames and their receptive field are reported in samples and
+    /// milliseconds.
+    Audio {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the frontend shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "d", long = "duration")]
+        /// The duration of the signal, e.g. `2.5 s` or `500 ms`.
+        duration: String,
+
+        #[structopt(short = "r", long = "rate")]
+        /// The sample rate of the signal, e.g. `16 kHz`.
+        rate: String,
+    },
+
+    /// Count the frames of a short-time Fourier transform, e.g. of the input of a network over
+    /// spectrograms.
+    ///
+    /// The signal is padded by half the window on both sides (`center`, like librosa and
+    /// torchaudio), by reflection (`reflect`), not at all (`none`, i.e. `center=False`), or every
+    /// hop starting in the signal is a frame (`kaldi`, i.e. `snip_edges=False`).
+    Stft {
+        #[structopt(short = "n", long = "length")]
+        /// The length of the signal in samples, or as a duration at a sample rate, e.g. `3 s at 16 kHz`.
+        length: String,
+
+        #[structopt(short = "w", long = "window")]
+        /// The size of the window (`n_fft`) in samples.
+        window: u64,
+
+        #[structopt(long = "hop")]
+        /// The hop length in samples.
+        hop: u64,
+
+        #[structopt(short = "p", long = "padding", default_value = "center")]
+        /// The padding of the signal: `center`, `reflect`, `none` or `kaldi`.
+        padding: String,
+
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file of a network over the frames, whose output dimension is reported.
+        toml: Option<std::path::PathBuf>,
+    },
+
+    /// Count the patches a sliding window extracts from an input, per axis and in total.
+    ///
+    /// Along every axis, the window moves like the filter of a convolution, as in `nn.Unfold` and
+    /// the `extract_patches` of scikit-learn. The window, stride and padding are given for every
+    /// axis, e.g. `16x8`, or once for all of them.
+    Patches {
+        #[structopt(short = "i", long = "input")]
+        /// The sizes of the axes of the input, e.g. `224x224`.
+        input: String,
+
+        #[structopt(short = "w", long = "window")]
+        /// The size of the window.
+        window: String,
+
+        #[structopt(short = "s", long = "stride", default_value = "1")]
+        /// The stride of the window.
+        stride: String,
+
+        #[structopt(short = "p", long = "padding", default_value = "0")]
+        /// The zero-padding added to both sides of every axis.
+        padding: String,
+    },
+
+    /// Plan the overlapping input tiles of a fully convolutional network on large images.
+    ///
+    /// Following the overlap-tile strategy of U-Net, every input tile adds a margin of context
+    /// around the region whose output is kept, and the tiles move by the size of that region.
+    /// Instead of the size of the output tiles, a memory budget can be given, under which the
+    /// largest tiles are planned.
+    Tile {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(
+            short = "o",
+            long = "output-tile",
+            required_unless = "budget",
+            conflicts_with = "budget"
+        )]
+        /// The size of the output tiles that are kept.
+        output_tile: Option<u32>,
+
+        #[structopt(short = "b", long = "budget")]
+        /// The memory available for the activations of a tile, e.g. `8 GB` or `512 MiB`.
+        budget: Option<String>,
+
+        #[structopt(long = "bytes-per-value", default_value = "4")]
+        /// The bytes of a value of the activations, e.g. 2 for half precision.
+        bytes_per_value: u64,
+
+        #[structopt(long = "image")]
+        /// The size of the (square) image, to count the tiles covering it.
+        image: Option<u32>,
+    },
+
+    /// Suggest the nearest input dimensions for which the network loses nothing.
+    ///
+    /// An input dimension is clean if no stride leaves the last rows and columns of its input
+    /// unused, and the merges and skip connections of the network agree. The nearest clean
+    /// dimensions below and above the input are suggested, e.g. to resize an image to. The search
+    /// ends predictably within the limits given, and reports whether it was exhaustive or truncated.
+    Resize {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input, or the path to an image or video whose size is used.
+        in_dim: String,
+
+        #[structopt(long = "max-results", default_value = "1")]
+        /// The number of clean dimensions to find on either side.
+        max_results: usize,
+
+        #[structopt(long = "max-depth", default_value = "65536")]
+        /// The largest distance from the input dimension that is searched.
+        max_depth: u32,
+
+        #[structopt(long = "timeout")]
+        /// The time after which the search stops, e.g. `30s`, `500ms` or `2m`.
+        timeout: Option<String>,
+
+        #[structopt(short = "j", long = "jobs", default_value = "1")]
+        /// The number of threads checking the dimensions, or 0 for all cores.
+        jobs: usize,
+
+        #[structopt(long = "no-cache")]
+        /// Search again instead of reusing the results of earlier runs.
+        no_cache: bool,
+    },
+
+    /// Report the output dimensions of a network for all images of a dataset.
+    ///
+    /// The PNG, JPEG and TIFF images are found in the directory and its subdirectories, and only
+    /// their headers are read. Besides the distribution of the output sizes, the images that are
+    /// too small for the network and those losing border pixels, since a stride doesn't divide
+    /// their size evenly, are listed.
+    Scan {
+        #[structopt(parse(from_os_str))]
+        /// The directory of the dataset.
+        directory: std::path::PathBuf,
+
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "j", long = "jobs", default_value = "1")]
+        /// The number of threads reading the images, or 0 for all cores.
+        jobs: usize,
+
+        #[structopt(short = "q", long = "quiet")]
+        /// Don't draw the progress on the standard error.
+        quiet: bool,
+
+        #[structopt(long = "format", default_value = "text")]
+        /// The output: `text` for a summary, or `ndjson` for a JSON line per image as it is evaluated.
+        format: ndjson::Format,
+    },
+
+    /// Report the output lengths of a 1D network for the series in CSV or Parquet files.
+    ///
+    /// The length of a series in a CSV (or TSV) file is the number of rows with a value in its
+    /// column, and that of a Parquet column is the number of rows of the file. Besides the
+    /// distribution of the input and output lengths, the series that are too short are listed.
+    Series {
+        #[structopt(parse(from_os_str), required = true)]
+        /// The CSV, TSV or Parquet files holding the series.
+        files: Vec<std::path::PathBuf>,
+
+        #[structopt(short = "c", long = "column")]
+        /// The column holding the series.
+        column: String,
+
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+    },
+
+    /// Check the skip connections of an encoder-decoder network.
+    ///
+    /// Every upsampling stage of the decoder is paired with the corresponding downsampling stage
+    /// of the encoder, in reverse order. The dimension of the feature map entering the downsampling
+    /// stage is compared to the output of the upsampling stage, and the crop or padding needed to
+    /// concatenate them is reported if they don't agree.
+    Skips {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input.
+        in_dim: u32,
+    },
+
+    /// Check that the outputs of a segmentation network have the resolution of its input.
+    ///
+    /// Every output is compared to the first input after all the upsampling. An output that does
+    /// not match is reported with its ratio and offset to the input, together with the
+    /// `upsample`, `conv_transpose` or `max_pool` layer, or the crop or padding, that would fix
+    /// it. The exit code is 1 if any output does not match.
+    Segmentation {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input.
+        in_dim: u32,
+    },
+
+    /// Compare several architectures in a table, e.g. the variants of a network.
+    ///
+    /// Every description is evaluated for the input dimension and summarized by a row with its
+    /// depth (the number of convolutions), the dimensions of its outputs, a sparkline of the
+    /// dimensions of its layers, its receptive field, its parameters and operations, and the memory
+    /// of its activations as 32-bit floats. The exit code is 1 if any description could not be
+    /// evaluated.
+    BenchTable {
+        #[structopt(parse(from_os_str), required = true)]
+        /// The descriptions.
+        files: Vec<std::path::PathBuf>,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input.
+        in_dim: u32,
+
+        #[structopt(short = "s", long = "sort", default_value = "name")]
+        /// The column to sort by: `name`, `depth`, `output`, `receptive-field`, `params`, `flops`
+        /// or `memory`.
+        sort: bench::Column,
+
+        #[structopt(short = "d", long = "descending")]
+        /// Sort in descending order.
+        descending: bool,
+    },
+
+    /// Write the shape contract of a network, the expected shapes of its inputs, outputs and layers.
+    ///
+    /// The contract maps the names of the inputs, outputs and layers to their height, width and
+    /// channels for the input dimension, e.g. for serving code validating its tensors at runtime.
+    /// It is written as YAML, or as JSON or toml depending on the extension of the output file.
+    Contract {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input.
+        in_dim: u32,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the `.yaml`, `.json` or `.toml` file to write, instead of printing the YAML.
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Print the fingerprint of a network, a stable hash of its structure.
+    ///
+    /// The fingerprint is the SHA-256 hash of a normalized text of the network, in which the
+    /// inputs and nodes are referred to by their positions. Comments, the order of the fields, the
+    /// names and the format of the description don't change it, so descriptions with the same
+    /// fingerprint define the same network, e.g. to tag experiment runs with their architecture.
+    Hash {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(long = "normalized")]
+        /// Print the normalized text that is hashed instead of the hash.
+        normalized: bool,
+    },
+
+    /// Record the trace of all the layers of a network in a snapshot, or check it against one.
+    ///
+    /// The snapshot is the CSV summary of the layers, after a line recording the input dimension.
+    /// With `--check`, the trace is computed again and the lines that changed since the snapshot
+    /// are printed, e.g. to catch edits of a description that change its shapes by accident. The
+    /// exit code is 1 if any changed.
+    Snapshot {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim", required_unless = "check")]
+        /// The dimension of the input. Defaults to the one recorded in the snapshot when checking.
+        in_dim: Option<u32>,
+
+        #[structopt(
+            long = "write",
+            parse(from_os_str),
+            required_unless = "check",
+            conflicts_with = "check"
+        )]
+        /// Write the snapshot to this file.
+        write: Option<std::path::PathBuf>,
+
+        #[structopt(long = "check", parse(from_os_str))]
+        /// Compare the trace to the snapshot in this file.
+        check: Option<std::path::PathBuf>,
+    },
+
+    /// Verify the shapes computed for an ONNX model against the shapes recorded in it.
+    ///
+    /// The shapes of the outputs and of the `value_info` of the model, which is filled in by the
+    /// ONNX shape inference, are compared to the shapes computed for the imported graph. Built with
+    /// the `tract` feature, the shapes inferred by tract for the input dimension are compared
+    /// instead. The command fails if any of them disagree.
+    Verify {
+        #[structopt(parse(from_os_str))]
+        /// Path to the ONNX model.
+        model: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input, unless the inputs of the model declare their own, which it
+        /// has to match unless the shapes are inferred by tract.
+        in_dim: Option<u32>,
+    },
+
+    /// Import the architecture of a model trained with a deep learning framework.
+    ///
+    /// The layers that determine the spatial dimensions are extracted from the model and
+    /// written in the toml graph format. Layers that cannot be represented exactly are
+    /// approximated and reported as warnings.
+    Import(ImportCommand),
+
+    /// Validate many descriptions at once, e.g. all the configs of a repository.
+    Check(CheckCommand),
+
+    /// Summarize a collection of architectures, e.g. to audit a model zoo.
+    ///
+    /// The descriptions in the directory and its subdirectories are found like by `check dir` and
+    /// summarized by the distributions of their depths and downsampling factors, the histogram of
+    /// the filter sizes of their convolutions, the range of their numbers of parameters, and the
+    /// groups of descriptions sharing the same backbone. The exit code is 1 if any description
+    /// could not be evaluated.
+    Stats {
+        #[structopt(parse(from_os_str))]
+        /// The directory of the descriptions.
+        directory: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the inputs that don't declare their own.
+        in_dim: Option<u32>,
+    },
+
+    /// Export the network as code of a deep learning framework.
+    ///
+    /// Layers that declare their number of output channels become (transposed) convolutions,
+    /// the others keep the channels of their input and become pooling, padding or upsampling.
+    Export(ExportCommand),
+
+    /// Write a starter description of a network, with a comment on every field.
+    ///
+    /// The templates are a plain CNN (`cnn`) and a convolutional autoencoder (`autoencoder`) as
+    /// lists of layers, and a small U-Net (`unet`) as a graph of nodes.
+    Init {
+        #[structopt(default_value = "cnn")]
+        /// The template: `cnn`, `autoencoder` or `unet`.
+        template: String,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the toml file to write. The description is printed unless a file is given.
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Assemble a network interactively from the input size, output size and downsampling factor.
+    ///
+    /// Every answer is validated before the next question is asked, and the network is written
+    /// to a toml file, following a comment with its receptive field.
+    Wizard,
+
+    /// Generate a block of layers that is tedious to write by hand, and report its receptive field.
+    ///
+    /// The block is written in the toml format, following a comment with its receptive field.
+    Generate(GenerateCommand),
+
+    /// Run a language server for the toml descriptions, e.g. for editor extensions.
+    ///
+    /// The server speaks JSON-RPC over the standard input and output like the Language Server
+    /// Protocol, publishing the errors and lossy divisions of the descriptions as diagnostics and
+    /// the output dimensions of the layers as inlay hints.
+    Lsp {
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input, unless the client passes its `inputDim` when initializing.
+        in_dim: Option<u32>,
+    },
+
+    /// Serve the evaluation of networks over HTTP, e.g. to validate uploaded architectures.
+    ///
+    /// `POST /eval` with a JSON body holding the `network` and the `input_dim` returns the trace of
+    /// the network, `POST /solve` with the `network` and the `output_dim` the range of the input
+    /// dimensions leading to it. The networks have the structure of the toml descriptions.
+    Serve {
+        #[structopt(short = "p", long = "port", default_value = "8080")]
+        /// The port to listen on.
+        port: u16,
+
+        #[structopt(long = "host", default_value = "127.0.0.1")]
+        /// The address to listen on, e.g. `0.0.0.0` to accept connections from other hosts.
+        host: String,
+    },
+
+    /// Serve a web page exploring a list of layers with sliders, e.g. for colleagues not using the
+    /// command line.
+    ///
+    /// The page has sliders for the input dimension and the filter size, stride, padding and
+    /// channels of the layers, and shows their shapes and receptive fields as they are moved. It
+    /// evaluates the network in the browser with the WebAssembly build of the library, which is
+    /// built with `wasm-pack build --target web -- --features wasm`.
+    Web {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file of the layers to start from, instead of a small example.
+        toml: Option<std::path::PathBuf>,
+
+        #[structopt(short = "i", long = "input-dim", default_value = "224")]
+        /// The dimension of the input to start from.
+        in_dim: u32,
+
+        #[structopt(long = "pkg", parse(from_os_str), default_value = "pkg")]
+        /// The directory of the npm package of the WebAssembly build, written by wasm-pack.
+        pkg: std::path::PathBuf,
+
+        #[structopt(short = "p", long = "port", default_value = "8000")]
+        /// The port to listen on.
+        port: u16,
+
+        #[structopt(long = "host", default_value = "127.0.0.1")]
+        /// The address to listen on, e.g. `0.0.0.0` to accept connections from other hosts.
+        host: String,
+    },
+
+    /// Evaluate a stream of candidate networks in JSON lines, e.g. from an architecture search.
+    ///
+    /// Every line of the standard input is the JSON description of a network, or an object holding
+    /// the `network` and its `input_dim`. For every line, the shapes of the `outputs` of the network
+    /// or its `error` are written to the standard output as a line of JSON, in the same order.
+    Stream {
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input of the candidates that don't give their `input_dim`.
+        in_dim: Option<u32>,
+
+        #[structopt(short = "j", long = "jobs", default_value = "1")]
+        /// The number of threads evaluating the candidates, or 0 for all cores.
+        jobs: usize,
+
+        #[structopt(short = "q", long = "quiet")]
+        /// Don't draw the progress on the standard error.
+        quiet: bool,
+
+        #[structopt(long = "cache-stats")]
+        /// Report the lookups of the outputs of the layers in the cache on the standard error.
+        cache_stats: bool,
+    },
 }
 
-fn main() {
-    let opt = Opt::from_args();
+#[derive(Debug, StructOpt)]
+enum ImportCommand {
+    /// Import an ONNX model.
+    Onnx {
+        #[structopt(parse(from_os_str))]
+        /// Path to the ONNX model.
+        model: std::path::PathBuf,
 
-    if let Some(path) = opt.toml {
-        // Parse the file content
-        let toml_content = match std::fs::read_to_string(&path) {
-            Ok(file) => file,
-            Err(e) => {
-                println!("Unable to open input file '{:?}'", path);
-                panic!("{}", e);
-            }
-        };
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the toml file to write, instead of printing the network.
+        output: Option<std::path::PathBuf>,
+    },
 
-        // De-serialize the toml content
-        let layers: Layers = match toml::from_str(&toml_content) {
-            Ok(layers) => layers,
-            Err(e) => {
-                panic!("Error reading toml input file: {}", e)
-            }
-        };
-
-        println!("{}", dim_after_layers(&layers.layers, opt.in_dim));
-    } else if opt.transposed {
-        println!(
-            "{}",
-            transposed_conv_output_dim(
-                opt.in_dim,
-                opt.filter_size,
-                opt.padding,
-                opt.stride,
-                opt.repeat
-            )
-        );
-    } else {
-        println!(
-            "{}",
-            conv_output_dim(
-                opt.in_dim,
-                opt.filter_size,
-                opt.padding,
-                opt.stride,
-                opt.repeat
-            )
-        );
-    }
+    /// Import a Keras model from the JSON written by `model.to_json()`.
+    Keras {
+        #[structopt(parse(from_os_str))]
+        /// Path to the JSON file.
+        model: std::path::PathBuf,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the toml file to write, instead of printing the network.
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Import a PyTorch model from the text written by `print(model)`.
+    ///
+    /// The layers are assumed to be applied in the order in which they are printed.
+    Pytorch {
+        #[structopt(parse(from_os_str))]
+        /// Path to the text file, or - to read from the standard input.
+        model: std::path::PathBuf,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the toml file to write, instead of printing the network.
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Import a TorchScript model from the archive written by `torch.jit.save`.
+    Torchscript {
+        #[structopt(parse(from_os_str))]
+        /// Path to the archive.
+        model: std::path::PathBuf,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the toml file to write, instead of printing the network.
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Import the summary table printed by `torchinfo.summary()` or `torchsummary.summary()`.
+    ///
+    /// The parameters of the layers are inferred from the output shapes in the table.
+    Torchinfo {
+        #[structopt(parse(from_os_str))]
+        /// Path to the text file, or - to read from the standard input.
+        model: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input, needed if the table has no 'Input Shape' column.
+        input_dim: Option<u32>,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the toml file to write, instead of printing the network.
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Import a TensorFlow Lite model from its `.tflite` file.
+    Tflite {
+        #[structopt(parse(from_os_str))]
+        /// Path to the .tflite file.
+        model: std::path::PathBuf,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the toml file to write, instead of printing the network.
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Import an Ultralytics YOLO architecture from its model YAML file, e.g. `yolov8s.yaml`.
+    Yolo {
+        #[structopt(parse(from_os_str))]
+        /// Path to the YAML file.
+        model: std::path::PathBuf,
+
+        #[structopt(short = "s", long = "scale")]
+        /// The model scale (e.g. n, s, m, l, x), taken from the file name by default.
+        scale: Option<char>,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the toml file to write, instead of printing the network.
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Import a Darknet network from its configuration, e.g. `yolov4.cfg`.
+    Darknet {
+        #[structopt(parse(from_os_str))]
+        /// Path to the .cfg file.
+        model: std::path::PathBuf,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the toml file to write, instead of printing the network.
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Import a Caffe network from its definition, e.g. `deploy.prototxt`.
+    Caffe {
+        #[structopt(parse(from_os_str))]
+        /// Path to the .prototxt file.
+        model: std::path::PathBuf,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the toml file to write, instead of printing the network.
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Import a vision model from the `config.json` of the Hugging Face Hub.
+    ///
+    /// Supports ResNet, RegNet, ConvNeXt, Swin and ViT-like models, and the backbones of other models.
+    Hf {
+        #[structopt(parse(from_os_str))]
+        /// Path to the config.json file.
+        model: std::path::PathBuf,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the toml file to write, instead of printing the network.
+        output: Option<std::path::PathBuf>,
+    },
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_conv_output_dim() {
-        assert_eq!(conv_output_dim(28, 5, 0, 1, 1), 24);
-        assert_eq!(conv_output_dim(24, 5, 0, 1, 1), 20);
-        assert_eq!(conv_output_dim(28, 5, 0, 1, 2), 20);
-        assert_eq!(conv_output_dim(4, 4, 1, 1, 1), 3);
-        assert_eq!(conv_output_dim(64, 2, 0, 2, 1), 32);
-    }
+#[derive(Debug, StructOpt)]
+enum ExportCommand {
+    /// Export a PyTorch module, an `nn.Sequential` for chains of layers.
+    Pytorch {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the Python file to write, instead of printing the code.
+        output: Option<std::path::PathBuf>,
+    },
+    /// Export a function returning a Keras model, a `tf.keras.Sequential` for chains of layers.
+    Keras {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the Python file to write, instead of printing the code.
+        output: Option<std::path::PathBuf>,
+    },
+    /// Export an ONNX model with the operations and the shapes of the weights of the network.
+    Onnx {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(long = "random")]
+        /// Fill the weights with random values instead of leaving them empty.
+        random: bool,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the ONNX file to write, instead of writing the model to the standard output.
+        output: Option<std::path::PathBuf>,
+    },
+    /// Export a Graphviz DOT graph of the network, with the output shapes of its nodes.
+    Dot {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input, without which only the numbers of channels are shown.
+        in_dim: Option<u32>,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the DOT file to write, instead of printing the graph.
+        output: Option<std::path::PathBuf>,
+    },
+    /// Export an SVG diagram of the network, which doesn't need Graphviz.
+    Svg {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input, without which the boxes are not scaled to the dimensions.
+        in_dim: Option<u32>,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the SVG file to write, instead of printing the diagram.
+        output: Option<std::path::PathBuf>,
+    },
+    /// Export a TikZ figure of the network in the style of PlotNeuralNet.
+    Tikz {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input, without which the blocks are not scaled to the dimensions.
+        in_dim: Option<u32>,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the LaTeX file to write, instead of printing the figure.
+        output: Option<std::path::PathBuf>,
+    },
+    /// Export a Rust module of constants holding the height, width, channels and length of every
+    /// tensor, e.g. for firmware allocating its buffers statically.
+    RustConsts {
+        #[structopt(short = "t", long = "toml", parse(from_os_str))]
+        /// Path to the toml file (or model file) from which the network shall be read.
+        toml: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the input.
+        in_dim: u32,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the Rust file to write, instead of printing the module.
+        output: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum CheckCommand {
+    /// Check all the descriptions in a directory and its subdirectories.
+    ///
+    /// The descriptions are the toml files defining `layers` or `nodes` and the JSON descriptions.
+    /// Every description is evaluated for the dimensions declared by its inputs, or the input
+    /// dimension given, and a pass/fail summary is printed. The exit code is 1 if any failed.
+    Dir {
+        #[structopt(parse(from_os_str))]
+        /// The directory of the descriptions.
+        directory: std::path::PathBuf,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the inputs that don't declare their own.
+        in_dim: Option<u32>,
+
+        #[structopt(long = "report")]
+        /// Write a JUnit report with a test case per description, e.g. `junit=out.xml`.
+        report: Option<String>,
+
+        #[structopt(long = "format", default_value = "text")]
+        /// The output: `text` for a summary, or `ndjson` for a JSON line per description as it is evaluated.
+        format: ndjson::Format,
+    },
+
+    /// Check the given descriptions, like `check dir`.
+    Files {
+        #[structopt(parse(from_os_str), required = true)]
+        /// The descriptions.
+        files: Vec<std::path::PathBuf>,
+
+        #[structopt(short = "i", long = "input-dim")]
+        /// The dimension of the inputs that don't declare their own.
+        in_dim: Option<u32>,
+
+        #[structopt(long = "report")]
+        /// Write a JUnit report with a test case per description, e.g. `junit=out.xml`.
+        report: Option<String>,
 
-    #[test]
-    fn test_transposed_conv_output_dim() {
-        assert_eq!(transposed_conv_output_dim(32, 2, 0, 2, 1), 64);
-        assert_eq!(transposed_conv_output_dim(1, 2, 0, 2, 4), 16);
+        #[structopt(long = "format", default_value = "text")]
+        /// The output: `text` for a summary, or `ndjson` for a JSON line per description as it is evaluated.
+        format: ndjson::Format,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum GenerateCommand {
+    /// Generate a WaveNet block of 1D causal convolutions whose dilations double, i.e. 1, 2, 4, ...
+    ///
+    /// The dilated convolutions are described by their effective filter size, and pad their input
+    /// on one side only, so they keep its dimension.
+    Wavenet {
+        #[structopt(short = "k", long = "kernel")]
+        /// The size of the kernel of the convolutions before dilation.
+        kernel: u16,
+
+        #[structopt(short = "n", long = "layers")]
+        /// The number of convolutions.
+        layers: u32,
+
+        #[structopt(short = "c", long = "channels")]
+        /// The number of output channels of the convolutions.
+        channels: Option<u16>,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the toml file to write. The block is printed unless a file is given.
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Generate the smallest TCN block of dilated causal convolutions covering a receptive field.
+    ///
+    /// The block is the WaveNet block with the fewest layers whose receptive field is at least
+    /// the target, which is a number of samples or a duration at a sample rate, e.g. `3 s at 16 kHz`.
+    Tcn {
+        #[structopt(short = "k", long = "kernel")]
+        /// The size of the kernel of the convolutions before dilation.
+        kernel: u16,
+
+        #[structopt(short = "r", long = "receptive-field")]
+        /// The receptive field to cover, in samples or as a duration at a sample rate.
+        receptive_field: String,
+
+        #[structopt(short = "c", long = "channels")]
+        /// The number of output channels of the convolutions.
+        channels: Option<u16>,
+
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        /// Path to the toml file to write. The block is printed unless a file is given.
+        output: Option<std::path::PathBuf>,
+    },
+}
+
+impl ExportCommand {
+    /// The file of the network, the format to export and the file to write the code to.
+    fn into_parts(
+        self,
+    ) -> (
+        std::path::PathBuf,
+        export::Format,
+        Option<std::path::PathBuf>,
+    ) {
+        match self {
+            ExportCommand::Pytorch { toml, output } => (toml, export::Format::PyTorch, output),
+            ExportCommand::Keras { toml, output } => (toml, export::Format::Keras, output),
+            ExportCommand::Onnx {
+                toml,
+                random,
+                output,
+            } => (toml, export::Format::Onnx { random }, output),
+            ExportCommand::Dot {
+                toml,
+                in_dim,
+                output,
+            } => (toml, export::Format::Dot { in_dim }, output),
+            ExportCommand::Svg {
+                toml,
+                in_dim,
+                output,
+            } => (toml, export::Format::Svg { in_dim }, output),
+            ExportCommand::Tikz {
+                toml,
+                in_dim,
+                output,
+            } => (toml, export::Format::Tikz { in_dim }, output),
+            ExportCommand::RustConsts {
+                toml,
+                in_dim,
+                output,
+            } => (toml, export::Format::RustConsts { in_dim }, output),
+        }
     }
+}
 
-    #[test]
-    fn test_conv_transposed_conv_chain() {
-        let in_dim = 64;
-        let stride = 2;
-        let filter_size = 3;
-        let padding = 1;
-
-        let conv_out = conv_output_dim(in_dim, filter_size, padding, stride, 1);
-        assert_eq!(conv_out, 32);
-        let transposed_conv_out =
-            transposed_conv_output_dim(conv_out, filter_size, padding, stride, 1);
-        assert_eq!(transposed_conv_out, 63);
+impl ImportCommand {
+    /// The model file, its format and the file to write the network to.
+    fn into_parts(
+        self,
+    ) -> (
+        std::path::PathBuf,
+        import::Format,
+        Option<std::path::PathBuf>,
+    ) {
+        match self {
+            ImportCommand::Onnx { model, output } => (model, import::Format::Onnx, output),
+            ImportCommand::Keras { model, output } => (model, import::Format::Keras, output),
+            ImportCommand::Pytorch { model, output } => (model, import::Format::PyTorch, output),
+            ImportCommand::Torchscript { model, output } => {
+                (model, import::Format::TorchScript, output)
+            }
+            ImportCommand::Torchinfo {
+                model,
+                input_dim,
+                output,
+            } => (model, import::Format::Torchinfo { input_dim }, output),
+            ImportCommand::Tflite { model, output } => (model, import::Format::TfLite, output),
+            ImportCommand::Yolo {
+                model,
+                scale,
+                output,
+            } => {
+                let scale = scale.or_else(|| import::yolo::scale_from_path(&model));
+                (model, import::Format::Yolo { scale }, output)
+            }
+            ImportCommand::Darknet { model, output } => (model, import::Format::Darknet, output),
+            ImportCommand::Caffe { model, output } => (model, import::Format::Caffe, output),
+            ImportCommand::Hf { model, output } => (model, import::Format::HuggingFace, output),
+        }
     }
+}
 
-    #[test]
-    fn test_dim_after_layers() {
-        // Convolutional auto-encoder
-        let layers = vec![
-            // encoder
-            Layer {
-                filter_size: 3,
-                stride: 1,
-                padding: 1,
-                transposed: false,
-            },
-            Layer {
-                filter_size: 2,
-                stride: 2,
-                padding: 0,
-                transposed: false,
-            },
-            Layer {
-                filter_size: 3,
-                stride: 1,
-                padding: 1,
-                transposed: false,
-            },
-            Layer {
-                filter_size: 2,
-                stride: 2,
-                padding: 0,
-                transposed: false,
-            },
-            // decoder
-            Layer {
-                filter_size: 2,
-                stride: 2,
-                padding: 0,
-                transposed: true,
-            },
-            Layer {
-                filter_size: 2,
-                stride: 2,
-                padding: 0,
-                transposed: true,
-            },
-        ];
-
-        assert_eq!(dim_after_layers(&layers, 64), 64);
+fn main() {
+    let opt = Opt::from_args();
+    let convention = opt.convention.unwrap_or(Convention::PyTorch);
+    let command = match opt.cmd {
+        Some(command) => command,
+        None => return cli::evaluate::run(&opt, convention),
+    };
+    match command {
+        Command::Backward { toml, out_dim } => cli::analyses::backward(&toml, out_dim),
+        Command::Fpn {
+            toml,
+            in_dim,
+            anchors,
+            classes,
+            box_outputs,
+        } => cli::analyses::fpn(&toml, in_dim, anchors, classes, box_outputs, convention),
+        Command::Skips { toml, in_dim } => cli::analyses::skips(&toml, in_dim, convention),
+        Command::Segmentation { toml, in_dim } => {
+            cli::analyses::segmentation(&toml, in_dim, convention)
+        }
+        Command::Verify { model, in_dim } => cli::models::verify(&model, in_dim),
+        Command::Import(command) => cli::models::import(command),
+        Command::Export(command) => cli::models::export(command, convention),
+        Command::Convert { input, output } => cli::models::convert(&input, output, convention),
+        Command::Fmt {
+            files,
+            defaults,
+            check,
+            force,
+        } => cli::models::fmt(&files, &defaults, check, force),
+        Command::Report {
+            toml,
+            in_dim,
+            html,
+            markdown,
+        } => cli::reports::report(&toml, in_dim, html, markdown, convention),
+        Command::Plot {
+            toml,
+            in_dim,
+            output,
+            params,
+            flops,
+        } => cli::reports::plot(&toml, in_dim, output, params, flops, convention),
+        Command::Check(command) => cli::reports::check(command),
+        Command::Stats { directory, in_dim } => cli::reports::stats(&directory, in_dim),
+        Command::BenchTable {
+            files,
+            in_dim,
+            sort,
+            descending,
+        } => cli::reports::bench_table(&files, in_dim, sort, descending),
+        Command::Init { template, output } => cli::authoring::init(&template, output),
+        Command::Wizard => cli::authoring::wizard(),
+        Command::Generate(command) => cli::authoring::generate(command),
+        Command::Audio {
+            toml,
+            duration,
+            rate,
+        } => cli::analyses::audio(&toml, &duration, &rate, convention),
+        Command::Stft {
+            length,
+            window,
+            hop,
+            padding,
+            toml,
+        } => cli::analyses::stft(&length, window, hop, &padding, toml, convention),
+        Command::Patches {
+            input,
+            window,
+            stride,
+            padding,
+        } => cli::analyses::patches(&input, &window, &stride, &padding),
+        Command::Tile {
+            toml,
+            output_tile,
+            budget,
+            bytes_per_value,
+            image,
+        } => cli::analyses::tile(
+            &toml,
+            output_tile,
+            budget,
+            bytes_per_value,
+            image,
+            convention,
+        ),
+        Command::Resize {
+            toml,
+            in_dim,
+            max_results,
+            max_depth,
+            timeout,
+            jobs,
+            no_cache,
+        } => cli::analyses::resize(
+            &toml,
+            &in_dim,
+            max_results,
+            max_depth,
+            timeout,
+            jobs,
+            no_cache,
+            convention,
+        ),
+        Command::Scan {
+            directory,
+            toml,
+            jobs,
+            quiet,
+            format,
+        } => cli::analyses::scan(&directory, &toml, jobs, quiet, format, convention),
+        Command::Series {
+            files,
+            column,
+            toml,
+        } => cli::analyses::series(&files, &column, &toml, convention),
+        Command::Contract {
+            toml,
+            in_dim,
+            output,
+        } => cli::analyses::contract(&toml, in_dim, output, convention),
+        Command::Hash { toml, normalized } => cli::analyses::hash(&toml, normalized, convention),
+        Command::Snapshot {
+            toml,
+            in_dim,
+            write,
+            check,
+        } => cli::analyses::snapshot(&toml, in_dim, write, check, convention),
+        Command::Lsp { in_dim } => cli::services::lsp(in_dim),
+        Command::Serve { port, host } => cli::services::serve(port, &host),
+        Command::Web {
+            toml,
+            in_dim,
+            pkg,
+            port,
+            host,
+        } => cli::services::web(toml, in_dim, &pkg, port, &host),
+        Command::Stream {
+            in_dim,
+            jobs,
+            quiet,
+            cache_stats,
+        } => cli::services::stream(in_dim, jobs, quiet, cache_stats),
     }
 }
@@ -0,0 +1,231 @@
+//! Estimates of the memory of the activations of a network during inference.
+//!
+//! The nodes are evaluated one after the other in the order of [`Graph::order`], and the output
+//! of a node is released as soon as the last node reading it has been evaluated, unless it is an
+//! output of the network. The peak is the largest memory held while evaluating a node, i.e. its
+//! inputs, its output and the outputs still needed by later nodes.
+//!
+//! Runtimes like TensorRT or TVM fuse a (transposed) convolution with the batch normalization and
+//! activation following it, and an addition with the activation following it, so the outputs
+//! between them are never allocated. Dropouts and identities are no-ops at inference and are fused
+//! into the node before them. With fusion, only the output of the last node of every fused group
+//! is allocated.
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::export::outputs;
+use crate::graph::{Graph, Op};
+use crate::report::abbreviate;
+use crate::summary;
+use crate::trace::write_columns;
+
+/// The bytes of a value of the activations, which are assumed to be 32-bit floats.
+const BYTES_PER_VALUE: u64 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The memory of the activations of a network, in bytes.
+pub struct Memory {
+    /// The memory of all the allocated outputs, if none was released.
+    pub total: u64,
+    /// The largest memory held at once.
+    pub peak: u64,
+    /// The node, or the last node of the fused group, whose evaluation needs the peak memory.
+    pub peak_at: String,
+    /// The groups of nodes that are fused, in the order of evaluation.
+    pub fused: Vec<Vec<String>>,
+}
+
+impl fmt::Display for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rows = vec![
+            vec![
+                "activations:".to_string(),
+                abbreviate(self.total as f64, "B"),
+                String::new(),
+            ],
+            vec![
+                "peak:".to_string(),
+                abbreviate(self.peak as f64, "B"),
+                format!("at '{}'", self.peak_at),
+            ],
+        ];
+        for group in &self.fused {
+            rows.push(vec!["fused:".to_string(), group.join(" + "), String::new()]);
+        }
+        write_columns(f, &rows, &[])
+    }
+}
+
+/// Whether a node of `op` following the nodes of `group` is fused into it.
+fn fuses(group: &[Op], op: Op) -> bool {
+    let relu = group.contains(&Op::Relu);
+    match (group[0], op) {
+        (_, Op::Dropout) | (_, Op::Identity) => true,
+        (Op::Conv, Op::BatchNorm) | (Op::Transposed, Op::BatchNorm) => group.len() == 1,
+        (Op::Conv, Op::Relu) | (Op::Transposed, Op::Relu) | (Op::Add, Op::Relu) => !relu,
+        _ => false,
+    }
+}
+
+/// ## The memory of the activations of `graph` for an input of dimension `in_dim`.
+///
+/// With `fuse`, the standard fusion patterns are applied first. Returns an error if the channels
+/// of an output are unknown.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{memory, Architecture, Network};
+/// let network = Network::builder()
+///     .input_channels(1)
+///     .conv(1, 1, 0).channels(4)
+///     .conv(1, 1, 0).channels(2)
+///     .build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let memory = memory::memory(&graph, 8, false).unwrap();
+/// // The input is released once the first layer has been evaluated.
+/// assert_eq!(memory.total, 4 * 64 * (1 + 4 + 2));
+/// assert_eq!((memory.peak, memory.peak_at.as_str()), (4 * 64 * (4 + 2), "layer2"));
+/// ```
+pub fn memory(graph: &Graph, in_dim: u32, fuse: bool) -> Result<Memory, ConvDimError> {
+    let mut sizes: HashMap<&str, u64> = HashMap::new();
+    for row in summary::rows(graph, in_dim)? {
+        let activations = row.activations().ok_or_else(|| ConvDimError::Memory {
+            message: format!(
+                "the channels of '{}' are unknown, so its memory is too.",
+                row.name
+            ),
+        })?;
+        sizes.insert(row.name, activations.saturating_mul(BYTES_PER_VALUE));
+    }
+    let edges = graph.edges();
+    let kept = outputs(graph);
+    let mut readers: HashMap<&str, usize> = HashMap::new();
+    for inputs in edges.values() {
+        for input in inputs {
+            *readers.entry(input).or_insert(0) += 1;
+        }
+    }
+
+    // The groups of nodes evaluated at once, a node being fused into the group of its input if
+    // that input is read by no other node and is not an output.
+    let mut groups: Vec<Vec<(&str, Op)>> = vec![];
+    let mut group_of: HashMap<&str, usize> = HashMap::new();
+    for node in graph.order() {
+        let inputs = &edges[node.name.as_str()];
+        let group = match inputs.as_slice() {
+            [input] if fuse && readers[input] == 1 && !kept.contains(input) => {
+                group_of.get(input).copied().filter(|&group| {
+                    let ops: Vec<Op> = groups[group].iter().map(|&(_, op)| op).collect();
+                    fuses(&ops, node.op)
+                })
+            }
+            _ => None,
+        };
+        let group = group.unwrap_or_else(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[group].push((&node.name, node.op));
+        group_of.insert(&node.name, group);
+    }
+
+    let mut live: HashMap<&str, u64> = graph
+        .input_shapes(in_dim)
+        .into_iter()
+        .map(|(name, _)| (name, sizes[name]))
+        .collect();
+    let mut total: u64 = live.values().sum();
+    let (mut peak, mut peak_at) = (total, String::new());
+    for group in &groups {
+        let (head, _) = group[0];
+        let (last, _) = group[group.len() - 1];
+        live.insert(last, sizes[last]);
+        total = total.saturating_add(sizes[last]);
+        let held: u64 = live.values().sum();
+        if held > peak || peak_at.is_empty() {
+            peak = held;
+            peak_at = last.to_string();
+        }
+        for input in &edges[head] {
+            let remaining = readers.get_mut(input).expect("every input has a reader");
+            *remaining -= 1;
+            if *remaining == 0 && !kept.contains(input) {
+                live.remove(input);
+            }
+        }
+    }
+    Ok(Memory {
+        total,
+        peak,
+        peak_at,
+        fused: groups
+            .iter()
+            .filter(|group| group.len() > 1)
+            .map(|group| group.iter().map(|(name, _)| name.to_string()).collect())
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_memory() {
+        let graph = parse_architecture(
+            r#"
+            input_channels = 1
+
+            [[nodes]]
+            name = "conv"
+            op = "conv"
+            filter_size = 1
+            channels = 2
+
+            [[nodes]]
+            name = "bn"
+            op = "batchnorm"
+
+            [[nodes]]
+            name = "relu"
+            op = "relu"
+
+            [[nodes]]
+            name = "skip"
+            op = "add"
+            inputs = ["relu", "relu"]
+
+            [[nodes]]
+            name = "act"
+            op = "relu"
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        // Every output holds 4 * 4 * 2 values of 4 bytes, the input half of it.
+        let unfused = memory(&graph, 4, false).unwrap();
+        assert_eq!((unfused.total, unfused.peak), (64 + 5 * 128, 256));
+        assert_eq!(unfused.peak_at, "bn");
+        assert!(unfused.fused.is_empty());
+
+        let fused = memory(&graph, 4, true).unwrap();
+        assert_eq!(fused.total, 64 + 2 * 128);
+        assert_eq!((fused.peak, fused.peak_at.as_str()), (256, "act"));
+        assert_eq!(
+            fused.fused,
+            [vec!["conv", "bn", "relu"], vec!["skip", "act"]]
+        );
+        assert_eq!(
+            fused.to_string(),
+            "activations:  320 B\n\
+             peak:         256 B             at 'act'\n\
+             fused:        conv + bn + relu\n\
+             fused:        skip + act\n"
+        );
+    }
+}
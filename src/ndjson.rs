@@ -0,0 +1,61 @@
+//! The output of the analyses of many items as JSON lines, e.g. for `jq` or dataflow jobs.
+//!
+//! With `--format ndjson`, [`check`](crate::check) and [`scan`](crate::scan) write a JSON object
+//! on its own line for every description or image as soon as it is evaluated, instead of their
+//! summary once all of them are. Every object names the analysis in its `type` and the item in its
+//! `path`, so the lines can be consumed on their own and the streams of several analyses merged.
+use std::str::FromStr;
+
+use crate::convert::{compact_json, string};
+use crate::error::ConvDimError;
+
+type Table = toml::value::Table;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## Whether the results are summarized as text or written as JSON lines.
+pub enum Format {
+    Text,
+    Ndjson,
+}
+
+impl FromStr for Format {
+    type Err = ConvDimError;
+
+    fn from_str(text: &str) -> Result<Format, ConvDimError> {
+        match text {
+            "text" => Ok(Format::Text),
+            "ndjson" => Ok(Format::Ndjson),
+            _ => Err(ConvDimError::Parse {
+                message: format!("'{}' is not one of 'text' and 'ndjson'.", text),
+            }),
+        }
+    }
+}
+
+/// ## The `record` of an item of the analysis `kind` as a JSON line, without its line break.
+///
+/// The `type` naming the analysis comes first, so that the lines are told apart at a glance.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::ndjson::line;
+/// let mut record = toml::value::Table::new();
+/// record.insert("path".to_string(), toml::Value::String("a.toml".to_string()));
+/// assert_eq!(line("check", record), r#"{"type": "check", "path": "a.toml"}"#);
+/// ```
+pub fn line(kind: &str, record: Table) -> String {
+    let kind = format!("{{\"type\": {}", string(kind));
+    match compact_json(&toml::Value::Table(record)).strip_prefix('{') {
+        Some("}") | None => kind + "}",
+        Some(members) => format!("{}, {}", kind, members),
+    }
+}
+
+/// The members `pairs` as a table, e.g. the record of an item.
+pub(crate) fn table<'a>(pairs: impl IntoIterator<Item = (&'a str, toml::Value)>) -> Table {
+    pairs
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
+}
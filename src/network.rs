@@ -0,0 +1,518 @@
+//! Networks described as a list of layers, whose intermediate shapes are computed lazily.
+//!
+//! A [`Network`] wraps the [`Layers`] read from a configuration and yields the shape of the
+//! feature map after every layer through an iterator, so that other tools can consume the shapes
+//! programmatically instead of parsing the printed trace.
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::graph::Shape;
+use crate::layers::{
+    located_output_dim, Branch, ConvParams, ConvTransposeParams, Entry, Layer, Layers, PassThrough,
+    PassThroughParams, PoolParams, UpsampleParams,
+};
+use crate::trace::write_columns;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+/// ## A network of successive layers.
+///
+/// The network is serialized like its [`Layers`].
+pub struct Network {
+    layers: Layers,
+}
+
+impl From<Layers> for Network {
+    fn from(layers: Layers) -> Network {
+        Network { layers }
+    }
+}
+
+impl Network {
+    /// ## A builder assembling the network layer by layer.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use convdim::Network;
+    /// let network = Network::builder()
+    ///     .conv(3, 2, 1)
+    ///     .maxpool(2, 2)
+    ///     .conv_t(2, 2, 0)
+    ///     .build();
+    /// assert_eq!(network.output_shape(64).unwrap().dim, 32);
+    /// ```
+    pub fn builder() -> NetworkBuilder {
+        NetworkBuilder::default()
+    }
+
+    /// The layers of the network.
+    pub fn layers(&self) -> &Layers {
+        &self.layers
+    }
+
+    /// ## The shapes after the layers of the network for an input of dimension `in_dim`.
+    ///
+    /// The shapes are yielded in the order in which the layers are evaluated. The layers of the
+    /// branches of a parallel block are followed by the concatenation of their outputs, like the
+    /// nodes of the graph the layers are converted into. The iterator ends after the first error.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use convdim::{Layers, Network};
+    /// let layers: Layers = toml::from_str(
+    ///     r#"
+    ///     input_channels = 3
+    ///
+    ///     [[layers]]
+    ///     type = "conv"
+    ///     filter_size = 3
+    ///     stride = 2
+    ///     padding = 1
+    ///     channels = 16
+    ///
+    ///     [[layers]]
+    ///     type = "max_pool"
+    ///     filter_size = 2
+    ///     stride = 2
+    ///     "#,
+    /// )
+    /// .unwrap();
+    /// let dims: Vec<u32> = Network::from(layers)
+    ///     .shapes(64)
+    ///     .map(|shape| shape.unwrap().dim)
+    ///     .collect();
+    /// assert_eq!(dims, vec![32, 16]);
+    /// ```
+    pub fn shapes(&self, in_dim: u32) -> Shapes<'_> {
+        Shapes {
+            stack: vec![Frame::Sequence(self.layers.layers.iter())],
+            current: Shape {
+                dim: in_dim,
+                channels: self.layers.input_channels,
+            },
+            position: 0,
+        }
+    }
+
+    /// ## The shape of the output of the network for an input of dimension `in_dim`.
+    pub fn output_shape(&self, in_dim: u32) -> Result<Shape, ConvDimError> {
+        let mut output = Shape {
+            dim: in_dim,
+            channels: self.layers.input_channels,
+        };
+        for shape in self.shapes(in_dim) {
+            output = shape?;
+        }
+        Ok(output)
+    }
+}
+
+/// Append the rows of the layers of `entries` nested `depth` blocks deep to `rows`, counting the
+/// layers in `position` to label the unnamed ones like the trace.
+fn layer_rows(entries: &[Entry], depth: usize, position: &mut usize, rows: &mut Vec<Vec<String>>) {
+    let indent = "  ".repeat(depth);
+    for entry in entries {
+        match entry {
+            Entry::Layer(layer) => {
+                *position += 1;
+                let name = match layer.name() {
+                    Some(name) => name.to_string(),
+                    None => format!("layer {}", position),
+                };
+                let (filter_size, stride, padding) = layer.geometry();
+                rows.push(vec![
+                    format!("{}{}", indent, name),
+                    layer.kind().to_string(),
+                    filter_size.to_string(),
+                    stride.to_string(),
+                    padding.to_string(),
+                    layer
+                        .channels()
+                        .map_or_else(String::new, |channels| channels.to_string()),
+                ]);
+            }
+            Entry::Parallel { parallel } => {
+                rows.push(vec![format!("{}parallel", indent)]);
+                for (i, branch) in parallel.iter().enumerate() {
+                    rows.push(vec![format!("{}  branch {}", indent, i + 1)]);
+                    layer_rows(&branch.layers, depth + 2, position, rows);
+                }
+            }
+        }
+    }
+}
+
+/// The layers are displayed as a table of their types and parameters, in which the layers of the
+/// branches of parallel blocks are indented.
+impl std::fmt::Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut rows = vec![["layer", "type", "kernel", "stride", "padding", "channels"]
+            .iter()
+            .map(|header| header.to_string())
+            .collect()];
+        layer_rows(&self.layers.layers, 0, &mut 0, &mut rows);
+        write_columns(f, &rows, &[2, 3, 4, 5])
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// ## A builder of a [`Network`], created by [`Network::builder`].
+///
+/// The layers are appended in the order of the calls, and [`name`](NetworkBuilder::name) and
+/// [`channels`](NetworkBuilder::channels) apply to the layer appended last.
+pub struct NetworkBuilder {
+    input_channels: Option<u16>,
+    entries: Vec<Entry>,
+    outputs: Vec<String>,
+}
+
+impl NetworkBuilder {
+    /// The number of channels of the network input.
+    pub fn input_channels(mut self, channels: u16) -> NetworkBuilder {
+        self.input_channels = Some(channels);
+        self
+    }
+
+    /// Append a convolution.
+    pub fn conv(self, filter_size: u16, stride: u16, padding: u16) -> NetworkBuilder {
+        self.layer(Layer::Conv(ConvParams {
+            name: None,
+            filter_size,
+            stride,
+            padding,
+            channels: None,
+        }))
+    }
+
+    /// Append a transposed convolution.
+    pub fn conv_t(self, filter_size: u16, stride: u16, padding: u16) -> NetworkBuilder {
+        self.layer(Layer::ConvTranspose(ConvTransposeParams {
+            name: None,
+            filter_size,
+            stride,
+            padding,
+            channels: None,
+        }))
+    }
+
+    /// Append a max pooling layer without padding.
+    pub fn maxpool(self, filter_size: u16, stride: u16) -> NetworkBuilder {
+        self.layer(Layer::MaxPool(PoolParams {
+            name: None,
+            filter_size,
+            stride,
+            padding: 0,
+        }))
+    }
+
+    /// Append an upsampling layer.
+    pub fn upsample(self, scale: u16) -> NetworkBuilder {
+        self.layer(Layer::Upsample(UpsampleParams { name: None, scale }))
+    }
+
+    /// Append a layer of the `kind` keeping the shape of its input, e.g. a `relu`.
+    pub fn pass_through(self, kind: PassThrough) -> NetworkBuilder {
+        self.layer(Layer::PassThrough(PassThroughParams { name: None, kind }))
+    }
+
+    /// Append `layer`.
+    pub fn layer(mut self, layer: Layer) -> NetworkBuilder {
+        self.entries.push(Entry::Layer(layer));
+        self
+    }
+
+    /// Append a parallel block of `branches`, whose outputs are concatenated.
+    pub fn parallel(mut self, branches: Vec<NetworkBuilder>) -> NetworkBuilder {
+        let parallel = branches
+            .into_iter()
+            .map(|branch| Branch {
+                layers: branch.entries,
+            })
+            .collect();
+        self.entries.push(Entry::Parallel { parallel });
+        self
+    }
+
+    /// Name the last layer, by which it can be listed among the outputs.
+    pub fn name(mut self, name: &str) -> NetworkBuilder {
+        if let Some(Entry::Layer(layer)) = self.entries.last_mut() {
+            let name = Some(name.to_string());
+            match layer {
+                Layer::Conv(params) => params.name = name,
+                Layer::ConvTranspose(params) => params.name = name,
+                Layer::MaxPool(params) => params.name = name,
+                Layer::Upsample(params) => params.name = name,
+                Layer::Formula(params) => params.name = name,
+                Layer::PassThrough(params) => params.name = name,
+                Layer::Custom(params) => params.name = name,
+            }
+        }
+        self
+    }
+
+    /// The number of output channels of the last layer, if it is a (transposed) convolution, a
+    /// formula or a custom layer.
+    ///
+    /// Pooling and upsampling keep the channels of their input, so their channels are not set.
+    pub fn channels(mut self, channels: u16) -> NetworkBuilder {
+        match self.entries.last_mut() {
+            Some(Entry::Layer(Layer::Conv(params))) => params.channels = Some(channels),
+            Some(Entry::Layer(Layer::ConvTranspose(params))) => params.channels = Some(channels),
+            Some(Entry::Layer(Layer::Formula(params))) => params.channels = Some(channels),
+            Some(Entry::Layer(Layer::Custom(params))) => {
+                let channels = toml::Value::Integer(channels.into());
+                params.params.insert("channels".to_string(), channels);
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// Report the output of the layer `name`, in addition to the other outputs.
+    pub fn output(mut self, name: &str) -> NetworkBuilder {
+        self.outputs.push(name.to_string());
+        self
+    }
+
+    /// The network of the appended layers.
+    pub fn build(self) -> Network {
+        Network::from(Layers {
+            input_channels: self.input_channels,
+            layers: self.entries,
+            outputs: self.outputs,
+        })
+    }
+}
+
+/// The entries still to be evaluated at one level of nesting.
+enum Frame<'a> {
+    /// The remaining entries of a list of layers.
+    Sequence(std::slice::Iter<'a, Entry>),
+    /// The remaining branches of a parallel block applied to `input`, and the outputs of the
+    /// branches evaluated so far.
+    Parallel {
+        branches: std::slice::Iter<'a, Branch>,
+        input: Shape,
+        outputs: Vec<Shape>,
+    },
+}
+
+/// ## The iterator over the shapes after the layers of a [`Network`].
+pub struct Shapes<'a> {
+    stack: Vec<Frame<'a>>,
+    current: Shape,
+    /// The number of layers evaluated so far.
+    position: usize,
+}
+
+impl Shapes<'_> {
+    /// The concatenation of the `outputs` of the branches of a parallel block.
+    fn concat(outputs: &[Shape]) -> Result<Shape, ConvDimError> {
+        match outputs.first() {
+            Some(first) if outputs.iter().all(|output| output.dim == first.dim) => Ok(Shape {
+                dim: first.dim,
+                channels: outputs.iter().map(|output| output.channels).sum(),
+            }),
+            Some(_) => Err(ConvDimError::BranchMismatch {
+                dims: outputs.iter().map(|output| output.dim).collect(),
+            }),
+            None => Err(ConvDimError::EmptyParallel),
+        }
+    }
+}
+
+impl Iterator for Shapes<'_> {
+    type Item = Result<Shape, ConvDimError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = loop {
+            match self.stack.last_mut()? {
+                Frame::Sequence(entries) => match entries.next() {
+                    Some(Entry::Layer(layer)) => {
+                        self.position += 1;
+                        break located_output_dim(self.position, self.current.dim, layer).map(
+                            |dim| Shape {
+                                dim,
+                                channels: layer.channels().or(self.current.channels),
+                            },
+                        );
+                    }
+                    Some(Entry::Parallel { parallel }) => self.stack.push(Frame::Parallel {
+                        branches: parallel.iter(),
+                        input: self.current,
+                        outputs: vec![],
+                    }),
+                    None => {
+                        self.stack.pop();
+                        if let Some(Frame::Parallel { outputs, .. }) = self.stack.last_mut() {
+                            outputs.push(self.current);
+                        }
+                    }
+                },
+                Frame::Parallel {
+                    branches,
+                    input,
+                    outputs,
+                } => match branches.next() {
+                    Some(branch) => {
+                        self.current = *input;
+                        self.stack.push(Frame::Sequence(branch.layers.iter()));
+                    }
+                    None => {
+                        let concat = Shapes::concat(outputs);
+                        self.stack.pop();
+                        break concat;
+                    }
+                },
+            }
+        };
+        match result {
+            Ok(shape) => self.current = shape,
+            Err(_) => self.stack.clear(),
+        }
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LayerRef;
+    use crate::Architecture;
+
+    #[test]
+    fn test_shapes_follow_the_graph() {
+        let layers: Layers = toml::from_str(
+            r#"
+            input_channels = 3
+
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            stride = 2
+            padding = 1
+            channels = 16
+
+            [[layers]]
+            parallel = [
+                { layers = [{ type = "conv", filter_size = 1, channels = 8 }] },
+                { layers = [
+                    { type = "max_pool", filter_size = 3, stride = 1, padding = 1 },
+                    { type = "conv", filter_size = 1, channels = 4 },
+                ] },
+            ]
+
+            [[layers]]
+            type = "upsample"
+            scale = 2
+            "#,
+        )
+        .unwrap();
+        let network = Network::from(layers.clone());
+        let shapes: Vec<Shape> = network.shapes(64).map(Result::unwrap).collect();
+        let graph = Architecture::Layers(layers).into_graph();
+        let expected: Vec<Shape> = graph
+            .shapes(64)
+            .unwrap()
+            .into_iter()
+            .map(|(_, shape)| shape)
+            .collect();
+        assert_eq!(shapes, expected);
+        assert_eq!(
+            network.output_shape(64),
+            Ok(Shape {
+                dim: 64,
+                channels: Some(12)
+            })
+        );
+    }
+
+    #[test]
+    fn test_shapes_end_after_an_error() {
+        let layers: Layers = toml::from_str(
+            r#"
+            [[layers]]
+            name = "pool"
+            type = "max_pool"
+            filter_size = 2
+            stride = 2
+
+            [[layers]]
+            name = "large"
+            type = "conv"
+            filter_size = 7
+
+            [[layers]]
+            type = "conv"
+            filter_size = 1
+            "#,
+        )
+        .unwrap();
+        let network = Network::from(layers);
+        let mut shapes = network.shapes(8);
+        assert_eq!(shapes.next().unwrap().unwrap().dim, 4);
+        assert!(matches!(
+            shapes.next(),
+            Some(Err(ConvDimError::KernelLargerThanInput { layer: Some(LayerRef::Located { position: 2, name: Some(name), .. }), .. })) if name == "large"
+        ));
+        assert!(shapes.next().is_none());
+    }
+
+    #[test]
+    fn test_display() {
+        let network = Network::builder()
+            .input_channels(3)
+            .conv(7, 2, 3)
+            .channels(64)
+            .name("stem")
+            .parallel(vec![
+                Network::builder().conv(1, 1, 0).channels(32),
+                Network::builder().maxpool(3, 1),
+            ])
+            .upsample(2)
+            .build();
+        assert_eq!(
+            network.to_string(),
+            "layer        type      kernel  stride  padding  channels
+stem         conv           7       2        3        64
+parallel
+  branch 1
+    layer 2  conv           1       1        0        32
+  branch 2
+    layer 3  max_pool       3       1        0
+layer 4      upsample       2       2        0
+"
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let network = Network::builder()
+            .input_channels(3)
+            .conv(3, 2, 1)
+            .channels(16)
+            .name("stem")
+            .output("stem")
+            .parallel(vec![
+                Network::builder().conv(1, 1, 0).channels(8),
+                Network::builder().maxpool(3, 1).conv(1, 1, 1).channels(4),
+            ])
+            .upsample(2)
+            .name("up")
+            .output("up")
+            .build();
+        assert_eq!(
+            crate::dims_at_outputs(network.layers(), 64),
+            Ok(vec![("stem".to_string(), 32), ("up".to_string(), 64)])
+        );
+        assert_eq!(
+            network.output_shape(64),
+            Ok(Shape {
+                dim: 64,
+                channels: Some(12)
+            })
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! Numerical verification of the output dimensions.
+//!
+//! Instead of evaluating the formulas for the output dimensions, every layer is applied to a
+//! zero-filled tensor of the dimension of its input: convolutions pad their input and slide their
+//! filter across it as long as it fits, and transposed convolutions scatter every input value into
+//! the output through their filter and crop the padding off the result. The dimensions of the
+//! resulting tensors serve as an oracle for the formulas, which catches regressions in them.
+//!
+//! The poolings that round up, which are layers in [`ceil_mode`](crate::layers::FormulaParams)
+//! like those of the Caffe [convention](crate::convention), slide their window in ceil mode: a
+//! last window may stick out of the padded input, as long as it starts before the padding on its
+//! far side.
+//!
+//! The tensors have a single channel and a single axis, since the channels don't affect the
+//! spatial dimensions and both axes are computed alike. Their memory grows with their dimension,
+//! which is limited to [`MAX_DIM`], and a tensor is dropped once all the nodes reading it have been
+//! evaluated.
+
+use std::collections::HashMap;
+
+use crate::error::ConvDimError;
+use crate::graph::{Graph, Op};
+
+/// The largest dimension of a tensor, which takes 64 MiB.
+pub const MAX_DIM: usize = 1 << 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Whether a convolution only slides its filter where it fits, or also to a last partial window.
+enum Rounding {
+    Floor,
+    Ceil,
+}
+
+/// A tensor with a single channel along one axis.
+struct Tensor {
+    data: Vec<f32>,
+}
+
+impl Tensor {
+    fn zeros(dim: usize) -> Result<Tensor, ConvDimError> {
+        if dim > MAX_DIM {
+            return Err(ConvDimError::Numeric {
+                message: format!(
+                    "a tensor of dimension {} exceeds the largest dimension of {}.",
+                    dim, MAX_DIM
+                ),
+            });
+        }
+        Ok(Tensor {
+            data: vec![0.0; dim],
+        })
+    }
+
+    fn dim(&self) -> usize {
+        self.data.len()
+    }
+
+    /// The tensor surrounded by `padding` zeros on both sides.
+    fn pad(&self, padding: usize) -> Result<Tensor, ConvDimError> {
+        let mut padded = Tensor::zeros(self.dim().saturating_add(2 * padding))?;
+        padded.data[padding..padding + self.dim()].copy_from_slice(&self.data);
+        Ok(padded)
+    }
+
+    /// Slide a `filter_size` filter of ones across the padded tensor with `stride`, summing the
+    /// values under the filter at every position where it fits into the tensor. With
+    /// [`Rounding::Ceil`], it also slides to a last window sticking out of the tensor, unless that
+    /// window starts in the padding.
+    fn conv(
+        &self,
+        filter_size: usize,
+        stride: usize,
+        padding: usize,
+        rounding: Rounding,
+    ) -> Result<Tensor, ConvDimError> {
+        let padded = self.pad(padding)?;
+        let fits = |start: usize| match rounding {
+            Rounding::Floor => start + filter_size <= padded.dim(),
+            // The window before it did not reach the end yet.
+            Rounding::Ceil => {
+                start + filter_size < padded.dim() + stride && start < padded.dim() - padding
+            }
+        };
+        let mut outputs = vec![];
+        let mut start = 0;
+        while fits(start) {
+            let end = (start + filter_size).min(padded.dim());
+            outputs.push(padded.data[start..end].iter().sum());
+            start += stride;
+        }
+        Ok(Tensor { data: outputs })
+    }
+
+    /// Scatter every value through a `filter_size` filter of ones placed `stride` apart, and crop
+    /// `padding` values off both sides of the result, which fails if nothing is left.
+    fn transposed(
+        &self,
+        filter_size: usize,
+        stride: usize,
+        padding: usize,
+    ) -> Result<Result<Tensor, String>, ConvDimError> {
+        let extent = match self.dim() {
+            0 => 0,
+            dim => (dim - 1).saturating_mul(stride).saturating_add(filter_size),
+        };
+        let mut scattered = Tensor::zeros(extent)?;
+        for (i, value) in self.data.iter().enumerate() {
+            for output in &mut scattered.data[i * stride..i * stride + filter_size] {
+                *output += value;
+            }
+        }
+        if 2 * padding >= extent {
+            return Ok(Err(format!(
+                "cropping {} on every side of {} leaves nothing",
+                padding, extent
+            )));
+        }
+        Ok(Ok(Tensor {
+            data: scattered.data[padding..extent - padding].to_vec(),
+        }))
+    }
+}
+
+/// ## Compare the dimensions computed for `graph` with the dimensions of zero-filled tensors.
+///
+/// Returns a line for every node with both dimensions, and whether all of them agree, or the error
+/// computing the dimensions. The nodes computed by a formula are taken as they are computed, except
+/// for the poolings in ceil mode, which are applied as such. Inputs and layers whose tensors would
+/// exceed [`MAX_DIM`] are an error.
+pub fn verify(graph: &Graph, in_dim: u32) -> Result<(Vec<String>, bool), ConvDimError> {
+    let mut tensors: HashMap<&str, Tensor> = HashMap::new();
+    for (name, shape) in graph.input_shapes(in_dim) {
+        tensors.insert(name, Tensor::zeros(shape.dim as usize)?);
+    }
+    let shapes: HashMap<String, u32> = graph
+        .shapes(in_dim)?
+        .into_iter()
+        .map(|(name, shape)| (name, shape.dim))
+        .collect();
+    let edges = graph.edges();
+    let mut readers: HashMap<&str, usize> = HashMap::new();
+    for inputs in edges.values() {
+        for input in inputs {
+            *readers.entry(input).or_insert(0) += 1;
+        }
+    }
+    let mut lines = vec![];
+    let mut agree = true;
+    for node in graph.order() {
+        let inputs = &edges[node.name.as_str()];
+        let input = &tensors[inputs[0]];
+        let (filter_size, stride, padding) = (
+            node.filter_size.unwrap_or(1) as usize,
+            node.stride as usize,
+            node.padding as usize,
+        );
+        let rounding = match (&node.formula, node.ceil_mode) {
+            (_, true) => Some(Rounding::Ceil),
+            (Some(_), false) => None,
+            (None, false) => Some(Rounding::Floor),
+        };
+        let computed = shapes[&node.name];
+        let output = match (node.op, rounding) {
+            // Other formulas have no numerical counterpart, so their outputs are taken as computed.
+            (_, None) => {
+                lines.push(format!("formula  {}: {}", node.name, computed));
+                Ok(Tensor::zeros(computed as usize)?)
+            }
+            (Op::Conv, Some(rounding)) => Ok(input.conv(filter_size, stride, padding, rounding)?),
+            (Op::Transposed, Some(_)) => input.transposed(filter_size, stride, padding)?,
+            // Merges, subnetworks and pass-through layers keep the dimension of their (first) input.
+            _ => Ok(Tensor::zeros(input.dim())?),
+        };
+        for input in inputs {
+            let count = readers.get_mut(input).expect("Every input is counted.");
+            *count -= 1;
+            if *count == 0 {
+                tensors.remove(input);
+            }
+        }
+        let output = match output {
+            // The formula nodes are reported above.
+            Ok(output) if rounding.is_none() => output,
+            Ok(output) => {
+                match output.dim() == computed as usize {
+                    true => lines.push(format!("ok       {}: {}", node.name, computed)),
+                    false => {
+                        agree = false;
+                        lines.push(format!(
+                            "MISMATCH {}: computed {}, numerically {}",
+                            node.name,
+                            computed,
+                            output.dim()
+                        ));
+                    }
+                }
+                output
+            }
+            Err(e) => {
+                agree = false;
+                lines.push(format!(
+                    "MISMATCH {}: computed {}, numerically {}",
+                    node.name, computed, e
+                ));
+                Tensor::zeros(computed as usize)?
+            }
+        };
+        // The outputs that no node reads are not kept.
+        if readers.contains_key(node.name.as_str()) {
+            tensors.insert(&node.name, output);
+        }
+    }
+    Ok((lines, agree))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tensors() {
+        let dim = |dim, filter_size, stride, padding, rounding| {
+            let tensor = Tensor::zeros(dim).unwrap();
+            tensor
+                .conv(filter_size, stride, padding, rounding)
+                .unwrap()
+                .dim()
+        };
+        assert_eq!(dim(7, 3, 2, 1, Rounding::Floor), 4);
+        assert_eq!(dim(7, 2, 2, 0, Rounding::Floor), 3);
+        assert_eq!(dim(7, 9, 1, 1, Rounding::Floor), 1);
+        // In ceil mode, a last window sticks out of the input, unless it starts in the padding.
+        assert_eq!(dim(7, 2, 2, 0, Rounding::Ceil), 4);
+        assert_eq!(dim(6, 3, 2, 0, Rounding::Ceil), 3);
+        assert_eq!(dim(3, 2, 2, 1, Rounding::Ceil), 2);
+        let input = Tensor::zeros(7).unwrap();
+        assert_eq!(input.transposed(4, 2, 1).unwrap().unwrap().dim(), 14);
+        assert_eq!(input.transposed(3, 1, 1).unwrap().unwrap().dim(), 7);
+        assert!(Tensor::zeros(1)
+            .unwrap()
+            .transposed(2, 1, 1)
+            .unwrap()
+            .is_err());
+        assert!(Tensor::zeros(MAX_DIM + 1).is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let graph: Graph = toml::from_str(
+            r#"
+            [[nodes]]
+            name = "down"
+            op = "conv"
+            filter_size = 3
+            stride = 2
+
+            [[nodes]]
+            name = "up"
+            op = "transposed"
+            filter_size = 3
+            stride = 2
+
+            [[nodes]]
+            name = "sum"
+            op = "add"
+            inputs = ["input", "up"]
+            "#,
+        )
+        .unwrap();
+        let (lines, agree) = verify(&graph, 33).unwrap();
+        assert!(agree);
+        assert_eq!(
+            lines,
+            vec!["ok       down: 16", "ok       up: 33", "ok       sum: 33"]
+        );
+
+        // The poolings of Caffe round up, and drop a last window starting in the padding.
+        for padding in 0..3 {
+            let architecture = crate::architecture::parse_architecture(&format!(
+                "[[layers]]\ntype = \"max_pool\"\nfilter_size = 3\nstride = 2\npadding = {}\n",
+                padding
+            ))
+            .unwrap();
+            let graph = crate::convention::Convention::Caffe
+                .apply(architecture)
+                .into_graph();
+            for in_dim in 3..40 {
+                let (lines, agree) = verify(&graph, in_dim).unwrap();
+                assert!(agree, "{:?}", lines);
+                assert!(lines[0].starts_with("ok "));
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_inputs() {
+        let graph: Graph = toml::from_str(
+            "[[nodes]]\nname = \"pool\"\nop = \"conv\"\nfilter_size = 2\nstride = 2\n",
+        )
+        .unwrap();
+        let (lines, agree) = verify(&graph, 100_000).unwrap();
+        assert!(agree);
+        assert_eq!(lines, vec!["ok       pool: 50000"]);
+        assert_eq!(
+            verify(&graph, u32::MAX).unwrap_err().to_string(),
+            "Unable to verify the dimensions numerically: a tensor of dimension 4294967295 \
+             exceeds the largest dimension of 16777216."
+        );
+    }
+}
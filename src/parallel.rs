@@ -0,0 +1,60 @@
+//! The evaluation of independent configurations on several threads, e.g. for searches over the
+//! kernels, strides and paddings of the layers.
+//!
+//! The items are evaluated on a [rayon] pool of the number of threads requested, whose threads
+//! steal work from each other, so that slow and fast configurations balance.
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+
+/// ## The number of threads to use for `requested` jobs, where 0 uses all available cores.
+pub fn jobs(requested: usize) -> usize {
+    match requested {
+        0 => std::thread::available_parallelism().map_or(1, |cores| cores.get()),
+        jobs => jobs,
+    }
+}
+
+/// ## Apply `f` to all `items` on up to `jobs` threads, returning the results in order.
+///
+/// With a single job or item, `f` is applied on the calling thread.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{conv_output_dim, parallel};
+/// let strides: Vec<u32> = (1..=4).collect();
+/// let dims = parallel::map(&strides, 2, |&stride| conv_output_dim(64u32, 3, 1, stride, 1));
+/// assert_eq!(dims, vec![Ok(64), Ok(32), Ok(22), Ok(16)]);
+/// ```
+pub fn map<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let jobs = jobs.clamp(1, items.len().max(1));
+    if jobs == 1 {
+        return items.iter().map(f).collect();
+    }
+    match ThreadPoolBuilder::new().num_threads(jobs).build() {
+        Ok(pool) => pool.install(|| items.par_iter().map(&f).collect()),
+        // Without threads, the items are still evaluated, only on the calling thread.
+        Err(_) => items.iter().map(f).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_keeps_the_order() {
+        let items: Vec<u32> = (0..1000).collect();
+        let squares: Vec<u32> = items.iter().map(|i| i * i).collect();
+        for jobs in [1, 3, 16] {
+            assert_eq!(map(&items, jobs, |i| i * i), squares);
+        }
+        assert_eq!(map(&[] as &[u32], 4, |i| *i), Vec::<u32>::new());
+        assert!(super::jobs(0) >= 1);
+    }
+}
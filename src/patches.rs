@@ -0,0 +1,119 @@
+//! The number of patches a sliding window extracts from an input, e.g. for the patch-based
+//! pipelines of self-supervised pretraining.
+//!
+//! Along every axis, the window moves like the filter of a convolution, so the number of patches
+//! is the output dimension of a convolution with the size, stride and padding of the window. This
+//! matches `torch.nn.Unfold` and the `extract_patches` of scikit-learn with an extraction step.
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::layers::conv_output_dim;
+use crate::trace::write_columns;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The number of patches along every axis of the input, and in total.
+pub struct Patches {
+    pub per_axis: Vec<u64>,
+    pub total: u64,
+}
+
+impl fmt::Display for Patches {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rows: Vec<Vec<String>> = self
+            .per_axis
+            .iter()
+            .enumerate()
+            .map(|(i, count)| vec![format!("axis {}:", i + 1), count.to_string()])
+            .collect();
+        rows.push(vec!["total:".to_string(), self.total.to_string()]);
+        write_columns(f, &rows, &[1])
+    }
+}
+
+fn invalid(message: String) -> ConvDimError {
+    ConvDimError::Patches { message }
+}
+
+/// ## The sizes of the axes given by `text`, separated by `x` or commas, e.g. `224x224` or `3,64`.
+pub fn sizes(text: &str) -> Result<Vec<u64>, ConvDimError> {
+    text.split(['x', ','])
+        .map(|size| size.trim().parse())
+        .collect::<Result<Vec<u64>, _>>()
+        .map_err(|_| invalid(format!("'{}' are not sizes like '224x224'.", text)))
+}
+
+/// ## The number of patches of size `window` extracted from an `input` with `stride` and `padding`.
+///
+/// The window, stride and padding are given for every axis of the input, or once for all of them.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::patches;
+/// let patches = patches::count(&[224, 224], &[16], &[16], &[0]).unwrap();
+/// assert_eq!((patches.per_axis, patches.total), (vec![14, 14], 196));
+/// ```
+pub fn count(
+    input: &[u64],
+    window: &[u64],
+    stride: &[u64],
+    padding: &[u64],
+) -> Result<Patches, ConvDimError> {
+    let mut axes = vec![];
+    for (name, values) in [("window", window), ("stride", stride), ("padding", padding)] {
+        if values.len() != 1 && values.len() != input.len() {
+            return Err(invalid(format!(
+                "the {} has {} sizes for an input with {} axes.",
+                name,
+                values.len(),
+                input.len()
+            )));
+        }
+    }
+    let at = |values: &[u64], i: usize| values[i.min(values.len() - 1)];
+    for (i, &size) in input.iter().enumerate() {
+        axes.push(conv_output_dim(
+            size,
+            at(window, i),
+            at(padding, i),
+            at(stride, i),
+            1,
+        )?);
+    }
+    let total = axes
+        .iter()
+        .try_fold(1u64, |total, &count| total.checked_mul(count))
+        .ok_or_else(|| invalid("the total number of patches exceeds the range of u64.".into()))?;
+    Ok(Patches {
+        per_axis: axes,
+        total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patches() {
+        assert_eq!(sizes("32x48"), Ok(vec![32, 48]));
+        assert!(sizes("32xx48").is_err());
+
+        // sklearn's extract_patches_2d extracts all the patches, i.e. with a step of one.
+        let patches = count(&[32, 48], &[8], &[1], &[0]).unwrap();
+        assert_eq!(patches.per_axis, vec![25, 41]);
+        assert_eq!(
+            patches.to_string(),
+            "axis 1:    25\naxis 2:    41\ntotal:   1025\n"
+        );
+
+        // nn.Unfold(kernel_size=(3, 5), stride=2, padding=(1, 0)) on a 7x10 input.
+        let patches = count(&[7, 10], &[3, 5], &[2], &[1, 0]).unwrap();
+        assert_eq!((patches.per_axis, patches.total), (vec![4, 3], 12));
+
+        assert!(count(&[7, 10], &[3, 5, 5], &[1], &[0]).is_err());
+        assert!(count(&[7], &[9], &[1], &[0]).is_err());
+    }
+}
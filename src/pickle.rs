@@ -0,0 +1,436 @@
+//! A minimal reader of Python pickles.
+//!
+//! Pickles are programs for a small stack machine that rebuilds the pickled objects. Instead of
+//! calling the classes and functions referred to, the objects they would create are represented
+//! by the class or function and its arguments, which is enough to read the structure of the
+//! modules and the shapes of the tensors stored by PyTorch.
+
+#[derive(Debug, Clone, PartialEq)]
+/// ## A value rebuilt from a pickle.
+pub enum Pickle {
+    None,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Tuple(Vec<Pickle>),
+    List(Vec<Pickle>),
+    Dict(Vec<(Pickle, Pickle)>),
+    /// A class or function, given by its module and name.
+    Global(String, String),
+    /// An object created by calling `callable` with `args`, whose `state` was set afterwards.
+    Object {
+        callable: Box<Pickle>,
+        args: Vec<Pickle>,
+        state: Option<Box<Pickle>>,
+    },
+    /// A reference to data stored outside of the pickle, e.g. the storage of a tensor.
+    Persistent(Box<Pickle>),
+}
+
+impl Pickle {
+    /// The entry `key` of a dictionary, or of the state of an object.
+    pub fn get(&self, key: &str) -> Option<&Pickle> {
+        match self {
+            Pickle::Dict(items) => items
+                .iter()
+                .find(|(name, _)| matches!(name, Pickle::String(name) if name == key))
+                .map(|(_, value)| value),
+            Pickle::Object {
+                state: Some(state), ..
+            } => state.get(key),
+            _ => None,
+        }
+    }
+
+    /// The qualified name of the class of an object, e.g. `torch.nn.modules.conv.Conv2d`.
+    pub fn class(&self) -> Option<String> {
+        match self {
+            Pickle::Object { callable, .. } => match callable.as_ref() {
+                Pickle::Global(module, name) => Some(format!("{}.{}", module, name)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Pickle::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// The opcodes of the pickle protocols 0 to 5 that are supported.
+mod op {
+    pub const MARK: u8 = b'(';
+    pub const STOP: u8 = b'.';
+    pub const POP: u8 = b'0';
+    pub const POP_MARK: u8 = b'1';
+    pub const BINFLOAT: u8 = b'G';
+    pub const BININT: u8 = b'J';
+    pub const BININT1: u8 = b'K';
+    pub const BININT2: u8 = b'M';
+    pub const NONE: u8 = b'N';
+    pub const BINPERSID: u8 = b'Q';
+    pub const REDUCE: u8 = b'R';
+    pub const BINUNICODE: u8 = b'X';
+    pub const EMPTY_LIST: u8 = b']';
+    pub const APPEND: u8 = b'a';
+    pub const BUILD: u8 = b'b';
+    pub const GLOBAL: u8 = b'c';
+    pub const APPENDS: u8 = b'e';
+    pub const BINGET: u8 = b'h';
+    pub const LONG_BINGET: u8 = b'j';
+    pub const BINPUT: u8 = b'q';
+    pub const LONG_BINPUT: u8 = b'r';
+    pub const SETITEM: u8 = b's';
+    pub const TUPLE: u8 = b't';
+    pub const SETITEMS: u8 = b'u';
+    pub const EMPTY_DICT: u8 = b'}';
+    pub const EMPTY_TUPLE: u8 = b')';
+    pub const BINBYTES: u8 = b'B';
+    pub const SHORT_BINBYTES: u8 = b'C';
+    pub const PROTO: u8 = 0x80;
+    pub const NEWOBJ: u8 = 0x81;
+    pub const TUPLE1: u8 = 0x85;
+    pub const TUPLE2: u8 = 0x86;
+    pub const TUPLE3: u8 = 0x87;
+    pub const NEWTRUE: u8 = 0x88;
+    pub const NEWFALSE: u8 = 0x89;
+    pub const LONG1: u8 = 0x8a;
+    pub const SHORT_BINUNICODE: u8 = 0x8c;
+    pub const BINUNICODE8: u8 = 0x8d;
+    pub const STACK_GLOBAL: u8 = 0x93;
+    pub const MEMOIZE: u8 = 0x94;
+    pub const FRAME: u8 = 0x95;
+}
+
+/// The state of the stack machine.
+struct Machine<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    stack: Vec<Pickle>,
+    /// The positions in `stack` of the marks pushed by `MARK`.
+    marks: Vec<usize>,
+    memo: std::collections::HashMap<u32, Pickle>,
+}
+
+impl<'a> Machine<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        match self.bytes.get(self.pos..self.pos + n) {
+            Some(bytes) => {
+                self.pos += n;
+                Ok(bytes)
+            }
+            None => Err("The pickle is truncated.".to_string()),
+        }
+    }
+
+    /// Read a little-endian unsigned integer of `n` bytes.
+    fn uint(&mut self, n: usize) -> Result<u64, String> {
+        Ok(self
+            .take(n)?
+            .iter()
+            .rev()
+            .fold(0, |value, &byte| (value << 8) | byte as u64))
+    }
+
+    /// Read a line, as used by the text opcodes of protocol 0.
+    fn line(&mut self) -> Result<String, String> {
+        let length = self.bytes[self.pos..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .ok_or_else(|| "The pickle is truncated.".to_string())?;
+        let line = String::from_utf8_lossy(self.take(length)?).into_owned();
+        self.pos += 1;
+        Ok(line)
+    }
+
+    fn string(&mut self, length_bytes: usize) -> Result<Pickle, String> {
+        let length = self.uint(length_bytes)? as usize;
+        Ok(Pickle::String(
+            String::from_utf8_lossy(self.take(length)?).into_owned(),
+        ))
+    }
+
+    fn pop(&mut self) -> Result<Pickle, String> {
+        self.stack
+            .pop()
+            .ok_or_else(|| "The stack of the pickle is empty.".to_string())
+    }
+
+    /// Pop the values pushed since the last mark, as well as the mark.
+    fn pop_mark(&mut self) -> Result<Vec<Pickle>, String> {
+        match self.marks.pop() {
+            Some(mark) if mark <= self.stack.len() => Ok(self.stack.split_off(mark)),
+            _ => Err("The pickle lacks a mark.".to_string()),
+        }
+    }
+
+    fn top(&mut self) -> Result<&mut Pickle, String> {
+        self.stack
+            .last_mut()
+            .ok_or_else(|| "The stack of the pickle is empty.".to_string())
+    }
+
+    fn run(&mut self) -> Result<Pickle, String> {
+        loop {
+            let opcode = self.take(1)?[0];
+            match opcode {
+                op::PROTO => self.pos += 1,
+                op::FRAME => self.pos += 8,
+                op::STOP => return self.pop(),
+                op::MARK => self.marks.push(self.stack.len()),
+                op::POP => {
+                    self.pop()?;
+                }
+                op::POP_MARK => {
+                    self.pop_mark()?;
+                }
+                op::NONE => self.stack.push(Pickle::None),
+                op::NEWTRUE => self.stack.push(Pickle::Bool(true)),
+                op::NEWFALSE => self.stack.push(Pickle::Bool(false)),
+                op::BININT => {
+                    let value = self.uint(4)? as u32 as i32;
+                    self.stack.push(Pickle::Int(value as i64));
+                }
+                op::BININT1 => {
+                    let value = self.uint(1)?;
+                    self.stack.push(Pickle::Int(value as i64));
+                }
+                op::BININT2 => {
+                    let value = self.uint(2)?;
+                    self.stack.push(Pickle::Int(value as i64));
+                }
+                op::LONG1 => {
+                    // A little-endian two's complement integer of the given number of bytes.
+                    let length = self.uint(1)? as usize;
+                    if length > 8 {
+                        return Err("Integers of the pickle exceed 64 bits.".to_string());
+                    }
+                    let value = self.uint(length)?;
+                    let shift = 64 - 8 * length as u32;
+                    let value = if length == 0 {
+                        0
+                    } else {
+                        ((value << shift) as i64) >> shift
+                    };
+                    self.stack.push(Pickle::Int(value));
+                }
+                op::BINFLOAT => {
+                    // Unlike the integers, the floats are big-endian.
+                    let bits = self
+                        .take(8)?
+                        .iter()
+                        .fold(0, |bits, &byte| (bits << 8) | byte as u64);
+                    let value = f64::from_bits(bits);
+                    self.stack.push(Pickle::Float(value));
+                }
+                op::SHORT_BINUNICODE => {
+                    let string = self.string(1)?;
+                    self.stack.push(string);
+                }
+                op::BINUNICODE => {
+                    let string = self.string(4)?;
+                    self.stack.push(string);
+                }
+                op::BINUNICODE8 => {
+                    let string = self.string(8)?;
+                    self.stack.push(string);
+                }
+                op::SHORT_BINBYTES | op::BINBYTES => {
+                    let length = self.uint(if opcode == op::BINBYTES { 4 } else { 1 })? as usize;
+                    let bytes = self.take(length)?.to_vec();
+                    self.stack.push(Pickle::Bytes(bytes));
+                }
+                op::EMPTY_TUPLE => self.stack.push(Pickle::Tuple(vec![])),
+                op::TUPLE => {
+                    let values = self.pop_mark()?;
+                    self.stack.push(Pickle::Tuple(values));
+                }
+                op::TUPLE1 | op::TUPLE2 | op::TUPLE3 => {
+                    let n = (opcode - op::TUPLE1 + 1) as usize;
+                    if self.stack.len() < n {
+                        return Err("The stack of the pickle is empty.".to_string());
+                    }
+                    let values = self.stack.split_off(self.stack.len() - n);
+                    self.stack.push(Pickle::Tuple(values));
+                }
+                op::EMPTY_LIST => self.stack.push(Pickle::List(vec![])),
+                op::EMPTY_DICT => self.stack.push(Pickle::Dict(vec![])),
+                op::APPEND | op::APPENDS => {
+                    let values = if opcode == op::APPEND {
+                        vec![self.pop()?]
+                    } else {
+                        self.pop_mark()?
+                    };
+                    // Lists of other classes (e.g. `torch.nn.ModuleList`) are extended like an object.
+                    match self.top()? {
+                        Pickle::List(list) => list.extend(values),
+                        Pickle::Object { args, .. } => args.extend(values),
+                        _ => return Err("Only lists can be appended to.".to_string()),
+                    }
+                }
+                op::SETITEM | op::SETITEMS => {
+                    let values = if opcode == op::SETITEM {
+                        let value = self.pop()?;
+                        vec![self.pop()?, value]
+                    } else {
+                        self.pop_mark()?
+                    };
+                    let mut values = values.into_iter();
+                    let mut items = vec![];
+                    while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                        items.push((key, value));
+                    }
+                    match self.top()? {
+                        Pickle::Dict(dict) => dict.extend(items),
+                        // E.g. an `OrderedDict`.
+                        Pickle::Object { state, .. } => {
+                            let state = state.get_or_insert_with(|| Box::new(Pickle::Dict(vec![])));
+                            if let Pickle::Dict(dict) = state.as_mut() {
+                                dict.extend(items);
+                            }
+                        }
+                        _ => return Err("Only dictionaries can be assigned items.".to_string()),
+                    }
+                }
+                op::GLOBAL => {
+                    let module = self.line()?;
+                    let name = self.line()?;
+                    self.stack.push(Pickle::Global(module, name));
+                }
+                op::STACK_GLOBAL => {
+                    let name = self.pop()?;
+                    let module = self.pop()?;
+                    match (module, name) {
+                        (Pickle::String(module), Pickle::String(name)) => {
+                            self.stack.push(Pickle::Global(module, name))
+                        }
+                        _ => return Err("Invalid global of the pickle.".to_string()),
+                    }
+                }
+                op::REDUCE | op::NEWOBJ => {
+                    let args = match self.pop()? {
+                        Pickle::Tuple(args) => args,
+                        _ => return Err("The arguments of a call are not a tuple.".to_string()),
+                    };
+                    let callable = Box::new(self.pop()?);
+                    self.stack.push(Pickle::Object {
+                        callable,
+                        args,
+                        state: None,
+                    });
+                }
+                op::BUILD => {
+                    let value = self.pop()?;
+                    match self.top()? {
+                        Pickle::Object { state, .. } => *state = Some(Box::new(value)),
+                        _ => return Err("Only objects can be assigned a state.".to_string()),
+                    }
+                }
+                op::BINPERSID => {
+                    let id = self.pop()?;
+                    self.stack.push(Pickle::Persistent(Box::new(id)));
+                }
+                op::BINPUT | op::LONG_BINPUT => {
+                    let index = self.uint(if opcode == op::BINPUT { 1 } else { 4 })? as u32;
+                    let value = self.top()?.clone();
+                    self.memo.insert(index, value);
+                }
+                op::MEMOIZE => {
+                    let value = self.top()?.clone();
+                    self.memo.insert(self.memo.len() as u32, value);
+                }
+                op::BINGET | op::LONG_BINGET => {
+                    let index = self.uint(if opcode == op::BINGET { 1 } else { 4 })? as u32;
+                    match self.memo.get(&index) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => {
+                            return Err(format!("The memo of the pickle lacks entry {}.", index))
+                        }
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "Unsupported opcode 0x{:02x} at position {} of the pickle.",
+                        opcode,
+                        self.pos - 1
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// ## Rebuild the value pickled in `bytes`.
+///
+/// Values that are memoized and referred to later are copies, i.e. changes made to a value
+/// after it has been memoized are not reflected by its later references.
+pub fn load(bytes: &[u8]) -> Result<Pickle, String> {
+    Machine {
+        bytes,
+        pos: 0,
+        stack: vec![],
+        marks: vec![],
+        memo: Default::default(),
+    }
+    .run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        // pickle.dumps({"a": (1, -2, 300), "b": [None, True, 1.5, "x"]}, protocol=2)
+        let bytes = b"\x80\x02}q\x00(X\x01\x00\x00\x00aq\x01K\x01J\xfe\xff\xff\xffM,\x01\x87q\x02X\x01\x00\x00\x00bq\x03]q\x04(N\x88G?\xf8\x00\x00\x00\x00\x00\x00X\x01\x00\x00\x00xq\x05eu.";
+        let value = load(bytes).unwrap();
+        assert_eq!(
+            value.get("a"),
+            Some(&Pickle::Tuple(vec![
+                Pickle::Int(1),
+                Pickle::Int(-2),
+                Pickle::Int(300)
+            ]))
+        );
+        assert_eq!(
+            value.get("b"),
+            Some(&Pickle::List(vec![
+                Pickle::None,
+                Pickle::Bool(true),
+                Pickle::Float(1.5),
+                Pickle::String("x".to_string())
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_objects() {
+        // An object of class `m.C` with state {"n": 7}, and a call of `f.g` with a persistent id.
+        let bytes = b"\x80\x02cm\nC\nq\x00)\x81}X\x01\x00\x00\x00nK\x07sbcf\ng\n(X\x01\x00\x00\x00sQK\x00tR\x86.";
+        let value = load(bytes).unwrap();
+        let objects = match value {
+            Pickle::Tuple(objects) => objects,
+            _ => panic!("Expected a tuple."),
+        };
+        assert_eq!(objects[0].class(), Some("m.C".to_string()));
+        assert_eq!(objects[0].get("n").and_then(Pickle::as_i64), Some(7));
+        assert_eq!(
+            objects[1],
+            Pickle::Object {
+                callable: Box::new(Pickle::Global("f".to_string(), "g".to_string())),
+                args: vec![
+                    Pickle::Persistent(Box::new(Pickle::String("s".to_string()))),
+                    Pickle::Int(0)
+                ],
+                state: None,
+            }
+        );
+        assert!(load(b"\x80\x02K").is_err());
+    }
+}
@@ -0,0 +1,377 @@
+//! Plots of the output dimension, parameters and FLOPs of the layers against their depth, e.g. for
+//! reports and slides.
+//!
+//! The plots are drawn on a [`Canvas`], which is either an SVG document or a bitmap drawn and
+//! encoded as PNG by [plotters]. Text on bitmaps would need a system font in plotters, so the labels
+//! of the PNG are written in a small bitmap font of digits and capital letters.
+use std::convert::TryFrom;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+use plotters::prelude::{BitMapBackend, DrawingBackend, RGBColor};
+
+use crate::error::ConvDimError;
+use crate::export::svg::escape;
+use crate::graph::Graph;
+use crate::report::abbreviate;
+use crate::summary::{self, Row};
+
+const WIDTH: u32 = 720;
+/// The height of the plot of every series.
+const PANEL_HEIGHT: u32 = 220;
+/// The space left of the axes for the labels of the values, and around the other sides.
+const MARGIN_LEFT: u32 = 80;
+const MARGIN: u32 = 30;
+
+const BACKGROUND: [u8; 3] = [255, 255, 255];
+const AXES: [u8; 3] = [51, 51, 51];
+const TEXT: [u8; 3] = [34, 34, 34];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## A quantity of the layers which is plotted against their depth.
+pub enum Series {
+    Dims,
+    Params,
+    Flops,
+}
+
+impl Series {
+    fn title(self) -> &'static str {
+        match self {
+            Series::Dims => "OUTPUT DIMENSION",
+            Series::Params => "PARAMETERS",
+            Series::Flops => "FLOPS",
+        }
+    }
+
+    fn color(self) -> [u8; 3] {
+        match self {
+            Series::Dims => [31, 119, 180],
+            Series::Params => [44, 160, 44],
+            Series::Flops => [214, 39, 40],
+        }
+    }
+
+    /// The value of the series for `row`, if it is known.
+    fn value(self, row: &Row) -> Option<u64> {
+        match self {
+            Series::Dims => Some(row.shape.dim.into()),
+            Series::Params => row.params,
+            Series::Flops => row.flops,
+        }
+    }
+}
+
+/// ## A surface on which the plots are drawn, with the origin at the top left.
+pub trait Canvas {
+    /// Fill the rectangle of the given size whose top left corner is at `(x, y)`.
+    fn rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: [u8; 3]);
+
+    /// Draw a line between the points `from` and `to`.
+    fn line(&mut self, from: (u32, u32), to: (u32, u32), color: [u8; 3]);
+
+    /// Write `text` with its top left corner at `(x, y)`, or its top right corner if `right`.
+    fn text(&mut self, x: u32, y: u32, text: &str, right: bool);
+}
+
+/// ## Draw the `series` of the layers summarized in `rows` on `canvas`, one below the other.
+///
+/// The canvas needs to be 720 pixels wide and 220 pixels high for every series.
+pub fn draw(canvas: &mut impl Canvas, rows: &[Row], series: &[Series]) {
+    let (left, right) = (MARGIN_LEFT, WIDTH - MARGIN);
+    let last = rows.len().saturating_sub(1).max(1) as u32;
+    let x = |depth: usize| left + depth as u32 * (right - left) / last;
+    canvas.rect(0, 0, WIDTH, series.len() as u32 * PANEL_HEIGHT, BACKGROUND);
+    for (i, &series) in series.iter().enumerate() {
+        let (top, bottom) = (
+            i as u32 * PANEL_HEIGHT + MARGIN,
+            (i as u32 + 1) * PANEL_HEIGHT - MARGIN,
+        );
+        let values: Vec<Option<u64>> = rows.iter().map(|row| series.value(row)).collect();
+        let max = values.iter().flatten().copied().max().unwrap_or(0).max(1);
+        let y = |value: u64| {
+            bottom - (u128::from(value) * u128::from(bottom - top) / u128::from(max)) as u32
+        };
+
+        canvas.text(left, top - 20, series.title(), false);
+        canvas.line((left, top), (left, bottom), AXES);
+        canvas.line((left, bottom), (right, bottom), AXES);
+        canvas.text(left - 8, top - 4, &abbreviate(max as f64, ""), true);
+        canvas.text(left - 8, bottom - 8, "0", true);
+        canvas.text(left, bottom + 8, "0", false);
+        canvas.text(
+            right,
+            bottom + 8,
+            &format!("DEPTH {}", rows.len() - 1),
+            true,
+        );
+
+        let points: Vec<Option<(u32, u32)>> = values
+            .iter()
+            .enumerate()
+            .map(|(depth, value)| value.map(|value| (x(depth), y(value))))
+            .collect();
+        for pair in points.windows(2) {
+            if let [Some(from), Some(to)] = pair {
+                canvas.line(*from, *to, series.color());
+            }
+        }
+        for (x, y) in points.into_iter().flatten() {
+            canvas.rect(x - 2, y - 2, 5, 5, series.color());
+        }
+    }
+}
+
+/// ## An SVG document, whose drawing is appended to its body.
+pub struct Svg {
+    pub body: String,
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+impl Canvas for Svg {
+    fn rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: [u8; 3]) {
+        self.body += &format!(
+            "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+            x,
+            y,
+            width,
+            height,
+            hex(color)
+        );
+    }
+
+    fn line(&mut self, (x1, y1): (u32, u32), (x2, y2): (u32, u32), color: [u8; 3]) {
+        self.body += &format!(
+            "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"2\"/>\n",
+            x1,
+            y1,
+            x2,
+            y2,
+            hex(color)
+        );
+    }
+
+    fn text(&mut self, x: u32, y: u32, text: &str, right: bool) {
+        // The baseline of the text is below its top.
+        self.body += &format!(
+            "  <text x=\"{}\" y=\"{}\" text-anchor=\"{}\">{}</text>\n",
+            x,
+            y + 11,
+            if right { "end" } else { "start" },
+            escape(text)
+        );
+    }
+}
+
+/// ## Plot the `series` of the layers of `graph` for an input of dimension `in_dim` as SVG.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::plot::{self, Series};
+/// # use convdim::{Architecture, Network};
+/// let network = Network::builder().conv(3, 2, 1).conv_t(2, 2, 0).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let svg = plot::svg(&graph, 64, &[Series::Dims]).unwrap();
+/// assert!(svg.starts_with("<svg"));
+/// ```
+pub fn svg(graph: &Graph, in_dim: u32, series: &[Series]) -> Result<String, ConvDimError> {
+    let rows = summary::rows(graph, in_dim)?;
+    let mut svg = Svg {
+        body: String::new(),
+    };
+    draw(&mut svg, &rows, series);
+    Ok(format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+         font-family=\"sans-serif\" font-size=\"12\" fill=\"{}\">\n{}</svg>\n",
+        WIDTH,
+        series.len() as u32 * PANEL_HEIGHT,
+        hex(TEXT),
+        svg.body
+    ))
+}
+
+/// The glyphs of the bitmap font, three pixels wide and five high, with one bit per pixel.
+const GLYPHS: &[(char, [u8; 5])] = &[
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b001, 0b001, 0b001]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+];
+
+/// The size of a pixel of the glyphs, and the horizontal space a character takes.
+const GLYPH_SCALE: u32 = 2;
+const ADVANCE: u32 = 4 * GLYPH_SCALE;
+
+/// ## A bitmap drawn by the bitmap backend of plotters, which writes it as PNG once presented.
+pub struct Bitmap<'a> {
+    pub backend: BitMapBackend<'a>,
+}
+
+impl Bitmap<'_> {
+    fn rgb([r, g, b]: [u8; 3]) -> RGBColor {
+        RGBColor(r, g, b)
+    }
+
+    fn coord(x: u32, y: u32) -> (i32, i32) {
+        (
+            i32::try_from(x).unwrap_or(i32::MAX),
+            i32::try_from(y).unwrap_or(i32::MAX),
+        )
+    }
+}
+
+// Drawing on the bitmap only fails for a bitmap writing to a closed stream, so the results are
+// ignored and the errors come from `present`.
+impl Canvas for Bitmap<'_> {
+    fn rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: [u8; 3]) {
+        if width > 0 && height > 0 {
+            let _ = self.backend.draw_rect(
+                Bitmap::coord(x, y),
+                Bitmap::coord(x.saturating_add(width - 1), y.saturating_add(height - 1)),
+                &Bitmap::rgb(color),
+                true,
+            );
+        }
+    }
+
+    fn line(&mut self, (x1, y1): (u32, u32), (x2, y2): (u32, u32), color: [u8; 3]) {
+        // The lines are two pixels thick, like the lines of the SVG.
+        for (ox, oy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let _ = self.backend.draw_line(
+                Bitmap::coord(x1 + ox, y1 + oy),
+                Bitmap::coord(x2 + ox, y2 + oy),
+                &Bitmap::rgb(color),
+            );
+        }
+    }
+
+    fn text(&mut self, x: u32, y: u32, text: &str, right: bool) {
+        let width = text.chars().count() as u32 * ADVANCE;
+        let x = match right {
+            true => x.saturating_sub(width),
+            false => x,
+        };
+        for (i, c) in text.chars().enumerate() {
+            let rows = match GLYPHS
+                .iter()
+                .find(|(glyph, _)| *glyph == c.to_ascii_uppercase())
+            {
+                Some((_, rows)) => rows,
+                None => continue,
+            };
+            let left = x + i as u32 * ADVANCE;
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        let (px, py) = (left + column * GLYPH_SCALE, y + row as u32 * GLYPH_SCALE);
+                        self.rect(px, py, GLYPH_SCALE, GLYPH_SCALE, TEXT);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// ## Plot the `series` of the layers of `graph` for an input of dimension `in_dim` as the PNG `path`.
+///
+/// PNG files cannot be written by the WebAssembly build, which has no file system.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn png(graph: &Graph, in_dim: u32, series: &[Series], path: &Path) -> Result<(), ConvDimError> {
+    let rows = summary::rows(graph, in_dim)?;
+    let mut bitmap = Bitmap {
+        backend: BitMapBackend::new(path, (WIDTH, series.len() as u32 * PANEL_HEIGHT)),
+    };
+    draw(&mut bitmap, &rows, series);
+    bitmap.backend.present().map_err(|e| ConvDimError::Plot {
+        message: format!("'{}' cannot be written: {}.", path.display(), e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_plots() {
+        let graph = parse_architecture(
+            r#"
+            input_channels = 3
+
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            stride = 2
+            padding = 1
+            channels = 16
+
+            [[layers]]
+            type = "conv_transpose"
+            filter_size = 2
+            stride = 2
+            channels = 3
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let series = [Series::Dims, Series::Params];
+
+        let svg = svg(&graph, 64, &series).unwrap();
+        assert!(svg.contains("height=\"440\""));
+        assert!(svg.contains(">OUTPUT DIMENSION</text>"));
+        assert!(svg.contains(">DEPTH 2</text>"));
+        // The dimensions 64, 32 and 64 are joined by two lines.
+        assert_eq!(svg.matches("stroke=\"#1f77b4\"").count(), 2);
+
+        let path = std::env::temp_dir().join(format!("convdim-plot-{}.png", std::process::id()));
+        png(&graph, 64, &series, &path).unwrap();
+        let image = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(image.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR"));
+        assert_eq!(image[16..24], [0, 0, 2, 208, 0, 0, 1, 184]);
+
+        let missing = Path::new("/nonexistent/plot.png");
+        assert!(matches!(
+            png(&graph, 64, &series, missing),
+            Err(ConvDimError::Plot { .. })
+        ));
+    }
+}
@@ -0,0 +1,187 @@
+//! Custom layer types, whose arithmetic is provided by the user of the library.
+//!
+//! Models often contain an operation that convdim does not know, e.g. a custom pooling or a
+//! learned resampler. Such a layer type is registered with a function mapping the parameters of
+//! the layer to its [`Geometry`], i.e. the (transposed) convolution it is equivalent to as far as
+//! the dimensions are concerned, like an upsampling is a transposed convolution whose filter size
+//! and stride are its scale. Once registered, the type can be used in the list format like the
+//! built-in types, and layers of that class are recognized in the printouts of PyTorch models.
+//!
+//! The types are registered for the whole process, and must be registered before the networks
+//! using them are read.
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use crate::error::ConvDimError;
+
+/// The parameters of a custom layer, i.e. all its keys except for its `type` and `name`.
+pub type Params = toml::value::Table;
+
+/// The function computing the geometry of a custom layer from its parameters.
+pub type GeometryFn = dyn Fn(&Params) -> Result<Geometry, String> + Send + Sync;
+
+/// The types of the list format, which cannot be registered.
+pub(crate) const BUILTIN_TYPES: &[&str] = &[
+    "conv",
+    "conv_transpose",
+    "max_pool",
+    "upsample",
+    "formula",
+    "batchnorm",
+    "relu",
+    "dropout",
+    "identity",
+];
+
+static REGISTRY: RwLock<BTreeMap<String, Arc<GeometryFn>>> = RwLock::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## The (transposed) convolution a custom layer is equivalent to.
+pub struct Geometry {
+    pub filter_size: u16,
+    pub stride: u16,
+    pub padding: u16,
+    pub transposed: bool,
+}
+
+impl Geometry {
+    /// ## A convolution with the given `filter_size`, `stride` and `padding`.
+    pub fn conv(filter_size: u16, stride: u16, padding: u16) -> Geometry {
+        Geometry {
+            filter_size,
+            stride,
+            padding,
+            transposed: false,
+        }
+    }
+
+    /// ## A transposed convolution with the given `filter_size`, `stride` and `padding`.
+    pub fn transposed(filter_size: u16, stride: u16, padding: u16) -> Geometry {
+        Geometry {
+            filter_size,
+            stride,
+            padding,
+            transposed: true,
+        }
+    }
+}
+
+/// ## Register the layer type `kind`, whose geometry is computed by `geometry`.
+///
+/// A type registered before is replaced. Returns an error if `kind` is a built-in type.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::plugin::{self, Geometry};
+/// # use convdim::{dim_after_layers, parse_architecture, Architecture};
+/// plugin::register("resampler", |params| {
+///     let factor = params.get("factor").and_then(|f| f.as_integer()).ok_or("needs a 'factor'")?;
+///     Ok(Geometry::transposed(factor as u16, factor as u16, 0))
+/// })
+/// .unwrap();
+/// let text = "[[layers]]\ntype = \"resampler\"\nfactor = 4\n";
+/// match parse_architecture(text).unwrap() {
+///     Architecture::Layers(layers) => assert_eq!(dim_after_layers(&layers.layers, 8), Ok(32)),
+///     Architecture::Graph(_) => unreachable!(),
+/// }
+/// ```
+pub fn register<F>(kind: &str, geometry: F) -> Result<(), ConvDimError>
+where
+    F: Fn(&Params) -> Result<Geometry, String> + Send + Sync + 'static,
+{
+    if BUILTIN_TYPES.contains(&kind) {
+        return Err(ConvDimError::CustomLayer {
+            kind: kind.to_string(),
+            message: "the type is built in and cannot be registered.".to_string(),
+        });
+    }
+    REGISTRY
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(kind.to_string(), Arc::new(geometry));
+    Ok(())
+}
+
+/// ## Remove the registered layer type `kind`, returning whether it was registered.
+pub fn unregister(kind: &str) -> bool {
+    REGISTRY
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .remove(kind)
+        .is_some()
+}
+
+/// The function registered for the layer type `kind`.
+fn lookup(kind: &str) -> Option<Arc<GeometryFn>> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(kind)
+        .cloned()
+}
+
+/// ## Whether the layer type `kind` is registered.
+pub fn is_registered(kind: &str) -> bool {
+    lookup(kind).is_some()
+}
+
+/// ## The registered layer types, in alphabetical order.
+pub fn registered() -> Vec<String> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .keys()
+        .cloned()
+        .collect()
+}
+
+/// ## The geometry of a layer of the registered type `kind` with the parameters `params`.
+pub fn geometry(kind: &str, params: &Params) -> Result<Geometry, ConvDimError> {
+    // The function is called without holding the lock, so that it may register types itself.
+    let function = lookup(kind).ok_or_else(|| ConvDimError::UnknownLayerType {
+        kind: kind.to_string(),
+    })?;
+    function(params).map_err(|message| ConvDimError::CustomLayer {
+        kind: kind.to_string(),
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let pool = |params: &Params| match params.get("window").and_then(toml::Value::as_integer) {
+            Some(window) if window > 0 => Ok(Geometry::conv(window as u16, window as u16, 0)),
+            _ => Err("the 'window' needs to be a positive integer.".to_string()),
+        };
+        assert!(register("max_pool", pool).is_err());
+        register("test_registry_pool", pool).unwrap();
+        assert!(is_registered("test_registry_pool"));
+        assert!(registered().contains(&"test_registry_pool".to_string()));
+
+        let mut params = Params::new();
+        params.insert("window".to_string(), toml::Value::Integer(3));
+        assert_eq!(
+            geometry("test_registry_pool", &params),
+            Ok(Geometry::conv(3, 3, 0))
+        );
+        params.insert("window".to_string(), toml::Value::Integer(0));
+        assert_eq!(
+            geometry("test_registry_pool", &params).unwrap_err().to_string(),
+            "Invalid layer of type 'test_registry_pool': the 'window' needs to be a positive integer."
+        );
+
+        assert!(unregister("test_registry_pool"));
+        assert!(!unregister("test_registry_pool"));
+        assert_eq!(
+            geometry("test_registry_pool", &params),
+            Err(ConvDimError::UnknownLayerType {
+                kind: "test_registry_pool".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! Progress reports of long-running evaluations, e.g. of a dataset scan or a stream of candidates.
+//!
+//! The report is a single line on the standard error, drawn by [indicatif] with the number of
+//! items evaluated, of those matching, and a bar with the estimated time left if the number of
+//! items is known. It is only drawn on terminals, so that the logs of scripts and CI jobs stay
+//! clean, and can be turned off by `--quiet`. The counters are atomic, so a report can be shared
+//! by the threads of [`parallel::map`](crate::parallel::map).
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
+
+#[derive(Debug)]
+/// ## The progress of the evaluation of items, drawn on the standard error.
+///
+/// The estimated time left is only reported once the total number of items is known.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::progress::Progress;
+/// let mut progress = Progress::hidden("images");
+/// progress.set_total(10);
+/// progress.add(4, 3);
+/// assert_eq!(progress.counts(), (4, 3));
+/// progress.finish();
+/// ```
+pub struct Progress {
+    unit: &'static str,
+    bar: ProgressBar,
+    matching: Arc<AtomicU64>,
+}
+
+impl Progress {
+    /// A report of the evaluation of `unit`s that is never drawn, e.g. for library users.
+    pub fn hidden(unit: &'static str) -> Progress {
+        Progress::new(unit, ProgressDrawTarget::hidden())
+    }
+
+    /// A report drawn on the standard error if it is a terminal, unless it is `quiet`.
+    pub fn stderr(unit: &'static str, quiet: bool) -> Progress {
+        match quiet {
+            true => Progress::hidden(unit),
+            // indicatif only draws on terminals, at most 20 times per second.
+            false => Progress::new(unit, ProgressDrawTarget::stderr()),
+        }
+    }
+
+    fn new(unit: &'static str, target: ProgressDrawTarget) -> Progress {
+        let progress = Progress {
+            unit,
+            bar: ProgressBar::with_draw_target(None, target),
+            matching: Arc::new(AtomicU64::new(0)),
+        };
+        progress.bar.set_style(progress.style());
+        progress
+    }
+
+    /// The style of the line, with a bar and the time left once the total is known.
+    fn style(&self) -> ProgressStyle {
+        let template = match self.bar.length() {
+            Some(_) => format!(
+                "[{{bar:30}}] {{pos}}/{{len}} {} ({{percent}}%), {{matching}} matching, \
+                 {{elapsed}} elapsed, ETA {{eta}}",
+                self.unit
+            ),
+            None => format!(
+                "{{pos}} {}, {{matching}} matching, {{elapsed}} elapsed, {{rate}}/s",
+                self.unit
+            ),
+        };
+        let matching = Arc::clone(&self.matching);
+        ProgressStyle::with_template(&template)
+            .expect("The template of the progress is valid.")
+            .progress_chars("## ")
+            .with_key(
+                "matching",
+                move |_: &ProgressState, w: &mut dyn std::fmt::Write| {
+                    let _ = write!(w, "{}", matching.load(Ordering::Relaxed));
+                },
+            )
+            .with_key(
+                "rate",
+                |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+                    let _ = write!(w, "{:.0}", state.per_sec());
+                },
+            )
+    }
+
+    /// Set the total number of items, once it is known.
+    pub fn set_total(&mut self, total: u64) {
+        self.bar.set_length(total);
+        self.bar.set_style(self.style());
+    }
+
+    /// The number of items evaluated and of those matching.
+    pub fn counts(&self) -> (u64, u64) {
+        (self.bar.position(), self.matching.load(Ordering::Relaxed))
+    }
+
+    /// ## Count `evaluated` more items, of which `matching` matched, and redraw the line if due.
+    pub fn add(&self, evaluated: u64, matching: u64) {
+        self.matching.fetch_add(matching, Ordering::Relaxed);
+        self.bar.inc(evaluated);
+    }
+
+    /// ## Clear the line, once all the items are evaluated.
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts() {
+        let mut progress = Progress::hidden("images");
+        progress.set_total(40);
+        progress.add(10, 8);
+        assert_eq!(progress.counts(), (10, 8));
+        assert_eq!(progress.bar.length(), Some(40));
+        assert!(progress.bar.is_hidden());
+
+        let progress = Progress::stderr("candidates", true);
+        crate::parallel::map(&[1u64; 1000], 4, |&item| progress.add(item, item));
+        assert_eq!(progress.counts(), (1000, 1000));
+        assert!(progress.bar.is_hidden());
+    }
+}
@@ -0,0 +1,268 @@
+//! A minimal reader and writer for the protocol buffers wire format.
+//!
+//! Only the wire format itself is handled: a message is a sequence of fields, each consisting
+//! of a field number and a value of one of the wire types. The interpretation of the fields is
+//! left to the caller, which knows the schema of the message (e.g. the ONNX model format).
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// ## The value of a field, by wire type.
+pub enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+#[derive(Debug, Clone, Default)]
+/// ## A decoded message, consisting of its fields in the order in which they were read.
+pub struct Message<'a> {
+    pub fields: Vec<(u32, Value<'a>)>,
+}
+
+/// Read a base 128 varint from the start of `bytes`, advancing `pos`.
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = match bytes.get(*pos) {
+            Some(&byte) => byte,
+            None => return Err("Unexpected end of message while reading a varint.".to_string()),
+        };
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("Varint is longer than 64 bits.".to_string())
+}
+
+/// Read `n` bytes from `bytes`, advancing `pos`.
+fn read_bytes<'a>(bytes: &'a [u8], pos: &mut usize, n: usize) -> Result<&'a [u8], String> {
+    // The length is read from the message, so it may point far beyond its end.
+    match pos.checked_add(n).and_then(|end| bytes.get(*pos..end)) {
+        Some(slice) => {
+            *pos += n;
+            Ok(slice)
+        }
+        None => Err("Unexpected end of message while reading a field.".to_string()),
+    }
+}
+
+impl<'a> Message<'a> {
+    /// ## Decode a message from its wire format.
+    pub fn decode(bytes: &'a [u8]) -> Result<Message<'a>, String> {
+        let mut fields = vec![];
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let key = read_varint(bytes, &mut pos)?;
+            let field = (key >> 3) as u32;
+            let value = match key & 0x7 {
+                0 => Value::Varint(read_varint(bytes, &mut pos)?),
+                1 => {
+                    let mut buffer = [0u8; 8];
+                    buffer.copy_from_slice(read_bytes(bytes, &mut pos, 8)?);
+                    Value::Fixed64(u64::from_le_bytes(buffer))
+                }
+                2 => {
+                    let length =
+                        usize::try_from(read_varint(bytes, &mut pos)?).unwrap_or(usize::MAX);
+                    Value::Bytes(read_bytes(bytes, &mut pos, length)?)
+                }
+                5 => {
+                    let mut buffer = [0u8; 4];
+                    buffer.copy_from_slice(read_bytes(bytes, &mut pos, 4)?);
+                    Value::Fixed32(u32::from_le_bytes(buffer))
+                }
+                wire_type => return Err(format!("Unsupported wire type {}.", wire_type)),
+            };
+            fields.push((field, value));
+        }
+        Ok(Message { fields })
+    }
+
+    /// All values of the field with number `field`.
+    pub fn all(&self, field: u32) -> impl Iterator<Item = Value<'a>> + '_ {
+        self.fields
+            .iter()
+            .filter(move |(number, _)| *number == field)
+            .map(|(_, value)| *value)
+    }
+
+    /// The last value of the field with number `field`, which is the one that counts for singular fields.
+    pub fn get(&self, field: u32) -> Option<Value<'a>> {
+        self.all(field).last()
+    }
+
+    /// The string value of the field with number `field`.
+    pub fn string(&self, field: u32) -> Option<String> {
+        match self.get(field) {
+            Some(Value::Bytes(bytes)) => Some(String::from_utf8_lossy(bytes).into_owned()),
+            _ => None,
+        }
+    }
+
+    /// All string values of the repeated field with number `field`.
+    pub fn strings(&self, field: u32) -> Vec<String> {
+        self.all(field)
+            .filter_map(|value| match value {
+                Value::Bytes(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The varint value of the field with number `field`.
+    pub fn varint(&self, field: u32) -> Option<u64> {
+        match self.get(field) {
+            Some(Value::Varint(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// All varint values of the repeated field with number `field`, packed or not.
+    pub fn varints(&self, field: u32) -> Result<Vec<u64>, String> {
+        let mut values = vec![];
+        for value in self.all(field) {
+            match value {
+                Value::Varint(value) => values.push(value),
+                Value::Bytes(bytes) => {
+                    let mut pos = 0;
+                    while pos < bytes.len() {
+                        values.push(read_varint(bytes, &mut pos)?);
+                    }
+                }
+                _ => return Err(format!("Field {} is not a varint.", field)),
+            }
+        }
+        Ok(values)
+    }
+
+    /// All 32 bit float values of the repeated field with number `field`, packed or not.
+    pub fn floats(&self, field: u32) -> Result<Vec<f32>, String> {
+        let mut values = vec![];
+        for value in self.all(field) {
+            match value {
+                Value::Fixed32(bits) => values.push(f32::from_bits(bits)),
+                Value::Bytes(bytes) if bytes.len() % 4 == 0 => values.extend(
+                    bytes
+                        .chunks(4)
+                        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])),
+                ),
+                _ => return Err(format!("Field {} is not a float.", field)),
+            }
+        }
+        Ok(values)
+    }
+
+    /// All embedded messages of the repeated field with number `field`.
+    pub fn messages(&self, field: u32) -> Result<Vec<Message<'a>>, String> {
+        self.all(field)
+            .map(|value| match value {
+                Value::Bytes(bytes) => Message::decode(bytes),
+                _ => Err(format!("Field {} is not a message.", field)),
+            })
+            .collect()
+    }
+
+    /// The embedded message of the field with number `field`.
+    pub fn message(&self, field: u32) -> Result<Option<Message<'a>>, String> {
+        Ok(self.messages(field)?.pop())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// ## A writer for messages in the wire format.
+pub struct Encoder {
+    bytes: Vec<u8>,
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+impl Encoder {
+    pub fn new() -> Encoder {
+        Encoder::default()
+    }
+
+    /// Append a varint field. Negative integers are encoded in two's complement as in `int64` fields.
+    pub fn varint(mut self, field: u32, value: i64) -> Encoder {
+        write_varint(&mut self.bytes, (field as u64) << 3);
+        write_varint(&mut self.bytes, value as u64);
+        self
+    }
+
+    /// Append a 32 bit float field.
+    pub fn float(mut self, field: u32, value: f32) -> Encoder {
+        write_varint(&mut self.bytes, ((field as u64) << 3) | 5);
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /// Append a length-delimited field.
+    pub fn bytes(mut self, field: u32, value: &[u8]) -> Encoder {
+        write_varint(&mut self.bytes, ((field as u64) << 3) | 2);
+        write_varint(&mut self.bytes, value.len() as u64);
+        self.bytes.extend_from_slice(value);
+        self
+    }
+
+    /// Append a string field.
+    pub fn string(self, field: u32, value: &str) -> Encoder {
+        self.bytes(field, value.as_bytes())
+    }
+
+    /// Append an embedded message.
+    pub fn message(self, field: u32, message: Encoder) -> Encoder {
+        self.bytes(field, &message.bytes)
+    }
+
+    /// The encoded message.
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = Encoder::new()
+            .varint(1, 300)
+            .varint(2, -1)
+            .string(3, "conv")
+            .float(4, 2.0)
+            .message(5, Encoder::new().varint(1, 7))
+            .bytes(6, &[1, 2, 0x80, 0x01])
+            .finish();
+
+        let message = Message::decode(&bytes).unwrap();
+        assert_eq!(message.varint(1), Some(300));
+        assert_eq!(message.varint(2).map(|v| v as i64), Some(-1));
+        assert_eq!(message.string(3), Some("conv".to_string()));
+        assert_eq!(message.floats(4).unwrap(), vec![2.0]);
+        assert_eq!(message.message(5).unwrap().unwrap().varint(1), Some(7));
+        // Packed repeated varints.
+        assert_eq!(message.varints(6).unwrap(), vec![1, 2, 128]);
+    }
+
+    #[test]
+    fn test_truncated_message() {
+        assert!(Message::decode(&[0x0a, 0x05, 0x01]).is_err());
+        // A length of u64::MAX, which overflows the position of the end of the field.
+        let huge = [
+            0x3a, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01,
+        ];
+        assert_eq!(
+            Message::decode(&huge).unwrap_err(),
+            "Unexpected end of message while reading a field."
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! The native module of the Python package in `python/`, built with PyO3 and maturin.
+//!
+//! The module `convdim._convdim` exposes the formulas and the analyses of networks given as their
+//! toml description, and is re-exported by the `convdim` package. The results of the analyses are
+//! returned as lists of dictionaries, like the JSON documents of the [C interface](crate::ffi),
+//! and errors are raised as `convdim.ConvDimError`, a subclass of `ValueError`.
+
+// The macros of PyO3 0.22 check its `gil-refs` feature and convert the errors of the functions
+// into `PyErr`s in the code they expand to.
+#![allow(unexpected_cfgs, clippy::useless_conversion)]
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde::Serialize;
+
+use crate::architecture::parse_architecture;
+use crate::error;
+use crate::layers;
+use crate::summary::rows;
+use crate::trace::outputs;
+
+create_exception!(
+    convdim,
+    ConvDimError,
+    PyValueError,
+    "An error computing the dimensions of a network, or parsing it."
+);
+
+/// The `error` of the library as a Python exception.
+fn raise(error: error::ConvDimError) -> PyErr {
+    ConvDimError::new_err(error.to_string())
+}
+
+/// `value` as a Python object, where tables become dictionaries and arrays lists.
+fn object(py: Python<'_>, value: &toml::Value) -> PyResult<PyObject> {
+    Ok(match value {
+        toml::Value::String(text) => text.into_py(py),
+        toml::Value::Integer(number) => number.into_py(py),
+        toml::Value::Float(number) => number.into_py(py),
+        toml::Value::Boolean(flag) => flag.into_py(py),
+        toml::Value::Datetime(datetime) => datetime.to_string().into_py(py),
+        toml::Value::Array(values) => {
+            let list = PyList::empty_bound(py);
+            for value in values {
+                list.append(object(py, value)?)?;
+            }
+            list.into_py(py)
+        }
+        toml::Value::Table(table) => {
+            let dict = PyDict::new_bound(py);
+            for (key, value) in table {
+                dict.set_item(key, object(py, value)?)?;
+            }
+            dict.into_py(py)
+        }
+    })
+}
+
+/// The serializable `result` as a Python object.
+fn respond<T: Serialize>(py: Python<'_>, result: T) -> PyResult<PyObject> {
+    let value = toml::Value::try_from(result).map_err(|e| ConvDimError::new_err(e.to_string()))?;
+    object(py, &value)
+}
+
+/// The output dimension of a convolution applied `repeat` times.
+#[pyfunction]
+#[pyo3(signature = (in_dim, filter_size, padding = 0, stride = 1, repeat = 1))]
+fn conv_output_dim(
+    in_dim: u32,
+    filter_size: u32,
+    padding: u32,
+    stride: u32,
+    repeat: u32,
+) -> PyResult<u32> {
+    layers::conv_output_dim(in_dim, filter_size, padding, stride, repeat).map_err(raise)
+}
+
+/// The output dimension of a transposed convolution applied `repeat` times.
+#[pyfunction]
+#[pyo3(signature = (in_dim, filter_size, padding = 0, stride = 1, repeat = 1))]
+fn transposed_conv_output_dim(
+    in_dim: u32,
+    filter_size: u32,
+    padding: u32,
+    stride: u32,
+    repeat: u32,
+) -> PyResult<u32> {
+    layers::transposed_conv_output_dim(in_dim, filter_size, padding, stride, repeat).map_err(raise)
+}
+
+/// The outputs of all the layers or nodes of the network, like `convdim --trace`.
+///
+/// The outputs are dictionaries with the `name`, the `dim` and, for graphs, the `channels`.
+#[pyfunction]
+fn trace(py: Python<'_>, description: &str, in_dim: u32) -> PyResult<PyObject> {
+    let outputs = parse_architecture(description)
+        .and_then(|architecture| outputs(architecture, in_dim))
+        .map_err(raise)?;
+    respond(py, outputs)
+}
+
+/// The summary rows of the inputs and nodes of the network.
+///
+/// The rows hold the `shape`, `params`, `flops`, `receptive_field` and `jump` of every input and
+/// node, where unknown numbers of parameters and operations are missing.
+#[pyfunction]
+fn summary(py: Python<'_>, description: &str, in_dim: u32) -> PyResult<PyObject> {
+    let graph = parse_architecture(description).map_err(raise)?.into_graph();
+    let rows = rows(&graph, in_dim).map_err(raise)?;
+    respond(py, rows)
+}
+
+/// ## The native module `convdim._convdim`.
+#[pymodule]
+fn _convdim(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("ConvDimError", module.py().get_type_bound::<ConvDimError>())?;
+    module.add_function(wrap_pyfunction!(conv_output_dim, module)?)?;
+    module.add_function(wrap_pyfunction!(transposed_conv_output_dim, module)?)?;
+    module.add_function(wrap_pyfunction!(trace, module)?)?;
+    module.add_function(wrap_pyfunction!(summary, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,370 @@
+//! Self-contained reports of networks, e.g. to attach to design reviews.
+//!
+//! The HTML report is a single file without external resources: the styles are embedded and the
+//! charts are inline SVG, so it can be viewed in any browser without `convdim`. The Markdown report
+//! draws the network as a Mermaid diagram instead, which is rendered by GitHub and GitLab, so it
+//! can be added to the documentation of a repository or to the description of a pull request.
+use std::collections::HashMap;
+
+use crate::error::ConvDimError;
+use crate::export::svg::escape;
+use crate::export::{operation, outputs};
+use crate::graph::Graph;
+use crate::summary::{self, Row};
+
+const STYLE: &str = "<style>
+  body { font-family: sans-serif; margin: 2em; color: #222222; }
+  table { border-collapse: collapse; margin-bottom: 2em; }
+  th, td { padding: 4px 10px; border-bottom: 1px solid #dddddd; }
+  th { text-align: left; background: #f0f0f0; }
+  td.number { text-align: right; font-variant-numeric: tabular-nums; }
+  .warning { color: #a05000; }
+  svg text { font-size: 11px; }
+</style>
+";
+
+/// The height of a bar of the charts and the width of their labels, in pixels.
+const BAR_HEIGHT: u32 = 18;
+const LABEL_WIDTH: u32 = 180;
+/// The width of the longest bar of the charts.
+const BAR_WIDTH: u32 = 400;
+
+/// `n` in `unit` abbreviated with a metric prefix, e.g. `1.2 GB`.
+pub(crate) fn abbreviate(n: f64, unit: &str) -> String {
+    let prefixes = [(1e12, "T"), (1e9, "G"), (1e6, "M"), (1e3, "k")];
+    match prefixes.iter().find(|(scale, _)| n >= *scale) {
+        Some((scale, prefix)) => format!("{:.1} {}{}", n / scale, prefix, unit),
+        None => format!("{} {}", n, unit).trim_end().to_string(),
+    }
+}
+
+/// The output shape of `row`, e.g. `56×56×64`.
+fn shape(row: &Row) -> String {
+    match row.shape.channels {
+        Some(channels) => format!("{0}×{0}×{1}", row.shape.dim, channels),
+        None => format!("{0}×{0}", row.shape.dim),
+    }
+}
+
+/// A cell of a table, holding a number or `–` if it is unknown.
+fn number(n: Option<u64>) -> String {
+    format!(
+        "<td class=\"number\">{}</td>",
+        n.map_or_else(|| "–".to_string(), |n| summary::group(n, ","))
+    )
+}
+
+/// A horizontal bar chart of the (known) `values` of the layers, labeled with their `unit`.
+fn chart(rows: &[Row], values: impl Fn(&Row) -> Option<f64>, unit: &str) -> String {
+    let max = rows.iter().filter_map(&values).fold(0.0, f64::max);
+    let mut chart = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        LABEL_WIDTH + BAR_WIDTH + 100,
+        rows.len() as u32 * BAR_HEIGHT
+    );
+    for (i, row) in rows.iter().enumerate() {
+        let y = i as u32 * BAR_HEIGHT;
+        chart += &format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>\n",
+            LABEL_WIDTH - 6,
+            y + 13,
+            escape(row.name)
+        );
+        let (width, label) = match values(row) {
+            Some(value) if max > 0.0 => (
+                (BAR_WIDTH as f64 * value / max).round() as u32,
+                abbreviate(value, unit),
+            ),
+            Some(value) => (0, abbreviate(value, unit)),
+            None => (0, "unknown".to_string()),
+        };
+        chart += &format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#6a9fd4\"/>\n\
+             <text x=\"{}\" y=\"{}\">{}</text>\n",
+            LABEL_WIDTH,
+            y + 2,
+            width,
+            BAR_HEIGHT - 4,
+            LABEL_WIDTH + width + 6,
+            y + 13,
+            escape(&label)
+        );
+    }
+    chart + "</svg>\n"
+}
+
+/// ## Write a self-contained HTML report on `graph` for an input of dimension `in_dim`.
+///
+/// The report consists of the table of the layers with their shapes, parameters and operations,
+/// the receptive fields, charts of the shapes, the memory of the activations (as 32 bit floats)
+/// and the operations of the layers, as well as the warnings about the design of the network.
+pub fn html(graph: &Graph, in_dim: u32) -> Result<String, ConvDimError> {
+    let rows = summary::rows(graph, in_dim)?;
+    let params: Option<u64> = rows.iter().map(|row| row.params).sum();
+    let flops: Option<u64> = rows.iter().map(|row| row.flops).sum();
+
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>convdim report</title>\n",
+    );
+    html += STYLE;
+    html += "</head>\n<body>\n<h1>convdim report</h1>\n";
+    html += &format!(
+        "<p>{} layers, {} parameters, {} FLOPs.</p>\n",
+        graph.nodes.len(),
+        params.map_or_else(|| "unknown".to_string(), |n| summary::group(n, ",")),
+        flops.map_or_else(|| "unknown".to_string(), |n| abbreviate(n as f64, "")),
+    );
+
+    html += "<h2>Layers</h2>\n<table>\n<tr><th>Layer</th><th>Type</th><th>Kernel</th>\
+             <th>Stride</th><th>Padding</th><th>Output</th><th>Parameters</th><th>FLOPs</th></tr>\n";
+    for row in &rows {
+        let (kernel, stride, padding) = match row.node.filter(|node| node.filter_size.is_some()) {
+            Some(node) => (
+                format!("{0}×{0}", node.filter_size.unwrap()),
+                node.stride.to_string(),
+                node.padding.to_string(),
+            ),
+            None => (String::new(), String::new(), String::new()),
+        };
+        html += &format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"number\">{}</td>\
+             <td class=\"number\">{}</td><td>{}</td>{}{}</tr>\n",
+            escape(row.name),
+            row.kind(),
+            kernel,
+            stride,
+            padding,
+            shape(row),
+            number(row.params),
+            number(row.flops)
+        );
+    }
+    html += "</table>\n";
+
+    html += "<h2>Receptive fields</h2>\n<table>\n\
+             <tr><th>Layer</th><th>Receptive field</th><th>Jump</th></tr>\n";
+    for row in &rows {
+        html += &format!(
+            "<tr><td>{}</td><td class=\"number\">{}</td><td class=\"number\">{}</td></tr>\n",
+            escape(row.name),
+            row.receptive_field,
+            row.jump
+        );
+    }
+    html += "</table>\n";
+
+    html += "<h2>Spatial dimensions</h2>\n";
+    html += &chart(&rows, |row| Some(row.shape.dim as f64), "px");
+    html += "<h2>Memory of the activations</h2>\n";
+    html += &chart(&rows, |row| row.activations().map(|n| 4.0 * n as f64), "B");
+    html += "<h2>FLOPs</h2>\n";
+    html += &chart(&rows, |row| row.flops.map(|n| n as f64), "");
+
+    html += "<h2>Warnings</h2>\n";
+    let warnings = summary::warnings(graph, &rows);
+    if warnings.is_empty() {
+        html += "<p>No warnings.</p>\n";
+    } else {
+        html += "<ul>\n";
+        for warning in warnings {
+            html += &format!("<li class=\"warning\">{}</li>\n", escape(&warning));
+        }
+        html += "</ul>\n";
+    }
+    html += "</body>\n</html>\n";
+    Ok(html)
+}
+
+/// `text` escaped for the cells of Markdown tables.
+fn escape_markdown(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '|' | '\\' | '*' | '_' | '`' | '<' | '>' | '[' | ']' => format!("\\{}", c),
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+/// `text` escaped for the labels of Mermaid diagrams, which are enclosed in quotes.
+fn escape_mermaid(text: &str) -> String {
+    text.replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+/// ## Draw the layers summarized by `rows` as a Mermaid flowchart from left to right.
+///
+/// The inputs are drawn as stadiums, the nodes as boxes labeled with their operation and shape,
+/// and the outputs of `graph` are highlighted.
+fn mermaid(graph: &Graph, rows: &[Row]) -> String {
+    let ids: HashMap<&str, String> = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| (row.name, format!("n{}", i)))
+        .collect();
+    let mut mermaid = String::from("```mermaid\nflowchart LR\n");
+    for row in rows {
+        let mut label = escape_mermaid(row.name);
+        if let Some(node) = row.node {
+            label += &format!("<br/>{}", operation(node));
+        }
+        label += &format!("<br/>{}", shape(row));
+        match row.node {
+            None => mermaid += &format!("    {}([\"{}\"])\n", ids[row.name], label),
+            Some(_) => mermaid += &format!("    {}[\"{}\"]\n", ids[row.name], label),
+        }
+    }
+    let edges = graph.edges();
+    for node in &graph.nodes {
+        for input in &edges[node.name.as_str()] {
+            mermaid += &format!("    {} --> {}\n", ids[input], ids[node.name.as_str()]);
+        }
+    }
+    mermaid += "    classDef output stroke-width:3px\n";
+    for output in outputs(graph) {
+        mermaid += &format!("    class {} output\n", ids[output]);
+    }
+    mermaid + "```\n"
+}
+
+/// ## Write a Markdown report on `graph` for an input of dimension `in_dim`.
+///
+/// The report consists of the same tables and warnings as the HTML report, and a Mermaid diagram
+/// of the network instead of the charts.
+pub fn markdown(graph: &Graph, in_dim: u32) -> Result<String, ConvDimError> {
+    let rows = summary::rows(graph, in_dim)?;
+    let params: Option<u64> = rows.iter().map(|row| row.params).sum();
+    let flops: Option<u64> = rows.iter().map(|row| row.flops).sum();
+    let optional = |n: Option<u64>| n.map_or_else(|| "–".to_string(), |n| summary::group(n, ","));
+
+    let mut markdown = String::from("# convdim report\n\n");
+    markdown += &format!(
+        "{} layers, {} parameters, {} FLOPs.\n\n",
+        graph.nodes.len(),
+        params.map_or_else(|| "unknown".to_string(), |n| summary::group(n, ",")),
+        flops.map_or_else(|| "unknown".to_string(), |n| abbreviate(n as f64, "")),
+    );
+    markdown += "## Network\n\n";
+    markdown += &mermaid(graph, &rows);
+
+    markdown += "\n## Layers\n\n\
+                 | Layer | Type | Kernel | Stride | Padding | Output | Parameters | FLOPs |\n\
+                 | --- | --- | --- | ---: | ---: | --- | ---: | ---: |\n";
+    for row in &rows {
+        let (kernel, stride, padding) = match row.node.filter(|node| node.filter_size.is_some()) {
+            Some(node) => (
+                format!("{0}×{0}", node.filter_size.unwrap()),
+                node.stride.to_string(),
+                node.padding.to_string(),
+            ),
+            None => (String::new(), String::new(), String::new()),
+        };
+        markdown += &format!(
+            "| {} | {} | {} | {} | {} | {} | {} | {} |\n",
+            escape_markdown(row.name),
+            row.kind(),
+            kernel,
+            stride,
+            padding,
+            shape(row),
+            optional(row.params),
+            optional(row.flops)
+        );
+    }
+
+    markdown += "\n## Receptive fields\n\n\
+                 | Layer | Receptive field | Jump |\n\
+                 | --- | ---: | ---: |\n";
+    for row in &rows {
+        markdown += &format!(
+            "| {} | {} | {} |\n",
+            escape_markdown(row.name),
+            row.receptive_field,
+            row.jump
+        );
+    }
+
+    markdown += "\n## Warnings\n\n";
+    let warnings = summary::warnings(graph, &rows);
+    if warnings.is_empty() {
+        markdown += "No warnings.\n";
+    }
+    for warning in warnings {
+        markdown += &format!("- {}\n", warning);
+    }
+    Ok(markdown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html() {
+        let graph: Graph = toml::from_str(
+            r#"
+            input_channels = 3
+
+            [[nodes]]
+            name = "conv1"
+            op = "conv"
+            filter_size = 3
+            stride = 2
+            channels = 8
+
+            [[nodes]]
+            name = "up"
+            op = "transposed"
+            filter_size = 2
+            stride = 2
+            "#,
+        )
+        .unwrap();
+        let html = html(&graph, 32).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<p>2 layers, 224 parameters, 97.2 k FLOPs.</p>"));
+        assert!(html.contains(
+            "<tr><td>conv1</td><td>conv</td><td>3×3</td><td class=\"number\">2</td>\
+             <td class=\"number\">0</td><td>15×15×8</td><td class=\"number\">224</td>\
+             <td class=\"number\">97,200</td></tr>"
+        ));
+        assert!(html.contains(
+            "<tr><td>up</td><td class=\"number\">3</td><td class=\"number\">1</td></tr>"
+        ));
+        // The stride of 2 doesn't divide the input of 32 minus the filter size of 3.
+        assert!(html.contains("<li class=\"warning\">Node 'conv1' ignores the last 1 rows"));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn test_markdown() {
+        let graph: Graph = toml::from_str(
+            r#"
+            input_channels = 3
+
+            [[nodes]]
+            name = "conv|1"
+            op = "conv"
+            filter_size = 3
+            padding = 1
+            channels = 8
+
+            [[nodes]]
+            name = "cat"
+            op = "concat"
+            inputs = ["input", "conv|1"]
+            "#,
+        )
+        .unwrap();
+        let markdown = markdown(&graph, 8).unwrap();
+        assert!(markdown.starts_with("# convdim report\n\n2 layers, 224 parameters, "));
+        assert!(markdown.contains(
+            "    n0([\"input<br/>8×8×3\"])\n    n1[\"conv|1<br/>conv 3x3, padding 1<br/>8×8×8\"]\n"
+        ));
+        assert!(markdown.contains("    n0 --> n2\n    n1 --> n2\n"));
+        assert!(markdown.contains("    class n2 output\n```\n"));
+        assert!(markdown.contains("| conv\\|1 | conv | 3×3 | 1 | 1 | 8×8×8 | 224 | 27,648 |\n"));
+        assert!(markdown.contains("| conv\\|1 | 3 | 1 |\n"));
+        assert!(markdown.ends_with("## Warnings\n\nNo warnings.\n"));
+    }
+}
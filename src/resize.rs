@@ -0,0 +1,403 @@
+//! The nearest input dimensions for which a network loses nothing, e.g. to decide what to resize
+//! an arbitrary photo to.
+//!
+//! An input dimension is clean if every layer is valid, no convolution ignores the last rows and
+//! columns of its input since its stride doesn't divide it evenly, the inputs of every merge agree
+//! and the feature maps paired by the [skip connections](crate::skips) of an encoder-decoder network
+//! agree. The clean dimensions are searched below and above the given one, within limits on the
+//! number of results, the distance from it and the time, which the suggestion reports if they
+//! truncated the search. The dimensions can be checked on several threads, in batches of
+//! distances whose results are then taken in order, so the suggestion doesn't depend on the jobs.
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::graph::Graph;
+use crate::parallel;
+use crate::skips::pair_skips;
+use crate::summary;
+use crate::trace::write_columns;
+use crate::Architecture;
+
+/// The number of dimensions searched on either side of the given one by default.
+pub const SEARCH: u32 = 1 << 16;
+
+/// The number of distances searched by every job at once when the search runs on several threads.
+const BATCH: u32 = 64;
+
+fn invalid(message: String) -> ConvDimError {
+    ConvDimError::Resize { message }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## The limits of the search of clean input dimensions, so that it ends predictably.
+pub struct Limits {
+    /// The number of clean dimensions to find on either side.
+    pub max_results: usize,
+    /// The largest distance from the given dimension that is searched.
+    pub max_depth: u32,
+    /// The time after which the search stops, if any.
+    pub timeout: Option<Duration>,
+    /// The number of threads checking the dimensions, or 0 for all cores.
+    pub jobs: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_results: 1,
+            max_depth: SEARCH,
+            timeout: None,
+            jobs: 1,
+        }
+    }
+}
+
+/// ## The duration `text` describes, e.g. `30s`, `500ms`, `2m` or `1h`, where plain numbers are seconds.
+pub fn duration(text: &str) -> Result<Duration, ConvDimError> {
+    let units = [("ms", 0.001), ("s", 1.0), ("m", 60.0), ("h", 3600.0)];
+    let text = text.trim();
+    let (number, factor) = units
+        .iter()
+        .find(|(unit, _)| text.ends_with(unit))
+        .map_or((text, 1.0), |(unit, factor)| {
+            (text[..text.len() - unit.len()].trim(), *factor)
+        });
+    number
+        .parse::<f64>()
+        .ok()
+        .and_then(|number| Duration::try_from_secs_f64(number * factor).ok())
+        .ok_or_else(|| invalid(format!("'{}' is not a duration like '30s'.", text)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+/// ## Why a search may have missed clean dimensions.
+pub enum Truncation {
+    /// The clean dimensions on a side are further away than the largest distance searched.
+    Depth { max_depth: u32 },
+    /// The time ran out once the dimensions up to `distance` were searched.
+    Timeout { distance: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The nearest clean input dimensions below and above a dimension, and their outputs.
+pub struct Suggestion {
+    pub dim: u32,
+    /// The largest clean dimensions up to `dim` and their output dimensions, the nearest first.
+    pub down: Vec<(u32, u32)>,
+    /// The smallest clean dimensions from `dim` on and their output dimensions, the nearest first.
+    pub up: Vec<(u32, u32)>,
+    /// Why the search may have missed clean dimensions, or `None` if it was exhaustive.
+    pub truncated: Option<Truncation>,
+}
+
+impl Suggestion {
+    /// Whether the dimension itself is clean.
+    pub fn is_clean(&self) -> bool {
+        self.down.first().map(|(dim, _)| *dim) == Some(self.dim)
+    }
+
+    /// Whether the search found the nearest clean dimensions on both sides, or that there are none.
+    pub fn is_exhaustive(&self) -> bool {
+        self.truncated.is_none()
+    }
+}
+
+impl fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rows = vec![];
+        let mut side = |label: &str, clean: &[(u32, u32)]| {
+            if clean.is_empty() {
+                rows.push(vec![label.to_string(), "none".to_string()]);
+            }
+            for (i, (dim, output)) in clean.iter().enumerate() {
+                let label = if i == 0 { label } else { "" };
+                rows.push(vec![
+                    label.to_string(),
+                    dim.to_string(),
+                    format!("-> {}", output),
+                ]);
+            }
+        };
+        if self.is_clean() {
+            side("clean:", &self.down[..1]);
+            for (label, clean) in [("down:", &self.down[1..]), ("up:", &self.up[1..])] {
+                if !clean.is_empty() {
+                    side(label, clean);
+                }
+            }
+        } else {
+            side("down:", &self.down);
+            side("up:", &self.up);
+        }
+        write_columns(f, &rows, &[1])?;
+        match self.truncated {
+            None => writeln!(f, "search: exhaustive"),
+            Some(Truncation::Depth { max_depth }) => {
+                writeln!(f, "search: truncated at the depth of {}", max_depth)
+            }
+            Some(Truncation::Timeout { distance }) => writeln!(
+                f,
+                "search: truncated by the timeout at a distance of {}",
+                distance
+            ),
+        }
+    }
+}
+
+/// The output dimension of `graph` for the input dimension `dim`, if it is clean.
+fn clean(architecture: &Architecture, graph: &Graph, dim: u32) -> Option<u32> {
+    let rows = summary::rows(graph, dim).ok()?;
+    let edges = graph.edges();
+    let dims: HashMap<&str, u32> = rows.iter().map(|row| (row.name, row.shape.dim)).collect();
+    let lossy = rows.iter().any(|row| {
+        row.node.is_some_and(|node| {
+            summary::ignored(node, dims[edges[node.name.as_str()][0]])
+                .is_some_and(|(ignored, _)| ignored > 0)
+        })
+    });
+    let aligned = pair_skips(architecture, dim)
+        .map(|pairing| pairing.pairs.iter().all(|pair| pair.is_aligned()))
+        .unwrap_or(false);
+    match (lossy, aligned) {
+        (false, true) => Some(
+            rows.last()
+                .expect("The summary has a row for every input.")
+                .shape
+                .dim,
+        ),
+        _ => None,
+    }
+}
+
+/// ## The nearest clean input dimensions of `architecture` below and above `dim`.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{resize, Architecture, Network};
+/// let network = Network::builder().conv(3, 2, 1).conv(3, 2, 1).build();
+/// let architecture = Architecture::Layers(network.layers().clone());
+/// let suggestion = resize::suggest(&architecture, 1023).unwrap();
+/// assert_eq!(suggestion.down, vec![(1021, 256)]);
+/// assert_eq!(suggestion.up, vec![(1025, 257)]);
+/// assert!(suggestion.is_exhaustive());
+/// ```
+pub fn suggest(architecture: &Architecture, dim: u32) -> Result<Suggestion, ConvDimError> {
+    search(architecture, dim, &Limits::default())
+}
+
+/// ## The nearest clean input dimensions of `architecture` below and above `dim`, within `limits`.
+///
+/// The dimensions are searched by their distance from `dim`, alternating between both sides,
+/// until `max_results` clean dimensions are found on both sides, the distance exceeds
+/// `max_depth` or the `timeout` expires. The suggestion records whether the search was truncated.
+///
+/// With several `jobs`, the dimensions of a batch of distances are checked at once, so the timeout
+/// is only checked between the batches.
+pub fn search(
+    architecture: &Architecture,
+    dim: u32,
+    limits: &Limits,
+) -> Result<Suggestion, ConvDimError> {
+    let graph = architecture.clone().into_graph();
+    if graph.nodes.is_empty() {
+        return Err(ConvDimError::EmptyGraph);
+    }
+    if limits.max_results == 0 {
+        return Err(invalid(
+            "at least 1 result needs to be searched.".to_string(),
+        ));
+    }
+    let check = |dim: u32| clean(architecture, &graph, dim).map(|output| (dim, output));
+    let jobs = parallel::jobs(limits.jobs);
+    let batch = if jobs == 1 { 1 } else { BATCH * jobs as u32 };
+    let start = Instant::now();
+    let (mut down, mut up, mut truncated) = (vec![], vec![], None);
+    let mut first = 0;
+    'search: while first <= limits.max_depth {
+        if limits
+            .timeout
+            .is_some_and(|timeout| start.elapsed() >= timeout)
+        {
+            truncated = Some(Truncation::Timeout { distance: first });
+            break;
+        }
+        let last = first.saturating_add(batch - 1).min(limits.max_depth);
+        // The dimensions at the distances of the batch on both sides, the nearest first.
+        let dims: Vec<u32> = (first..=last)
+            .flat_map(|distance| {
+                let below = (distance > 0 && distance < dim).then(|| dim - distance);
+                [below, dim.checked_add(distance)]
+            })
+            .flatten()
+            .collect();
+        let checked: HashMap<u32, Option<(u32, u32)>> = dims
+            .iter()
+            .copied()
+            .zip(parallel::map(&dims, jobs, |&dim| check(dim)))
+            .collect();
+        for distance in first..=last {
+            let down_open = down.len() < limits.max_results && distance < dim;
+            let up_open = up.len() < limits.max_results && dim.checked_add(distance).is_some();
+            if !down_open && !up_open {
+                break 'search;
+            }
+            if distance == 0 {
+                if let Some(clean) = checked[&dim] {
+                    down.push(clean);
+                    up.push(clean);
+                }
+                continue;
+            }
+            if down_open {
+                down.extend(checked[&(dim - distance)]);
+            }
+            if up_open {
+                up.extend(checked[&(dim + distance)]);
+            }
+        }
+        match last.checked_add(1) {
+            Some(next) => first = next,
+            None => break,
+        }
+    }
+    // The sides lacking results that have dimensions beyond the largest distance are incomplete.
+    let beyond = limits.max_depth.checked_add(1);
+    let down_beyond = beyond.is_some_and(|beyond| beyond < dim);
+    let up_beyond = beyond.is_some_and(|beyond| dim.checked_add(beyond).is_some());
+    if truncated.is_none()
+        && ((down.len() < limits.max_results && down_beyond)
+            || (up.len() < limits.max_results && up_beyond))
+    {
+        truncated = Some(Truncation::Depth {
+            max_depth: limits.max_depth,
+        });
+    }
+    Ok(Suggestion {
+        dim,
+        down,
+        up,
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_suggest() {
+        // A U-Net-like network whose skip connection needs a multiple of 4 for the input.
+        let architecture = parse_architecture(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 2
+            stride = 2
+
+            [[layers]]
+            type = "conv"
+            filter_size = 2
+            stride = 2
+
+            [[layers]]
+            type = "conv_transpose"
+            filter_size = 2
+            stride = 2
+            "#,
+        )
+        .unwrap();
+        let suggestion = suggest(&architecture, 767).unwrap();
+        assert_eq!(suggestion.down, vec![(764, 382)]);
+        assert_eq!(suggestion.up, vec![(768, 384)]);
+        assert_eq!(
+            suggestion.to_string(),
+            "down:  764  -> 382\nup:    768  -> 384\nsearch: exhaustive\n"
+        );
+        let clean = suggest(&architecture, 768).unwrap();
+        assert!(clean.is_clean());
+        assert_eq!(
+            clean.to_string(),
+            "clean:  768  -> 384\nsearch: exhaustive\n"
+        );
+
+        let limits = Limits {
+            max_results: 2,
+            ..Limits::default()
+        };
+        let two = search(&architecture, 768, &limits).unwrap();
+        assert_eq!(two.down, vec![(768, 384), (764, 382)]);
+        assert_eq!(two.up, vec![(768, 384), (772, 386)]);
+        assert_eq!(
+            two.to_string(),
+            "clean:  768  -> 384\ndown:   764  -> 382\nup:     772  -> 386\nsearch: exhaustive\n"
+        );
+
+        let limits = Limits {
+            max_depth: 2,
+            ..Limits::default()
+        };
+        let shallow = search(&architecture, 6, &limits).unwrap();
+        assert_eq!((shallow.down, shallow.up), (vec![(4, 2)], vec![(8, 4)]));
+        assert!(shallow.truncated.is_none());
+        let shallow = search(
+            &architecture,
+            10,
+            &Limits {
+                max_depth: 1,
+                ..limits
+            },
+        )
+        .unwrap();
+        assert_eq!(shallow.truncated, Some(Truncation::Depth { max_depth: 1 }));
+
+        let limits = Limits {
+            timeout: Some(Duration::ZERO),
+            ..Limits::default()
+        };
+        let timeout = search(&architecture, 767, &limits).unwrap();
+        assert_eq!(timeout.down, vec![]);
+        assert_eq!(timeout.truncated, Some(Truncation::Timeout { distance: 0 }));
+        assert!(timeout
+            .to_string()
+            .starts_with("down:  none\nup:    none\nsearch: truncated by the timeout"));
+    }
+
+    #[test]
+    fn test_jobs_dont_change_the_suggestion() {
+        let architecture = parse_architecture(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            stride = 7
+            "#,
+        )
+        .unwrap();
+        let limits = Limits {
+            max_results: 5,
+            max_depth: 1000,
+            ..Limits::default()
+        };
+        for dim in [1, 2, 100, 1000, u32::MAX - 3] {
+            let serial = search(&architecture, dim, &limits).unwrap();
+            let parallel = search(&architecture, dim, &Limits { jobs: 4, ..limits }).unwrap();
+            assert_eq!(parallel, serial);
+        }
+    }
+
+    #[test]
+    fn test_duration() {
+        assert_eq!(duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(duration("2 m"), Ok(Duration::from_secs(120)));
+        assert_eq!(duration("1.5"), Ok(Duration::from_millis(1500)));
+        assert!(duration("-1s").is_err());
+        assert!(duration("soon").is_err());
+    }
+}
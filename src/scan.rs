@@ -0,0 +1,436 @@
+//! The output dimensions of a network for all images of a dataset, e.g. to decide whether the
+//! images need to be resized.
+//!
+//! The PNG, JPEG and TIFF images are found in the directory and its subdirectories, like the class
+//! folders of an image dataset, and only their [headers](crate::image) are read. Since the inputs
+//! of the network are square, it is evaluated for the height and for the width of every image.
+//! The images can also be reported one by one as [JSON lines](crate::ndjson), as soon as their
+//! headers are read.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::graph::Graph;
+use crate::image::{self, Header};
+use crate::ndjson::{self, table};
+use crate::parallel;
+use crate::progress::Progress;
+use crate::summary;
+use crate::trace::write_columns;
+
+/// The extensions of the images, which are compared ignoring their case.
+const EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "tif", "tiff"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## A convolution ignoring the last rows or columns of its input for an image.
+pub struct Ignored {
+    pub node: String,
+    pub rows: u32,
+    pub columns: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## What the network makes of an image.
+pub enum Outcome {
+    /// The height and width of the output, and the convolutions ignoring border pixels.
+    Output {
+        height: u32,
+        width: u32,
+        ignored: Vec<Ignored>,
+    },
+    /// The image is too small for the network.
+    TooSmall { message: String },
+    /// The header of the image cannot be read.
+    Unreadable { message: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## An image of the dataset, its header if it can be read, and its outcome.
+pub struct Entry {
+    pub path: PathBuf,
+    pub header: Option<Header>,
+    pub outcome: Outcome,
+}
+
+impl Entry {
+    /// ## The entry as a JSON line, with the `image` size, the `outcome` (`output`, `too_small` or
+    /// `unreadable`) and the `output` size and `ignored` border pixels, or the `error`.
+    pub fn json_line(&self) -> String {
+        let integer = |value: u32| toml::Value::Integer(i64::from(value));
+        let size = |height: u32, width: u32| {
+            toml::Value::Table(table([
+                ("height", integer(height)),
+                ("width", integer(width)),
+            ]))
+        };
+        let mut record = table([("path", toml::Value::String(self.path.display().to_string()))]);
+        if let Some(header) = self.header {
+            record.insert("image".to_string(), size(header.height, header.width));
+        }
+        let (outcome, members) = match &self.outcome {
+            Outcome::Output {
+                height,
+                width,
+                ignored,
+            } => {
+                let ignored = ignored
+                    .iter()
+                    .map(|ignored| {
+                        toml::Value::Table(table([
+                            ("node", toml::Value::String(ignored.node.clone())),
+                            ("rows", integer(ignored.rows)),
+                            ("columns", integer(ignored.columns)),
+                        ]))
+                    })
+                    .collect();
+                (
+                    "output",
+                    table([
+                        ("output", size(*height, *width)),
+                        ("ignored", toml::Value::Array(ignored)),
+                    ]),
+                )
+            }
+            Outcome::TooSmall { message } => (
+                "too_small",
+                table([("error", toml::Value::String(message.clone()))]),
+            ),
+            Outcome::Unreadable { message } => (
+                "unreadable",
+                table([("error", toml::Value::String(message.clone()))]),
+            ),
+        };
+        record.insert(
+            "outcome".to_string(),
+            toml::Value::String(outcome.to_string()),
+        );
+        record.extend(members);
+        ndjson::line("scan", record)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The images of a dataset, ordered by their paths.
+pub struct Scan {
+    pub entries: Vec<Entry>,
+}
+
+impl Scan {
+    /// The number of images for every height and width of the output.
+    pub fn distribution(&self) -> BTreeMap<(u32, u32), usize> {
+        let mut distribution = BTreeMap::new();
+        for entry in &self.entries {
+            if let Outcome::Output { height, width, .. } = entry.outcome {
+                *distribution.entry((height, width)).or_insert(0) += 1;
+            }
+        }
+        distribution
+    }
+}
+
+/// `header` as `height x width`.
+fn size(header: &Option<Header>) -> String {
+    header.map_or(String::new(), |header| {
+        format!("{} x {}", header.height, header.width)
+    })
+}
+
+impl fmt::Display for Scan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "images:  {}", self.entries.len())?;
+        let mut rows = vec![vec!["output".to_string(), "images".to_string()]];
+        for ((height, width), count) in self.distribution() {
+            rows.push(vec![format!("{} x {}", height, width), count.to_string()]);
+        }
+        writeln!(f)?;
+        write_columns(f, &rows, &[1])?;
+
+        let (mut small, mut unreadable, mut border) = (vec![], vec![], vec![]);
+        for entry in &self.entries {
+            let path = entry.path.display().to_string();
+            match &entry.outcome {
+                Outcome::TooSmall { message } => {
+                    small.push(vec![path, size(&entry.header), message.clone()])
+                }
+                Outcome::Unreadable { message } => unreadable.push(vec![path, message.clone()]),
+                Outcome::Output { ignored, .. } if !ignored.is_empty() => {
+                    let nodes: Vec<String> = ignored
+                        .iter()
+                        .map(|ignored| {
+                            format!(
+                                "{} ignores {} rows and {} columns",
+                                ignored.node, ignored.rows, ignored.columns
+                            )
+                        })
+                        .collect();
+                    border.push(vec![path, size(&entry.header), nodes.join(", ")]);
+                }
+                Outcome::Output { .. } => {}
+            }
+        }
+        for (title, rows) in [
+            ("too small", small),
+            ("losing border pixels", border),
+            ("unreadable", unreadable),
+        ] {
+            if !rows.is_empty() {
+                writeln!(f, "\n{}:  {}", title, rows.len())?;
+                write_columns(f, &rows, &[])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The paths of the images in `directory` and its subdirectories.
+fn images(directory: &Path, paths: &mut Vec<PathBuf>) -> Result<(), ConvDimError> {
+    let unreadable = |e: std::io::Error| ConvDimError::Scan {
+        message: format!("'{}': {}", directory.display(), e),
+    };
+    for entry in std::fs::read_dir(directory).map_err(unreadable)? {
+        let path = entry.map_err(unreadable)?.path();
+        if path.is_dir() {
+            images(&path, paths)?;
+        } else if path.extension().is_some_and(|extension| {
+            let extension = extension.to_string_lossy().to_lowercase();
+            EXTENSIONS.contains(&extension.as_str())
+        }) {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The output dimension of `graph` for an input of dimension `dim`, and the number of last rows
+/// the convolutions ignore.
+fn evaluate(graph: &Graph, dim: u32) -> Result<(u32, Vec<(String, u32)>), ConvDimError> {
+    let rows = summary::rows(graph, dim)?;
+    let edges = graph.edges();
+    let dims: BTreeMap<&str, u32> = rows.iter().map(|row| (row.name, row.shape.dim)).collect();
+    let ignored = rows
+        .iter()
+        .filter_map(|row| {
+            let node = row.node?;
+            let (ignored, _) = summary::ignored(node, dims[edges[node.name.as_str()][0]])?;
+            Some((node.name.clone(), ignored)).filter(|_| ignored > 0)
+        })
+        .collect();
+    let output = rows.last().expect("The summary has a row for every input.");
+    Ok((output.shape.dim, ignored))
+}
+
+/// What `graph` makes of an image with `header`.
+fn outcome(graph: &Graph, header: Header) -> Outcome {
+    let (height, width) = match (
+        evaluate(graph, header.height),
+        evaluate(graph, header.width),
+    ) {
+        (Ok(height), Ok(width)) => (height, width),
+        (Err(e), _) | (_, Err(e)) => {
+            return Outcome::TooSmall {
+                message: e.to_string(),
+            }
+        }
+    };
+    let mut ignored: Vec<Ignored> = vec![];
+    for (node, rows) in height.1 {
+        ignored.push(Ignored {
+            node,
+            rows,
+            columns: 0,
+        });
+    }
+    for (node, columns) in width.1 {
+        match ignored.iter_mut().find(|ignored| ignored.node == node) {
+            Some(ignored) => ignored.columns = columns,
+            None => ignored.push(Ignored {
+                node,
+                rows: 0,
+                columns,
+            }),
+        }
+    }
+    Outcome::Output {
+        height: height.0,
+        width: width.0,
+        ignored,
+    }
+}
+
+/// The entry of the image at `path` with `header`.
+fn entry(graph: &Graph, path: PathBuf, header: Result<Header, ConvDimError>) -> Entry {
+    match header {
+        Ok(header) => Entry {
+            path,
+            header: Some(header),
+            outcome: outcome(graph, header),
+        },
+        Err(e) => Entry {
+            path,
+            header: None,
+            outcome: Outcome::Unreadable {
+                message: e.to_string(),
+            },
+        },
+    }
+}
+
+/// ## Evaluate `graph` for the images with `headers`, given with their paths.
+///
+/// ## Example
+///
+/// ```rust
+/// # use std::path::PathBuf;
+/// # use convdim::{scan, Architecture, Network};
+/// # use convdim::image::Header;
+/// let network = Network::builder().conv(3, 2, 0).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let header = |height, width| Header { height, width, channels: 3 };
+/// let headers = vec![
+///     (PathBuf::from("a.png"), Ok(header(48, 64))),
+///     (PathBuf::from("b.png"), Ok(header(49, 65))),
+///     (PathBuf::from("c.png"), Ok(header(2, 2))),
+/// ];
+/// let scan = scan::evaluate_headers(&graph, headers);
+/// assert_eq!(scan.distribution().into_iter().collect::<Vec<_>>(), vec![((23, 31), 1), ((24, 32), 1)]);
+/// ```
+pub fn evaluate_headers(
+    graph: &Graph,
+    headers: Vec<(PathBuf, Result<Header, ConvDimError>)>,
+) -> Scan {
+    let entries = headers
+        .into_iter()
+        .map(|(path, header)| entry(graph, path, header))
+        .collect();
+    Scan { entries }
+}
+
+/// ## Evaluate `graph` for the images in `directory` and its subdirectories, reading their headers
+/// on up to `jobs` threads, where 0 uses all available cores.
+///
+/// The images for which the network has an output are counted as matching by the `progress`.
+pub fn scan(
+    graph: &Graph,
+    directory: &Path,
+    jobs: usize,
+    progress: &mut Progress,
+) -> Result<Scan, ConvDimError> {
+    let entries = Mutex::new(vec![]);
+    scan_each(graph, directory, jobs, progress, |entry| {
+        entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(entry)
+    })?;
+    let mut entries = entries
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(Scan { entries })
+}
+
+/// ## Evaluate `graph` for the images in `directory` like [`scan`], passing every entry to `emit`
+/// as soon as it is evaluated.
+///
+/// With several jobs, the entries are emitted in the order in which their headers are read.
+pub fn scan_each<F>(
+    graph: &Graph,
+    directory: &Path,
+    jobs: usize,
+    progress: &mut Progress,
+    emit: F,
+) -> Result<(), ConvDimError>
+where
+    F: Fn(Entry) + Sync,
+{
+    let mut paths = vec![];
+    images(directory, &mut paths)?;
+    paths.sort();
+    progress.set_total(paths.len() as u64);
+    let progress = &*progress;
+    parallel::map(&paths, parallel::jobs(jobs), |path| {
+        let entry = entry(graph, path.clone(), image::read(path));
+        let matching = matches!(entry.outcome, Outcome::Output { .. });
+        progress.add(1, u64::from(matching));
+        emit(entry)
+    });
+    progress.finish();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_scan() {
+        let graph = parse_architecture(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            stride = 2
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let header = |height, width| {
+            Ok(Header {
+                height,
+                width,
+                channels: 3,
+            })
+        };
+        let scan = evaluate_headers(
+            &graph,
+            vec![
+                (PathBuf::from("a.png"), header(48, 64)),
+                (PathBuf::from("b.png"), header(49, 65)),
+                (PathBuf::from("c.png"), header(49, 2)),
+                (
+                    PathBuf::from("d.png"),
+                    Err(ConvDimError::Image {
+                        message: "the PNG image has no IHDR chunk.".to_string(),
+                    }),
+                ),
+            ],
+        );
+        assert_eq!(
+            scan.entries[0].outcome,
+            Outcome::Output {
+                height: 23,
+                width: 31,
+                ignored: vec![Ignored {
+                    node: "layer1".to_string(),
+                    rows: 1,
+                    columns: 1
+                }],
+            }
+        );
+        assert!(matches!(scan.entries[2].outcome, Outcome::TooSmall { .. }));
+        let text = scan.to_string();
+        assert!(
+            text.starts_with("images:  4\n\noutput   images\n23 x 31       1\n24 x 32       1\n")
+        );
+        assert!(text.contains(
+            "losing border pixels:  1\na.png  48 x 64  layer1 ignores 1 rows and 1 columns\n"
+        ));
+        assert!(text.ends_with(
+            "unreadable:  1\nd.png  Unable to read the image: the PNG image has no IHDR chunk.\n"
+        ));
+        assert_eq!(
+            scan.entries[0].json_line(),
+            "{\"type\": \"scan\", \"ignored\": [{\"columns\": 1, \"node\": \"layer1\", \"rows\": 1}], \
+             \"image\": {\"height\": 48, \"width\": 64}, \"outcome\": \"output\", \
+             \"output\": {\"height\": 23, \"width\": 31}, \"path\": \"a.png\"}"
+        );
+        assert!(scan.entries[3]
+            .json_line()
+            .ends_with("\"outcome\": \"unreadable\", \"path\": \"d.png\"}"));
+    }
+}
@@ -0,0 +1,522 @@
+//! The structure of the descriptions, checked before they are interpreted to report all the
+//! unknown types, misspelled keys and missing parameters of a description at once.
+//!
+//! Unknown types and operations are reported together with the closest known one. Keys that are
+//! not parameters of their layer or node are only reported if they resemble one, e.g.
+//! `fliter_size`, so that descriptions may still carry additional keys for other tools.
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::formula::Formula;
+use crate::plugin;
+
+/// The built-in types of layers, with their required and optional parameters.
+const LAYER_TYPES: &[(&str, &[&str], &[&str])] = &[
+    (
+        "conv",
+        &["filter_size"],
+        &["name", "stride", "padding", "channels"],
+    ),
+    (
+        "conv_transpose",
+        &["filter_size"],
+        &["name", "stride", "padding", "channels"],
+    ),
+    ("max_pool", &["filter_size", "stride"], &["name", "padding"]),
+    ("upsample", &["scale"], &["name"]),
+    (
+        "formula",
+        &["formula"],
+        &[
+            "name",
+            "filter_size",
+            "stride",
+            "padding",
+            "transposed",
+            "channels",
+        ],
+    ),
+    ("batchnorm", &[], &["name"]),
+    ("relu", &[], &["name"]),
+    ("dropout", &[], &["name"]),
+    ("identity", &[], &["name"]),
+];
+
+/// The required and optional parameters of the layers without a type.
+const UNTYPED_LAYER: (&[&str], &[&str]) = (
+    &["filter_size", "stride", "padding", "transposed"],
+    &["name", "channels", "type"],
+);
+
+/// The keys of a list of layers and of a graph.
+const LAYERS_KEYS: &[&str] = &["input_channels", "outputs", "layers"];
+const GRAPH_KEYS: &[&str] = &[
+    "input_channels",
+    "inputs",
+    "nodes",
+    "outputs",
+    "subnetworks",
+];
+
+/// The operations of nodes, and the required and optional keys of nodes, inputs and subnetworks.
+const OPS: &[&str] = &[
+    "conv",
+    "transposed",
+    "add",
+    "concat",
+    "subnetwork",
+    "batchnorm",
+    "relu",
+    "dropout",
+    "identity",
+];
+const NODE_KEYS: (&[&str], &[&str]) = (
+    &["name", "op"],
+    &[
+        "inputs",
+        "filter_size",
+        "stride",
+        "padding",
+        "formula",
+        "channels",
+        "subnetwork",
+    ],
+);
+const INPUT_KEYS: (&[&str], &[&str]) = (&["name"], &["input_dim", "channels"]);
+const SUBNETWORK_KEYS: (&[&str], &[&str]) = (&["name", "nodes"], &[]);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## A problem of the structure of a description.
+///
+/// The (one-based) `line` is known if the problem could be located in the text of the description.
+pub struct Problem {
+    pub line: Option<usize>,
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// ## The lines of the description `text` without their comments.
+pub fn code_lines(text: &str) -> Vec<&str> {
+    text.lines()
+        .map(|line| {
+            let mut quoted = false;
+            for (i, c) in line.char_indices() {
+                match c {
+                    '"' => quoted = !quoted,
+                    '#' if !quoted => return &line[..i],
+                    _ => {}
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+/// ## The positions in the `line` of a description of the values of `key`, i.e. of the text
+/// following `key =`.
+pub fn values<'a>(line: &'a str, key: &str) -> Vec<&'a str> {
+    line.match_indices(key)
+        .filter(|(i, _)| {
+            !line[..*i]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_')
+        })
+        .filter_map(|(i, _)| line[i + key.len()..].trim_start().strip_prefix('='))
+        .map(str::trim_start)
+        .collect()
+}
+
+/// ## The number of edits turning `a` into `b`.
+///
+/// The edits are insertions, deletions, substitutions and transpositions of adjacent characters,
+/// the most common typing errors.
+fn distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // The distances between the prefixes of `a` and `b`, of the current and the two previous rows.
+    let mut rows = vec![vec![0; b.len() + 1]; 3];
+    rows[2] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        rows.rotate_left(1);
+        rows[2][0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[1][j] + 1)
+                .min(rows[2][j - 1] + 1)
+                .min(rows[1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[0][j - 2] + 1);
+            }
+            rows[2][j] = best;
+        }
+    }
+    rows[2][b.len()]
+}
+
+/// ## The candidate closest to the unknown `word`, if it is close enough to be a typing error.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::schema::suggestion;
+/// assert_eq!(suggestion("fliter_size", &["filter_size", "stride"]), Some("filter_size"));
+/// assert_eq!(suggestion("activation", &["filter_size", "stride"]), None);
+/// ```
+pub fn suggestion<'a>(word: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let tolerance = (word.chars().count() / 3).max(1);
+    candidates
+        .iter()
+        .map(|candidate| (distance(word, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= tolerance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The problems found so far, located in the optional `text` of the description.
+struct Checker<'a> {
+    lines: Option<Vec<&'a str>>,
+    problems: Vec<Problem>,
+    /// The number of tables visited so far with every key, and every key with its string value.
+    seen: HashMap<(String, Option<String>), usize>,
+    /// The position of the current layer among all layers, like in the trace.
+    position: usize,
+}
+
+/// The position of the keys and values of a table among the tables with the same ones.
+type Occurrences = HashMap<(String, Option<String>), usize>;
+
+impl<'a> Checker<'a> {
+    /// Visit `table`, returning the occurrences of its keys and values.
+    fn visit(&mut self, table: &toml::value::Table) -> Occurrences {
+        let mut occurrences = HashMap::new();
+        for (key, value) in table {
+            let mut tokens = vec![(key.clone(), None)];
+            if let Some(value) = value.as_str() {
+                tokens.push((key.clone(), Some(value.to_string())));
+            }
+            for token in tokens {
+                let seen = self.seen.entry(token.clone()).or_insert(0);
+                occurrences.insert(token, *seen);
+                *seen += 1;
+            }
+        }
+        occurrences
+    }
+
+    /// The (one-based) line of the `occurrence`-th `key`, with the string `value` if given.
+    fn line(&self, (key, value): &(String, Option<String>), occurrence: usize) -> Option<usize> {
+        let quoted = value.as_ref().map(|value| format!("\"{}\"", value));
+        self.lines
+            .as_ref()?
+            .iter()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                let count = values(line, key)
+                    .iter()
+                    .filter(|v| quoted.as_ref().is_none_or(|quoted| v.starts_with(quoted)))
+                    .count();
+                std::iter::repeat_n(i + 1, count)
+            })
+            .nth(occurrence)
+    }
+
+    /// Report the `message` about `location`, at the line of the `key` of the table of the
+    /// `occurrences`, or else of the name, type or operation of the table.
+    fn report(
+        &mut self,
+        location: &str,
+        occurrences: &Occurrences,
+        key: Option<&str>,
+        message: String,
+    ) {
+        let anchor = match key {
+            Some(key) => occurrences.get_key_value(&(key.to_string(), None)),
+            None => ["name", "type", "op"].iter().find_map(|key| {
+                occurrences
+                    .iter()
+                    .find(|((k, value), _)| k == key && value.is_some())
+            }),
+        };
+        let line = anchor.and_then(|(token, occurrence)| self.line(token, *occurrence));
+        self.problems.push(Problem {
+            line,
+            location: location.to_string(),
+            message,
+        });
+    }
+
+    /// Check that `table` at `location` has the `required` keys, and report the keys resembling
+    /// the `required` or `optional` ones.
+    fn keys(
+        &mut self,
+        location: &str,
+        table: &toml::value::Table,
+        occurrences: &Occurrences,
+        (required, optional): (&[&str], &[&str]),
+    ) {
+        let known: Vec<&str> = required.iter().chain(optional).copied().collect();
+        let mut misspelled = vec![];
+        for key in table.keys() {
+            if known.contains(&key.as_str()) {
+                continue;
+            }
+            if let Some(known) = suggestion(key, &known) {
+                misspelled.push(known);
+                self.report(
+                    location,
+                    occurrences,
+                    Some(key),
+                    format!("unknown key '{}', did you mean '{}'?", key, known),
+                );
+            }
+        }
+        for key in required {
+            if !table.contains_key(*key) && !misspelled.contains(key) {
+                let message = format!("the key '{}' is missing", key);
+                self.report(location, occurrences, None, message);
+            }
+        }
+    }
+
+    /// Check that the `formula` of a layer or node, if it has one, can be parsed.
+    fn formula(&mut self, location: &str, table: &toml::value::Table, occurrences: &Occurrences) {
+        if let Some(formula) = table.get("formula").and_then(toml::Value::as_str) {
+            if let Err(e) = Formula::parse(formula) {
+                let message = format!("invalid formula '{}': {}", formula, e);
+                self.report(location, occurrences, Some("formula"), message);
+            }
+        }
+    }
+
+    /// Check the `entries` of a list of layers.
+    fn entries(&mut self, entries: &[toml::Value]) {
+        for entry in entries {
+            let table = match entry.as_table() {
+                Some(table) => table,
+                None => continue,
+            };
+            if let Some(branches) = table.get("parallel").and_then(toml::Value::as_array) {
+                for branch in branches {
+                    if let Some(layers) = branch.get("layers").and_then(toml::Value::as_array) {
+                        self.entries(layers);
+                    }
+                }
+                continue;
+            }
+            self.position += 1;
+            let occurrences = self.visit(table);
+            let location = match table.get("name").and_then(toml::Value::as_str) {
+                Some(name) => format!("layer '{}'", name),
+                None => format!("layer {}", self.position),
+            };
+            let kind = match table.get("type") {
+                Some(kind) => kind.as_str().unwrap_or_default(),
+                None => {
+                    self.keys(&location, table, &occurrences, UNTYPED_LAYER);
+                    continue;
+                }
+            };
+            match LAYER_TYPES.iter().find(|(known, ..)| *known == kind) {
+                Some((_, required, optional)) => {
+                    let optional: Vec<&str> = optional.iter().chain(&["type"]).copied().collect();
+                    self.keys(&location, table, &occurrences, (required, &optional));
+                    self.formula(&location, table, &occurrences);
+                }
+                // The keys of custom layers are checked by the functions computing their geometry.
+                None if plugin::is_registered(kind) => {}
+                None => {
+                    let registered = plugin::registered();
+                    let types: Vec<&str> = LAYER_TYPES
+                        .iter()
+                        .map(|(kind, ..)| *kind)
+                        .chain(registered.iter().map(String::as_str))
+                        .collect();
+                    self.unknown(&location, &occurrences, "type", kind, &types)
+                }
+            }
+        }
+    }
+
+    /// Report the unknown `value` of `key`, suggesting the closest of the `known` values.
+    fn unknown(
+        &mut self,
+        location: &str,
+        occurrences: &Occurrences,
+        key: &str,
+        value: &str,
+        known: &[&str],
+    ) {
+        let message = match suggestion(value, known) {
+            Some(known) => format!("unknown {} '{}', did you mean '{}'?", key, value, known),
+            None => format!(
+                "unknown {} '{}', expected one of '{}'",
+                key,
+                value,
+                known.join("', '")
+            ),
+        };
+        self.report(location, occurrences, Some(key), message);
+    }
+
+    /// Check the `nodes` of a graph or subnetwork, whose locations end in `suffix`.
+    fn nodes(&mut self, nodes: &[toml::Value], suffix: &str) {
+        for (i, node) in nodes.iter().enumerate() {
+            let table = match node.as_table() {
+                Some(table) => table,
+                None => continue,
+            };
+            let occurrences = self.visit(table);
+            let location = match table.get("name").and_then(toml::Value::as_str) {
+                Some(name) => format!("node '{}'{}", name, suffix),
+                None => format!("node {}{}", i + 1, suffix),
+            };
+            self.keys(&location, table, &occurrences, NODE_KEYS);
+            self.formula(&location, table, &occurrences);
+            if let Some(op) = table.get("op").and_then(toml::Value::as_str) {
+                if !OPS.contains(&op) {
+                    self.unknown(&location, &occurrences, "op", op, OPS);
+                }
+            }
+        }
+    }
+
+    /// Check the keys of the tables in `array` at the `location` numbered by their position.
+    fn tables(&mut self, array: &[toml::Value], location: &str, keys: (&[&str], &[&str])) {
+        for (i, table) in array.iter().enumerate() {
+            if let Some(table) = table.as_table() {
+                let occurrences = self.visit(table);
+                self.keys(
+                    &format!("{} {}", location, i + 1),
+                    table,
+                    &occurrences,
+                    keys,
+                );
+            }
+        }
+    }
+}
+
+/// ## The problems of the structure of the description `value`, located in its `text` if given.
+///
+/// Descriptions defining `nodes` are checked as graphs, all others as lists of layers. The
+/// problems are sorted by their line.
+pub fn check(value: &toml::Value, text: Option<&str>) -> Vec<Problem> {
+    let mut checker = Checker {
+        lines: text.map(code_lines),
+        problems: vec![],
+        seen: HashMap::new(),
+        position: 0,
+    };
+    let table = match value.as_table() {
+        Some(table) => table,
+        None => return vec![],
+    };
+    let array = |key| {
+        table
+            .get(key)
+            .and_then(toml::Value::as_array)
+            .map_or(&[][..], Vec::as_slice)
+    };
+    let occurrences = checker.visit(table);
+    if table.contains_key("nodes") {
+        checker.keys("the graph", table, &occurrences, (&["nodes"], GRAPH_KEYS));
+        checker.tables(array("inputs"), "input", INPUT_KEYS);
+        checker.nodes(array("nodes"), "");
+        for subnetwork in array("subnetworks") {
+            if let Some(table) = subnetwork.as_table() {
+                let occurrences = checker.visit(table);
+                let name = table.get("name").and_then(toml::Value::as_str);
+                let location = format!("subnetwork '{}'", name.unwrap_or_default());
+                checker.keys(&location, table, &occurrences, SUBNETWORK_KEYS);
+                if let Some(nodes) = table.get("nodes").and_then(toml::Value::as_array) {
+                    checker.nodes(nodes, &format!(" of {}", location));
+                }
+            }
+        }
+    } else {
+        checker.keys(
+            "the network",
+            table,
+            &occurrences,
+            (&["layers"], LAYERS_KEYS),
+        );
+        checker.entries(array("layers"));
+    }
+    let mut problems = checker.problems;
+    problems.sort_by_key(|problem| problem.line);
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("fliter_size", "filter_size"), 1);
+        assert_eq!(distance("cnov", "conv"), 1);
+        assert_eq!(distance("maxpool", "max_pool"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "conv"), 4);
+    }
+
+    #[test]
+    fn test_check_reports_all_problems() {
+        let text = r#"
+[[layers]]
+type = "conv"
+fliter_size = 3
+padding = 1
+
+[[layers]]
+type = "maxpool"
+filter_size = 2
+stride = 2
+
+[[layers]]
+type = "upsample"
+name = "up"
+activation = "relu"
+"#;
+        let value: toml::Value = toml::from_str(text).unwrap();
+        let problems: Vec<String> = check(&value, Some(text))
+            .iter()
+            .map(Problem::to_string)
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                "line 4: layer 1: unknown key 'fliter_size', did you mean 'filter_size'?",
+                "line 8: layer 2: unknown type 'maxpool', did you mean 'max_pool'?",
+                "line 14: layer 'up': the key 'scale' is missing",
+            ]
+        );
+
+        let graph: toml::Value = toml::from_str(
+            r#"
+            nodes = [
+                { name = "a", op = "cnov", filter_size = 3 },
+                { name = "b", op = "add", input = ["a"] },
+            ]
+            "#,
+        )
+        .unwrap();
+        let problems: Vec<String> = check(&graph, None).iter().map(Problem::to_string).collect();
+        assert_eq!(
+            problems,
+            vec![
+                "node 'a': unknown op 'cnov', did you mean 'conv'?",
+                "node 'b': unknown key 'input', did you mean 'inputs'?",
+            ]
+        );
+    }
+}
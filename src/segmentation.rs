@@ -0,0 +1,231 @@
+//! Check that the outputs of a segmentation network have the resolution of its input.
+//!
+//! The logits of a segmentation network are compared pixel by pixel to the mask of the input, so
+//! after all the upsampling, every output needs the dimension of the input. An output that is off
+//! by a factor, e.g. half the resolution because an upsampling stage is missing, is reported with
+//! its ratio and offset to the input, together with the layers that would fix it: an `upsample`
+//! or `conv_transpose` layer for outputs that are too small, a `max_pool` layer for outputs that
+//! are too large, or a crop or padding of the output if the ratio is not an integer.
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::graph::{sides, Graph};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## An output of the network, and the layers fixing its resolution if it is not the input's.
+pub struct Output {
+    pub name: String,
+    pub dim: u32,
+    /// The alternative fixes, which are empty if the output has the resolution of the input.
+    pub fixes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The resolutions of the outputs of a network compared to the resolution of its input.
+pub struct Resolution {
+    pub input: String,
+    pub in_dim: u32,
+    pub outputs: Vec<Output>,
+}
+
+impl Resolution {
+    /// Whether all the outputs have the resolution of the input.
+    pub fn is_ok(&self) -> bool {
+        self.outputs.iter().all(|output| output.fixes.is_empty())
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "'{}': {}", self.input, self.in_dim)?;
+        for output in &self.outputs {
+            if output.fixes.is_empty() {
+                writeln!(f, "'{}': {}, ok", output.name, output.dim)?;
+                continue;
+            }
+            writeln!(
+                f,
+                "'{}': {}, {} of the input, {}",
+                output.name,
+                output.dim,
+                ratio(self.in_dim, output.dim),
+                offset(self.in_dim, output.dim)
+            )?;
+            for fix in &output.fixes {
+                writeln!(f, "  fix: {}", fix)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The ratio of `out_dim` to `in_dim`, e.g. `1/2`, `x2` or `0.47`.
+fn ratio(in_dim: u32, out_dim: u32) -> String {
+    match (in_dim, out_dim) {
+        (_, 0) | (0, _) => format!("{}/{}", out_dim, in_dim),
+        _ if in_dim.is_multiple_of(out_dim) => format!("1/{}", in_dim / out_dim),
+        _ if out_dim.is_multiple_of(in_dim) => format!("x{}", out_dim / in_dim),
+        _ => format!("{:.3}", f64::from(out_dim) / f64::from(in_dim)),
+    }
+}
+
+/// The offset of `out_dim` to `in_dim`, e.g. `256 short`.
+fn offset(in_dim: u32, out_dim: u32) -> String {
+    match out_dim < in_dim {
+        true => format!("{} short", in_dim - out_dim),
+        false => format!("{} over", out_dim - in_dim),
+    }
+}
+
+/// ## The layers appended to an output of dimension `out_dim` that give it the dimension `in_dim`.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::segmentation::fixes;
+/// assert_eq!(
+///     fixes(512, 256),
+///     [
+///         "append an `upsample` layer with `scale = 2`",
+///         "append a `conv_transpose` layer with `filter_size = 2`, `stride = 2`, `padding = 0`",
+///     ]
+/// );
+/// assert_eq!(fixes(512, 512), Vec::<String>::new());
+/// ```
+pub fn fixes(in_dim: u32, out_dim: u32) -> Vec<String> {
+    let crop_or_pad = |dim: u32| match dim > in_dim {
+        true => format!("crop the output by {}", sides(dim - in_dim)),
+        false => format!("pad the output by {}", sides(in_dim - dim)),
+    };
+    if out_dim == in_dim {
+        return vec![];
+    }
+    if out_dim == 0 || in_dim == 0 {
+        return vec![crop_or_pad(out_dim)];
+    }
+    if out_dim > in_dim {
+        let scale = out_dim / in_dim;
+        return match out_dim.is_multiple_of(in_dim) {
+            true => vec![format!(
+                "append a `max_pool` layer with `filter_size = {0}`, `stride = {0}`",
+                scale
+            )],
+            false => vec![crop_or_pad(out_dim)],
+        };
+    }
+
+    // The nearest integer scale, with a transposed convolution whose filter covers the stride
+    // that produces the dimension of the input exactly.
+    let scale = (in_dim + out_dim / 2) / out_dim;
+    if scale < 2 {
+        return vec![crop_or_pad(out_dim)];
+    }
+    let mut fixes = vec![];
+    let upsampled = u64::from(out_dim) * u64::from(scale);
+    match upsampled == u64::from(in_dim) {
+        true => fixes.push(format!(
+            "append an `upsample` layer with `scale = {}`",
+            scale
+        )),
+        false => fixes.push(format!(
+            "append an `upsample` layer with `scale = {}` and {}",
+            scale,
+            crop_or_pad(upsampled as u32)
+        )),
+    }
+    let filter = i64::from(in_dim) - (i64::from(out_dim) - 1) * i64::from(scale);
+    let padding = ((i64::from(scale) - filter).max(0) + 1) / 2;
+    let filter = filter + 2 * padding;
+    if filter >= 1 && filter <= i64::from(u16::MAX) {
+        fixes.push(format!(
+            "append a `conv_transpose` layer with `filter_size = {}`, `stride = {}`, `padding = {}`",
+            filter, scale, padding
+        ));
+    }
+    fixes
+}
+
+/// ## The resolutions of the outputs of `graph` for an input of dimension `in_dim`.
+///
+/// The outputs are compared to the first input.
+pub fn check(graph: &Graph, in_dim: u32) -> Result<Resolution, ConvDimError> {
+    let (input, shape) = graph.input_shapes(in_dim)[0];
+    let outputs = graph
+        .output_shapes(in_dim)?
+        .into_iter()
+        .map(|(name, output)| Output {
+            fixes: fixes(shape.dim, output.dim),
+            name,
+            dim: output.dim,
+        })
+        .collect();
+    Ok(Resolution {
+        input: input.to_string(),
+        in_dim: shape.dim,
+        outputs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_check() {
+        let graph = parse_architecture(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            stride = 2
+            padding = 1
+
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            stride = 2
+
+            [[layers]]
+            name = "logits"
+            type = "conv_transpose"
+            filter_size = 2
+            stride = 2
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let resolution = check(&graph, 100).unwrap();
+        assert!(!resolution.is_ok());
+        assert_eq!(
+            resolution.to_string(),
+            "'input': 100\n\
+             'logits': 48, 0.480 of the input, 52 short\n  \
+               fix: append an `upsample` layer with `scale = 2` and pad the output by 2 on each side\n  \
+               fix: append a `conv_transpose` layer with `filter_size = 6`, `stride = 2`, `padding = 0`\n"
+        );
+        assert_eq!(
+            (ratio(100, 50), ratio(100, 200)),
+            ("1/2".into(), "x2".into())
+        );
+
+        assert_eq!(
+            fixes(100, 25),
+            [
+                "append an `upsample` layer with `scale = 4`",
+                "append a `conv_transpose` layer with `filter_size = 4`, `stride = 4`, `padding = 0`",
+            ]
+        );
+        assert_eq!(
+            fixes(100, 99),
+            ["pad the output by 0 on one side and 1 on the other"]
+        );
+        assert_eq!(
+            fixes(100, 200),
+            ["append a `max_pool` layer with `filter_size = 2`, `stride = 2`"]
+        );
+        assert_eq!(fixes(100, 150), ["crop the output by 25 on each side"]);
+    }
+}
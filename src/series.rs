@@ -0,0 +1,267 @@
+//! The lengths of time series stored in CSV and Parquet files, and the output lengths of a network
+//! for them, e.g. to find the series that are too short for the convolutions of a 1D model.
+//!
+//! The length of a series in a CSV file is the number of rows with a value in its column, where
+//! the first row holds the names of the columns. The length of a column of a Parquet file is its
+//! number of rows, which is read from the footer of the file without decoding its data.
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::graph::Graph;
+use crate::thrift::{Struct, Value};
+use crate::trace::write_columns;
+
+/// The magic bytes at the start and at the end of a Parquet file.
+const PARQUET_MAGIC: &[u8] = b"PAR1";
+
+fn invalid(message: String) -> ConvDimError {
+    ConvDimError::Series { message }
+}
+
+/// The fields of the CSV `text` per row, separated by `delimiter` and optionally quoted.
+fn records(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let (mut records, mut record, mut field) = (vec![], vec![], String::new());
+    let (mut quoted, mut chars) = (false, text.chars().peekable());
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if quoted => field.push(c),
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+/// ## The length of the series in `column` of the CSV `text`, whose fields are separated by `delimiter`.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::series;
+/// let csv = "time,value\n0,1.5\n1,2.5\n2,\n";
+/// assert_eq!(series::csv_length(csv, ',', "value"), Ok(2));
+/// assert!(series::csv_length(csv, ',', "price").is_err());
+/// ```
+pub fn csv_length(text: &str, delimiter: char, column: &str) -> Result<u64, ConvDimError> {
+    let records = records(text, delimiter);
+    let index = records
+        .first()
+        .and_then(|names| names.iter().position(|name| name.trim() == column))
+        .ok_or_else(|| invalid(format!("there is no column '{}'.", column)))?;
+    Ok(records[1..]
+        .iter()
+        .filter(|record| {
+            record
+                .get(index)
+                .is_some_and(|value| !value.trim().is_empty())
+        })
+        .count() as u64)
+}
+
+/// ## The length of `column` of the Parquet file `bytes`, i.e. its number of rows.
+pub fn parquet_length(bytes: &[u8], column: &str) -> Result<u64, ConvDimError> {
+    let corrupt = || invalid("the file is not a Parquet file.".to_string());
+    if bytes.len() < 12 || !bytes.starts_with(PARQUET_MAGIC) || !bytes.ends_with(PARQUET_MAGIC) {
+        return Err(corrupt());
+    }
+    // The footer is followed by its length and the magic bytes.
+    let end = bytes.len() - 8;
+    let length = u32::from_le_bytes([bytes[end], bytes[end + 1], bytes[end + 2], bytes[end + 3]]);
+    let footer = end
+        .checked_sub(length as usize)
+        .map(|start| &bytes[start..end])
+        .ok_or_else(corrupt)?;
+    let metadata = Struct::decode(footer)
+        .map_err(|e| invalid(format!("the metadata cannot be read: {}", e)))?;
+    // The first element of the schema is its root, whose children are the columns.
+    let schema = metadata.structs(2);
+    if !schema.iter().skip(1).any(|element| {
+        element.string(4).as_deref() == Some(column)
+            && !matches!(element.get(5), Some(Value::Int(children)) if *children > 0)
+    }) {
+        return Err(invalid(format!("there is no column '{}'.", column)));
+    }
+    metadata
+        .int(3)
+        .and_then(|rows| u64::try_from(rows).ok())
+        .ok_or_else(|| invalid("the metadata has no number of rows.".to_string()))
+}
+
+/// ## The length of the series in `column` of the CSV, TSV or Parquet file at `path`.
+pub fn length(path: &Path, column: &str) -> Result<u64, ConvDimError> {
+    let context = |e: ConvDimError| match e {
+        ConvDimError::Series { message } => invalid(format!("'{}': {}", path.display(), message)),
+        e => e,
+    };
+    let bytes = std::fs::read(path).map_err(|e| invalid(format!("'{}': {}", path.display(), e)))?;
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("parquet") | Some("pq") => parquet_length(&bytes, column).map_err(context),
+        Some(extension) => {
+            let delimiter = if extension == "tsv" { '\t' } else { ',' };
+            csv_length(&String::from_utf8_lossy(&bytes), delimiter, column).map_err(context)
+        }
+        None => csv_length(&String::from_utf8_lossy(&bytes), ',', column).map_err(context),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## A series, its length if it can be read, and the output length or why there is none.
+pub struct Series {
+    pub path: PathBuf,
+    pub length: Option<u64>,
+    pub output: Result<u32, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The output lengths of a network for several series.
+pub struct Lengths {
+    pub series: Vec<Series>,
+}
+
+impl Lengths {
+    /// The number of series for every input and output length.
+    pub fn distribution(&self) -> BTreeMap<(u64, u32), usize> {
+        let mut distribution = BTreeMap::new();
+        for series in &self.series {
+            if let (Some(length), Ok(output)) = (series.length, &series.output) {
+                *distribution.entry((length, *output)).or_insert(0) += 1;
+            }
+        }
+        distribution
+    }
+}
+
+impl fmt::Display for Lengths {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "series:  {}\n", self.series.len())?;
+        let mut rows = vec![vec![
+            "length".to_string(),
+            "output".to_string(),
+            "series".to_string(),
+        ]];
+        for ((length, output), count) in self.distribution() {
+            rows.push(vec![
+                length.to_string(),
+                output.to_string(),
+                count.to_string(),
+            ]);
+        }
+        write_columns(f, &rows, &[0, 1, 2])?;
+        let failed: Vec<Vec<String>> = self
+            .series
+            .iter()
+            .filter_map(|series| {
+                let error = series.output.as_ref().err()?;
+                let length = series
+                    .length
+                    .map_or(String::new(), |length| length.to_string());
+                Some(vec![
+                    series.path.display().to_string(),
+                    length,
+                    error.clone(),
+                ])
+            })
+            .collect();
+        if !failed.is_empty() {
+            writeln!(f, "\nfailed:  {}", failed.len())?;
+            write_columns(f, &failed, &[1])?;
+        }
+        Ok(())
+    }
+}
+
+/// The output length of `graph` for a series of `length`.
+fn output(graph: &Graph, length: u64) -> Result<u32, ConvDimError> {
+    let in_dim = u32::try_from(length).map_err(|_| {
+        invalid(format!(
+            "the length {} exceeds the largest input of {}.",
+            length,
+            u32::MAX
+        ))
+    })?;
+    Ok(graph.output_shape(in_dim)?.dim)
+}
+
+/// ## The output lengths of `graph` for the series in `column` of the files at `paths`.
+pub fn lengths(graph: &Graph, paths: &[PathBuf], column: &str) -> Lengths {
+    let series = paths
+        .iter()
+        .map(|path| match length(path, column) {
+            Ok(length) => Series {
+                path: path.clone(),
+                length: Some(length),
+                output: output(graph, length).map_err(|e| e.to_string()),
+            },
+            Err(e) => Series {
+                path: path.clone(),
+                length: None,
+                output: Err(e.to_string()),
+            },
+        })
+        .collect();
+    Lengths { series }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_lengths() {
+        assert_eq!(
+            csv_length("id;\"a;b\"\n1;\"x\"\"\"\n2;\n3;y\n", ';', "a;b"),
+            Ok(2)
+        );
+
+        // The footer of a Parquet file with the column `value` and 1000 rows.
+        let footer = [
+            0x15, 0x02, 0x19, 0x2c, 0x48, 0x06, b's', b'c', b'h', b'e', b'm', b'a', 0x15, 0x02,
+            0x00, 0x48, 0x05, b'v', b'a', b'l', b'u', b'e', 0x00, 0x16, 0xd0, 0x0f, 0x00,
+        ];
+        let mut parquet = PARQUET_MAGIC.to_vec();
+        parquet.extend(footer);
+        parquet.extend((footer.len() as u32).to_le_bytes());
+        parquet.extend(PARQUET_MAGIC);
+        assert_eq!(parquet_length(&parquet, "value"), Ok(1000));
+        assert!(parquet_length(&parquet, "schema").is_err());
+        assert!(parquet_length(&parquet[..20], "value").is_err());
+
+        let graph = parse_architecture(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 5
+            stride = 2
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let lengths = lengths(&graph, &[PathBuf::from("missing.csv")], "value");
+        assert_eq!(lengths.series[0].length, None);
+        assert_eq!(output(&graph, 1000), Ok(498));
+        assert!(output(&graph, 4).is_err());
+    }
+}
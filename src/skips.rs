@@ -0,0 +1,398 @@
+//! Pair the downsampling and upsampling stages of encoder-decoder networks.
+//!
+//! In U-Net-like architectures, the feature map entering each downsampling stage of the encoder
+//! is passed via a skip connection to the decoder, where it is concatenated with the output of the
+//! corresponding upsampling stage. The stages are paired in reverse order: the last downsampling
+//! stage corresponds to the first upsampling stage and so forth. For the skip connections to work,
+//! the spatial dimensions of the paired feature maps need to agree, or the encoder feature map needs
+//! to be cropped as in the original U-Net.
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::fpn::{self, Stride};
+use crate::graph::{self, Op};
+use crate::layers::located_output_dim;
+use crate::{walk_layers, Architecture, Entry};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## A feature map at the end of a skip connection.
+pub struct Feature {
+    /// The name of the layer or node producing the feature map.
+    pub name: String,
+    pub dim: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## A downsampling stage of the encoder paired with an upsampling stage of the decoder.
+///
+/// The `encoder` feature map is the one entering the downsampling stage, the `decoder`
+/// feature map is the output of the upsampling stage.
+pub struct Pair {
+    pub encoder: Feature,
+    pub decoder: Feature,
+}
+
+impl Pair {
+    /// Whether the spatial dimensions of the paired feature maps agree.
+    pub fn is_aligned(&self) -> bool {
+        self.encoder.dim == self.decoder.dim
+    }
+
+    /// The crop or padding reconciling the paired feature maps, if they don't agree.
+    pub fn fix(&self) -> Option<String> {
+        if self.is_aligned() {
+            None
+        } else {
+            Some(graph::reconcile(
+                &self.encoder.name,
+                self.encoder.dim,
+                &self.decoder.name,
+                self.decoder.dim,
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+/// ## The result of pairing the stages of an encoder-decoder network.
+pub struct Pairing {
+    /// The paired stages, from the outermost to the innermost pair.
+    pub pairs: Vec<Pair>,
+    /// The names of the stages that could not be paired.
+    pub unpaired: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resampling {
+    Down,
+    Up,
+    None,
+}
+
+/// Classify a stage by its effective `stride`.
+fn resampling(stride: Stride) -> Resampling {
+    if stride.num > stride.den {
+        Resampling::Down
+    } else if stride.num < stride.den {
+        Resampling::Up
+    } else {
+        Resampling::None
+    }
+}
+
+/// A stage of the network: its name, the feature map entering it, its output dimension and resampling.
+type Stage = (String, Feature, u32, Resampling);
+
+/// The stages of a list of layers. Parallel blocks are considered a single stage.
+fn layer_stages(layers: &[Entry], in_dim: u32) -> Result<Vec<Stage>, ConvDimError> {
+    let mut stages = vec![];
+    let mut previous = graph::INPUT.to_string();
+    let mut dim = in_dim;
+    let mut count = 0;
+    for entry in layers {
+        let (name, out_dim, stride) = match entry {
+            Entry::Layer(layer) => {
+                count += 1;
+                let (_, layer_stride, _) = layer.geometry();
+                let stride = if layer.is_transposed() {
+                    Stride::ONE.upsample(layer_stride)
+                } else {
+                    Stride::ONE.downsample(layer_stride)
+                };
+                let name = match layer.name() {
+                    Some(name) => name.to_string(),
+                    None => format!("layer {}", count),
+                };
+                (name, located_output_dim(count, dim, layer)?, stride)
+            }
+            Entry::Parallel { parallel } => {
+                let mut layer_count = 0;
+                let out_dim = walk_layers(std::slice::from_ref(entry), dim, &mut |_, _| {
+                    layer_count += 1
+                })?;
+                let name = format!("layers {}-{}", count + 1, count + layer_count);
+                count += layer_count;
+                let stride = match parallel.first() {
+                    Some(branch) => {
+                        fpn::layer_strides(&branch.layers, Stride::ONE, &mut Default::default())
+                    }
+                    None => Stride::ONE,
+                };
+                (name, out_dim, stride)
+            }
+        };
+        stages.push((
+            name.clone(),
+            Feature {
+                name: previous,
+                dim,
+            },
+            out_dim,
+            resampling(stride),
+        ));
+        previous = name;
+        dim = out_dim;
+    }
+    Ok(stages)
+}
+
+/// The stages of a graph, in evaluation order.
+///
+/// The merges are not checked, since their inputs disagree exactly when the paired stages do.
+fn node_stages(graph: &graph::Graph, in_dim: u32) -> Result<Vec<Stage>, ConvDimError> {
+    let edges = graph.edges();
+    let nodes: HashMap<&str, &graph::Node> = graph
+        .nodes
+        .iter()
+        .map(|node| (node.name.as_str(), node))
+        .collect();
+    let mut dims: HashMap<String, u32> = graph
+        .input_shapes(in_dim)
+        .into_iter()
+        .map(|(name, shape)| (name.to_string(), shape.dim))
+        .collect();
+    let mut stages = vec![];
+    for (name, shape) in graph.unchecked_shapes(in_dim)? {
+        let node = nodes[name.as_str()];
+        let input = edges[name.as_str()][0];
+        let stride = match node.op {
+            Op::Conv => Stride::ONE.downsample(node.stride),
+            Op::Transposed => Stride::ONE.upsample(node.stride),
+            _ => Stride::ONE,
+        };
+        stages.push((
+            name.clone(),
+            Feature {
+                name: input.to_string(),
+                dim: dims[input],
+            },
+            shape.dim,
+            resampling(stride),
+        ));
+        dims.insert(name, shape.dim);
+    }
+    Ok(stages)
+}
+
+/// ## Pair the downsampling and upsampling stages of the network for an input of dimension `in_dim`.
+///
+/// Every upsampling stage is paired with the most recent downsampling stage that has not been
+/// paired yet, so that the innermost stages around the bottleneck are paired first.
+pub fn pair_skips(architecture: &Architecture, in_dim: u32) -> Result<Pairing, ConvDimError> {
+    let stages = match architecture {
+        Architecture::Layers(layers) => layer_stages(&layers.layers, in_dim)?,
+        Architecture::Graph(graph) => node_stages(graph, in_dim)?,
+    };
+
+    let mut pairing = Pairing::default();
+    let mut open: Vec<Feature> = vec![];
+    let mut down_names: Vec<String> = vec![];
+    for (name, input, out_dim, resampling) in stages {
+        match resampling {
+            Resampling::Down => {
+                open.push(input);
+                down_names.push(name);
+            }
+            Resampling::Up => match open.pop() {
+                Some(encoder) => {
+                    down_names.pop();
+                    pairing.pairs.push(Pair {
+                        encoder,
+                        decoder: Feature { name, dim: out_dim },
+                    });
+                }
+                None => pairing.unpaired.push(name),
+            },
+            Resampling::None => {}
+        }
+    }
+    pairing.unpaired.extend(down_names);
+    // Report the pairs from the outermost to the innermost one.
+    pairing.pairs.reverse();
+    Ok(pairing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Layers;
+
+    #[test]
+    fn test_aligned_autoencoder() {
+        let layers: Layers = toml::from_str(
+            r#"
+            [[layers]]
+            name = "enc1"
+            filter_size = 3
+            stride = 1
+            padding = 1
+            transposed = false
+
+            [[layers]]
+            filter_size = 2
+            stride = 2
+            padding = 0
+            transposed = false
+
+            [[layers]]
+            name = "enc2"
+            filter_size = 3
+            stride = 1
+            padding = 1
+            transposed = false
+
+            [[layers]]
+            filter_size = 2
+            stride = 2
+            padding = 0
+            transposed = false
+
+            [[layers]]
+            name = "dec2"
+            filter_size = 2
+            stride = 2
+            padding = 0
+            transposed = true
+
+            [[layers]]
+            name = "dec1"
+            filter_size = 2
+            stride = 2
+            padding = 0
+            transposed = true
+            "#,
+        )
+        .unwrap();
+
+        let pairing = pair_skips(&Architecture::Layers(layers), 64).unwrap();
+        assert!(pairing.unpaired.is_empty());
+        let names: Vec<(&str, &str)> = pairing
+            .pairs
+            .iter()
+            .map(|pair| (pair.encoder.name.as_str(), pair.decoder.name.as_str()))
+            .collect();
+        assert_eq!(names, vec![("enc1", "dec1"), ("enc2", "dec2")]);
+        assert!(pairing.pairs.iter().all(Pair::is_aligned));
+    }
+
+    #[test]
+    fn test_unet_crops() {
+        // Two levels of the original U-Net with unpadded convolutions.
+        let layers: Layers = toml::from_str(
+            r#"
+            [[layers]]
+            filter_size = 3
+            stride = 1
+            padding = 0
+            transposed = false
+
+            [[layers]]
+            name = "enc1"
+            filter_size = 3
+            stride = 1
+            padding = 0
+            transposed = false
+
+            [[layers]]
+            filter_size = 2
+            stride = 2
+            padding = 0
+            transposed = false
+
+            [[layers]]
+            filter_size = 3
+            stride = 1
+            padding = 0
+            transposed = false
+
+            [[layers]]
+            filter_size = 3
+            stride = 1
+            padding = 0
+            transposed = false
+
+            [[layers]]
+            name = "up1"
+            filter_size = 2
+            stride = 2
+            padding = 0
+            transposed = true
+
+            [[layers]]
+            filter_size = 3
+            stride = 1
+            padding = 0
+            transposed = false
+            "#,
+        )
+        .unwrap();
+
+        let pairing = pair_skips(&Architecture::Layers(layers), 572).unwrap();
+        assert_eq!(pairing.pairs.len(), 1);
+        let pair = &pairing.pairs[0];
+        assert_eq!(pair.encoder.dim, 568);
+        assert_eq!(pair.decoder.dim, 560);
+        assert_eq!(
+            pair.fix().unwrap(),
+            "offset of 8: crop 'enc1' by 4 on each side or pad 'up1' by 4 on each side"
+        );
+    }
+
+    #[test]
+    fn test_unpaired_stages() {
+        let graph: graph::Graph = toml::from_str(
+            r#"
+            [[nodes]]
+            name = "down1"
+            op = "conv"
+            filter_size = 2
+            stride = 2
+
+            [[nodes]]
+            name = "down2"
+            op = "conv"
+            filter_size = 2
+            stride = 2
+
+            [[nodes]]
+            name = "up"
+            op = "transposed"
+            filter_size = 2
+            stride = 2
+
+            [[nodes]]
+            name = "skip"
+            op = "concat"
+            inputs = ["down1", "up"]
+            "#,
+        )
+        .unwrap();
+
+        let graph = Architecture::Graph(graph);
+        let pairing = pair_skips(&graph, 64).unwrap();
+        assert_eq!(
+            pairing.pairs,
+            vec![Pair {
+                encoder: Feature {
+                    name: "down1".to_string(),
+                    dim: 32
+                },
+                decoder: Feature {
+                    name: "up".to_string(),
+                    dim: 32
+                },
+            }]
+        );
+        assert_eq!(pairing.unpaired, vec!["down1".to_string()]);
+
+        // The pairs are reported even though the concatenation of their outputs fails.
+        let pairing = pair_skips(&graph, 66).unwrap();
+        assert_eq!(
+            (pairing.pairs[0].encoder.dim, pairing.pairs[0].decoder.dim),
+            (33, 32)
+        );
+        assert!(pairing.pairs[0].fix().is_some());
+    }
+}
@@ -0,0 +1,163 @@
+//! Snapshots of the trace of a network, e.g. to catch edits of a description in CI that change its
+//! shapes by accident.
+//!
+//! A snapshot is the [CSV summary](crate::summary::csv) of all the layers of a network for an input
+//! dimension, after a comment line recording that dimension. Checking a network against its
+//! snapshot computes the summary again, and lists the lines that were removed or added since.
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::graph::Graph;
+use crate::summary;
+
+/// The start of the first line of a snapshot, which is followed by the input dimension.
+const HEADER: &str = "# convdim snapshot, input dimension ";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## A line of a snapshot that differs from the current trace.
+pub enum Change {
+    /// A line of the snapshot that is not in the current trace.
+    Removed(String),
+    /// A line of the current trace that is not in the snapshot.
+    Added(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The lines that differ between a snapshot and the current trace, in the order of the lines.
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    /// Whether the current trace matches the snapshot.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            match change {
+                Change::Removed(line) => writeln!(f, "- {}", line)?,
+                Change::Added(line) => writeln!(f, "+ {}", line)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// ## The snapshot of the trace of `graph` for the input dimension `in_dim`.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{snapshot, Architecture, Network};
+/// let network = Network::builder().conv(3, 2, 1).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let recorded = snapshot::record(&graph, 32).unwrap();
+/// assert!(snapshot::check(&graph, &recorded, None).unwrap().is_empty());
+///
+/// let changed = Network::builder().conv(3, 2, 0).build();
+/// let changed = Architecture::Layers(changed.layers().clone()).into_graph();
+/// assert_eq!(snapshot::check(&changed, &recorded, None).unwrap().changes.len(), 2);
+/// ```
+pub fn record(graph: &Graph, in_dim: u32) -> Result<String, ConvDimError> {
+    let rows = summary::rows(graph, in_dim)?;
+    Ok(format!("{}{}\n{}", HEADER, in_dim, summary::csv(&rows)))
+}
+
+/// ## The input dimension recorded in the first line of `snapshot`.
+pub fn in_dim(snapshot: &str) -> Result<u32, ConvDimError> {
+    snapshot
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix(HEADER))
+        .and_then(|dim| dim.trim().parse().ok())
+        .ok_or_else(|| ConvDimError::Snapshot {
+            message: format!(
+                "the first line does not record the input dimension like '{}224'.",
+                HEADER
+            ),
+        })
+}
+
+/// ## The lines removed from `expected` and added in `actual`, by their longest common subsequence.
+pub fn diff(expected: &str, actual: &str) -> Diff {
+    let (expected, actual): (Vec<&str>, Vec<&str>) =
+        (expected.lines().collect(), actual.lines().collect());
+    // The length of the longest common subsequence of the lines from `i` and `j` on.
+    let mut common = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j, mut changes) = (0, 0, vec![]);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            i += 1;
+            j += 1;
+        } else if j == actual.len() || (i < expected.len() && common[i + 1][j] >= common[i][j + 1])
+        {
+            changes.push(Change::Removed(expected[i].to_string()));
+            i += 1;
+        } else {
+            changes.push(Change::Added(actual[j].to_string()));
+            j += 1;
+        }
+    }
+    Diff { changes }
+}
+
+/// ## The differences between `snapshot` and the trace of `graph`.
+///
+/// The trace is computed for `in_dim`, or for the input dimension recorded in the snapshot.
+pub fn check(graph: &Graph, snapshot: &str, in_dim: Option<u32>) -> Result<Diff, ConvDimError> {
+    let in_dim = match in_dim {
+        Some(in_dim) => in_dim,
+        None => self::in_dim(snapshot)?,
+    };
+    Ok(diff(snapshot, &record(graph, in_dim)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_snapshot() {
+        let graph = |stride: u32| {
+            parse_architecture(&format!(
+                "[[layers]]\ntype = \"conv\"\nfilter_size = 3\nstride = {}\nchannels = 8\n\n\
+                 [[layers]]\ntype = \"relu\"\n",
+                stride
+            ))
+            .unwrap()
+            .into_graph()
+        };
+        let recorded = record(&graph(2), 64).unwrap();
+        assert!(recorded.starts_with("# convdim snapshot, input dimension 64\nindex,name,"));
+        assert_eq!(in_dim(&recorded), Ok(64));
+        assert!(check(&graph(2), &recorded, None).unwrap().is_empty());
+
+        let changed = check(&graph(1), &recorded, None).unwrap();
+        assert_eq!(changed.changes.len(), 4);
+        assert!(matches!(&changed.changes[0], Change::Removed(line) if line.contains(",31,31,")));
+        assert!(matches!(&changed.changes[2], Change::Added(line) if line.contains(",62,62,")));
+        assert!(changed.to_string().starts_with("- 1,layer1,conv,3,2,"));
+
+        assert_eq!(
+            check(&graph(2), &recorded, Some(65)).unwrap().changes.len(),
+            8
+        );
+        assert!(in_dim("index,name\n").is_err());
+    }
+}
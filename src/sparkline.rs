@@ -0,0 +1,100 @@
+//! One-line sparklines of the dimension after every layer of a network.
+//!
+//! A sparkline is a compact version of a chart, e.g. `█▄▂▁▂▄█` for an autoencoder, which fits in
+//! the cell of a table or a line of a log where a trace or a chart is too verbose.
+use std::fmt;
+
+use crate::error::ConvDimError;
+use crate::graph::Graph;
+
+/// The blocks of a sparkline, from the smallest dimension to the largest.
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## A sparkline of the output dimensions of successive layers.
+///
+/// Every layer is a block whose height is proportional to its dimension, rounded up, so the largest
+/// dimension is a full block and every dimension is at least the lowest block.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::sparkline::Sparkline;
+/// let sparkline = Sparkline::from(vec![64, 32, 16, 8, 16, 32, 64]);
+/// assert_eq!(sparkline.to_string(), "█▄▂▁▂▄█");
+/// ```
+pub struct Sparkline {
+    pub dims: Vec<u32>,
+}
+
+impl From<Vec<u32>> for Sparkline {
+    fn from(dims: Vec<u32>) -> Sparkline {
+        Sparkline { dims }
+    }
+}
+
+impl Sparkline {
+    /// ## The sparkline of the first input and the nodes of `graph` for an input dimension `in_dim`.
+    pub fn new(graph: &Graph, in_dim: u32) -> Result<Sparkline, ConvDimError> {
+        let mut dims: Vec<u32> = graph
+            .input_shapes(in_dim)
+            .into_iter()
+            .take(1)
+            .map(|(_, shape)| shape.dim)
+            .collect();
+        dims.extend(
+            graph
+                .shapes(in_dim)?
+                .into_iter()
+                .map(|(_, shape)| shape.dim),
+        );
+        Ok(Sparkline { dims })
+    }
+}
+
+impl fmt::Display for Sparkline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let max = u64::from(self.dims.iter().copied().max().unwrap_or(0).max(1));
+        let levels = BLOCKS.len() as u64;
+        for &dim in &self.dims {
+            let level = (u64::from(dim) * levels).div_ceil(max).clamp(1, levels);
+            write!(f, "{}", BLOCKS[level as usize - 1])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_sparkline() {
+        let graph = parse_architecture(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            stride = 2
+
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            stride = 1
+            padding = 1
+
+            [[layers]]
+            type = "conv_transpose"
+            filter_size = 4
+            stride = 2
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let sparkline = Sparkline::new(&graph, 33).unwrap();
+        assert_eq!(sparkline.dims, [33, 16, 16, 34]);
+        assert_eq!(sparkline.to_string(), "█▄▄█");
+        assert_eq!(Sparkline::from(vec![]).to_string(), "");
+    }
+}
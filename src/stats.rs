@@ -0,0 +1,206 @@
+//! Statistics of the spatial dimensions along a network, e.g. the size of the bottleneck of an
+//! encoder-decoder network.
+//!
+//! The nodes are considered in the order in which they are evaluated, starting from the (first)
+//! input. A stage is a run of nodes along which the dimension only shrinks or only grows, so an
+//! encoder-decoder network has a contracting and an expanding stage, which meet at its bottleneck.
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::graph::Graph;
+use crate::trace::write_columns;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The dimension of the output of a node, labelled by its name.
+pub struct Extreme {
+    pub name: String,
+    pub dim: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## A run of nodes along which the dimension only shrinks or only grows.
+///
+/// The stage starts after the node `from`, whose output has the dimension `in_dim`, and ends with
+/// the node `to`, whose output has the dimension `out_dim`.
+pub struct Stage {
+    pub from: String,
+    pub to: String,
+    pub in_dim: u32,
+    pub out_dim: u32,
+}
+
+impl Stage {
+    /// ## Whether the dimension shrinks along the stage.
+    pub fn is_contraction(&self) -> bool {
+        self.out_dim < self.in_dim
+    }
+
+    /// The factor by which the dimension shrinks or grows, e.g. `/8` or `x2.5`.
+    fn factor(&self) -> String {
+        let (large, small, symbol) = match self.is_contraction() {
+            true => (self.in_dim, self.out_dim, "/"),
+            false => (self.out_dim, self.in_dim, "x"),
+        };
+        match small {
+            0 => format!("{}inf", symbol),
+            small if large % small == 0 => format!("{}{}", symbol, large / small),
+            small => format!("{}{:.2}", symbol, f64::from(large) / f64::from(small)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The smallest and largest outputs of the nodes of a network, and its stages.
+///
+/// The smallest output is the bottleneck of the network. Of several nodes with the same
+/// dimension, the first one is reported.
+pub struct Stats {
+    pub bottleneck: Extreme,
+    pub maximum: Extreme,
+    pub stages: Vec<Stage>,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rows = vec![];
+        for (label, extreme) in [
+            ("bottleneck:", &self.bottleneck),
+            ("maximum:", &self.maximum),
+        ] {
+            rows.push(vec![
+                label.to_string(),
+                extreme.dim.to_string(),
+                format!("at '{}'", extreme.name),
+            ]);
+        }
+        for stage in &self.stages {
+            let label = match stage.is_contraction() {
+                true => "contraction:",
+                false => "expansion:",
+            };
+            rows.push(vec![
+                label.to_string(),
+                format!("{} -> {} ({})", stage.in_dim, stage.out_dim, stage.factor()),
+                format!("from '{}' to '{}'", stage.from, stage.to),
+            ]);
+        }
+        write_columns(f, &rows, &[])
+    }
+}
+
+/// ## The statistics of the dimensions of the nodes of `graph` for an input of dimension `in_dim`.
+///
+/// Returns an error if the graph has no nodes or cannot be evaluated.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{stats, Architecture, Network};
+/// let network = Network::builder().conv(3, 2, 1).conv(3, 2, 1).conv_t(2, 2, 0).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let stats = stats::stats(&graph, 64).unwrap();
+/// assert_eq!((stats.bottleneck.name.as_str(), stats.bottleneck.dim), ("layer2", 16));
+/// assert_eq!(stats.stages.len(), 2);
+/// ```
+pub fn stats(graph: &Graph, in_dim: u32) -> Result<Stats, ConvDimError> {
+    let shapes = graph.shapes(in_dim)?;
+    let dims: Vec<(&str, u32)> = shapes
+        .iter()
+        .map(|(name, shape)| (name.as_str(), shape.dim))
+        .collect();
+    let extreme = |(name, dim): (&str, u32)| Extreme {
+        name: name.to_string(),
+        dim,
+    };
+    // The first of the smallest and largest dimensions, since `min_by_key` and `max_by_key`
+    // return the first and the last of equal elements.
+    let bottleneck = dims.iter().copied().min_by_key(|&(_, dim)| dim);
+    let maximum = dims.iter().copied().rev().max_by_key(|&(_, dim)| dim);
+    let (bottleneck, maximum) = match (bottleneck, maximum) {
+        (Some(bottleneck), Some(maximum)) => (extreme(bottleneck), extreme(maximum)),
+        _ => return Err(ConvDimError::EmptyGraph),
+    };
+
+    let (input, shape) = graph.input_shapes(in_dim)[0];
+    let mut stages: Vec<Stage> = vec![];
+    let mut previous = shape.dim;
+    for (name, dim) in dims {
+        let contracting = dim < previous;
+        match stages.last_mut() {
+            _ if dim == previous => {}
+            Some(stage) if stage.is_contraction() == contracting => {
+                stage.to = name.to_string();
+                stage.out_dim = dim;
+            }
+            // A new stage starts at the end of the previous one, or at the input.
+            last => {
+                let (from, in_dim) = match last {
+                    Some(stage) => (stage.to.clone(), stage.out_dim),
+                    None => (input.to_string(), shape.dim),
+                };
+                stages.push(Stage {
+                    from,
+                    to: name.to_string(),
+                    in_dim,
+                    out_dim: dim,
+                });
+            }
+        }
+        previous = dim;
+    }
+    Ok(Stats {
+        bottleneck,
+        maximum,
+        stages,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_stats() {
+        let graph = parse_architecture(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            stride = 2
+            padding = 1
+
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            padding = 1
+
+            [[layers]]
+            type = "max_pool"
+            filter_size = 2
+            stride = 2
+
+            [[layers]]
+            type = "upsample"
+            scale = 4
+
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let stats = stats(&graph, 64).unwrap();
+        assert_eq!(
+            stats.to_string(),
+            "bottleneck:   16                at 'layer3'\n\
+             maximum:      64                at 'layer4'\n\
+             contraction:  64 -> 16 (/4)     from 'input' to 'layer3'\n\
+             expansion:    16 -> 64 (x4)     from 'layer3' to 'layer4'\n\
+             contraction:  64 -> 62 (/1.03)  from 'layer4' to 'layer5'\n"
+        );
+    }
+}
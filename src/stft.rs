@@ -0,0 +1,107 @@
+//! The number of frames of a short-time Fourier transform, e.g. the time axis of a spectrogram.
+//!
+//! The window moves over the signal like the filter of a convolution whose stride is the hop
+//! length, after padding the signal according to the convention of the library:
+//!
+//! - `center`: the signal is padded by half the window on both sides, like `librosa.stft`,
+//!   `torch.stft` and `torchaudio.transforms.Spectrogram` with `center=True`,
+//! - `reflect`: the same padding by reflection, which needs a signal longer than half the window,
+//!   like the default `pad_mode` of `torch.stft`,
+//! - `none`: the signal is not padded, like `center=False`, so the frames lie within the signal,
+//! - `kaldi`: every hop starting in the signal is a frame, like the Kaldi features of
+//!   `torchaudio.compliance.kaldi` with `snip_edges=False`.
+use std::str::FromStr;
+
+use crate::error::ConvDimError;
+use crate::layers::conv_output_dim;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## The padding of the signal before it is cut into frames.
+pub enum Padding {
+    Center,
+    Reflect,
+    None,
+    Kaldi,
+}
+
+impl FromStr for Padding {
+    type Err = ConvDimError;
+
+    fn from_str(text: &str) -> Result<Padding, ConvDimError> {
+        match text {
+            "center" => Ok(Padding::Center),
+            "reflect" => Ok(Padding::Reflect),
+            "none" => Ok(Padding::None),
+            "kaldi" => Ok(Padding::Kaldi),
+            _ => Err(invalid(format!(
+                "'{}' is not one of the paddings 'center', 'reflect', 'none' and 'kaldi'.",
+                text
+            ))),
+        }
+    }
+}
+
+fn invalid(message: String) -> ConvDimError {
+    ConvDimError::Stft { message }
+}
+
+/// ## The number of frames of a signal of `length` samples with `window` and `hop` samples.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::stft::{self, Padding};
+/// // One second at 16 kHz with a window of 400 samples and a hop of 160 samples.
+/// assert_eq!(stft::frames(16_000, 400, 160, Padding::Center), Ok(101));
+/// assert_eq!(stft::frames(16_000, 400, 160, Padding::None), Ok(98));
+/// ```
+pub fn frames(length: u64, window: u64, hop: u64, padding: Padding) -> Result<u64, ConvDimError> {
+    if window == 0 || hop == 0 {
+        return Err(invalid(
+            "the window and the hop need to be positive.".to_string(),
+        ));
+    }
+    let pad = match padding {
+        Padding::Center => window / 2,
+        Padding::Reflect if length > window / 2 => window / 2,
+        Padding::Reflect => {
+            return Err(invalid(format!(
+                "the signal of {} samples cannot be reflected to pad it by {} samples.",
+                length,
+                window / 2
+            )))
+        }
+        Padding::None => 0,
+        Padding::Kaldi => return Ok((length + hop / 2) / hop),
+    };
+    conv_output_dim(length, window, pad, hop, 1).map_err(|_| {
+        invalid(format!(
+            "the signal of {} samples is shorter than the window of {} samples.",
+            length, window
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames() {
+        assert_eq!("kaldi".parse(), Ok(Padding::Kaldi));
+        assert!("same".parse::<Padding>().is_err());
+
+        // librosa.stft(np.zeros(22050), n_fft=2048, hop_length=512) has 44 frames.
+        assert_eq!(frames(22_050, 2048, 512, Padding::Center), Ok(44));
+        assert_eq!(frames(22_050, 2048, 512, Padding::None), Ok(40));
+        // An odd window is padded by 3 samples on both sides.
+        assert_eq!(frames(100, 7, 10, Padding::Center), Ok(10));
+        // kaldi.fbank(snip_edges=False) of one second with a shift of 10 ms.
+        assert_eq!(frames(16_000, 400, 160, Padding::Kaldi), Ok(100));
+
+        assert!(frames(300, 400, 160, Padding::None).is_err());
+        assert!(frames(200, 400, 160, Padding::Reflect).is_err());
+        assert_eq!(frames(201, 400, 160, Padding::Reflect), Ok(2));
+        assert!(frames(16_000, 400, 0, Padding::Center).is_err());
+    }
+}
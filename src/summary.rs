@@ -0,0 +1,637 @@
+//! Summaries of the layers of a network, e.g. for the architecture tables of papers.
+//!
+//! Every input and node of the network is summarized by a row with its parameters, its output
+//! shape, its number of weights and biases, the floating point operations it performs and its
+//! receptive field. The numbers of weights and operations are only known for the layers whose
+//! input and output channels are both known.
+//!
+//! The receptive field of a node is the size of the region of the input that its outputs depend
+//! on, and its jump is the distance between the centers of the receptive fields of neighboring
+//! outputs, in pixels of the input. A convolution with filter size `f` and stride `s` grows the
+//! receptive field by `(f - 1)` times the jump of its input, and multiplies the jump by `s`.
+//! Transposed convolutions divide the jump by `s` instead, and each of their outputs depends on
+//! `ceil(f / s)` neighboring inputs. Merges have the largest receptive field of their inputs.
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::export::tikz::escape;
+use crate::graph::{Graph, Node, Op, Shape};
+use crate::trace::write_columns;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+/// ## The summary of an input or node of a network.
+pub struct Row<'a> {
+    pub name: &'a str,
+    /// The node, which is `None` for the inputs of the network.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node: Option<&'a Node>,
+    pub shape: Shape,
+    /// The number of weights and biases, if the channels are known.
+    pub params: Option<u64>,
+    /// The number of floating point operations, counting multiplications and additions separately.
+    pub flops: Option<u64>,
+    pub receptive_field: u64,
+    pub jump: f64,
+}
+
+impl<'a> Row<'a> {
+    /// The kind of layer, e.g. `conv` or `input`.
+    pub fn kind(&self) -> &'static str {
+        match self.node.map(|node| node.op) {
+            None => "input",
+            Some(Op::Conv) => "conv",
+            Some(Op::Transposed) => "transposed conv",
+            Some(Op::Add) => "add",
+            Some(Op::Concat) => "concat",
+            Some(Op::Subnetwork) => "subnetwork",
+            Some(Op::BatchNorm) => "batchnorm",
+            Some(Op::Relu) => "relu",
+            Some(Op::Dropout) => "dropout",
+            Some(Op::Identity) => "identity",
+        }
+    }
+
+    /// The number of values of the output, if its channels are known.
+    ///
+    /// The number saturates, though [`rows`] only summarizes outputs whose values can be counted.
+    pub fn activations(&self) -> Option<u64> {
+        let dim = self.shape.dim as u64;
+        self.shape
+            .channels
+            .map(|channels| dim.saturating_mul(dim).saturating_mul(channels as u64))
+    }
+}
+
+/// The product of `factors`, if it fits into 64 bits.
+fn product(factors: &[u64]) -> Option<u64> {
+    factors
+        .iter()
+        .try_fold(1u128, |product, &factor| {
+            product.checked_mul(u128::from(factor))
+        })
+        .and_then(|product| u64::try_from(product).ok())
+}
+
+/// The error about the `counted` values of the input or node `name`, which exceed 64 bits.
+fn too_many(name: &str, counted: &str) -> ConvDimError {
+    ConvDimError::Summary {
+        message: format!(
+            "the {} of '{}' exceed the range of 64-bit integers.",
+            counted, name
+        ),
+    }
+}
+
+/// Check that the values of the output `shape` of the input or node `name` can be counted.
+fn check_activations(name: &str, shape: Shape) -> Result<(), ConvDimError> {
+    let dim = u64::from(shape.dim);
+    match shape.channels {
+        Some(channels) if product(&[dim, dim, u64::from(channels)]).is_none() => {
+            Err(too_many(name, "output values"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// ## Summarize the inputs and nodes of `graph` for an input of dimension `in_dim`.
+///
+/// The nodes are summarized in the order in which they are evaluated. The numbers of values,
+/// weights and operations are counted in 64 bits, and an input too large for them is an error.
+pub fn rows(graph: &Graph, in_dim: u32) -> Result<Vec<Row<'_>>, ConvDimError> {
+    let mut rows: Vec<Row> = graph
+        .input_shapes(in_dim)
+        .into_iter()
+        .map(|(name, shape)| Row {
+            name,
+            node: None,
+            shape,
+            params: Some(0),
+            flops: Some(0),
+            receptive_field: 1,
+            jump: 1.0,
+        })
+        .collect();
+    for row in &rows {
+        check_activations(row.name, row.shape)?;
+    }
+    let nodes: HashMap<&str, &Node> = graph
+        .nodes
+        .iter()
+        .map(|node| (node.name.as_str(), node))
+        .collect();
+    let edges = graph.edges();
+    let mut computed: HashMap<&str, usize> = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| (row.name, i))
+        .collect();
+    for (name, shape) in graph.shapes(in_dim)? {
+        let node = nodes[name.as_str()];
+        check_activations(&node.name, shape)?;
+        let count =
+            |factors: &[u64]| product(factors).ok_or_else(|| too_many(&node.name, "operations"));
+        let inputs: Vec<&Row> = edges[node.name.as_str()]
+            .iter()
+            .map(|input| &rows[computed[input]])
+            .collect();
+        let input = inputs[0];
+        let (in_dim, out_dim) = (input.shape.dim as u64, shape.dim as u64);
+        let filter_size = node.filter_size.unwrap_or(1) as u64;
+        let (params, flops) = match (node.op, input.shape.channels, shape.channels) {
+            // Weighted (transposed) convolutions, which declare their output channels.
+            (Op::Conv, Some(c_in), Some(c_out)) | (Op::Transposed, Some(c_in), Some(c_out))
+                if node.channels.is_some() =>
+            {
+                let params = || too_many(&node.name, "parameters");
+                let weights = product(&[filter_size, filter_size, c_in as u64, c_out as u64])
+                    .ok_or_else(params)?;
+                // A convolution applies all weights at every output, a transposed convolution
+                // at every input.
+                let positions = if node.op == Op::Conv { out_dim } else { in_dim };
+                (
+                    Some(weights.checked_add(c_out as u64).ok_or_else(params)?),
+                    Some(count(&[2, weights, positions, positions])?),
+                )
+            }
+            (Op::Conv, _, _) | (Op::Transposed, _, _) if node.channels.is_some() => (None, None),
+            // Pooling compares the values of the filter at every output, upsampling only copies.
+            (Op::Transposed, _, _) => (Some(0), Some(0)),
+            (Op::Conv, _, Some(c)) => (
+                Some(0),
+                Some(count(&[
+                    filter_size,
+                    filter_size,
+                    c as u64,
+                    out_dim,
+                    out_dim,
+                ])?),
+            ),
+            (Op::Add, _, Some(c)) => (
+                Some(0),
+                Some(count(&[
+                    inputs.len() as u64 - 1,
+                    c as u64,
+                    out_dim,
+                    out_dim,
+                ])?),
+            ),
+            (Op::Concat, _, _) | (Op::Subnetwork, _, _) => (Some(0), Some(0)),
+            // A batch normalization scales and shifts every channel, which is fused into the
+            // preceding convolution for inference, and dropout is disabled.
+            (Op::BatchNorm, _, Some(c)) => (
+                Some(2 * c as u64),
+                Some(count(&[2, c as u64, out_dim, out_dim])?),
+            ),
+            (Op::Relu, _, Some(c)) => (Some(0), Some(count(&[c as u64, out_dim, out_dim])?)),
+            (Op::Dropout, _, _) | (Op::Identity, _, _) => (Some(0), Some(0)),
+            _ => (Some(0), None),
+        };
+
+        let stride = node.stride as f64;
+        let (receptive_field, jump) = match node.op {
+            Op::Conv => (
+                input
+                    .receptive_field
+                    .saturating_add(((filter_size - 1) as f64 * input.jump).round() as u64),
+                input.jump * stride,
+            ),
+            Op::Transposed => (
+                input.receptive_field.saturating_add(
+                    ((filter_size.div_ceil(node.stride as u64) - 1) as f64 * input.jump).round()
+                        as u64,
+                ),
+                input.jump / stride,
+            ),
+            _ => {
+                let widest = inputs.iter().max_by_key(|row| row.receptive_field).unwrap();
+                (widest.receptive_field, widest.jump)
+            }
+        };
+
+        computed.insert(&node.name, rows.len());
+        rows.push(Row {
+            name: &node.name,
+            node: Some(node),
+            shape,
+            params,
+            flops,
+            receptive_field,
+            jump,
+        });
+    }
+    Ok(rows)
+}
+
+/// ## The design issues of the layers summarized by `rows`, e.g. for design reviews.
+///
+/// The issues are convolutions that ignore the last rows and columns of their input since their
+/// stride doesn't divide it evenly, convolutions padding by at least their filter size, which
+/// produces outputs depending on nothing but padding, and layers whose channels are unknown.
+pub fn warnings(graph: &Graph, rows: &[Row]) -> Vec<String> {
+    issues(graph, rows)
+        .into_iter()
+        .map(|(_, warning)| warning)
+        .collect()
+}
+
+/// The number of last rows and columns the convolution `node` ignores of its input of dimension
+/// `dim`, since its stride doesn't divide the padded input evenly, and the padded dimension.
+pub(crate) fn ignored(node: &Node, dim: u32) -> Option<(u32, u32)> {
+    let filter_size = match (node.op, node.filter_size) {
+        (Op::Conv, Some(filter_size)) => filter_size as u32,
+        _ => return None,
+    };
+    let padded = dim + 2 * node.padding as u32;
+    Some(((padded - filter_size) % node.stride as u32, padded))
+}
+
+/// ## The [`warnings`] about the layers summarized by `rows`, with the name of their node.
+///
+/// The warning about the layers whose channels are unknown concerns no single node.
+pub fn issues<'a>(graph: &Graph, rows: &[Row<'a>]) -> Vec<(Option<&'a str>, String)> {
+    let edges = graph.edges();
+    let dims: HashMap<&str, u32> = rows.iter().map(|row| (row.name, row.shape.dim)).collect();
+    let mut warnings = vec![];
+    let mut unknown = 0;
+    for row in rows {
+        let node = match row.node {
+            Some(node) => node,
+            None => continue,
+        };
+        if row.params.is_none() || row.flops.is_none() {
+            unknown += 1;
+        }
+        let filter_size = match (node.op, node.filter_size) {
+            (Op::Conv, Some(filter_size)) => filter_size as u32,
+            _ => continue,
+        };
+        let (ignored, padded) = ignored(node, dims[edges[node.name.as_str()][0]])
+            .expect("The convolution has a filter size.");
+        if ignored > 0 {
+            warnings.push((
+                Some(row.name),
+                format!(
+                    "Node '{}' ignores the last {} rows and columns of its (padded) input of dimension {}, \
+                     which its stride {} doesn't divide evenly.",
+                    row.name, ignored, padded, node.stride
+                ),
+            ));
+        }
+        if node.padding as u32 >= filter_size {
+            warnings.push((
+                Some(row.name),
+                format!(
+                    "Node '{}' pads by {}, which is at least its filter size {}, such that its outer outputs \
+                     only depend on the padding.",
+                    row.name, node.padding, filter_size
+                ),
+            ));
+        }
+    }
+    if unknown > 0 {
+        warnings.push((
+            None,
+            format!(
+                "The parameters or operations of {} layers are unknown, since their channels are not declared.",
+                unknown
+            ),
+        ));
+    }
+    warnings
+}
+
+/// `n` with its groups of thousands separated by `separator`, e.g. `11{,}689{,}512` for LaTeX.
+pub fn group(n: u64, separator: &str) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped += separator;
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// ## Write `rows` as a LaTeX `tabular`, which uses the rules of the `booktabs` package.
+///
+/// The last row is the total number of parameters, which is only given if it is known for all layers.
+pub fn latex(rows: &[Row]) -> String {
+    let mut table = String::from(
+        "% Requires \\usepackage{booktabs}.\n\
+         \\begin{tabular}{llrrrlr}\n\
+         \\toprule\n\
+         Layer & Type & Kernel & Stride & Padding & Output & Parameters \\\\\n\
+         \\midrule\n",
+    );
+    for row in rows {
+        let shape = match row.shape.channels {
+            Some(channels) => format!(
+                "${}\\times{}\\times{}$",
+                row.shape.dim, row.shape.dim, channels
+            ),
+            None => format!("${}\\times{}$", row.shape.dim, row.shape.dim),
+        };
+        let columns = match row.node {
+            None => vec![
+                "input".to_string(),
+                String::new(),
+                String::new(),
+                String::new(),
+            ],
+            Some(node) => {
+                let op = row.kind();
+                match node.filter_size {
+                    Some(f) => vec![
+                        op.to_string(),
+                        format!("${}\\times{}$", f, f),
+                        node.stride.to_string(),
+                        node.padding.to_string(),
+                    ],
+                    None => vec![op.to_string(), String::new(), String::new(), String::new()],
+                }
+            }
+        };
+        let params = match (row.node, row.params) {
+            (None, _) => String::new(),
+            (Some(_), Some(params)) => group(params, "{,}"),
+            (Some(_), None) => "--".to_string(),
+        };
+        table += &format!(
+            "{} & {} & {} & {} \\\\\n",
+            escape(row.name),
+            columns.join(" & "),
+            shape,
+            params
+        );
+    }
+    let total = rows
+        .iter()
+        .map(|row| row.params)
+        .sum::<Option<u64>>()
+        .map_or_else(|| "--".to_string(), |total| group(total, "{,}"));
+    table += &format!(
+        "\\midrule\nTotal & & & & & & {} \\\\\n\\bottomrule\n\\end{{tabular}}\n",
+        total
+    );
+    table
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// ## The summary of all the inputs and nodes of a network.
+///
+/// The summary is displayed as a table with aligned columns, which ends with the total number of
+/// parameters and operations. Unknown values are displayed as `-`.
+pub struct Summary<'a> {
+    pub rows: Vec<Row<'a>>,
+}
+
+impl<'a> From<Vec<Row<'a>>> for Summary<'a> {
+    fn from(rows: Vec<Row<'a>>) -> Summary<'a> {
+        Summary { rows }
+    }
+}
+
+impl std::fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let known = |n: Option<u64>| n.map_or_else(|| "-".to_string(), |n| group(n, ","));
+        let mut rows: Vec<Vec<String>> = vec![[
+            "layer",
+            "type",
+            "kernel",
+            "stride",
+            "padding",
+            "output",
+            "params",
+            "flops",
+            "receptive field",
+        ]
+        .iter()
+        .map(|header| header.to_string())
+        .collect()];
+        for row in &self.rows {
+            let (kernel, stride, padding) = match row.node.filter(|node| node.filter_size.is_some())
+            {
+                Some(node) => (
+                    node.filter_size.unwrap().to_string(),
+                    node.stride.to_string(),
+                    node.padding.to_string(),
+                ),
+                None => (String::new(), String::new(), String::new()),
+            };
+            let output = match row.shape.channels {
+                Some(channels) => format!("{}x{}x{}", row.shape.dim, row.shape.dim, channels),
+                None => format!("{}x{}", row.shape.dim, row.shape.dim),
+            };
+            rows.push(vec![
+                row.name.to_string(),
+                row.kind().to_string(),
+                kernel,
+                stride,
+                padding,
+                output,
+                known(row.params),
+                known(row.flops),
+                row.receptive_field.to_string(),
+            ]);
+        }
+        let total = |n: fn(&Row) -> Option<u64>| known(self.rows.iter().map(n).sum());
+        rows.push(vec![
+            "total".to_string(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            total(|row| row.params),
+            total(|row| row.flops),
+        ]);
+        write_columns(f, &rows, &[2, 3, 4, 6, 7, 8])
+    }
+}
+
+/// `field` quoted for CSV if it contains a separator, a quote or a line break.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// ## Write `rows` as CSV, e.g. for the analysis with pandas or a spreadsheet.
+///
+/// The spatial dimensions are written as `height` and `width`, and the dilation is always 1 since
+/// dilated filters are described by their effective filter size. Unknown values are left empty.
+pub fn csv(rows: &[Row]) -> String {
+    let mut csv = String::from(
+        "index,name,type,kernel,stride,padding,dilation,height,width,channels,params,flops,\
+         receptive_field,jump\n",
+    );
+    let optional = |n: Option<u64>| n.map_or_else(String::new, |n| n.to_string());
+    for (i, row) in rows.iter().enumerate() {
+        let (kernel, stride, padding, dilation) =
+            match row.node.filter(|node| node.filter_size.is_some()) {
+                Some(node) => (
+                    node.filter_size.map(u64::from),
+                    Some(node.stride as u64),
+                    Some(node.padding as u64),
+                    Some(1),
+                ),
+                None => (None, None, None, None),
+            };
+        csv += &format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            i,
+            quote(row.name),
+            row.kind(),
+            optional(kernel),
+            optional(stride),
+            optional(padding),
+            optional(dilation),
+            row.shape.dim,
+            row.shape.dim,
+            optional(row.shape.channels.map(u64::from)),
+            optional(row.params),
+            optional(row.flops),
+            row.receptive_field,
+            row.jump
+        );
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAPH: &str = r#"
+        input_channels = 3
+
+        [[nodes]]
+        name = "conv_1"
+        op = "conv"
+        filter_size = 7
+        stride = 2
+        padding = 3
+        channels = 64
+
+        [[nodes]]
+        name = "pool"
+        op = "conv"
+        filter_size = 3
+        stride = 2
+        padding = 1
+
+        [[nodes]]
+        name = "sum"
+        op = "add"
+        inputs = ["pool", "pool"]
+        "#;
+
+    #[test]
+    fn test_rows() {
+        let graph: Graph = toml::from_str(GRAPH).unwrap();
+        let rows = rows(&graph, 224).unwrap();
+        let summary: Vec<(&str, u32, Option<u64>)> = rows
+            .iter()
+            .map(|row| (row.name, row.shape.dim, row.params))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("input", 224, Some(0)),
+                ("conv_1", 112, Some(9472)),
+                ("pool", 56, Some(0)),
+                ("sum", 56, Some(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_counts_exceeding_64_bits_are_errors() {
+        let graph: Graph = toml::from_str(GRAPH).unwrap();
+        assert_eq!(
+            rows(&graph, 4_000_000_000).unwrap_err().to_string(),
+            "Unable to summarize the network: the output values of 'input' exceed the range of \
+             64-bit integers."
+        );
+        let graph: Graph = toml::from_str(
+            "input_channels = 1\n[[nodes]]\nname = \"conv\"\nop = \"conv\"\nfilter_size = 1\nchannels = 1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            rows(&graph, 4_000_000_000).unwrap_err(),
+            too_many("conv", "operations")
+        );
+        let rows = rows(&graph, 2_000_000_000).unwrap();
+        assert_eq!(rows[1].flops, Some(8_000_000_000_000_000_000));
+    }
+
+    #[test]
+    fn test_latex() {
+        let graph: Graph = toml::from_str(GRAPH).unwrap();
+        assert_eq!(
+            latex(&rows(&graph, 224).unwrap()),
+            r"% Requires \usepackage{booktabs}.
+\begin{tabular}{llrrrlr}
+\toprule
+Layer & Type & Kernel & Stride & Padding & Output & Parameters \\
+\midrule
+input & input &  &  &  & $224\times224\times3$ &  \\
+conv\_1 & conv & $7\times7$ & 2 & 3 & $112\times112\times64$ & 9{,}472 \\
+pool & conv & $3\times3$ & 2 & 1 & $56\times56\times64$ & 0 \\
+sum & add &  &  &  & $56\times56\times64$ & 0 \\
+\midrule
+Total & & & & & & 9{,}472 \\
+\bottomrule
+\end{tabular}
+"
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        let graph: Graph =
+            toml::from_str(GRAPH.replace("\"pool\"", "\"pool, 3x3\"").as_str()).unwrap();
+        assert_eq!(
+            csv(&rows(&graph, 224).unwrap()),
+            "index,name,type,kernel,stride,padding,dilation,height,width,channels,params,flops,\
+             receptive_field,jump
+0,input,input,,,,,224,224,3,0,0,1,1
+1,conv_1,conv,7,2,3,1,112,112,64,9472,236027904,7,2
+2,\"pool, 3x3\",conv,3,2,1,1,56,56,64,0,1806336,11,4
+3,sum,add,,,,,56,56,64,0,200704,11,4
+"
+        );
+    }
+
+    #[test]
+    fn test_summary_display() {
+        let graph: Graph = toml::from_str(GRAPH).unwrap();
+        assert_eq!(
+            Summary::from(rows(&graph, 224).unwrap()).to_string(),
+            "layer   type   kernel  stride  padding  output      params        flops  receptive field
+input   input                           224x224x3        0            0                1
+conv_1  conv        7       2        3  112x112x64   9,472  236,027,904                7
+pool    conv        3       2        1  56x56x64         0    1,806,336               11
+sum     add                             56x56x64         0      200,704               11
+total                                                9,472  238,034,944
+"
+        );
+    }
+
+    #[test]
+    fn test_unknown_channels() {
+        let graph: Graph =
+            toml::from_str(GRAPH.replace("input_channels = 3", "").as_str()).unwrap();
+        let table = latex(&rows(&graph, 224).unwrap());
+        assert!(
+            table.contains(r"conv\_1 & conv & $7\times7$ & 2 & 3 & $112\times112\times64$ & -- \\")
+        );
+        assert!(table.contains(r"Total & & & & & & -- \\"));
+    }
+}
@@ -0,0 +1,670 @@
+//! The output dimensions of networks as formulas of their input dimension, e.g. for the shape
+//! sections of papers and docstrings.
+//!
+//! The formulas of the layers are composed symbolically: a convolution maps its input `x` to
+//! `floor((x + 2*p - f) / s) + 1` and a transposed convolution to `(x - 1)*s + f - 2*p`. The
+//! constants are collected, whole multiples are moved out of the floors, floors of exact divisions
+//! are dropped and nested floors are merged into one, e.g. a convolution with a stride of one only shifts its
+//! input. The input of the network is the variable `n`, and the formulas hold for the inputs for
+//! which the network is valid, which is not checked.
+//!
+//! The [`ClosedForm`] of an output simplifies its formula further, e.g. floors that round up are
+//! written as ceilings, and adds the inputs for which the network is valid, and the linear formula
+//! the output follows for the multiples of the product of the strides.
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::error::ConvDimError;
+use crate::formula::{gcd, Formula};
+use crate::graph::{Graph, Node, Op, INPUT};
+
+/// The name of the variable of the input dimension.
+pub const VARIABLE: &str = "n";
+
+const OVERFLOW: &str = "the formula of the output exceeds the range of its arithmetic";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The non-constant part of a formula.
+enum Base {
+    /// The dimension of an input, by its name.
+    Variable(String),
+    /// The floor of the division of a formula by a divisor larger than one.
+    Floor(Box<Expr>, i128),
+    /// The ceiling of the division of a formula by a divisor larger than one, in simplified formulas.
+    Ceil(Box<Expr>, i128),
+    /// The text of a formula of a layer, with its input substituted.
+    Formula(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## A formula for a dimension, `scale * base + offset` with an optional base.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::symbolic::Expr;
+/// let expr = Expr::variable("n").conv(3, 2, 1).unwrap().conv(3, 2, 1).unwrap();
+/// assert_eq!(expr.to_string(), "floor((n + 3) / 4)");
+/// assert_eq!(expr.eval(224), Some(56));
+/// ```
+pub struct Expr {
+    term: Option<(i128, Base)>,
+    offset: i128,
+}
+
+impl Expr {
+    /// ## The dimension of the input `name`.
+    pub fn variable(name: &str) -> Expr {
+        Expr {
+            term: Some((1, Base::Variable(name.to_string()))),
+            offset: 0,
+        }
+    }
+
+    /// ## A constant dimension.
+    pub fn constant(value: i128) -> Expr {
+        Expr {
+            term: None,
+            offset: value,
+        }
+    }
+
+    fn shift(self, shift: i128) -> Result<Expr, String> {
+        Ok(Expr {
+            offset: self.offset.checked_add(shift).ok_or(OVERFLOW)?,
+            ..self
+        })
+    }
+
+    fn times(self, factor: i128) -> Result<Expr, String> {
+        let term = match self.term {
+            Some((scale, base)) => Some((scale.checked_mul(factor).ok_or(OVERFLOW)?, base)),
+            None => None,
+        };
+        Ok(Expr {
+            term,
+            offset: self.offset.checked_mul(factor).ok_or(OVERFLOW)?,
+        })
+    }
+
+    /// The floor of the division by `divisor`.
+    fn floor_div(self, divisor: i128) -> Result<Expr, String> {
+        // The formulas are integers, so the multiples of the divisor in the offset can be moved out.
+        let (quotient, remainder) = (
+            self.offset.div_euclid(divisor),
+            self.offset.rem_euclid(divisor),
+        );
+        let term = match self.term {
+            None => None,
+            Some((scale, base)) if scale % divisor == 0 && remainder == 0 => {
+                Some((scale / divisor, base))
+            }
+            // floor((floor(x / a) + r) / b) = floor((x + a*r) / (a*b))
+            Some((1, Base::Floor(inner, inner_divisor))) => {
+                let shift = inner_divisor.checked_mul(remainder).ok_or(OVERFLOW)?;
+                let divisor = inner_divisor.checked_mul(divisor).ok_or(OVERFLOW)?;
+                Some((1, Base::Floor(Box::new(inner.shift(shift)?), divisor)))
+            }
+            Some(term) => {
+                let inner = Expr {
+                    term: Some(term),
+                    offset: remainder,
+                };
+                Some((1, Base::Floor(Box::new(inner), divisor)))
+            }
+        };
+        Ok(Expr {
+            term,
+            offset: quotient,
+        })
+    }
+
+    /// ## The output of a convolution with the given filter size, stride and padding.
+    pub fn conv(self, filter_size: u32, stride: u32, padding: u32) -> Result<Expr, String> {
+        let shift = 2 * i128::from(padding) - i128::from(filter_size);
+        self.shift(shift)?.floor_div(stride.into())?.shift(1)
+    }
+
+    /// ## The output of a transposed convolution with the given filter size, stride and padding.
+    pub fn transposed(self, filter_size: u32, stride: u32, padding: u32) -> Result<Expr, String> {
+        let shift = i128::from(filter_size) - 2 * i128::from(padding);
+        self.shift(-1)?.times(stride.into())?.shift(shift)
+    }
+
+    /// The output of a layer with the `formula` and the given geometry.
+    fn formula(self, formula: &Formula, geometry: (u16, u16, u16)) -> Expr {
+        let input = match &self.term {
+            Some((1, Base::Variable(name))) if self.offset == 0 => name.clone(),
+            None => self.offset.to_string(),
+            _ => format!("({})", self),
+        };
+        Expr {
+            term: Some((1, Base::Formula(formula.substitute(&input, geometry)))),
+            offset: 0,
+        }
+    }
+
+    /// ## The value of the formula for the dimension `n` of all its inputs, if it is defined.
+    pub fn eval(&self, n: i128) -> Option<i128> {
+        let base = match &self.term {
+            None => return Some(self.offset),
+            Some((scale, base)) => scale.checked_mul(match base {
+                Base::Variable(_) => n,
+                Base::Floor(inner, divisor) => inner.eval(n)?.div_euclid(*divisor),
+                Base::Ceil(inner, divisor) => -(-inner.eval(n)?).div_euclid(*divisor),
+                Base::Formula(text) => Formula::parse(text).ok()?.value(n)?,
+            })?,
+        };
+        base.checked_add(self.offset)
+    }
+
+    /// ## The formula with its floors written as simply as possible.
+    ///
+    /// Floors that round up are written as ceilings, e.g. `floor((n + 3) / 4)` as `ceil(n / 4)`, and
+    /// constants are moved into the floors if they become smaller than the divisor, e.g.
+    /// `floor((n + 7) / 12) - 1` is written as `floor((n - 5) / 12)`. The formulas are equal.
+    pub fn simplify(&self) -> Expr {
+        let (scale, base) = match &self.term {
+            Some((scale, base)) => (*scale, base),
+            None => return self.clone(),
+        };
+        let (inner, divisor) = match base {
+            Base::Floor(inner, divisor) => (inner.simplify(), *divisor),
+            Base::Ceil(inner, divisor) => {
+                let term = Some((scale, Base::Ceil(Box::new(inner.simplify()), *divisor)));
+                return Expr { term, ..*self };
+            }
+            _ => return self.clone(),
+        };
+        let rounds_up = |inner: &Expr| inner.offset == divisor - 1;
+        let mut expr = Expr {
+            term: Some((scale, Base::Floor(Box::new(inner.clone()), divisor))),
+            offset: self.offset,
+        };
+        let folded = self
+            .offset
+            .checked_mul(divisor)
+            .and_then(|shift| inner.offset.checked_add(shift))
+            .filter(|folded| folded.abs() < divisor);
+        if let (1, Some(folded), false) = (scale, folded, rounds_up(&inner)) {
+            expr = Expr {
+                term: Some((
+                    1,
+                    Base::Floor(
+                        Box::new(Expr {
+                            offset: folded,
+                            ..inner
+                        }),
+                        divisor,
+                    ),
+                )),
+                offset: 0,
+            };
+        }
+        if let Some((scale, Base::Floor(inner, divisor))) = &expr.term {
+            if rounds_up(inner) {
+                let inner = Expr {
+                    offset: 0,
+                    ..(**inner).clone()
+                };
+                expr.term = Some((*scale, Base::Ceil(Box::new(inner), *divisor)));
+            }
+        }
+        expr
+    }
+
+    /// The period of the formula and its increase over a period, if the formula is periodic.
+    ///
+    /// An integer formula increasing by `c` over the period `p` increases by `c / g` over the period
+    /// `p * d / g` after a division by `d`, where `g` is the greatest common divisor of `c` and `d`.
+    fn progression(&self) -> Option<(i128, i128)> {
+        let (scale, base) = match &self.term {
+            Some(term) => term,
+            None => return Some((1, 0)),
+        };
+        let (period, increase) = match base {
+            Base::Variable(_) => (1, 1),
+            Base::Floor(inner, divisor) | Base::Ceil(inner, divisor) => {
+                let (period, increase) = inner.progression()?;
+                let g = gcd(increase, *divisor);
+                (period.checked_mul(divisor / g)?, increase / g)
+            }
+            Base::Formula(_) => return None,
+        };
+        Some((period, increase.checked_mul(*scale)?))
+    }
+
+    /// ## The linear formula which the formula is equal to for the multiples of its period.
+    ///
+    /// Returns `None` for formulas of the formulas of layers, which have no known period.
+    pub fn linear(&self) -> Option<Linear> {
+        let (period, increase) = self.progression()?;
+        let g = gcd(increase, period);
+        Some(Linear {
+            scale: increase / g,
+            divisor: period / g,
+            offset: self.eval(0)?,
+            modulus: period,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ## A linear formula `scale * n / divisor + offset`, exact for the multiples of `modulus`.
+pub struct Linear {
+    pub scale: i128,
+    pub divisor: i128,
+    pub offset: i128,
+    pub modulus: i128,
+}
+
+impl fmt::Display for Linear {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let expr = Expr {
+            term: Some((self.scale, Base::Variable(VARIABLE.to_string()))),
+            offset: 0,
+        };
+        match (self.scale, self.divisor) {
+            (0, _) => return write!(f, "{}", self.offset),
+            (_, 1) => write!(f, "{}", expr)?,
+            (_, divisor) => write!(f, "{} / {}", expr, divisor)?,
+        }
+        match self.offset {
+            0 => Ok(()),
+            offset if offset > 0 => write!(f, " + {}", offset),
+            offset => write!(f, " - {}", -offset),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// ## The simplified formula of an output, with the inputs for which it holds.
+pub struct ClosedForm {
+    /// The simplified formula, see [`Expr::simplify`].
+    pub expr: Expr,
+    /// The linear formula of the output, exact for the multiples of its modulus.
+    pub linear: Option<Linear>,
+    /// The smallest input for which the network is valid, if any is up to `u32::MAX`.
+    pub min_input: Option<i128>,
+}
+
+impl fmt::Display for ClosedForm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.expr)?;
+        match self.min_input {
+            Some(min_input) => write!(f, " for n >= {}", min_input)?,
+            None => return write!(f, ", but no input is valid"),
+        }
+        match self.linear {
+            Some(linear) if linear.modulus > 1 => {
+                write!(
+                    f,
+                    ", i.e. {} for the multiples of {}",
+                    linear, linear.modulus
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// ## The closed form of `output`, whose network is valid if all the `exprs` are positive.
+///
+/// The smallest valid input is searched assuming that the formulas never decrease, which holds for
+/// all layers except for those with a formula.
+pub fn closed_form(output: &Expr, exprs: &[&Expr]) -> ClosedForm {
+    let valid = |n: i128| {
+        exprs
+            .iter()
+            .all(|expr| expr.eval(n).is_some_and(|dim| dim >= 1))
+    };
+    let (mut low, mut high) = (1, i128::from(u32::MAX));
+    let min_input = match valid(high) {
+        true => {
+            while low < high {
+                let middle = low + (high - low) / 2;
+                match valid(middle) {
+                    true => high = middle,
+                    false => low = middle + 1,
+                }
+            }
+            Some(low)
+        }
+        false => None,
+    };
+    ClosedForm {
+        expr: output.simplify(),
+        linear: output.linear(),
+        min_input,
+    }
+}
+
+impl fmt::Display for Base {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Base::Variable(name) => write!(f, "{}", name),
+            Base::Floor(inner, divisor) if inner.offset == 0 => {
+                write!(f, "floor({} / {})", inner, divisor)
+            }
+            Base::Floor(inner, divisor) => write!(f, "floor(({}) / {})", inner, divisor),
+            Base::Ceil(inner, divisor) if inner.offset == 0 => {
+                write!(f, "ceil({} / {})", inner, divisor)
+            }
+            Base::Ceil(inner, divisor) => write!(f, "ceil(({}) / {})", inner, divisor),
+            Base::Formula(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (scale, base) = match &self.term {
+            Some(term) => term,
+            None => return write!(f, "{}", self.offset),
+        };
+        if *scale != 1 {
+            write!(f, "{} * ", scale)?;
+        }
+        match base {
+            Base::Formula(_) if *scale != 1 || self.offset != 0 => write!(f, "({})", base)?,
+            _ => write!(f, "{}", base)?,
+        }
+        match self.offset {
+            0 => Ok(()),
+            offset if offset > 0 => write!(f, " + {}", offset),
+            offset => write!(f, " - {}", -offset),
+        }
+    }
+}
+
+/// ## The output dimension of `node` as a formula of the dimension `input` of its (first) input.
+pub fn node_expr(node: &Node, input: Expr) -> Result<Expr, ConvDimError> {
+    let overflow = |message: String| ConvDimError::Formula {
+        layer: None,
+        message,
+    };
+    match node.op {
+        Op::Conv | Op::Transposed => {
+            let filter_size = match (node.filter_size, &node.formula) {
+                (Some(filter_size), _) => filter_size,
+                (None, Some(_)) => 1,
+                (None, None) => {
+                    return Err(ConvDimError::MissingFilterSize {
+                        node: node.name.clone(),
+                    })
+                }
+            };
+            if let Some(formula) = &node.formula {
+                return Ok(input.formula(formula, (filter_size, node.stride, node.padding)));
+            }
+            if node.stride == 0 {
+                return Err(ConvDimError::ZeroStride { layer: None });
+            }
+            if filter_size == 0 {
+                return Err(ConvDimError::ZeroFilterSize { layer: None });
+            }
+            let (filter_size, stride, padding) =
+                (filter_size.into(), node.stride.into(), node.padding.into());
+            if node.op == Op::Transposed {
+                input.transposed(filter_size, stride, padding)
+            } else {
+                input.conv(filter_size, stride, padding)
+            }
+            .map_err(overflow)
+        }
+        // Merges keep the dimension of their inputs, which is checked when they are evaluated.
+        Op::Add | Op::Concat | Op::BatchNorm | Op::Relu | Op::Dropout | Op::Identity => Ok(input),
+        Op::Subnetwork => unreachable!("Subnetworks are expanded when the graph is read."),
+    }
+}
+
+/// ## The output dimension of every node of `graph` as a formula of the dimensions of its inputs.
+///
+/// The inputs with a dimension of their own are constants, the others are the variable `n`, or are
+/// named after the input if there are several of them. The formulas are returned in topological order.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{symbolic, Architecture, Network};
+/// let network = Network::builder().conv(3, 1, 0).maxpool(2, 2).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let exprs = symbolic::graph_exprs(&graph).unwrap();
+/// assert_eq!(exprs[1].1.to_string(), "floor(n / 2) - 1");
+/// ```
+pub fn graph_exprs(graph: &Graph) -> Result<Vec<(String, Expr)>, ConvDimError> {
+    let variables = graph
+        .inputs
+        .iter()
+        .filter(|input| input.input_dim.is_none())
+        .count();
+    let mut computed: HashMap<&str, Expr> = match graph.inputs.is_empty() {
+        true => vec![(INPUT, Expr::variable(VARIABLE))]
+            .into_iter()
+            .collect(),
+        false => graph
+            .inputs
+            .iter()
+            .map(|input| {
+                let expr = match input.input_dim {
+                    Some(dim) => Expr::constant(dim.into()),
+                    None if variables == 1 => Expr::variable(VARIABLE),
+                    None => Expr::variable(&input.name),
+                };
+                (input.name.as_str(), expr)
+            })
+            .collect(),
+    };
+    let edges = graph.edges();
+    let order = graph.order();
+    if order.len() < graph.nodes.len() {
+        let nodes = graph
+            .nodes
+            .iter()
+            .filter(|node| !order.iter().any(|ordered| ordered.name == node.name))
+            .map(|node| node.name.clone())
+            .collect();
+        return Err(ConvDimError::Unevaluable { nodes });
+    }
+    let mut exprs = vec![];
+    for node in order {
+        let input = computed[edges[node.name.as_str()][0]].clone();
+        let expr = node_expr(node, input).map_err(|e| e.in_layer(&node.name))?;
+        computed.insert(&node.name, expr.clone());
+        exprs.push((node.name.clone(), expr));
+    }
+    Ok(exprs)
+}
+
+/// ## The closed forms of the outputs of `graph`, which are valid if all its nodes are.
+///
+/// The outputs are the nodes listed in `outputs`, or the last node if none are listed.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{symbolic, Architecture, Network};
+/// let network = Network::builder().conv(3, 2, 1).conv(3, 2, 1).conv(3, 2, 1).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let (_, output) = &symbolic::closed_forms(&graph).unwrap()[0];
+/// assert_eq!(output.to_string(), "ceil(n / 8) for n >= 1, i.e. n / 8 for the multiples of 8");
+/// ```
+pub fn closed_forms(graph: &Graph) -> Result<Vec<(String, ClosedForm)>, ConvDimError> {
+    let exprs = graph_exprs(graph)?;
+    let all: Vec<&Expr> = exprs.iter().map(|(_, expr)| expr).collect();
+    let outputs: Vec<&str> = match graph.outputs.is_empty() {
+        true => exprs
+            .last()
+            .map(|(name, _)| name.as_str())
+            .into_iter()
+            .collect(),
+        false => graph.outputs.iter().map(String::as_str).collect(),
+    };
+    outputs
+        .into_iter()
+        .map(
+            |output| match exprs.iter().find(|(name, _)| name == output) {
+                Some((name, expr)) => Ok((name.clone(), closed_form(expr, &all))),
+                None => Err(ConvDimError::UnknownOutput {
+                    name: output.to_string(),
+                }),
+            },
+        )
+        .collect()
+}
+
+/// ## The output of a single (`transposed`) convolution applied `repeat` times to `input`.
+///
+/// Convolutions with a stride of one shift their input, so they are repeated in constant time.
+pub fn repeated(
+    input: Expr,
+    (filter_size, stride, padding): (u32, u32, u32),
+    transposed: bool,
+    repeat: u32,
+) -> Result<Expr, ConvDimError> {
+    if stride == 0 {
+        return Err(ConvDimError::ZeroStride { layer: None });
+    }
+    if filter_size == 0 {
+        return Err(ConvDimError::ZeroFilterSize { layer: None });
+    }
+    let apply = |expr: Expr| match transposed {
+        true => expr.transposed(filter_size, stride, padding),
+        false => expr.conv(filter_size, stride, padding),
+    };
+    let overflow = |message: String| ConvDimError::Formula {
+        layer: None,
+        message,
+    };
+    if stride == 1 {
+        let shift = apply(Expr::constant(0)).map_err(overflow)?.offset;
+        let shift = shift
+            .checked_mul(repeat.into())
+            .ok_or_else(|| OVERFLOW.to_string());
+        return shift.and_then(|shift| input.shift(shift)).map_err(overflow);
+    }
+    let mut expr = input;
+    for _ in 0..repeat {
+        expr = apply(expr).map_err(overflow)?;
+    }
+    Ok(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_exprs_agree_with_the_dimensions() {
+        let graph = parse_architecture(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 7
+            stride = 2
+            padding = 3
+
+            [[layers]]
+            type = "max_pool"
+            filter_size = 3
+            stride = 2
+            padding = 1
+
+            [[layers]]
+            type = "conv"
+            filter_size = 5
+            stride = 3
+
+            [[layers]]
+            type = "conv_transpose"
+            filter_size = 4
+            stride = 2
+            padding = 1
+
+            [[layers]]
+            type = "formula"
+            formula = "ceil(n / s)"
+            stride = 2
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let exprs = graph_exprs(&graph).unwrap();
+        assert_eq!(exprs[0].1.to_string(), "floor((n + 1) / 2)");
+        assert_eq!(exprs[1].1.to_string(), "floor((n + 3) / 4)");
+        assert_eq!(exprs[2].1.to_string(), "floor((n + 7) / 12) - 1");
+        assert_eq!(exprs[3].1.to_string(), "2 * floor((n + 7) / 12) - 2");
+        assert_eq!(
+            exprs[4].1.to_string(),
+            "ceil((2 * floor((n + 7) / 12) - 2) / 2)"
+        );
+        for n in 17..300 {
+            let shapes = graph.shapes(n as u32).unwrap();
+            for ((name, expr), (_, shape)) in exprs.iter().zip(shapes) {
+                assert_eq!(expr.eval(n), Some(shape.dim.into()), "{} for {}", name, n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_closed_forms() {
+        let n = || Expr::variable(VARIABLE);
+        let expr = n()
+            .conv(7, 2, 3)
+            .unwrap()
+            .conv(3, 2, 1)
+            .unwrap()
+            .conv(5, 3, 0)
+            .unwrap();
+        assert_eq!(expr.to_string(), "floor((n + 7) / 12) - 1");
+        let form = closed_form(&expr, &[&expr]);
+        assert_eq!(
+            form.to_string(),
+            "floor((n - 5) / 12) for n >= 17, i.e. n / 12 - 1 for the multiples of 12"
+        );
+        let expr = repeated(n(), (3, 1, 1), false, 4)
+            .unwrap()
+            .transposed(4, 2, 1)
+            .unwrap();
+        assert_eq!(closed_form(&expr, &[&expr]).to_string(), "2 * n for n >= 1");
+        let expr = n()
+            .conv(2, 2, 0)
+            .unwrap()
+            .times(3)
+            .unwrap()
+            .conv(3, 2, 1)
+            .unwrap();
+        assert_eq!(expr.simplify().to_string(), "ceil(3 * floor(n / 2) / 2)");
+        for expr in [
+            expr,
+            n().conv(3, 3, 0).unwrap().transposed(3, 2, 0).unwrap(),
+        ] {
+            let (simplified, linear) = (expr.simplify(), expr.linear().unwrap());
+            for n in -50..300 {
+                assert_eq!(simplified.eval(n), expr.eval(n), "{} for {}", expr, n);
+                if n % linear.modulus == 0 {
+                    let value = linear.scale * n / linear.divisor + linear.offset;
+                    assert_eq!(expr.eval(n), Some(value), "{} for {}", expr, n);
+                }
+            }
+        }
+        let formula = Formula::parse("ceil(n / s)").unwrap();
+        assert_eq!(n().formula(&formula, (1, 2, 0)).linear(), None);
+        let expr = n().shift(-300).unwrap();
+        assert_eq!(closed_form(&expr, &[&expr]).min_input, Some(301));
+    }
+
+    #[test]
+    fn test_repeated() {
+        let n = || Expr::variable(VARIABLE);
+        let expr = repeated(n(), (3, 1, 0), false, 1_000_000).unwrap();
+        assert_eq!(expr.to_string(), "n - 2000000");
+        let expr = repeated(n(), (2, 2, 0), true, 3).unwrap();
+        assert_eq!(expr.to_string(), "8 * n");
+        let expr = repeated(n(), (2, 2, 0), false, 3).unwrap();
+        assert_eq!(expr.to_string(), "floor(n / 8)");
+        assert!(repeated(n(), (2, 2, 0), true, 200).is_err());
+        assert!(repeated(n(), (2, 0, 0), false, 1).is_err());
+    }
+}
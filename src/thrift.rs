@@ -0,0 +1,195 @@
+//! A minimal reader for the Thrift compact protocol, e.g. for the footer of Parquet files.
+//!
+//! Like the [protocol buffers](crate::protobuf) reader, only the protocol itself is handled: a
+//! struct is a sequence of fields, each consisting of a field id and a value of one of the types.
+//! The interpretation of the fields is left to the caller, which knows the schema of the struct.
+
+#[derive(Debug, Clone, PartialEq)]
+/// ## The value of a field, by type. Integers of all sizes are read as `i64`.
+pub enum Value<'a> {
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    Binary(&'a [u8]),
+    List(Vec<Value<'a>>),
+    Map(Vec<(Value<'a>, Value<'a>)>),
+    Struct(Struct<'a>),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// ## A decoded struct, consisting of its fields in the order in which they were read.
+pub struct Struct<'a> {
+    pub fields: Vec<(i16, Value<'a>)>,
+}
+
+/// A reader of values from `bytes`, starting at `pos`.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or("Unexpected end of struct while reading a field.")?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or("Unexpected end of struct while reading a field.")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("Varint is longer than 64 bits.".to_string())
+    }
+
+    /// A zigzag encoded varint.
+    fn int(&mut self) -> Result<i64, String> {
+        let value = self.varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    /// A value of the compact `kind`, where booleans in lists and maps take a byte of their own.
+    fn value(&mut self, kind: u8) -> Result<Value<'a>, String> {
+        Ok(match kind {
+            1 | 2 => Value::Bool(self.byte()? == 1),
+            3 => Value::Int(self.byte()? as i8 as i64),
+            4..=6 => Value::Int(self.int()?),
+            7 => {
+                let mut buffer = [0u8; 8];
+                buffer.copy_from_slice(self.bytes(8)?);
+                Value::Double(f64::from_le_bytes(buffer))
+            }
+            8 => {
+                let length = self.varint()? as usize;
+                Value::Binary(self.bytes(length)?)
+            }
+            9 | 10 => {
+                let header = self.byte()?;
+                let size = match header >> 4 {
+                    15 => self.varint()? as usize,
+                    size => size as usize,
+                };
+                let mut values = vec![];
+                for _ in 0..size {
+                    values.push(self.value(header & 0x0f)?);
+                }
+                Value::List(values)
+            }
+            11 => {
+                let size = self.varint()? as usize;
+                let kinds = if size > 0 { self.byte()? } else { 0 };
+                let mut entries = vec![];
+                for _ in 0..size {
+                    entries.push((self.value(kinds >> 4)?, self.value(kinds & 0x0f)?));
+                }
+                Value::Map(entries)
+            }
+            12 => Value::Struct(self.fields()?),
+            kind => return Err(format!("Unsupported type {}.", kind)),
+        })
+    }
+
+    /// The fields of a struct up to its stop field.
+    fn fields(&mut self) -> Result<Struct<'a>, String> {
+        let mut fields = vec![];
+        let mut id = 0i16;
+        loop {
+            let header = self.byte()?;
+            if header == 0 {
+                return Ok(Struct { fields });
+            }
+            // The id is given as a delta to the previous id, or in full if the delta is 0.
+            id = match header >> 4 {
+                0 => self.int()? as i16,
+                delta => id + delta as i16,
+            };
+            let value = match header & 0x0f {
+                // The value of a boolean field is its type.
+                kind @ (1 | 2) => Value::Bool(kind == 1),
+                kind => self.value(kind)?,
+            };
+            fields.push((id, value));
+        }
+    }
+}
+
+impl<'a> Struct<'a> {
+    /// ## Decode a struct from the compact protocol.
+    pub fn decode(bytes: &'a [u8]) -> Result<Struct<'a>, String> {
+        Reader { bytes, pos: 0 }.fields()
+    }
+
+    /// The value of the field with id `id`.
+    pub fn get(&self, id: i16) -> Option<&Value<'a>> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == id)
+            .map(|(_, value)| value)
+    }
+
+    /// The integer value of the field with id `id`.
+    pub fn int(&self, id: i16) -> Option<i64> {
+        match self.get(id) {
+            Some(Value::Int(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The string value of the field with id `id`.
+    pub fn string(&self, id: i16) -> Option<String> {
+        match self.get(id) {
+            Some(Value::Binary(bytes)) => Some(String::from_utf8_lossy(bytes).into_owned()),
+            _ => None,
+        }
+    }
+
+    /// The structs in the list of the field with id `id`.
+    pub fn structs(&self, id: i16) -> Vec<&Struct<'a>> {
+        match self.get(id) {
+            Some(Value::List(values)) => values
+                .iter()
+                .filter_map(|value| match value {
+                    Value::Struct(element) => Some(element),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        // { 1: i32 1, 2: list<struct { 4: "x" }>, 3: i64 -300, 5: true }
+        let bytes = [
+            0x15, 0x02, 0x19, 0x1c, 0x48, 0x01, b'x', 0x00, 0x16, 0xd7, 0x04, 0x21, 0x00,
+        ];
+        let decoded = Struct::decode(&bytes).unwrap();
+        assert_eq!(decoded.int(1), Some(1));
+        assert_eq!(decoded.structs(2)[0].string(4), Some("x".to_string()));
+        assert_eq!(decoded.int(3), Some(-300));
+        assert_eq!(decoded.get(5), Some(&Value::Bool(true)));
+        assert!(Struct::decode(&bytes[..5]).is_err());
+    }
+}
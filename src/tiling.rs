@@ -0,0 +1,407 @@
+//! Overlap-tile plans for the inference of fully convolutional networks on images too large to be
+//! processed at once, like the overlap-tile strategy of U-Net.
+//!
+//! The image is cut into overlapping input tiles. Every tile adds a margin of context around the
+//! region whose output is kept, so that the kept outputs see their whole receptive field and not
+//! the border of the tile. The kept regions abut, so the tiles move by the size of a kept region.
+//!
+//! Under a memory budget, the largest output tile is chosen whose input tile fits the budget with
+//! the activation-memory model of the [`report`](crate::report): every input and node keeps its
+//! output, whose values take a given number of bytes each. Without the reuse of the memory by the
+//! framework, this is an upper bound of the memory needed by the inference.
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::graph::Graph;
+use crate::report::abbreviate;
+use crate::summary;
+use crate::trace::write_columns;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+/// ## The input tiles producing output tiles of a given size, and their overlap.
+///
+/// The sizes are given in pixels of the input, except for the `output_tile`, the `output` of the
+/// network for an input tile and the `crop` on either side of it, which are pixels of the output.
+pub struct TilePlan {
+    pub receptive_field: u64,
+    /// The number of input pixels per output pixel.
+    pub downsampling: f64,
+    pub output_tile: u32,
+    pub input_tile: u32,
+    /// The context on either side of the region of the input tile whose output is kept.
+    pub margin: u32,
+    /// The distance between neighboring input tiles, i.e. the size of the region whose output is kept.
+    pub stride: u32,
+    pub output: u32,
+    pub crop: u32,
+    /// The bytes of the activations of an input tile, if planned under a memory budget.
+    pub memory: Option<u64>,
+    /// The number of tiles covering an image, if its size is given.
+    pub tiles: Option<u64>,
+}
+
+impl TilePlan {
+    /// ## The plan with the number of tiles covering a square image of `image` pixels per side.
+    ///
+    /// The tiles move by the `stride`, and the last ones extend beyond the image, which is padded
+    /// by mirroring it like the margins at its border.
+    pub fn cover(mut self, image: u32) -> Self {
+        let per_axis = u64::from(image.div_ceil(self.stride).max(1));
+        self.tiles = Some(per_axis * per_axis);
+        self
+    }
+}
+
+impl fmt::Display for TilePlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = [
+            ("receptive field:", self.receptive_field.to_string()),
+            ("downsampling:", self.downsampling.to_string()),
+            ("output tile:", self.output_tile.to_string()),
+            ("input tile:", self.input_tile.to_string()),
+            ("margin:", self.margin.to_string()),
+            ("tile stride:", self.stride.to_string()),
+            ("output crop:", self.crop.to_string()),
+        ];
+        let mut rows: Vec<Vec<String>> = rows
+            .iter()
+            .map(|(label, value)| vec![label.to_string(), value.clone()])
+            .collect();
+        if let Some(memory) = self.memory {
+            rows.push(vec!["memory:".to_string(), abbreviate(memory as f64, "B")]);
+        }
+        if let Some(tiles) = self.tiles {
+            rows.push(vec!["tiles:".to_string(), tiles.to_string()]);
+        }
+        write_columns(f, &rows, &[1])
+    }
+}
+
+fn invalid(message: String) -> ConvDimError {
+    ConvDimError::Tiling { message }
+}
+
+/// The receptive field and the downsampling of the (last) output of `graph`, which don't depend
+/// on the dimension of the input, found by evaluating the graph for a dimension it accepts.
+fn geometry(graph: &Graph, probe: u32) -> Result<(u64, f64), ConvDimError> {
+    let mut error = None;
+    let mut in_dim = probe.max(1);
+    loop {
+        match summary::rows(graph, in_dim) {
+            Ok(rows) => {
+                let last = rows.last().expect("The summary has a row for every input.");
+                return Ok((last.receptive_field, last.jump));
+            }
+            Err(e) => error = Some(error.unwrap_or(e)),
+        }
+        in_dim = match in_dim.checked_mul(2) {
+            Some(in_dim) => in_dim,
+            None => return Err(error.expect("The graph was evaluated at least once.")),
+        };
+    }
+}
+
+/// ## The overlap-tile plan producing output tiles of `output_tile` pixels with `graph`.
+///
+/// The `output_tile` depends on `(output_tile - 1) * downsampling + receptive_field` input pixels.
+/// The input tile is the smallest one covering them that is accepted by the network, and whose
+/// margins and crops are the same on both sides.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{tiling, Architecture, Network};
+/// // Two convolutions without padding and a pooling layer, with a receptive field of 6.
+/// let network = Network::builder().conv(3, 1, 0).conv(3, 1, 0).maxpool(2, 2).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// let plan = tiling::plan(&graph, 100).unwrap();
+/// assert_eq!((plan.input_tile, plan.margin, plan.stride), (204, 2, 200));
+/// ```
+pub fn plan(graph: &Graph, output_tile: u32) -> Result<TilePlan, ConvDimError> {
+    if output_tile == 0 {
+        return Err(invalid("the output tile needs to be positive.".to_string()));
+    }
+    let (receptive_field, downsampling) = geometry(graph, output_tile)?;
+    let stride = f64::from(output_tile) * downsampling;
+    if (stride - stride.round()).abs() > 1e-9 || stride > f64::from(u32::MAX) {
+        return Err(invalid(format!(
+            "an output tile of {} covers {} input pixels, which is not a whole number.",
+            output_tile, stride
+        )));
+    }
+    let stride = stride.round() as u32;
+    let start = (f64::from(output_tile - 1) * downsampling).ceil() as u64 + receptive_field;
+    let fits = |input_tile: u64| {
+        let input_tile = u32::try_from(input_tile).ok()?;
+        let output = graph.output_shape(input_tile).ok()?.dim;
+        match (
+            output.checked_sub(output_tile),
+            input_tile.checked_sub(stride),
+        ) {
+            (Some(excess), Some(context)) if excess % 2 == 0 && context % 2 == 0 => {
+                Some((input_tile, output, excess / 2))
+            }
+            _ => None,
+        }
+    };
+    // Networks without padding shrink their input, and strided layers round it down.
+    let (input_tile, output, crop) = (start..=2 * start + 64).find_map(fits).ok_or_else(|| {
+        invalid(format!(
+            "no input tile from {} to {} gives an output that can be cropped evenly to {}.",
+            start,
+            2 * start + 64,
+            output_tile
+        ))
+    })?;
+    Ok(TilePlan {
+        receptive_field,
+        downsampling,
+        output_tile,
+        input_tile,
+        margin: (input_tile - stride) / 2,
+        stride,
+        output,
+        crop,
+        memory: None,
+        tiles: None,
+    })
+}
+
+/// ## The number of bytes `text` describes, e.g. `8 GB`, `512MiB` or `1000000`.
+///
+/// The units `kB`, `MB`, `GB` and `TB` are powers of 1000, the units `KiB`, `MiB`, `GiB` and `TiB`
+/// powers of 1024.
+pub fn bytes(text: &str) -> Result<u64, ConvDimError> {
+    let units = [
+        ("KiB", 1u64 << 10),
+        ("MiB", 1 << 20),
+        ("GiB", 1 << 30),
+        ("TiB", 1 << 40),
+        ("kB", 1_000),
+        ("KB", 1_000),
+        ("MB", 1_000_000),
+        ("GB", 1_000_000_000),
+        ("TB", 1_000_000_000_000),
+        ("B", 1),
+    ];
+    let text = text.trim();
+    let (number, factor) = units
+        .iter()
+        .find(|(unit, _)| text.ends_with(unit))
+        .map_or((text, 1), |(unit, factor)| {
+            (text[..text.len() - unit.len()].trim(), *factor)
+        });
+    number
+        .parse::<f64>()
+        .ok()
+        .map(|number| number * factor as f64)
+        .filter(|bytes| bytes.is_finite() && *bytes >= 0.0 && *bytes < u64::MAX as f64)
+        .map(|bytes| bytes as u64)
+        .ok_or_else(|| invalid(format!("'{}' is not a memory size like '8 GB'.", text)))
+}
+
+/// ## The bytes of the activations of `graph` for an input of dimension `in_dim`.
+///
+/// Every input and node keeps its output, whose values take `bytes_per_value` bytes each. Returns
+/// an error if the channels of an output are unknown.
+pub fn activation_memory(
+    graph: &Graph,
+    in_dim: u32,
+    bytes_per_value: u64,
+) -> Result<u64, ConvDimError> {
+    let mut memory = 0u64;
+    for row in summary::rows(graph, in_dim)? {
+        let activations = row.activations().ok_or_else(|| {
+            invalid(format!(
+                "the channels of '{}' are unknown, so its memory is too.",
+                row.name
+            ))
+        })?;
+        memory = memory.saturating_add(activations.saturating_mul(bytes_per_value));
+    }
+    Ok(memory)
+}
+
+/// ## The plan with the largest output tile whose input tile fits into `budget` bytes.
+///
+/// The memory of an input tile is its [`activation_memory`]. Since it grows with the tile, the
+/// smallest output tile exceeding the budget is found by doubling and bisection, and the largest
+/// tile below it that can be planned is returned. Output tiles are at most 2^20 pixels.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::{tiling, Architecture, Network};
+/// let network = Network::builder().input_channels(3).conv(3, 1, 0).channels(8).build();
+/// let graph = Architecture::Layers(network.layers().clone()).into_graph();
+/// // An input tile of 34 pixels takes 4 * (34 * 34 * 3 + 32 * 32 * 8) bytes.
+/// let plan = tiling::plan_for_budget(&graph, 46_640, 4).unwrap().cover(100);
+/// assert_eq!((plan.output_tile, plan.input_tile, plan.tiles), (32, 34, Some(16)));
+/// ```
+pub fn plan_for_budget(
+    graph: &Graph,
+    budget: u64,
+    bytes_per_value: u64,
+) -> Result<TilePlan, ConvDimError> {
+    const LARGEST: u32 = 1 << 20;
+    // The plan for an output tile and its memory, which is `None` if the tile cannot be planned.
+    let planned = |output_tile: u32| -> Result<Option<TilePlan>, ConvDimError> {
+        let mut tiles = match plan(graph, output_tile) {
+            Ok(tiles) => tiles,
+            Err(_) => return Ok(None),
+        };
+        tiles.memory = Some(activation_memory(graph, tiles.input_tile, bytes_per_value)?);
+        Ok(Some(tiles))
+    };
+    let exceeds = |output_tile: u32| -> Result<bool, ConvDimError> {
+        Ok(planned(output_tile)?.is_some_and(|tiles| tiles.memory > Some(budget)))
+    };
+
+    // The output tiles from `low` are known to fit, unless they cannot be planned, and the tile
+    // `high` exceeds the budget.
+    let (mut low, mut high) = (0, 1);
+    while high <= LARGEST && !exceeds(high)? {
+        low = high;
+        high *= 2;
+    }
+    high = high.min(LARGEST + 1);
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        match exceeds(middle)? {
+            true => high = middle,
+            false => low = middle,
+        }
+    }
+    for output_tile in (1..high).rev() {
+        if let Some(tiles) = planned(output_tile)? {
+            return Ok(tiles);
+        }
+    }
+    match planned(1)? {
+        Some(tiles) => Err(invalid(format!(
+            "the smallest tile needs {} of activations, which exceeds the budget of {}.",
+            abbreviate(tiles.memory.unwrap_or(0) as f64, "B"),
+            abbreviate(budget as f64, "B")
+        ))),
+        None => plan(graph, 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::parse_architecture;
+
+    #[test]
+    fn test_plans() {
+        // A small U-Net without padding, whose output tile is centered in its input tile.
+        let unet = parse_architecture(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+
+            [[layers]]
+            type = "max_pool"
+            filter_size = 2
+            stride = 2
+
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+
+            [[layers]]
+            type = "conv_transpose"
+            filter_size = 2
+            stride = 2
+
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let tiles = plan(&unet, 100).unwrap();
+        assert_eq!((tiles.receptive_field, tiles.downsampling), (10, 1.0));
+        assert_eq!((tiles.input_tile, tiles.output, tiles.crop), (110, 102, 1));
+        assert_eq!((tiles.margin, tiles.stride), (5, 100));
+
+        // A network with padding keeps the dimension, so its output is cropped by the margin.
+        let same = parse_architecture(
+            r#"
+            [[layers]]
+            type = "conv"
+            filter_size = 7
+            stride = 2
+            padding = 3
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        let tiles = plan(&same, 32).unwrap();
+        assert_eq!(
+            tiles.to_string(),
+            "receptive field:   7\n\
+             downsampling:      2\n\
+             output tile:      32\n\
+             input tile:       72\n\
+             margin:            4\n\
+             tile stride:      64\n\
+             output crop:       2\n"
+        );
+    }
+
+    #[test]
+    fn test_budget() {
+        assert_eq!(bytes("8 GB"), Ok(8_000_000_000));
+        assert_eq!(bytes("1.5KiB"), Ok(1536));
+        assert_eq!(bytes("640"), Ok(640));
+        assert!(bytes("8 GiBs").is_err());
+
+        // A same-padded convolution with 4 channels on 2 input channels: 24 bytes per pixel.
+        let graph = parse_architecture(
+            r#"
+            input_channels = 2
+
+            [[layers]]
+            type = "conv"
+            filter_size = 3
+            padding = 1
+            channels = 4
+            "#,
+        )
+        .unwrap()
+        .into_graph();
+        // An output tile of 60 needs an input tile of 62, which takes 24 * 62 * 62 bytes.
+        let tiles = plan_for_budget(&graph, 24 * 62 * 62, 4)
+            .unwrap()
+            .cover(1000);
+        assert_eq!((tiles.output_tile, tiles.input_tile), (60, 62));
+        assert_eq!((tiles.memory, tiles.tiles), (Some(92_256), Some(289)));
+        assert_eq!(
+            tiles.to_string(),
+            "receptive field:        3\n\
+             downsampling:           1\n\
+             output tile:           60\n\
+             input tile:            62\n\
+             margin:                 1\n\
+             tile stride:           60\n\
+             output crop:            1\n\
+             memory:           92.3 kB\n\
+             tiles:                289\n"
+        );
+        let smaller = plan_for_budget(&graph, 24 * 62 * 62 - 1, 4).unwrap();
+        assert_eq!((smaller.output_tile, smaller.input_tile), (59, 61));
+
+        assert!(plan_for_budget(&graph, 100, 4).is_err());
+        let unknown =
+            parse_architecture("[[layers]]\ntype = \"max_pool\"\nfilter_size = 2\nstride = 2\n")
+                .unwrap()
+                .into_graph();
+        assert!(plan_for_budget(&unknown, 1 << 30, 4).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+//! Traces of the outputs of the layers of a network, printed in aligned columns.
+//!
+//! The [`Display`](std::fmt::Display) implementations of the traces, the [`Network`](crate::Network)
+//! and the [`Summary`](crate::summary::Summary) share the alignment of [`write_columns`], so the
+//! command line interface and library users print the same tables.
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::architecture::Architecture;
+use crate::error::ConvDimError;
+use crate::layers::trace_layers;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The outputs of the layers of a network, labelled by the names of the layers.
+///
+/// The outputs are dimensions for lists of layers and [`Shape`](crate::graph::Shape)s for graphs.
+/// The trace is displayed with one `name: output` line per layer, whose outputs are aligned.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::trace::Trace;
+/// let trace = Trace::from(vec![("stem".to_string(), 32), ("layer 2".to_string(), 16)]);
+/// assert_eq!(trace.to_string(), "stem:     32\nlayer 2:  16\n");
+/// ```
+pub struct Trace<T> {
+    pub entries: Vec<(String, T)>,
+}
+
+impl<T> From<Vec<(String, T)>> for Trace<T> {
+    fn from(entries: Vec<(String, T)>) -> Trace<T> {
+        Trace { entries }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Trace<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows: Vec<Vec<String>> = self
+            .entries
+            .iter()
+            .map(|(name, output)| vec![format!("{}:", name), output.to_string()])
+            .collect();
+        write_columns(f, &rows, &[])
+    }
+}
+
+#[derive(Serialize)]
+/// ## The output of a layer or node in the trace, whose channels are only known for graphs.
+pub struct Output {
+    pub name: String,
+    pub dim: u32,
+    pub channels: Option<u16>,
+}
+
+/// ## The outputs of all the layers or nodes of `architecture`, e.g. for the JSON documents of the
+/// C interface and the server.
+pub fn outputs(architecture: Architecture, in_dim: u32) -> Result<Vec<Output>, ConvDimError> {
+    match architecture {
+        Architecture::Layers(layers) => trace_layers(&layers.layers, in_dim).map(|trace| {
+            trace
+                .into_iter()
+                .map(|(name, dim)| Output {
+                    name,
+                    dim,
+                    channels: None,
+                })
+                .collect()
+        }),
+        Architecture::Graph(graph) => graph.shapes(in_dim).map(|shapes| {
+            shapes
+                .into_iter()
+                .map(|(name, shape)| Output {
+                    name,
+                    dim: shape.dim,
+                    channels: shape.channels,
+                })
+                .collect()
+        }),
+    }
+}
+
+/// ## Write `rows` with the cells of every column padded to the width of the column.
+///
+/// The columns are separated by two spaces, and the columns whose index is listed in `right`
+/// are aligned to the right, e.g. those holding numbers. Trailing whitespace is trimmed.
+pub(crate) fn write_columns(
+    f: &mut fmt::Formatter,
+    rows: &[Vec<String>],
+    right: &[usize],
+) -> fmt::Result {
+    let mut widths: Vec<usize> = vec![];
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            match widths.get_mut(i) {
+                Some(w) => *w = (*w).max(width),
+                None => widths.push(width),
+            }
+        }
+    }
+    for row in rows {
+        let mut line = String::new();
+        for (i, cell) in row.iter().enumerate() {
+            if i > 0 {
+                line += "  ";
+            }
+            let padding = " ".repeat(widths[i] - cell.chars().count());
+            if right.contains(&i) {
+                line += &padding;
+                line += cell;
+            } else {
+                line += cell;
+                line += &padding;
+            }
+        }
+        writeln!(f, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Shape;
+
+    #[test]
+    fn test_trace_display() {
+        let trace = Trace::from(vec![
+            (
+                "conv_1".to_string(),
+                Shape {
+                    dim: 112,
+                    channels: Some(64),
+                },
+            ),
+            (
+                "up".to_string(),
+                Shape {
+                    dim: 224,
+                    channels: None,
+                },
+            ),
+        ]);
+        assert_eq!(
+            trace.to_string(),
+            "conv_1:  dim 112, 64 channels\nup:      dim 224\n"
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Verification of the computed shapes against the shapes recorded in ONNX models.
+//!
+//! ONNX models may record the shapes of their intermediate tensors in the `value_info` of the
+//! graph, which is filled in by the ONNX shape inference (`onnx.shape_inference.infer_shapes`) and
+//! by some exporters, and the shapes of their outputs. These shapes were computed by the framework
+//! that exported the model, so comparing them to the shapes computed by `convdim` for the imported
+//! graph confirms that both follow the same conventions for the arithmetic of the layers.
+//!
+//! The recorded shapes are only valid for the input dimension the model was exported with, so an
+//! input dimension differing from the one the model declares is rejected rather than reported as
+//! disagreements of every feature map. With the `tract` feature, the shapes are inferred by the
+//! ONNX shape inference of tract instead, independently of `convdim` and for any input dimension.
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+use crate::graph::{Graph, Shape};
+use crate::import::onnx::{self, RecordedShape};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// ## The shape of a tensor recorded in the model next to the shape computed by `convdim`.
+pub struct Comparison<'a> {
+    pub recorded: &'a RecordedShape,
+    pub computed: Shape,
+}
+
+impl Comparison<'_> {
+    /// Whether the known dimensions of the recorded shape agree with the computed shape.
+    pub fn agrees(&self) -> bool {
+        let dims = &self.recorded.dims;
+        let channels = match (dims[1], self.computed.channels) {
+            (Some(recorded), Some(computed)) => recorded == computed as i64,
+            _ => true,
+        };
+        channels
+            && dims[2..]
+                .iter()
+                .flatten()
+                .all(|&dim| dim == self.computed.dim as i64)
+    }
+}
+
+/// The shape `dims` in the layout (N, C, spatial...), e.g. `1x64x56x56`, with `?` for unknown dimensions.
+fn describe(dims: &[Option<i64>]) -> String {
+    dims.iter()
+        .map(|dim| dim.map_or_else(|| "?".to_string(), |dim| dim.to_string()))
+        .collect::<Vec<String>>()
+        .join("x")
+}
+
+/// ## The input dimension for which the shapes recorded in the model of `graph` are compared.
+///
+/// The inputs that the model declares with their dimension are only valid for that dimension, so
+/// `in_dim` is rejected if it differs. Without such inputs, `in_dim` is required.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::graph::Graph;
+/// # use convdim::verify::input_dim;
+/// let graph: Graph = toml::from_str(
+///     "[[inputs]]\nname = \"x\"\ninput_dim = 224\n\n[[nodes]]\nname = \"c\"\nop = \"conv\"\nfilter_size = 3\n",
+/// )
+/// .unwrap();
+/// assert_eq!(input_dim(&graph, None), Ok(224));
+/// assert_eq!(input_dim(&graph, Some(224)), Ok(224));
+/// assert!(input_dim(&graph, Some(256)).is_err());
+/// ```
+pub fn input_dim(graph: &Graph, in_dim: Option<u32>) -> Result<u32, ConvDimError> {
+    let declared = graph.inputs.iter().find_map(|input| input.input_dim);
+    match (declared, in_dim) {
+        (Some(declared), Some(in_dim)) if in_dim != declared => Err(ConvDimError::Verify {
+            message: format!(
+                "the model declares an input dimension of {}, and the shapes recorded in it are \
+                 only valid for that dimension, not for {}.",
+                declared, in_dim
+            ),
+        }),
+        (_, Some(in_dim)) => Ok(in_dim),
+        (Some(declared), None) if !graph.needs_input_dim() => Ok(declared),
+        _ => Err(ConvDimError::Verify {
+            message: "the input dimension is required, since not all the inputs declare their own."
+                .to_string(),
+        }),
+    }
+}
+
+/// ## The input dimension and the shapes of the ONNX model encoded in `bytes` to compare to `graph`.
+///
+/// Without the `tract` feature, these are the shapes recorded in the model, for the dimension its
+/// inputs declare (see [`input_dim`]).
+#[cfg(not(feature = "tract"))]
+pub fn model_shapes(
+    graph: &mut Graph,
+    bytes: &[u8],
+    in_dim: Option<u32>,
+) -> Result<(u32, Vec<RecordedShape>), ConvDimError> {
+    let in_dim = input_dim(graph, in_dim)?;
+    let shapes =
+        onnx::recorded_shapes(bytes).map_err(|message| ConvDimError::Verify { message })?;
+    Ok((in_dim, shapes))
+}
+
+/// ## The input dimension and the shapes of the ONNX model encoded in `bytes` to compare to `graph`.
+///
+/// With the `tract` feature, these are the shapes inferred by tract for the dimension `in_dim`,
+/// which replaces the dimensions the inputs of `graph` declare, or for the declared dimension.
+#[cfg(feature = "tract")]
+pub fn model_shapes(
+    graph: &mut Graph,
+    bytes: &[u8],
+    in_dim: Option<u32>,
+) -> Result<(u32, Vec<RecordedShape>), ConvDimError> {
+    let in_dim = match in_dim {
+        Some(in_dim) => in_dim,
+        None => input_dim(graph, None)?,
+    };
+    for input in graph.inputs.iter_mut() {
+        input.input_dim = None;
+    }
+    let shapes =
+        onnx::inferred_shapes(bytes, in_dim).map_err(|message| ConvDimError::Verify { message })?;
+    Ok((in_dim, shapes))
+}
+
+/// ## Compare the `recorded` shapes to the shapes computed for `graph` with input dimension `in_dim`.
+pub fn compare<'a>(
+    graph: &Graph,
+    in_dim: u32,
+    recorded: &'a [RecordedShape],
+) -> Result<Vec<Comparison<'a>>, ConvDimError> {
+    let shapes: HashMap<String, Shape> = graph
+        .input_shapes(in_dim)
+        .into_iter()
+        .map(|(name, shape)| (name.to_string(), shape))
+        .chain(graph.shapes(in_dim)?)
+        .collect();
+    Ok(recorded
+        .iter()
+        .filter_map(|recorded| {
+            shapes
+                .get(&recorded.node)
+                .map(|&computed| Comparison { recorded, computed })
+        })
+        .collect())
+}
+
+/// ## Report the `comparisons` line by line, marking the disagreements, followed by a summary.
+pub fn report(comparisons: &[Comparison]) -> String {
+    let mut report = String::new();
+    for comparison in comparisons {
+        let computed = comparison.computed;
+        // The computed shape in the layout of the recorded one.
+        let mut dims = vec![
+            comparison.recorded.dims[0],
+            computed.channels.map(i64::from),
+        ];
+        dims.extend(vec![
+            Some(computed.dim as i64);
+            comparison.recorded.dims.len() - 2
+        ]);
+        report += &format!(
+            "{} {} (node '{}'): model {}, convdim {}\n",
+            if comparison.agrees() {
+                "ok      "
+            } else {
+                "MISMATCH"
+            },
+            comparison.recorded.tensor,
+            comparison.recorded.node,
+            describe(&comparison.recorded.dims),
+            describe(&dims)
+        );
+    }
+    let disagreements = comparisons.iter().filter(|c| !c.agrees()).count();
+    report += &format!(
+        "{} of {} recorded shapes agree with convdim.\n",
+        comparisons.len() - disagreements,
+        comparisons.len()
+    );
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disagreements_are_reported() {
+        let graph: Graph = toml::from_str(
+            r#"
+            input_channels = 3
+
+            [[nodes]]
+            name = "conv"
+            op = "conv"
+            filter_size = 3
+            stride = 2
+            channels = 16
+
+            [[nodes]]
+            name = "pool"
+            op = "conv"
+            filter_size = 2
+            stride = 2
+            "#,
+        )
+        .unwrap();
+        let recorded = vec![
+            RecordedShape {
+                tensor: "relu".to_string(),
+                node: "conv".to_string(),
+                dims: vec![None, Some(16), Some(15), Some(15)],
+            },
+            // Pooling in ceil mode rounds up.
+            RecordedShape {
+                tensor: "pool".to_string(),
+                node: "pool".to_string(),
+                dims: vec![Some(1), Some(16), Some(8), Some(8)],
+            },
+        ];
+        let comparisons = compare(&graph, 32, &recorded).unwrap();
+        assert!(comparisons[0].agrees());
+        assert!(!comparisons[1].agrees());
+        assert_eq!(
+            report(&comparisons),
+            "ok       relu (node 'conv'): model ?x16x15x15, convdim ?x16x15x15\n\
+             MISMATCH pool (node 'pool'): model 1x16x8x8, convdim 1x16x7x7\n\
+             1 of 2 recorded shapes agree with convdim.\n"
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! The number of frames and the resolution of videos, read from the metadata of MP4 (or QuickTime)
+//! and AVI containers.
+//!
+//! The frames are never decoded: an MP4 video has its resolution in the header of its video track
+//! and its number of frames in the sample sizes of the track, and an AVI video has both in its main
+//! header. Reading the metadata of an MP4 file skips the media data, which makes up most of it.
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::error::ConvDimError;
+
+/// The extensions of videos, which are compared ignoring their case.
+pub const EXTENSIONS: [&str; 4] = ["mp4", "m4v", "mov", "avi"];
+
+/// The bytes at the start of an AVI file that hold its main header.
+const AVI_HEADER: u64 = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+/// ## The number of frames of a video and their size.
+pub struct Header {
+    pub frames: u32,
+    pub height: u32,
+    pub width: u32,
+}
+
+fn invalid(message: &str) -> ConvDimError {
+    ConvDimError::Video {
+        message: message.to_string(),
+    }
+}
+
+fn be32(bytes: &[u8], at: usize) -> Option<u32> {
+    let field = bytes.get(at..at + 4)?;
+    Some(u32::from_be_bytes([field[0], field[1], field[2], field[3]]))
+}
+
+fn le32(bytes: &[u8], at: usize) -> Option<u32> {
+    let field = bytes.get(at..at + 4)?;
+    Some(u32::from_le_bytes([field[0], field[1], field[2], field[3]]))
+}
+
+/// The boxes of an MP4 file in `bytes`, with their type and content.
+fn boxes(mut bytes: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut boxes = vec![];
+    while bytes.len() >= 8 {
+        let (kind, header) = (&bytes[4..8], 8);
+        // A size of 1 is followed by a 64 bit size, and a size of 0 extends to the end.
+        let (size, header) = match be32(bytes, 0).unwrap_or(0) {
+            0 => (bytes.len() as u64, header),
+            1 => match bytes.get(8..16) {
+                Some(size) => (
+                    u64::from_be_bytes(size.try_into().expect("The size has 8 bytes.")),
+                    16,
+                ),
+                None => break,
+            },
+            size => (u64::from(size), header),
+        };
+        if size < header as u64 || size > bytes.len() as u64 {
+            break;
+        }
+        boxes.push((kind, &bytes[header..size as usize]));
+        bytes = &bytes[size as usize..];
+    }
+    boxes
+}
+
+/// The content of the first box of type `kind` among the boxes in `bytes`.
+fn find<'a>(bytes: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    boxes(bytes)
+        .into_iter()
+        .find(|(found, _)| *found == kind)
+        .map(|(_, content)| content)
+}
+
+/// The header of an MP4 video, from its first video track.
+fn mp4(bytes: &[u8]) -> Result<Header, ConvDimError> {
+    let moov = find(bytes, b"moov").ok_or_else(|| invalid("the MP4 video has no movie box."))?;
+    let track = boxes(moov)
+        .into_iter()
+        .filter(|(kind, _)| *kind == b"trak")
+        .map(|(_, trak)| trak)
+        .find(|trak| {
+            find(trak, b"mdia")
+                .and_then(|mdia| find(mdia, b"hdlr"))
+                .and_then(|hdlr| hdlr.get(8..12))
+                == Some(b"vide")
+        })
+        .ok_or_else(|| invalid("the MP4 video has no video track."))?;
+    let truncated = || invalid("the MP4 video has a truncated video track.");
+    let tkhd = find(track, b"tkhd").ok_or_else(truncated)?;
+    // The times and the duration of the track take 64 bits from version 1 on.
+    let size = if tkhd.first() == Some(&1) { 88 } else { 76 };
+    // The width and the height are 16.16 fixed-point numbers.
+    let (width, height) = match (be32(tkhd, size), be32(tkhd, size + 4)) {
+        (Some(width), Some(height)) => (width >> 16, height >> 16),
+        _ => return Err(truncated()),
+    };
+    let frames = find(track, b"mdia")
+        .and_then(|mdia| find(mdia, b"minf"))
+        .and_then(|minf| find(minf, b"stbl"))
+        .and_then(|stbl| find(stbl, b"stsz"))
+        .and_then(|stsz| be32(stsz, 8))
+        .ok_or_else(truncated)?;
+    Ok(Header {
+        frames,
+        height,
+        width,
+    })
+}
+
+/// The header of an AVI video, from the main header at the start of its header list.
+fn avi(bytes: &[u8]) -> Result<Header, ConvDimError> {
+    let avih = bytes
+        .get(12..)
+        .filter(|list| list.starts_with(b"LIST") && list.get(8..12) == Some(b"hdrl"))
+        .and_then(|list| list.get(12..))
+        .filter(|avih| avih.starts_with(b"avih"))
+        .ok_or_else(|| invalid("the AVI video has no main header."))?;
+    match (le32(avih, 24), le32(avih, 40), le32(avih, 44)) {
+        (Some(frames), Some(width), Some(height)) => Ok(Header {
+            frames,
+            height,
+            width,
+        }),
+        _ => Err(invalid("the AVI video has a truncated main header.")),
+    }
+}
+
+/// ## The header of the MP4 or AVI video `bytes`, recognized by their signature.
+///
+/// ## Example
+///
+/// ```rust
+/// # use convdim::video;
+/// // The main header of an AVI video with 300 frames of 320x240.
+/// let mut avi = b"RIFF\0\0\0\0AVI LIST\0\0\0\0hdrlavih\x38\0\0\0".to_vec();
+/// avi.extend([0; 56]);
+/// avi[48..52].copy_from_slice(&300u32.to_le_bytes());
+/// avi[64..68].copy_from_slice(&320u32.to_le_bytes());
+/// avi[68..72].copy_from_slice(&240u32.to_le_bytes());
+/// let header = video::parse(&avi).unwrap();
+/// assert_eq!((header.frames, header.height, header.width), (300, 240, 320));
+/// ```
+pub fn parse(bytes: &[u8]) -> Result<Header, ConvDimError> {
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"AVI ") {
+        avi(bytes)
+    } else if bytes.get(4..8) == Some(b"ftyp") {
+        mp4(bytes)
+    } else {
+        Err(invalid("the file is not an MP4 or AVI video."))
+    }
+}
+
+/// The metadata of the video in `file`, i.e. all of it but the media data of an MP4 file.
+fn metadata(file: &mut File) -> std::io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    file.by_ref().take(12).read_to_end(&mut bytes)?;
+    if bytes.starts_with(b"RIFF") {
+        file.by_ref().take(AVI_HEADER).read_to_end(&mut bytes)?;
+        return Ok(bytes);
+    }
+    file.seek(SeekFrom::Start(0))?;
+    bytes.clear();
+    loop {
+        let mut header = vec![];
+        file.by_ref().take(16).read_to_end(&mut header)?;
+        if header.len() < 8 {
+            return Ok(bytes);
+        }
+        let start = file.seek(SeekFrom::Current(-(header.len() as i64)))?;
+        // A box of size 0 extends to the end of the file.
+        let (size, header_size) = match be32(&header, 0).expect("The header has 8 bytes.") {
+            0 => {
+                file.read_to_end(&mut bytes)?;
+                return Ok(bytes);
+            }
+            1 if header.len() == 16 => (u64::from_be_bytes(header[8..16].try_into().unwrap()), 16),
+            1 => return Ok(bytes),
+            size => (u64::from(size), 8),
+        };
+        // A box smaller than its header would be read again and again.
+        let malformed = || {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "the MP4 box '{}' has an invalid size of {}.",
+                    String::from_utf8_lossy(&header[4..8]),
+                    size
+                ),
+            )
+        };
+        if size < header_size {
+            return Err(malformed());
+        }
+        if &header[4..8] == b"mdat" {
+            let end = start.checked_add(size).ok_or_else(malformed)?;
+            file.seek(SeekFrom::Start(end))?;
+        } else {
+            let before = bytes.len();
+            file.by_ref().take(size).read_to_end(&mut bytes)?;
+            if ((bytes.len() - before) as u64) < size {
+                return Ok(bytes);
+            }
+        }
+    }
+}
+
+/// ## The header of the MP4 or AVI video at `path`.
+pub fn read(path: &Path) -> Result<Header, ConvDimError> {
+    let unreadable = |e: std::io::Error| ConvDimError::Video {
+        message: format!("'{}': {}", path.display(), e),
+    };
+    let mut file = File::open(path).map_err(unreadable)?;
+    let bytes = metadata(&mut file).map_err(unreadable)?;
+    parse(&bytes).map_err(|e| match e {
+        ConvDimError::Video { message } => ConvDimError::Video {
+            message: format!("'{}': {}", path.display(), message),
+        },
+        e => e,
+    })
+}
+
+/// ## Whether the file at `path` is a video, judged by its extension.
+pub fn is_video(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        let extension = extension.to_string_lossy().to_lowercase();
+        EXTENSIONS.contains(&extension.as_str())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An MP4 box of type `kind` holding `content`.
+    fn mp4_box(kind: &[u8], content: &[u8]) -> Vec<u8> {
+        let mut bytes = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+        bytes.extend(kind);
+        bytes.extend(content);
+        bytes
+    }
+
+    #[test]
+    fn test_mp4() {
+        // A version 0 track header of 1280x720.
+        let mut tkhd = vec![0; 84];
+        tkhd[76..80].copy_from_slice(&(1280u32 << 16).to_be_bytes());
+        tkhd[80..84].copy_from_slice(&(720u32 << 16).to_be_bytes());
+        let mut hdlr = vec![0; 24];
+        hdlr[8..12].copy_from_slice(b"vide");
+        let mut stsz = vec![0; 12];
+        stsz[8..12].copy_from_slice(&250u32.to_be_bytes());
+        let stbl = mp4_box(b"stbl", &mp4_box(b"stsz", &stsz));
+        let minf = mp4_box(b"minf", &stbl);
+        let mdia = mp4_box(b"mdia", &[mp4_box(b"hdlr", &hdlr), minf].concat());
+        let trak = mp4_box(b"trak", &[mp4_box(b"tkhd", &tkhd), mdia].concat());
+        let video = [
+            mp4_box(b"ftyp", b"isom\0\0\0\0"),
+            mp4_box(b"mdat", &[0; 100]),
+            mp4_box(b"moov", &trak),
+        ]
+        .concat();
+        let expected = Header {
+            frames: 250,
+            height: 720,
+            width: 1280,
+        };
+        assert_eq!(parse(&video), Ok(expected));
+
+        // The metadata read from a file skips the media data.
+        let path = std::env::temp_dir().join(format!("convdim-{}.mp4", std::process::id()));
+        std::fs::write(&path, &video).unwrap();
+        let header = read(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(header, Ok(expected));
+
+        assert!(parse(&video[..video.len() - 4]).is_err());
+        assert!(parse(b"\x1aE\xdf\xa3").is_err());
+
+        // A media data box with a 64-bit size of 0 would be sought back to its start forever.
+        let mut looping = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        looping.extend(b"\0\0\0\x01mdat\0\0\0\0\0\0\0\0");
+        std::fs::write(&path, &looping).unwrap();
+        let header = read(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(header
+            .unwrap_err()
+            .to_string()
+            .contains("the MP4 box 'mdat' has an invalid size of 0."));
+    }
+}
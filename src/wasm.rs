@@ -0,0 +1,112 @@
+//! The JavaScript API of the WebAssembly build, generated by wasm-bindgen.
+//!
+//! With the `wasm` feature, `wasm-pack build --target web -- --features wasm` compiles the library
+//! for the `wasm32-unknown-unknown` target and writes the npm package to `pkg/`: the module
+//! `convdim.js` with the bindings and the compiled library `convdim_bg.wasm`. The functions take
+//! the networks as their toml description, in the list or the graph format, and return the results
+//! of the analyses as the objects of the JSON documents of the [C interface](crate::ffi). Errors
+//! are thrown as instances of the class [`ConvDimError`].
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::architecture::parse_architecture;
+use crate::error;
+use crate::layers;
+use crate::summary::rows;
+use crate::trace::outputs;
+
+#[wasm_bindgen]
+extern "C" {
+    /// `JSON.parse`, turning a JSON document into the JavaScript value it describes.
+    #[wasm_bindgen(js_namespace = JSON, js_name = parse)]
+    fn parse(text: &str) -> JsValue;
+}
+
+#[wasm_bindgen]
+/// ## An error computing the dimensions of a network, or parsing it.
+pub struct ConvDimError {
+    message: String,
+}
+
+#[wasm_bindgen]
+impl ConvDimError {
+    /// The message of the error.
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+}
+
+impl From<error::ConvDimError> for ConvDimError {
+    fn from(error: error::ConvDimError) -> ConvDimError {
+        ConvDimError {
+            message: error.to_string(),
+        }
+    }
+}
+
+/// The serializable `result` as a JavaScript value, without the members that are `None`.
+fn respond<T: Serialize>(result: T) -> Result<JsValue, ConvDimError> {
+    let error = |message: String| ConvDimError { message };
+    let value = toml::Value::try_from(result).map_err(|e| error(e.to_string()))?;
+    let json = serde_json::to_string(&value).map_err(|e| error(e.to_string()))?;
+    Ok(parse(&json))
+}
+
+/// ## The output dimension of a convolution applied `repeat` times, see [`layers::conv_output_dim`].
+///
+/// The `padding`, `stride` and `repeat` default to 0, 1 and 1.
+#[wasm_bindgen(js_name = convOutputDim)]
+pub fn conv_output_dim(
+    in_dim: u32,
+    filter_size: u32,
+    padding: Option<u32>,
+    stride: Option<u32>,
+    repeat: Option<u32>,
+) -> Result<u32, ConvDimError> {
+    let (padding, stride, repeat) = (
+        padding.unwrap_or(0),
+        stride.unwrap_or(1),
+        repeat.unwrap_or(1),
+    );
+    layers::conv_output_dim(in_dim, filter_size, padding, stride, repeat)
+        .map_err(ConvDimError::from)
+}
+
+/// ## The output dimension of a transposed convolution applied `repeat` times, see
+/// [`layers::transposed_conv_output_dim`].
+///
+/// The `padding`, `stride` and `repeat` default to 0, 1 and 1.
+#[wasm_bindgen(js_name = transposedConvOutputDim)]
+pub fn transposed_conv_output_dim(
+    in_dim: u32,
+    filter_size: u32,
+    padding: Option<u32>,
+    stride: Option<u32>,
+    repeat: Option<u32>,
+) -> Result<u32, ConvDimError> {
+    let (padding, stride, repeat) = (
+        padding.unwrap_or(0),
+        stride.unwrap_or(1),
+        repeat.unwrap_or(1),
+    );
+    layers::transposed_conv_output_dim(in_dim, filter_size, padding, stride, repeat)
+        .map_err(ConvDimError::from)
+}
+
+/// ## The outputs of all the layers or nodes of the network `description`, like `--trace`.
+///
+/// The outputs are objects with the `name`, the `dim` and, for graphs, the `channels`.
+#[wasm_bindgen]
+pub fn trace(description: &str, in_dim: u32) -> Result<JsValue, ConvDimError> {
+    respond(outputs(parse_architecture(description)?, in_dim)?)
+}
+
+/// ## The summary rows of the network `description`, see [`summary::rows`](crate::summary::rows).
+///
+/// The rows hold the shapes, parameters, operations and receptive fields of the inputs and nodes.
+#[wasm_bindgen]
+pub fn summary(description: &str, in_dim: u32) -> Result<JsValue, ConvDimError> {
+    let graph = parse_architecture(description)?.into_graph();
+    respond(rows(&graph, in_dim)?)
+}
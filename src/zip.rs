@@ -0,0 +1,248 @@
+//! A minimal reader of zip archives.
+//!
+//! Only the entries that are stored without compression can be read, which is how PyTorch
+//! writes its archives (so that the tensors can be memory-mapped).
+//!
+//! The sizes and offsets are read from the archive, so all the arithmetic on them is checked.
+use std::convert::TryFrom;
+
+/// The signatures of the records of a zip archive.
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+/// The id of the extra field holding the sizes and offsets of zip64 entries.
+const ZIP64_EXTRA: u16 = 0x0001;
+/// The compression method of entries that are stored as is.
+const STORED: u16 = 0;
+
+/// Read the little-endian unsigned integer of `n` bytes at `pos`.
+fn uint(bytes: &[u8], pos: usize, n: usize) -> Option<u64> {
+    Some(
+        bytes
+            .get(pos..pos.checked_add(n)?)?
+            .iter()
+            .rev()
+            .fold(0, |value, &byte| (value << 8) | byte as u64),
+    )
+}
+
+fn u16_at(bytes: &[u8], pos: usize) -> Option<u16> {
+    uint(bytes, pos, 2).map(|value| value as u16)
+}
+
+fn u32_at(bytes: &[u8], pos: usize) -> Option<u32> {
+    uint(bytes, pos, 4).map(|value| value as u32)
+}
+
+#[derive(Debug, Clone)]
+/// ## An entry of a zip archive.
+struct Entry {
+    name: String,
+    method: u16,
+    size: u64,
+    /// The offset of the local header of the entry.
+    offset: u64,
+}
+
+#[derive(Debug)]
+/// ## A zip archive, read from its central directory.
+pub struct Archive<'a> {
+    bytes: &'a [u8],
+    entries: Vec<Entry>,
+}
+
+impl<'a> Archive<'a> {
+    /// ## Read the central directory of the archive in `bytes`.
+    pub fn read(bytes: &'a [u8]) -> Result<Archive<'a>, String> {
+        let invalid = || "Invalid zip archive.".to_string();
+        // The end of central directory record is followed by a comment of at most 65535 bytes.
+        let end = (0..bytes.len().saturating_sub(21))
+            .rev()
+            .take(65535 + 22)
+            .find(|&pos| u32_at(bytes, pos) == Some(END_OF_CENTRAL_DIRECTORY))
+            .ok_or_else(|| "The file is not a zip archive.".to_string())?;
+        let count = u16_at(bytes, end + 10).ok_or_else(invalid)?;
+        let mut pos = u32_at(bytes, end + 16).ok_or_else(invalid)? as usize;
+        let add = |a: usize, b: usize| a.checked_add(b).ok_or_else(invalid);
+
+        let mut entries = vec![];
+        for _ in 0..count {
+            if u32_at(bytes, pos) != Some(CENTRAL_HEADER) {
+                return Err(invalid());
+            }
+            let field = |offset| u16_at(bytes, add(pos, offset)?).ok_or_else(invalid);
+            let (name_length, extra_length, comment_length) = (
+                field(28)? as usize,
+                field(30)? as usize,
+                field(32)? as usize,
+            );
+            let extra_start = add(add(pos, 46)?, name_length)?;
+            let name = bytes.get(add(pos, 46)?..extra_start).ok_or_else(invalid)?;
+            let mut compressed_size = u32_at(bytes, add(pos, 20)?).ok_or_else(invalid)? as u64;
+            let mut size = u32_at(bytes, add(pos, 24)?).ok_or_else(invalid)? as u64;
+            let mut offset = u32_at(bytes, add(pos, 42)?).ok_or_else(invalid)? as u64;
+
+            // Sizes and offsets that don't fit into 32 bits are moved to the zip64 extra field,
+            // in the order uncompressed size, compressed size, offset.
+            let extra_end = add(extra_start, extra_length)?;
+            let mut extra = extra_start;
+            while add(extra, 4)? <= extra_end {
+                let (id, length) = (
+                    u16_at(bytes, extra).ok_or_else(invalid)?,
+                    u16_at(bytes, add(extra, 2)?).ok_or_else(invalid)? as usize,
+                );
+                if id == ZIP64_EXTRA {
+                    let mut value = add(extra, 4)?;
+                    for field in [&mut size, &mut compressed_size, &mut offset] {
+                        if *field == u32::MAX as u64 {
+                            *field = uint(bytes, value, 8).ok_or_else(invalid)?;
+                            value = add(value, 8)?;
+                        }
+                    }
+                }
+                extra = add(add(extra, 4)?, length)?;
+            }
+
+            entries.push(Entry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                method: field(10)?,
+                size,
+                offset,
+            });
+            pos = add(extra_end, comment_length)?;
+        }
+        Ok(Archive { bytes, entries })
+    }
+
+    /// The names of the entries of the archive.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.name.as_str())
+    }
+
+    /// ## The content of the entry `name`.
+    pub fn get(&self, name: &str) -> Result<&'a [u8], String> {
+        let entry = match self.entries.iter().find(|entry| entry.name == name) {
+            Some(entry) => entry,
+            None => return Err(format!("The archive does not contain '{}'.", name)),
+        };
+        if entry.method != STORED {
+            return Err(format!(
+                "The entry '{}' of the archive is compressed, which is not supported.",
+                name
+            ));
+        }
+        let invalid = || format!("The entry '{}' of the archive is truncated.", name);
+        let header = usize::try_from(entry.offset).map_err(|_| invalid())?;
+        if u32_at(self.bytes, header) != Some(LOCAL_HEADER) {
+            return Err(invalid());
+        }
+        let length = |offset| {
+            let pos = header.checked_add(offset).ok_or_else(invalid)?;
+            u16_at(self.bytes, pos).ok_or_else(invalid)
+        };
+        // The local header repeats the name, but may have an extra field of a different length.
+        let start = header
+            .checked_add(30 + length(26)? as usize + length(28)? as usize)
+            .ok_or_else(invalid)?;
+        let end = usize::try_from(entry.size)
+            .ok()
+            .and_then(|size| start.checked_add(size))
+            .ok_or_else(invalid)?;
+        self.bytes.get(start..end).ok_or_else(invalid)
+    }
+}
+
+#[cfg(test)]
+/// ## Write an archive storing `files` without compression (and without checksums).
+pub fn write(files: &[(&str, &[u8])]) -> Vec<u8> {
+    fn push(bytes: &mut Vec<u8>, values: &[u32], sizes: &[usize]) {
+        for (value, size) in values.iter().zip(sizes) {
+            bytes.extend_from_slice(&value.to_le_bytes()[..*size]);
+        }
+    }
+    let mut bytes = vec![];
+    let mut directory = vec![];
+    for (name, content) in files {
+        let (offset, size, name_length) =
+            (bytes.len() as u32, content.len() as u32, name.len() as u32);
+        // Signature, version, flags, method, time, date, checksum, sizes, lengths of name and extra field.
+        push(
+            &mut bytes,
+            &[LOCAL_HEADER, 20, 0, 0, 0, 0, 0, size, size, name_length, 0],
+            &[4, 2, 2, 2, 2, 2, 4, 4, 4, 2, 2],
+        );
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(content);
+        // The central header additionally has the creator version, a comment, disk, attributes and offset.
+        push(
+            &mut directory,
+            &[
+                CENTRAL_HEADER,
+                20,
+                20,
+                0,
+                0,
+                0,
+                0,
+                0,
+                size,
+                size,
+                name_length,
+                0,
+                0,
+                0,
+                0,
+                0,
+                offset,
+            ],
+            &[4, 2, 2, 2, 2, 2, 2, 4, 4, 4, 2, 2, 2, 2, 2, 4, 4],
+        );
+        directory.extend_from_slice(name.as_bytes());
+    }
+    let (start, count) = (bytes.len() as u32, files.len() as u32);
+    let length = directory.len() as u32;
+    bytes.extend(directory);
+    push(
+        &mut bytes,
+        &[
+            END_OF_CENTRAL_DIRECTORY,
+            0,
+            0,
+            count,
+            count,
+            length,
+            start,
+            0,
+        ],
+        &[4, 2, 2, 2, 2, 4, 4, 2],
+    );
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_written_archive() {
+        let bytes = write(&[("model/data.pkl", b"\x80\x02."), ("model/version", b"3\n")]);
+        let archive = Archive::read(&bytes).unwrap();
+        assert_eq!(
+            archive.names().collect::<Vec<_>>(),
+            vec!["model/data.pkl", "model/version"]
+        );
+        assert_eq!(archive.get("model/version"), Ok(&b"3\n"[..]));
+        assert!(archive.get("model/constants.pkl").is_err());
+        assert!(Archive::read(b"not a zip archive").is_err());
+
+        // A stored zip64 entry of u64::MAX bytes, whose end overflows.
+        let mut archive = archive;
+        archive.entries[1].size = u64::MAX;
+        assert_eq!(
+            archive.get("model/version").unwrap_err(),
+            "The entry 'model/version' of the archive is truncated."
+        );
+        archive.entries[1].offset = u64::MAX - 10;
+        assert!(archive.get("model/version").is_err());
+    }
+}
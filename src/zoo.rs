@@ -0,0 +1,337 @@
+//! Statistics of a collection of architectures, e.g. to audit the networks of a model zoo.
+//!
+//! The descriptions are found in a directory and its subdirectories like by
+//! [`check`](crate::check), and summarized by the distributions of their depths (the numbers of
+//! (transposed) convolutions) and of their downsampling factors (the largest stride of a feature
+//! map relative to the input), the histogram of the filter sizes of all their convolutions, and
+//! the range of their numbers of parameters.
+//!
+//! The backbone of a network computes its most downsampled feature map: it consists of the first
+//! node with the largest downsampling factor and the nodes it depends on. Networks whose backbones
+//! have the same [fingerprint](crate::fingerprint) share their backbone, whatever the names of its
+//! nodes or the heads following it.
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::architecture::read_architecture;
+use crate::check::descriptions;
+use crate::error::ConvDimError;
+use crate::fingerprint;
+use crate::graph::{Graph, Op};
+use crate::report::abbreviate;
+use crate::summary;
+use crate::trace::write_columns;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+/// ## The measures of a description of the collection.
+pub struct Config {
+    pub path: PathBuf,
+    /// The number of (transposed) convolutions.
+    pub depth: usize,
+    /// The largest stride of a feature map relative to the input, e.g. 32.
+    pub downsampling: f64,
+    /// The number of weights and biases, if the channels of all the layers are known.
+    pub params: Option<u64>,
+    /// The filter sizes of the (transposed) convolutions.
+    pub filter_sizes: Vec<u16>,
+    /// The fingerprint of the backbone.
+    pub backbone: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+/// ## The descriptions of a collection, ordered by their paths.
+pub struct Zoo {
+    pub configs: Vec<Config>,
+    /// The descriptions that could not be evaluated, with their errors.
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// The distinct `values` in increasing order, with the number of times each occurs.
+fn counts<T: PartialOrd + Copy>(mut values: Vec<T>) -> Vec<(T, usize)> {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let mut counts: Vec<(T, usize)> = vec![];
+    for value in values {
+        match counts.last_mut() {
+            Some((last, count)) if *last == value => *count += 1,
+            _ => counts.push((value, 1)),
+        }
+    }
+    counts
+}
+
+/// The downsampling `factor` as text, e.g. `/32`.
+fn factor(factor: f64) -> String {
+    match (factor - factor.round()).abs() < 1e-9 {
+        true => format!("/{}", factor.round()),
+        false => format!("/{:.2}", factor),
+    }
+}
+
+impl Zoo {
+    /// The depths of the descriptions, with the number of descriptions of each.
+    pub fn depths(&self) -> Vec<(usize, usize)> {
+        counts(self.configs.iter().map(|config| config.depth).collect())
+    }
+
+    /// The downsampling factors of the descriptions, with the number of descriptions of each.
+    pub fn downsampling(&self) -> Vec<(f64, usize)> {
+        counts(
+            self.configs
+                .iter()
+                .map(|config| config.downsampling)
+                .collect(),
+        )
+    }
+
+    /// The filter sizes of the convolutions of all the descriptions, with the number of each.
+    pub fn filter_sizes(&self) -> Vec<(u16, usize)> {
+        let sizes = self.configs.iter().flat_map(|config| &config.filter_sizes);
+        counts(sizes.copied().collect())
+    }
+
+    /// The smallest, median and largest numbers of parameters of the descriptions that have one.
+    pub fn params(&self) -> Option<(u64, u64, u64)> {
+        let mut params: Vec<u64> = self
+            .configs
+            .iter()
+            .filter_map(|config| config.params)
+            .collect();
+        params.sort_unstable();
+        Some((*params.first()?, params[params.len() / 2], *params.last()?))
+    }
+
+    /// The groups of descriptions that share their backbone, ordered by their first paths.
+    pub fn shared_backbones(&self) -> Vec<Vec<&Path>> {
+        let mut groups: Vec<(&str, Vec<&Path>)> = vec![];
+        for config in &self.configs {
+            match groups
+                .iter_mut()
+                .find(|(backbone, _)| *backbone == config.backbone)
+            {
+                Some((_, paths)) => paths.push(&config.path),
+                None => groups.push((&config.backbone, vec![&config.path])),
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(_, paths)| paths)
+            .filter(|paths| paths.len() > 1)
+            .collect()
+    }
+}
+
+impl fmt::Display for Zoo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |counts: Vec<String>| match counts.is_empty() {
+            true => "-".to_string(),
+            false => counts.join(", "),
+        };
+        let mut rows = vec![
+            vec![
+                "descriptions:".to_string(),
+                format!("{}, {} failed", self.configs.len(), self.failed.len()),
+            ],
+            vec![
+                "depths:".to_string(),
+                join(
+                    self.depths()
+                        .iter()
+                        .map(|(depth, count)| format!("{} ({})", depth, count))
+                        .collect(),
+                ),
+            ],
+            vec![
+                "downsampling:".to_string(),
+                join(
+                    self.downsampling()
+                        .iter()
+                        .map(|&(downsampling, count)| {
+                            format!("{} ({})", factor(downsampling), count)
+                        })
+                        .collect(),
+                ),
+            ],
+            vec![
+                "filter sizes:".to_string(),
+                join(
+                    self.filter_sizes()
+                        .iter()
+                        .map(|(size, count)| format!("{0}x{0} ({1})", size, count))
+                        .collect(),
+                ),
+            ],
+            vec![
+                "parameters:".to_string(),
+                self.params().map_or_else(
+                    || "-".to_string(),
+                    |(min, median, max)| {
+                        format!(
+                            "min {}, median {}, max {}",
+                            abbreviate(min as f64, ""),
+                            abbreviate(median as f64, ""),
+                            abbreviate(max as f64, "")
+                        )
+                    },
+                ),
+            ],
+        ];
+        for paths in self.shared_backbones() {
+            let paths: Vec<String> = paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            rows.push(vec!["shared backbone:".to_string(), paths.join(", ")]);
+        }
+        for (path, error) in &self.failed {
+            rows.push(vec![
+                "failed:".to_string(),
+                format!("{}: {}", path.display(), error.lines().next().unwrap_or("")),
+            ]);
+        }
+        write_columns(f, &rows, &[])
+    }
+}
+
+/// The part of `graph` computing its node or input `end`, which becomes its output.
+fn backbone(graph: &Graph, end: &str) -> Graph {
+    let edges = graph.edges();
+    let ancestors = graph.ancestors(end, &edges);
+    let nodes = graph
+        .nodes
+        .iter()
+        .filter(|node| ancestors.contains(node.name.as_str()))
+        .map(|node| {
+            // The omitted inputs refer to the nodes before them, which may not be part of it.
+            let mut node = node.clone();
+            node.inputs = edges[node.name.as_str()]
+                .iter()
+                .map(|input| input.to_string())
+                .collect();
+            node
+        })
+        .collect();
+    Graph {
+        input_channels: graph.input_channels,
+        outputs: vec![end.to_string()],
+        inputs: graph.inputs.clone(),
+        nodes,
+    }
+}
+
+/// ## The measures of the description at `path` for the input dimension `in_dim`.
+///
+/// The input dimension is only needed by the networks whose inputs don't all declare their own.
+pub fn config(path: &Path, in_dim: Option<u32>) -> Result<Config, ConvDimError> {
+    let graph = read_architecture(path)?.into_graph();
+    let in_dim =
+        match in_dim {
+            Some(in_dim) => in_dim,
+            None if !graph.needs_input_dim() => 0,
+            None => return Err(ConvDimError::Zoo {
+                message:
+                    "the input dimension is required, since not all the inputs declare their own."
+                        .to_string(),
+            }),
+        };
+    let rows = summary::rows(&graph, in_dim)?;
+    let deepest = rows
+        .iter()
+        .reduce(|deepest, row| match row.jump > deepest.jump {
+            true => row,
+            false => deepest,
+        })
+        .expect("The summary has a row for every input.");
+    let convolutions = graph
+        .nodes
+        .iter()
+        .filter(|node| matches!(node.op, Op::Conv | Op::Transposed));
+    Ok(Config {
+        path: path.to_path_buf(),
+        depth: convolutions.clone().count(),
+        downsampling: deepest.jump,
+        params: rows.iter().map(|row| row.params).sum(),
+        filter_sizes: convolutions.filter_map(|node| node.filter_size).collect(),
+        backbone: fingerprint::hash(&backbone(&graph, deepest.name)),
+    })
+}
+
+/// ## Summarize the descriptions in `directory` and its subdirectories for the input dimension
+/// `in_dim`.
+pub fn zoo(directory: &Path, in_dim: Option<u32>) -> Result<Zoo, ConvDimError> {
+    let mut paths = vec![];
+    descriptions(directory, &mut paths).map_err(|e| match e {
+        ConvDimError::Check { message } => ConvDimError::Zoo { message },
+        e => e,
+    })?;
+    paths.sort();
+    let mut zoo = Zoo {
+        configs: vec![],
+        failed: vec![],
+    };
+    for path in paths {
+        match config(&path, in_dim) {
+            Ok(config) => zoo.configs.push(config),
+            Err(e) => zoo.failed.push((path, e.to_string())),
+        }
+    }
+    Ok(zoo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zoo() {
+        let directory = std::env::temp_dir().join(format!("convdim-zoo-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("nested")).unwrap();
+        let write = |name: &str, text: &str| std::fs::write(directory.join(name), text).unwrap();
+        let stem = "input_channels = 3\n\n\
+                    [[layers]]\ntype = \"conv\"\nfilter_size = 3\nstride = 2\npadding = 1\nchannels = 8\n\n\
+                    [[layers]]\ntype = \"conv\"\nfilter_size = 3\nstride = 2\npadding = 1\nchannels = 8\n";
+        write("a.toml", stem);
+        // The same backbone followed by a head keeping the resolution.
+        write(
+            "nested/b.toml",
+            &format!(
+                "{}\n[[layers]]\nname = \"head\"\ntype = \"conv\"\nfilter_size = 1\nchannels = 2\n",
+                stem
+            ),
+        );
+        write(
+            "c.toml",
+            "[[layers]]\ntype = \"conv\"\nfilter_size = 5\nstride = 4\n",
+        );
+        write(
+            "d.toml",
+            "[[layers]]\ntype = \"conv\"\nfilter_size = 33\nstride = 4\n",
+        );
+
+        let zoo = zoo(&directory, Some(16));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let zoo = zoo.unwrap();
+        assert_eq!(zoo.configs.len(), 3);
+        assert_eq!(zoo.failed.len(), 1);
+        assert_eq!(zoo.depths(), [(1, 1), (2, 1), (3, 1)]);
+        assert_eq!(zoo.downsampling(), [(4.0, 3)]);
+        assert_eq!(zoo.filter_sizes(), [(1, 1), (3, 4), (5, 1)]);
+        assert_eq!(zoo.params(), Some((0, 808, 826)));
+        assert_eq!(
+            zoo.to_string(),
+            format!(
+                "descriptions:     3, 1 failed\n\
+                 depths:           1 (1), 2 (1), 3 (1)\n\
+                 downsampling:     /4 (3)\n\
+                 filter sizes:     1x1 (1), 3x3 (4), 5x5 (1)\n\
+                 parameters:       min 0, median 808, max 826\n\
+                 shared backbone:  {0}/a.toml, {0}/nested/b.toml\n\
+                 failed:           {0}/d.toml: {1}\n",
+                directory.display(),
+                zoo.failed[0].1
+            )
+        );
+    }
+}